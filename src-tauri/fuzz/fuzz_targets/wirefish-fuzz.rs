@@ -1,15 +1,11 @@
 #![no_main]
 use libfuzzer_sys::fuzz_target;
 use sniffer_parser::*;
-use pnet::packet::ethernet::EthernetPacket;
 
 fuzz_target!(|data: &[u8]| {
-    let ethernet_packet = EthernetPacket::new(data);
-    match ethernet_packet {
-        Some(e_pack) => {
-            parse_ethernet_frame(&e_pack, 0);
-        },
-        _ => ()
+    // The first byte selects the link type of the frame following it
+    if let Some((selector, frame)) = data.split_first() {
+        let link_type = LinkType::ALL[*selector as usize % LinkType::ALL.len()];
+        parse_frame(link_type, frame, FrameMeta::new(0));
     }
-    
-});
\ No newline at end of file
+});
@@ -0,0 +1,405 @@
+//! Benchmarks of the dissection throughput
+//!
+//! Recorded captures are fed through [`parse_frame`], and the payloads of their TCP/UDP segments
+//! through the dissector of their protocol, measuring the packets parsed per second. Before each
+//! benchmark, the allocations made to parse the whole capture once are reported per packet.
+//!
+//! Corpora are the .pcap files of the directory set by `WIREFISH_BENCH_CORPUS`; when none is set,
+//! the frames of the fuzzing corpus are used. Run with:
+//!
+//! ```sh
+//! WIREFISH_BENCH_CORPUS=path/to/captures cargo bench -p sniffer_parser
+//! ```
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::collections::BTreeMap;
+use std::fs;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use pnet::packet::ethernet::{EtherTypes, EthernetPacket};
+use pnet::packet::ip::{IpNextHeaderProtocol, IpNextHeaderProtocols};
+use pnet::packet::ipv4::Ipv4Packet;
+use pnet::packet::ipv6::Ipv6Packet;
+use pnet::packet::tcp::TcpPacket;
+use pnet::packet::udp::UdpPacket;
+use pnet::packet::Packet;
+use sniffer_parser::registry::{DissectionContext, Dissector, DissectorRegistry, Transport};
+use sniffer_parser::serializable_packet::PacketMeta;
+use sniffer_parser::{cleanup_sniffing_state, parse_frame, FrameMeta, LinkType, ParserContext};
+
+/// Directory of the .pcap files to benchmark
+const CORPUS_VARIABLE: &str = "WIREFISH_BENCH_CORPUS";
+/// Fuzzing corpus, used when no directory is set: each file is a frame preceded by the index of
+/// its link type
+const FUZZING_CORPUS: &str = "../../mayhem/corpus";
+
+/// Magic numbers of the .pcap files, with timestamps in microseconds and nanoseconds
+const PCAP_MAGIC_MICROS: u32 = 0xa1b2c3d4;
+const PCAP_MAGIC_NANOS: u32 = 0xa1b23c4d;
+
+/// Allocator counting the allocations and the bytes allocated
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+static ALLOCATED_BYTES: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        ALLOCATED_BYTES.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// Frame of a recorded capture
+struct Frame {
+    link_type: LinkType,
+    capture_time: Option<Duration>,
+    data: Vec<u8>,
+}
+
+/// Frames of a recorded capture, in capture order
+struct Corpus {
+    name: String,
+    frames: Vec<Frame>,
+}
+
+/// Payload of a TCP/UDP segment, to feed to a dissector
+struct Payload {
+    source_ip: IpAddr,
+    source_port: u16,
+    dest_ip: IpAddr,
+    dest_port: u16,
+    is_request: bool,
+    meta: PacketMeta,
+    data: Vec<u8>,
+}
+
+impl Payload {
+    /// Get the context of the dissection of the payload
+    fn context(&self) -> DissectionContext<'_> {
+        DissectionContext {
+            source_ip: self.source_ip,
+            source_port: self.source_port,
+            dest_ip: self.dest_ip,
+            dest_port: self.dest_port,
+            is_request: self.is_request,
+            is_fin: false,
+            meta: &self.meta,
+        }
+    }
+}
+
+/// Read the frames of a .pcap file, none if it is not a valid one or its link type is unsupported
+fn read_pcap(path: &Path) -> Option<Vec<Frame>> {
+    let bytes = fs::read(path).ok()?;
+    let header = bytes.get(..24)?;
+    let magic = u32::from_le_bytes(header[..4].try_into().unwrap());
+    let (big_endian, nanos) = match magic {
+        PCAP_MAGIC_MICROS | PCAP_MAGIC_NANOS => (false, magic == PCAP_MAGIC_NANOS),
+        _ => match magic.swap_bytes() {
+            PCAP_MAGIC_MICROS | PCAP_MAGIC_NANOS => (true, magic.swap_bytes() == PCAP_MAGIC_NANOS),
+            _ => return None,
+        },
+    };
+    let read_u32 = |bytes: &[u8]| {
+        let bytes = bytes[..4].try_into().unwrap();
+        if big_endian {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        }
+    };
+    let link_type = LinkType::from_linktype(read_u32(&header[20..]) & 0x0fff_ffff)?;
+
+    let mut frames = vec![];
+    let mut records = &bytes[24..];
+    while let Some(record) = records.get(..16) {
+        let (seconds, fraction) = (read_u32(record), read_u32(&record[4..]));
+        let length = read_u32(&record[8..]) as usize;
+        let data = match records.get(16..16 + length) {
+            Some(data) => data,
+            None => break,
+        };
+        let fraction = if nanos {
+            Duration::from_nanos(fraction as u64)
+        } else {
+            Duration::from_micros(fraction as u64)
+        };
+
+        frames.push(Frame {
+            link_type,
+            capture_time: Some(Duration::from_secs(seconds as u64) + fraction),
+            data: data.to_vec(),
+        });
+        records = &records[16 + length..];
+    }
+
+    Some(frames)
+}
+
+/// Read the frames of the fuzzing corpus
+fn read_fuzzing_corpus(directory: &Path) -> Vec<Frame> {
+    corpus_files(directory, "bin")
+        .iter()
+        .filter_map(|path| fs::read(path).ok())
+        .filter_map(|data| {
+            let (selector, frame) = data.split_first()?;
+            Some(Frame {
+                link_type: LinkType::ALL[*selector as usize % LinkType::ALL.len()],
+                capture_time: None,
+                data: frame.to_vec(),
+            })
+        })
+        .collect()
+}
+
+/// Get the files of a directory with the given extension, sorted by name
+fn corpus_files(directory: &Path, extension: &str) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = fs::read_dir(directory)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| path.extension().is_some_and(|e| e == extension))
+                .collect()
+        })
+        .unwrap_or_default();
+    files.sort();
+
+    files
+}
+
+/// Load the corpora to benchmark
+fn corpora() -> Vec<Corpus> {
+    match std::env::var_os(CORPUS_VARIABLE) {
+        Some(directory) => corpus_files(Path::new(&directory), "pcap")
+            .iter()
+            .filter_map(|path| {
+                let frames = read_pcap(path);
+                if frames.is_none() {
+                    eprintln!("Skipped {}: not a supported .pcap file", path.display());
+                }
+
+                Some(Corpus {
+                    name: path.file_stem()?.to_string_lossy().into_owned(),
+                    frames: frames?,
+                })
+            })
+            .collect(),
+        None => vec![Corpus {
+            name: "fuzzing".to_owned(),
+            frames: read_fuzzing_corpus(
+                &Path::new(env!("CARGO_MANIFEST_DIR")).join(FUZZING_CORPUS),
+            ),
+        }],
+    }
+    .into_iter()
+    .filter(|corpus| !corpus.frames.is_empty())
+    .collect()
+}
+
+/// Parse all the frames of a corpus, starting from a clean state
+fn parse_corpus(corpus: &Corpus) {
+    cleanup_sniffing_state();
+    for (id, frame) in corpus.frames.iter().enumerate() {
+        black_box(parse_frame(
+            frame.link_type,
+            &frame.data,
+            FrameMeta {
+                capture_time: frame.capture_time,
+                ..FrameMeta::new(id)
+            },
+        ));
+    }
+}
+
+/// Report the allocations made by a run, per packet
+fn report_allocations(name: &str, packets: usize, run: impl FnOnce()) {
+    let (allocations, bytes) = (
+        ALLOCATIONS.load(Ordering::Relaxed),
+        ALLOCATED_BYTES.load(Ordering::Relaxed),
+    );
+    run();
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - allocations;
+    let bytes = ALLOCATED_BYTES.load(Ordering::Relaxed) - bytes;
+
+    println!(
+        "{}: {} packets, {:.1} allocations and {:.0} bytes allocated per packet",
+        name,
+        packets,
+        allocations as f64 / packets as f64,
+        bytes as f64 / packets as f64
+    );
+}
+
+/// Get the TCP/UDP payload of an Ethernet frame, with the context of its dissection
+fn segment_payload(frame: &Frame) -> Option<(Transport, Payload)> {
+    if frame.link_type != LinkType::Ethernet {
+        return None;
+    }
+    let ethernet = EthernetPacket::new(&frame.data)?;
+    let (source_ip, dest_ip, protocol, segment): (IpAddr, IpAddr, IpNextHeaderProtocol, &[u8]) =
+        match ethernet.get_ethertype() {
+            EtherTypes::Ipv4 => {
+                let ipv4 = Ipv4Packet::new(ethernet.payload())?;
+                let header_length = ipv4.get_header_length() as usize * 4;
+                (
+                    ipv4.get_source().into(),
+                    ipv4.get_destination().into(),
+                    ipv4.get_next_level_protocol(),
+                    ethernet.payload().get(header_length..)?,
+                )
+            }
+            EtherTypes::Ipv6 => {
+                let ipv6 = Ipv6Packet::new(ethernet.payload())?;
+                (
+                    ipv6.get_source().into(),
+                    ipv6.get_destination().into(),
+                    ipv6.get_next_header(),
+                    ethernet.payload().get(40..)?,
+                )
+            }
+            _ => return None,
+        };
+
+    let (transport, source_port, dest_port, data) = match protocol {
+        IpNextHeaderProtocols::Tcp => {
+            let tcp = TcpPacket::new(segment)?;
+            let (source_port, dest_port) = (tcp.get_source(), tcp.get_destination());
+            (
+                Transport::Tcp,
+                source_port,
+                dest_port,
+                tcp.payload().to_vec(),
+            )
+        }
+        IpNextHeaderProtocols::Udp => {
+            let udp = UdpPacket::new(segment)?;
+            let (source_port, dest_port) = (udp.get_source(), udp.get_destination());
+            (
+                Transport::Udp,
+                source_port,
+                dest_port,
+                udp.payload().to_vec(),
+            )
+        }
+        _ => return None,
+    };
+
+    let capture_time = frame.capture_time.unwrap_or_default();
+    let meta = PacketMeta {
+        timestamp: capture_time.as_micros() as u64,
+        capture_time,
+        captured_length: frame.data.len(),
+        wire_length: frame.data.len(),
+        ..PacketMeta::default()
+    };
+
+    Some((
+        transport,
+        Payload {
+            source_ip,
+            source_port,
+            dest_ip,
+            dest_port,
+            is_request: true,
+            meta,
+            data,
+        },
+    ))
+}
+
+/// Group the TCP/UDP payloads of a corpus by the dissector of their protocol, chosen by their
+/// ports like the parser does, or else by their content
+fn dissector_payloads(
+    corpus: &Corpus,
+    registry: &DissectorRegistry,
+) -> BTreeMap<String, (Arc<dyn Dissector>, Vec<Payload>)> {
+    let mut payloads: BTreeMap<String, (Arc<dyn Dissector>, Vec<Payload>)> = BTreeMap::new();
+
+    for (transport, mut payload) in corpus.frames.iter().filter_map(segment_payload) {
+        if payload.data.is_empty() {
+            continue;
+        }
+        let (source_port, dest_port) = (payload.source_port, payload.dest_port);
+
+        let by_ports = match (
+            registry.by_port(source_port, transport),
+            registry.by_port(dest_port, transport),
+        ) {
+            (Some((source, _)), Some((dest, dissector))) if dest <= source => {
+                Some((dissector, true))
+            }
+            (Some((_, dissector)), _) => Some((dissector, false)),
+            (None, Some((_, dissector))) => Some((dissector, true)),
+            (None, None) => None,
+        };
+        let (dissector, is_request) = match by_ports {
+            Some(dissector) => dissector,
+            None => match registry.probe(&payload.data, transport) {
+                Some(dissector) => (dissector, true),
+                None => continue,
+            },
+        };
+        payload.is_request = is_request;
+
+        payloads
+            .entry(dissector.name().to_owned())
+            .or_insert_with(|| (dissector.clone(), vec![]))
+            .1
+            .push(payload);
+    }
+
+    payloads
+}
+
+fn parse_frame_throughput(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parse_frame");
+
+    for corpus in corpora() {
+        report_allocations(&corpus.name, corpus.frames.len(), || parse_corpus(&corpus));
+
+        group.throughput(Throughput::Elements(corpus.frames.len() as u64));
+        group.bench_function(&corpus.name, |b| b.iter(|| parse_corpus(&corpus)));
+    }
+
+    group.finish();
+}
+
+fn dissector_throughput(c: &mut Criterion) {
+    let registry = DissectorRegistry::with_builtin_dissectors();
+    let mut group = c.benchmark_group("dissector");
+
+    for corpus in corpora() {
+        for (name, (dissector, payloads)) in dissector_payloads(&corpus, &registry) {
+            let dissect_all = || {
+                let mut state = ParserContext::new();
+                for payload in &payloads {
+                    black_box(dissector.dissect(&mut state, &payload.context(), &payload.data));
+                }
+            };
+            let id = format!("{}/{}", corpus.name, name);
+            report_allocations(&id, payloads.len(), dissect_all);
+
+            group.throughput(Throughput::Elements(payloads.len() as u64));
+            group.bench_function(&id, |b| b.iter(dissect_all));
+        }
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, parse_frame_throughput, dissector_throughput);
+criterion_main!(benches);
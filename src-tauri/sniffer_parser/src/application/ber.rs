@@ -0,0 +1,171 @@
+//! Minimal BER (ASN.1) decoding of tag-length-value elements
+//!
+//! Only single-byte tags and definite lengths are supported, enough for the
+//! protocols encoded with BER by the application layer parsers.
+
+/// BER element: tag and content
+pub(crate) struct BerElement<'a> {
+    pub tag: u8,
+    pub value: &'a [u8],
+}
+
+/// Parse the first BER element of the data, returning it with the remaining data
+pub(crate) fn parse_element(data: &[u8]) -> Option<(BerElement<'_>, &[u8])> {
+    let tag = *data.first()?;
+    let first_length = *data.get(1)?;
+
+    let (length, header_length) = match first_length {
+        0x00..=0x7f => (first_length as usize, 2),
+        // Indefinite length
+        0x80 => return None,
+        _ => {
+            let length_bytes = (first_length & 0x7f) as usize;
+            if length_bytes > 4 {
+                return None;
+            }
+            let length = data
+                .get(2..2 + length_bytes)?
+                .iter()
+                .fold(0usize, |length, byte| (length << 8) | *byte as usize);
+            (length, 2 + length_bytes)
+        }
+    };
+
+    let value = data.get(header_length..header_length + length)?;
+    Some((BerElement { tag, value }, &data[header_length + length..]))
+}
+
+/// Parse all the BER elements of the data, which must not contain anything else
+pub(crate) fn parse_elements(mut data: &[u8]) -> Option<Vec<BerElement<'_>>> {
+    let mut elements = vec![];
+    while !data.is_empty() {
+        let (element, rest) = parse_element(data)?;
+        elements.push(element);
+        data = rest;
+    }
+
+    Some(elements)
+}
+
+/// Decode a non-negative INTEGER (or ENUMERATED) value
+pub(crate) fn to_unsigned(value: &[u8]) -> Option<u64> {
+    // Leading zero avoiding the two's complement negative interpretation
+    let value = match value {
+        [0x00, rest @ ..] if !rest.is_empty() => rest,
+        _ => value,
+    };
+    if value.is_empty() || value.len() > 8 {
+        return None;
+    }
+
+    Some(
+        value
+            .iter()
+            .fold(0u64, |number, byte| (number << 8) | *byte as u64),
+    )
+}
+
+/// Decode a signed INTEGER value
+pub(crate) fn to_signed(value: &[u8]) -> Option<i64> {
+    if value.is_empty() || value.len() > 8 {
+        return None;
+    }
+
+    // Two's complement: the sign bit is extended
+    let sign = if value[0] & 0x80 != 0 { -1 } else { 0 };
+    Some(
+        value
+            .iter()
+            .fold(sign, |number, byte| (number << 8) | *byte as i64),
+    )
+}
+
+/// Decode an OBJECT IDENTIFIER value, in dotted notation
+pub(crate) fn to_oid(value: &[u8]) -> Option<String> {
+    // The last subidentifier must not be truncated
+    if value.last()? & 0x80 != 0 {
+        return None;
+    }
+
+    let mut subidentifiers = vec![];
+    let mut subidentifier: u64 = 0;
+    for byte in value {
+        subidentifier = subidentifier.checked_mul(128)? | (*byte & 0x7f) as u64;
+        if byte & 0x80 == 0 {
+            subidentifiers.push(subidentifier);
+            subidentifier = 0;
+        }
+    }
+
+    // The first subidentifier holds the first two arcs
+    let first = subidentifiers[0];
+    let (root, second) = match first {
+        0..=39 => (0, first),
+        40..=79 => (1, first - 40),
+        _ => (2, first - 80),
+    };
+
+    let mut oid = format!("{}.{}", root, second);
+    for subidentifier in &subidentifiers[1..] {
+        oid.push_str(&format!(".{}", subidentifier));
+    }
+
+    Some(oid)
+}
+
+/// Decode a string value (VisibleString, OCTET STRING with text)
+pub(crate) fn to_string(value: &[u8]) -> String {
+    String::from_utf8_lossy(value).into_owned()
+}
+
+/// Decode a BOOLEAN value
+pub(crate) fn to_bool(value: &[u8]) -> bool {
+    value.iter().any(|byte| *byte != 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_element, parse_elements, to_oid, to_signed, to_unsigned};
+
+    #[test]
+    fn long_form_length() {
+        let mut data = vec![0x04, 0x81, 0x80];
+        data.extend_from_slice(&[0x41; 128]);
+        data.push(0xff);
+
+        let (element, rest) = parse_element(&data).unwrap();
+        assert_eq!(element.tag, 0x04);
+        assert_eq!(element.value.len(), 128);
+        assert_eq!(rest, &[0xff]);
+    }
+
+    #[test]
+    fn truncated_element() {
+        assert!(parse_elements(&[0x80, 0x02, 0x01]).is_none());
+        assert!(parse_elements(&[0x30, 0x80, 0x00, 0x00]).is_none());
+    }
+
+    #[test]
+    fn integers() {
+        assert_eq!(
+            to_unsigned(&[0x00, 0xff, 0xff, 0xff, 0xff]),
+            Some(0xffff_ffff)
+        );
+        assert_eq!(to_unsigned(&[0x01, 0x00]), Some(256));
+        assert_eq!(to_unsigned(&[0x7f]), Some(127));
+        assert_eq!(to_unsigned(&[]), None);
+        assert_eq!(to_signed(&[0xff, 0x38]), Some(-200));
+        assert_eq!(to_signed(&[0x00, 0xc8]), Some(200));
+    }
+
+    #[test]
+    fn object_identifiers() {
+        assert_eq!(
+            to_oid(&[0x2b, 0x06, 0x01, 0x04, 0x01, 0x82, 0x37]).as_deref(),
+            Some("1.3.6.1.4.1.311")
+        );
+        assert_eq!(to_oid(&[0x88, 0x37, 0x03]).as_deref(), Some("2.999.3"));
+        assert_eq!(to_oid(&[0x2b, 0x86]), None);
+        assert_eq!(to_oid(&[]), None);
+    }
+}
@@ -0,0 +1,291 @@
+//! Cassandra CQL native protocol Packet parsing
+//!
+//! CQL frames (TCP port 9042) start with a header made of version (with the direction bit),
+//! flags, stream id, opcode and body length. Query strings, consistency levels and errors
+//! are decoded from uncompressed bodies; segments without a frame header are not dissected.
+
+use std::net::IpAddr;
+
+use tracing::debug;
+
+use crate::serializable_packet::application::SerializableCqlPacket;
+use crate::serializable_packet::{ParsedPacket, SerializablePacket};
+
+/// Direction bit of the version field
+const RESPONSE_FLAG: u8 = 0x80;
+/// Compressed body flag
+const COMPRESSION_FLAG: u8 = 0x01;
+
+/// CQL opcodes
+#[allow(non_snake_case)]
+mod CqlOpcodes {
+    pub const ERROR: u8 = 0x00;
+    pub const QUERY: u8 = 0x07;
+    pub const RESULT: u8 = 0x08;
+    pub const PREPARE: u8 = 0x09;
+    pub const EXECUTE: u8 = 0x0a;
+}
+
+/// Build a CQL packet from a transport-layer packet, save it in a Parsed Packet
+pub fn handle_cql_packet(
+    source_ip: IpAddr,
+    source_port: u16,
+    dest_ip: IpAddr,
+    dest_port: u16,
+    packet: &[u8],
+    parsed_packet: &mut ParsedPacket,
+) {
+    if let Some(cql_packet) = parse_cql_packet(packet) {
+        debug!(
+            "CQL Packet: {}:{} > {}:{}; Opcode: {}, Stream: {}, Query: {:?}",
+            source_ip,
+            source_port,
+            dest_ip,
+            dest_port,
+            cql_packet.opcode,
+            cql_packet.stream,
+            cql_packet.query
+        );
+
+        parsed_packet.set_application_layer_packet(Some(SerializablePacket::CqlPacket(cql_packet)));
+    }
+}
+
+fn parse_cql_packet(packet: &[u8]) -> Option<SerializableCqlPacket> {
+    let version = *packet.first()?;
+    let protocol_version = version & !RESPONSE_FLAG;
+    if !(1..=5).contains(&protocol_version) {
+        return None;
+    }
+
+    // Stream ids take 1 byte up to version 2, 2 bytes later
+    let (stream, header_length) = match protocol_version {
+        1 | 2 => (*packet.get(2)? as i8 as i16, 8),
+        _ => (i16::from_be_bytes([*packet.get(2)?, *packet.get(3)?]), 9),
+    };
+    let header = packet.get(..header_length)?;
+    let flags = header[1];
+    let opcode = header[header_length - 5];
+    let length = u32::from_be_bytes(header[header_length - 4..].try_into().unwrap());
+    let opcode_name = opcode_name(opcode)?;
+
+    let mut cql_packet = SerializableCqlPacket {
+        version: protocol_version,
+        response: version & RESPONSE_FLAG != 0,
+        flags,
+        stream,
+        opcode: opcode_name.to_owned(),
+        length,
+        query: None,
+        consistency: None,
+        prepared_id: None,
+        result_kind: None,
+        error_code: None,
+        error_message: None,
+    };
+
+    if flags & COMPRESSION_FLAG != 0 {
+        return Some(cql_packet);
+    }
+    let body = &packet[header_length..];
+
+    match opcode {
+        CqlOpcodes::QUERY => {
+            let (query, rest) = read_long_string(body)?;
+            cql_packet.query = Some(query);
+            cql_packet.consistency = read_short(rest).map(consistency_name);
+        }
+        CqlOpcodes::PREPARE => cql_packet.query = Some(read_long_string(body)?.0),
+        CqlOpcodes::EXECUTE => {
+            let (id, rest) = read_short_bytes(body)?;
+            cql_packet.prepared_id = Some(to_hex(id));
+            // Result metadata id, from version 5
+            let rest = match protocol_version {
+                5 => read_short_bytes(rest)?.1,
+                _ => rest,
+            };
+            cql_packet.consistency = read_short(rest).map(consistency_name);
+        }
+        CqlOpcodes::RESULT => {
+            let kind = u32::from_be_bytes(body.get(..4)?.try_into().unwrap());
+            cql_packet.result_kind = Some(result_kind_name(kind).to_owned());
+        }
+        CqlOpcodes::ERROR => {
+            cql_packet.error_code = Some(u32::from_be_bytes(body.get(..4)?.try_into().unwrap()));
+            let length = read_short(&body[4..])? as usize;
+            cql_packet.error_message =
+                Some(String::from_utf8_lossy(body.get(6..6 + length)?).into_owned());
+        }
+        _ => (),
+    }
+
+    Some(cql_packet)
+}
+
+fn read_short(data: &[u8]) -> Option<u16> {
+    Some(u16::from_be_bytes([*data.first()?, *data.get(1)?]))
+}
+
+/// Read a [long string]: 4-bytes length followed by the UTF-8 string
+fn read_long_string(data: &[u8]) -> Option<(String, &[u8])> {
+    let length = u32::from_be_bytes(data.get(..4)?.try_into().unwrap()) as usize;
+    let string = data.get(4..4 + length)?;
+
+    Some((
+        String::from_utf8_lossy(string).into_owned(),
+        &data[4 + length..],
+    ))
+}
+
+/// Read [short bytes]: 2-bytes length followed by the bytes
+fn read_short_bytes(data: &[u8]) -> Option<(&[u8], &[u8])> {
+    let length = read_short(data)? as usize;
+    let bytes = data.get(2..2 + length)?;
+
+    Some((bytes, &data[2 + length..]))
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn opcode_name(opcode: u8) -> Option<&'static str> {
+    Some(match opcode {
+        0x00 => "ERROR",
+        0x01 => "STARTUP",
+        0x02 => "READY",
+        0x03 => "AUTHENTICATE",
+        0x05 => "OPTIONS",
+        0x06 => "SUPPORTED",
+        0x07 => "QUERY",
+        0x08 => "RESULT",
+        0x09 => "PREPARE",
+        0x0a => "EXECUTE",
+        0x0b => "REGISTER",
+        0x0c => "EVENT",
+        0x0d => "BATCH",
+        0x0e => "AUTH_CHALLENGE",
+        0x0f => "AUTH_RESPONSE",
+        0x10 => "AUTH_SUCCESS",
+        _ => return None,
+    })
+}
+
+fn consistency_name(consistency: u16) -> String {
+    match consistency {
+        0x0000 => "ANY",
+        0x0001 => "ONE",
+        0x0002 => "TWO",
+        0x0003 => "THREE",
+        0x0004 => "QUORUM",
+        0x0005 => "ALL",
+        0x0006 => "LOCAL_QUORUM",
+        0x0007 => "EACH_QUORUM",
+        0x0008 => "SERIAL",
+        0x0009 => "LOCAL_SERIAL",
+        0x000a => "LOCAL_ONE",
+        _ => "Unknown",
+    }
+    .to_owned()
+}
+
+fn result_kind_name(kind: u32) -> &'static str {
+    match kind {
+        0x0001 => "Void",
+        0x0002 => "Rows",
+        0x0003 => "Set_keyspace",
+        0x0004 => "Prepared",
+        0x0005 => "Schema_change",
+        _ => "Unknown",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr};
+
+    use crate::serializable_packet::{ParsedPacket, SerializablePacket};
+
+    use super::handle_cql_packet;
+
+    #[test]
+    fn query_request() {
+        let query = b"SELECT * FROM ks.users";
+        let body = [
+            &(query.len() as u32).to_be_bytes()[..],
+            query,
+            &[0x00, 0x06],
+            &[0x00],
+        ]
+        .concat();
+
+        let parsed_packet = parse(&build_test_frame(0x04, 7, 0x07, &body));
+        match parsed_packet.get_application_layer_packet().unwrap() {
+            SerializablePacket::CqlPacket(cql_packet) => {
+                assert!(!cql_packet.response);
+                assert_eq!(cql_packet.stream, 7);
+                assert_eq!(cql_packet.opcode, "QUERY");
+                assert_eq!(cql_packet.query.as_deref(), Some("SELECT * FROM ks.users"));
+                assert_eq!(cql_packet.consistency.as_deref(), Some("LOCAL_QUORUM"));
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn error_response() {
+        let message = b"unconfigured table users";
+        let body = [
+            &0x2200u32.to_be_bytes()[..],
+            &(message.len() as u16).to_be_bytes(),
+            message,
+        ]
+        .concat();
+
+        let parsed_packet = parse(&build_test_frame(0x84, 7, 0x00, &body));
+        match parsed_packet.get_application_layer_packet().unwrap() {
+            SerializablePacket::CqlPacket(cql_packet) => {
+                assert!(cql_packet.response);
+                assert_eq!(cql_packet.error_code, Some(0x2200));
+                assert_eq!(
+                    cql_packet.error_message.as_deref(),
+                    Some("unconfigured table users")
+                );
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn continuation_segment() {
+        let parsed_packet = parse(b"row data continuing a previous frame");
+        assert!(parsed_packet.get_application_layer_packet().is_none());
+    }
+
+    ///////////////////// Utils
+
+    fn parse(packet: &[u8]) -> ParsedPacket {
+        let mut parsed_packet = ParsedPacket::new(0);
+        handle_cql_packet(
+            IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)),
+            50000,
+            IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2)),
+            9042,
+            packet,
+            &mut parsed_packet,
+        );
+
+        parsed_packet
+    }
+
+    fn build_test_frame(version: u8, stream: i16, opcode: u8, body: &[u8]) -> Vec<u8> {
+        [
+            &[version, 0x00][..],
+            &stream.to_be_bytes(),
+            &[opcode],
+            &(body.len() as u32).to_be_bytes(),
+            body,
+        ]
+        .concat()
+    }
+}
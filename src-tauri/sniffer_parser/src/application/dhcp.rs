@@ -0,0 +1,416 @@
+//! DHCP (BOOTP) Packet parsing
+//!
+//! DHCP messages are carried over UDP, from the clients on port 68 to the servers and relay agents
+//! on port 67. Each message is a fixed BOOTP header followed, after the magic cookie, by the DHCP
+//! options: the type of the message (Discover, Offer, Request, ACK...), the addresses offered or
+//! requested, the lease times, and the parameters requested by the client. Plain BOOTP messages
+//! carry no magic cookie, hence no options.
+//!
+//! Options overloading the server name and boot file fields (option 52) are not decoded: those
+//! fields are reported as they are.
+
+use std::net::Ipv4Addr;
+
+use tracing::debug;
+
+use crate::serializable_packet::application::{DhcpOption, SerializableDhcpPacket};
+use crate::serializable_packet::{ParsedPacket, SerializablePacket};
+
+/// Length of the BOOTP header, up to the magic cookie
+const HEADER_LENGTH: usize = 236;
+
+/// Magic cookie preceding the DHCP options
+const MAGIC_COOKIE: [u8; 4] = [0x63, 0x82, 0x53, 0x63];
+
+/// Broadcast bit of the BOOTP flags
+const FLAG_BROADCAST: u16 = 0x8000;
+
+/// Ethernet hardware type, whose addresses are MAC addresses
+const HARDWARE_TYPE_ETHERNET: u8 = 1;
+
+/// DHCP Option Codes
+#[allow(non_snake_case)]
+mod DhcpOptionCodes {
+    pub const PAD: u8 = 0;
+    pub const SUBNET_MASK: u8 = 1;
+    pub const ROUTER: u8 = 3;
+    pub const DNS_SERVER: u8 = 6;
+    pub const HOSTNAME: u8 = 12;
+    pub const DOMAIN_NAME: u8 = 15;
+    pub const REQUESTED_IP: u8 = 50;
+    pub const LEASE_TIME: u8 = 51;
+    pub const MESSAGE_TYPE: u8 = 53;
+    pub const SERVER_IDENTIFIER: u8 = 54;
+    pub const PARAMETER_REQUEST_LIST: u8 = 55;
+    pub const RENEWAL_TIME: u8 = 58;
+    pub const REBINDING_TIME: u8 = 59;
+    pub const VENDOR_CLASS: u8 = 60;
+    pub const CLIENT_IDENTIFIER: u8 = 61;
+    pub const END: u8 = 255;
+}
+
+/// Build a DHCP packet from a transport-layer packet, save it in a Parsed Packet
+pub fn handle_dhcp_packet(packet: &[u8], parsed_packet: &mut ParsedPacket) {
+    match parse_dhcp_message(packet) {
+        Some(dhcp_packet) => {
+            debug!(
+                "DHCP Packet: {}; Type: {:?}, Transaction: {:#010x}, Client: {}",
+                dhcp_packet.op,
+                dhcp_packet.message_type,
+                dhcp_packet.transaction_id,
+                dhcp_packet.client_hardware_address
+            );
+
+            parsed_packet
+                .set_application_layer_packet(Some(SerializablePacket::DhcpPacket(dhcp_packet)));
+        }
+        None => {
+            debug!("Malformed DHCP Packet");
+            parsed_packet.set_application_layer_packet(Some(SerializablePacket::MalformedPacket(
+                "Malformed DHCP Packet".to_string(),
+            )));
+        }
+    }
+}
+
+/// Check if a payload is a DHCP message: a BOOTP request or reply followed by the magic cookie
+pub fn is_dhcp_message(payload: &[u8]) -> bool {
+    matches!(payload.first(), Some(1 | 2))
+        && payload.get(HEADER_LENGTH..HEADER_LENGTH + MAGIC_COOKIE.len()) == Some(&MAGIC_COOKIE[..])
+}
+
+/// Parse a DHCP (or BOOTP) message, with its options
+pub fn parse_dhcp_message(packet: &[u8]) -> Option<SerializableDhcpPacket> {
+    if packet.len() < HEADER_LENGTH {
+        return None;
+    }
+
+    let op = match packet[0] {
+        1 => "Boot Request (1)".to_owned(),
+        2 => "Boot Reply (2)".to_owned(),
+        _ => return None,
+    };
+    let hardware_type = packet[1];
+    let hardware_address_length = packet[2];
+    let hardware_address = packet.get(28..28 + hardware_address_length.min(16) as usize)?;
+    let client_hardware_address = if hardware_type == HARDWARE_TYPE_ETHERNET {
+        hardware_address
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect::<Vec<_>>()
+            .join(":")
+    } else {
+        to_hex(hardware_address)
+    };
+
+    let mut dhcp_packet = SerializableDhcpPacket {
+        op,
+        hardware_type,
+        hardware_address_length,
+        hops: packet[3],
+        transaction_id: u32::from_be_bytes(packet[4..8].try_into().unwrap()),
+        seconds: u16::from_be_bytes([packet[8], packet[9]]),
+        broadcast: u16::from_be_bytes([packet[10], packet[11]]) & FLAG_BROADCAST != 0,
+        client_ip: read_ipv4(&packet[12..16])?,
+        your_ip: read_ipv4(&packet[16..20])?,
+        server_ip: read_ipv4(&packet[20..24])?,
+        relay_ip: read_ipv4(&packet[24..28])?,
+        client_hardware_address,
+        server_name: read_string(&packet[44..108]),
+        boot_file: read_string(&packet[108..236]),
+        message_type: None,
+        subnet_mask: None,
+        routers: vec![],
+        dns_servers: vec![],
+        hostname: None,
+        domain_name: None,
+        requested_ip: None,
+        lease_time: None,
+        renewal_time: None,
+        rebinding_time: None,
+        server_identifier: None,
+        parameter_request_list: vec![],
+        vendor_class: None,
+        client_identifier: None,
+        options: vec![],
+    };
+
+    // BOOTP vendor extensions are not decoded
+    if is_dhcp_message(packet) {
+        parse_options(
+            &packet[HEADER_LENGTH + MAGIC_COOKIE.len()..],
+            &mut dhcp_packet,
+        )?;
+    }
+
+    Some(dhcp_packet)
+}
+
+/// Parse the DHCP options, up to the end option, none if one is truncated
+fn parse_options(mut options: &[u8], dhcp_packet: &mut SerializableDhcpPacket) -> Option<()> {
+    while let Some(&code) = options.first() {
+        match code {
+            DhcpOptionCodes::PAD => {
+                options = &options[1..];
+                continue;
+            }
+            DhcpOptionCodes::END => break,
+            _ => (),
+        }
+
+        let length = *options.get(1)? as usize;
+        let value = options.get(2..2 + length)?;
+        options = &options[2 + length..];
+
+        dhcp_packet.options.push(DhcpOption {
+            code,
+            name: option_name(code).to_owned(),
+            length,
+        });
+
+        match code {
+            DhcpOptionCodes::MESSAGE_TYPE => {
+                dhcp_packet.message_type = value.first().map(|&message_type| {
+                    format!("{} ({})", message_type_name(message_type), message_type)
+                });
+            }
+            DhcpOptionCodes::SUBNET_MASK => dhcp_packet.subnet_mask = read_ipv4(value),
+            DhcpOptionCodes::ROUTER => dhcp_packet.routers = read_ipv4_list(value),
+            DhcpOptionCodes::DNS_SERVER => dhcp_packet.dns_servers = read_ipv4_list(value),
+            DhcpOptionCodes::HOSTNAME => dhcp_packet.hostname = read_string(value),
+            DhcpOptionCodes::DOMAIN_NAME => dhcp_packet.domain_name = read_string(value),
+            DhcpOptionCodes::REQUESTED_IP => dhcp_packet.requested_ip = read_ipv4(value),
+            DhcpOptionCodes::LEASE_TIME => dhcp_packet.lease_time = read_u32(value),
+            DhcpOptionCodes::SERVER_IDENTIFIER => dhcp_packet.server_identifier = read_ipv4(value),
+            DhcpOptionCodes::PARAMETER_REQUEST_LIST => {
+                dhcp_packet.parameter_request_list = value
+                    .iter()
+                    .map(|&parameter| format!("{} ({})", option_name(parameter), parameter))
+                    .collect();
+            }
+            DhcpOptionCodes::RENEWAL_TIME => dhcp_packet.renewal_time = read_u32(value),
+            DhcpOptionCodes::REBINDING_TIME => dhcp_packet.rebinding_time = read_u32(value),
+            DhcpOptionCodes::VENDOR_CLASS => dhcp_packet.vendor_class = read_string(value),
+            DhcpOptionCodes::CLIENT_IDENTIFIER => {
+                dhcp_packet.client_identifier = Some(to_hex(value))
+            }
+            _ => (),
+        }
+    }
+
+    Some(())
+}
+
+fn read_ipv4(data: &[u8]) -> Option<Ipv4Addr> {
+    let octets: [u8; 4] = data.get(..4)?.try_into().unwrap();
+
+    Some(Ipv4Addr::from(octets))
+}
+
+fn read_ipv4_list(data: &[u8]) -> Vec<Ipv4Addr> {
+    data.chunks_exact(4).filter_map(read_ipv4).collect()
+}
+
+fn read_u32(data: &[u8]) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(..4)?.try_into().unwrap()))
+}
+
+/// Read a string terminated by NUL or by the end of the field, none if empty
+fn read_string(data: &[u8]) -> Option<String> {
+    let end = data
+        .iter()
+        .position(|&byte| byte == 0)
+        .unwrap_or(data.len());
+
+    (end > 0).then(|| String::from_utf8_lossy(&data[..end]).into_owned())
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn message_type_name(message_type: u8) -> &'static str {
+    match message_type {
+        1 => "Discover",
+        2 => "Offer",
+        3 => "Request",
+        4 => "Decline",
+        5 => "ACK",
+        6 => "NAK",
+        7 => "Release",
+        8 => "Inform",
+        _ => "Unknown",
+    }
+}
+
+fn option_name(code: u8) -> &'static str {
+    match code {
+        1 => "Subnet Mask",
+        2 => "Time Offset",
+        3 => "Router",
+        6 => "Domain Name Server",
+        12 => "Host Name",
+        15 => "Domain Name",
+        26 => "Interface MTU",
+        28 => "Broadcast Address",
+        42 => "NTP Servers",
+        43 => "Vendor-Specific Information",
+        44 => "NetBIOS Name Server",
+        50 => "Requested IP Address",
+        51 => "IP Address Lease Time",
+        52 => "Option Overload",
+        53 => "DHCP Message Type",
+        54 => "Server Identifier",
+        55 => "Parameter Request List",
+        56 => "Message",
+        57 => "Maximum DHCP Message Size",
+        58 => "Renewal Time Value",
+        59 => "Rebinding Time Value",
+        60 => "Vendor Class Identifier",
+        61 => "Client Identifier",
+        66 => "TFTP Server Name",
+        67 => "Bootfile Name",
+        81 => "Client Fully Qualified Domain Name",
+        82 => "Relay Agent Information",
+        119 => "Domain Search",
+        121 => "Classless Static Route",
+        _ => "Unknown",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use crate::serializable_packet::{ParsedPacket, SerializablePacket};
+
+    use super::{handle_dhcp_packet, is_dhcp_message, parse_dhcp_message};
+
+    const CLIENT_MAC: [u8; 6] = [0x08, 0x00, 0x27, 0xaa, 0xbb, 0xcc];
+
+    #[test]
+    fn dhcp_discover() {
+        let discover = build_test_message(
+            1,
+            [0; 4],
+            &[
+                &[53, 1, 1],
+                &[50, 4, 192, 168, 1, 10],
+                &[12, 6, b'l', b'a', b'p', b't', b'o', b'p'],
+                &[55, 4, 1, 3, 6, 15],
+                &[61, 7, 1, 0x08, 0x00, 0x27, 0xaa, 0xbb, 0xcc],
+            ],
+        );
+        assert!(is_dhcp_message(&discover));
+        let dhcp_packet = parse_dhcp_message(&discover).unwrap();
+
+        assert_eq!(dhcp_packet.op, "Boot Request (1)");
+        assert_eq!(dhcp_packet.message_type.as_deref(), Some("Discover (1)"));
+        assert_eq!(dhcp_packet.transaction_id, 0x3903f326);
+        assert!(dhcp_packet.broadcast);
+        assert_eq!(dhcp_packet.client_hardware_address, "08:00:27:aa:bb:cc");
+        assert_eq!(
+            dhcp_packet.requested_ip,
+            Some(Ipv4Addr::new(192, 168, 1, 10))
+        );
+        assert_eq!(dhcp_packet.hostname.as_deref(), Some("laptop"));
+        assert_eq!(
+            dhcp_packet.parameter_request_list,
+            vec![
+                "Subnet Mask (1)",
+                "Router (3)",
+                "Domain Name Server (6)",
+                "Domain Name (15)"
+            ]
+        );
+        assert_eq!(
+            dhcp_packet.client_identifier.as_deref(),
+            Some("01080027aabbcc")
+        );
+        assert_eq!(dhcp_packet.options.len(), 5);
+        assert_eq!(dhcp_packet.server_name, None);
+    }
+
+    #[test]
+    fn dhcp_ack() {
+        let ack = build_test_message(
+            2,
+            [192, 168, 1, 10],
+            &[
+                &[53, 1, 5],
+                &[54, 4, 192, 168, 1, 1],
+                &[51, 4, 0x00, 0x01, 0x51, 0x80],
+                &[1, 4, 255, 255, 255, 0],
+                &[3, 4, 192, 168, 1, 1],
+                &[6, 8, 1, 1, 1, 1, 8, 8, 8, 8],
+                // Padding between options
+                &[0, 0],
+            ],
+        );
+        let dhcp_packet = parse_dhcp_message(&ack).unwrap();
+
+        assert_eq!(dhcp_packet.op, "Boot Reply (2)");
+        assert_eq!(dhcp_packet.message_type.as_deref(), Some("ACK (5)"));
+        assert_eq!(dhcp_packet.your_ip, Ipv4Addr::new(192, 168, 1, 10));
+        assert_eq!(
+            dhcp_packet.server_identifier,
+            Some(Ipv4Addr::new(192, 168, 1, 1))
+        );
+        assert_eq!(dhcp_packet.lease_time, Some(86400));
+        assert_eq!(
+            dhcp_packet.subnet_mask,
+            Some(Ipv4Addr::new(255, 255, 255, 0))
+        );
+        assert_eq!(dhcp_packet.routers, vec![Ipv4Addr::new(192, 168, 1, 1)]);
+        assert_eq!(
+            dhcp_packet.dns_servers,
+            vec![Ipv4Addr::new(1, 1, 1, 1), Ipv4Addr::new(8, 8, 8, 8)]
+        );
+    }
+
+    #[test]
+    fn bootp_without_options() {
+        let mut bootp = build_test_message(2, [10, 0, 0, 5], &[]);
+        bootp.truncate(236);
+        let dhcp_packet = parse_dhcp_message(&bootp).unwrap();
+
+        assert!(!is_dhcp_message(&bootp));
+        assert_eq!(dhcp_packet.message_type, None);
+        assert!(dhcp_packet.options.is_empty());
+    }
+
+    #[test]
+    fn truncated_option() {
+        let mut discover = build_test_message(1, [0; 4], &[&[53, 1, 1], &[12, 6, b'l']]);
+        // No end option either
+        discover.pop();
+        let mut parsed_packet = ParsedPacket::new(0);
+        handle_dhcp_packet(&discover, &mut parsed_packet);
+
+        assert!(matches!(
+            parsed_packet.get_application_layer_packet(),
+            Some(SerializablePacket::MalformedPacket(_))
+        ));
+        assert!(parse_dhcp_message(&[1; 100]).is_none());
+    }
+
+    ///////////////////// Utils
+
+    fn build_test_message(op: u8, your_ip: [u8; 4], options: &[&[u8]]) -> Vec<u8> {
+        let mut message = vec![op, 1, 6, 0];
+        message.extend([0x39, 0x03, 0xf3, 0x26]);
+        message.extend([0x00, 0x00, 0x80, 0x00]);
+        message.extend([0; 4]);
+        message.extend(your_ip);
+        message.extend([0; 8]);
+        message.extend(CLIENT_MAC);
+        message.extend([0; 10 + 64 + 128]);
+        message.extend([0x63, 0x82, 0x53, 0x63]);
+        for option in options {
+            message.extend(*option);
+        }
+        message.push(255);
+
+        message
+    }
+}
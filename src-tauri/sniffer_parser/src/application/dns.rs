@@ -1,8 +1,14 @@
 //! DNS Packet parsing
+//!
+//! Messages over TCP are preceded by a 2-bytes length field (RFC 1035 section 4.2.2),
+//! messages over UDP are not: both framings are accepted.
+//!
+//! Multicast DNS messages (RFC 6762), exchanged over UDP port 5353, share the same format: the
+//! DNS-SD service advertisements they carry are decoded as PTR, SRV and TXT records.
 
 use dns_parser::Packet as DnsPacket;
-use log::debug;
 use std::net::IpAddr;
+use tracing::debug;
 
 use crate::serializable_packet::{
     application::SerializableDnsPacket, ParsedPacket, SerializablePacket,
@@ -17,7 +23,17 @@ pub fn handle_dns_packet(
     packet: &[u8],
     parsed_packet: &mut ParsedPacket,
 ) {
-    if let Ok(dns_packet) = DnsPacket::parse(packet) {
+    // Segments without data (e.g. TCP handshake)
+    if packet.is_empty() {
+        return;
+    }
+
+    let dns_packet = match tcp_message(packet) {
+        Some(message) => DnsPacket::parse(message).or_else(|_| DnsPacket::parse(packet)),
+        None => DnsPacket::parse(packet),
+    };
+
+    if let Ok(dns_packet) = dns_packet {
         debug!(
             "DNS Packet: {}:{} > {}:{}; ID: {}, Questions: {}, Answers: {}, Authority: {}, Additional: {}",
             source_ip,
@@ -42,6 +58,16 @@ pub fn handle_dns_packet(
     }
 }
 
+/// Get the DNS message of a TCP segment, if the segment starts with a matching length field
+fn tcp_message(packet: &[u8]) -> Option<&[u8]> {
+    let length = u16::from_be_bytes([*packet.first()?, *packet.get(1)?]) as usize;
+
+    match packet.len() - 2 == length {
+        true => Some(&packet[2..]),
+        false => None,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::net::{IpAddr, Ipv4Addr};
@@ -357,6 +383,102 @@ mod tests {
         }
     }
 
+    #[test]
+    fn dns_query_over_tcp() {
+        let dns_packet = NewDnsPacket::new_query(ID, false);
+        let dns_packet_bytes = dns_packet.build_bytes_vec().unwrap();
+        let tcp_segment = [
+            &(dns_packet_bytes.len() as u16).to_be_bytes()[..],
+            &dns_packet_bytes,
+        ]
+        .concat();
+
+        let mut parsed_packet = ParsedPacket::new(0);
+        handle_dns_packet(
+            IpAddr::V4(Ipv4Addr::new(10, 10, 10, 10)),
+            4444,
+            IpAddr::V4(Ipv4Addr::new(11, 11, 11, 11)),
+            53,
+            tcp_segment.as_slice(),
+            &mut parsed_packet,
+        );
+
+        match parsed_packet.get_application_layer_packet().unwrap() {
+            SerializablePacket::DnsPacket(new_dns_packet) => {
+                assert_eq!(new_dns_packet.header.id, ID);
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn dns_reply_with_mx_and_txt_answers() {
+        let dns_packet_bytes = [
+            // Header: response, 2 answers
+            &[
+                0x12, 0x34, 0x81, 0x80, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x00,
+            ][..],
+            // a.io MX 10 mx.a.io
+            &[0x01, b'a', 0x02, b'i', b'o', 0x00, 0x00, 0x0f, 0x00, 0x01],
+            &[0x00, 0x00, 0x00, 0x3c, 0x00, 0x0d, 0x00, 0x0a],
+            &[0x02, b'm', b'x', 0x01, b'a', 0x02, b'i', b'o', 0x00],
+            // a.io TXT "hello" "world"
+            &[0x01, b'a', 0x02, b'i', b'o', 0x00, 0x00, 0x10, 0x00, 0x01],
+            &[0x00, 0x00, 0x00, 0x3c, 0x00, 0x0c],
+            &[
+                0x05, b'h', b'e', b'l', b'l', b'o', 0x05, b'w', b'o', b'r', b'l', b'd',
+            ],
+        ]
+        .concat();
+
+        let mut parsed_packet = ParsedPacket::new(0);
+        handle_dns_packet(
+            IpAddr::V4(Ipv4Addr::new(11, 11, 11, 11)),
+            53,
+            IpAddr::V4(Ipv4Addr::new(10, 10, 10, 10)),
+            4444,
+            dns_packet_bytes.as_slice(),
+            &mut parsed_packet,
+        );
+
+        match parsed_packet.get_application_layer_packet().unwrap() {
+            SerializablePacket::DnsPacket(new_dns_packet) => {
+                assert_eq!(new_dns_packet.header.response_code, "NoError");
+
+                match &new_dns_packet.answers[0].data {
+                    CustomResourceData::MX(mx) => {
+                        assert_eq!(mx.preference, 10);
+                        assert_eq!(mx.exchange, "mx.a.io");
+                    }
+                    _ => unreachable!(),
+                }
+                match &new_dns_packet.answers[1].data {
+                    CustomResourceData::TXT(txt) => {
+                        assert_eq!(txt.strings, vec!["hello", "world"]);
+                        assert_eq!(txt.data, b"helloworld");
+                    }
+                    _ => unreachable!(),
+                }
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn empty_dns_segment() {
+        let mut parsed_packet = ParsedPacket::new(0);
+        handle_dns_packet(
+            IpAddr::V4(Ipv4Addr::new(10, 10, 10, 10)),
+            4444,
+            IpAddr::V4(Ipv4Addr::new(11, 11, 11, 11)),
+            53,
+            &[],
+            &mut parsed_packet,
+        );
+
+        assert!(parsed_packet.get_application_layer_packet().is_none());
+    }
+
     #[test]
     fn malformed_dns_packet() {
         let malformed_dns_packet = [0, 1, 2, 3, 0, 1, 2, 3];
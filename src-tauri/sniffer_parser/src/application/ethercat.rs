@@ -0,0 +1,220 @@
+//! EtherCAT Packet parsing
+//!
+//! EtherCAT frames are carried directly over Ethernet (ethertype 0x88A4): a 2-bytes header
+//! (little endian, as all the protocol fields) is followed by a sequence of datagrams,
+//! each one made of header, data and working counter.
+
+use tracing::debug;
+
+use crate::serializable_packet::application::{EthercatDatagram, SerializableEthercatPacket};
+use crate::serializable_packet::{ParsedPacket, SerializablePacket};
+
+const HEADER_LENGTH: usize = 2;
+const DATAGRAM_HEADER_LENGTH: usize = 10;
+const WORKING_COUNTER_LENGTH: usize = 2;
+
+/// EtherCAT frame type carrying datagrams (commands)
+const FRAME_TYPE_COMMANDS: u8 = 0x1;
+
+/// Build an EtherCAT packet from a link-layer packet, save it in a Parsed Packet
+pub fn handle_ethercat_packet(packet: &[u8], parsed_packet: &mut ParsedPacket) {
+    match parse_ethercat_packet(packet) {
+        Some(ethercat_packet) => {
+            debug!(
+                "EtherCAT Packet: Length: {}, Datagrams: {}",
+                ethercat_packet.length,
+                ethercat_packet.datagrams.len()
+            );
+
+            parsed_packet.set_application_layer_packet(Some(SerializablePacket::EthercatPacket(
+                ethercat_packet,
+            )));
+        }
+        None => {
+            debug!("Malformed EtherCAT Packet");
+            parsed_packet.set_application_layer_packet(Some(SerializablePacket::MalformedPacket(
+                "Malformed EtherCAT Packet".to_string(),
+            )));
+        }
+    }
+}
+
+fn parse_ethercat_packet(packet: &[u8]) -> Option<SerializableEthercatPacket> {
+    if packet.len() < HEADER_LENGTH {
+        return None;
+    }
+
+    let header = u16::from_le_bytes([packet[0], packet[1]]);
+    let length = header & 0x07ff;
+    let frame_type = (header >> 12) as u8;
+    let mut data = packet[HEADER_LENGTH..].get(..length as usize)?;
+
+    let mut datagrams = vec![];
+    if frame_type == FRAME_TYPE_COMMANDS {
+        loop {
+            let (datagram, rest) = parse_datagram(data)?;
+            let more_follows = datagram.more_follows;
+            datagrams.push(datagram);
+            data = rest;
+
+            if !more_follows {
+                break;
+            }
+        }
+    }
+
+    Some(SerializableEthercatPacket {
+        length,
+        frame_type,
+        datagrams,
+    })
+}
+
+fn parse_datagram(data: &[u8]) -> Option<(EthercatDatagram, &[u8])> {
+    if data.len() < DATAGRAM_HEADER_LENGTH {
+        return None;
+    }
+
+    let command = data[0];
+    let length_flags = u16::from_le_bytes([data[6], data[7]]);
+    let length = length_flags & 0x07ff;
+
+    let end = DATAGRAM_HEADER_LENGTH + length as usize;
+    let working_counter = data.get(end..end + WORKING_COUNTER_LENGTH)?;
+
+    // Position and node addressed commands carry slave and offset addresses, the others a logical one
+    let (slave_address, offset_address, logical_address) = match command {
+        0x01..=0x09 | 0x0d | 0x0e => (
+            Some(u16::from_le_bytes([data[2], data[3]])),
+            Some(u16::from_le_bytes([data[4], data[5]])),
+            None,
+        ),
+        0x0a..=0x0c => (
+            None,
+            None,
+            Some(u32::from_le_bytes([data[2], data[3], data[4], data[5]])),
+        ),
+        _ => (None, None, None),
+    };
+
+    Some((
+        EthercatDatagram {
+            command: command_name(command),
+            index: data[1],
+            slave_address,
+            offset_address,
+            logical_address,
+            length,
+            circulating: length_flags & 0x4000 != 0,
+            more_follows: length_flags & 0x8000 != 0,
+            irq: u16::from_le_bytes([data[8], data[9]]),
+            working_counter: u16::from_le_bytes([working_counter[0], working_counter[1]]),
+        },
+        &data[end + WORKING_COUNTER_LENGTH..],
+    ))
+}
+
+fn command_name(command: u8) -> String {
+    match command {
+        0x00 => "NOP",
+        0x01 => "APRD",
+        0x02 => "APWR",
+        0x03 => "APRW",
+        0x04 => "FPRD",
+        0x05 => "FPWR",
+        0x06 => "FPRW",
+        0x07 => "BRD",
+        0x08 => "BWR",
+        0x09 => "BRW",
+        0x0a => "LRD",
+        0x0b => "LWR",
+        0x0c => "LRW",
+        0x0d => "ARMW",
+        0x0e => "FRMW",
+        _ => "Unknown",
+    }
+    .to_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::serializable_packet::{ParsedPacket, SerializablePacket};
+
+    use super::handle_ethercat_packet;
+
+    #[test]
+    fn ethercat_datagrams() {
+        let datagrams = [
+            // FPRD to station 0x1001, register 0x0130, more datagrams following
+            build_test_datagram(0x04, 0x01, [0x01, 0x10, 0x30, 0x01], &[0x08, 0x00], true, 1),
+            // LRW of the process image
+            build_test_datagram(0x0c, 0x02, [0x00, 0x00, 0x01, 0x00], &[0u8; 6], false, 3),
+        ]
+        .concat();
+        let header = (0x1000 | datagrams.len() as u16).to_le_bytes();
+        let frame = [&header[..], &datagrams].concat();
+
+        let mut parsed_packet = ParsedPacket::new(0);
+        handle_ethercat_packet(&frame, &mut parsed_packet);
+        match parsed_packet.get_application_layer_packet().unwrap() {
+            SerializablePacket::EthercatPacket(ethercat_packet) => {
+                assert_eq!(ethercat_packet.datagrams.len(), 2);
+
+                let fprd = &ethercat_packet.datagrams[0];
+                assert_eq!(fprd.command, "FPRD");
+                assert_eq!(fprd.slave_address, Some(0x1001));
+                assert_eq!(fprd.offset_address, Some(0x0130));
+                assert_eq!(fprd.working_counter, 1);
+
+                let lrw = &ethercat_packet.datagrams[1];
+                assert_eq!(lrw.command, "LRW");
+                assert_eq!(lrw.logical_address, Some(0x0001_0000));
+                assert_eq!(lrw.length, 6);
+                assert_eq!(lrw.working_counter, 3);
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn malformed_ethercat_packet() {
+        // Datagram announced as followed by another one, which is missing
+        let datagram = build_test_datagram(0x07, 0x00, [0u8; 4], &[0x00], true, 0);
+        let header = (0x1000 | datagram.len() as u16).to_le_bytes();
+        let frame = [&header[..], &datagram].concat();
+
+        let mut parsed_packet = ParsedPacket::new(0);
+        handle_ethercat_packet(&frame, &mut parsed_packet);
+
+        assert!(matches!(
+            parsed_packet.get_application_layer_packet(),
+            Some(SerializablePacket::MalformedPacket(_))
+        ));
+    }
+
+    ///////////////////// Utils
+
+    fn build_test_datagram(
+        command: u8,
+        index: u8,
+        address: [u8; 4],
+        data: &[u8],
+        more_follows: bool,
+        working_counter: u16,
+    ) -> Vec<u8> {
+        let mut length_flags = data.len() as u16;
+        if more_follows {
+            length_flags |= 0x8000;
+        }
+
+        [
+            &[command, index][..],
+            &address,
+            &length_flags.to_le_bytes(),
+            &[0x00, 0x00],
+            data,
+            &working_counter.to_le_bytes(),
+        ]
+        .concat()
+    }
+}
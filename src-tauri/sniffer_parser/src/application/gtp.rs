@@ -0,0 +1,537 @@
+//! GTP Packet parsing
+//!
+//! The GPRS Tunnelling Protocol connects the nodes of a mobile core network over UDP:
+//! - GTPv2-C (port 2123): the control plane, creating, modifying and deleting the sessions of the
+//!   subscribers and their bearers
+//! - GTP-U (port 2152, version 1): the user plane, tunnelling the packets of the subscribers
+//!
+//! Each GTPv2-C message carries information elements: among them, the IMSI of the subscriber, the
+//! APN and the address allocated to it, and the fully qualified TEIDs (F-TEIDs) of the tunnels of
+//! the sender. Each bearer context groups the F-TEIDs of the user-plane tunnels of a bearer, so
+//! that the G-PDUs carrying the traffic of the subscriber can be told apart by their TEID.
+//!
+//! Piggybacked GTPv2-C messages are not decoded, and only the addresses and protocol of the packets
+//! tunnelled by the G-PDUs are reported.
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use pnet::packet::ip::IpNextHeaderProtocol;
+use tracing::debug;
+
+use crate::serializable_packet::application::{GtpBearer, GtpFTeid, SerializableGtpPacket};
+use crate::serializable_packet::{ParsedPacket, SerializablePacket};
+
+/// Length of the mandatory part of the GTP-U header
+const GTP_U_HEADER_LENGTH: usize = 8;
+
+/// GTPv2-C header flag of the TEID
+const FLAG_TEID: u8 = 0x08;
+
+/// GTP-U header flag of the protocol type, set for GTP (unset for GTP')
+const FLAG_PROTOCOL_TYPE: u8 = 0x10;
+
+/// GTP-U header flags of the optional fields: extension header, sequence number, N-PDU number
+const FLAGS_OPTIONAL_FIELDS: u8 = 0x07;
+const FLAG_SEQUENCE: u8 = 0x02;
+
+/// GTP-U message type of the packets of the subscribers
+const G_PDU: u8 = 255;
+
+/// GTPv2-C Information Element Types
+#[allow(non_snake_case)]
+mod IeTypes {
+    pub const IMSI: u8 = 1;
+    pub const CAUSE: u8 = 2;
+    pub const APN: u8 = 71;
+    pub const EBI: u8 = 73;
+    pub const MSISDN: u8 = 76;
+    pub const PAA: u8 = 79;
+    pub const F_TEID: u8 = 87;
+    pub const BEARER_CONTEXT: u8 = 93;
+}
+
+/// Build a GTP packet from a transport-layer packet, save it in a Parsed Packet
+pub fn handle_gtp_packet(packet: &[u8], parsed_packet: &mut ParsedPacket) {
+    match parse_gtp_message(packet) {
+        Some(gtp_packet) => {
+            debug!(
+                "GTPv{} Packet: {}; TEID: {:?}",
+                gtp_packet.version, gtp_packet.message_type, gtp_packet.teid
+            );
+
+            parsed_packet
+                .set_application_layer_packet(Some(SerializablePacket::GtpPacket(gtp_packet)));
+        }
+        None => {
+            debug!("Malformed GTP Packet");
+            parsed_packet.set_application_layer_packet(Some(SerializablePacket::MalformedPacket(
+                "Malformed GTP Packet".to_string(),
+            )));
+        }
+    }
+}
+
+/// Check if a payload is a GTP message: a GTPv2-C or GTP-U header whose length fits the payload
+pub fn is_gtp_message(payload: &[u8]) -> bool {
+    let (flags, length) = match payload.get(..4) {
+        Some(header) => (
+            header[0],
+            u16::from_be_bytes([header[2], header[3]]) as usize,
+        ),
+        None => return false,
+    };
+
+    match flags >> 5 {
+        1 => flags & FLAG_PROTOCOL_TYPE != 0 && GTP_U_HEADER_LENGTH + length == payload.len(),
+        2 => 4 + length <= payload.len() && length >= 4,
+        _ => false,
+    }
+}
+
+/// Parse a GTPv2-C or GTP-U message
+pub fn parse_gtp_message(packet: &[u8]) -> Option<SerializableGtpPacket> {
+    let version = packet.first()? >> 5;
+    let message_type = *packet.get(1)?;
+    let length = u16::from_be_bytes(packet.get(2..4)?.try_into().unwrap()) as usize;
+
+    let mut gtp_packet = SerializableGtpPacket {
+        version,
+        message_type: String::new(),
+        teid: None,
+        sequence: None,
+        imsi: None,
+        msisdn: None,
+        apn: None,
+        cause: None,
+        pdn_address: None,
+        f_teids: vec![],
+        bearers: vec![],
+        bearer_ids: vec![],
+        inner_source: None,
+        inner_destination: None,
+        inner_protocol: None,
+        length: packet.len(),
+    };
+
+    match version {
+        1 => {
+            gtp_packet.message_type =
+                format!("{} ({})", gtp_u_message_name(message_type), message_type);
+            parse_gtp_u_message(packet, length, &mut gtp_packet)?;
+        }
+        2 => {
+            gtp_packet.message_type =
+                format!("{} ({})", gtp_c_message_name(message_type), message_type);
+            parse_gtp_c_message(packet, length, &mut gtp_packet)?;
+        }
+        _ => return None,
+    }
+
+    Some(gtp_packet)
+}
+
+/// Parse the header and the information elements of a GTPv2-C message
+fn parse_gtp_c_message(
+    packet: &[u8],
+    length: usize,
+    gtp_packet: &mut SerializableGtpPacket,
+) -> Option<()> {
+    let message = packet.get(..4 + length)?;
+
+    let mut offset = 4;
+    if message[0] & FLAG_TEID != 0 {
+        gtp_packet.teid = Some(read_u32(message.get(4..8)?));
+        offset = 8;
+    }
+    let sequence = message.get(offset..offset + 3)?;
+    gtp_packet.sequence = Some(u32::from_be_bytes([
+        0,
+        sequence[0],
+        sequence[1],
+        sequence[2],
+    ]));
+
+    for (ie_type, value) in parse_information_elements(message.get(offset + 4..)?)? {
+        match ie_type {
+            IeTypes::IMSI => gtp_packet.imsi = Some(read_tbcd(value)),
+            IeTypes::MSISDN => gtp_packet.msisdn = Some(read_tbcd(value)),
+            IeTypes::APN => gtp_packet.apn = Some(read_apn(value)),
+            IeTypes::CAUSE => gtp_packet.cause = Some(read_cause(value)?),
+            IeTypes::PAA => gtp_packet.pdn_address = read_pdn_address(value),
+            IeTypes::EBI => gtp_packet.bearer_ids.push(value.first()? & 0x0f),
+            IeTypes::F_TEID => gtp_packet.f_teids.push(read_f_teid(value)?),
+            IeTypes::BEARER_CONTEXT => gtp_packet.bearers.push(read_bearer_context(value)?),
+            _ => (),
+        }
+    }
+
+    Some(())
+}
+
+/// Parse the header of a GTP-U message, and the addresses of the packet tunnelled by a G-PDU
+fn parse_gtp_u_message(
+    packet: &[u8],
+    length: usize,
+    gtp_packet: &mut SerializableGtpPacket,
+) -> Option<()> {
+    let message = packet.get(..GTP_U_HEADER_LENGTH + length)?;
+    let flags = message[0];
+    gtp_packet.teid = Some(read_u32(message.get(4..8)?));
+
+    let mut offset = GTP_U_HEADER_LENGTH;
+    if flags & FLAGS_OPTIONAL_FIELDS != 0 {
+        let optional_fields = message.get(8..12)?;
+        if flags & FLAG_SEQUENCE != 0 {
+            gtp_packet.sequence =
+                Some(u16::from_be_bytes([optional_fields[0], optional_fields[1]]) as u32);
+        }
+        offset = 12;
+
+        // Extension headers, each ending with the type of the next one
+        let mut next_extension = optional_fields[3];
+        while next_extension != 0 {
+            let extension_length = *message.get(offset)? as usize * 4;
+            if extension_length == 0 {
+                return None;
+            }
+            next_extension = *message.get(offset + extension_length - 1)?;
+            offset += extension_length;
+        }
+    }
+
+    if message[1] == G_PDU {
+        let inner = message.get(offset..)?;
+        let (source, destination, protocol) = match inner.first()? >> 4 {
+            4 => (
+                IpAddr::V4(Ipv4Addr::from(read_u32(inner.get(12..16)?))),
+                IpAddr::V4(Ipv4Addr::from(read_u32(inner.get(16..20)?))),
+                inner[9],
+            ),
+            6 => (
+                IpAddr::V6(Ipv6Addr::from(read_u128(inner.get(8..24)?))),
+                IpAddr::V6(Ipv6Addr::from(read_u128(inner.get(24..40)?))),
+                inner[6],
+            ),
+            _ => return Some(()),
+        };
+        gtp_packet.inner_source = Some(source);
+        gtp_packet.inner_destination = Some(destination);
+        gtp_packet.inner_protocol =
+            Some(format!("{} ({})", IpNextHeaderProtocol(protocol), protocol));
+    }
+
+    Some(())
+}
+
+/// Split information elements into their type and value, the instance being ignored
+fn parse_information_elements(mut data: &[u8]) -> Option<Vec<(u8, &[u8])>> {
+    let mut elements = vec![];
+
+    while !data.is_empty() {
+        let length = u16::from_be_bytes(data.get(1..3)?.try_into().unwrap()) as usize;
+        elements.push((data[0], data.get(4..4 + length)?));
+        data = &data[4 + length..];
+    }
+
+    Some(elements)
+}
+
+/// Read the EPS bearer ID, the cause and the F-TEIDs grouped by a bearer context
+fn read_bearer_context(value: &[u8]) -> Option<GtpBearer> {
+    let mut bearer = GtpBearer {
+        bearer_id: None,
+        cause: None,
+        f_teids: vec![],
+    };
+
+    for (ie_type, value) in parse_information_elements(value)? {
+        match ie_type {
+            IeTypes::EBI => bearer.bearer_id = Some(value.first()? & 0x0f),
+            IeTypes::CAUSE => bearer.cause = Some(read_cause(value)?),
+            IeTypes::F_TEID => bearer.f_teids.push(read_f_teid(value)?),
+            _ => (),
+        }
+    }
+
+    Some(bearer)
+}
+
+fn read_f_teid(value: &[u8]) -> Option<GtpFTeid> {
+    let flags = *value.first()?;
+    let interface_type = flags & 0x3f;
+
+    let mut offset = 5;
+    let ipv4 = if flags & 0x80 != 0 {
+        offset += 4;
+        Some(Ipv4Addr::from(read_u32(value.get(5..9)?)))
+    } else {
+        None
+    };
+    let ipv6 = if flags & 0x40 != 0 {
+        Some(Ipv6Addr::from(read_u128(value.get(offset..offset + 16)?)))
+    } else {
+        None
+    };
+
+    Some(GtpFTeid {
+        interface: format!("{} ({})", interface_name(interface_type), interface_type),
+        teid: read_u32(value.get(1..5)?),
+        ipv4,
+        ipv6,
+    })
+}
+
+fn read_cause(value: &[u8]) -> Option<String> {
+    let cause = *value.first()?;
+
+    Some(format!("{} ({})", cause_name(cause), cause))
+}
+
+/// Read the address allocated to the subscriber, the IPv4 one of a dual-stack allocation
+fn read_pdn_address(value: &[u8]) -> Option<IpAddr> {
+    match value.first()? & 0x07 {
+        1 => Some(IpAddr::V4(Ipv4Addr::from(read_u32(value.get(1..5)?)))),
+        2 => Some(IpAddr::V6(Ipv6Addr::from(read_u128(value.get(2..18)?)))),
+        3 => Some(IpAddr::V4(Ipv4Addr::from(read_u32(value.get(18..22)?)))),
+        _ => None,
+    }
+}
+
+/// Read digits in TBCD encoding: two digits per byte, the least significant nibble first, padded
+/// with 0xf
+fn read_tbcd(value: &[u8]) -> String {
+    value
+        .iter()
+        .flat_map(|byte| [byte & 0x0f, byte >> 4])
+        .take_while(|digit| *digit != 0x0f)
+        .map(|digit| char::from_digit(digit as u32, 16).unwrap())
+        .collect()
+}
+
+/// Read an APN encoded as DNS labels, each preceded by its length
+fn read_apn(mut value: &[u8]) -> String {
+    let mut labels = vec![];
+
+    while let Some((&length, rest)) = value.split_first() {
+        let label = &rest[..(length as usize).min(rest.len())];
+        labels.push(String::from_utf8_lossy(label).into_owned());
+        value = &rest[label.len()..];
+    }
+
+    labels.join(".")
+}
+
+fn read_u32(data: &[u8]) -> u32 {
+    u32::from_be_bytes(data[..4].try_into().unwrap())
+}
+
+fn read_u128(data: &[u8]) -> u128 {
+    u128::from_be_bytes(data[..16].try_into().unwrap())
+}
+
+fn gtp_c_message_name(message_type: u8) -> &'static str {
+    match message_type {
+        1 => "Echo Request",
+        2 => "Echo Response",
+        3 => "Version Not Supported Indication",
+        32 => "Create Session Request",
+        33 => "Create Session Response",
+        34 => "Modify Bearer Request",
+        35 => "Modify Bearer Response",
+        36 => "Delete Session Request",
+        37 => "Delete Session Response",
+        95 => "Create Bearer Request",
+        96 => "Create Bearer Response",
+        97 => "Update Bearer Request",
+        98 => "Update Bearer Response",
+        99 => "Delete Bearer Request",
+        100 => "Delete Bearer Response",
+        170 => "Release Access Bearers Request",
+        171 => "Release Access Bearers Response",
+        176 => "Downlink Data Notification",
+        177 => "Downlink Data Notification Acknowledge",
+        _ => "Unknown",
+    }
+}
+
+fn gtp_u_message_name(message_type: u8) -> &'static str {
+    match message_type {
+        1 => "Echo Request",
+        2 => "Echo Response",
+        26 => "Error Indication",
+        31 => "Supported Extension Headers Notification",
+        254 => "End Marker",
+        255 => "G-PDU",
+        _ => "Unknown",
+    }
+}
+
+fn interface_name(interface_type: u8) -> &'static str {
+    match interface_type {
+        0 => "S1-U eNodeB GTP-U",
+        1 => "S1-U SGW GTP-U",
+        2 => "S12 RNC GTP-U",
+        3 => "S12 SGW GTP-U",
+        4 => "S5/S8 SGW GTP-U",
+        5 => "S5/S8 PGW GTP-U",
+        6 => "S5/S8 SGW GTP-C",
+        7 => "S5/S8 PGW GTP-C",
+        10 => "S11 MME GTP-C",
+        11 => "S11/S4 SGW GTP-C",
+        _ => "Unknown",
+    }
+}
+
+fn cause_name(cause: u8) -> &'static str {
+    match cause {
+        16 => "Request accepted",
+        17 => "Request accepted partially",
+        18 => "New PDN type due to network preference",
+        19 => "New PDN type due to single address bearer only",
+        64 => "Context Not Found",
+        65 => "Invalid Message Format",
+        66 => "Version not supported by next peer",
+        67 => "Invalid length",
+        68 => "Service not supported",
+        69 => "Mandatory IE incorrect",
+        70 => "Mandatory IE missing",
+        72 => "System failure",
+        73 => "No resources available",
+        78 => "Missing or unknown APN",
+        84 => "All dynamic addresses are occupied",
+        92 => "User authentication failed",
+        93 => "APN access denied - no subscription",
+        94 => "Request rejected (reason not specified)",
+        _ => "Unknown",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr};
+
+    use super::{is_gtp_message, parse_gtp_message};
+    use crate::serializable_packet::application::{GtpBearer, GtpFTeid};
+
+    #[test]
+    fn create_session_request() {
+        let mut ies = ie(1, &[0x21, 0x43, 0x65, 0x87, 0x09, 0x21, 0x43, 0xf5]);
+        ies.extend(ie(71, b"\x08internet\x03mnc"));
+        ies.extend(ie(87, &f_teid(10, 0x1000, [10, 0, 0, 1])));
+        ies.extend(ie(79, &[0x01, 0, 0, 0, 0]));
+        let mut bearer = ie(73, &[0x05]);
+        bearer.extend(ie(87, &f_teid(0, 0x2000, [10, 0, 1, 1])));
+        ies.extend(ie(93, &bearer));
+        let message = gtp_c(32, None, 7, &ies);
+
+        assert!(is_gtp_message(&message));
+        let gtp = parse_gtp_message(&message).unwrap();
+        assert_eq!(gtp.version, 2);
+        assert_eq!(gtp.message_type, "Create Session Request (32)");
+        assert_eq!(gtp.teid, None);
+        assert_eq!(gtp.sequence, Some(7));
+        assert_eq!(gtp.imsi.as_deref(), Some("123456789012345"));
+        assert_eq!(gtp.apn.as_deref(), Some("internet.mnc"));
+        assert_eq!(gtp.pdn_address, Some(IpAddr::V4(Ipv4Addr::UNSPECIFIED)));
+        assert_eq!(
+            gtp.f_teids,
+            vec![GtpFTeid {
+                interface: "S11 MME GTP-C (10)".to_owned(),
+                teid: 0x1000,
+                ipv4: Some(Ipv4Addr::new(10, 0, 0, 1)),
+                ipv6: None,
+            }]
+        );
+        assert_eq!(
+            gtp.bearers,
+            vec![GtpBearer {
+                bearer_id: Some(5),
+                cause: None,
+                f_teids: vec![GtpFTeid {
+                    interface: "S1-U eNodeB GTP-U (0)".to_owned(),
+                    teid: 0x2000,
+                    ipv4: Some(Ipv4Addr::new(10, 0, 1, 1)),
+                    ipv6: None,
+                }],
+            }]
+        );
+    }
+
+    #[test]
+    fn delete_session_response() {
+        let message = gtp_c(37, Some(0x1000), 8, &ie(2, &[16, 0]));
+
+        let gtp = parse_gtp_message(&message).unwrap();
+        assert_eq!(gtp.message_type, "Delete Session Response (37)");
+        assert_eq!(gtp.teid, Some(0x1000));
+        assert_eq!(gtp.cause.as_deref(), Some("Request accepted (16)"));
+    }
+
+    #[test]
+    fn g_pdu_inner_packet() {
+        let mut inner = vec![0x45, 0, 0, 20, 0, 0, 0, 0, 64, 17, 0, 0];
+        inner.extend([192, 168, 0, 2, 8, 8, 8, 8]);
+        // Sequence number and a PDU session container extension header
+        let mut message = vec![0x36, 255, 0, 0, 0, 0, 0x20, 0x00, 0, 9, 0, 0x85];
+        message.extend([1, 0x10, 0x01, 0x00]);
+        message.extend(&inner);
+        let length = (message.len() - 8) as u16;
+        message[2..4].copy_from_slice(&length.to_be_bytes());
+
+        assert!(is_gtp_message(&message));
+        let gtp = parse_gtp_message(&message).unwrap();
+        assert_eq!(gtp.version, 1);
+        assert_eq!(gtp.message_type, "G-PDU (255)");
+        assert_eq!(gtp.teid, Some(0x2000));
+        assert_eq!(gtp.sequence, Some(9));
+        assert_eq!(
+            gtp.inner_source,
+            Some(IpAddr::V4(Ipv4Addr::new(192, 168, 0, 2)))
+        );
+        assert_eq!(
+            gtp.inner_destination,
+            Some(IpAddr::V4(Ipv4Addr::new(8, 8, 8, 8)))
+        );
+        assert_eq!(gtp.inner_protocol.as_deref(), Some("Udp (17)"));
+    }
+
+    #[test]
+    fn malformed_gtp_message() {
+        // Information element longer than the message
+        let message = gtp_c(32, None, 1, &[1, 0, 8, 0, 0x21]);
+        assert!(parse_gtp_message(&message).is_none());
+        // GTP' and unknown versions
+        assert!(!is_gtp_message(&[0x20, 255, 0, 0, 0, 0, 0, 0]));
+        assert!(!is_gtp_message(&[0x60, 1, 0, 4, 0, 0, 0, 0]));
+    }
+
+    ///////////////////// Utils
+
+    fn ie(ie_type: u8, value: &[u8]) -> Vec<u8> {
+        let mut ie = vec![ie_type];
+        ie.extend((value.len() as u16).to_be_bytes());
+        ie.push(0);
+        ie.extend(value);
+        ie
+    }
+
+    fn f_teid(interface_type: u8, teid: u32, ipv4: [u8; 4]) -> Vec<u8> {
+        let mut f_teid = vec![0x80 | interface_type];
+        f_teid.extend(teid.to_be_bytes());
+        f_teid.extend(ipv4);
+        f_teid
+    }
+
+    fn gtp_c(message_type: u8, teid: Option<u32>, sequence: u32, ies: &[u8]) -> Vec<u8> {
+        let mut message = vec![if teid.is_some() { 0x48 } else { 0x40 }, message_type, 0, 0];
+        if let Some(teid) = teid {
+            message.extend(teid.to_be_bytes());
+        }
+        message.extend(&sequence.to_be_bytes()[1..]);
+        message.push(0);
+        message.extend(ies);
+        let length = (message.len() - 4) as u16;
+        message[2..4].copy_from_slice(&length.to_be_bytes());
+        message
+    }
+}
@@ -0,0 +1,329 @@
+//! Health metrics of the dissectors
+//!
+//! Every invocation of a dissector is timed and its outcome counted, so that the dissectors
+//! slowing down the capture or failing on its traffic can be identified:
+//! - packets dissected, with their average and maximum parse time
+//! - errors, i.e. packets dissected as malformed
+//! - panics caught while dissecting
+//! - flows and bytes buffered while reassembling messages spanning multiple segments
+//!
+//! Buffers belong to the parser contexts: each context publishes the size of its own ones,
+//! forgotten once the context is cleared or dropped.
+//! The last panics are kept along with the flow of the packet being dissected, to report them.
+
+use std::{
+    any::Any,
+    collections::{BTreeMap, HashMap, VecDeque},
+    net::IpAddr,
+    sync::Mutex,
+    time::Duration,
+};
+
+use serde::Serialize;
+
+use super::{
+    http::HttpConnection, http2::Http2Direction, quic::QuicConnection, ApplicationProtocol,
+};
+use crate::ParserContext;
+
+/// Maximum number of panics kept for reporting, the oldest ones being dropped first
+const MAX_PANICS: usize = 100;
+
+/// Metrics shared by all the threads, since they are collected while packets are being parsed
+static PARSER_HEALTH: Mutex<ParserHealth> = Mutex::new(ParserHealth {
+    dissectors: BTreeMap::new(),
+    buffers: None,
+    panics: VecDeque::new(),
+});
+
+/// Outcome of a dissector invocation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum DissectionOutcome {
+    Dissected,
+    Malformed,
+    Panicked,
+}
+
+/// Health metrics of a dissector, as returned to the frontend
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DissectorHealth {
+    pub dissector: String,
+    pub packets: u64,
+    pub errors: u64,
+    pub panics: u64,
+    pub total_parse_time_us: u64,
+    pub average_parse_time_us: f64,
+    pub max_parse_time_us: u64,
+    pub buffered_flows: usize,
+    pub buffered_bytes: usize,
+}
+
+/// Panic caught while dissecting a packet, with the flow of the packet
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct DissectorPanic {
+    pub dissector: String,
+    pub source_ip: IpAddr,
+    pub source_port: u16,
+    pub dest_ip: IpAddr,
+    pub dest_port: u16,
+    pub payload_length: usize,
+    pub message: String,
+}
+
+/// Timing and outcomes of the invocations of a dissector
+#[derive(Debug, Default)]
+struct DissectorCounters {
+    packets: u64,
+    errors: u64,
+    panics: u64,
+    total_parse_time: Duration,
+    max_parse_time: Duration,
+}
+
+/// Buffers held by each parser context, as (flows, bytes) for each dissector
+type ContextBuffers = HashMap<usize, BTreeMap<String, (usize, usize)>>;
+
+#[derive(Debug)]
+struct ParserHealth {
+    dissectors: BTreeMap<String, DissectorCounters>,
+    buffers: Option<ContextBuffers>,
+    panics: VecDeque<DissectorPanic>,
+}
+
+/// Record the time spent by a dissector on a packet, and its outcome
+pub(crate) fn record_dissection(dissector: &str, parse_time: Duration, outcome: DissectionOutcome) {
+    let mut health = PARSER_HEALTH.lock().unwrap();
+    if !health.dissectors.contains_key(dissector) {
+        health
+            .dissectors
+            .insert(dissector.to_owned(), DissectorCounters::default());
+    }
+    let counters = health.dissectors.get_mut(dissector).unwrap();
+
+    counters.packets += 1;
+    counters.total_parse_time += parse_time;
+    counters.max_parse_time = counters.max_parse_time.max(parse_time);
+    match outcome {
+        DissectionOutcome::Dissected => (),
+        DissectionOutcome::Malformed => counters.errors += 1,
+        DissectionOutcome::Panicked => counters.panics += 1,
+    }
+}
+
+/// Keep a panic caught while dissecting a packet, for reporting
+pub(crate) fn record_panic(panic: DissectorPanic) {
+    let mut health = PARSER_HEALTH.lock().unwrap();
+    if health.panics.len() == MAX_PANICS {
+        health.panics.pop_front();
+    }
+    health.panics.push_back(panic);
+}
+
+/// Get the message of a panic, given as a `&str` or a `String` by `panic!`
+pub(crate) fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "Unknown panic".to_owned()
+    }
+}
+
+/// Publish the size of the reassembly buffers of a parser context
+pub(crate) fn publish_buffers(state: &ParserContext) {
+    let size = |buffers: &HashMap<_, Vec<u8>>| {
+        (
+            buffers.len(),
+            buffers.values().map(|buffer| buffer.len()).sum(),
+        )
+    };
+    let context_buffers = BTreeMap::from([
+        (
+            ApplicationProtocol::Http.name(),
+            (
+                state.http_parsers.len(),
+                state
+                    .http_parsers
+                    .values()
+                    .map(HttpConnection::buffered_bytes)
+                    .sum(),
+            ),
+        ),
+        (ApplicationProtocol::Tls.name(), size(&state.tls_parsers)),
+        (
+            ApplicationProtocol::Http2.name(),
+            (
+                state.http2_connections.len(),
+                state
+                    .http2_connections
+                    .values()
+                    .map(Http2Direction::buffered_bytes)
+                    .sum(),
+            ),
+        ),
+        (
+            ApplicationProtocol::Quic.name(),
+            (
+                state.quic_connections.len(),
+                state
+                    .quic_connections
+                    .values()
+                    .map(QuicConnection::buffered_bytes)
+                    .sum(),
+            ),
+        ),
+        (
+            ApplicationProtocol::Ptp.name(),
+            (
+                state.ptp_exchanges.len(),
+                state.ptp_exchanges.buffered_bytes(),
+            ),
+        ),
+    ]);
+
+    let mut health = PARSER_HEALTH.lock().unwrap();
+    health
+        .buffers
+        .get_or_insert_with(HashMap::new)
+        .insert(state.id, context_buffers);
+}
+
+/// Forget the buffers of a parser context, once its state is cleared
+pub(crate) fn forget_buffers(context_id: usize) {
+    let mut health = PARSER_HEALTH.lock().unwrap();
+    if let Some(buffers) = health.buffers.as_mut() {
+        buffers.remove(&context_id);
+    }
+}
+
+/// Get the health metrics of the dissectors invoked so far, the slowest first
+pub fn get_parser_health() -> Vec<DissectorHealth> {
+    let health = PARSER_HEALTH.lock().unwrap();
+
+    let mut report: BTreeMap<&str, DissectorHealth> = health
+        .dissectors
+        .iter()
+        .map(|(dissector, counters)| {
+            let total_parse_time_us = counters.total_parse_time.as_micros() as u64;
+
+            (
+                dissector.as_str(),
+                DissectorHealth {
+                    dissector: dissector.clone(),
+                    packets: counters.packets,
+                    errors: counters.errors,
+                    panics: counters.panics,
+                    total_parse_time_us,
+                    average_parse_time_us: total_parse_time_us as f64
+                        / counters.packets.max(1) as f64,
+                    max_parse_time_us: counters.max_parse_time.as_micros() as u64,
+                    ..Default::default()
+                },
+            )
+        })
+        .collect();
+
+    for context_buffers in health.buffers.iter().flat_map(HashMap::values) {
+        for (dissector, (flows, bytes)) in context_buffers {
+            if let Some(dissector_health) = report.get_mut(dissector.as_str()) {
+                dissector_health.buffered_flows += flows;
+                dissector_health.buffered_bytes += bytes;
+            }
+        }
+    }
+
+    let mut report: Vec<DissectorHealth> = report.into_values().collect();
+    report.sort_by(|a, b| {
+        b.total_parse_time_us
+            .cmp(&a.total_parse_time_us)
+            .then_with(|| a.dissector.cmp(&b.dissector))
+    });
+
+    report
+}
+
+/// Get the last panics caught while dissecting packets, the oldest first
+pub fn get_dissector_panics() -> Vec<DissectorPanic> {
+    PARSER_HEALTH
+        .lock()
+        .unwrap()
+        .panics
+        .iter()
+        .cloned()
+        .collect()
+}
+
+/// Forget the metrics and panics collected so far, keeping the buffers currently held
+pub fn reset_parser_health() {
+    let mut health = PARSER_HEALTH.lock().unwrap();
+    health.dissectors.clear();
+    health.panics.clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr};
+    use std::time::Duration;
+
+    use super::{get_parser_health, publish_buffers, record_dissection, DissectionOutcome};
+    use crate::{FlowKey, ParserContext};
+
+    #[test]
+    fn dissector_health() {
+        record_dissection(
+            "health_a",
+            Duration::from_micros(10),
+            DissectionOutcome::Dissected,
+        );
+        record_dissection(
+            "health_a",
+            Duration::from_micros(30),
+            DissectionOutcome::Malformed,
+        );
+        record_dissection(
+            "health_b",
+            Duration::from_secs(3600),
+            DissectionOutcome::Dissected,
+        );
+
+        let report = get_parser_health();
+        // The slowest dissector comes first
+        assert_eq!(report[0].dissector, "health_b");
+
+        let health_a = report
+            .iter()
+            .find(|health| health.dissector == "health_a")
+            .unwrap();
+        assert_eq!(health_a.packets, 2);
+        assert_eq!(health_a.errors, 1);
+        assert_eq!(health_a.total_parse_time_us, 40);
+        assert_eq!(health_a.average_parse_time_us, 20.0);
+        assert_eq!(health_a.max_parse_time_us, 30);
+    }
+
+    #[test]
+    fn buffers_of_context() {
+        let endpoint = (IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)), 443);
+        let mut context = ParserContext::new();
+        context
+            .tls_parsers
+            .insert(FlowKey::of(endpoint, endpoint), vec![0x16; 100]);
+        record_dissection(
+            "tls",
+            Duration::from_micros(1),
+            DissectionOutcome::Dissected,
+        );
+        publish_buffers(&context);
+
+        let report = get_parser_health();
+        let tls = report
+            .iter()
+            .find(|health| health.dissector == "tls")
+            .unwrap();
+        assert!(tls.buffered_flows >= 1);
+        assert!(tls.buffered_bytes >= 100);
+    }
+}
@@ -0,0 +1,271 @@
+//! Heuristic detection of application-layer protocols
+//!
+//! Traffic on ports not associated with any protocol is recognized by the signature of its
+//! payload, probed by each registered dissector. The built-in ones recognize:
+//! - HTTP: request lines (`GET / HTTP/1.1`) and status lines (`HTTP/1.1 200 OK`), and in the
+//!   tolerant parsing mode lines ended by LF only and HTTP/0.9 simple requests (`GET /`)
+//! - HTTP/2: the preface sent by the client at the start of the connection (`PRI * HTTP/2.0`)
+//! - TLS: record headers with a known content type, version and a plausible length
+//! - DNS: headers with sane flags and counts, followed by a well-formed question
+//! - WireGuard: messages of a known type with zero reserved bytes and the length of their type
+//!
+//! Since only the first segments of a connection usually carry a recognizable signature, the
+//! detected dissector is remembered for both directions of the connection.
+
+use std::{net::IpAddr, sync::Arc};
+
+use super::{
+    http::{get_http_parsing_mode, HttpParsingMode},
+    http2::CONNECTION_PREFACE,
+    registry::{dissectors, Dissector, Transport},
+    ApplicationProtocol,
+};
+use crate::{FlowKey, ParserContext};
+
+/// HTTP methods which can start a request line
+const HTTP_METHODS: &[&str] = &[
+    "GET", "POST", "PUT", "DELETE", "HEAD", "OPTIONS", "PATCH", "CONNECT", "TRACE",
+];
+
+/// Maximum length of a TLS record: 2^14 bytes of data plus the expansion of the encryption
+const MAX_TLS_RECORD_LENGTH: usize = 16384 + 2048;
+
+/// Maximum number of records in a section of a plausible DNS message
+const MAX_DNS_RECORDS: u16 = 64;
+
+/// Detect the dissector of the payload of a connection, remembering it for the following segments
+///
+/// The returned flag tells whether the payload is a request (i.e. sent by the client).
+pub fn detect_protocol(
+    state: &mut ParserContext,
+    source_ip: IpAddr,
+    source_port: u16,
+    dest_ip: IpAddr,
+    dest_port: u16,
+    transport: Transport,
+    packet: &[u8],
+) -> Option<(Arc<dyn Dissector>, bool)> {
+    let (key, direction) = FlowKey::of((source_ip, source_port), (dest_ip, dest_port));
+    let flow = (key, direction);
+    let reversed = (key, direction.reverse());
+
+    if let Some(detected) = state.detected_protocols.get(&flow).cloned() {
+        return Some(detected);
+    }
+
+    let dissector = dissectors().read().unwrap().probe(packet, transport)?;
+    let is_request = dissector.is_request(packet);
+
+    // Each DNS message stands on its own: nothing to remember
+    if dissector.name() != ApplicationProtocol::Dns.name() {
+        let detected = &mut state.detected_protocols;
+        detected.insert(flow, (dissector.clone(), is_request));
+        detected.insert(reversed, (dissector.clone(), !is_request));
+    }
+
+    Some((dissector, is_request))
+}
+
+/// Check if the payload starts with an HTTP/1.x request line
+pub fn is_http_request(packet: &[u8]) -> bool {
+    let line = match first_line(packet) {
+        Some(line) => line,
+        None => return false,
+    };
+
+    match line.split(' ').collect::<Vec<_>>()[..] {
+        [method, target, version] => {
+            HTTP_METHODS.contains(&method) && !target.is_empty() && is_http_version(version)
+        }
+        ["GET", target] => is_tolerant() && target.starts_with('/'),
+        _ => false,
+    }
+}
+
+/// Check if the payload starts with an HTTP/1.x status line
+pub fn is_http_response(packet: &[u8]) -> bool {
+    let line = match first_line(packet) {
+        Some(line) => line,
+        None => return false,
+    };
+
+    let mut parts = line.splitn(3, ' ');
+    match (parts.next(), parts.next()) {
+        (Some(version), Some(code)) => {
+            is_http_version(version)
+                && code.len() == 3
+                && code.bytes().all(|digit| digit.is_ascii_digit())
+        }
+        _ => false,
+    }
+}
+
+/// Check if the payload starts with the HTTP/2 connection preface
+pub fn is_http2_preface(packet: &[u8]) -> bool {
+    packet.starts_with(CONNECTION_PREFACE)
+}
+
+/// Check if the payload starts with a TLS record header
+pub fn is_tls_record(packet: &[u8]) -> bool {
+    if packet.len() < 5 {
+        return false;
+    }
+
+    // ChangeCipherSpec, Alert, Handshake and ApplicationData
+    let content_type = (20..=23).contains(&packet[0]);
+    // SSL 3.0 up to TLS 1.3, which keeps 1.2 in the record layer
+    let version = packet[1] == 3 && packet[2] <= 4;
+    let length = u16::from_be_bytes([packet[3], packet[4]]) as usize;
+
+    content_type && version && (1..=MAX_TLS_RECORD_LENGTH).contains(&length)
+}
+
+/// Check if the payload is a plausible DNS message, with a single well-formed question
+pub fn is_dns_message(packet: &[u8]) -> bool {
+    if packet.len() < 12 {
+        return false;
+    }
+
+    let field = |offset: usize| u16::from_be_bytes([packet[offset], packet[offset + 1]]);
+    let flags = field(2);
+    let is_response = flags & 0x8000 != 0;
+    let opcode = (flags >> 11) & 0x0f;
+    let reserved = flags & 0x0040;
+    let (questions, answers, authority, additional) = (field(4), field(6), field(8), field(10));
+
+    if opcode > 5 || reserved != 0 || questions != 1 {
+        return false;
+    }
+    if [answers, authority, additional]
+        .iter()
+        .any(|count| *count > MAX_DNS_RECORDS)
+    {
+        return false;
+    }
+    if !is_response && answers != 0 {
+        return false;
+    }
+
+    // Question name, as a sequence of labels terminated by the root one
+    let mut offset = 12;
+    loop {
+        let length = match packet.get(offset) {
+            Some(length) => *length as usize,
+            None => return false,
+        };
+        offset += 1;
+
+        if length == 0 {
+            break;
+        }
+        if length > 63 {
+            return false;
+        }
+
+        match packet.get(offset..offset + length) {
+            Some(label) if label.iter().all(|c| c.is_ascii_graphic()) => offset += length,
+            _ => return false,
+        }
+    }
+
+    // Question class, ignoring the mDNS unicast-response bit: IN or ANY
+    match packet.get(offset + 2..offset + 4) {
+        Some(class) => matches!(u16::from_be_bytes([class[0], class[1]]) & 0x7fff, 1 | 255),
+        None => false,
+    }
+}
+
+/// Get the first line of a payload, if terminated by CRLF, or by LF only when parsing tolerantly
+fn first_line(packet: &[u8]) -> Option<&str> {
+    let end = packet.iter().position(|byte| *byte == b'\n')?;
+    let line = match packet[..end].strip_suffix(b"\r") {
+        Some(line) => line,
+        None if is_tolerant() => &packet[..end],
+        None => return None,
+    };
+
+    std::str::from_utf8(line).ok()
+}
+
+fn is_tolerant() -> bool {
+    get_http_parsing_mode() == HttpParsingMode::Tolerant
+}
+
+fn is_http_version(version: &str) -> bool {
+    matches!(version, "HTTP/1.0" | "HTTP/1.1")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_dns_message, is_http_request, is_http_response, is_tls_record};
+
+    #[test]
+    fn http_signatures() {
+        assert!(is_http_request(
+            b"GET /index.html HTTP/1.1\r\nHost: a\r\n\r\n"
+        ));
+        assert!(is_http_request(b"CONNECT example.com:443 HTTP/1.1\r\n"));
+        assert!(is_http_response(b"HTTP/1.1 404 Not Found\r\n\r\n"));
+        assert!(is_http_response(b"HTTP/1.0 200\r\n"));
+
+        assert!(!is_http_request(b"GET /index.html HTTP/1.1"));
+        assert!(!is_http_request(b"FETCH / HTTP/1.1\r\n"));
+        assert!(!is_http_request(b"GET / HTTP/2\r\n"));
+        assert!(!is_http_response(b"HTTP/1.1 2000 OK\r\n"));
+        assert!(!is_http_response(b"GET / HTTP/1.1\r\n"));
+
+        // Accepted by the default tolerant parsing
+        assert!(is_http_request(b"GET /index.html\r\n"));
+        assert!(is_http_response(b"HTTP/1.0 200 OK\n"));
+        assert!(!is_http_request(b"POST /index.html\r\n"));
+    }
+
+    #[test]
+    fn tls_signatures() {
+        // Handshake record of a ClientHello
+        assert!(is_tls_record(&[0x16, 0x03, 0x01, 0x02, 0x00, 0x01]));
+        // Application data
+        assert!(is_tls_record(&[0x17, 0x03, 0x03, 0x00, 0x20]));
+
+        assert!(!is_tls_record(&[0x18, 0x03, 0x03, 0x00, 0x20]));
+        assert!(!is_tls_record(&[0x16, 0x02, 0x00, 0x00, 0x20]));
+        assert!(!is_tls_record(&[0x17, 0x03, 0x03, 0x80, 0x00]));
+        assert!(!is_tls_record(&[0x16, 0x03, 0x01]));
+    }
+
+    #[test]
+    fn dns_signatures() {
+        let query = dns_query(b"\x07example\x03com\x00", 0x0001);
+        assert!(is_dns_message(&query));
+
+        // mDNS query asking for a unicast response
+        let mdns_query = dns_query(b"\x05_http\x04_tcp\x05local\x00", 0x8001);
+        assert!(is_dns_message(&mdns_query));
+
+        let mut truncated = query.clone();
+        truncated.truncate(20);
+        assert!(!is_dns_message(&truncated));
+
+        let mut query_with_answers = query.clone();
+        query_with_answers[7] = 1;
+        assert!(!is_dns_message(&query_with_answers));
+
+        let chaos_class = dns_query(b"\x07example\x03com\x00", 0x0003);
+        assert!(!is_dns_message(&chaos_class));
+
+        assert!(!is_dns_message(b"GET / HTTP/1.1\r\n\r\n"));
+    }
+
+    ///////////////////// Utils
+
+    fn dns_query(name: &[u8], class: u16) -> Vec<u8> {
+        let mut query = vec![
+            0x12, 0x34, 0x01, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        ];
+        query.extend(name);
+        query.extend([0x00, 0x01]);
+        query.extend(class.to_be_bytes());
+
+        query
+    }
+}
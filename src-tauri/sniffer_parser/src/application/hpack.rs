@@ -0,0 +1,393 @@
+//! HPACK decoding of the header blocks of HTTP/2 (RFC 7541)
+//!
+//! Header fields are either literals or indexes in a table, made of the static table, shared by
+//! all the connections, followed by the dynamic table of the direction of the connection. The
+//! dynamic table is filled by the header blocks themselves, so it must be kept from the start of
+//! the connection: header blocks decoded without it miss their indexed fields.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::OnceLock,
+};
+
+/// Size of the dynamic table until the encoder updates it
+const DEFAULT_TABLE_SIZE: usize = 4096;
+/// Upper bound of the size of the dynamic table, to bound the memory kept for each connection
+const MAX_TABLE_SIZE: usize = 64 * 1024;
+/// Size accounted for each entry of the dynamic table on top of its name and value
+const ENTRY_OVERHEAD: usize = 32;
+/// Symbol of the Huffman code marking the end of the string, never sent
+const EOS: u16 = 256;
+
+/// Header fields of the static table, indexed from 1
+const STATIC_TABLE: [(&str, &str); 61] = [
+    (":authority", ""),
+    (":method", "GET"),
+    (":method", "POST"),
+    (":path", "/"),
+    (":path", "/index.html"),
+    (":scheme", "http"),
+    (":scheme", "https"),
+    (":status", "200"),
+    (":status", "204"),
+    (":status", "206"),
+    (":status", "304"),
+    (":status", "400"),
+    (":status", "404"),
+    (":status", "500"),
+    ("accept-charset", ""),
+    ("accept-encoding", "gzip, deflate"),
+    ("accept-language", ""),
+    ("accept-ranges", ""),
+    ("accept", ""),
+    ("access-control-allow-origin", ""),
+    ("age", ""),
+    ("allow", ""),
+    ("authorization", ""),
+    ("cache-control", ""),
+    ("content-disposition", ""),
+    ("content-encoding", ""),
+    ("content-language", ""),
+    ("content-length", ""),
+    ("content-location", ""),
+    ("content-range", ""),
+    ("content-type", ""),
+    ("cookie", ""),
+    ("date", ""),
+    ("etag", ""),
+    ("expect", ""),
+    ("expires", ""),
+    ("from", ""),
+    ("host", ""),
+    ("if-match", ""),
+    ("if-modified-since", ""),
+    ("if-none-match", ""),
+    ("if-range", ""),
+    ("if-unmodified-since", ""),
+    ("last-modified", ""),
+    ("link", ""),
+    ("location", ""),
+    ("max-forwards", ""),
+    ("proxy-authenticate", ""),
+    ("proxy-authorization", ""),
+    ("range", ""),
+    ("referer", ""),
+    ("refresh", ""),
+    ("retry-after", ""),
+    ("server", ""),
+    ("set-cookie", ""),
+    ("strict-transport-security", ""),
+    ("transfer-encoding", ""),
+    ("user-agent", ""),
+    ("vary", ""),
+    ("via", ""),
+    ("www-authenticate", ""),
+];
+
+/// Length in bits of the Huffman code of each byte, followed by EOS
+///
+/// The code is canonical: the codes of the same length are consecutive, in the order of their
+/// symbols, and follow the shorter ones, so they can be rebuilt from their lengths alone.
+const HUFFMAN_CODE_LENGTHS: [u8; 257] = [
+    13, 23, 28, 28, 28, 28, 28, 28, 28, 24, 30, 28, 28, 30, 28, 28, 28, 28, 28, 28, 28, 28, 30, 28,
+    28, 28, 28, 28, 28, 28, 28, 28, 6, 10, 10, 12, 13, 6, 8, 11, 10, 10, 8, 11, 8, 6, 6, 6, 5, 5,
+    5, 6, 6, 6, 6, 6, 6, 6, 7, 8, 15, 6, 12, 10, 13, 6, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7,
+    7, 7, 7, 7, 7, 7, 7, 7, 8, 7, 8, 13, 19, 13, 14, 6, 15, 5, 6, 5, 6, 5, 6, 6, 6, 5, 7, 7, 6, 6,
+    6, 5, 6, 7, 6, 5, 5, 6, 7, 7, 7, 7, 7, 15, 11, 14, 13, 28, 20, 22, 20, 20, 22, 22, 22, 23, 22,
+    23, 23, 23, 23, 23, 24, 23, 24, 24, 22, 23, 24, 23, 23, 23, 23, 21, 22, 23, 22, 23, 23, 24, 22,
+    21, 20, 22, 22, 23, 23, 21, 23, 22, 22, 24, 21, 22, 23, 23, 21, 21, 22, 21, 23, 22, 23, 23, 20,
+    22, 22, 22, 23, 22, 22, 23, 26, 26, 20, 19, 22, 23, 22, 25, 26, 26, 26, 27, 27, 26, 24, 25, 19,
+    21, 26, 27, 27, 26, 27, 24, 21, 21, 26, 26, 28, 27, 27, 27, 20, 24, 20, 21, 22, 21, 21, 23, 22,
+    22, 25, 25, 24, 24, 26, 23, 26, 27, 26, 26, 27, 27, 27, 27, 27, 28, 27, 27, 27, 27, 27, 26, 30,
+];
+
+/// Symbols of the Huffman code, by length and code
+static HUFFMAN_SYMBOLS: OnceLock<HashMap<(u8, u32), u16>> = OnceLock::new();
+
+/// Decoder of the header blocks sent in a direction of a connection
+#[derive(Debug)]
+pub(crate) struct HpackDecoder {
+    /// Dynamic table, the newest entry first
+    table: VecDeque<(String, String)>,
+    size: usize,
+    max_size: usize,
+}
+
+impl Default for HpackDecoder {
+    fn default() -> Self {
+        HpackDecoder {
+            table: VecDeque::new(),
+            size: 0,
+            max_size: DEFAULT_TABLE_SIZE,
+        }
+    }
+}
+
+impl HpackDecoder {
+    /// Decode a header block, updating the dynamic table
+    pub(crate) fn decode(&mut self, block: &[u8]) -> Option<Vec<(String, String)>> {
+        let mut headers = vec![];
+        let mut offset = 0;
+
+        while let Some(&first) = block.get(offset) {
+            if first & 0x80 != 0 {
+                // Indexed field
+                let index = read_integer(block, &mut offset, 7)?;
+                headers.push(self.field(index)?);
+            } else if first & 0x40 != 0 {
+                // Literal field, added to the dynamic table
+                let field = self.literal(block, &mut offset, 6)?;
+                self.insert(field.clone());
+                headers.push(field);
+            } else if first & 0x20 != 0 {
+                // Update of the size of the dynamic table
+                let max_size = read_integer(block, &mut offset, 5)?;
+                if max_size > MAX_TABLE_SIZE {
+                    return None;
+                }
+                self.max_size = max_size;
+                self.evict(0);
+            } else {
+                // Literal field without indexing, or never indexed
+                headers.push(self.literal(block, &mut offset, 4)?);
+            }
+        }
+
+        Some(headers)
+    }
+
+    /// Get the field at an index of the static or dynamic table
+    fn field(&self, index: usize) -> Option<(String, String)> {
+        match index.checked_sub(1)? {
+            index if index < STATIC_TABLE.len() => {
+                let (name, value) = STATIC_TABLE[index];
+                Some((name.to_owned(), value.to_owned()))
+            }
+            index => self.table.get(index - STATIC_TABLE.len()).cloned(),
+        }
+    }
+
+    /// Read a literal field, whose name is either a literal or the one of an indexed field
+    fn literal(&self, block: &[u8], offset: &mut usize, prefix: u8) -> Option<(String, String)> {
+        let name = match read_integer(block, offset, prefix)? {
+            0 => read_string(block, offset)?,
+            index => self.field(index)?.0,
+        };
+        let value = read_string(block, offset)?;
+
+        Some((name, value))
+    }
+
+    fn insert(&mut self, field: (String, String)) {
+        let size = entry_size(&field);
+        if size > self.max_size {
+            // An entry larger than the table empties it
+            self.table.clear();
+            self.size = 0;
+            return;
+        }
+
+        self.evict(size);
+        self.size += size;
+        self.table.push_front(field);
+    }
+
+    /// Evict the oldest entries until the given size fits in the table
+    fn evict(&mut self, needed: usize) {
+        while self.size + needed > self.max_size {
+            match self.table.pop_back() {
+                Some(field) => self.size -= entry_size(&field),
+                None => break,
+            }
+        }
+    }
+}
+
+fn entry_size((name, value): &(String, String)) -> usize {
+    name.len() + value.len() + ENTRY_OVERHEAD
+}
+
+/// Read an integer whose first byte shares its most significant bits with a representation type
+fn read_integer(block: &[u8], offset: &mut usize, prefix: u8) -> Option<usize> {
+    let mask = (1 << prefix) - 1;
+    let mut value = (*block.get(*offset)? & mask) as usize;
+    *offset += 1;
+    if value < mask as usize {
+        return Some(value);
+    }
+
+    // Continuation bytes, 7 bits each from the least significant ones
+    let mut shift = 0;
+    loop {
+        let byte = *block.get(*offset)?;
+        *offset += 1;
+        value += ((byte & 0x7f) as usize) << shift;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+
+        shift += 7;
+        if shift > 28 {
+            return None;
+        }
+    }
+}
+
+/// Read a string literal, Huffman-encoded or not
+fn read_string(block: &[u8], offset: &mut usize) -> Option<String> {
+    let is_huffman = *block.get(*offset)? & 0x80 != 0;
+    let length = read_integer(block, offset, 7)?;
+    let bytes = block.get(*offset..offset.checked_add(length)?)?;
+    *offset += length;
+
+    let bytes = match is_huffman {
+        true => huffman_decode(bytes)?,
+        false => bytes.to_vec(),
+    };
+
+    Some(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+fn huffman_decode(bytes: &[u8]) -> Option<Vec<u8>> {
+    let symbols = HUFFMAN_SYMBOLS.get_or_init(huffman_symbols);
+    let mut decoded = Vec::with_capacity(bytes.len() * 8 / 5);
+    let (mut code, mut length) = (0u32, 0u8);
+
+    for byte in bytes {
+        for bit in (0..8).rev() {
+            code = (code << 1) | ((byte >> bit) & 1) as u32;
+            length += 1;
+
+            match symbols.get(&(length, code)) {
+                Some(&EOS) => return None,
+                Some(&symbol) => {
+                    decoded.push(symbol as u8);
+                    (code, length) = (0, 0);
+                }
+                None if length == 30 => return None,
+                None => (),
+            }
+        }
+    }
+
+    // The padding is made of at most 7 bits, the most significant ones of EOS (all set)
+    if length > 7 || code != (1 << length) - 1 {
+        return None;
+    }
+
+    Some(decoded)
+}
+
+/// Rebuild the canonical Huffman code from the lengths of its codes
+fn huffman_symbols() -> HashMap<(u8, u32), u16> {
+    let mut symbols: Vec<u16> = (0..=EOS).collect();
+    symbols.sort_by_key(|&symbol| HUFFMAN_CODE_LENGTHS[symbol as usize]);
+
+    let mut codes = HashMap::new();
+    let (mut code, mut previous_length) = (0u32, 0u8);
+    for (i, symbol) in symbols.into_iter().enumerate() {
+        let length = HUFFMAN_CODE_LENGTHS[symbol as usize];
+        if i > 0 {
+            code = (code + 1) << (length - previous_length);
+        }
+        previous_length = length;
+        codes.insert((length, code), symbol);
+    }
+
+    codes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::HpackDecoder;
+
+    #[test]
+    fn requests_with_huffman_coding() {
+        // RFC 7541, appendix C.4
+        let mut decoder = HpackDecoder::default();
+
+        assert_eq!(
+            decoder
+                .decode(&hex("828684418cf1e3c2e5f23a6ba0ab90f4ff"))
+                .unwrap(),
+            fields(&[
+                (":method", "GET"),
+                (":scheme", "http"),
+                (":path", "/"),
+                (":authority", "www.example.com"),
+            ])
+        );
+        assert_eq!(
+            decoder.decode(&hex("828684be5886a8eb10649cbf")).unwrap(),
+            fields(&[
+                (":method", "GET"),
+                (":scheme", "http"),
+                (":path", "/"),
+                (":authority", "www.example.com"),
+                ("cache-control", "no-cache"),
+            ])
+        );
+        assert_eq!(
+            decoder
+                .decode(&hex("828785bf408825a849e95ba97d7f8925a849e95bb8e8b4bf"))
+                .unwrap(),
+            fields(&[
+                (":method", "GET"),
+                (":scheme", "https"),
+                (":path", "/index.html"),
+                (":authority", "www.example.com"),
+                ("custom-key", "custom-value"),
+            ])
+        );
+        assert_eq!(decoder.size, 164);
+    }
+
+    #[test]
+    fn entries_are_evicted() {
+        let mut decoder = HpackDecoder::default();
+
+        // Table of 64 bytes, filled by a 54-bytes entry and then by a 34-bytes one
+        let mut block = hex("3f21400a");
+        block.extend_from_slice(b"custom-key");
+        block.push(0x0c);
+        block.extend_from_slice(b"custom-value");
+        block.extend_from_slice(&hex("4001"));
+        block.push(b'k');
+        block.push(0x01);
+        block.push(b'v');
+        decoder.decode(&block).unwrap();
+
+        assert_eq!(decoder.decode(&hex("be")).unwrap(), fields(&[("k", "v")]));
+        assert!(decoder.decode(&hex("bf")).is_none());
+    }
+
+    #[test]
+    fn malformed_blocks() {
+        let mut decoder = HpackDecoder::default();
+
+        // Index 0
+        assert!(decoder.decode(&hex("80")).is_none());
+        // Name longer than the block
+        assert!(decoder.decode(&hex("0085ab")).is_none());
+        // Missing value
+        assert!(decoder.decode(&hex("000161")).is_none());
+        // Huffman padding longer than 7 bits
+        assert!(decoder.decode(&hex("00016182ffff")).is_none());
+    }
+
+    ///////////////////// Utils
+
+    fn hex(string: &str) -> Vec<u8> {
+        (0..string.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&string[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    fn fields(fields: &[(&str, &str)]) -> Vec<(String, String)> {
+        fields
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+}
@@ -1,38 +1,165 @@
 //! HTTP Packet parsing
-
-use std::{io::Read, net::IpAddr};
+//!
+//! Real-world servers often deviate from the HTTP/1.x syntax. In the tolerant parsing mode (the
+//! default), their messages are parsed anyway, and the deviations tolerated are listed as the
+//! quirks of each message:
+//! - HTTP/0.9: simple requests (`GET /path`), answered by a bare body ended by the connection close
+//! - status lines without a reason phrase
+//! - lines ended by LF only, in the headers and in the chunked bodies
+//! - bodies ended by the connection close before the end announced by their headers
+//!
+//! The strict mode only parses the messages framed as RFC 9112 requires.
+//!
+//! The trailer fields following a chunked body are merged into the headers of its message, after
+//! the header fields (see [`merge_chunks`]).
+//!
+//! Both the directions of a connection are parsed together, so that the responses are framed by
+//! the requests they answer (see [`HttpConnection`]).
+
+use std::{collections::VecDeque, fmt, io::Read, net::IpAddr, sync::RwLock};
 
 use encoding_rs::Encoding;
 use flate2::bufread::{DeflateDecoder, GzDecoder, ZlibDecoder};
 use httparse::Header;
-use log::debug;
 use mime::Mime;
+use serde::{Deserialize, Serialize};
+use tracing::debug;
 
 use crate::{
+    flows::{exceeds_flow_limit, get_buffer_limits, FlowDirection, FlowKey},
     serializable_packet::{
         application::{
-            HttpContentType, SerializableHttpRequestPacket, SerializableHttpResponsePacket,
+            DockerRegistryEndpoint, DockerRegistryRequest, HttpContentType,
+            SerializableHttpRequestPacket, SerializableHttpResponsePacket,
         },
         ParsedPacket, SerializablePacket,
     },
-    HttpPacketType, ACTIVE_HTTP_PARSERS,
+    HttpPacketType, ParserContext,
 };
 
 use super::{ContentEncoding, HeaderNamesValues};
 
+/// Parsing mode of the HTTP messages, chosen by the user
+///
+/// Shared by all the threads, since the mode is set while packets are being parsed.
+static HTTP_PARSING_MODE: RwLock<HttpParsingMode> = RwLock::new(HttpParsingMode::Tolerant);
+
+/// Version of the HTTP/0.9 messages, in place of the minor version of the HTTP/1.x ones
+pub const HTTP_09_VERSION: u8 = 9;
+
+/// Requests waiting for their response kept for each connection
+const MAX_PENDING_REQUESTS: usize = 64;
+
+/// Trailer fields parsed after a chunked body
+const MAX_TRAILERS: usize = 64;
+
+/// Deviations from the HTTP/1.x syntax tolerated while parsing a message
+#[allow(non_snake_case)]
+mod Quirks {
+    pub const HTTP_09: &str = "HTTP/0.9";
+    pub const MISSING_REASON: &str = "Missing reason phrase";
+    pub const LF_LINE_ENDINGS: &str = "Lines ended by LF only";
+    pub const CLOSE_DELIMITED: &str = "Body ended by the connection close";
+}
+
+/// How strictly the HTTP messages are parsed
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum HttpParsingMode {
+    /// Only the messages framed as RFC 9112 requires
+    Strict,
+    /// The messages of sloppy servers too, listing their quirks
+    Tolerant,
+}
+
+/// Replace the parsing mode of the HTTP messages
+pub fn set_http_parsing_mode(mode: HttpParsingMode) {
+    *HTTP_PARSING_MODE.write().unwrap() = mode;
+}
+
+/// Get the parsing mode of the HTTP messages
+pub fn get_http_parsing_mode() -> HttpParsingMode {
+    *HTTP_PARSING_MODE.read().unwrap()
+}
+
 /// Errors occurring during the parsing of HTTP data
 #[derive(Debug)]
 enum HttpParsingError {
     TransferEncodingMalformed(String),
+    ContentLengthMalformed(String),
     DecodingPayloadFailed(String, String),
     UnknownDecodingAlgorithm(String, String),
     Other,
 }
 
+impl fmt::Display for HttpParsingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HttpParsingError::TransferEncodingMalformed(reason)
+            | HttpParsingError::ContentLengthMalformed(reason)
+            | HttpParsingError::DecodingPayloadFailed(_, reason)
+            | HttpParsingError::UnknownDecodingAlgorithm(_, reason) => write!(f, "{}", reason),
+            HttpParsingError::Other => write!(f, "Malformed HTTP Packet"),
+        }
+    }
+}
+
 type Result<T> = std::result::Result<T, HttpParsingError>;
 
+/// Names and values of header or trailer fields
+type Fields = Vec<(String, String)>;
+
+/// HTTP/1.x connection being parsed, in both its directions
+///
+/// Responses are framed by the request they answer (RFC 9112): the responses to HEAD requests
+/// have no body whatever their headers, and a successful response to a CONNECT request, as well as
+/// a switch of protocols, turns the connection into a tunnel whose bytes are not parsed.
+#[derive(Debug, Default)]
+pub(crate) struct HttpConnection {
+    /// Bytes of the message being buffered, in each direction
+    forward: Vec<u8>,
+    reverse: Vec<u8>,
+    /// Methods of the requests waiting for their response, the oldest first
+    pending_methods: VecDeque<String>,
+    /// Whether the server answers with the bare bodies of HTTP/0.9
+    simple_responses: bool,
+    tunnel: bool,
+}
+
+impl HttpConnection {
+    /// Get the bytes of the message being buffered in a direction
+    pub(crate) fn buffer(&self, direction: FlowDirection) -> &[u8] {
+        match direction {
+            FlowDirection::Forward => &self.forward,
+            FlowDirection::Reverse => &self.reverse,
+        }
+    }
+
+    pub(crate) fn buffer_mut(&mut self, direction: FlowDirection) -> &mut Vec<u8> {
+        match direction {
+            FlowDirection::Forward => &mut self.forward,
+            FlowDirection::Reverse => &mut self.reverse,
+        }
+    }
+
+    /// Get the bytes buffered in both the directions
+    pub(crate) fn buffered_bytes(&self) -> usize {
+        self.forward.len() + self.reverse.len()
+    }
+
+    /// Record a request, waiting for its response
+    fn push_request(&mut self, method: Option<&str>) {
+        if self.pending_methods.len() >= MAX_PENDING_REQUESTS {
+            self.pending_methods.pop_front();
+        }
+        self.pending_methods
+            .push_back(method.unwrap_or_default().to_owned());
+    }
+}
+
 /// Build a HTTP request/response packet from a data-link packet, save it in a Parsed Packet
 pub fn handle_http_packet(
+    state: &mut ParserContext,
     source_ip: IpAddr,
     source_port: u16,
     dest_ip: IpAddr,
@@ -42,107 +169,309 @@ pub fn handle_http_packet(
     packet: &[u8],
     parsed_packet: &mut ParsedPacket,
 ) {
-    ACTIVE_HTTP_PARSERS.with(|parsers| {
-        let mut parsers = parsers.borrow_mut();
-        let current_payload = parsers
-            .entry(((source_ip, source_port), (dest_ip, dest_port)))
-            .and_modify(|payload| payload.append(packet.to_vec().as_mut()))
-            .or_insert(packet.to_vec());
+    let (key, direction) = FlowKey::of((source_ip, source_port), (dest_ip, dest_port));
 
-        let mut headers = [httparse::EMPTY_HEADER; 1024];
+    let connection = state.http_parsers.entry(key).or_default();
+    if connection.tunnel {
+        return;
+    }
 
-        match http_type {
-            HttpPacketType::Request => {
-                let mut request = httparse::Request::new(&mut headers);
-                let status = request.parse(&current_payload);
+    // Taken out of the connection while parsed, and put back unless the message is ended
+    let mut current_payload = std::mem::take(connection.buffer_mut(direction));
+    current_payload.extend_from_slice(packet);
 
-                if let Ok(status) = status {
-                    if status.is_complete() {
-                        let start = status.unwrap();
-                        let current_payload_size = current_payload.len() - start;
+    if exceeds_flow_limit(current_payload.len()) {
+        let message = format!(
+            "HTTP message exceeds the buffer limit of {} bytes",
+            get_buffer_limits().flow_bytes
+        );
+        debug!(
+            "{}: {}:{} > {}:{}",
+            message, source_ip, source_port, dest_ip, dest_port
+        );
+        parsed_packet
+            .set_application_layer_packet(Some(SerializablePacket::MalformedPacket(message)));
+        return;
+    }
 
-                        if packet_is_ended(&current_payload[start..], current_payload_size,
-                            request.headers, http_type, is_fin)
-                        {
-                            let parsed_payload = parse_http_payload(
-                                current_payload.clone(),
-                                start,
-                                request.headers,
-                            );
-
-                            match parsed_payload {
-                                Ok(parsed_payload) => {
-                                    debug!(
-                                        "HTTP Request Packet: {:?} {:?} {:?}; Headers: {:?}; Payload: {:?}",
-                                        request.method, request.path, request.version, request.headers, parsed_payload
-                                    );
-
-                                    parsed_packet.set_application_layer_packet(Some(
-                                        SerializablePacket::HttpRequestPacket(
-                                            SerializableHttpRequestPacket::new(&request, parsed_payload),
-                                        ),
-                                    ));
-                                },
-                                Err(_) => {
-                                    debug!("Malformed HTTP Request Packet");
-                                    parsed_packet.set_network_layer_packet(Some(SerializablePacket::MalformedPacket(
-                                        "Malformed HTTP Request Packet".to_string(),
-                                    )));
-                                }
+    let tolerant = get_http_parsing_mode() == HttpParsingMode::Tolerant;
+    let mut headers = [httparse::EMPTY_HEADER; 1024];
+    let mut ended = false;
+
+    match http_type {
+        HttpPacketType::Request => {
+            let mut request = httparse::Request::new(&mut headers);
+            let status = request.parse(&current_payload);
+
+            match status {
+                Ok(status) if status.is_complete() => {
+                    let start = status.unwrap();
+                    let current_payload_size = current_payload.len() - start;
+
+                    if let Some(mut quirks) = message_is_ended(
+                        &current_payload[start..],
+                        current_payload_size,
+                        request.headers,
+                        http_type,
+                        is_fin,
+                        tolerant,
+                    ) {
+                        let parsed_payload = parse_http_payload(
+                            current_payload.clone(),
+                            start,
+                            request.headers,
+                            tolerant,
+                        );
+
+                        match parsed_payload {
+                            Ok((parsed_payload, trailers)) => {
+                                debug!(
+                                    "HTTP Request Packet: {:?} {:?} {:?}; Headers: {:?}; Trailers: {:?}; Payload: {:?}",
+                                    request.method, request.path, request.version, request.headers, trailers, parsed_payload
+                                );
+
+                                let mut request_packet =
+                                    SerializableHttpRequestPacket::new(&request, parsed_payload);
+                                request_packet.trailers = trailers.len();
+                                request_packet.headers.extend(trailers);
+                                request_packet.registry =
+                                    parse_registry_request(&request_packet.path);
+                                quirks.extend(line_quirks(&current_payload));
+                                request_packet.quirks =
+                                    quirks.iter().map(|quirk| quirk.to_string()).collect();
+
+                                parsed_packet.set_application_layer_packet(Some(
+                                    SerializablePacket::HttpRequestPacket(request_packet),
+                                ));
+                            }
+                            Err(e) => {
+                                debug!("Malformed HTTP Request Packet: {}", e);
+                                parsed_packet.set_application_layer_packet(Some(
+                                    SerializablePacket::MalformedPacket(e.to_string()),
+                                ));
                             }
-
-                            parsers.remove(&((source_ip, source_port), (dest_ip, dest_port)));
-
                         }
+
+                        connection.push_request(request.method);
+                        ended = true;
+                    }
+                }
+                Err(_) if tolerant => {
+                    if let Some(path) = simple_request_path(&current_payload) {
+                        debug!("HTTP/0.9 Request Packet: GET {:?}", path);
+
+                        let mut no_headers = [];
+                        let request = httparse::Request {
+                            method: Some("GET"),
+                            path: Some(path),
+                            version: Some(HTTP_09_VERSION),
+                            headers: &mut no_headers,
+                        };
+                        let mut request_packet =
+                            SerializableHttpRequestPacket::new(&request, HttpContentType::None);
+                        request_packet.quirks = vec![Quirks::HTTP_09.to_owned()];
+
+                        parsed_packet.set_application_layer_packet(Some(
+                            SerializablePacket::HttpRequestPacket(request_packet),
+                        ));
+
+                        // The response is a bare body, up to the connection close
+                        connection.simple_responses = true;
+                        ended = true;
                     }
                 }
+                _ => (),
             }
-            HttpPacketType::Response => {
-                let mut response = httparse::Response::new(&mut headers);
-                let status = response.parse(current_payload);
-
-                if let Ok(status) = status {
-                    if status.is_complete() {
-                        let start = status.unwrap();
-                        let current_payload_size = current_payload.len() - start;
-
-                        if packet_is_ended(&current_payload[start..], current_payload_size,
-                            response.headers, http_type, is_fin)
-                        {
-                            let parsed_payload = parse_http_payload(
+        }
+        HttpPacketType::Response => {
+            let is_simple_response =
+                tolerant && !current_payload.starts_with(b"HTTP/") && connection.simple_responses;
+            if is_simple_response {
+                if is_fin {
+                    debug!("HTTP/0.9 Response Packet: {} bytes", current_payload.len());
+
+                    let mut no_headers = [];
+                    let response = httparse::Response {
+                        version: Some(HTTP_09_VERSION),
+                        code: Some(200),
+                        reason: Some(""),
+                        headers: &mut no_headers,
+                    };
+                    let mut response_packet = SerializableHttpResponsePacket::new(
+                        &response,
+                        simple_response_body(current_payload.clone()),
+                    );
+                    response_packet.quirks = vec![Quirks::HTTP_09.to_owned()];
+
+                    parsed_packet.set_application_layer_packet(Some(
+                        SerializablePacket::HttpResponsePacket(response_packet),
+                    ));
+
+                    connection.simple_responses = false;
+                } else {
+                    *connection.buffer_mut(direction) = current_payload;
+                }
+                return;
+            }
+
+            let mut response = httparse::Response::new(&mut headers);
+            let status = response.parse(&current_payload);
+
+            if let Ok(status) = status {
+                if status.is_complete() {
+                    let start = status.unwrap();
+                    let current_payload_size = current_payload.len() - start;
+                    let code = response.code.unwrap_or_default();
+                    let method = connection.pending_methods.front().map(String::as_str);
+                    let bodyless = has_no_body(method, code);
+
+                    let quirks = if bodyless {
+                        Some(vec![])
+                    } else {
+                        message_is_ended(
+                            &current_payload[start..],
+                            current_payload_size,
+                            response.headers,
+                            http_type,
+                            is_fin,
+                            tolerant,
+                        )
+                    };
+                    if let Some(mut quirks) = quirks {
+                        let parsed_payload = if bodyless {
+                            Ok((HttpContentType::None, vec![]))
+                        } else {
+                            parse_http_payload(
                                 current_payload.clone(),
                                 start,
                                 response.headers,
-                            );
-
-                            match parsed_payload {
-                                Ok(parsed_payload) => {
-                                    debug!(
-                                        "HTTP Response Packet: {:?} {:?} {:?}; Headers: {:?}; Payload: {:?}",
-                                        response.version, response.code, response.reason, response.headers, parsed_payload
-                                    );
-
-                                    parsed_packet.set_application_layer_packet(Some(
-                                        SerializablePacket::HttpResponsePacket(
-                                            SerializableHttpResponsePacket::new(&response, parsed_payload),
-                                        ),
-                                    ));
-                                },
-                                Err(_) => {
-                                    debug!("Malformed HTTP Response Packet");
-                                    parsed_packet.set_network_layer_packet(Some(SerializablePacket::MalformedPacket(
-                                        "Malformed HTTP Response Packet".to_string(),
-                                    )));
+                                tolerant,
+                            )
+                        };
+
+                        match parsed_payload {
+                            Ok((parsed_payload, trailers)) => {
+                                debug!(
+                                    "HTTP Response Packet: {:?} {:?} {:?}; Headers: {:?}; Trailers: {:?}; Payload: {:?}",
+                                    response.version, response.code, response.reason, response.headers, trailers, parsed_payload
+                                );
+
+                                let mut response_packet =
+                                    SerializableHttpResponsePacket::new(&response, parsed_payload);
+                                response_packet.trailers = trailers.len();
+                                response_packet.headers.extend(trailers);
+                                if response.reason == Some("") {
+                                    quirks.push(Quirks::MISSING_REASON);
                                 }
+                                quirks.extend(line_quirks(&current_payload));
+                                response_packet.quirks =
+                                    quirks.iter().map(|quirk| quirk.to_string()).collect();
+
+                                parsed_packet.set_application_layer_packet(Some(
+                                    SerializablePacket::HttpResponsePacket(response_packet),
+                                ));
+                            }
+                            Err(e) => {
+                                debug!("Malformed HTTP Response Packet: {}", e);
+                                parsed_packet.set_application_layer_packet(Some(
+                                    SerializablePacket::MalformedPacket(e.to_string()),
+                                ));
                             }
+                        }
 
-                            parsers.remove(&((source_ip, source_port), (dest_ip, dest_port)));
+                        // Interim responses precede the final one, answering the same request
+                        let is_interim = (100..200).contains(&code) && code != 101;
+                        if !is_interim {
+                            connection.tunnel = is_tunnel(method, code);
+                            connection.pending_methods.pop_front();
                         }
+                        ended = true;
                     }
                 }
             }
         }
-    });
+    }
+
+    if !ended {
+        *connection.buffer_mut(direction) = current_payload;
+    }
+}
+
+/// Check if a response has no body whatever its headers, given the method of its request
+fn has_no_body(method: Option<&str>, code: u16) -> bool {
+    (100..200).contains(&code)
+        || code == 204
+        || code == 304
+        || method == Some("HEAD")
+        || is_tunnel(method, code)
+}
+
+/// Check if a response turns its connection into a tunnel, given the method of its request
+fn is_tunnel(method: Option<&str>, code: u16) -> bool {
+    code == 101 || (method == Some("CONNECT") && (200..300).contains(&code))
+}
+
+/// Check if a message is ended, given its body buffered so far, returning the quirks of its framing
+///
+/// Besides the framing checked by [`packet_is_ended`], the tolerant mode ends the chunked bodies
+/// whose last chunk is followed by LF only, and the bodies cut short by the connection close.
+fn message_is_ended(
+    payload: &[u8],
+    current_payload_size: usize,
+    headers: &mut [Header],
+    http_type: HttpPacketType,
+    is_fin_set: bool,
+    tolerant: bool,
+) -> Option<Vec<&'static str>> {
+    let chunked = get_header_value(HeaderNamesValues::TRANSFER_ENCODING, headers)
+        == Some(HeaderNamesValues::CHUNKED);
+
+    if packet_is_ended(
+        payload,
+        current_payload_size,
+        headers,
+        http_type,
+        is_fin_set,
+    ) {
+        Some(vec![])
+    } else if !tolerant {
+        None
+    } else if chunked && (payload == b"0\n\n" || payload.ends_with(b"\n0\n\n")) {
+        Some(vec![])
+    } else if is_fin_set {
+        Some(vec![Quirks::CLOSE_DELIMITED])
+    } else {
+        None
+    }
+}
+
+/// Get the quirks of the lines of a message: its first line ended by LF only
+fn line_quirks(payload: &[u8]) -> Vec<&'static str> {
+    match payload.iter().position(|byte| *byte == b'\n') {
+        Some(end) if end == 0 || payload[end - 1] != b'\r' => vec![Quirks::LF_LINE_ENDINGS],
+        _ => vec![],
+    }
+}
+
+/// Get the path of an HTTP/0.9 simple request (`GET /path`), made of its request line only
+fn simple_request_path(payload: &[u8]) -> Option<&str> {
+    let end = payload.iter().position(|byte| *byte == b'\n')?;
+    let line = std::str::from_utf8(&payload[..end]).ok()?;
+    let line = line.strip_suffix('\r').unwrap_or(line);
+
+    match line.split(' ').collect::<Vec<_>>()[..] {
+        ["GET", path] if path.starts_with('/') => Some(path),
+        _ => None,
+    }
+}
+
+/// Decode the body of an HTTP/0.9 response: HTML documents were the only ones served
+fn simple_response_body(payload: Vec<u8>) -> HttpContentType {
+    if payload.is_empty() {
+        return HttpContentType::None;
+    }
+
+    decode_body(payload, Some(mime::TEXT_HTML.as_ref()), None).unwrap_or(HttpContentType::None)
 }
 
 // We can say thay an HTTP Request is ended when one the following is true:
@@ -168,9 +497,13 @@ fn packet_is_ended(
         };
     }
 
-    // If Content-Length is equal
-    if length.is_some() && current_payload_size == length.unwrap().parse::<usize>().unwrap() {
-        return true;
+    // If Content-Length is equal, or not a number (the message is reported as malformed)
+    if let Some(length) = length {
+        match length.trim().parse::<usize>() {
+            Ok(length) if current_payload_size == length => return true,
+            Err(_) => return true,
+            _ => (),
+        }
     }
 
     // If Transfer-Encoding is chuncked and last chunck arrived
@@ -189,9 +522,7 @@ fn packet_is_ended(
             i += 1;
         }
 
-        println!("{} {}", i, last_bytes.len());
-
-        if i == last_bytes.len() {
+        if i == last_bytes.len() || trailers_are_ended(payload) {
             return true;
         }
     }
@@ -199,119 +530,261 @@ fn packet_is_ended(
     false
 }
 
+/// Check if a chunked body is ended by its last chunk followed by trailer fields
+fn trailers_are_ended(payload: &[u8]) -> bool {
+    if !payload.ends_with(b"\r\n\r\n") {
+        return false;
+    }
+
+    let last_chunk = payload
+        .windows(5)
+        .rposition(|window| window == b"\r\n0\r\n")
+        .map(|position| position + 2)
+        .or_else(|| payload.starts_with(b"0\r\n").then_some(0));
+    let trailers = match last_chunk {
+        Some(last_chunk) => &payload[last_chunk + 3..],
+        None => return false,
+    };
+
+    let mut headers = [httparse::EMPTY_HEADER; MAX_TRAILERS];
+    matches!(
+        httparse::parse_headers(trailers, &mut headers),
+        Ok(httparse::Status::Complete((length, _))) if length == trailers.len()
+    )
+}
+
+/// Parse the body of a message, obtaining its content and the trailer fields following it
 fn parse_http_payload(
     payload_with_headers: Vec<u8>,
     start: usize,
     headers: &mut [Header],
-) -> Result<HttpContentType> {
-    let mut payload = payload_with_headers[start..].to_vec();
+    tolerant: bool,
+) -> Result<(HttpContentType, Fields)> {
+    if let Some(length) = get_header_value(HeaderNamesValues::CONTENT_LENGTH, headers) {
+        if length.trim().parse::<usize>().is_err() {
+            return Err(HttpParsingError::ContentLengthMalformed(format!(
+                "Malformed HTTP Packet: Content-Length not a valid number ({})",
+                length
+            )));
+        }
+    }
+
+    let payload = payload_with_headers[start..].to_vec();
     if payload.is_empty() {
-        return Ok(HttpContentType::None);
+        return Ok((HttpContentType::None, vec![]));
     }
 
     let transfer_encoding = get_header_value(HeaderNamesValues::TRANSFER_ENCODING, headers);
-    if transfer_encoding.is_some() && transfer_encoding.unwrap() == HeaderNamesValues::CHUNKED {
-        payload = merge_chunks(payload)?;
-    }
+    let (payload, trailers) = if transfer_encoding.is_some()
+        && transfer_encoding.unwrap() == HeaderNamesValues::CHUNKED
+    {
+        merge_chunks(payload, tolerant)?
+    } else {
+        (payload, vec![])
+    };
 
     let mime = get_header_value(HeaderNamesValues::CONTENT_TYPE, headers);
-    if mime.is_none() {
-        return Ok(HttpContentType::Unknown(payload));
-    }
-    let mime = mime.unwrap().parse::<Mime>();
-    if mime.is_err() {
-        return Ok(HttpContentType::Unknown(payload));
-    }
-
-    let mime = mime.unwrap();
     let encoding = get_header_value(HeaderNamesValues::CONTENT_ENCODING, headers);
 
-    return match encoding {
-        Some(encoding) => {
-            let result = decode_payload(&mut payload, encoding);
-            return match result {
-                Ok(decoded_payload) => Ok(get_http_type(mime, decoded_payload.to_vec(), None)),
-                Err(algo) => match algo {
-                    HttpParsingError::DecodingPayloadFailed(algo, _) => {
-                        Ok(get_http_type(mime, payload.to_vec(), Some(&algo)))
-                    }
-                    HttpParsingError::UnknownDecodingAlgorithm(algo, _) => {
-                        Ok(get_http_type(mime, payload.to_vec(), Some(&algo)))
-                    }
-                    _ => Err(HttpParsingError::Other),
-                },
-            };
-        }
-        None => Ok(get_http_type(mime, payload.to_vec(), None)),
+    let content = decode_body(payload, mime, encoding).ok_or(HttpParsingError::Other)?;
+    Ok((content, trailers))
+}
+
+/// Decode a body by the content type and encoding given in the headers of its message
+///
+/// As browsers do, the type of a body declared without a content type, or as a generic
+/// `application/octet-stream`, is sniffed from its first bytes.
+pub(super) fn decode_body(
+    mut payload: Vec<u8>,
+    content_type: Option<&str>,
+    encoding: Option<&str>,
+) -> Option<HttpContentType> {
+    let declared = content_type.and_then(|content_type| content_type.parse::<Mime>().ok());
+
+    let decoded = match encoding {
+        Some(encoding) => match decode_payload(&mut payload, encoding) {
+            Ok(decoded_payload) => decoded_payload,
+            Err(HttpParsingError::DecodingPayloadFailed(algo, _))
+            | Err(HttpParsingError::UnknownDecodingAlgorithm(algo, _)) => {
+                return Some(match declared {
+                    Some(mime) => get_http_type(mime, payload, Some(&algo)),
+                    None => HttpContentType::Unknown(payload),
+                });
+            }
+            Err(_) => return None,
+        },
+        None => payload,
     };
+
+    let mime = match declared {
+        Some(mime) if mime.essence_str() != mime::APPLICATION_OCTET_STREAM.essence_str() => mime,
+        declared => match sniff_content_type(&decoded).or(declared) {
+            Some(mime) => mime,
+            None => return Some(HttpContentType::Unknown(decoded)),
+        },
+    };
+
+    Some(get_http_type(mime, decoded, None))
 }
 
-fn merge_chunks(payload: Vec<u8>) -> Result<Vec<u8>> {
+/// Merge the chunks of a body sent with the chunked transfer encoding, obtaining the body and the
+/// trailer fields following its last chunk
+///
+/// Every length and boundary is checked against the payload, so that a malformed or hostile body
+/// is reported instead of being read out of bounds. Chunk extensions are not supported.
+/// Lines ended by LF only are accepted when parsing tolerantly.
+fn merge_chunks(payload: Vec<u8>, tolerant: bool) -> Result<(Vec<u8>, Fields)> {
+    let malformed = |reason: String| {
+        HttpParsingError::TransferEncodingMalformed(format!(
+            "Malformed Transfer-Encoding HTTP Packet: {}",
+            reason
+        ))
+    };
+    // Length of the line ending at the given index, if any
+    let line_ending = |index: usize| match payload.get(index..) {
+        Some([b'\r', b'\n', ..]) => Some(2),
+        Some([b'\n', ..]) if tolerant => Some(1),
+        _ => None,
+    };
+
     let mut merged = vec![];
     let mut index = 0;
-    let mut length = "".to_owned();
 
     loop {
-        length.clear();
-        loop {
-            if payload[index] == b"\r"[0] && payload[index + 1] == b"\n"[0] {
-                break;
-            }
+        // Length of the chunk in hexadecimal, up to the CRLF
+        let line_end = if tolerant {
+            payload[index..]
+                .iter()
+                .position(|byte| *byte == b'\n')
+                .map(|position| match index + position {
+                    end if end > index && payload[end - 1] == b'\r' => end - 1,
+                    end => end,
+                })
+        } else {
+            payload[index..]
+                .windows(2)
+                .position(|window| window == b"\r\n")
+                .map(|position| index + position)
+        }
+        .ok_or_else(|| malformed("chunk's length not terminated".to_owned()))?;
+        let digits = &payload[index..line_end];
+
+        if let Some(digit) = digits.iter().find(|digit| !digit.is_ascii_hexdigit()) {
+            return Err(malformed(format!(
+                "chunk's length not valid Hexadecimal character (\\x{})",
+                digit
+            )));
+        }
 
-            if !payload[index].is_ascii_hexdigit() {
-                return Err(HttpParsingError::TransferEncodingMalformed(
-                    format!(
-                        "Malformed Transfer-Encoding HTTP Packet: chunk's length not valid Hexadecimal character (\\x{})",
-                        payload[index]
-                    ),
-                ));
+        let length = std::str::from_utf8(digits)
+            .ok()
+            .and_then(|digits| usize::from_str_radix(digits, 16).ok())
+            .ok_or_else(|| malformed("chunk's length missing or too large".to_owned()))?;
+
+        // Skip \r\n
+        index = line_end + line_ending(line_end).unwrap();
+
+        // The last chunk is empty, and followed by the trailer fields and the CRLF ending the body
+        if length == 0 {
+            if line_ending(index).is_some() {
+                break;
             }
 
-            length.push(char::from_u32(payload[index] as u32).unwrap());
-            index += 1;
+            let mut headers = [httparse::EMPTY_HEADER; MAX_TRAILERS];
+            return match httparse::parse_headers(&payload[index..], &mut headers) {
+                Ok(httparse::Status::Complete((_, trailers))) => {
+                    let trailers = trailers
+                        .iter()
+                        .map(|trailer| {
+                            (
+                                trailer.name.to_owned(),
+                                std::str::from_utf8(trailer.value)
+                                    .unwrap_or("Not valid UTF8")
+                                    .to_owned(),
+                            )
+                        })
+                        .collect();
+                    Ok((merged, trailers))
+                }
+                Ok(httparse::Status::Partial) => {
+                    Err(malformed("last chunk is too small".to_owned()))
+                }
+                Err(e) => Err(malformed(format!("trailer fields not valid ({})", e))),
+            };
         }
 
-        let length = usize::from_str_radix(&length, 16).unwrap();
-        println!("Length: {}", length);
+        let chunk = index
+            .checked_add(length)
+            .and_then(|end| payload.get(index..end))
+            .ok_or_else(|| malformed(format!("chunk of {} bytes is truncated", length)))?;
+        merged.extend_from_slice(chunk);
+        index += length;
 
         // Skip \r\n
-        index += 2;
+        index += line_ending(index)
+            .ok_or_else(|| malformed("chunk not terminated by CRLF".to_owned()))?;
 
-        for _ in 0..length {
-            merged.push(payload[index]);
-            index += 1;
+        if index >= payload.len() {
+            return Err(malformed("last chunk is too small".to_owned()));
         }
+    }
 
-        // Skip \r\n
-        index += 2;
-
-        // If last chunk
-        let is_last_chunk = std::panic::catch_unwind(|| {
-            if payload[index] == b"0"[0]
-                && payload[index + 1] == b"\r"[0]
-                && payload[index + 2] == b"\n"[0]
-                && payload[index + 3] == b"\r"[0]
-                && payload[index + 4] == b"\n"[0]
-            {
-                true
-            } else {
-                false
-            }
-        })
-        .map_err(|_| {
-            HttpParsingError::TransferEncodingMalformed(
-                "Malformed Transfer-Encoding HTTP Packet: last chunk is too small".to_owned(),
-            )
+    Ok((merged, vec![]))
+}
+
+/// Identify a request to a Docker registry (distribution v2 API) from its path
+///
+/// Image names may contain slashes, so the endpoint is located by its last path component.
+pub fn parse_registry_request(path: &str) -> Option<DockerRegistryRequest> {
+    let path = path.split(['?', '#']).next()?;
+    if path == "/v2" || path == "/v2/" {
+        return Some(DockerRegistryRequest {
+            endpoint: DockerRegistryEndpoint::Base,
+            image: None,
+            reference: None,
         });
+    }
 
-        match is_last_chunk {
-            Ok(true) => break,
-            Err(err) => return Err(err),
-            _ => (),
-        }
+    let path = path.strip_prefix("/v2/")?;
+    if path == "_catalog" {
+        return Some(DockerRegistryRequest {
+            endpoint: DockerRegistryEndpoint::Catalog,
+            image: None,
+            reference: None,
+        });
+    }
+
+    let (image, endpoint, reference) = if let Some(image) = path.strip_suffix("/tags/list") {
+        (image, DockerRegistryEndpoint::Tags, "")
+    } else {
+        [
+            ("/manifests/", DockerRegistryEndpoint::Manifest),
+            ("/blobs/uploads", DockerRegistryEndpoint::BlobUpload),
+            ("/blobs/", DockerRegistryEndpoint::Blob),
+            ("/referrers/", DockerRegistryEndpoint::Referrers),
+        ]
+        .into_iter()
+        .find_map(|(marker, endpoint)| {
+            let index = path.rfind(marker)?;
+            Some((&path[..index], endpoint, &path[index + marker.len()..]))
+        })?
+    };
+
+    // Repository names are made of lowercase alphanumeric components and separators
+    let valid_image = !image.is_empty()
+        && image
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || "._-/".contains(c));
+    if !valid_image {
+        return None;
     }
 
-    Ok(merged)
+    let reference = reference.trim_matches('/');
+    Some(DockerRegistryRequest {
+        endpoint,
+        image: Some(image.to_owned()),
+        reference: (!reference.is_empty()).then(|| reference.to_owned()),
+    })
 }
 
 fn get_header_value<'a, 'b>(name: &'a str, headers: &'b [Header]) -> Option<&'b str> {
@@ -328,7 +801,7 @@ fn get_http_type(mime: Mime, payload: Vec<u8>, encoding: Option<&str>) -> HttpCo
         (_, _) if encoding.is_some() => {
             HttpContentType::Encoded(encoding.unwrap().to_string(), payload)
         }
-        (mime::TEXT, _) => {
+        (mime::TEXT, _) | (mime::APPLICATION, mime::JSON) => {
             let charset = mime.get_param(mime::CHARSET);
 
             if let Some(charset) = charset {
@@ -354,6 +827,83 @@ fn get_http_type(mime: Mime, payload: Vec<u8>, encoding: Option<&str>) -> HttpCo
     };
 }
 
+/// Signatures of the image formats displayed by browsers, sniffed at the start of a body
+const IMAGE_SIGNATURES: &[(&[u8], &str)] = &[
+    (b"GIF87a", "image/gif"),
+    (b"GIF89a", "image/gif"),
+    (b"\x89PNG\r\n\x1a\n", "image/png"),
+    (b"\xff\xd8\xff", "image/jpeg"),
+    (b"BM", "image/bmp"),
+    (b"\x00\x00\x01\x00", "image/x-icon"),
+];
+
+/// Tags starting an HTML document, matched case-insensitively (WHATWG MIME Sniffing)
+const HTML_TAGS: &[&str] = &[
+    "<!DOCTYPE HTML",
+    "<HTML",
+    "<HEAD",
+    "<SCRIPT",
+    "<IFRAME",
+    "<H1",
+    "<DIV",
+    "<FONT",
+    "<TABLE",
+    "<A",
+    "<STYLE",
+    "<TITLE",
+    "<B",
+    "<BODY",
+    "<BR",
+    "<P",
+    "<!--",
+];
+
+/// Sniff the content type of a body from its bytes, as browsers do when it is not declared
+///
+/// Images are told by their signature, HTML documents by their first tag and JSON documents by
+/// being valid JSON. Other bodies without binary bytes are plain text.
+fn sniff_content_type(payload: &[u8]) -> Option<Mime> {
+    if let Some((_, image)) = IMAGE_SIGNATURES
+        .iter()
+        .find(|(signature, _)| payload.starts_with(signature))
+    {
+        return image.parse().ok();
+    }
+    // WebP: a RIFF container of the WEBP form
+    if payload.len() >= 14 && payload.starts_with(b"RIFF") && &payload[8..14] == b"WEBPVP" {
+        return "image/webp".parse().ok();
+    }
+
+    let start = payload
+        .iter()
+        .position(|byte| !matches!(byte, b'\t' | b'\n' | b'\x0c' | b'\r' | b' '))?;
+    let document = &payload[start..];
+
+    let is_html = HTML_TAGS.iter().any(|tag| {
+        document.len() > tag.len()
+            && document[..tag.len()].eq_ignore_ascii_case(tag.as_bytes())
+            // The tag must be terminated, e.g. <B but not <BUTTON
+            && matches!(document[tag.len()], b' ' | b'>')
+    });
+    if is_html {
+        return Some(mime::TEXT_HTML);
+    }
+    if document.starts_with(b"<?xml") {
+        return Some(mime::TEXT_XML);
+    }
+    if matches!(document[0], b'{' | b'[')
+        && serde_json::from_slice::<serde::de::IgnoredAny>(document).is_ok()
+    {
+        return Some(mime::APPLICATION_JSON);
+    }
+
+    // Binary data bytes (WHATWG MIME Sniffing), never found in text
+    let is_binary = payload
+        .iter()
+        .any(|byte| matches!(byte, 0x00..=0x08 | 0x0b | 0x0e..=0x1a | 0x1c..=0x1f));
+    (!is_binary).then_some(mime::TEXT_PLAIN)
+}
+
 fn decode_payload<'a>(payload: &mut Vec<u8>, encoding: &'a str) -> Result<Vec<u8>> {
     let mut extensions = encoding.split(", ").collect::<Vec<&str>>();
     extensions.reverse();
@@ -433,6 +983,9 @@ fn decode_payload<'a>(payload: &mut Vec<u8>, encoding: &'a str) -> Result<Vec<u8
 // - X Error Transfer-Encoding chunked
 //   - X Last chunk not formatted correctly
 //   - X Length not in hexadecimal
+//   - X Chunk truncated
+//   - X Length too large
+// - X Error Content-Length not a number
 
 #[cfg(test)]
 mod tests {
@@ -440,14 +993,18 @@ mod tests {
     use std::net::{IpAddr, Ipv4Addr};
 
     use super::{
-        decode_payload, get_http_type, handle_http_packet, merge_chunks, packet_is_ended,
-        HttpParsingError,
+        decode_body, decode_payload, get_http_type, handle_http_packet, merge_chunks,
+        packet_is_ended, parse_registry_request, sniff_content_type, HttpParsingError,
+        HTTP_09_VERSION,
     };
     use crate::{
         application::{HeaderNamesValues, WellKnownPorts},
         http::get_header_value,
-        serializable_packet::{application::HttpContentType, ParsedPacket, SerializablePacket},
-        HttpPacketType,
+        serializable_packet::{
+            application::{DockerRegistryEndpoint, HttpContentType},
+            ParsedPacket, SerializablePacket,
+        },
+        HttpPacketType, ParserContext,
     };
 
     const BASIC_REQUEST: &[u8] = b"GET / HTTP/1.1\r\n\r\n";
@@ -457,6 +1014,9 @@ mod tests {
         b"HTTP/1.1 200 OK\r\nContent-Length: 4\r\n\r\nmiao\r\n\r\n";
     const CONTENT_LENGTH_ENDED_LENGTH: usize = 4;
 
+    const CONTENT_LENGTH_NOT_NUMBER_RESPONSE: &[u8] =
+        b"HTTP/1.1 200 OK\r\nContent-Length: four\r\n\r\nmiao";
+
     const CHUNKED_RESPONSE: &[u8] = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n\
     4\r\nmiao\r\n0\r\n\r\n";
     const CHUNKED_RESPONSE_LENGTH: usize = 14;
@@ -489,8 +1049,11 @@ mod tests {
 
     #[test]
     fn incomplete_header_http_packet() {
+        let mut state = ParserContext::new();
+
         let mut parsed_packet = ParsedPacket::new(0);
         handle_http_packet(
+            &mut state,
             IpAddr::V4(Ipv4Addr::new(10, 10, 10, 10)),
             4444,
             IpAddr::V4(Ipv4Addr::new(11, 11, 11, 11)),
@@ -506,8 +1069,11 @@ mod tests {
 
     #[test]
     fn complete_header_http_packet() {
+        let mut state = ParserContext::new();
+
         let mut parsed_packet = ParsedPacket::new(0);
         handle_http_packet(
+            &mut state,
             IpAddr::V4(Ipv4Addr::new(10, 10, 10, 10)),
             4444,
             IpAddr::V4(Ipv4Addr::new(11, 11, 11, 11)),
@@ -522,6 +1088,7 @@ mod tests {
 
         let mut parsed_packet = ParsedPacket::new(0);
         handle_http_packet(
+            &mut state,
             IpAddr::V4(Ipv4Addr::new(10, 10, 10, 10)),
             4444,
             IpAddr::V4(Ipv4Addr::new(11, 11, 11, 11)),
@@ -567,8 +1134,11 @@ mod tests {
 
     #[test]
     fn valid_http_request_with_no_length_indication() {
+        let mut state = ParserContext::new();
+
         let mut parsed_packet = ParsedPacket::new(0);
         handle_http_packet(
+            &mut state,
             IpAddr::V4(Ipv4Addr::new(10, 10, 10, 10)),
             4444,
             IpAddr::V4(Ipv4Addr::new(11, 11, 11, 11)),
@@ -614,8 +1184,11 @@ mod tests {
 
     #[test]
     fn valid_http_response_with_no_length_indication_and_no_fin_set() {
+        let mut state = ParserContext::new();
+
         let mut parsed_packet = ParsedPacket::new(0);
         handle_http_packet(
+            &mut state,
             IpAddr::V4(Ipv4Addr::new(10, 10, 10, 10)),
             WellKnownPorts::HTTP_PORT,
             IpAddr::V4(Ipv4Addr::new(11, 11, 11, 11)),
@@ -631,8 +1204,11 @@ mod tests {
 
     #[test]
     fn valid_http_response_with_no_length_indication_and_fin_set() {
+        let mut state = ParserContext::new();
+
         let mut parsed_packet = ParsedPacket::new(0);
         handle_http_packet(
+            &mut state,
             IpAddr::V4(Ipv4Addr::new(10, 10, 10, 10)),
             WellKnownPorts::HTTP_PORT,
             IpAddr::V4(Ipv4Addr::new(11, 11, 11, 11)),
@@ -804,6 +1380,71 @@ mod tests {
         }
     }
 
+    // Content sniffing
+    #[test]
+    fn sniffed_content_types() {
+        let sniffed = |payload: &[u8]| sniff_content_type(payload).map(|mime| mime.to_string());
+
+        assert_eq!(sniffed(b"GIF89a\x01\x00"), Some("image/gif".to_owned()));
+        assert_eq!(
+            sniffed(b"\x89PNG\r\n\x1a\n\x00\x00\x00\x0dIHDR"),
+            Some("image/png".to_owned())
+        );
+        assert_eq!(
+            sniffed(b"RIFF\x24\x00\x00\x00WEBPVP8 "),
+            Some("image/webp".to_owned())
+        );
+        assert_eq!(
+            sniffed(b"\r\n  <!doctype html><html></html>"),
+            Some("text/html".to_owned())
+        );
+        assert_eq!(sniffed(b"<p>miao</p>"), Some("text/html".to_owned()));
+        assert_eq!(
+            sniffed(br#" {"miao": [1, 2]}"#),
+            Some("application/json".to_owned())
+        );
+        assert_eq!(sniffed(b"miao bau"), Some("text/plain".to_owned()));
+
+        // <BUTTON is not a tag starting a document, unterminated JSON is plain text
+        assert_eq!(sniffed(b"<button>"), Some("text/plain".to_owned()));
+        assert_eq!(sniffed(br#"{"miao": "#), Some("text/plain".to_owned()));
+        assert_eq!(sniffed(b"\x00\x01\x02miao"), None);
+        assert_eq!(sniffed(b" \r\n"), None);
+    }
+
+    #[test]
+    fn missing_or_generic_content_type() {
+        let html = b"<html><body>miao</body></html>".to_vec();
+        let json = br#"{"miao": "bau"}"#.to_vec();
+
+        match decode_body(html.clone(), None, None) {
+            Some(HttpContentType::TextDefaultDecoded(decoded)) => {
+                assert_eq!(decoded.as_bytes(), html)
+            }
+            _ => unreachable!(),
+        }
+        match decode_body(json.clone(), Some("application/octet-stream"), None) {
+            Some(HttpContentType::TextDefaultDecoded(decoded)) => {
+                assert_eq!(decoded.as_bytes(), json)
+            }
+            _ => unreachable!(),
+        }
+        assert!(matches!(
+            decode_body(b"GIF87a".to_vec(), Some("miao"), None),
+            Some(HttpContentType::Image(_))
+        ));
+
+        // Declared types are trusted
+        assert!(matches!(
+            decode_body(html, Some("image/png"), None),
+            Some(HttpContentType::Image(_))
+        ));
+        assert!(matches!(
+            decode_body(b"\x00\x01".to_vec(), Some("application/octet-stream"), None),
+            Some(HttpContentType::Unknown(_))
+        ));
+    }
+
     #[test]
     fn transfer_encoding_chunked_last_chunk_formatted_wrongly() {
         println!(
@@ -816,6 +1457,7 @@ mod tests {
                 - CHUNKED_LAST_CHUNK_NOT_ENDED_RESPONSE_LENGTH..]
                 .as_bytes()
                 .to_vec(),
+            false,
         );
 
         match result {
@@ -836,6 +1478,7 @@ mod tests {
                 [CHUNKED_NOT_HEXA_RESPONSE.len() - CHUNKED_NOT_HEXA_RESPONSE_LENGTH..]
                 .as_bytes()
                 .to_vec(),
+            false,
         );
 
         match result {
@@ -846,6 +1489,126 @@ mod tests {
         }
     }
 
+    #[test]
+    fn transfer_encoding_chunked_merged() {
+        let (result, trailers) =
+            merge_chunks(b"4\r\nmiao\r\n5\r\n bau!\r\n0\r\n\r\n".to_vec(), false).unwrap();
+
+        assert_eq!(result, b"miao bau!");
+        assert!(trailers.is_empty());
+    }
+
+    #[test]
+    fn transfer_encoding_chunked_trailers() {
+        let mut state = ParserContext::new();
+
+        let body = b"4\r\nmiao\r\n0\r\nServer-Timing: db;dur=53\r\nETag: \"miao\"\r\n\r\n";
+        let (result, trailers) = merge_chunks(body.to_vec(), false).unwrap();
+        assert_eq!(result, b"miao");
+        assert_eq!(
+            trailers,
+            vec![
+                ("Server-Timing".to_owned(), "db;dur=53".to_owned()),
+                ("ETag".to_owned(), "\"miao\"".to_owned())
+            ]
+        );
+        assert!(matches!(
+            merge_chunks(b"4\r\nmiao\r\n0\r\nETag\r\n\r\n".to_vec(), false),
+            Err(HttpParsingError::TransferEncodingMalformed(_))
+        ));
+
+        // The trailers end the message, and follow its header fields
+        let mut handle = |is_fin: bool, payload: &[u8]| {
+            let mut parsed_packet = ParsedPacket::new(0);
+            handle_http_packet(
+                &mut state,
+                IpAddr::V4(Ipv4Addr::new(11, 11, 11, 11)),
+                WellKnownPorts::HTTP_PORT,
+                IpAddr::V4(Ipv4Addr::new(10, 10, 10, 10)),
+                4447,
+                HttpPacketType::Response,
+                is_fin,
+                payload,
+                &mut parsed_packet,
+            );
+            match parsed_packet.get_application_layer_packet() {
+                Some(SerializablePacket::HttpResponsePacket(response)) => Some(response.clone()),
+                _ => None,
+            }
+        };
+        let mut payload =
+            b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\nTrailer: ETag\r\n\r\n".to_vec();
+        payload.extend(b"4\r\nmiao\r\n0\r\nETag: \"miao\"\r\n");
+        assert!(handle(false, &payload).is_none());
+        let response = handle(false, b"\r\n").unwrap();
+        assert_eq!(response.headers.len(), 3);
+        assert_eq!(
+            response.headers[2],
+            ("ETag".to_owned(), "\"miao\"".to_owned())
+        );
+        assert_eq!(response.trailers, 1);
+        assert!(
+            matches!(response.payload, HttpContentType::TextDefaultDecoded(text) if text == "miao")
+        );
+    }
+
+    #[test]
+    fn transfer_encoding_chunked_chunk_truncated() {
+        let result = merge_chunks(b"a\r\nmiao\r\n".to_vec(), false);
+
+        match result {
+            Err(HttpParsingError::TransferEncodingMalformed(str)) => {
+                assert_eq!(
+                    str,
+                    "Malformed Transfer-Encoding HTTP Packet: chunk of 10 bytes is truncated"
+                );
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn transfer_encoding_chunked_length_too_large() {
+        for payload in [
+            b"ffffffffffffffffffff\r\nmiao\r\n0\r\n\r\n".to_vec(),
+            b"ffffffffffffffff\r\nmiao\r\n0\r\n\r\n".to_vec(),
+            b"\r\nmiao\r\n0\r\n\r\n".to_vec(),
+            b"4".to_vec(),
+        ] {
+            assert!(matches!(
+                merge_chunks(payload, false),
+                Err(HttpParsingError::TransferEncodingMalformed(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn content_length_not_a_number() {
+        let mut state = ParserContext::new();
+
+        let mut parsed_packet = ParsedPacket::new(0);
+        handle_http_packet(
+            &mut state,
+            IpAddr::V4(Ipv4Addr::new(10, 10, 10, 10)),
+            WellKnownPorts::HTTP_PORT,
+            IpAddr::V4(Ipv4Addr::new(11, 11, 11, 11)),
+            4444,
+            HttpPacketType::Response,
+            false,
+            CONTENT_LENGTH_NOT_NUMBER_RESPONSE,
+            &mut parsed_packet,
+        );
+
+        match parsed_packet.get_application_layer_packet() {
+            Some(SerializablePacket::MalformedPacket(reason)) => assert_eq!(
+                reason,
+                "Malformed HTTP Packet: Content-Length not a valid number (four)"
+            ),
+            _ => unreachable!(),
+        }
+        assert!(parsed_packet.get_network_layer_packet().is_none());
+    }
+
     // Encoding
 
     #[test]
@@ -912,4 +1675,251 @@ mod tests {
             _ => unreachable!(),
         }
     }
+
+    #[test]
+    fn http_09_exchange() {
+        let mut state = ParserContext::new();
+
+        let client = (IpAddr::V4(Ipv4Addr::new(10, 10, 10, 10)), 4445);
+        let server = (
+            IpAddr::V4(Ipv4Addr::new(11, 11, 11, 11)),
+            WellKnownPorts::HTTP_PORT,
+        );
+        let mut handle = |(source, destination): ((IpAddr, u16), (IpAddr, u16)),
+                          http_type: HttpPacketType,
+                          is_fin: bool,
+                          payload: &[u8]| {
+            let mut parsed_packet = ParsedPacket::new(0);
+            handle_http_packet(
+                &mut state,
+                source.0,
+                source.1,
+                destination.0,
+                destination.1,
+                http_type,
+                is_fin,
+                payload,
+                &mut parsed_packet,
+            );
+            parsed_packet.get_application_layer_packet().cloned()
+        };
+
+        match handle(
+            (client, server),
+            HttpPacketType::Request,
+            false,
+            b"GET /index.html\r\n",
+        ) {
+            Some(SerializablePacket::HttpRequestPacket(request)) => {
+                assert_eq!(request.path, "/index.html");
+                assert_eq!(request.version, HTTP_09_VERSION);
+                assert_eq!(request.quirks, vec!["HTTP/0.9".to_owned()]);
+            }
+            _ => unreachable!(),
+        }
+
+        // The body is only ended by the connection close
+        let body = b"<html>miao</html>";
+        assert!(handle((server, client), HttpPacketType::Response, false, body).is_none());
+        match handle((server, client), HttpPacketType::Response, true, b"") {
+            Some(SerializablePacket::HttpResponsePacket(response)) => {
+                assert_eq!(response.version, HTTP_09_VERSION);
+                assert_eq!(response.code, 200);
+                assert!(response.headers.is_empty());
+                assert!(matches!(
+                    response.payload,
+                    HttpContentType::TextDefaultDecoded(text) if text == "<html>miao</html>"
+                ));
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn responses_framed_by_requests() {
+        let mut state = ParserContext::new();
+
+        let client = (IpAddr::V4(Ipv4Addr::new(10, 10, 10, 10)), 4446);
+        let server = (
+            IpAddr::V4(Ipv4Addr::new(11, 11, 11, 11)),
+            WellKnownPorts::HTTP_PORT,
+        );
+        let handle = |state: &mut ParserContext,
+                      (source, destination): ((IpAddr, u16), (IpAddr, u16)),
+                      http_type: HttpPacketType,
+                      payload: &[u8]| {
+            let mut parsed_packet = ParsedPacket::new(0);
+            handle_http_packet(
+                state,
+                source.0,
+                source.1,
+                destination.0,
+                destination.1,
+                http_type,
+                false,
+                payload,
+                &mut parsed_packet,
+            );
+            parsed_packet.get_application_layer_packet().cloned()
+        };
+        let response = |state: &mut ParserContext, payload: &[u8]| match handle(
+            state,
+            (server, client),
+            HttpPacketType::Response,
+            payload,
+        ) {
+            Some(SerializablePacket::HttpResponsePacket(response)) => Some(response),
+            _ => None,
+        };
+
+        let ok = b"HTTP/1.1 200 OK\r\nContent-Length: 4\r\n\r\nmiao";
+
+        // The response to a HEAD request has no body, whatever its Content-Length
+        let head = b"HEAD / HTTP/1.1\r\n\r\n";
+        assert!(handle(&mut state, (client, server), HttpPacketType::Request, head).is_some());
+        let head = response(&mut state, &ok[..ok.len() - 4]).unwrap();
+        assert!(matches!(head.payload, HttpContentType::None));
+
+        // Interim responses precede the final one, answering the same request
+        let post = b"POST / HTTP/1.1\r\nContent-Length: 4\r\nExpect: 100-continue\r\n\r\nmiao";
+        assert!(handle(&mut state, (client, server), HttpPacketType::Request, post).is_some());
+        assert_eq!(
+            response(&mut state, b"HTTP/1.1 100 Continue\r\n\r\n")
+                .unwrap()
+                .code,
+            100
+        );
+        assert!(response(&mut state, b"HTTP/1.1 204 No Content\r\n\r\n").is_some());
+        assert!(matches!(
+            response(&mut state, ok).unwrap().payload,
+            HttpContentType::TextDefaultDecoded(text) if text == "miao"
+        ));
+
+        // The bytes following a successful CONNECT are tunneled, not parsed
+        let connect = b"CONNECT example.com:443 HTTP/1.1\r\nHost: example.com:443\r\n\r\n";
+        assert!(handle(
+            &mut state,
+            (client, server),
+            HttpPacketType::Request,
+            connect
+        )
+        .is_some());
+        let established =
+            response(&mut state, b"HTTP/1.1 200 Connection Established\r\n\r\n").unwrap();
+        assert!(matches!(established.payload, HttpContentType::None));
+        assert!(handle(
+            &mut state,
+            (client, server),
+            HttpPacketType::Request,
+            BASIC_REQUEST
+        )
+        .is_none());
+        assert!(response(&mut state, ok).is_none());
+    }
+
+    #[test]
+    fn sloppy_responses() {
+        let mut state = ParserContext::new();
+
+        let mut handle = |client_port: u16, is_fin: bool, payload: &[u8]| {
+            let mut parsed_packet = ParsedPacket::new(0);
+            handle_http_packet(
+                &mut state,
+                IpAddr::V4(Ipv4Addr::new(11, 11, 11, 11)),
+                WellKnownPorts::HTTP_PORT,
+                IpAddr::V4(Ipv4Addr::new(10, 10, 10, 10)),
+                client_port,
+                HttpPacketType::Response,
+                is_fin,
+                payload,
+                &mut parsed_packet,
+            );
+            match parsed_packet.get_application_layer_packet() {
+                Some(SerializablePacket::HttpResponsePacket(response)) => Some(response.clone()),
+                _ => None,
+            }
+        };
+
+        let response = handle(
+            5000,
+            true,
+            b"HTTP/1.0 200\nContent-Type: text/plain\n\nmiao",
+        )
+        .unwrap();
+        assert_eq!(response.reason, "");
+        assert_eq!(
+            response.quirks,
+            vec![
+                "Missing reason phrase".to_owned(),
+                "Lines ended by LF only".to_owned()
+            ]
+        );
+        assert!(
+            matches!(response.payload, HttpContentType::TextDefaultDecoded(text) if text == "miao")
+        );
+
+        let chunked = b"HTTP/1.1 200 OK\nTransfer-Encoding: chunked\n\n4\nmiao\n0\n\n";
+        let response = handle(5001, false, chunked).unwrap();
+        assert_eq!(response.quirks, vec!["Lines ended by LF only".to_owned()]);
+        assert!(
+            matches!(response.payload, HttpContentType::TextDefaultDecoded(text) if text == "miao")
+        );
+
+        // Cut short by the connection close
+        let truncated = b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\nmiao";
+        assert!(handle(5002, false, truncated).is_none());
+        let response = handle(5002, true, b"").unwrap();
+        assert_eq!(
+            response.quirks,
+            vec!["Body ended by the connection close".to_owned()]
+        );
+        assert!(
+            matches!(response.payload, HttpContentType::TextDefaultDecoded(text) if text == "miao")
+        );
+    }
+
+    #[test]
+    fn transfer_encoding_chunked_lf_line_endings() {
+        let payload = b"4\nmiao\r\n5\n bau!\n0\n\n".to_vec();
+
+        assert_eq!(merge_chunks(payload.clone(), true).unwrap().0, b"miao bau!");
+        assert!(matches!(
+            merge_chunks(payload, false),
+            Err(HttpParsingError::TransferEncodingMalformed(_))
+        ));
+    }
+
+    #[test]
+    fn docker_registry_requests() {
+        let manifest = parse_registry_request("/v2/library/nginx/manifests/1.25").unwrap();
+        assert_eq!(manifest.endpoint, DockerRegistryEndpoint::Manifest);
+        assert_eq!(manifest.image.as_deref(), Some("library/nginx"));
+        assert_eq!(manifest.reference.as_deref(), Some("1.25"));
+
+        let blob = parse_registry_request("/v2/team/app/blobs/sha256:0a1b?ns=docker.io").unwrap();
+        assert_eq!(blob.endpoint, DockerRegistryEndpoint::Blob);
+        assert_eq!(blob.image.as_deref(), Some("team/app"));
+        assert_eq!(blob.reference.as_deref(), Some("sha256:0a1b"));
+
+        let upload = parse_registry_request("/v2/team/app/blobs/uploads/").unwrap();
+        assert_eq!(upload.endpoint, DockerRegistryEndpoint::BlobUpload);
+        assert_eq!(upload.reference, None);
+
+        let tags = parse_registry_request("/v2/alpine/tags/list?n=10").unwrap();
+        assert_eq!(tags.endpoint, DockerRegistryEndpoint::Tags);
+        assert_eq!(tags.image.as_deref(), Some("alpine"));
+
+        assert_eq!(
+            parse_registry_request("/v2/").unwrap().endpoint,
+            DockerRegistryEndpoint::Base
+        );
+    }
+
+    #[test]
+    fn not_docker_registry_requests() {
+        assert!(parse_registry_request("/").is_none());
+        assert!(parse_registry_request("/v2/Library/Nginx/manifests/latest").is_none());
+        assert!(parse_registry_request("/api/v2/items/blobs/1").is_none());
+        assert!(parse_registry_request("/v2/manifests/latest").is_none());
+    }
 }
@@ -0,0 +1,683 @@
+//! HTTP/2 Packet parsing
+//!
+//! An HTTP/2 connection carries frames, each belonging either to a stream (a request and its
+//! response) or to the whole connection (stream 0). The frames are reassembled from the segments
+//! of each direction of the connection, and the messages are rebuilt from the frames of their
+//! streams:
+//! - HEADERS and CONTINUATION: header blocks, decoded with HPACK, then trailers
+//! - DATA: body, decoded by its content type and encoding like the HTTP/1.x ones
+//! - RST_STREAM: cancellation of the stream, whose message is dropped
+//! - SETTINGS and GOAWAY: parameters and shutdown of the connection
+//!
+//! Only cleartext HTTP/2 can be dissected: connections with prior knowledge, recognized by the
+//! preface sent by the client, and connections to etcd, whose gRPC calls are labelled with their
+//! method. Header blocks are compressed against the previous ones of the connection, so their
+//! indexed fields are missing when the start of the connection was not captured.
+
+use std::{collections::HashMap, mem, net::IpAddr};
+
+use tracing::debug;
+
+use crate::{
+    serializable_packet::{
+        application::{Http2Frame, Http2Message, HttpContentType, SerializableHttp2Packet},
+        ParsedPacket, SerializablePacket,
+    },
+    FlowKey, ParserContext,
+};
+
+use super::{hpack::HpackDecoder, http::decode_body};
+
+/// Sent by the client at the start of the connection, before its first frame
+pub const CONNECTION_PREFACE: &[u8] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
+
+/// Length of the header of each frame
+const FRAME_HEADER_LENGTH: usize = 9;
+/// Maximum number of streams of a direction whose message is being rebuilt
+const MAX_STREAMS: usize = 256;
+/// Maximum number of bytes of a body kept for decoding, the following ones are only counted
+const MAX_BODY_LENGTH: usize = 1 << 20;
+
+/// HTTP/2 frame types
+#[allow(non_snake_case)]
+mod FrameTypes {
+    pub const DATA: u8 = 0x0;
+    pub const HEADERS: u8 = 0x1;
+    pub const PRIORITY: u8 = 0x2;
+    pub const RST_STREAM: u8 = 0x3;
+    pub const SETTINGS: u8 = 0x4;
+    pub const PUSH_PROMISE: u8 = 0x5;
+    pub const PING: u8 = 0x6;
+    pub const GOAWAY: u8 = 0x7;
+    pub const WINDOW_UPDATE: u8 = 0x8;
+    pub const CONTINUATION: u8 = 0x9;
+}
+
+/// HTTP/2 frame flags
+#[allow(non_snake_case)]
+mod Flags {
+    pub const END_STREAM: u8 = 0x1;
+    pub const END_HEADERS: u8 = 0x4;
+    pub const PADDED: u8 = 0x8;
+    pub const PRIORITY: u8 = 0x20;
+}
+
+/// Method and path of a request
+type RequestLine = (Option<String>, Option<String>);
+
+/// State of a direction of an HTTP/2 connection
+#[derive(Debug, Default)]
+pub(crate) struct Http2Direction {
+    /// Whether the start of the direction, possibly carrying the preface, was seen
+    started: bool,
+    /// Bytes of the frame being reassembled
+    buffer: Vec<u8>,
+    decoder: HpackDecoder,
+    /// Header block waiting for its CONTINUATION frames
+    pending_headers: Option<PendingHeaders>,
+    /// Messages being rebuilt, by stream
+    streams: HashMap<u32, Http2Stream>,
+    /// Requests sent in the other direction, by stream
+    requests: HashMap<u32, RequestLine>,
+}
+
+#[derive(Debug)]
+struct PendingHeaders {
+    stream_id: u32,
+    block: Vec<u8>,
+    ends_stream: bool,
+    is_push_promise: bool,
+}
+
+#[derive(Debug, Default)]
+struct Http2Stream {
+    headers: Option<Vec<(String, String)>>,
+    trailers: Vec<(String, String)>,
+    body: Vec<u8>,
+    body_length: usize,
+}
+
+/// Frames of a segment, with the messages they complete and the requests they start
+#[derive(Debug, Default)]
+struct Dissection {
+    frames: Vec<Http2Frame>,
+    messages: Vec<Http2Message>,
+    requests: Vec<(u32, RequestLine)>,
+}
+
+/// Build an HTTP/2 packet from a transport-layer packet, save it in a Parsed Packet
+#[allow(clippy::too_many_arguments)]
+pub fn handle_http2_packet(
+    state: &mut ParserContext,
+    source_ip: IpAddr,
+    source_port: u16,
+    dest_ip: IpAddr,
+    dest_port: u16,
+    is_request: bool,
+    is_fin: bool,
+    packet: &[u8],
+    parsed_packet: &mut ParsedPacket,
+) {
+    let (key, direction) = FlowKey::of((source_ip, source_port), (dest_ip, dest_port));
+    let flow = (key, direction);
+
+    let dissection = {
+        let connections = &mut state.http2_connections;
+        let dissection = connections
+            .entry(flow)
+            .or_default()
+            .dissect(packet, is_request);
+
+        match &dissection {
+            // Responses are labelled with the method and path of their requests
+            Ok(dissection) if !dissection.requests.is_empty() => {
+                let reversed = connections.entry((key, direction.reverse())).or_default();
+                for (stream_id, request) in &dissection.requests {
+                    if reversed.requests.len() < MAX_STREAMS {
+                        reversed.requests.insert(*stream_id, request.clone());
+                    }
+                }
+            }
+            Ok(_) => (),
+            // The following frames can't be found anymore
+            Err(_) => {
+                connections.remove(&flow);
+            }
+        }
+        if is_fin {
+            connections.remove(&flow);
+        }
+
+        dissection
+    };
+
+    match dissection {
+        Ok(dissection) if dissection.frames.is_empty() => (),
+        Ok(dissection) => {
+            debug!(
+                "HTTP/2 Packet: {}:{} > {}:{}; Frames: {:?}; Messages: {}",
+                source_ip,
+                source_port,
+                dest_ip,
+                dest_port,
+                dissection
+                    .frames
+                    .iter()
+                    .map(|frame| &frame.frame_type)
+                    .collect::<Vec<_>>(),
+                dissection.messages.len()
+            );
+
+            parsed_packet.set_application_layer_packet(Some(SerializablePacket::Http2Packet(
+                SerializableHttp2Packet {
+                    frames: dissection.frames,
+                    messages: dissection.messages,
+                },
+            )));
+        }
+        Err(error) => {
+            debug!("{}", error);
+            parsed_packet
+                .set_application_layer_packet(Some(SerializablePacket::MalformedPacket(error)));
+        }
+    }
+}
+
+impl Http2Direction {
+    /// Get the number of bytes held for the frames and the bodies being reassembled
+    pub(crate) fn buffered_bytes(&self) -> usize {
+        self.buffer.len()
+            + self
+                .streams
+                .values()
+                .map(|stream| stream.body.len())
+                .sum::<usize>()
+    }
+
+    /// Parse the frames completed by a segment
+    fn dissect(&mut self, mut packet: &[u8], is_request: bool) -> Result<Dissection, String> {
+        if !self.started {
+            self.started = true;
+            packet = packet.strip_prefix(CONNECTION_PREFACE).unwrap_or(packet);
+        }
+        self.buffer.extend_from_slice(packet);
+
+        let buffer = mem::take(&mut self.buffer);
+        let mut dissection = Dissection::default();
+        let mut offset = 0;
+
+        while let Some(header) = buffer.get(offset..offset + FRAME_HEADER_LENGTH) {
+            let length = u32::from_be_bytes([0, header[0], header[1], header[2]]);
+            let start = offset + FRAME_HEADER_LENGTH;
+            let payload = match buffer.get(start..start + length as usize) {
+                Some(payload) => payload,
+                None => break,
+            };
+
+            let mut frame = Http2Frame {
+                frame_type: frame_type_name(header[3]),
+                flags: header[4],
+                stream_id: u32::from_be_bytes([header[5], header[6], header[7], header[8]])
+                    & 0x7fff_ffff,
+                length,
+                ..Default::default()
+            };
+            self.handle_frame(header[3], payload, is_request, &mut frame, &mut dissection)?;
+
+            dissection.frames.push(frame);
+            offset = start + length as usize;
+        }
+
+        self.buffer = buffer[offset..].to_vec();
+
+        Ok(dissection)
+    }
+
+    fn handle_frame(
+        &mut self,
+        frame_type: u8,
+        payload: &[u8],
+        is_request: bool,
+        frame: &mut Http2Frame,
+        dissection: &mut Dissection,
+    ) -> Result<(), String> {
+        let malformed = || "Malformed HTTP/2 Packet".to_owned();
+        let (flags, stream_id) = (frame.flags, frame.stream_id);
+
+        // A header block ends with its END_HEADERS flag, before any other frame
+        if frame_type != FrameTypes::CONTINUATION {
+            self.pending_headers = None;
+        }
+
+        match frame_type {
+            FrameTypes::DATA => {
+                let data = unpad(flags, payload).ok_or_else(malformed)?;
+                if let Some(stream) = self.streams.get_mut(&stream_id) {
+                    let kept = data.len().min(MAX_BODY_LENGTH - stream.body.len());
+                    stream.body.extend_from_slice(&data[..kept]);
+                    stream.body_length += data.len();
+                }
+                if flags & Flags::END_STREAM != 0 {
+                    self.end_stream(stream_id, is_request, dissection);
+                }
+            }
+            FrameTypes::HEADERS | FrameTypes::PUSH_PROMISE => {
+                let block = unpad(flags, payload).ok_or_else(malformed)?;
+                let block = match frame_type {
+                    FrameTypes::HEADERS if flags & Flags::PRIORITY != 0 => block.get(5..),
+                    FrameTypes::HEADERS => Some(block),
+                    // Promised stream
+                    _ => block.get(4..),
+                }
+                .ok_or_else(malformed)?;
+
+                self.pending_headers = Some(PendingHeaders {
+                    stream_id,
+                    block: block.to_vec(),
+                    ends_stream: frame_type == FrameTypes::HEADERS
+                        && flags & Flags::END_STREAM != 0,
+                    is_push_promise: frame_type == FrameTypes::PUSH_PROMISE,
+                });
+            }
+            FrameTypes::CONTINUATION => match self.pending_headers.as_mut() {
+                Some(pending) if pending.stream_id == stream_id => {
+                    pending.block.extend_from_slice(payload)
+                }
+                _ => return Err(malformed()),
+            },
+            FrameTypes::RST_STREAM => {
+                let error_code = payload.try_into().map_err(|_| malformed())?;
+                frame.error_code = Some(error_code_name(u32::from_be_bytes(error_code)));
+                self.streams.remove(&stream_id);
+            }
+            FrameTypes::SETTINGS => {
+                if payload.len() % 6 != 0 {
+                    return Err(malformed());
+                }
+                frame.settings = payload
+                    .chunks(6)
+                    .map(|setting| {
+                        (
+                            setting_name(u16::from_be_bytes([setting[0], setting[1]])),
+                            u32::from_be_bytes([setting[2], setting[3], setting[4], setting[5]]),
+                        )
+                    })
+                    .collect();
+            }
+            FrameTypes::GOAWAY => {
+                if payload.len() < 8 {
+                    return Err(malformed());
+                }
+                frame.last_stream_id = Some(
+                    u32::from_be_bytes([payload[0], payload[1], payload[2], payload[3]])
+                        & 0x7fff_ffff,
+                );
+                frame.error_code = Some(error_code_name(u32::from_be_bytes([
+                    payload[4], payload[5], payload[6], payload[7],
+                ])));
+            }
+            _ => (),
+        }
+
+        let is_block_ended = matches!(
+            frame_type,
+            FrameTypes::HEADERS | FrameTypes::PUSH_PROMISE | FrameTypes::CONTINUATION
+        ) && flags & Flags::END_HEADERS != 0;
+        if is_block_ended {
+            if let Some(pending) = self.pending_headers.take() {
+                frame.headers = self.end_headers(pending, is_request, dissection)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Decode a complete header block, starting the message of its stream or adding its trailers
+    fn end_headers(
+        &mut self,
+        pending: PendingHeaders,
+        is_request: bool,
+        dissection: &mut Dissection,
+    ) -> Result<Vec<(String, String)>, String> {
+        let headers = self
+            .decoder
+            .decode(&pending.block)
+            .ok_or_else(|| "Malformed HTTP/2 header block".to_owned())?;
+        // The promised request is only decoded to keep the dynamic table up to date
+        if pending.is_push_promise {
+            return Ok(headers);
+        }
+
+        let stream_id = pending.stream_id;
+        if !self.streams.contains_key(&stream_id) && self.streams.len() < MAX_STREAMS {
+            self.streams.insert(stream_id, Http2Stream::default());
+        }
+        if let Some(stream) = self.streams.get_mut(&stream_id) {
+            match &stream.headers {
+                // Informational responses precede the final one
+                Some(previous) if !is_informational(previous) => stream.trailers = headers.clone(),
+                _ => {
+                    if is_request {
+                        dissection.requests.push((
+                            stream_id,
+                            (header(&headers, ":method"), header(&headers, ":path")),
+                        ));
+                    }
+                    stream.headers = Some(headers.clone());
+                }
+            }
+        }
+
+        if pending.ends_stream {
+            self.end_stream(stream_id, is_request, dissection);
+        }
+
+        Ok(headers)
+    }
+
+    /// Rebuild the message of a stream ended by the last frame
+    fn end_stream(&mut self, stream_id: u32, is_request: bool, dissection: &mut Dissection) {
+        let stream = match self.streams.remove(&stream_id) {
+            Some(stream) => stream,
+            None => return,
+        };
+        let headers = stream.headers.unwrap_or_default();
+
+        let (method, path) = match is_request {
+            true => (header(&headers, ":method"), header(&headers, ":path")),
+            false => self.requests.remove(&stream_id).unwrap_or_default(),
+        };
+        let grpc_method = header(&headers, "content-type")
+            .filter(|content_type| content_type.starts_with("application/grpc"))
+            .and(path.as_ref())
+            .map(|path| path.trim_start_matches('/').to_owned());
+        // Responses without a body carry the status of the call in their headers
+        let grpc_status = header(&stream.trailers, "grpc-status")
+            .or_else(|| header(&headers, "grpc-status"))
+            .and_then(|status| status.parse().ok());
+        let payload = match stream.body.is_empty() {
+            true => HttpContentType::None,
+            false => decode_body(
+                stream.body.clone(),
+                header(&headers, "content-type").as_deref(),
+                header(&headers, "content-encoding").as_deref(),
+            )
+            .unwrap_or(HttpContentType::Unknown(stream.body)),
+        };
+
+        dissection.messages.push(Http2Message {
+            stream_id,
+            request: is_request,
+            method,
+            path,
+            authority: header(&headers, ":authority"),
+            status: header(&headers, ":status").and_then(|status| status.parse().ok()),
+            headers,
+            trailers: stream.trailers,
+            payload,
+            body_length: stream.body_length,
+            grpc_method,
+            grpc_status,
+        });
+    }
+}
+
+/// Get the content of a DATA, HEADERS or PUSH_PROMISE frame without its padding
+fn unpad(flags: u8, payload: &[u8]) -> Option<&[u8]> {
+    if flags & Flags::PADDED == 0 {
+        return Some(payload);
+    }
+
+    let padding = *payload.first()? as usize;
+    payload.get(1..payload.len().checked_sub(padding)?)
+}
+
+/// Get the value of the first header with the given name
+fn header(headers: &[(String, String)], name: &str) -> Option<String> {
+    headers
+        .iter()
+        .find(|(header, _)| header == name)
+        .map(|(_, value)| value.clone())
+}
+
+/// Check if the headers are the ones of an informational (1xx) response
+fn is_informational(headers: &[(String, String)]) -> bool {
+    header(headers, ":status").is_some_and(|status| status.starts_with('1'))
+}
+
+fn frame_type_name(frame_type: u8) -> String {
+    let name = match frame_type {
+        FrameTypes::DATA => "DATA",
+        FrameTypes::HEADERS => "HEADERS",
+        FrameTypes::PRIORITY => "PRIORITY",
+        FrameTypes::RST_STREAM => "RST_STREAM",
+        FrameTypes::SETTINGS => "SETTINGS",
+        FrameTypes::PUSH_PROMISE => "PUSH_PROMISE",
+        FrameTypes::PING => "PING",
+        FrameTypes::GOAWAY => "GOAWAY",
+        FrameTypes::WINDOW_UPDATE => "WINDOW_UPDATE",
+        FrameTypes::CONTINUATION => "CONTINUATION",
+        _ => return format!("Unknown ({:#04x})", frame_type),
+    };
+
+    name.to_owned()
+}
+
+fn setting_name(identifier: u16) -> String {
+    let name = match identifier {
+        0x1 => "HEADER_TABLE_SIZE",
+        0x2 => "ENABLE_PUSH",
+        0x3 => "MAX_CONCURRENT_STREAMS",
+        0x4 => "INITIAL_WINDOW_SIZE",
+        0x5 => "MAX_FRAME_SIZE",
+        0x6 => "MAX_HEADER_LIST_SIZE",
+        0x8 => "ENABLE_CONNECT_PROTOCOL",
+        _ => return format!("Unknown ({:#06x})", identifier),
+    };
+
+    name.to_owned()
+}
+
+fn error_code_name(error_code: u32) -> String {
+    let name = match error_code {
+        0x0 => "NO_ERROR",
+        0x1 => "PROTOCOL_ERROR",
+        0x2 => "INTERNAL_ERROR",
+        0x3 => "FLOW_CONTROL_ERROR",
+        0x4 => "SETTINGS_TIMEOUT",
+        0x5 => "STREAM_CLOSED",
+        0x6 => "FRAME_SIZE_ERROR",
+        0x7 => "REFUSED_STREAM",
+        0x8 => "CANCEL",
+        0x9 => "COMPRESSION_ERROR",
+        0xa => "CONNECT_ERROR",
+        0xb => "ENHANCE_YOUR_CALM",
+        0xc => "INADEQUATE_SECURITY",
+        0xd => "HTTP_1_1_REQUIRED",
+        _ => return format!("Unknown ({:#x})", error_code),
+    };
+
+    name.to_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr};
+
+    use crate::serializable_packet::application::SerializableHttp2Packet;
+    use crate::serializable_packet::{ParsedPacket, SerializablePacket};
+    use crate::ParserContext;
+
+    use super::handle_http2_packet;
+
+    const CLIENT: (IpAddr, u16) = (IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)), 50000);
+    const SERVER: (IpAddr, u16) = (IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2)), 2379);
+
+    /// Preface, SETTINGS, HEADERS and DATA of an etcd range request, then SETTINGS acknowledgement
+    const GRPC_REQUEST: &str = "505249202a20485454502f322e300d0a0d0a534d0d0a0d0a000000040000000000\
+        00003901040000000183049060a924882d9dcb6571af9b8b1b4754c5418b089d5c0b8170dc684d34cf865f8b1d\
+        75d0620d263d4c4d656440027465864d833505b11f00000a00010000000100000000050a03666f6f00000004010\
+        0000000";
+    /// SETTINGS, its acknowledgement, HEADERS, DATA and trailers of the response
+    const GRPC_RESPONSE: &str = "0000000400000000000000000401000000000000260104000000018\
+        85f8b1d75d0620d263d4c4d65646196c361be940b8a6a22541004e2810dc006e01f53168dff000007000000000\
+        00100000000020a0000000c01050000000140889acac8b21234da8f0130";
+
+    #[test]
+    fn grpc_call() {
+        let mut state = ParserContext::new();
+
+        // The request is split in the middle of its HEADERS frame
+        let request = hex(GRPC_REQUEST);
+        let http2_packet = parse(&mut state, CLIENT, SERVER, true, &request[..60]).unwrap();
+        assert_eq!(frame_types(&http2_packet), ["SETTINGS"]);
+        assert!(http2_packet.messages.is_empty());
+        let http2_packet = parse(&mut state, CLIENT, SERVER, true, &request[60..]).unwrap();
+        assert_eq!(frame_types(&http2_packet), ["HEADERS", "DATA", "SETTINGS"]);
+        let request = &http2_packet.messages[0];
+        assert!(request.request);
+        assert_eq!(request.stream_id, 1);
+        assert_eq!(request.method.as_deref(), Some("POST"));
+        assert_eq!(request.path.as_deref(), Some("/etcdserverpb.KV/Range"));
+        assert_eq!(request.authority.as_deref(), Some("127.0.0.1:42443"));
+        assert_eq!(request.body_length, 10);
+        assert_eq!(
+            request.grpc_method.as_deref(),
+            Some("etcdserverpb.KV/Range")
+        );
+
+        let http2_packet = parse(&mut state, SERVER, CLIENT, false, &hex(GRPC_RESPONSE)).unwrap();
+        assert_eq!(
+            frame_types(&http2_packet),
+            ["SETTINGS", "SETTINGS", "HEADERS", "DATA", "HEADERS"]
+        );
+        let response = &http2_packet.messages[0];
+        assert!(!response.request);
+        assert_eq!(response.status, Some(200));
+        assert_eq!(response.path.as_deref(), Some("/etcdserverpb.KV/Range"));
+        assert_eq!(response.body_length, 7);
+        assert_eq!(
+            response.trailers,
+            vec![("grpc-status".to_owned(), "0".to_owned())]
+        );
+        assert_eq!(
+            response.grpc_method.as_deref(),
+            Some("etcdserverpb.KV/Range")
+        );
+        assert_eq!(response.grpc_status, Some(0));
+    }
+
+    #[test]
+    fn reset_stream_and_goaway() {
+        let mut state = ParserContext::new();
+
+        let mut frames = build_test_frame(0x4, 0x0, 0, &[0x00, 0x03, 0x00, 0x00, 0x00, 0x64]);
+        // Request headers without END_STREAM, as literals without indexing
+        frames.extend(build_test_frame(
+            0x1,
+            0x4,
+            3,
+            &[
+                0x02, 0x03, b'G', b'E', b'T', 0x04, 0x06, b'/', b'w', b'a', b't', b'c', b'h',
+            ],
+        ));
+        frames.extend(build_test_frame(0x3, 0x0, 3, &8u32.to_be_bytes()));
+        frames.extend(build_test_frame(0x7, 0x0, 0, &[0, 0, 0, 3, 0, 0, 0, 0]));
+
+        let http2_packet = parse(&mut state, CLIENT, SERVER, true, &frames).unwrap();
+        assert!(http2_packet.messages.is_empty());
+        let frames = http2_packet.frames;
+        assert_eq!(
+            frames[0].settings,
+            vec![("MAX_CONCURRENT_STREAMS".to_owned(), 100)]
+        );
+        assert_eq!(
+            frames[1].headers,
+            vec![
+                (":method".to_owned(), "GET".to_owned()),
+                (":path".to_owned(), "/watch".to_owned()),
+            ]
+        );
+        assert_eq!(frames[2].error_code.as_deref(), Some("CANCEL"));
+        assert_eq!(frames[3].last_stream_id, Some(3));
+        assert_eq!(frames[3].error_code.as_deref(), Some("NO_ERROR"));
+    }
+
+    #[test]
+    fn malformed_frame() {
+        let mut state = ParserContext::new();
+
+        let mut parsed_packet = ParsedPacket::new(0);
+        handle_http2_packet(
+            &mut state,
+            CLIENT.0,
+            CLIENT.1,
+            SERVER.0,
+            SERVER.1,
+            true,
+            false,
+            &build_test_frame(0x3, 0x0, 1, &[0x00, 0x08]),
+            &mut parsed_packet,
+        );
+
+        match parsed_packet.get_application_layer_packet() {
+            Some(SerializablePacket::MalformedPacket(str)) => {
+                assert_eq!(str, "Malformed HTTP/2 Packet")
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    ///////////////////// Utils
+
+    fn parse(
+        state: &mut ParserContext,
+        source: (IpAddr, u16),
+        destination: (IpAddr, u16),
+        is_request: bool,
+        packet: &[u8],
+    ) -> Option<SerializableHttp2Packet> {
+        let mut parsed_packet = ParsedPacket::new(0);
+        handle_http2_packet(
+            state,
+            source.0,
+            source.1,
+            destination.0,
+            destination.1,
+            is_request,
+            false,
+            packet,
+            &mut parsed_packet,
+        );
+
+        match parsed_packet.get_application_layer_packet() {
+            Some(SerializablePacket::Http2Packet(http2_packet)) => Some(http2_packet.clone()),
+            None => None,
+            _ => unreachable!(),
+        }
+    }
+
+    fn build_test_frame(frame_type: u8, flags: u8, stream_id: u32, payload: &[u8]) -> Vec<u8> {
+        let mut frame = (payload.len() as u32).to_be_bytes()[1..].to_vec();
+        frame.extend_from_slice(&[frame_type, flags]);
+        frame.extend_from_slice(&stream_id.to_be_bytes());
+        frame.extend_from_slice(payload);
+
+        frame
+    }
+
+    fn frame_types(http2_packet: &SerializableHttp2Packet) -> Vec<&str> {
+        http2_packet
+            .frames
+            .iter()
+            .map(|frame| frame.frame_type.as_str())
+            .collect()
+    }
+
+    fn hex(string: &str) -> Vec<u8> {
+        (0..string.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&string[i..i + 2], 16).unwrap())
+            .collect()
+    }
+}
@@ -0,0 +1,359 @@
+//! IEC 61850 GOOSE and Sampled Values Packet parsing
+//!
+//! Both protocols are carried directly over Ethernet (ethertypes 0x88B8 and 0x88BA):
+//! a common header (APPID, length and reserved fields) precedes a BER encoded APDU.
+
+use tracing::debug;
+
+use super::ber::{parse_element, parse_elements, to_bool, to_string, to_unsigned, BerElement};
+use crate::serializable_packet::application::{
+    Iec61850Timestamp, SerializableGoosePacket, SerializableSvPacket, SvAsdu, SvSample,
+};
+use crate::serializable_packet::{ParsedPacket, SerializablePacket};
+
+const HEADER_LENGTH: usize = 8;
+
+/// Reserved 1 field: simulated frame (IEC 61850-8-1 Ed. 2)
+const RESERVED1_SIMULATION: u16 = 0x8000;
+
+/// BER tags of the APDUs
+#[allow(non_snake_case)]
+mod Iec61850Tags {
+    pub const GOOSE_PDU: u8 = 0x61;
+    pub const SAV_PDU: u8 = 0x60;
+    pub const SAV_ASDU_SEQUENCE: u8 = 0xa2;
+    pub const SAV_ASDU: u8 = 0x30;
+}
+
+/// Common header of GOOSE and SV frames
+struct Iec61850Header<'a> {
+    appid: u16,
+    length: u16,
+    simulation: bool,
+    apdu: &'a [u8],
+}
+
+/// Build a GOOSE packet from a link-layer packet, save it in a Parsed Packet
+pub fn handle_goose_packet(packet: &[u8], parsed_packet: &mut ParsedPacket) {
+    match parse_goose_packet(packet) {
+        Some(goose_packet) => {
+            debug!(
+                "GOOSE Packet: APPID: {:#06x}, Dataset: {}, stNum: {}, sqNum: {}",
+                goose_packet.appid, goose_packet.dataset, goose_packet.st_num, goose_packet.sq_num
+            );
+
+            parsed_packet
+                .set_application_layer_packet(Some(SerializablePacket::GoosePacket(goose_packet)));
+        }
+        None => {
+            debug!("Malformed GOOSE Packet");
+            parsed_packet.set_application_layer_packet(Some(SerializablePacket::MalformedPacket(
+                "Malformed GOOSE Packet".to_string(),
+            )));
+        }
+    }
+}
+
+/// Build a Sampled Values packet from a link-layer packet, save it in a Parsed Packet
+pub fn handle_sv_packet(packet: &[u8], parsed_packet: &mut ParsedPacket) {
+    match parse_sv_packet(packet) {
+        Some(sv_packet) => {
+            debug!(
+                "SV Packet: APPID: {:#06x}, ASDUs: {}",
+                sv_packet.appid,
+                sv_packet.asdus.len()
+            );
+
+            parsed_packet
+                .set_application_layer_packet(Some(SerializablePacket::SvPacket(sv_packet)));
+        }
+        None => {
+            debug!("Malformed SV Packet");
+            parsed_packet.set_application_layer_packet(Some(SerializablePacket::MalformedPacket(
+                "Malformed SV Packet".to_string(),
+            )));
+        }
+    }
+}
+
+fn parse_header(packet: &[u8]) -> Option<Iec61850Header<'_>> {
+    if packet.len() < HEADER_LENGTH {
+        return None;
+    }
+
+    // Length counts from the APPID to the end of the APDU
+    let length = u16::from_be_bytes([packet[2], packet[3]]);
+    if (length as usize) < HEADER_LENGTH || packet.len() < length as usize {
+        return None;
+    }
+
+    Some(Iec61850Header {
+        appid: u16::from_be_bytes([packet[0], packet[1]]),
+        length,
+        simulation: u16::from_be_bytes([packet[4], packet[5]]) & RESERVED1_SIMULATION != 0,
+        apdu: &packet[HEADER_LENGTH..length as usize],
+    })
+}
+
+fn parse_goose_packet(packet: &[u8]) -> Option<SerializableGoosePacket> {
+    let header = parse_header(packet)?;
+    let (pdu, _) = parse_element(header.apdu)?;
+    if pdu.tag != Iec61850Tags::GOOSE_PDU {
+        return None;
+    }
+
+    let mut goose_packet = SerializableGoosePacket {
+        appid: header.appid,
+        length: header.length,
+        simulation: header.simulation,
+        gocb_ref: String::new(),
+        time_allowed_to_live: 0,
+        dataset: String::new(),
+        go_id: None,
+        timestamp: None,
+        st_num: 0,
+        sq_num: 0,
+        test: false,
+        conf_rev: 0,
+        nds_com: false,
+        num_dataset_entries: 0,
+        all_data_entries: 0,
+    };
+
+    for BerElement { tag, value } in parse_elements(pdu.value)? {
+        match tag {
+            0x80 => goose_packet.gocb_ref = to_string(value),
+            0x81 => goose_packet.time_allowed_to_live = to_u32(value)?,
+            0x82 => goose_packet.dataset = to_string(value),
+            0x83 => goose_packet.go_id = Some(to_string(value)),
+            0x84 => goose_packet.timestamp = Some(to_timestamp(value)?),
+            0x85 => goose_packet.st_num = to_u32(value)?,
+            0x86 => goose_packet.sq_num = to_u32(value)?,
+            0x87 => goose_packet.test = to_bool(value),
+            0x88 => goose_packet.conf_rev = to_u32(value)?,
+            0x89 => goose_packet.nds_com = to_bool(value),
+            0x8a => goose_packet.num_dataset_entries = to_u32(value)?,
+            0xab => goose_packet.all_data_entries = parse_elements(value)?.len(),
+            _ => (),
+        }
+    }
+
+    Some(goose_packet)
+}
+
+fn parse_sv_packet(packet: &[u8]) -> Option<SerializableSvPacket> {
+    let header = parse_header(packet)?;
+    let (pdu, _) = parse_element(header.apdu)?;
+    if pdu.tag != Iec61850Tags::SAV_PDU {
+        return None;
+    }
+
+    let sequence = parse_elements(pdu.value)?
+        .into_iter()
+        .find(|element| element.tag == Iec61850Tags::SAV_ASDU_SEQUENCE)?;
+
+    let asdus = parse_elements(sequence.value)?
+        .into_iter()
+        .filter(|element| element.tag == Iec61850Tags::SAV_ASDU)
+        .map(|element| parse_sv_asdu(element.value))
+        .collect::<Option<Vec<SvAsdu>>>()?;
+
+    Some(SerializableSvPacket {
+        appid: header.appid,
+        length: header.length,
+        simulation: header.simulation,
+        asdus,
+    })
+}
+
+fn parse_sv_asdu(data: &[u8]) -> Option<SvAsdu> {
+    let mut asdu = SvAsdu {
+        sv_id: String::new(),
+        dataset: None,
+        smp_cnt: 0,
+        conf_rev: 0,
+        refr_tm: None,
+        smp_synch: 0,
+        smp_rate: None,
+        samples: vec![],
+        smp_mod: None,
+    };
+
+    for BerElement { tag, value } in parse_elements(data)? {
+        match tag {
+            0x80 => asdu.sv_id = to_string(value),
+            0x81 => asdu.dataset = Some(to_string(value)),
+            0x82 => asdu.smp_cnt = to_u16(value)?,
+            0x83 => asdu.conf_rev = to_u32(value)?,
+            0x84 => asdu.refr_tm = Some(to_timestamp(value)?),
+            0x85 => asdu.smp_synch = to_unsigned(value)? as u8,
+            0x86 => asdu.smp_rate = Some(to_u16(value)?),
+            0x87 => {
+                asdu.samples = value
+                    .chunks_exact(8)
+                    .map(|sample| SvSample {
+                        value: i32::from_be_bytes(sample[..4].try_into().unwrap()),
+                        quality: u32::from_be_bytes(sample[4..].try_into().unwrap()),
+                    })
+                    .collect()
+            }
+            0x88 => asdu.smp_mod = Some(to_u16(value)?),
+            _ => (),
+        }
+    }
+
+    Some(asdu)
+}
+
+/// Decode an unsigned INTEGER fitting 32 bits
+fn to_u32(value: &[u8]) -> Option<u32> {
+    to_unsigned(value).and_then(|number| u32::try_from(number).ok())
+}
+
+/// Decode an unsigned INTEGER fitting 16 bits
+fn to_u16(value: &[u8]) -> Option<u16> {
+    to_unsigned(value).and_then(|number| u16::try_from(number).ok())
+}
+
+/// UtcTime: seconds since the epoch, fraction of second (24 bits) and time quality
+fn to_timestamp(value: &[u8]) -> Option<Iec61850Timestamp> {
+    if value.len() != 8 {
+        return None;
+    }
+
+    let fraction = u32::from_be_bytes([0, value[4], value[5], value[6]]) as u64;
+    Some(Iec61850Timestamp {
+        seconds: u32::from_be_bytes(value[..4].try_into().unwrap()),
+        nanoseconds: ((fraction * 1_000_000_000) >> 24) as u32,
+        quality: value[7],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::serializable_packet::{ParsedPacket, SerializablePacket};
+
+    use super::{handle_goose_packet, handle_sv_packet};
+
+    #[test]
+    fn goose_packet() {
+        let goose = build_test_frame(
+            0x61,
+            &[
+                tlv(0x80, b"IED1LD0/LLN0$GO$gcb01"),
+                tlv(0x81, &[0x07, 0xd0]),
+                tlv(0x82, b"IED1LD0/LLN0$DataSet01"),
+                tlv(0x84, &[0x63, 0x00, 0x00, 0x00, 0x80, 0x00, 0x00, 0x0a]),
+                tlv(0x85, &[0x00, 0x80, 0x00, 0x00, 0x01]),
+                tlv(0x86, &[0x05]),
+                tlv(0x87, &[0x00]),
+                tlv(0x88, &[0x01]),
+                tlv(0x89, &[0x00]),
+                tlv(0x8a, &[0x02]),
+                tlv(
+                    0xab,
+                    &[tlv(0x83, &[0x01]), tlv(0x84, &[0x03, 0x00])].concat(),
+                ),
+            ]
+            .concat(),
+        );
+
+        let mut parsed_packet = ParsedPacket::new(0);
+        handle_goose_packet(&goose, &mut parsed_packet);
+        match parsed_packet.get_application_layer_packet().unwrap() {
+            SerializablePacket::GoosePacket(goose_packet) => {
+                assert_eq!(goose_packet.appid, 0x0001);
+                assert_eq!(goose_packet.gocb_ref, "IED1LD0/LLN0$GO$gcb01");
+                assert_eq!(goose_packet.dataset, "IED1LD0/LLN0$DataSet01");
+                assert_eq!(goose_packet.time_allowed_to_live, 2000);
+                assert_eq!(goose_packet.st_num, 0x8000_0001);
+                assert_eq!(goose_packet.sq_num, 5);
+                assert_eq!(
+                    goose_packet.timestamp.as_ref().unwrap().nanoseconds,
+                    500_000_000
+                );
+                assert_eq!(goose_packet.all_data_entries, 2);
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn sv_packet() {
+        let asdu = [
+            tlv(0x80, b"MU01"),
+            tlv(0x82, &[0x0f, 0x9f]),
+            tlv(0x83, &[0x00, 0x00, 0x00, 0x01]),
+            tlv(0x85, &[0x02]),
+            tlv(
+                0x87,
+                &[
+                    0xff, 0xff, 0xff, 0x9c, 0x00, 0x00, 0x20, 0x00, 0x00, 0x00, 0x00, 0x64, 0x00,
+                    0x00, 0x00, 0x00,
+                ],
+            ),
+        ]
+        .concat();
+        let sv = build_test_frame(
+            0x60,
+            &[tlv(0x80, &[0x01]), tlv(0xa2, &tlv(0x30, &asdu))].concat(),
+        );
+
+        let mut parsed_packet = ParsedPacket::new(0);
+        handle_sv_packet(&sv, &mut parsed_packet);
+        match parsed_packet.get_application_layer_packet().unwrap() {
+            SerializablePacket::SvPacket(sv_packet) => {
+                assert_eq!(sv_packet.asdus.len(), 1);
+                let asdu = &sv_packet.asdus[0];
+                assert_eq!(asdu.sv_id, "MU01");
+                assert_eq!(asdu.smp_cnt, 3999);
+                assert_eq!(asdu.smp_synch, 2);
+                assert_eq!(asdu.samples.len(), 2);
+                assert_eq!(asdu.samples[0].value, -100);
+                assert_eq!(asdu.samples[0].quality, 0x2000);
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn malformed_goose_packet() {
+        // Length field larger than the frame
+        let mut parsed_packet = ParsedPacket::new(0);
+        handle_goose_packet(
+            &[0x00, 0x01, 0x00, 0x40, 0x00, 0x00, 0x00, 0x00, 0x61, 0x00],
+            &mut parsed_packet,
+        );
+
+        assert!(matches!(
+            parsed_packet.get_application_layer_packet(),
+            Some(SerializablePacket::MalformedPacket(_))
+        ));
+    }
+
+    ///////////////////// Utils
+
+    fn tlv(tag: u8, value: &[u8]) -> Vec<u8> {
+        let mut element = vec![tag];
+        if value.len() < 0x80 {
+            element.push(value.len() as u8);
+        } else {
+            element.extend_from_slice(&[0x82, (value.len() >> 8) as u8, value.len() as u8]);
+        }
+        element.extend_from_slice(value);
+        element
+    }
+
+    fn build_test_frame(pdu_tag: u8, pdu: &[u8]) -> Vec<u8> {
+        let apdu = tlv(pdu_tag, pdu);
+        let length = (8 + apdu.len()) as u16;
+
+        [
+            &[0x00, 0x01][..],
+            &length.to_be_bytes(),
+            &[0x00, 0x00, 0x00, 0x00],
+            &apdu,
+        ]
+        .concat()
+    }
+}
@@ -0,0 +1,683 @@
+//! IKE Packet parsing
+//!
+//! The Internet Key Exchange negotiates the IPsec SAs of a VPN over UDP:
+//! - port 500: the IKE messages, until a NAT is detected between the peers
+//! - port 4500 (NAT traversal): the IKE messages, preceded by a zero non-ESP marker, along with
+//!   the ESP packets of the SAs, encapsulated in UDP (RFC 3948)
+//!
+//! The IKE_SA_INIT exchange of IKEv2 is sent in the clear: the SA proposals, the Diffie-Hellman
+//! group and the NAT detection notifications are decoded from it. The NAT detection notifications
+//! carry the SHA-1 hash of the SPIs and of the address and port of each peer, as seen by the
+//! sender: the hash not matching the addresses of the captured packet tells a NAT in between.
+//! The payloads following the Encrypted one, as the whole IKEv1 messages, are not decoded.
+
+use std::net::IpAddr;
+
+use sha1::{Digest, Sha1};
+use tracing::debug;
+
+use crate::serializable_packet::application::{IkeProposal, IkeTransform, SerializableIkePacket};
+use crate::serializable_packet::{ParsedPacket, SerializablePacket};
+use crate::transport::parse_esp_header;
+
+use super::WellKnownPorts;
+
+/// Length of the IKE header
+const IKE_HEADER_LENGTH: usize = 28;
+
+/// Header flags of the original initiator and of the responses
+const FLAG_INITIATOR: u8 = 0x08;
+const FLAG_RESPONSE: u8 = 0x20;
+
+/// Byte sent alone by the peers behind a NAT, to keep its mapping alive
+const NAT_KEEPALIVE: u8 = 0xff;
+
+/// IKEv2 Payload Types
+#[allow(non_snake_case)]
+mod PayloadTypes {
+    pub const NONE: u8 = 0;
+    pub const SA: u8 = 33;
+    pub const KE: u8 = 34;
+    pub const NOTIFY: u8 = 41;
+    pub const ENCRYPTED: u8 = 46;
+    pub const ENCRYPTED_FRAGMENT: u8 = 53;
+}
+
+/// IKEv2 Notify Message Types of the NAT detection
+#[allow(non_snake_case)]
+mod NotifyTypes {
+    pub const NAT_DETECTION_SOURCE_IP: u16 = 16388;
+    pub const NAT_DETECTION_DESTINATION_IP: u16 = 16389;
+}
+
+/// Build an IKE packet, or a UDP-encapsulated ESP one, from a transport-layer packet, save it in a
+/// Parsed Packet
+pub fn handle_ike_packet(
+    source_ip: IpAddr,
+    source_port: u16,
+    dest_ip: IpAddr,
+    dest_port: u16,
+    packet: &[u8],
+    parsed_packet: &mut ParsedPacket,
+) {
+    let nat_traversal = [source_port, dest_port].contains(&WellKnownPorts::IKE_NAT_T_PORT);
+    let message = match packet {
+        [NAT_KEEPALIVE] if nat_traversal => {
+            debug!(
+                "NAT-keepalive {}:{} > {}:{}",
+                source_ip, source_port, dest_ip, dest_port
+            );
+            return;
+        }
+        [0, 0, 0, 0, message @ ..] if nat_traversal => message,
+        _ if nat_traversal => {
+            let esp_packet = parse_esp_header(packet, true);
+            parsed_packet.set_application_layer_packet(Some(match esp_packet {
+                Some(esp_packet) => SerializablePacket::EspPacket(esp_packet),
+                None => SerializablePacket::MalformedPacket("Malformed ESP Packet".to_string()),
+            }));
+            return;
+        }
+        _ => packet,
+    };
+
+    match parse_ike_message(message) {
+        Some(mut ike_packet) => {
+            ike_packet.udp_encapsulated = nat_traversal;
+            if ike_packet.major_version == 2 {
+                let spis = &message[..16];
+                ike_packet.source_nat = nat_detected(message, NotifyTypes::NAT_DETECTION_SOURCE_IP)
+                    .map(|hash| hash != nat_detection_hash(spis, source_ip, source_port));
+                ike_packet.destination_nat =
+                    nat_detected(message, NotifyTypes::NAT_DETECTION_DESTINATION_IP)
+                        .map(|hash| hash != nat_detection_hash(spis, dest_ip, dest_port));
+            }
+
+            debug!(
+                "IKEv{} Packet: {}; message ID: {}",
+                ike_packet.major_version, ike_packet.exchange_type, ike_packet.message_id
+            );
+
+            parsed_packet
+                .set_application_layer_packet(Some(SerializablePacket::IkePacket(ike_packet)));
+        }
+        None => {
+            debug!("Malformed IKE Packet");
+            parsed_packet.set_application_layer_packet(Some(SerializablePacket::MalformedPacket(
+                "Malformed IKE Packet".to_string(),
+            )));
+        }
+    }
+}
+
+/// Check if a payload is an IKEv1 or IKEv2 message, whose length is the one of the payload
+pub fn is_ike_message(payload: &[u8]) -> bool {
+    match payload.get(..IKE_HEADER_LENGTH) {
+        Some(header) => {
+            matches!(header[17], 0x10 | 0x20)
+                && u32::from_be_bytes(header[24..].try_into().unwrap()) as usize == payload.len()
+        }
+        None => false,
+    }
+}
+
+/// Parse an IKE message, decoding the payloads of IKEv2 until the encrypted ones
+pub fn parse_ike_message(packet: &[u8]) -> Option<SerializableIkePacket> {
+    let header = packet.get(..IKE_HEADER_LENGTH)?;
+    let (major_version, minor_version) = (header[17] >> 4, header[17] & 0x0f);
+    let exchange_type = header[18];
+    let flags = header[19];
+    let length = u32::from_be_bytes(header[24..].try_into().unwrap()) as usize;
+    let message = packet
+        .get(..length)
+        .filter(|_| length >= IKE_HEADER_LENGTH)?;
+
+    let mut ike_packet = SerializableIkePacket {
+        major_version,
+        minor_version,
+        initiator_spi: to_hex(&header[..8]),
+        responder_spi: to_hex(&header[8..16]),
+        exchange_type: String::new(),
+        initiator: flags & FLAG_INITIATOR != 0,
+        response: flags & FLAG_RESPONSE != 0,
+        message_id: u32::from_be_bytes(header[20..24].try_into().unwrap()),
+        payloads: vec![],
+        proposals: vec![],
+        dh_group: None,
+        notifications: vec![],
+        source_nat: None,
+        destination_nat: None,
+        udp_encapsulated: false,
+        length: packet.len(),
+    };
+
+    match major_version {
+        1 => {
+            ike_packet.exchange_type =
+                format!("{} ({})", ikev1_exchange_name(exchange_type), exchange_type);
+        }
+        2 => {
+            ike_packet.exchange_type =
+                format!("{} ({})", exchange_name(exchange_type), exchange_type);
+            parse_payloads(message, &mut ike_packet)?;
+        }
+        _ => return None,
+    }
+
+    Some(ike_packet)
+}
+
+/// Parse the chain of payloads of an IKEv2 message, each naming the type of the next one
+fn parse_payloads(message: &[u8], ike_packet: &mut SerializableIkePacket) -> Option<()> {
+    for (payload_type, body) in payloads(message) {
+        let body = body?;
+        ike_packet
+            .payloads
+            .push(format!("{} ({})", payload_name(payload_type), payload_type));
+
+        match payload_type {
+            PayloadTypes::SA => ike_packet.proposals = parse_proposals(body)?,
+            PayloadTypes::KE => {
+                let group = u16::from_be_bytes(body.get(..2)?.try_into().unwrap());
+                ike_packet.dh_group = Some(format!("{} ({})", dh_group_name(group), group));
+            }
+            PayloadTypes::NOTIFY => {
+                let notify_type = u16::from_be_bytes(body.get(2..4)?.try_into().unwrap());
+                ike_packet.notifications.push(format!(
+                    "{} ({})",
+                    notify_name(notify_type),
+                    notify_type
+                ));
+            }
+            _ => (),
+        }
+    }
+
+    Some(())
+}
+
+/// Iterate over the payloads of an IKEv2 message, as their type and their body (none if
+/// malformed), stopping at the encrypted ones: the payloads they carry are not readable
+fn payloads(message: &[u8]) -> impl Iterator<Item = (u8, Option<&[u8]>)> {
+    let mut next_payload = message[16];
+    let mut offset = IKE_HEADER_LENGTH;
+
+    std::iter::from_fn(move || {
+        if next_payload == PayloadTypes::NONE {
+            return None;
+        }
+        let payload_type = next_payload;
+
+        let length = message
+            .get(offset + 2..offset + 4)
+            .map(|length| u16::from_be_bytes([length[0], length[1]]) as usize);
+        let body = length
+            .filter(|length| *length >= 4)
+            .and_then(|length| message.get(offset + 4..offset + length));
+
+        next_payload = match (body, payload_type) {
+            (None, _) | (_, PayloadTypes::ENCRYPTED | PayloadTypes::ENCRYPTED_FRAGMENT) => {
+                PayloadTypes::NONE
+            }
+            _ => message[offset],
+        };
+        offset += length.unwrap_or_default();
+
+        Some((payload_type, body))
+    })
+}
+
+/// Parse the proposals of an SA payload, each one listing its transforms
+fn parse_proposals(mut body: &[u8]) -> Option<Vec<IkeProposal>> {
+    let mut proposals = vec![];
+
+    while !body.is_empty() {
+        let length = u16::from_be_bytes(body.get(2..4)?.try_into().unwrap()) as usize;
+        let proposal = body.get(..length).filter(|_| length >= 8)?;
+        let (number, protocol, spi_size) = (proposal[4], proposal[5], proposal[6] as usize);
+
+        let spi = proposal.get(8..8 + spi_size)?;
+        let mut transforms_data = &proposal[8 + spi_size..];
+        let mut transforms = vec![];
+        for _ in 0..proposal[7] {
+            let transform_length =
+                u16::from_be_bytes(transforms_data.get(2..4)?.try_into().unwrap()) as usize;
+            let transform = transforms_data
+                .get(..transform_length)
+                .filter(|_| transform_length >= 8)?;
+            transforms.push(parse_transform(transform));
+            transforms_data = &transforms_data[transform_length..];
+        }
+
+        proposals.push(IkeProposal {
+            number,
+            protocol: format!("{} ({})", protocol_name(protocol), protocol),
+            spi: (!spi.is_empty()).then(|| to_hex(spi)),
+            transforms,
+        });
+        body = &body[length..];
+    }
+
+    Some(proposals)
+}
+
+/// Parse a transform of a proposal, along with the key length given by its attributes
+fn parse_transform(transform: &[u8]) -> IkeTransform {
+    let transform_type = transform[4];
+    let id = u16::from_be_bytes([transform[6], transform[7]]);
+
+    // Attributes in the short, type/value format: the key length is the only one defined
+    let key_length = transform[8..]
+        .chunks_exact(4)
+        .find(|attribute| attribute[..2] == [0x80, 0x0e])
+        .map(|attribute| u16::from_be_bytes([attribute[2], attribute[3]]));
+
+    IkeTransform {
+        transform_type: format!(
+            "{} ({})",
+            transform_type_name(transform_type),
+            transform_type
+        ),
+        id: format!("{} ({})", transform_name(transform_type, id), id),
+        key_length,
+    }
+}
+
+/// Get the hash carried by a NAT detection notification of an IKEv2 message, if any
+fn nat_detected(message: &[u8], notify_type: u16) -> Option<Vec<u8>> {
+    payloads(message)
+        .filter(|(payload_type, _)| *payload_type == PayloadTypes::NOTIFY)
+        .filter_map(|(_, body)| body)
+        .find(|body| body.get(2..4) == Some(&notify_type.to_be_bytes()[..]))
+        .and_then(|body| {
+            let spi_size = body[1] as usize;
+            body.get(4 + spi_size..).map(<[u8]>::to_vec)
+        })
+}
+
+/// Hash of the NAT detection notifications: SHA-1 of the SPIs, of an address and of a port
+fn nat_detection_hash(spis: &[u8], address: IpAddr, port: u16) -> Vec<u8> {
+    let mut hasher = Sha1::new();
+    hasher.update(spis);
+    match address {
+        IpAddr::V4(address) => hasher.update(address.octets()),
+        IpAddr::V6(address) => hasher.update(address.octets()),
+    }
+    hasher.update(port.to_be_bytes());
+
+    hasher.finalize().to_vec()
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn exchange_name(exchange_type: u8) -> &'static str {
+    match exchange_type {
+        34 => "IKE_SA_INIT",
+        35 => "IKE_AUTH",
+        36 => "CREATE_CHILD_SA",
+        37 => "INFORMATIONAL",
+        38 => "IKE_SESSION_RESUME",
+        43 => "IKE_INTERMEDIATE",
+        _ => "Unknown",
+    }
+}
+
+fn ikev1_exchange_name(exchange_type: u8) -> &'static str {
+    match exchange_type {
+        1 => "Base",
+        2 => "Identity Protection (Main Mode)",
+        3 => "Authentication Only",
+        4 => "Aggressive",
+        5 => "Informational",
+        32 => "Quick Mode",
+        33 => "New Group Mode",
+        _ => "Unknown",
+    }
+}
+
+fn payload_name(payload_type: u8) -> &'static str {
+    match payload_type {
+        33 => "Security Association",
+        34 => "Key Exchange",
+        35 => "Identification - Initiator",
+        36 => "Identification - Responder",
+        37 => "Certificate",
+        38 => "Certificate Request",
+        39 => "Authentication",
+        40 => "Nonce",
+        41 => "Notify",
+        42 => "Delete",
+        43 => "Vendor ID",
+        44 => "Traffic Selector - Initiator",
+        45 => "Traffic Selector - Responder",
+        46 => "Encrypted and Authenticated",
+        47 => "Configuration",
+        48 => "Extensible Authentication",
+        53 => "Encrypted and Authenticated Fragment",
+        _ => "Unknown",
+    }
+}
+
+fn notify_name(notify_type: u16) -> &'static str {
+    match notify_type {
+        1 => "UNSUPPORTED_CRITICAL_PAYLOAD",
+        4 => "INVALID_IKE_SPI",
+        5 => "INVALID_MAJOR_VERSION",
+        7 => "INVALID_SYNTAX",
+        9 => "INVALID_MESSAGE_ID",
+        11 => "INVALID_SPI",
+        14 => "NO_PROPOSAL_CHOSEN",
+        17 => "INVALID_KE_PAYLOAD",
+        24 => "AUTHENTICATION_FAILED",
+        34 => "SINGLE_PAIR_REQUIRED",
+        35 => "NO_ADDITIONAL_SAS",
+        36 => "INTERNAL_ADDRESS_FAILURE",
+        37 => "FAILED_CP_REQUIRED",
+        38 => "TS_UNACCEPTABLE",
+        39 => "INVALID_SELECTORS",
+        43 => "TEMPORARY_FAILURE",
+        44 => "CHILD_SA_NOT_FOUND",
+        16384 => "INITIAL_CONTACT",
+        16385 => "SET_WINDOW_SIZE",
+        16386 => "ADDITIONAL_TS_POSSIBLE",
+        16387 => "IPCOMP_SUPPORTED",
+        16388 => "NAT_DETECTION_SOURCE_IP",
+        16389 => "NAT_DETECTION_DESTINATION_IP",
+        16390 => "COOKIE",
+        16391 => "USE_TRANSPORT_MODE",
+        16392 => "HTTP_CERT_LOOKUP_SUPPORTED",
+        16393 => "REKEY_SA",
+        16394 => "ESP_TFC_PADDING_NOT_SUPPORTED",
+        16395 => "NON_FIRST_FRAGMENTS_ALSO",
+        16396 => "MOBIKE_SUPPORTED",
+        16430 => "IKEV2_FRAGMENTATION_SUPPORTED",
+        16431 => "SIGNATURE_HASH_ALGORITHMS",
+        _ => "Unknown",
+    }
+}
+
+fn protocol_name(protocol: u8) -> &'static str {
+    match protocol {
+        1 => "IKE",
+        2 => "AH",
+        3 => "ESP",
+        _ => "Unknown",
+    }
+}
+
+fn transform_type_name(transform_type: u8) -> &'static str {
+    match transform_type {
+        1 => "Encryption Algorithm",
+        2 => "Pseudorandom Function",
+        3 => "Integrity Algorithm",
+        4 => "Diffie-Hellman Group",
+        5 => "Extended Sequence Numbers",
+        _ => "Unknown",
+    }
+}
+
+fn transform_name(transform_type: u8, id: u16) -> &'static str {
+    match (transform_type, id) {
+        (1, 3) => "3DES",
+        (1, 11) => "NULL",
+        (1, 12) => "AES-CBC",
+        (1, 13) => "AES-CTR",
+        (1, 14) => "AES-CCM-8",
+        (1, 16) => "AES-CCM-16",
+        (1, 18) => "AES-GCM-8",
+        (1, 19) => "AES-GCM-12",
+        (1, 20) => "AES-GCM-16",
+        (1, 28) => "ChaCha20-Poly1305",
+        (2, 1) => "HMAC-MD5",
+        (2, 2) => "HMAC-SHA1",
+        (2, 4) => "AES128-XCBC",
+        (2, 5) => "HMAC-SHA2-256",
+        (2, 6) => "HMAC-SHA2-384",
+        (2, 7) => "HMAC-SHA2-512",
+        (2, 8) => "AES128-CMAC",
+        (3, 0) => "NONE",
+        (3, 1) => "HMAC-MD5-96",
+        (3, 2) => "HMAC-SHA1-96",
+        (3, 5) => "AES-XCBC-96",
+        (3, 12) => "HMAC-SHA2-256-128",
+        (3, 13) => "HMAC-SHA2-384-192",
+        (3, 14) => "HMAC-SHA2-512-256",
+        (4, group) => dh_group_name(group),
+        (5, 0) => "No ESN",
+        (5, 1) => "ESN",
+        _ => "Unknown",
+    }
+}
+
+fn dh_group_name(group: u16) -> &'static str {
+    match group {
+        0 => "NONE",
+        2 => "MODP-1024",
+        5 => "MODP-1536",
+        14 => "MODP-2048",
+        15 => "MODP-3072",
+        16 => "MODP-4096",
+        19 => "ECP-256",
+        20 => "ECP-384",
+        21 => "ECP-521",
+        31 => "Curve25519",
+        32 => "Curve448",
+        _ => "Unknown",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr};
+
+    use super::{handle_ike_packet, is_ike_message, nat_detection_hash, parse_ike_message};
+    use crate::serializable_packet::application::IkeTransform;
+    use crate::serializable_packet::{ParsedPacket, SerializablePacket};
+
+    const INITIATOR: IpAddr = IpAddr::V4(Ipv4Addr::new(192, 168, 1, 10));
+    const RESPONDER: IpAddr = IpAddr::V4(Ipv4Addr::new(203, 0, 113, 5));
+    const SPIS: [u8; 16] = [1, 2, 3, 4, 5, 6, 7, 8, 0, 0, 0, 0, 0, 0, 0, 0];
+
+    #[test]
+    fn ike_sa_init_request() {
+        let message = ike_sa_init(INITIATOR, 500);
+
+        assert!(is_ike_message(&message));
+        let ike = parse_ike_message(&message).unwrap();
+        assert_eq!((ike.major_version, ike.minor_version), (2, 0));
+        assert_eq!(ike.initiator_spi, "0102030405060708");
+        assert_eq!(ike.responder_spi, "0000000000000000");
+        assert_eq!(ike.exchange_type, "IKE_SA_INIT (34)");
+        assert!(ike.initiator && !ike.response);
+        assert_eq!(
+            ike.payloads,
+            [
+                "Security Association (33)",
+                "Key Exchange (34)",
+                "Notify (41)",
+                "Notify (41)"
+            ]
+        );
+        assert_eq!(ike.dh_group.as_deref(), Some("ECP-256 (19)"));
+        assert_eq!(
+            ike.notifications,
+            [
+                "NAT_DETECTION_SOURCE_IP (16388)",
+                "NAT_DETECTION_DESTINATION_IP (16389)"
+            ]
+        );
+
+        assert_eq!(ike.proposals.len(), 1);
+        assert_eq!(ike.proposals[0].protocol, "IKE (1)");
+        assert_eq!(ike.proposals[0].spi, None);
+        assert_eq!(
+            ike.proposals[0].transforms,
+            [
+                transform("Encryption Algorithm (1)", "AES-GCM-16 (20)", Some(256)),
+                transform("Pseudorandom Function (2)", "HMAC-SHA2-256 (5)", None),
+                transform("Diffie-Hellman Group (4)", "ECP-256 (19)", None),
+            ]
+        );
+    }
+
+    #[test]
+    fn nat_detection() {
+        // Sent from behind a NAT, mapping the address of the initiator to the one of its router
+        let router = IpAddr::V4(Ipv4Addr::new(198, 51, 100, 1));
+        let mut parsed_packet = ParsedPacket::new(0);
+        handle_ike_packet(
+            router,
+            500,
+            RESPONDER,
+            500,
+            &ike_sa_init(INITIATOR, 500),
+            &mut parsed_packet,
+        );
+
+        match parsed_packet.get_application_layer_packet().unwrap() {
+            SerializablePacket::IkePacket(ike) => {
+                assert_eq!(ike.source_nat, Some(true));
+                assert_eq!(ike.destination_nat, Some(false));
+                assert!(!ike.udp_encapsulated);
+            }
+            _ => unreachable!(),
+        }
+
+        let mut parsed_packet = ParsedPacket::new(1);
+        handle_ike_packet(
+            INITIATOR,
+            500,
+            RESPONDER,
+            500,
+            &ike_sa_init(INITIATOR, 500),
+            &mut parsed_packet,
+        );
+
+        match parsed_packet.get_application_layer_packet().unwrap() {
+            SerializablePacket::IkePacket(ike) => assert_eq!(ike.source_nat, Some(false)),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn nat_traversal_port() {
+        let mut message = vec![0, 0, 0, 0];
+        message.extend(ike_sa_init(INITIATOR, 4500));
+        let mut parsed_packet = ParsedPacket::new(0);
+        handle_ike_packet(
+            INITIATOR,
+            4500,
+            RESPONDER,
+            4500,
+            &message,
+            &mut parsed_packet,
+        );
+
+        match parsed_packet.get_application_layer_packet().unwrap() {
+            SerializablePacket::IkePacket(ike) => {
+                assert!(ike.udp_encapsulated);
+                assert_eq!(ike.source_nat, Some(false));
+            }
+            _ => unreachable!(),
+        }
+
+        let esp = [0xc0, 0x01, 0x02, 0x03, 0, 0, 0, 5, 0xaa, 0xbb];
+        let mut parsed_packet = ParsedPacket::new(1);
+        handle_ike_packet(INITIATOR, 4500, RESPONDER, 4500, &esp, &mut parsed_packet);
+
+        match parsed_packet.get_application_layer_packet().unwrap() {
+            SerializablePacket::EspPacket(esp) => {
+                assert_eq!(esp.spi, 0xc0010203);
+                assert_eq!(esp.sequence, 5);
+                assert!(esp.udp_encapsulated);
+                assert_eq!(esp.length, 2);
+            }
+            _ => unreachable!(),
+        }
+
+        let mut parsed_packet = ParsedPacket::new(2);
+        handle_ike_packet(
+            INITIATOR,
+            4500,
+            RESPONDER,
+            4500,
+            &[0xff],
+            &mut parsed_packet,
+        );
+        assert!(parsed_packet.get_application_layer_packet().is_none());
+    }
+
+    #[test]
+    fn malformed_ike_message() {
+        let mut message = ike_sa_init(INITIATOR, 500);
+        // Payload longer than the message
+        message[IKE_SA_INIT_SA_LENGTH_OFFSET] = 0xff;
+        assert!(parse_ike_message(&message).is_none());
+
+        let mut message = ike_sa_init(INITIATOR, 500);
+        message.pop();
+        assert!(!is_ike_message(&message));
+        assert!(parse_ike_message(&message).is_none());
+    }
+
+    ///////////////////// Utils
+
+    /// Offset of the high byte of the length of the SA payload of the messages built
+    const IKE_SA_INIT_SA_LENGTH_OFFSET: usize = 30;
+
+    fn ike_sa_init(source: IpAddr, port: u16) -> Vec<u8> {
+        let mut transforms = transform_data(3, 1, 20, &[0x80, 0x0e, 0x01, 0x00]);
+        transforms.extend(transform_data(3, 2, 5, &[]));
+        transforms.extend(transform_data(0, 4, 19, &[]));
+        let mut proposal = vec![0, 0, 0, 0, 1, 1, 0, 3];
+        proposal.extend(transforms);
+        let length = proposal.len() as u16;
+        proposal[2..4].copy_from_slice(&length.to_be_bytes());
+
+        let mut ke = vec![0, 19, 0, 0];
+        ke.extend([0x42; 64]);
+
+        let mut source_ip = vec![0, 0];
+        source_ip.extend(16388u16.to_be_bytes());
+        source_ip.extend(nat_detection_hash(&SPIS, source, port));
+        let mut destination_ip = vec![0, 0];
+        destination_ip.extend(16389u16.to_be_bytes());
+        destination_ip.extend(nat_detection_hash(&SPIS, RESPONDER, port));
+
+        let mut message = SPIS.to_vec();
+        message.extend([33, 0x20, 34, 0x08, 0, 0, 0, 0, 0, 0, 0, 0]);
+        message.extend(payload(34, &proposal));
+        message.extend(payload(41, &ke));
+        message.extend(payload(41, &source_ip));
+        message.extend(payload(0, &destination_ip));
+        let length = message.len() as u32;
+        message[24..28].copy_from_slice(&length.to_be_bytes());
+        message
+    }
+
+    fn payload(next_payload: u8, body: &[u8]) -> Vec<u8> {
+        let mut payload = vec![next_payload, 0];
+        payload.extend((body.len() as u16 + 4).to_be_bytes());
+        payload.extend(body);
+        payload
+    }
+
+    fn transform_data(last: u8, transform_type: u8, id: u16, attributes: &[u8]) -> Vec<u8> {
+        let mut transform = vec![last, 0];
+        transform.extend((attributes.len() as u16 + 8).to_be_bytes());
+        transform.extend([transform_type, 0]);
+        transform.extend(id.to_be_bytes());
+        transform.extend(attributes);
+        transform
+    }
+
+    fn transform(transform_type: &str, id: &str, key_length: Option<u16>) -> IkeTransform {
+        IkeTransform {
+            transform_type: transform_type.to_owned(),
+            id: id.to_owned(),
+            key_length,
+        }
+    }
+}
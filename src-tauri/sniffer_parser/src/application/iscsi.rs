@@ -0,0 +1,240 @@
+//! iSCSI Packet parsing
+//!
+//! iSCSI PDUs (TCP port 3260) start with a 48-bytes basic header segment (RFC 7143).
+//! All the PDUs starting in a segment are dissected, assuming no header and data digests:
+//! segments continuing the data of a previous PDU are not dissected.
+
+use std::net::IpAddr;
+
+use tracing::debug;
+
+use crate::serializable_packet::application::{IscsiPdu, SerializableIscsiPacket};
+use crate::serializable_packet::{ParsedPacket, SerializablePacket};
+
+const BASIC_HEADER_LENGTH: usize = 48;
+
+/// iSCSI opcodes
+#[allow(non_snake_case)]
+mod IscsiOpcodes {
+    pub const NOP_OUT: u8 = 0x00;
+    pub const SCSI_COMMAND: u8 = 0x01;
+    pub const TASK_MANAGEMENT_REQUEST: u8 = 0x02;
+    pub const SCSI_DATA_OUT: u8 = 0x05;
+    pub const NOP_IN: u8 = 0x20;
+    pub const SCSI_RESPONSE: u8 = 0x21;
+    pub const SCSI_DATA_IN: u8 = 0x25;
+    pub const R2T: u8 = 0x31;
+    pub const ASYNC_MESSAGE: u8 = 0x32;
+}
+
+/// Build an iSCSI packet from a transport-layer packet, save it in a Parsed Packet
+pub fn handle_iscsi_packet(
+    source_ip: IpAddr,
+    source_port: u16,
+    dest_ip: IpAddr,
+    dest_port: u16,
+    packet: &[u8],
+    parsed_packet: &mut ParsedPacket,
+) {
+    let pdus = parse_iscsi_pdus(packet);
+    if pdus.is_empty() {
+        return;
+    }
+
+    debug!(
+        "iSCSI Packet: {}:{} > {}:{}; PDUs: {:?}",
+        source_ip,
+        source_port,
+        dest_ip,
+        dest_port,
+        pdus.iter().map(|pdu| &pdu.opcode).collect::<Vec<_>>()
+    );
+
+    parsed_packet.set_application_layer_packet(Some(SerializablePacket::IscsiPacket(
+        SerializableIscsiPacket { pdus },
+    )));
+}
+
+fn parse_iscsi_pdus(mut packet: &[u8]) -> Vec<IscsiPdu> {
+    let mut pdus = vec![];
+
+    while let Some(pdu) = parse_basic_header(packet) {
+        let ahs_length = pdu.total_ahs_length as usize * 4;
+        let data_length = (pdu.data_segment_length as usize + 3) & !3;
+        let pdu_length = BASIC_HEADER_LENGTH + ahs_length + data_length;
+
+        pdus.push(pdu);
+        match packet.get(pdu_length..) {
+            Some(rest) => packet = rest,
+            None => break,
+        }
+    }
+
+    pdus
+}
+
+fn parse_basic_header(header: &[u8]) -> Option<IscsiPdu> {
+    if header.len() < BASIC_HEADER_LENGTH {
+        return None;
+    }
+
+    let opcode = header[0] & 0x3f;
+    let opcode_name = opcode_name(opcode)?;
+    let word = |offset: usize| u32::from_be_bytes(header[offset..offset + 4].try_into().unwrap());
+
+    let lun = match opcode {
+        IscsiOpcodes::NOP_OUT
+        | IscsiOpcodes::SCSI_COMMAND
+        | IscsiOpcodes::TASK_MANAGEMENT_REQUEST
+        | IscsiOpcodes::SCSI_DATA_OUT
+        | IscsiOpcodes::NOP_IN
+        | IscsiOpcodes::SCSI_DATA_IN
+        | IscsiOpcodes::R2T
+        | IscsiOpcodes::ASYNC_MESSAGE => Some(u16::from_be_bytes([header[8], header[9]]) & 0x3fff),
+        _ => None,
+    };
+
+    // Initiator PDUs carry the command sequence number, target ones the status one
+    let (cmd_sn, stat_sn) = match opcode {
+        IscsiOpcodes::SCSI_DATA_OUT => (None, None),
+        0x00..=0x1f => (Some(word(24)), None),
+        _ => (None, Some(word(24))),
+    };
+
+    let scsi_command = match opcode {
+        IscsiOpcodes::SCSI_COMMAND => Some(scsi_command_name(header[32])),
+        _ => None,
+    };
+    let scsi_status = match opcode {
+        IscsiOpcodes::SCSI_RESPONSE => Some(header[3]),
+        _ => None,
+    };
+
+    Some(IscsiPdu {
+        opcode: opcode_name.to_owned(),
+        immediate: header[0] & 0x40 != 0,
+        final_pdu: header[1] & 0x80 != 0,
+        total_ahs_length: header[4],
+        data_segment_length: u32::from_be_bytes([0, header[5], header[6], header[7]]),
+        lun,
+        initiator_task_tag: word(16),
+        cmd_sn,
+        stat_sn,
+        scsi_command,
+        scsi_status,
+    })
+}
+
+fn opcode_name(opcode: u8) -> Option<&'static str> {
+    Some(match opcode {
+        0x00 => "NOP-Out",
+        0x01 => "SCSI Command",
+        0x02 => "Task Management Function Request",
+        0x03 => "Login Request",
+        0x04 => "Text Request",
+        0x05 => "SCSI Data-Out",
+        0x06 => "Logout Request",
+        0x10 => "SNACK Request",
+        0x20 => "NOP-In",
+        0x21 => "SCSI Response",
+        0x22 => "Task Management Function Response",
+        0x23 => "Login Response",
+        0x24 => "Text Response",
+        0x25 => "SCSI Data-In",
+        0x26 => "Logout Response",
+        0x31 => "Ready To Transfer",
+        0x32 => "Asynchronous Message",
+        0x3f => "Reject",
+        _ => return None,
+    })
+}
+
+fn scsi_command_name(operation_code: u8) -> String {
+    match operation_code {
+        0x00 => "TEST UNIT READY".to_owned(),
+        0x03 => "REQUEST SENSE".to_owned(),
+        0x12 => "INQUIRY".to_owned(),
+        0x1a => "MODE SENSE(6)".to_owned(),
+        0x25 => "READ CAPACITY(10)".to_owned(),
+        0x28 => "READ(10)".to_owned(),
+        0x2a => "WRITE(10)".to_owned(),
+        0x35 => "SYNCHRONIZE CACHE(10)".to_owned(),
+        0x5a => "MODE SENSE(10)".to_owned(),
+        0x88 => "READ(16)".to_owned(),
+        0x8a => "WRITE(16)".to_owned(),
+        0x9e => "SERVICE ACTION IN(16)".to_owned(),
+        0xa0 => "REPORT LUNS".to_owned(),
+        _ => format!("Unknown ({:#04x})", operation_code),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr};
+
+    use crate::serializable_packet::{ParsedPacket, SerializablePacket};
+
+    use super::handle_iscsi_packet;
+
+    #[test]
+    fn scsi_command_and_data() {
+        // READ(10) to LUN 1, followed by a Data-In PDU with 5 bytes of data
+        let mut command = build_test_header(0x01, 0, 1, 0x1234);
+        command[24..28].copy_from_slice(&7u32.to_be_bytes());
+        command[32] = 0x28;
+        let mut data_in = build_test_header(0x25, 5, 1, 0x1234);
+        data_in.extend_from_slice(&[0xaa; 8]);
+        let segment = [command, data_in].concat();
+
+        let parsed_packet = parse(&segment);
+        match parsed_packet.get_application_layer_packet().unwrap() {
+            SerializablePacket::IscsiPacket(iscsi_packet) => {
+                assert_eq!(iscsi_packet.pdus.len(), 2);
+
+                let command = &iscsi_packet.pdus[0];
+                assert_eq!(command.opcode, "SCSI Command");
+                assert_eq!(command.lun, Some(1));
+                assert_eq!(command.initiator_task_tag, 0x1234);
+                assert_eq!(command.cmd_sn, Some(7));
+                assert_eq!(command.scsi_command.as_deref(), Some("READ(10)"));
+
+                let data_in = &iscsi_packet.pdus[1];
+                assert_eq!(data_in.opcode, "SCSI Data-In");
+                assert_eq!(data_in.data_segment_length, 5);
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn continuation_segment() {
+        let parsed_packet = parse(&[0x3a; 100]);
+        assert!(parsed_packet.get_application_layer_packet().is_none());
+    }
+
+    ///////////////////// Utils
+
+    fn parse(packet: &[u8]) -> ParsedPacket {
+        let mut parsed_packet = ParsedPacket::new(0);
+        handle_iscsi_packet(
+            IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)),
+            50000,
+            IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2)),
+            3260,
+            packet,
+            &mut parsed_packet,
+        );
+
+        parsed_packet
+    }
+
+    fn build_test_header(opcode: u8, data_length: u32, lun: u16, task_tag: u32) -> Vec<u8> {
+        let mut header = vec![0u8; 48];
+        header[0] = opcode;
+        header[1] = 0x80;
+        header[5..8].copy_from_slice(&data_length.to_be_bytes()[1..]);
+        header[8..10].copy_from_slice(&lun.to_be_bytes());
+        header[16..20].copy_from_slice(&task_tag.to_be_bytes());
+        header
+    }
+}
@@ -0,0 +1,450 @@
+//! Kafka wire protocol Packet parsing
+//!
+//! Kafka messages (TCP port 9092) start with a 4-bytes size. Requests carry api key, api version,
+//! correlation id and client id; responses only the correlation id, so the api of a response is
+//! taken from the request with the same correlation id.
+//!
+//! Topic names are decoded from Produce, Fetch and Metadata requests in non-flexible versions
+//! (the ones not using compact encodings); segments without a message header are not dissected.
+
+use std::net::IpAddr;
+
+use tracing::debug;
+
+use crate::serializable_packet::application::SerializableKafkaPacket;
+use crate::serializable_packet::{ParsedPacket, SerializablePacket};
+use crate::ParserContext;
+
+/// Kafka API keys with decoded topics
+#[allow(non_snake_case)]
+mod ApiKeys {
+    pub const PRODUCE: i16 = 0;
+    pub const FETCH: i16 = 1;
+    pub const METADATA: i16 = 3;
+}
+
+/// Client and broker of a request, with its correlation id
+pub(crate) type KafkaRequestKey = ((IpAddr, u16), (IpAddr, u16), i32);
+
+/// Upper bound of the message size, to tell headers from continuation data
+const MAX_MESSAGE_SIZE: i32 = 100 * 1024 * 1024;
+const MAX_API_VERSION: i16 = 20;
+
+/// Build a Kafka packet from a transport-layer packet, save it in a Parsed Packet
+#[allow(clippy::too_many_arguments)]
+pub fn handle_kafka_packet(
+    state: &mut ParserContext,
+    source_ip: IpAddr,
+    source_port: u16,
+    dest_ip: IpAddr,
+    dest_port: u16,
+    is_request: bool,
+    packet: &[u8],
+    parsed_packet: &mut ParsedPacket,
+) {
+    let kafka_packet = match is_request {
+        true => parse_kafka_request(packet).inspect(|kafka_packet| {
+            state.kafka_requests.insert(
+                (
+                    (source_ip, source_port),
+                    (dest_ip, dest_port),
+                    kafka_packet.correlation_id,
+                ),
+                (
+                    kafka_packet.api_key.unwrap(),
+                    kafka_packet.api_version.unwrap(),
+                ),
+            );
+        }),
+        false => parse_kafka_response(packet).and_then(|mut kafka_packet| {
+            let (api_key, api_version) = state.kafka_requests.remove(&(
+                (dest_ip, dest_port),
+                (source_ip, source_port),
+                kafka_packet.correlation_id,
+            ))?;
+            kafka_packet.api_key = Some(api_key);
+            kafka_packet.api_name = api_name(api_key).map(str::to_owned);
+            kafka_packet.api_version = Some(api_version);
+            Some(kafka_packet)
+        }),
+    };
+
+    if let Some(kafka_packet) = kafka_packet {
+        debug!(
+            "Kafka Packet: {}:{} > {}:{}; API: {:?} v{:?}, Correlation ID: {}, Topics: {:?}",
+            source_ip,
+            source_port,
+            dest_ip,
+            dest_port,
+            kafka_packet.api_name,
+            kafka_packet.api_version,
+            kafka_packet.correlation_id,
+            kafka_packet.topics
+        );
+
+        parsed_packet
+            .set_application_layer_packet(Some(SerializablePacket::KafkaPacket(kafka_packet)));
+    }
+}
+
+fn parse_kafka_request(packet: &[u8]) -> Option<SerializableKafkaPacket> {
+    let mut reader = Reader(packet);
+    let length = reader.i32()?;
+    let api_key = reader.i16()?;
+    let api_version = reader.i16()?;
+    let correlation_id = reader.i32()?;
+    let client_id = reader.nullable_string()?;
+
+    let api_name = api_name(api_key)?;
+    if !(8..=MAX_MESSAGE_SIZE).contains(&length) || !(0..=MAX_API_VERSION).contains(&api_version) {
+        return None;
+    }
+
+    Some(SerializableKafkaPacket {
+        length,
+        request: true,
+        api_key: Some(api_key),
+        api_name: Some(api_name.to_owned()),
+        api_version: Some(api_version),
+        correlation_id,
+        client_id,
+        topics: read_topics(&mut reader, api_key, api_version),
+    })
+}
+
+fn parse_kafka_response(packet: &[u8]) -> Option<SerializableKafkaPacket> {
+    let mut reader = Reader(packet);
+    let length = reader.i32()?;
+    let correlation_id = reader.i32()?;
+
+    if !(4..=MAX_MESSAGE_SIZE).contains(&length) {
+        return None;
+    }
+
+    Some(SerializableKafkaPacket {
+        length,
+        request: false,
+        api_key: None,
+        api_name: None,
+        api_version: None,
+        correlation_id,
+        client_id: None,
+        topics: vec![],
+    })
+}
+
+/// Read the topic names of a request body, as far as the segment allows
+fn read_topics(reader: &mut Reader, api_key: i16, api_version: i16) -> Vec<String> {
+    let mut topics = vec![];
+    // Topics in the following segments are ignored
+    let _ = read_topic_names(reader, api_key, api_version, &mut topics);
+
+    topics
+}
+
+fn read_topic_names(
+    reader: &mut Reader,
+    api_key: i16,
+    api_version: i16,
+    topics: &mut Vec<String>,
+) -> Option<()> {
+    match (api_key, api_version) {
+        (ApiKeys::PRODUCE, 0..=8) => {
+            if api_version >= 3 {
+                reader.nullable_string()?;
+            }
+            reader.skip(2 + 4)?;
+
+            for _ in 0..reader.i32()? {
+                topics.push(reader.nullable_string()?.unwrap_or_default());
+                for _ in 0..reader.i32()? {
+                    reader.skip(4)?;
+                    let records = reader.i32()?;
+                    reader.skip(records.max(0) as usize)?;
+                }
+            }
+        }
+        (ApiKeys::FETCH, 0..=11) => {
+            reader.skip(4 + 4 + 4)?;
+            if api_version >= 3 {
+                reader.skip(4)?;
+            }
+            if api_version >= 4 {
+                reader.skip(1)?;
+            }
+            if api_version >= 7 {
+                reader.skip(4 + 4)?;
+            }
+
+            let mut partition_length = 4 + 8 + 4;
+            if api_version >= 5 {
+                partition_length += 8;
+            }
+            if api_version >= 9 {
+                partition_length += 4;
+            }
+
+            for _ in 0..reader.i32()? {
+                topics.push(reader.nullable_string()?.unwrap_or_default());
+                let partitions = reader.i32()?;
+                reader.skip(partitions.max(0) as usize * partition_length)?;
+            }
+        }
+        // A null array (version 1 and later) requests all the topics
+        (ApiKeys::METADATA, 0..=8) => {
+            for _ in 0..reader.i32()? {
+                topics.push(reader.nullable_string()?.unwrap_or_default());
+            }
+        }
+        _ => (),
+    }
+
+    Some(())
+}
+
+/// Big endian reader of Kafka primitive types
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, length: usize) -> Option<&'a [u8]> {
+        let bytes = self.0.get(..length)?;
+        self.0 = &self.0[length..];
+        Some(bytes)
+    }
+
+    fn skip(&mut self, length: usize) -> Option<()> {
+        self.take(length).map(|_| ())
+    }
+
+    fn i16(&mut self) -> Option<i16> {
+        self.take(2)
+            .map(|bytes| i16::from_be_bytes(bytes.try_into().unwrap()))
+    }
+
+    fn i32(&mut self) -> Option<i32> {
+        self.take(4)
+            .map(|bytes| i32::from_be_bytes(bytes.try_into().unwrap()))
+    }
+
+    /// Read a nullable string: 2-bytes length (-1 for null) followed by the UTF-8 string
+    fn nullable_string(&mut self) -> Option<Option<String>> {
+        match self.i16()? {
+            length if length < 0 => Some(None),
+            length => self
+                .take(length as usize)
+                .map(|string| Some(String::from_utf8_lossy(string).into_owned())),
+        }
+    }
+}
+
+fn api_name(api_key: i16) -> Option<&'static str> {
+    Some(match api_key {
+        0 => "Produce",
+        1 => "Fetch",
+        2 => "ListOffsets",
+        3 => "Metadata",
+        4 => "LeaderAndIsr",
+        5 => "StopReplica",
+        6 => "UpdateMetadata",
+        7 => "ControlledShutdown",
+        8 => "OffsetCommit",
+        9 => "OffsetFetch",
+        10 => "FindCoordinator",
+        11 => "JoinGroup",
+        12 => "Heartbeat",
+        13 => "LeaveGroup",
+        14 => "SyncGroup",
+        15 => "DescribeGroups",
+        16 => "ListGroups",
+        17 => "SaslHandshake",
+        18 => "ApiVersions",
+        19 => "CreateTopics",
+        20 => "DeleteTopics",
+        21 => "DeleteRecords",
+        22 => "InitProducerId",
+        23 => "OffsetForLeaderEpoch",
+        24 => "AddPartitionsToTxn",
+        25 => "AddOffsetsToTxn",
+        26 => "EndTxn",
+        27 => "WriteTxnMarkers",
+        28 => "TxnOffsetCommit",
+        29 => "DescribeAcls",
+        30 => "CreateAcls",
+        31 => "DeleteAcls",
+        32 => "DescribeConfigs",
+        33 => "AlterConfigs",
+        34 => "AlterReplicaLogDirs",
+        35 => "DescribeLogDirs",
+        36 => "SaslAuthenticate",
+        37 => "CreatePartitions",
+        38 => "CreateDelegationToken",
+        39 => "RenewDelegationToken",
+        40 => "ExpireDelegationToken",
+        41 => "DescribeDelegationToken",
+        42 => "DeleteGroups",
+        43 => "ElectLeaders",
+        44 => "IncrementalAlterConfigs",
+        45 => "AlterPartitionReassignments",
+        46 => "ListPartitionReassignments",
+        47 => "OffsetDelete",
+        48 => "DescribeClientQuotas",
+        49 => "AlterClientQuotas",
+        50 => "DescribeUserScramCredentials",
+        51 => "AlterUserScramCredentials",
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr};
+
+    use crate::serializable_packet::{ParsedPacket, SerializablePacket};
+    use crate::ParserContext;
+
+    use super::handle_kafka_packet;
+
+    const CLIENT: (IpAddr, u16) = (IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)), 50000);
+    const BROKER: (IpAddr, u16) = (IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2)), 9092);
+
+    #[test]
+    fn metadata_request_and_response() {
+        let mut state = ParserContext::new();
+
+        let body = [
+            &2i32.to_be_bytes()[..],
+            &string(b"orders"),
+            &string(b"payments"),
+        ]
+        .concat();
+        let request = build_test_request(3, 1, 42, &body);
+
+        let mut parsed_packet = ParsedPacket::new(0);
+        handle_kafka_packet(
+            &mut state,
+            CLIENT.0,
+            CLIENT.1,
+            BROKER.0,
+            BROKER.1,
+            true,
+            &request,
+            &mut parsed_packet,
+        );
+        match parsed_packet.get_application_layer_packet().unwrap() {
+            SerializablePacket::KafkaPacket(kafka_packet) => {
+                assert_eq!(kafka_packet.api_name.as_deref(), Some("Metadata"));
+                assert_eq!(kafka_packet.api_version, Some(1));
+                assert_eq!(kafka_packet.correlation_id, 42);
+                assert_eq!(kafka_packet.client_id.as_deref(), Some("wirefish"));
+                assert_eq!(kafka_packet.topics, vec!["orders", "payments"]);
+            }
+            _ => unreachable!(),
+        }
+
+        let response = [&8i32.to_be_bytes()[..], &42i32.to_be_bytes(), &[0u8; 4]].concat();
+        let mut parsed_packet = ParsedPacket::new(1);
+        handle_kafka_packet(
+            &mut state,
+            BROKER.0,
+            BROKER.1,
+            CLIENT.0,
+            CLIENT.1,
+            false,
+            &response,
+            &mut parsed_packet,
+        );
+        match parsed_packet.get_application_layer_packet().unwrap() {
+            SerializablePacket::KafkaPacket(kafka_packet) => {
+                assert!(!kafka_packet.request);
+                assert_eq!(kafka_packet.api_name.as_deref(), Some("Metadata"));
+                assert_eq!(kafka_packet.correlation_id, 42);
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn produce_request_topics() {
+        let mut state = ParserContext::new();
+
+        // Version 3: transactional id, acks, timeout, then topics with partitions
+        let body = [
+            &(-1i16).to_be_bytes()[..],
+            &1i16.to_be_bytes(),
+            &30000i32.to_be_bytes(),
+            &2i32.to_be_bytes(),
+            &string(b"orders"),
+            &1i32.to_be_bytes(),
+            &0i32.to_be_bytes(),
+            &3i32.to_be_bytes(),
+            &[0xaa, 0xbb, 0xcc],
+            &string(b"audit"),
+            &0i32.to_be_bytes(),
+        ]
+        .concat();
+        let request = build_test_request(0, 3, 7, &body);
+
+        let mut parsed_packet = ParsedPacket::new(0);
+        handle_kafka_packet(
+            &mut state,
+            CLIENT.0,
+            CLIENT.1,
+            BROKER.0,
+            BROKER.1,
+            true,
+            &request,
+            &mut parsed_packet,
+        );
+        match parsed_packet.get_application_layer_packet().unwrap() {
+            SerializablePacket::KafkaPacket(kafka_packet) => {
+                assert_eq!(kafka_packet.api_name.as_deref(), Some("Produce"));
+                assert_eq!(kafka_packet.topics, vec!["orders", "audit"]);
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn response_without_request() {
+        let mut state = ParserContext::new();
+
+        let response = [&8i32.to_be_bytes()[..], &99i32.to_be_bytes(), &[0u8; 4]].concat();
+
+        let mut parsed_packet = ParsedPacket::new(0);
+        handle_kafka_packet(
+            &mut state,
+            BROKER.0,
+            BROKER.1,
+            CLIENT.0,
+            CLIENT.1,
+            false,
+            &response,
+            &mut parsed_packet,
+        );
+        assert!(parsed_packet.get_application_layer_packet().is_none());
+    }
+
+    ///////////////////// Utils
+
+    fn string(value: &[u8]) -> Vec<u8> {
+        [&(value.len() as i16).to_be_bytes()[..], value].concat()
+    }
+
+    fn build_test_request(
+        api_key: i16,
+        api_version: i16,
+        correlation_id: i32,
+        body: &[u8],
+    ) -> Vec<u8> {
+        let message = [
+            &api_key.to_be_bytes()[..],
+            &api_version.to_be_bytes(),
+            &correlation_id.to_be_bytes(),
+            &string(b"wirefish"),
+            body,
+        ]
+        .concat();
+
+        [&(message.len() as i32).to_be_bytes()[..], &message].concat()
+    }
+}
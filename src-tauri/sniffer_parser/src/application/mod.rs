@@ -1,30 +1,163 @@
 //! Application layer Packet parsing
 
-use std::{cell::RefCell, collections::HashMap, net::IpAddr};
+use std::{
+    collections::BTreeMap,
+    net::IpAddr,
+    panic::{self, AssertUnwindSafe},
+    sync::{Arc, RwLock},
+    time::Instant,
+};
 
-use crate::serializable_packet::ParsedPacket;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
 
-use self::{dns::handle_dns_packet, http::handle_http_packet, tls::handle_tls_packet};
+use crate::{
+    flows::drop_flow_state,
+    references::link_application_packet,
+    serializable_packet::{ParsedPacket, SerializablePacket},
+    ParserContext,
+};
 
+use self::{
+    health::{panic_message, record_dissection, record_panic, DissectionOutcome, DissectorPanic},
+    heuristics::detect_protocol,
+    registry::{dissectors, DissectionContext, Dissector, Transport},
+};
+
+mod ber;
+pub mod cql;
+pub mod dhcp;
 pub mod dns;
+pub mod ethercat;
+pub mod gtp;
+pub mod health;
+pub mod heuristics;
+mod hpack;
 pub mod http;
+pub mod http2;
+pub mod iec61850;
+pub mod ike;
+pub mod iscsi;
+pub mod kafka;
+pub mod ntp;
+pub mod nvme_tcp;
+pub mod profinet;
+pub mod ptp;
+pub mod quic;
+pub mod registry;
+pub mod s7comm;
+pub mod snmp;
 pub mod tls;
+pub mod wireguard;
+pub mod zookeeper;
+
+/// Source and destination (IP, port) of the packets of a direction of a connection, the
+/// connection being identified by its [`crate::FlowKey`]
+pub(crate) type Flow = ((IpAddr, u16), (IpAddr, u16));
+
+/// Protocols decoded on the ports chosen by the user, instead of the well-known ones
+///
+/// Shared by all the threads, since the overrides are set while packets are being parsed.
+static PORT_OVERRIDES: RwLock<BTreeMap<u16, ApplicationProtocol>> = RwLock::new(BTreeMap::new());
+
+/// Application-layer protocols carried over TCP/UDP, supported by wirefish
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum ApplicationProtocol {
+    Http,
+    Http2,
+    Tls,
+    Dns,
+    Ptp,
+    S7comm,
+    Iscsi,
+    NvmeTcp,
+    Cql,
+    Kafka,
+    Zookeeper,
+    Quic,
+    Dhcp,
+    Snmp,
+    Gtp,
+    Ike,
+    WireGuard,
+    Ntp,
+}
 
-thread_local!(
-    pub(crate) static ACTIVE_HTTP_PARSERS: RefCell<
-        HashMap<((IpAddr, u16), (IpAddr, u16)), Vec<u8>>,
-    > = RefCell::new(HashMap::new());
-    pub(crate) static ACTIVE_TLS_PARSERS: RefCell<
-        HashMap<((IpAddr, u16), (IpAddr, u16)), Vec<u8>>,
-    > = RefCell::new(HashMap::new());
-);
+impl ApplicationProtocol {
+    /// Get the name of the dissector of the protocol
+    pub fn name(&self) -> String {
+        let name = match self {
+            ApplicationProtocol::Http => "http",
+            ApplicationProtocol::Http2 => "http2",
+            ApplicationProtocol::Tls => "tls",
+            ApplicationProtocol::Dns => "dns",
+            ApplicationProtocol::Ptp => "ptp",
+            ApplicationProtocol::S7comm => "s7comm",
+            ApplicationProtocol::Iscsi => "iscsi",
+            ApplicationProtocol::NvmeTcp => "nvme_tcp",
+            ApplicationProtocol::Cql => "cql",
+            ApplicationProtocol::Kafka => "kafka",
+            ApplicationProtocol::Zookeeper => "zookeeper",
+            ApplicationProtocol::Quic => "quic",
+            ApplicationProtocol::Dhcp => "dhcp",
+            ApplicationProtocol::Snmp => "snmp",
+            ApplicationProtocol::Gtp => "gtp",
+            ApplicationProtocol::Ike => "ike",
+            ApplicationProtocol::WireGuard => "wireguard",
+            ApplicationProtocol::Ntp => "ntp",
+        };
+
+        name.to_owned()
+    }
+
+    /// Get the transport protocols the protocol is carried over
+    pub fn transports(&self) -> &'static [Transport] {
+        match self {
+            ApplicationProtocol::Dns => &[Transport::Tcp, Transport::Udp],
+            ApplicationProtocol::Ptp
+            | ApplicationProtocol::Quic
+            | ApplicationProtocol::Dhcp
+            | ApplicationProtocol::Snmp
+            | ApplicationProtocol::Gtp
+            | ApplicationProtocol::Ike
+            | ApplicationProtocol::WireGuard
+            | ApplicationProtocol::Ntp => &[Transport::Udp],
+            _ => &[Transport::Tcp],
+        }
+    }
+}
 
 /// IANA Well Known TCP/UDP Ports
 #[allow(non_snake_case)]
 mod WellKnownPorts {
     pub const HTTP_PORT: u16 = 80;
+    pub const HTTP_PROXY_PORT: u16 = 3128;
+    pub const DOCKER_REGISTRY_PORT: u16 = 5000;
+    pub const HTTP_ALT_PORT: u16 = 8080;
     pub const TLS_PORT: u16 = 443;
+    pub const QUIC_PORT: u16 = 443;
     pub const DNS_PORT: u16 = 53;
+    pub const MDNS_PORT: u16 = 5353;
+    pub const PTP_EVENT_PORT: u16 = 319;
+    pub const PTP_GENERAL_PORT: u16 = 320;
+    pub const DHCP_SERVER_PORT: u16 = 67;
+    pub const DHCP_CLIENT_PORT: u16 = 68;
+    pub const ISO_TSAP_PORT: u16 = 102;
+    pub const NTP_PORT: u16 = 123;
+    pub const SNMP_PORT: u16 = 161;
+    pub const SNMP_TRAP_PORT: u16 = 162;
+    pub const IKE_PORT: u16 = 500;
+    pub const GTP_C_PORT: u16 = 2123;
+    pub const GTP_U_PORT: u16 = 2152;
+    pub const ZOOKEEPER_PORT: u16 = 2181;
+    pub const ETCD_PORT: u16 = 2379;
+    pub const ISCSI_PORT: u16 = 3260;
+    pub const NVME_TCP_PORT: u16 = 4420;
+    pub const IKE_NAT_T_PORT: u16 = 4500;
+    pub const CQL_PORT: u16 = 9042;
+    pub const KAFKA_PORT: u16 = 9092;
+    pub const WIREGUARD_PORT: u16 = 51820;
 }
 
 // HTTP ----------------------------------------------------------------------------------------------------------------
@@ -53,50 +186,274 @@ pub enum HttpPacketType {
     Response,
 }
 
+/// Replace the table of the protocols to decode on specific ports ("decode as")
+pub fn set_port_overrides(overrides: BTreeMap<u16, ApplicationProtocol>) {
+    *PORT_OVERRIDES.write().unwrap() = overrides;
+}
+
+/// Get the table of the protocols to decode on specific ports
+pub fn get_port_overrides() -> BTreeMap<u16, ApplicationProtocol> {
+    PORT_OVERRIDES.read().unwrap().clone()
+}
+
+/// Get the dissector of a packet from its ports, overridden by the user or well known, and whether
+/// the packet is a request (i.e. sent to the port of the protocol)
+///
+/// Overrides of protocols not carried over the transport protocol of the packet are ignored.
+fn dissector_by_ports(
+    source_port: u16,
+    dest_port: u16,
+    transport: Transport,
+) -> Option<(Arc<dyn Dissector>, bool)> {
+    let registry = dissectors().read().unwrap();
+
+    let overrides = PORT_OVERRIDES.read().unwrap();
+    let overridden = |port| {
+        overrides
+            .get(port)
+            .and_then(|protocol| registry.get(&protocol.name()))
+            .filter(|dissector| dissector.transports().contains(&transport))
+    };
+    if let Some(dissector) = overridden(&dest_port) {
+        return Some((dissector, true));
+    }
+    if let Some(dissector) = overridden(&source_port) {
+        return Some((dissector, false));
+    }
+
+    match (
+        registry.by_port(source_port, transport),
+        registry.by_port(dest_port, transport),
+    ) {
+        (Some((source, _)), Some((dest, dissector))) if dest <= source => Some((dissector, true)),
+        (Some((_, dissector)), _) => Some((dissector, false)),
+        (None, Some((_, dissector))) => Some((dissector, true)),
+        (None, None) => None,
+    }
+}
+
 /// Build an application-layer packet from a transport-layer one, save it in a Parsed Packet
+///
+/// The dissector is chosen by the ports of the packet, falling back to the signature of its
+/// payload when no port is associated with a protocol.
+/// A panic of the dissector is caught and reported: the packet is left without its application
+/// layer, and the state kept for its flow is dropped so that the following packets start afresh.
+#[allow(clippy::too_many_arguments)]
 pub fn handle_application_protocol(
+    state: &mut ParserContext,
     source_ip: IpAddr,
     source_port: u16,
     dest_ip: IpAddr,
     dest_port: u16,
+    transport: Transport,
     is_fin: bool,
     packet: &[u8],
     parsed_packet: &mut ParsedPacket,
 ) {
-    match (source_port, dest_port) {
-        (WellKnownPorts::HTTP_PORT, _) | (_, WellKnownPorts::HTTP_PORT) => {
-            let http_type = match dest_port {
-                WellKnownPorts::HTTP_PORT => HttpPacketType::Request,
-                _ => HttpPacketType::Response,
+    let dissector = dissector_by_ports(source_port, dest_port, transport).or_else(|| {
+        detect_protocol(
+            state,
+            source_ip,
+            source_port,
+            dest_ip,
+            dest_port,
+            transport,
+            packet,
+        )
+    });
+    let (dissector, is_request) = match dissector {
+        Some(dissector) => dissector,
+        None => return,
+    };
+
+    let context = DissectionContext {
+        source_ip,
+        source_port,
+        dest_ip,
+        dest_port,
+        is_request,
+        is_fin,
+        meta: parsed_packet.get_meta(),
+    };
+    let start = Instant::now();
+    let dissection = panic::catch_unwind(AssertUnwindSafe(|| {
+        dissector.dissect(state, &context, packet)
+    }));
+    let parse_time = start.elapsed();
+
+    match dissection {
+        Ok(application_layer_packet) => {
+            let outcome = match application_layer_packet {
+                Some(SerializablePacket::MalformedPacket(_)) => DissectionOutcome::Malformed,
+                _ => DissectionOutcome::Dissected,
             };
+            record_dissection(dissector.name(), parse_time, outcome);
 
-            handle_http_packet(
+            if let Some(application_layer_packet) = application_layer_packet {
+                parsed_packet.set_application_layer_packet(Some(application_layer_packet));
+                link_application_packet(
+                    state,
+                    parsed_packet,
+                    (source_ip, source_port),
+                    (dest_ip, dest_port),
+                );
+            }
+        }
+        Err(payload) => {
+            let message = panic_message(payload.as_ref());
+            warn!(
+                "Dissector {} panicked on {}:{} -> {}:{}: {}",
+                dissector.name(),
+                source_ip,
+                source_port,
+                dest_ip,
+                dest_port,
+                message
+            );
+            record_dissection(dissector.name(), parse_time, DissectionOutcome::Panicked);
+            record_panic(DissectorPanic {
+                dissector: dissector.name().to_owned(),
                 source_ip,
                 source_port,
                 dest_ip,
                 dest_port,
-                http_type,
-                is_fin,
-                packet,
-                parsed_packet,
-            )
+                payload_length: packet.len(),
+                message,
+            });
+            drop_flow_state(state, (source_ip, source_port), (dest_ip, dest_port));
         }
-        (WellKnownPorts::TLS_PORT, _) | (_, WellKnownPorts::TLS_PORT) => handle_tls_packet(
-            source_ip,
-            source_port,
-            dest_ip,
-            dest_port,
-            packet,
-            parsed_packet,
-        ),
-        (WellKnownPorts::DNS_PORT, _) | (_, WellKnownPorts::DNS_PORT) => handle_dns_packet(
-            source_ip,
-            source_port,
-            dest_ip,
-            dest_port,
-            packet,
-            parsed_packet,
-        ),
-        _ => (),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::net::{IpAddr, Ipv4Addr};
+    use std::sync::Arc;
+
+    use super::health::{get_dissector_panics, get_parser_health};
+    use super::registry::{register_dissector, DissectionContext, Dissector, Transport};
+    use super::{
+        dissector_by_ports, handle_application_protocol, set_port_overrides, ApplicationProtocol,
+    };
+    use crate::serializable_packet::{ParsedPacket, SerializablePacket};
+    use crate::{FlowKey, ParserContext};
+
+    #[test]
+    fn well_known_ports() {
+        assert_eq!(
+            protocol_by_ports(50000, 8080),
+            Some(("http".to_owned(), true))
+        );
+        assert_eq!(
+            protocol_by_ports(9092, 50000),
+            Some(("kafka".to_owned(), false))
+        );
+        // HTTP takes precedence over TLS
+        assert_eq!(protocol_by_ports(443, 80), Some(("http".to_owned(), true)));
+        assert_eq!(protocol_by_ports(50000, 50001), None);
+    }
+
+    #[test]
+    fn overridden_ports() {
+        set_port_overrides(BTreeMap::from([(18443, ApplicationProtocol::Tls)]));
+
+        assert_eq!(
+            protocol_by_ports(50000, 18443),
+            Some(("tls".to_owned(), true))
+        );
+        assert_eq!(
+            protocol_by_ports(18443, 80),
+            Some(("tls".to_owned(), false))
+        );
+
+        set_port_overrides(BTreeMap::new());
+        assert_eq!(
+            protocol_by_ports(18443, 80),
+            Some(("http".to_owned(), true))
+        );
+    }
+
+    #[test]
+    fn ports_of_transport() {
+        let name = |transport| {
+            dissector_by_ports(50000, 443, transport)
+                .map(|(dissector, _)| dissector.name().to_owned())
+        };
+        assert_eq!(name(Transport::Tcp), Some("tls".to_owned()));
+        assert_eq!(name(Transport::Udp), Some("quic".to_owned()));
+    }
+
+    #[test]
+    fn dissector_panic_is_caught() {
+        register_dissector(Arc::new(PanickingDissector));
+        let client = (IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)), 50000);
+        let server = (IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2)), 65533);
+        let (key, direction) = FlowKey::of(server, client);
+        let mut state = ParserContext::new();
+        state
+            .http_parsers
+            .entry(key)
+            .or_default()
+            .buffer_mut(direction)
+            .push(1);
+
+        let mut parsed_packet = ParsedPacket::new(0);
+        handle_application_protocol(
+            &mut state,
+            client.0,
+            client.1,
+            server.0,
+            server.1,
+            Transport::Tcp,
+            false,
+            b"payload",
+            &mut parsed_packet,
+        );
+
+        assert!(parsed_packet.get_application_layer_packet().is_none());
+        // The state of the flow is dropped in both directions
+        assert!(state.http_parsers.is_empty());
+        let panic = get_dissector_panics()
+            .into_iter()
+            .find(|panic| panic.dissector == "panicking")
+            .unwrap();
+        assert_eq!(panic.source_port, 50000);
+        assert_eq!(panic.dest_port, 65533);
+        assert_eq!(panic.payload_length, 7);
+        assert_eq!(panic.message, "index out of bounds");
+        let health = get_parser_health()
+            .into_iter()
+            .find(|health| health.dissector == "panicking")
+            .unwrap();
+        assert_eq!(health.panics, 1);
+    }
+
+    ///////////////////// Utils
+
+    struct PanickingDissector;
+
+    impl Dissector for PanickingDissector {
+        fn name(&self) -> &str {
+            "panicking"
+        }
+
+        fn ports(&self) -> &[u16] {
+            &[65533]
+        }
+
+        fn dissect(
+            &self,
+            _state: &mut ParserContext,
+            _context: &DissectionContext,
+            _payload: &[u8],
+        ) -> Option<SerializablePacket> {
+            panic!("index out of bounds")
+        }
+    }
+
+    fn protocol_by_ports(source_port: u16, dest_port: u16) -> Option<(String, bool)> {
+        dissector_by_ports(source_port, dest_port, Transport::Tcp)
+            .map(|(dissector, is_request)| (dissector.name().to_owned(), is_request))
     }
 }
@@ -0,0 +1,325 @@
+//! NTP Packet parsing
+//!
+//! NTP messages are carried over UDP port 123. Their first byte holds the version and the mode of
+//! the message, which tells its format:
+//! - modes 1 to 5 (symmetric active and passive, client, server, broadcast): time messages, with
+//!   the stratum, poll interval and precision of the clock of the sender, and its timestamps
+//! - mode 6: control messages (as sent by ntpq), reading and writing the variables of a server
+//! - mode 7: private messages of ntpd (as sent by ntpdc), among which the monlist request, which
+//!   returns the last clients of the server in up to 100 items and is abused for amplification
+//!
+//! Timestamps are in seconds since the NTP epoch (1 January 1900), with their fraction.
+
+use std::net::Ipv4Addr;
+
+use tracing::debug;
+
+use crate::serializable_packet::application::SerializableNtpPacket;
+use crate::serializable_packet::{ParsedPacket, SerializablePacket};
+
+/// NTP Modes
+#[allow(non_snake_case)]
+mod Modes {
+    pub const SYMMETRIC_ACTIVE: u8 = 1;
+    pub const SYMMETRIC_PASSIVE: u8 = 2;
+    pub const CLIENT: u8 = 3;
+    pub const SERVER: u8 = 4;
+    pub const BROADCAST: u8 = 5;
+    pub const CONTROL: u8 = 6;
+    pub const PRIVATE: u8 = 7;
+}
+
+/// Length of a time message, without its extension fields and authenticator
+const TIME_MESSAGE_LENGTH: usize = 48;
+
+/// Length of the header of a control message
+const CONTROL_HEADER_LENGTH: usize = 12;
+
+/// Length of the header of a private message
+const PRIVATE_HEADER_LENGTH: usize = 8;
+
+const FLAG_RESPONSE: u8 = 0x80;
+
+/// Build an NTP packet from a transport-layer packet, save it in a Parsed Packet
+pub fn handle_ntp_packet(packet: &[u8], parsed_packet: &mut ParsedPacket) {
+    match parse_ntp_message(packet) {
+        Some(ntp_packet) => {
+            debug!(
+                "NTP Packet: version {}, {}; request: {:?}",
+                ntp_packet.version, ntp_packet.mode, ntp_packet.request
+            );
+
+            parsed_packet
+                .set_application_layer_packet(Some(SerializablePacket::NtpPacket(ntp_packet)));
+        }
+        None => {
+            debug!("Malformed NTP Packet");
+            parsed_packet.set_application_layer_packet(Some(SerializablePacket::MalformedPacket(
+                "Malformed NTP Packet".to_string(),
+            )));
+        }
+    }
+}
+
+/// Parse an NTP message
+pub fn parse_ntp_message(packet: &[u8]) -> Option<SerializableNtpPacket> {
+    let first = *packet.first()?;
+    let (version, mode) = ((first >> 3) & 0x07, first & 0x07);
+    if version == 0 || mode == 0 {
+        return None;
+    }
+
+    let mut ntp_packet = SerializableNtpPacket {
+        leap_indicator: None,
+        version,
+        mode: mode_to_string(mode),
+        stratum: None,
+        poll: None,
+        precision: None,
+        root_delay: None,
+        root_dispersion: None,
+        reference_id: None,
+        reference_timestamp: None,
+        origin_timestamp: None,
+        receive_timestamp: None,
+        transmit_timestamp: None,
+        response: false,
+        sequence: None,
+        request: None,
+        implementation: None,
+        item_count: None,
+        item_size: None,
+        length: packet.len(),
+    };
+    let u16_at = |offset: usize| u16::from_be_bytes([packet[offset], packet[offset + 1]]);
+
+    match mode {
+        Modes::CONTROL => {
+            if packet.len() < CONTROL_HEADER_LENGTH {
+                return None;
+            }
+            ntp_packet.leap_indicator = Some(first >> 6);
+            ntp_packet.response = packet[1] & FLAG_RESPONSE != 0;
+            ntp_packet.sequence = Some(u16_at(2));
+            ntp_packet.request = Some(opcode_to_string(packet[1] & 0x1f));
+        }
+        // The leap indicator bits hold the response and more flags instead
+        Modes::PRIVATE => {
+            if packet.len() < PRIVATE_HEADER_LENGTH {
+                return None;
+            }
+            ntp_packet.response = first & FLAG_RESPONSE != 0;
+            ntp_packet.sequence = Some((packet[1] & 0x7f) as u16);
+            ntp_packet.implementation = Some(packet[2]);
+            ntp_packet.request = Some(request_code_to_string(packet[3]));
+            ntp_packet.item_count = Some(u16_at(4) & 0x0fff);
+            ntp_packet.item_size = Some(u16_at(6) & 0x0fff);
+        }
+        _ => {
+            if packet.len() < TIME_MESSAGE_LENGTH {
+                return None;
+            }
+            let stratum = packet[1];
+            ntp_packet.leap_indicator = Some(first >> 6);
+            ntp_packet.stratum = Some(stratum);
+            ntp_packet.poll = Some(packet[2] as i8);
+            ntp_packet.precision = Some(packet[3] as i8);
+            ntp_packet.root_delay = Some(short_format(&packet[4..8]));
+            ntp_packet.root_dispersion = Some(short_format(&packet[8..12]));
+            ntp_packet.reference_id = Some(reference_id(stratum, &packet[12..16]));
+            ntp_packet.reference_timestamp = timestamp_format(&packet[16..24]);
+            ntp_packet.origin_timestamp = timestamp_format(&packet[24..32]);
+            ntp_packet.receive_timestamp = timestamp_format(&packet[32..40]);
+            ntp_packet.transmit_timestamp = timestamp_format(&packet[40..48]);
+        }
+    }
+
+    Some(ntp_packet)
+}
+
+/// Get a duration in the NTP short format: 16 bits of seconds and 16 bits of fraction
+fn short_format(bytes: &[u8]) -> f64 {
+    u32::from_be_bytes(bytes.try_into().unwrap()) as f64 / 65536.0
+}
+
+/// Get a timestamp in the NTP timestamp format: 32 bits of seconds and 32 bits of fraction, none
+/// when zero (i.e. unknown)
+fn timestamp_format(bytes: &[u8]) -> Option<f64> {
+    let timestamp = u64::from_be_bytes(bytes.try_into().unwrap());
+
+    (timestamp != 0).then_some(timestamp as f64 / 4_294_967_296.0)
+}
+
+/// Get the reference ID: the code of the reference clock of the stratum 1 servers, or the kiss
+/// code of the stratum 0 messages, else the (IPv4) address of the upstream server
+fn reference_id(stratum: u8, bytes: &[u8]) -> String {
+    if stratum <= 1 {
+        bytes
+            .iter()
+            .take_while(|byte| **byte != 0)
+            .map(|byte| *byte as char)
+            .collect()
+    } else {
+        Ipv4Addr::new(bytes[0], bytes[1], bytes[2], bytes[3]).to_string()
+    }
+}
+
+/// Get the name of a mode
+fn mode_to_string(mode: u8) -> String {
+    let name = match mode {
+        Modes::SYMMETRIC_ACTIVE => "Symmetric Active",
+        Modes::SYMMETRIC_PASSIVE => "Symmetric Passive",
+        Modes::CLIENT => "Client",
+        Modes::SERVER => "Server",
+        Modes::BROADCAST => "Broadcast",
+        Modes::CONTROL => "Control",
+        _ => "Private",
+    };
+
+    name.to_owned()
+}
+
+/// Get the name of the opcode of a control message
+fn opcode_to_string(opcode: u8) -> String {
+    let name = match opcode {
+        1 => "READSTAT",
+        2 => "READVAR",
+        3 => "WRITEVAR",
+        4 => "READCLOCK",
+        5 => "WRITECLOCK",
+        6 => "SETTRAP",
+        7 => "ASYNCMSG",
+        8 => "CONFIGURE",
+        9 => "SAVECONFIG",
+        10 => "READ_MRU",
+        11 => "READ_ORDLIST_A",
+        12 => "REQ_NONCE",
+        31 => "UNSETTRAP",
+        _ => return format!("Unknown ({})", opcode),
+    };
+
+    name.to_owned()
+}
+
+/// Get the name of the request code of a private message
+fn request_code_to_string(request_code: u8) -> String {
+    let name = match request_code {
+        0 => "PEER_LIST",
+        1 => "PEER_LIST_SUM",
+        2 => "PEER_INFO",
+        3 => "PEER_STATS",
+        4 => "SYS_INFO",
+        5 => "SYS_STATS",
+        6 => "IO_STATS",
+        7 => "MEM_STATS",
+        8 => "LOOP_INFO",
+        9 => "TIMER_STATS",
+        10 => "CONFIG",
+        11 => "UNCONFIG",
+        12 => "SET_SYS_FLAG",
+        13 => "CLR_SYS_FLAG",
+        16 => "GET_RESTRICT",
+        17 => "RESADDFLAGS",
+        18 => "RESSUBFLAGS",
+        19 => "UNRESTRICT",
+        20 => "MON_GETLIST",
+        21 => "RESET_STATS",
+        22 => "RESET_PEER",
+        23 => "REREAD_KEYS",
+        26 => "TRUSTKEY",
+        27 => "UNTRUSTKEY",
+        28 => "AUTHINFO",
+        29 => "TRAPS",
+        30 => "ADD_TRAP",
+        31 => "CLR_TRAP",
+        32 => "REQUEST_KEY",
+        33 => "CONTROL_KEY",
+        34 => "GET_CTLSTATS",
+        36 => "GET_CLOCKINFO",
+        37 => "SET_CLKFUDGE",
+        38 => "GET_KERNEL",
+        39 => "GET_CLKBUGINFO",
+        42 => "MON_GETLIST_1",
+        43 => "HOSTNAME_ASSOCID",
+        44 => "IF_STATS",
+        45 => "IF_RELOAD",
+        _ => return format!("Unknown ({})", request_code),
+    };
+
+    name.to_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_ntp_message;
+
+    #[test]
+    fn time_messages() {
+        let mut request = vec![0x00; 48];
+        // Version 4, client
+        request[0] = 0x23;
+        request[40..48].copy_from_slice(&[0xe9, 0x00, 0x00, 0x00, 0x80, 0x00, 0x00, 0x00]);
+        let request = parse_ntp_message(&request).unwrap();
+        assert_eq!(request.version, 4);
+        assert_eq!(request.mode, "Client");
+        assert_eq!(request.leap_indicator, Some(0));
+        assert_eq!(request.stratum, Some(0));
+        assert_eq!(request.origin_timestamp, None);
+        assert_eq!(request.transmit_timestamp, Some(3909091328.5));
+
+        let mut response = vec![0x00; 48];
+        // No warning, version 3, server
+        response[..4].copy_from_slice(&[0x1c, 0x02, 0x06, 0xe9]);
+        response[4..8].copy_from_slice(&[0x00, 0x00, 0x80, 0x00]);
+        response[12..16].copy_from_slice(&[192, 168, 1, 1]);
+        let response = parse_ntp_message(&response).unwrap();
+        assert_eq!(response.version, 3);
+        assert_eq!(response.mode, "Server");
+        assert_eq!(response.stratum, Some(2));
+        assert_eq!(response.poll, Some(6));
+        assert_eq!(response.precision, Some(-23));
+        assert_eq!(response.root_delay, Some(0.5));
+        assert_eq!(response.reference_id, Some("192.168.1.1".to_owned()));
+        assert!(!response.response);
+
+        let mut reference_clock = vec![0x00; 48];
+        reference_clock[..2].copy_from_slice(&[0x24, 0x01]);
+        reference_clock[12..16].copy_from_slice(b"GPS\0");
+        let reference_clock = parse_ntp_message(&reference_clock).unwrap();
+        assert_eq!(reference_clock.reference_id, Some("GPS".to_owned()));
+
+        // Truncated
+        assert!(parse_ntp_message(&[0x23; 47]).is_none());
+        assert!(parse_ntp_message(&[]).is_none());
+    }
+
+    #[test]
+    fn monlist_messages() {
+        // Version 2, private, implementation XNTPD, MON_GETLIST_1
+        let request = parse_ntp_message(&[0x17, 0x00, 0x03, 0x2a, 0x00, 0x00, 0x00, 0x00]).unwrap();
+        assert_eq!(request.version, 2);
+        assert_eq!(request.mode, "Private");
+        assert!(!request.response);
+        assert_eq!(request.implementation, Some(3));
+        assert_eq!(request.request, Some("MON_GETLIST_1".to_owned()));
+        assert_eq!(request.leap_indicator, None);
+
+        let mut response = vec![0x97, 0x00, 0x03, 0x2a, 0x00, 0x06, 0x00, 0x48];
+        response.extend([0x00; 6 * 72]);
+        let response = parse_ntp_message(&response).unwrap();
+        assert!(response.response);
+        assert_eq!(response.item_count, Some(6));
+        assert_eq!(response.item_size, Some(72));
+        assert_eq!(response.length, 440);
+
+        // Version 2, control, READVAR response
+        let control = parse_ntp_message(&[
+            0x16, 0x82, 0x00, 0x01, 0x06, 0x18, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        ])
+        .unwrap();
+        assert_eq!(control.mode, "Control");
+        assert!(control.response);
+        assert_eq!(control.sequence, Some(1));
+        assert_eq!(control.request, Some("READVAR".to_owned()));
+    }
+}
@@ -0,0 +1,262 @@
+//! NVMe/TCP Packet parsing
+//!
+//! NVMe/TCP PDUs (TCP port 4420) start with an 8-bytes common header (type, flags, header
+//! length, data offset and PDU length, little endian) followed by the PDU specific header:
+//! command capsules carry a submission queue entry, response capsules a completion queue entry.
+//! All the PDUs starting in a segment are dissected: segments continuing the data of a
+//! previous PDU are not dissected.
+
+use std::net::IpAddr;
+
+use tracing::debug;
+
+use crate::serializable_packet::application::{NvmeTcpPdu, SerializableNvmeTcpPacket};
+use crate::serializable_packet::{ParsedPacket, SerializablePacket};
+
+const COMMON_HEADER_LENGTH: usize = 8;
+
+/// NVMe/TCP PDU types
+#[allow(non_snake_case)]
+mod PduTypes {
+    pub const CAPSULE_COMMAND: u8 = 0x04;
+    pub const CAPSULE_RESPONSE: u8 = 0x05;
+    pub const H2C_DATA: u8 = 0x06;
+    pub const C2H_DATA: u8 = 0x07;
+    pub const R2T: u8 = 0x09;
+}
+
+/// NVMe over Fabrics command opcode
+const FABRICS_OPCODE: u8 = 0x7f;
+
+/// Build an NVMe/TCP packet from a transport-layer packet, save it in a Parsed Packet
+pub fn handle_nvme_tcp_packet(
+    source_ip: IpAddr,
+    source_port: u16,
+    dest_ip: IpAddr,
+    dest_port: u16,
+    packet: &[u8],
+    parsed_packet: &mut ParsedPacket,
+) {
+    let pdus = parse_nvme_tcp_pdus(packet);
+    if pdus.is_empty() {
+        return;
+    }
+
+    debug!(
+        "NVMe/TCP Packet: {}:{} > {}:{}; PDUs: {:?}",
+        source_ip,
+        source_port,
+        dest_ip,
+        dest_port,
+        pdus.iter().map(|pdu| &pdu.pdu_type).collect::<Vec<_>>()
+    );
+
+    parsed_packet.set_application_layer_packet(Some(SerializablePacket::NvmeTcpPacket(
+        SerializableNvmeTcpPacket { pdus },
+    )));
+}
+
+fn parse_nvme_tcp_pdus(mut packet: &[u8]) -> Vec<NvmeTcpPdu> {
+    let mut pdus = vec![];
+
+    while let Some(pdu) = parse_pdu(packet) {
+        let pdu_length = pdu.pdu_length as usize;
+
+        pdus.push(pdu);
+        match packet.get(pdu_length..) {
+            Some(rest) => packet = rest,
+            None => break,
+        }
+    }
+
+    pdus
+}
+
+fn parse_pdu(packet: &[u8]) -> Option<NvmeTcpPdu> {
+    if packet.len() < COMMON_HEADER_LENGTH {
+        return None;
+    }
+
+    let pdu_type = packet[0];
+    let header_length = packet[2];
+    let pdu_length = u32::from_le_bytes(packet[4..8].try_into().unwrap());
+
+    // Check the header length of the PDU type, to tell PDUs from continuation data
+    let expected_header_length = match pdu_type {
+        0x00 | 0x01 => 128,
+        0x02 | 0x03 | PduTypes::CAPSULE_RESPONSE | PduTypes::R2T => 24,
+        PduTypes::CAPSULE_COMMAND => 72,
+        PduTypes::H2C_DATA | PduTypes::C2H_DATA => 24,
+        _ => return None,
+    };
+    if header_length != expected_header_length || pdu_length < header_length as u32 {
+        return None;
+    }
+
+    let mut pdu = NvmeTcpPdu {
+        pdu_type: pdu_type_name(pdu_type).to_owned(),
+        flags: packet[1],
+        header_length,
+        pdu_data_offset: packet[3],
+        pdu_length,
+        command_id: None,
+        opcode: None,
+        command: None,
+        namespace_id: None,
+        status: None,
+        data_offset: None,
+        data_length: None,
+    };
+
+    let header = match packet.get(COMMON_HEADER_LENGTH..header_length as usize) {
+        Some(header) => header,
+        // PDU specific header in the next segment
+        None => return Some(pdu),
+    };
+    let half = |offset: usize| u16::from_le_bytes([header[offset], header[offset + 1]]);
+    let word = |offset: usize| u32::from_le_bytes(header[offset..offset + 4].try_into().unwrap());
+
+    match pdu_type {
+        PduTypes::CAPSULE_COMMAND => {
+            let opcode = header[0];
+            let namespace_id = word(4);
+            pdu.opcode = Some(opcode);
+            pdu.command_id = Some(half(2));
+            pdu.namespace_id = Some(namespace_id);
+            pdu.command = Some(command_name(opcode, header[4], namespace_id));
+        }
+        PduTypes::CAPSULE_RESPONSE => {
+            pdu.command_id = Some(half(12));
+            pdu.status = Some(half(14) >> 1);
+        }
+        PduTypes::H2C_DATA | PduTypes::C2H_DATA | PduTypes::R2T => {
+            pdu.command_id = Some(half(0));
+            pdu.data_offset = Some(word(4));
+            pdu.data_length = Some(word(8));
+        }
+        _ => (),
+    }
+
+    Some(pdu)
+}
+
+fn pdu_type_name(pdu_type: u8) -> &'static str {
+    match pdu_type {
+        0x00 => "ICReq",
+        0x01 => "ICResp",
+        0x02 => "H2CTermReq",
+        0x03 => "C2HTermReq",
+        PduTypes::CAPSULE_COMMAND => "CapsuleCmd",
+        PduTypes::CAPSULE_RESPONSE => "CapsuleResp",
+        PduTypes::H2C_DATA => "H2CData",
+        PduTypes::C2H_DATA => "C2HData",
+        PduTypes::R2T => "R2T",
+        _ => "Unknown",
+    }
+}
+
+/// Get the name of a command: I/O commands address a namespace, admin ones usually do not
+fn command_name(opcode: u8, fabrics_type: u8, namespace_id: u32) -> String {
+    if opcode == FABRICS_OPCODE {
+        return match fabrics_type {
+            0x00 => "Fabrics Property Set".to_owned(),
+            0x01 => "Fabrics Connect".to_owned(),
+            0x04 => "Fabrics Property Get".to_owned(),
+            0x05 => "Fabrics Authentication Send".to_owned(),
+            0x06 => "Fabrics Authentication Receive".to_owned(),
+            0x08 => "Fabrics Disconnect".to_owned(),
+            _ => format!("Fabrics ({:#04x})", fabrics_type),
+        };
+    }
+
+    let name = match (namespace_id != 0, opcode) {
+        (true, 0x00) => "Flush",
+        (true, 0x01) => "Write",
+        (true, 0x02) => "Read",
+        (true, 0x04) => "Write Uncorrectable",
+        (true, 0x05) => "Compare",
+        (true, 0x08) => "Write Zeroes",
+        (true, 0x09) => "Dataset Management",
+        (_, 0x06) => "Identify",
+        (false, 0x02) => "Get Log Page",
+        (false, 0x08) => "Abort",
+        (false, 0x09) => "Set Features",
+        (false, 0x0a) => "Get Features",
+        (false, 0x0c) => "Asynchronous Event Request",
+        (false, 0x18) => "Keep Alive",
+        _ => return format!("Unknown ({:#04x})", opcode),
+    };
+
+    name.to_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr};
+
+    use crate::serializable_packet::{ParsedPacket, SerializablePacket};
+
+    use super::handle_nvme_tcp_packet;
+
+    #[test]
+    fn read_command_capsule() {
+        let mut capsule = build_test_common_header(0x04, 72, 72);
+        let mut sqe = vec![0u8; 64];
+        sqe[0] = 0x02;
+        sqe[2..4].copy_from_slice(&0x0010u16.to_le_bytes());
+        sqe[4..8].copy_from_slice(&1u32.to_le_bytes());
+        capsule.extend_from_slice(&sqe);
+
+        let mut response = build_test_common_header(0x05, 24, 24);
+        let mut cqe = vec![0u8; 16];
+        cqe[12..14].copy_from_slice(&0x0010u16.to_le_bytes());
+        response.extend_from_slice(&cqe);
+
+        let parsed_packet = parse(&[capsule, response].concat());
+        match parsed_packet.get_application_layer_packet().unwrap() {
+            SerializablePacket::NvmeTcpPacket(nvme_tcp_packet) => {
+                assert_eq!(nvme_tcp_packet.pdus.len(), 2);
+
+                let command = &nvme_tcp_packet.pdus[0];
+                assert_eq!(command.pdu_type, "CapsuleCmd");
+                assert_eq!(command.command.as_deref(), Some("Read"));
+                assert_eq!(command.command_id, Some(0x10));
+                assert_eq!(command.namespace_id, Some(1));
+
+                let response = &nvme_tcp_packet.pdus[1];
+                assert_eq!(response.pdu_type, "CapsuleResp");
+                assert_eq!(response.command_id, Some(0x10));
+                assert_eq!(response.status, Some(0));
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn continuation_segment() {
+        let parsed_packet = parse(&[0x07; 64]);
+        assert!(parsed_packet.get_application_layer_packet().is_none());
+    }
+
+    ///////////////////// Utils
+
+    fn parse(packet: &[u8]) -> ParsedPacket {
+        let mut parsed_packet = ParsedPacket::new(0);
+        handle_nvme_tcp_packet(
+            IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)),
+            50000,
+            IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2)),
+            4420,
+            packet,
+            &mut parsed_packet,
+        );
+
+        parsed_packet
+    }
+
+    fn build_test_common_header(pdu_type: u8, header_length: u8, pdu_length: u32) -> Vec<u8> {
+        let mut header = vec![pdu_type, 0x00, header_length, 0x00];
+        header.extend_from_slice(&pdu_length.to_le_bytes());
+        header
+    }
+}
@@ -0,0 +1,147 @@
+//! PROFINET Real-Time Packet parsing
+//!
+//! Real-time frames are carried directly over Ethernet (ethertype 0x8892) and start with a
+//! frame ID. Cyclic frames end with an APDU status: cycle counter, data status and transfer status.
+
+use tracing::debug;
+
+use crate::serializable_packet::application::SerializableProfinetPacket;
+use crate::serializable_packet::{ParsedPacket, SerializablePacket};
+
+const FRAME_ID_LENGTH: usize = 2;
+const APDU_STATUS_LENGTH: usize = 4;
+
+/// Data status fields
+const DATA_STATUS_DATA_VALID: u8 = 0x04;
+const DATA_STATUS_PROVIDER_RUN: u8 = 0x10;
+
+/// Build a PROFINET packet from a link-layer packet, save it in a Parsed Packet
+pub fn handle_profinet_packet(packet: &[u8], parsed_packet: &mut ParsedPacket) {
+    match parse_profinet_packet(packet) {
+        Some(profinet_packet) => {
+            debug!(
+                "PROFINET Packet: Frame ID: {:#06x} ({}), Cycle counter: {:?}",
+                profinet_packet.frame_id, profinet_packet.frame_type, profinet_packet.cycle_counter
+            );
+
+            parsed_packet.set_application_layer_packet(Some(SerializablePacket::ProfinetPacket(
+                profinet_packet,
+            )));
+        }
+        None => {
+            debug!("Malformed PROFINET Packet");
+            parsed_packet.set_application_layer_packet(Some(SerializablePacket::MalformedPacket(
+                "Malformed PROFINET Packet".to_string(),
+            )));
+        }
+    }
+}
+
+fn parse_profinet_packet(packet: &[u8]) -> Option<SerializableProfinetPacket> {
+    if packet.len() < FRAME_ID_LENGTH {
+        return None;
+    }
+
+    let frame_id = u16::from_be_bytes([packet[0], packet[1]]);
+    let (frame_type, cyclic) = frame_type(frame_id);
+    let data = &packet[FRAME_ID_LENGTH..];
+
+    if !cyclic {
+        return Some(SerializableProfinetPacket {
+            frame_id,
+            frame_type: frame_type.to_owned(),
+            cycle_counter: None,
+            data_status: None,
+            data_valid: None,
+            provider_running: None,
+            transfer_status: None,
+            data_length: data.len(),
+        });
+    }
+
+    if data.len() < APDU_STATUS_LENGTH {
+        return None;
+    }
+    let status = &data[data.len() - APDU_STATUS_LENGTH..];
+    let data_status = status[2];
+
+    Some(SerializableProfinetPacket {
+        frame_id,
+        frame_type: frame_type.to_owned(),
+        cycle_counter: Some(u16::from_be_bytes([status[0], status[1]])),
+        data_status: Some(data_status),
+        data_valid: Some(data_status & DATA_STATUS_DATA_VALID != 0),
+        provider_running: Some(data_status & DATA_STATUS_PROVIDER_RUN != 0),
+        transfer_status: Some(status[3]),
+        data_length: data.len() - APDU_STATUS_LENGTH,
+    })
+}
+
+/// Get the frame type of a frame ID, and whether it identifies a cyclic frame
+fn frame_type(frame_id: u16) -> (&'static str, bool) {
+    match frame_id {
+        0x0000..=0x00ff => ("PTCP", false),
+        0x0100..=0x0fff => ("RT_CLASS_3", true),
+        0x8000..=0xbfff => ("RT_CLASS_2", true),
+        0xc000..=0xfbff => ("RT_CLASS_1", true),
+        0xfc01 => ("Alarm High", false),
+        0xfe01 => ("Alarm Low", false),
+        0xfefc => ("DCP Hello", false),
+        0xfefd => ("DCP Get/Set", false),
+        0xfefe => ("DCP Identify Request", false),
+        0xfeff => ("DCP Identify Response", false),
+        0xff00..=0xff1f => ("PTCP", false),
+        _ => ("Reserved", false),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::serializable_packet::{ParsedPacket, SerializablePacket};
+
+    use super::handle_profinet_packet;
+
+    #[test]
+    fn cyclic_frame() {
+        let mut frame = vec![0xc0, 0x01];
+        frame.extend_from_slice(&[0u8; 40]);
+        frame.extend_from_slice(&[0x12, 0x34, 0x35, 0x00]);
+
+        let mut parsed_packet = ParsedPacket::new(0);
+        handle_profinet_packet(&frame, &mut parsed_packet);
+        match parsed_packet.get_application_layer_packet().unwrap() {
+            SerializablePacket::ProfinetPacket(profinet_packet) => {
+                assert_eq!(profinet_packet.frame_type, "RT_CLASS_1");
+                assert_eq!(profinet_packet.cycle_counter, Some(0x1234));
+                assert_eq!(profinet_packet.data_valid, Some(true));
+                assert_eq!(profinet_packet.provider_running, Some(true));
+                assert_eq!(profinet_packet.data_length, 40);
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn dcp_frame() {
+        let mut parsed_packet = ParsedPacket::new(0);
+        handle_profinet_packet(&[0xfe, 0xfe, 0x05, 0x00], &mut parsed_packet);
+        match parsed_packet.get_application_layer_packet().unwrap() {
+            SerializablePacket::ProfinetPacket(profinet_packet) => {
+                assert_eq!(profinet_packet.frame_type, "DCP Identify Request");
+                assert!(profinet_packet.cycle_counter.is_none());
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn malformed_cyclic_frame() {
+        let mut parsed_packet = ParsedPacket::new(0);
+        handle_profinet_packet(&[0x80, 0x00, 0x00], &mut parsed_packet);
+
+        assert!(matches!(
+            parsed_packet.get_application_layer_packet(),
+            Some(SerializablePacket::MalformedPacket(_))
+        ));
+    }
+}
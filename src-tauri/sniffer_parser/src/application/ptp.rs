@@ -0,0 +1,534 @@
+//! PTP (IEEE 1588) and gPTP (IEEE 802.1AS) Packet parsing
+//!
+//! PTP messages are carried directly over Ethernet (ethertype 0x88F7, always for gPTP)
+//! or over UDP (event messages on port 319, general ones on port 320).
+//!
+//! Offset estimates follow the delay request-response mechanism, taking the capture time
+//! as the receive time of Sync messages and as the transmission time of Delay_Req ones:
+//! they are meaningful when capturing on the slave itself.
+
+use std::collections::HashMap;
+use std::mem::size_of;
+
+use tracing::debug;
+
+use crate::flows::{get_flow_timeouts, SWEEP_INTERVAL};
+use crate::health::publish_buffers;
+use crate::serializable_packet::application::{
+    PtpAnnounce, PtpOffsetEstimate, PtpPortIdentity, PtpTimestamp, SerializablePtpPacket,
+};
+use crate::serializable_packet::{ParsedPacket, SerializablePacket};
+use crate::ParserContext;
+
+/// PTP Message Types
+#[allow(non_snake_case)]
+mod PtpMessageTypes {
+    pub const SYNC: u8 = 0x0;
+    pub const DELAY_REQ: u8 = 0x1;
+    pub const PDELAY_REQ: u8 = 0x2;
+    pub const PDELAY_RESP: u8 = 0x3;
+    pub const FOLLOW_UP: u8 = 0x8;
+    pub const DELAY_RESP: u8 = 0x9;
+    pub const PDELAY_RESP_FOLLOW_UP: u8 = 0xa;
+    pub const ANNOUNCE: u8 = 0xb;
+    pub const SIGNALING: u8 = 0xc;
+    pub const MANAGEMENT: u8 = 0xd;
+}
+
+const HEADER_LENGTH: usize = 34;
+const TIMESTAMP_LENGTH: usize = 10;
+const PORT_IDENTITY_LENGTH: usize = 10;
+const ANNOUNCE_LENGTH: usize = 64;
+
+const FLAG_TWO_STEP: u16 = 0x0200;
+const FLAG_UTC_OFFSET_VALID: u16 = 0x0004;
+const FLAG_PTP_TIMESCALE: u16 = 0x0008;
+
+/// gPTP messages carry 1 in the transportSpecific (majorSdoId) field
+const TRANSPORT_SPECIFIC_GPTP: u8 = 1;
+
+/// Timestamps of the delay request-response exchanges seen so far
+///
+/// Each entry keeps the capture time it was last updated at, to expire it after the UDP flow
+/// timeout: the masters and the slaves gone silent, or the requests never answered.
+#[derive(Default)]
+pub(crate) struct PtpExchanges {
+    syncs: HashMap<(u8, PtpPortIdentity), PtpSync>,
+    /// Capture times of the Delay_Req messages
+    delay_requests: HashMap<(u8, PtpPortIdentity, u16), i128>,
+    /// Mean path delays, with their capture time
+    path_delays: HashMap<(u8, PtpPortIdentity), (i128, i128)>,
+    /// Offsets between TAI and UTC, with their capture time
+    utc_offsets: HashMap<(u8, PtpPortIdentity), (i128, i128)>,
+    last_sweep: i128,
+}
+
+impl PtpExchanges {
+    pub(crate) fn len(&self) -> usize {
+        self.syncs.len()
+            + self.delay_requests.len()
+            + self.path_delays.len()
+            + self.utc_offsets.len()
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Approximate bytes held by the entries
+    pub(crate) fn buffered_bytes(&self) -> usize {
+        self.syncs.len() * size_of::<((u8, PtpPortIdentity), PtpSync)>()
+            + self.delay_requests.len() * size_of::<((u8, PtpPortIdentity, u16), i128)>()
+            + (self.path_delays.len() + self.utc_offsets.len())
+                * size_of::<((u8, PtpPortIdentity), (i128, i128))>()
+    }
+
+    /// Drop the entries not updated since the given timeout
+    fn expire(&mut self, now: i128, timeout: i128) {
+        let alive = |capture: i128| now - capture < timeout;
+
+        self.syncs.retain(|_, sync| alive(sync.capture));
+        self.delay_requests.retain(|_, capture| alive(*capture));
+        self.path_delays.retain(|_, (_, capture)| alive(*capture));
+        self.utc_offsets.retain(|_, (_, capture)| alive(*capture));
+    }
+}
+
+/// Last Sync message sent by a master: origin time (t1) and capture time (t2)
+struct PtpSync {
+    sequence_id: u16,
+    origin: Option<i128>,
+    capture: i128,
+}
+
+/// Build a PTP packet from a link-layer or transport-layer packet, save it in a Parsed Packet
+pub fn handle_ptp_packet(
+    state: &mut ParserContext,
+    packet: &[u8],
+    parsed_packet: &mut ParsedPacket,
+) {
+    match parse_ptp_message(packet) {
+        Some(mut ptp_packet) => {
+            debug!(
+                "PTP Packet: {}:{}; Type: {}, Domain: {}, Sequence: {}",
+                ptp_packet.source_port.clock_identity,
+                ptp_packet.source_port.port_number,
+                ptp_packet.message_type,
+                ptp_packet.domain,
+                ptp_packet.sequence_id
+            );
+
+            let capture = parsed_packet.get_meta().capture_time.as_nanos() as i128;
+            ptp_packet.offset_estimate =
+                update_exchanges(&mut state.ptp_exchanges, &ptp_packet, capture);
+            sweep_exchanges(state, capture);
+
+            parsed_packet
+                .set_application_layer_packet(Some(SerializablePacket::PtpPacket(ptp_packet)));
+        }
+        None => {
+            debug!("Malformed PTP Packet");
+            parsed_packet.set_application_layer_packet(Some(SerializablePacket::MalformedPacket(
+                "Malformed PTP Packet".to_string(),
+            )));
+        }
+    }
+}
+
+/// Parse a PTP message, without estimating the clock offset
+pub fn parse_ptp_message(packet: &[u8]) -> Option<SerializablePtpPacket> {
+    if packet.len() < HEADER_LENGTH {
+        return None;
+    }
+
+    let transport_specific = packet[0] >> 4;
+    let message_type = packet[0] & 0x0f;
+    let length = u16::from_be_bytes([packet[2], packet[3]]);
+    if (length as usize) < HEADER_LENGTH || packet.len() < length as usize {
+        return None;
+    }
+    let packet = &packet[..length as usize];
+
+    let flags = u16::from_be_bytes([packet[6], packet[7]]);
+    let correction = i64::from_be_bytes(packet[8..16].try_into().unwrap());
+    let body = &packet[HEADER_LENGTH..];
+
+    let (timestamp, requesting_port, announce) = match message_type {
+        PtpMessageTypes::SYNC
+        | PtpMessageTypes::DELAY_REQ
+        | PtpMessageTypes::FOLLOW_UP
+        | PtpMessageTypes::PDELAY_REQ => (Some(parse_timestamp(body)?), None, None),
+        PtpMessageTypes::DELAY_RESP
+        | PtpMessageTypes::PDELAY_RESP
+        | PtpMessageTypes::PDELAY_RESP_FOLLOW_UP => (
+            Some(parse_timestamp(body)?),
+            Some(parse_port_identity(body.get(TIMESTAMP_LENGTH..)?)?),
+            None,
+        ),
+        PtpMessageTypes::ANNOUNCE => (
+            Some(parse_timestamp(body)?),
+            None,
+            Some(parse_announce(packet)?),
+        ),
+        _ => (None, None, None),
+    };
+
+    Some(SerializablePtpPacket {
+        transport_specific,
+        gptp: transport_specific == TRANSPORT_SPECIFIC_GPTP,
+        message_type: message_type_name(message_type),
+        version: packet[1] & 0x0f,
+        length,
+        domain: packet[4],
+        flags,
+        two_step: flags & FLAG_TWO_STEP != 0,
+        correction_ns: correction as f64 / 65536.0,
+        source_port: parse_port_identity(&packet[20..30])?,
+        sequence_id: u16::from_be_bytes([packet[30], packet[31]]),
+        log_message_interval: packet[33] as i8,
+        timestamp,
+        requesting_port,
+        announce,
+        offset_estimate: None,
+    })
+}
+
+/// Expire the exchanges at most once per sweep interval, then publish the entries left
+fn sweep_exchanges(state: &mut ParserContext, capture: i128) {
+    let exchanges = &mut state.ptp_exchanges;
+    if capture - exchanges.last_sweep < SWEEP_INTERVAL.as_nanos() as i128 {
+        return;
+    }
+    exchanges.last_sweep = capture;
+
+    let timeout = get_flow_timeouts().udp as i128 * 1_000_000_000;
+    exchanges.expire(capture, timeout);
+    publish_buffers(state);
+}
+
+/// Track the delay request-response exchanges, estimating the offset when all timestamps are known
+fn update_exchanges(
+    exchanges: &mut PtpExchanges,
+    ptp_packet: &SerializablePtpPacket,
+    capture: i128,
+) -> Option<PtpOffsetEstimate> {
+    let master = (ptp_packet.domain, ptp_packet.source_port.clone());
+    let correction = ptp_packet.correction_ns as i128;
+
+    // PTP timescale is TAI: convert to UTC, as the capture time
+    let utc_offset = match exchanges.utc_offsets.get(&master) {
+        Some((utc_offset, _)) if ptp_packet.flags & FLAG_PTP_TIMESCALE != 0 => *utc_offset,
+        _ => 0,
+    };
+    let timestamp = ptp_packet
+        .timestamp
+        .map(|timestamp| timestamp.as_nanos() - utc_offset);
+
+    match ptp_packet.message_type.as_str() {
+        "Sync" => {
+            let origin = match ptp_packet.two_step {
+                true => None,
+                false => timestamp.map(|timestamp| timestamp + correction),
+            };
+            exchanges.syncs.insert(
+                master.clone(),
+                PtpSync {
+                    sequence_id: ptp_packet.sequence_id,
+                    origin,
+                    capture,
+                },
+            );
+
+            estimate_offset(exchanges, &master)
+        }
+        "Follow_Up" => {
+            let sync = exchanges.syncs.get_mut(&master)?;
+            if sync.sequence_id != ptp_packet.sequence_id {
+                return None;
+            }
+            sync.origin = Some(timestamp? + correction);
+
+            estimate_offset(exchanges, &master)
+        }
+        "Delay_Req" => {
+            exchanges
+                .delay_requests
+                .insert((master.0, master.1, ptp_packet.sequence_id), capture);
+
+            None
+        }
+        "Delay_Resp" => {
+            let requesting_port = ptp_packet.requesting_port.clone()?;
+            let request_capture = exchanges.delay_requests.remove(&(
+                master.0,
+                requesting_port,
+                ptp_packet.sequence_id,
+            ))?;
+            let receive = timestamp? - correction;
+
+            let sync = exchanges.syncs.get(&master)?;
+            let master_to_slave = sync.capture - sync.origin?;
+            let slave_to_master = receive - request_capture;
+            exchanges.path_delays.insert(
+                master.clone(),
+                ((master_to_slave + slave_to_master) / 2, capture),
+            );
+
+            estimate_offset(exchanges, &master)
+        }
+        "Announce" => {
+            if let Some(announce) = &ptp_packet.announce {
+                if ptp_packet.flags & FLAG_UTC_OFFSET_VALID != 0 {
+                    exchanges.utc_offsets.insert(
+                        master,
+                        (announce.current_utc_offset as i128 * 1_000_000_000, capture),
+                    );
+                }
+            }
+
+            None
+        }
+        _ => None,
+    }
+}
+
+fn estimate_offset(
+    exchanges: &PtpExchanges,
+    master: &(u8, PtpPortIdentity),
+) -> Option<PtpOffsetEstimate> {
+    let sync = exchanges.syncs.get(master)?;
+    let (path_delay, _) = *exchanges.path_delays.get(master)?;
+    let offset = sync.capture - sync.origin? - path_delay;
+
+    Some(PtpOffsetEstimate {
+        offset_ns: offset as i64,
+        mean_path_delay_ns: path_delay as i64,
+    })
+}
+
+fn parse_timestamp(data: &[u8]) -> Option<PtpTimestamp> {
+    if data.len() < TIMESTAMP_LENGTH {
+        return None;
+    }
+
+    let mut seconds = [0u8; 8];
+    seconds[2..].copy_from_slice(&data[..6]);
+
+    Some(PtpTimestamp {
+        seconds: u64::from_be_bytes(seconds),
+        nanoseconds: u32::from_be_bytes(data[6..10].try_into().unwrap()),
+    })
+}
+
+fn parse_port_identity(data: &[u8]) -> Option<PtpPortIdentity> {
+    if data.len() < PORT_IDENTITY_LENGTH {
+        return None;
+    }
+
+    Some(PtpPortIdentity {
+        clock_identity: format_clock_identity(&data[..8]),
+        port_number: u16::from_be_bytes([data[8], data[9]]),
+    })
+}
+
+fn parse_announce(packet: &[u8]) -> Option<PtpAnnounce> {
+    if packet.len() < ANNOUNCE_LENGTH {
+        return None;
+    }
+
+    Some(PtpAnnounce {
+        current_utc_offset: i16::from_be_bytes([packet[44], packet[45]]),
+        grandmaster_priority1: packet[47],
+        grandmaster_clock_class: packet[48],
+        grandmaster_clock_accuracy: packet[49],
+        grandmaster_clock_variance: u16::from_be_bytes([packet[50], packet[51]]),
+        grandmaster_priority2: packet[52],
+        grandmaster_identity: format_clock_identity(&packet[53..61]),
+        steps_removed: u16::from_be_bytes([packet[61], packet[62]]),
+        time_source: packet[63],
+    })
+}
+
+fn format_clock_identity(identity: &[u8]) -> String {
+    identity
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect::<Vec<String>>()
+        .join(":")
+}
+
+fn message_type_name(message_type: u8) -> String {
+    match message_type {
+        PtpMessageTypes::SYNC => "Sync",
+        PtpMessageTypes::DELAY_REQ => "Delay_Req",
+        PtpMessageTypes::PDELAY_REQ => "Pdelay_Req",
+        PtpMessageTypes::PDELAY_RESP => "Pdelay_Resp",
+        PtpMessageTypes::FOLLOW_UP => "Follow_Up",
+        PtpMessageTypes::DELAY_RESP => "Delay_Resp",
+        PtpMessageTypes::PDELAY_RESP_FOLLOW_UP => "Pdelay_Resp_Follow_Up",
+        PtpMessageTypes::ANNOUNCE => "Announce",
+        PtpMessageTypes::SIGNALING => "Signaling",
+        PtpMessageTypes::MANAGEMENT => "Management",
+        _ => "Unknown",
+    }
+    .to_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::serializable_packet::{PacketMeta, ParsedPacket, SerializablePacket};
+    use crate::ParserContext;
+
+    use super::{handle_ptp_packet, parse_ptp_message};
+
+    const MASTER: [u8; 8] = [0x00, 0x1b, 0x19, 0xff, 0xfe, 0x00, 0x00, 0x01];
+    const SLAVE: [u8; 8] = [0x00, 0x1b, 0x19, 0xff, 0xfe, 0x00, 0x00, 0x02];
+
+    #[test]
+    fn two_step_sync() {
+        let sync = build_test_message(0x0, 0x0200, 0, MASTER, 7, (100, 0), None);
+        let ptp_packet = parse_ptp_message(&sync).unwrap();
+
+        assert_eq!(ptp_packet.message_type, "Sync");
+        assert!(ptp_packet.two_step);
+        assert!(!ptp_packet.gptp);
+        assert_eq!(ptp_packet.sequence_id, 7);
+        assert_eq!(
+            ptp_packet.source_port.clock_identity,
+            "00:1b:19:ff:fe:00:00:01"
+        );
+        assert_eq!(ptp_packet.timestamp.unwrap().seconds, 100);
+    }
+
+    #[test]
+    fn delay_resp_correction() {
+        let delay_resp = build_test_message(0x9, 0, 3 << 16, MASTER, 1, (100, 500), Some(SLAVE));
+        let ptp_packet = parse_ptp_message(&delay_resp).unwrap();
+
+        assert_eq!(ptp_packet.message_type, "Delay_Resp");
+        assert_eq!(ptp_packet.correction_ns, 3.0);
+        assert_eq!(
+            ptp_packet.requesting_port.unwrap().clock_identity,
+            "00:1b:19:ff:fe:00:00:02"
+        );
+    }
+
+    #[test]
+    fn malformed_ptp_packet() {
+        let mut state = ParserContext::new();
+
+        let mut parsed_packet = ParsedPacket::new(0);
+        handle_ptp_packet(&mut state, &[0x00, 0x02, 0x00, 0x2c], &mut parsed_packet);
+
+        assert!(matches!(
+            parsed_packet.get_application_layer_packet(),
+            Some(SerializablePacket::MalformedPacket(_))
+        ));
+    }
+
+    #[test]
+    fn offset_estimate() {
+        let mut state = ParserContext::new();
+
+        // Master clock synchronized with the capture one, 10 us of path delay
+        let messages = [
+            (
+                100_000_000_000,
+                build_test_message(0x0, 0x0200, 0, MASTER, 1, (0, 0), None),
+            ),
+            (
+                100_000_001_000,
+                build_test_message(0x8, 0, 0, MASTER, 1, (99, 999_990_000), None),
+            ),
+            (
+                100_000_500_000,
+                build_test_message(0x1, 0, 0, SLAVE, 4, (0, 0), None),
+            ),
+            (
+                100_000_600_000,
+                build_test_message(0x9, 0, 0, MASTER, 4, (100, 510_000), Some(SLAVE)),
+            ),
+        ];
+
+        let mut estimate = None;
+        for (capture, message) in messages {
+            let meta = PacketMeta::new(0, Duration::from_nanos(capture));
+            let mut parsed_packet = ParsedPacket::with_meta(meta);
+            handle_ptp_packet(&mut state, &message, &mut parsed_packet);
+
+            if let Some(SerializablePacket::PtpPacket(ptp_packet)) =
+                parsed_packet.get_application_layer_packet()
+            {
+                estimate = ptp_packet.offset_estimate;
+            }
+        }
+
+        let estimate = estimate.unwrap();
+        assert_eq!(estimate.mean_path_delay_ns, 10_000);
+        assert_eq!(estimate.offset_ns, 0);
+    }
+
+    #[test]
+    fn exchanges_expired() {
+        let mut state = ParserContext::new();
+
+        let messages = [
+            (
+                100_000_000_000,
+                build_test_message(0x1, 0, 0, SLAVE, 4, (0, 0), None),
+            ),
+            (
+                130_000_000_000,
+                build_test_message(0x0, 0x0200, 0, MASTER, 1, (0, 0), None),
+            ),
+            // Delay_Req never answered, past the UDP flow timeout
+            (
+                161_000_000_000,
+                build_test_message(0x0, 0x0200, 0, MASTER, 2, (0, 0), None),
+            ),
+        ];
+
+        for (capture, message) in messages {
+            let meta = PacketMeta::new(0, Duration::from_nanos(capture));
+            let mut parsed_packet = ParsedPacket::with_meta(meta);
+            handle_ptp_packet(&mut state, &message, &mut parsed_packet);
+        }
+
+        assert!(state.ptp_exchanges.delay_requests.is_empty());
+        assert_eq!(state.ptp_exchanges.len(), 1);
+    }
+
+    ///////////////////// Utils
+
+    fn build_test_message(
+        message_type: u8,
+        flags: u16,
+        correction: i64,
+        clock_identity: [u8; 8],
+        sequence_id: u16,
+        timestamp: (u64, u32),
+        requesting_clock: Option<[u8; 8]>,
+    ) -> Vec<u8> {
+        let mut message = vec![0u8; 34];
+        message[0] = message_type;
+        message[1] = 0x02;
+        message[6..8].copy_from_slice(&flags.to_be_bytes());
+        message[8..16].copy_from_slice(&correction.to_be_bytes());
+        message[20..28].copy_from_slice(&clock_identity);
+        message[28..30].copy_from_slice(&1u16.to_be_bytes());
+        message[30..32].copy_from_slice(&sequence_id.to_be_bytes());
+
+        message.extend_from_slice(&timestamp.0.to_be_bytes()[2..]);
+        message.extend_from_slice(&timestamp.1.to_be_bytes());
+        if let Some(requesting_clock) = requesting_clock {
+            message.extend_from_slice(&requesting_clock);
+            message.extend_from_slice(&1u16.to_be_bytes());
+        }
+
+        let length = message.len() as u16;
+        message[2..4].copy_from_slice(&length.to_be_bytes());
+        message
+    }
+}
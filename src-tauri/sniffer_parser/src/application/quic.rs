@@ -0,0 +1,798 @@
+//! QUIC Packet parsing
+//!
+//! A UDP datagram carries one or more QUIC packets, coalesced one after the other. Packets with a
+//! long header, sent while the connection is being established, expose the version and the
+//! connection ids of both the endpoints; packets with a short header only expose the connection id
+//! of the receiver, which is recognized among the ones learnt from the long headers.
+//!
+//! Initial packets are protected with keys derived from the connection id first chosen by the
+//! client (RFC 9001, section 5.2), so they are decrypted: their CRYPTO frames carry the TLS
+//! ClientHello and ServerHello, reassembled across packets. All the other packets, including the
+//! 1-RTT ones carrying HTTP/3, are protected with the keys negotiated by the handshake.
+
+use std::{collections::BTreeMap, net::IpAddr};
+
+use aes::cipher::{generic_array::GenericArray, BlockEncrypt};
+use aes::Aes128;
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use aes_gcm::{Aes128Gcm, Nonce};
+use hkdf::Hkdf;
+use sha2::Sha256;
+use tls_parser::parse_tls_message_handshake;
+use tracing::debug;
+
+use crate::{
+    serializable_packet::{
+        application::{QuicPacket, SerializableQuicPacket, SerializableTlsHandshakePacket},
+        ParsedPacket, SerializablePacket,
+    },
+    FlowKey, ParserContext,
+};
+
+use super::tls::handshake_details;
+
+/// Maximum length of a connection id
+const MAX_CONNECTION_ID_LENGTH: usize = 20;
+/// Length of the sample of the packet used to remove the header protection
+const SAMPLE_LENGTH: usize = 16;
+/// Maximum number of bytes of the CRYPTO frames of a direction kept waiting for its hello
+const MAX_CRYPTO_LENGTH: usize = 1 << 16;
+
+/// QUIC versions whose Initial packets can be decrypted
+#[allow(non_snake_case)]
+mod Versions {
+    pub const NEGOTIATION: u32 = 0;
+    pub const V1: u32 = 0x0000_0001;
+    pub const V2: u32 = 0x6b33_43cf;
+    pub const DRAFT_29: u32 = 0xff00_001d;
+    pub const DRAFT_32: u32 = 0xff00_0020;
+}
+
+/// Salts of the secrets of the Initial packets
+const INITIAL_SALT_V1: [u8; 20] = [
+    0x38, 0x76, 0x2c, 0xf7, 0xf5, 0x59, 0x34, 0xb3, 0x4d, 0x17, 0x9a, 0xe6, 0xa4, 0xc8, 0x0c, 0xad,
+    0xcc, 0xbb, 0x7f, 0x0a,
+];
+const INITIAL_SALT_V2: [u8; 20] = [
+    0x0d, 0xed, 0xe3, 0xde, 0xf7, 0x00, 0xa6, 0xdb, 0x81, 0x93, 0x81, 0xbe, 0x6e, 0x26, 0x9d, 0xcb,
+    0xf9, 0xbd, 0x2e, 0xd9,
+];
+const INITIAL_SALT_DRAFT_29: [u8; 20] = [
+    0xaf, 0xbf, 0xec, 0x28, 0x99, 0x93, 0xd2, 0x4c, 0x9e, 0x97, 0x86, 0xf1, 0x9c, 0x61, 0x11, 0xe0,
+    0x43, 0x90, 0xa8, 0x99,
+];
+
+/// QUIC frame types allowed in Initial packets
+#[allow(non_snake_case)]
+mod FrameTypes {
+    pub const PADDING: u64 = 0x00;
+    pub const PING: u64 = 0x01;
+    pub const ACK: u64 = 0x02;
+    pub const ACK_ECN: u64 = 0x03;
+    pub const CRYPTO: u64 = 0x06;
+    pub const CONNECTION_CLOSE: u64 = 0x1c;
+    pub const APPLICATION_CLOSE: u64 = 0x1d;
+}
+
+/// Types of the packets with a long header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LongPacketType {
+    Initial,
+    ZeroRtt,
+    Handshake,
+    Retry,
+}
+
+/// State of a QUIC connection, kept by the flow from the client to the server
+#[derive(Debug, Default)]
+pub(crate) struct QuicConnection {
+    /// Destination connection id of the first Initial packet of the client, from which the keys
+    /// of the Initial packets of both the directions are derived
+    original_dcid: Option<Vec<u8>>,
+    /// Connection id chosen by the client
+    client_cid: Option<Vec<u8>>,
+    /// Connection id chosen by the server
+    server_cid: Option<Vec<u8>>,
+    client_crypto: CryptoStream,
+    server_crypto: CryptoStream,
+}
+
+impl QuicConnection {
+    /// Get the number of bytes of the CRYPTO frames kept for the hello messages
+    pub(crate) fn buffered_bytes(&self) -> usize {
+        self.client_crypto.buffered_bytes() + self.server_crypto.buffered_bytes()
+    }
+}
+
+/// Data of the CRYPTO frames of the Initial packets of a direction, carrying its hello message
+#[derive(Debug, Default)]
+struct CryptoStream {
+    /// Data from the start of the stream, without gaps
+    data: Vec<u8>,
+    /// Data following a gap, by offset
+    pending: BTreeMap<usize, Vec<u8>>,
+    /// Whether the hello message was already found
+    done: bool,
+}
+
+/// Keys protecting the Initial packets of a direction
+struct InitialKeys {
+    key: Vec<u8>,
+    iv: Vec<u8>,
+    hp: Vec<u8>,
+}
+
+/// Build a QUIC packet from the payload of a UDP datagram, save it in a Parsed Packet
+#[allow(clippy::too_many_arguments)]
+pub fn handle_quic_packet(
+    state: &mut ParserContext,
+    source_ip: IpAddr,
+    source_port: u16,
+    dest_ip: IpAddr,
+    dest_port: u16,
+    is_request: bool,
+    packet: &[u8],
+    parsed_packet: &mut ParsedPacket,
+) {
+    let flow = FlowKey::new((source_ip, source_port), (dest_ip, dest_port));
+
+    let quic_packet = state
+        .quic_connections
+        .entry(flow)
+        .or_default()
+        .dissect(packet, is_request);
+
+    match quic_packet {
+        Some(quic_packet) => {
+            debug!(
+                "QUIC Packet: {}:{} > {}:{}; packets: {}",
+                source_ip,
+                source_port,
+                dest_ip,
+                dest_port,
+                quic_packet.packets.len()
+            );
+            parsed_packet
+                .set_application_layer_packet(Some(SerializablePacket::QuicPacket(quic_packet)));
+        }
+        None => {
+            debug!("Malformed QUIC Packet");
+            parsed_packet.set_application_layer_packet(Some(SerializablePacket::MalformedPacket(
+                "Malformed QUIC Packet".to_string(),
+            )));
+        }
+    }
+}
+
+impl QuicConnection {
+    /// Parse the packets coalesced in a datagram, none if the first one is malformed
+    fn dissect(&mut self, mut datagram: &[u8], is_client: bool) -> Option<SerializableQuicPacket> {
+        let mut quic_packet = SerializableQuicPacket::default();
+
+        // Datagrams may be padded with zeros after their last packet
+        while datagram.iter().any(|byte| *byte != 0) {
+            let length = if datagram[0] & 0x80 != 0 {
+                self.dissect_long_header(datagram, is_client, &mut quic_packet)
+            } else {
+                Some(self.dissect_short_header(datagram, is_client, &mut quic_packet))
+            };
+
+            match length {
+                Some(length) => datagram = &datagram[length..],
+                None => break,
+            }
+        }
+
+        (!quic_packet.packets.is_empty()).then_some(quic_packet)
+    }
+
+    /// Parse a packet with a long header, returning its length
+    fn dissect_long_header(
+        &mut self,
+        packet: &[u8],
+        is_client: bool,
+        quic_packet: &mut SerializableQuicPacket,
+    ) -> Option<usize> {
+        let version = u32::from_be_bytes(packet.get(1..5)?.try_into().ok()?);
+        let mut cursor = 5;
+        let dcid = read_connection_id(packet, &mut cursor)?;
+        let scid = read_connection_id(packet, &mut cursor)?;
+
+        let mut details = QuicPacket {
+            header_form: "Long".to_owned(),
+            version: Some(version_name(version)),
+            destination_connection_id: Some(to_hex(dcid)),
+            source_connection_id: Some(to_hex(scid)),
+            length: packet.len(),
+            ..Default::default()
+        };
+
+        if version == Versions::NEGOTIATION {
+            details.packet_type = "Version Negotiation".to_owned();
+            details.supported_versions = packet[cursor..]
+                .chunks_exact(4)
+                .map(|version| version_name(u32::from_be_bytes(version.try_into().unwrap())))
+                .collect();
+            quic_packet.packets.push(details);
+            return Some(packet.len());
+        }
+
+        let packet_type = long_packet_type(version, (packet[0] >> 4) & 0x03);
+        details.packet_type = match packet_type {
+            LongPacketType::Initial => "Initial",
+            LongPacketType::ZeroRtt => "0-RTT",
+            LongPacketType::Handshake => "Handshake",
+            LongPacketType::Retry => "Retry",
+        }
+        .to_owned();
+
+        if is_client {
+            self.client_cid = Some(scid.to_vec());
+        } else {
+            self.server_cid = Some(scid.to_vec());
+        }
+
+        if packet_type == LongPacketType::Retry {
+            // The client starts again, with the connection id chosen by the server
+            self.original_dcid = None;
+            quic_packet.packets.push(details);
+            return Some(packet.len());
+        }
+
+        if packet_type == LongPacketType::Initial {
+            let token_length = usize::try_from(read_varint(packet, &mut cursor)?).ok()?;
+            cursor = cursor
+                .checked_add(token_length)
+                .filter(|cursor| *cursor <= packet.len())?;
+        }
+        let length = usize::try_from(read_varint(packet, &mut cursor)?).ok()?;
+        let end = cursor
+            .checked_add(length)
+            .filter(|end| *end <= packet.len())?;
+        details.length = end;
+
+        if packet_type == LongPacketType::Initial {
+            if is_client && self.original_dcid.is_none() {
+                self.original_dcid = Some(dcid.to_vec());
+            }
+
+            let decrypted = self
+                .original_dcid
+                .as_deref()
+                .and_then(|original_dcid| initial_keys(version, original_dcid, is_client))
+                .and_then(|keys| unprotect(&packet[..end], cursor, &keys));
+            if let Some((packet_number, payload)) = decrypted {
+                let crypto = if is_client {
+                    &mut self.client_crypto
+                } else {
+                    &mut self.server_crypto
+                };
+
+                details.packet_number = Some(packet_number);
+                details.frames = read_frames(&payload, crypto);
+                if let Some(hello) = crypto.take_hello() {
+                    quic_packet.handshake = parse_hello(&hello);
+                }
+            }
+        }
+
+        quic_packet.packets.push(details);
+        Some(end)
+    }
+
+    /// Parse a packet with a short header, which extends to the end of the datagram
+    fn dissect_short_header(
+        &self,
+        packet: &[u8],
+        is_client: bool,
+        quic_packet: &mut SerializableQuicPacket,
+    ) -> usize {
+        let receiver_cid = if is_client {
+            &self.server_cid
+        } else {
+            &self.client_cid
+        };
+
+        quic_packet.packets.push(QuicPacket {
+            header_form: "Short".to_owned(),
+            packet_type: "1-RTT".to_owned(),
+            destination_connection_id: receiver_cid
+                .as_deref()
+                .filter(|cid| packet.get(1..1 + cid.len()) == Some(*cid))
+                .map(to_hex),
+            length: packet.len(),
+            ..Default::default()
+        });
+
+        packet.len()
+    }
+}
+
+impl CryptoStream {
+    /// Add the data of a CRYPTO frame
+    fn insert(&mut self, offset: usize, data: &[u8]) {
+        if self.done || offset.saturating_add(data.len()) > MAX_CRYPTO_LENGTH {
+            return;
+        }
+
+        let pending = self.pending.entry(offset).or_default();
+        if data.len() > pending.len() {
+            *pending = data.to_vec();
+        }
+
+        while let Some(entry) = self.pending.first_entry() {
+            let offset = *entry.key();
+            if offset > self.data.len() {
+                break;
+            }
+
+            let data = entry.remove();
+            if offset + data.len() > self.data.len() {
+                let start = self.data.len() - offset;
+                self.data.extend_from_slice(&data[start..]);
+            }
+        }
+    }
+
+    /// Take the first handshake message of the stream, once all its bytes have been received
+    fn take_hello(&mut self) -> Option<Vec<u8>> {
+        if self.done {
+            return None;
+        }
+
+        let length = self
+            .data
+            .get(1..4)
+            .map(|length| u32::from_be_bytes([0, length[0], length[1], length[2]]) as usize)?;
+        let hello = self.data.get(..4 + length)?.to_vec();
+
+        *self = CryptoStream {
+            done: true,
+            ..Default::default()
+        };
+
+        Some(hello)
+    }
+
+    fn buffered_bytes(&self) -> usize {
+        self.data.len() + self.pending.values().map(Vec::len).sum::<usize>()
+    }
+}
+
+/// Get the type of a packet with a long header from the type bits of its first byte
+fn long_packet_type(version: u32, bits: u8) -> LongPacketType {
+    // QUIC version 2 shuffles the types, so that middleboxes do not ossify on version 1
+    let bits = if version == Versions::V2 {
+        bits.wrapping_sub(1) & 0x03
+    } else {
+        bits
+    };
+
+    match bits {
+        0 => LongPacketType::Initial,
+        1 => LongPacketType::ZeroRtt,
+        2 => LongPacketType::Handshake,
+        _ => LongPacketType::Retry,
+    }
+}
+
+/// Get the name of a QUIC version
+fn version_name(version: u32) -> String {
+    match version {
+        Versions::V1 => "1".to_owned(),
+        Versions::V2 => "2".to_owned(),
+        0xff00_0000..=0xff00_00ff => format!("draft-{}", version & 0xff),
+        _ if version & 0x0f0f_0f0f == 0x0a0a_0a0a => format!("Reserved (0x{:08x})", version),
+        _ => format!("Unknown (0x{:08x})", version),
+    }
+}
+
+/// Derive the keys protecting the Initial packets sent by the client or by the server
+fn initial_keys(version: u32, original_dcid: &[u8], is_client: bool) -> Option<InitialKeys> {
+    let (salt, prefix): (&[u8], _) = match version {
+        Versions::V1 => (&INITIAL_SALT_V1, "quic"),
+        Versions::V2 => (&INITIAL_SALT_V2, "quicv2"),
+        Versions::DRAFT_29..=Versions::DRAFT_32 => (&INITIAL_SALT_DRAFT_29, "quic"),
+        _ => return None,
+    };
+
+    let (initial_secret, _) = Hkdf::<Sha256>::extract(Some(salt), original_dcid);
+    let label = if is_client { "client in" } else { "server in" };
+    let secret = expand_label(&initial_secret, label, 32);
+
+    Some(InitialKeys {
+        key: expand_label(&secret, &format!("{} key", prefix), 16),
+        iv: expand_label(&secret, &format!("{} iv", prefix), 12),
+        hp: expand_label(&secret, &format!("{} hp", prefix), 16),
+    })
+}
+
+/// HKDF-Expand-Label of TLS 1.3, with an empty context
+fn expand_label(secret: &[u8], label: &str, length: usize) -> Vec<u8> {
+    let label = format!("tls13 {}", label);
+    let mut info = (length as u16).to_be_bytes().to_vec();
+    info.push(label.len() as u8);
+    info.extend_from_slice(label.as_bytes());
+    info.push(0);
+
+    let mut output = vec![0; length];
+    Hkdf::<Sha256>::from_prk(secret)
+        .expect("Secrets are as long as SHA-256 hashes")
+        .expand(&info, &mut output)
+        .expect("Keys are shorter than 255 SHA-256 hashes");
+
+    output
+}
+
+/// Remove the header protection of a packet with a long header and decrypt its payload,
+/// returning its packet number
+///
+/// The packet number is assumed to be sent in full, as Initial packets are only a few.
+fn unprotect(packet: &[u8], pn_offset: usize, keys: &InitialKeys) -> Option<(u64, Vec<u8>)> {
+    let sample = packet.get(pn_offset + 4..pn_offset + 4 + SAMPLE_LENGTH)?;
+    let mut mask = GenericArray::clone_from_slice(sample);
+    Aes128::new(GenericArray::from_slice(&keys.hp)).encrypt_block(&mut mask);
+
+    let first_byte = packet[0] ^ (mask[0] & 0x0f);
+    let pn_length = (first_byte & 0x03) as usize + 1;
+    let mut header = packet[..pn_offset + pn_length].to_vec();
+    header[0] = first_byte;
+
+    let mut packet_number = 0;
+    for (i, byte) in header[pn_offset..].iter_mut().enumerate() {
+        *byte ^= mask[1 + i];
+        packet_number = (packet_number << 8) | *byte as u64;
+    }
+
+    let mut nonce = keys.iv.clone();
+    for (nonce_byte, pn_byte) in nonce[4..].iter_mut().zip(packet_number.to_be_bytes()) {
+        *nonce_byte ^= pn_byte;
+    }
+
+    let payload = Aes128Gcm::new(GenericArray::from_slice(&keys.key))
+        .decrypt(
+            Nonce::from_slice(&nonce),
+            Payload {
+                msg: &packet[pn_offset + pn_length..],
+                aad: &header,
+            },
+        )
+        .ok()?;
+
+    Some((packet_number, payload))
+}
+
+/// Get the names of the frames of a decrypted Initial packet, adding the data of its CRYPTO frames
+/// to the stream
+///
+/// Consecutive PADDING frames are listed once. Frames not allowed in Initial packets stop the
+/// parsing, since their length is unknown.
+fn read_frames(payload: &[u8], crypto: &mut CryptoStream) -> Vec<String> {
+    let mut frames: Vec<String> = vec![];
+    let mut cursor = 0;
+
+    while cursor < payload.len() {
+        let frame_type = match read_varint(payload, &mut cursor) {
+            Some(frame_type) => frame_type,
+            None => break,
+        };
+
+        let name = match frame_type {
+            FrameTypes::PADDING => "PADDING",
+            FrameTypes::PING => "PING",
+            FrameTypes::ACK | FrameTypes::ACK_ECN => "ACK",
+            FrameTypes::CRYPTO => "CRYPTO",
+            FrameTypes::CONNECTION_CLOSE | FrameTypes::APPLICATION_CLOSE => "CONNECTION_CLOSE",
+            _ => {
+                frames.push(format!("Unknown (0x{:02x})", frame_type));
+                break;
+            }
+        };
+        if !(name == "PADDING" && frames.last().map(String::as_str) == Some("PADDING")) {
+            frames.push(name.to_owned());
+        }
+
+        let skipped = match frame_type {
+            FrameTypes::ACK | FrameTypes::ACK_ECN => {
+                skip_ack(payload, &mut cursor, frame_type == FrameTypes::ACK_ECN)
+            }
+            FrameTypes::CRYPTO => read_crypto(payload, &mut cursor, crypto),
+            FrameTypes::CONNECTION_CLOSE | FrameTypes::APPLICATION_CLOSE => {
+                skip_connection_close(payload, &mut cursor, frame_type)
+            }
+            _ => Some(()),
+        };
+        if skipped.is_none() {
+            break;
+        }
+    }
+
+    frames
+}
+
+/// Skip the fields of an ACK frame
+fn skip_ack(payload: &[u8], cursor: &mut usize, has_ecn_counts: bool) -> Option<()> {
+    // Largest acknowledged, delay, number of ranges and first range
+    read_varint(payload, cursor)?;
+    read_varint(payload, cursor)?;
+    let ranges = read_varint(payload, cursor)?;
+    read_varint(payload, cursor)?;
+
+    // Gap and length of each range
+    for _ in 0..ranges {
+        read_varint(payload, cursor)?;
+        read_varint(payload, cursor)?;
+    }
+    if has_ecn_counts {
+        for _ in 0..3 {
+            read_varint(payload, cursor)?;
+        }
+    }
+
+    Some(())
+}
+
+/// Read the offset and the data of a CRYPTO frame, adding them to the stream
+fn read_crypto(payload: &[u8], cursor: &mut usize, crypto: &mut CryptoStream) -> Option<()> {
+    let offset = usize::try_from(read_varint(payload, cursor)?).ok()?;
+    let length = usize::try_from(read_varint(payload, cursor)?).ok()?;
+    let data = payload.get(*cursor..cursor.checked_add(length)?)?;
+    *cursor += length;
+
+    crypto.insert(offset, data);
+    Some(())
+}
+
+/// Skip the fields of a CONNECTION_CLOSE frame
+fn skip_connection_close(payload: &[u8], cursor: &mut usize, frame_type: u64) -> Option<()> {
+    // Error code, type of the frame causing the error (only for transport errors) and reason
+    read_varint(payload, cursor)?;
+    if frame_type == FrameTypes::CONNECTION_CLOSE {
+        read_varint(payload, cursor)?;
+    }
+    let length = usize::try_from(read_varint(payload, cursor)?).ok()?;
+    *cursor = cursor
+        .checked_add(length)
+        .filter(|end| *end <= payload.len())?;
+
+    Some(())
+}
+
+/// Parse the ClientHello or ServerHello carried by the CRYPTO frames
+fn parse_hello(hello: &[u8]) -> Option<SerializableTlsHandshakePacket> {
+    parse_tls_message_handshake(hello)
+        .ok()
+        .and_then(|(_, message)| handshake_details(&message))
+}
+
+/// Read a connection id, prefixed by its length
+fn read_connection_id<'a>(packet: &'a [u8], cursor: &mut usize) -> Option<&'a [u8]> {
+    let length = *packet.get(*cursor)? as usize;
+    if length > MAX_CONNECTION_ID_LENGTH {
+        return None;
+    }
+
+    let connection_id = packet.get(*cursor + 1..*cursor + 1 + length)?;
+    *cursor += 1 + length;
+
+    Some(connection_id)
+}
+
+/// Read a variable-length integer, whose length is given by the two most significant bits
+fn read_varint(data: &[u8], cursor: &mut usize) -> Option<u64> {
+    let length = 1 << (*data.get(*cursor)? >> 6);
+    let bytes = data.get(*cursor..*cursor + length)?;
+    *cursor += length;
+
+    Some(
+        bytes[1..]
+            .iter()
+            .fold((bytes[0] & 0x3f) as u64, |value, byte| {
+                (value << 8) | *byte as u64
+            }),
+    )
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{initial_keys, QuicConnection, Versions};
+    use crate::serializable_packet::application::SerializableQuicPacket;
+
+    #[test]
+    fn initial_keys_of_rfcs() {
+        let dcid = hex("8394c8f03e515708");
+
+        let client = initial_keys(Versions::V1, &dcid, true).unwrap();
+        assert_eq!(client.key, hex("1f369613dd76d5467730efcbe3b1a22d"));
+        assert_eq!(client.iv, hex("fa044b2f42a3fd3b46fb255c"));
+        assert_eq!(client.hp, hex("9f50449e04a0e810283a1e9933adedd2"));
+
+        let server = initial_keys(Versions::V1, &dcid, false).unwrap();
+        assert_eq!(server.key, hex("cf3a5331653c364c88f0f379b6067e37"));
+        assert_eq!(server.iv, hex("0ac1493ca1905853b0bba03e"));
+        assert_eq!(server.hp, hex("c206b8d9b9f0f37644430b490eeaa314"));
+
+        // QUIC version 2 (RFC 9369)
+        let server = initial_keys(Versions::V2, &dcid, false).unwrap();
+        assert_eq!(server.key, hex("82db637861d55e1d011f19ea71d5d2a7"));
+        assert_eq!(server.iv, hex("dd13c276499c0249d3310652"));
+        assert_eq!(server.hp, hex("edf6d05c83121201b436e16877593c3a"));
+
+        assert!(initial_keys(0x1a2a_3a4a, &dcid, true).is_none());
+    }
+
+    #[test]
+    fn handshake_of_connection() {
+        let mut connection = QuicConnection::default();
+
+        let client_initial = connection.dissect(&hex(CLIENT_INITIAL), true).unwrap();
+        let packet = &client_initial.packets[0];
+        assert_eq!(client_initial.packets.len(), 1);
+        assert_eq!(
+            (packet.header_form.as_str(), packet.packet_type.as_str()),
+            ("Long", "Initial")
+        );
+        assert_eq!(packet.version.as_deref(), Some("1"));
+        assert_eq!(
+            packet.destination_connection_id.as_deref(),
+            Some("6e243d60c288109c69eb979c4f9240d6c7728fba")
+        );
+        assert_eq!(
+            packet.source_connection_id.as_deref(),
+            Some("c50138f4cad6ec98")
+        );
+        assert_eq!(packet.packet_number, Some(0));
+        assert!(packet.frames.contains(&"CRYPTO".to_owned()));
+        let client_hello = client_initial.handshake.unwrap();
+        assert_eq!(client_hello.message_type, "ClientHello");
+        assert_eq!(client_hello.server_name.as_deref(), Some("example.com"));
+        assert_eq!(client_hello.alpn_protocols, ["h3"]);
+
+        // Initial and Handshake packets coalesced in the same datagram
+        let server_initial = connection.dissect(&hex(SERVER_INITIAL), false).unwrap();
+        assert_eq!(packet_types(&server_initial), ["Initial", "Handshake"]);
+        assert_eq!(server_initial.packets[0].frames, ["ACK", "CRYPTO"]);
+        assert!(server_initial.packets[1].frames.is_empty());
+        let server_hello = server_initial.handshake.unwrap();
+        assert_eq!(server_hello.message_type, "ServerHello");
+
+        let server_short = connection.dissect(&hex(SERVER_SHORT), false).unwrap();
+        let packet = &server_short.packets[0];
+        assert_eq!(
+            (packet.header_form.as_str(), packet.packet_type.as_str()),
+            ("Short", "1-RTT")
+        );
+        assert_eq!(
+            packet.destination_connection_id.as_deref(),
+            Some("c50138f4cad6ec98")
+        );
+        assert_eq!(packet.packet_number, None);
+    }
+
+    #[test]
+    fn version_negotiation() {
+        let mut connection = QuicConnection::default();
+        let packet = hex("80000000000401020304040506070800000001ff00001d1a2a3a4a");
+
+        let quic_packet = connection.dissect(&packet, false).unwrap();
+        assert_eq!(packet_types(&quic_packet), ["Version Negotiation"]);
+        assert_eq!(
+            quic_packet.packets[0].supported_versions,
+            ["1", "draft-29", "Reserved (0x1a2a3a4a)"]
+        );
+    }
+
+    #[test]
+    fn malformed_packets() {
+        let mut connection = QuicConnection::default();
+
+        // Connection id longer than 20 bytes
+        assert!(connection.dissect(&hex("c00000000115"), true).is_none());
+        // Length beyond the end of the datagram
+        assert!(connection
+            .dissect(&hex("c000000001000000400a0000"), true)
+            .is_none());
+        // Initial packet which cannot be decrypted
+        let quic_packet = connection
+            .dissect(
+                &hex("c0000000010401020304000000140000000000000000000000000000000000000000"),
+                true,
+            )
+            .unwrap();
+        assert_eq!(quic_packet.packets[0].packet_number, None);
+        assert!(quic_packet.handshake.is_none());
+    }
+
+    ///////////////////// Utils
+
+    /// Initial packet of a client connecting to example.com with ALPN h3
+    const CLIENT_INITIAL: &str = concat!(
+        "c900000001146e243d60c288109c69eb979c4f9240d6c7728fba08c50138f4cad6ec9800448a67a755df05fa",
+        "9203b5ae4c4efe740cc1c58763e4e9de1924de0c3654fbbe82fd4a3def700c46466b85a4619ecc6b0064b782",
+        "b8e0a92573e0663470de69c71fe0439d050eb764d773deaa708c357478c070c16732ec244600740e039b6dd0",
+        "acc048d1a3a08ec2de5b21ee99e923f57291512d2e97fe00c17d42048b990146ba00dac7b9b038efe87dd1a0",
+        "5317ff357a58b7991b1ad908f7abdb4c91857d025332c68f3f4ed0def58c86afee761277e22b73a02ba6d261",
+        "fca309dbe863273cf043ed9993025977a16c6b26e95538e93b2cb7a0c453c31912add65a29f7632164a11374",
+        "30e0e46d58c819ce724e99116fd78cb26d5aebdf1d21073cc10b1588235a96258359113da5a5687c4e7795f9",
+        "d55bd1b736de982204994774ceed5870d7fd276bdba178ef19a1d689cbad7350341e4b659a0e12148b7438a9",
+        "42c3be0a5c276d1bf1ca18a8d35f80bd4b74ad1e617976e1663c0eb618cdcb20505810889cb246b57e810b12",
+        "217a6fb6f9a75197120be55aa2fb94a6699124e6945c523be79ac7c62508cccfb43877d585f50f38ac1152b5",
+        "d0077ba33730f4d5ba92a07768213d3106f0e40ddec88727f68dd9032ce4cbf71405a36123c6923b8c9e82b9",
+        "694871e281032be8d74057ac1ab84bca86371b7cbb37f3f96354db4a53d30457bbaa010944a74bde7d4d97ea",
+        "ab58c46cc3a6767ec4e3add293f5424b1cda06526a4052ab63c910d08844bd9f93edba6e6488277d368e8435",
+        "32ddbbe14e96876b8e6fc2e06a42e2276f3ee5dadc6eebd64e1f82dc1adcf85693850e82d47bcebe42afa9d6",
+        "90b4078655d542f9dc7e7bcc088cbda466c59405f76818a06f7722c8462a7c2b9d797fb71ea53500fbe85624",
+        "393bd78992489d501f2d6178f65d0b76d9fda1adffe71e2893fb8305fdcfc0b2f2a6805e694d0f5e51cd7c05",
+        "0fb6fc45d04789a6a88c7af145d8abe203308763f06414dca4bf7c17d5cc9dccfd6bf09eb718a48faa04383b",
+        "48806f840b5a857216f4f0cae8fc15e65c29a5f17e9a13805b30d21ae44c41edc280515e13ef2944ecc47015",
+        "4741526935fa8e0ffcd7b56f59c5f1470b5ab884ab9d141cf01d2e962f1cb7e67097ee27b459023dc4ebbafd",
+        "506ff7a4a6f21ef673604484f75c3c924cbd8e413ef25e28e894f7097d63a2cbe6e22e73ad23d109790cabd4",
+        "b59fe7c91cb9f8f651328ad594f43ade1c9f5f2a8f993e21f714509f0a05fb8f0c50d9537261f9ce6ddc9a3e",
+        "95f7640b6853a34ea84d56994ad9aab2ffe9ef888ab5aa6ffa10501acc6b549961ed9697ef48a6877944a2b3",
+        "9c0554b9cc4f3bf1ec4258141d91ecb1499db728f5a982a0d51eae80d1a19217b424d5ec6516af0e70233872",
+        "d33f0fa09565307bbe7e7046179a7f7bd40a57d501bee21afb874a2409c48a2bec8948ce9c47edd979779be0",
+        "bb145da87fc37b2e6b47680b402fbdc6b25a6cc016527a8448509217e53583b3733fc0c6b57fc5f632dfbd5e",
+        "fe84b8ebe09d63ca6ed30f5bea5e17441eb0db6e08e7295cb46314d2355bc6a63013b56fbbfda2018a22bd1b",
+        "e841a5ac154a155cdf377d0d31c242216326a0d75bac01c05175d9a533927f92f7a769bc8d8cbe2e5d1e222b",
+        "137da015eccdf5c02d89d732",
+    );
+
+    /// Initial and Handshake packets of the server
+    const SERVER_INITIAL: &str = concat!(
+        "cd0000000108c50138f4cad6ec9808c1df9465a7e72dbd004075f339c402d5a327677912b8b28930440aaa70",
+        "3d3b67da2cca628dfa2abcc85bba5f06221ad4b3bf01e51f41602fa54d9b8689cf851c30277098983fa5d9be",
+        "69150edb14820a6cc1410a1146fda7dcc472129fa62ef5abeaa51a82cb9634de8e2aa79bcf5c0b04eac804af",
+        "bd18edc7ece222ccf7f960a40000000108c50138f4cad6ec9808c1df9465a7e72dbd4408ab9bcc660c280d74",
+        "3e706beea261f29740a59b20cc4b74aa8ffcc87d314013304c4010f9417fc80ee61fb38986dd15a22496ed6e",
+        "ab419da4b1254e938792d5629e5cfd724950de98cc4936c66d719c449daa8ebf4ebca79d3fd54a45cc271003",
+        "b2c46210d7ad489750cf254c2f637fba8cefa702772acf33ea30fc7ab8f61776898644186202b5d6fdee6d22",
+        "2c2dad7a22a15190e4d154e97bcae5cca81a6ed033a6982a418b87d35805207fa8b9cec89fe5b23bdd6dc669",
+        "7a60646fe243dd0be23736ccb57ea9a4ea8f73007262e144e3744502425a887960a3fad30b71a90ad58897d5",
+        "ea6889bf81f388e9ae59292a992d1cc9ebf7450b6676a1eb0e169d9caefff20fb6e807fecdbc052f5804c973",
+        "3d0b88c86ad3aa9fc8e3708a2e8f0bb5436b3ccdc9603a0d7311df14d506bc8888fbf6c3f4b9fcafa80c75ee",
+        "cdbec8cce403c4112b61344c108c9c6ea6afe52c596804ed9167181b8a09939be723c2d8d2919ca95fb37623",
+        "172304cc48b1b4d57e1e887f7457249c79c8e1290dcb806e61d514f71ea192df9ee365239e340275492cd04c",
+        "b787fdd5a7ce7c676e1e128aae10ddaf43d2d9d612dfe7de7986e137219de51c5dcfa7f9214471d3201ebcaa",
+        "841ccce47d65e3c1aae7200959803419255f50ea221f935145d81eefa4049941947b34c4210cf0447bce907b",
+        "64b44498696336bc196df14bb3da4eefe76bae91ed76d32b5ac25620e9e3121a857d19f651c557a9ca1f1b14",
+        "c4374707d94daa575eb9b9e08a22c22fbb9a9561f6545a2745631ea9fc3eb4b64d25551b1da24adbc11670b4",
+        "4f1c9bea578bf34cbfd64f821cab8a0c6ae870be11046b75cfa04d689bdc61a1af7a8e5a085044cbb9457f05",
+        "736d19112c46add69a9817aacc22495bb51ef86633e6ca497f12637755eaa938006589cdae4b07b110d164d9",
+        "445e5dcccc827707b3e07af5dd2cf984883b4e7fb7958fdda0bc3e755ef531e7945648d0adcd044936d25fdb",
+        "726f772721d45f48be2b1f91b247ba67cedc2b9b15d394866ad1c54dc49f1da10832c1b9a8202f57854cba32",
+        "ba8cf91098d385588a5df53b08987e81b00e9080068df6e0e6b4ff399333fa3d11ae2b06cc3b1b1b8571be69",
+        "4d19253ad3f9012f19256e9c5f69a37bee38c412d50e62d29424bed63d22e792ab39af906b962e2e59250f6d",
+        "524d44591212b02e534acfa7c807c86433d03a30541ed7c964f1a5332d9cc0a80613b2f8e75b243775ca445c",
+        "a535967769b27ad89eb34c26837ce031a6ce3e934a324573a9af45834aab3b5cec3128b6beef932ece2674ec",
+        "07405d8b657251e8d2ff36d9357ea1287947542578ffac62c2b464451f70b28c7ea1f39ddc7d0494807ac3b5",
+        "8086912b8f2886cca81415707d970174710cdb156c221b73efa45e868b4b1eefb0623fc03c5bd8c4ea16ab2f",
+        "d1813d3d206fdce7e322eced",
+    );
+
+    /// 1-RTT packet of the server
+    const SERVER_SHORT: &str = concat!(
+        "06c50138f4cad6ec985ff0cfb1587eb89aad2f722bc1bd877ebe6f58c514d4d24793fadb460a5d13b5f57d33",
+        "fa788ce59813578571f750362497e715bc49e2d99a74e50afd0fe03b52bb21f6eafd8a5a0d7e1bde0a23a355",
+        "c83a3e9fe4493e316cf14ed54c6b742cd7c1edd790308af7758029adde412962676b5c6e7a916d2f31d58987",
+        "1a9527f405c8",
+    );
+
+    fn packet_types(quic_packet: &SerializableQuicPacket) -> Vec<&str> {
+        quic_packet
+            .packets
+            .iter()
+            .map(|packet| packet.packet_type.as_str())
+            .collect()
+    }
+
+    fn hex(text: &str) -> Vec<u8> {
+        (0..text.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&text[i..i + 2], 16).unwrap())
+            .collect()
+    }
+}
@@ -0,0 +1,648 @@
+//! Registry of the application-layer dissectors
+//!
+//! Every application-layer protocol is decoded by a [`Dissector`], which declares the ports its
+//! protocol is associated with, the transport protocols carrying it, and can recognize its payloads. The dissectors of the protocols
+//! supported by wirefish are registered by default; more can be added at runtime with
+//! [`register_dissector`], e.g. to decode proprietary protocols without patching this crate.
+//!
+//! Dissectors registered at runtime take precedence over the built-in ones, so they can also
+//! replace the decoding of a supported protocol.
+
+use std::{
+    net::IpAddr,
+    sync::{Arc, OnceLock, RwLock},
+};
+
+use crate::serializable_packet::{PacketMeta, ParsedPacket, SerializablePacket};
+use crate::ParserContext;
+
+use super::{
+    cql::handle_cql_packet,
+    dhcp::{handle_dhcp_packet, is_dhcp_message},
+    dns::handle_dns_packet,
+    gtp::{handle_gtp_packet, is_gtp_message},
+    heuristics::{
+        is_dns_message, is_http2_preface, is_http_request, is_http_response, is_tls_record,
+    },
+    http::handle_http_packet,
+    http2::handle_http2_packet,
+    ike::{handle_ike_packet, is_ike_message},
+    iscsi::handle_iscsi_packet,
+    kafka::handle_kafka_packet,
+    ntp::handle_ntp_packet,
+    nvme_tcp::handle_nvme_tcp_packet,
+    ptp::handle_ptp_packet,
+    quic::handle_quic_packet,
+    s7comm::handle_s7comm_packet,
+    snmp::{handle_snmp_packet, is_snmp_message},
+    tls::handle_tls_packet,
+    wireguard::{handle_wireguard_packet, is_wireguard_message},
+    zookeeper::handle_zookeeper_packet,
+    ApplicationProtocol, HttpPacketType, WellKnownPorts,
+};
+
+/// Dissectors shared by all the threads, initialized with the built-in ones on first use
+static DISSECTORS: OnceLock<RwLock<DissectorRegistry>> = OnceLock::new();
+
+/// Transport-layer protocol carrying the payload to dissect
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transport {
+    Tcp,
+    Udp,
+}
+
+/// Transport-layer information about the payload to dissect
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DissectionContext<'a> {
+    pub source_ip: IpAddr,
+    pub source_port: u16,
+    pub dest_ip: IpAddr,
+    pub dest_port: u16,
+    /// Whether the payload is sent by the client (i.e. to the port of the protocol)
+    pub is_request: bool,
+    /// Whether the payload is the last one of its TCP connection
+    pub is_fin: bool,
+    /// Capture metadata of the packet carrying the payload (e.g. its timestamp and direction)
+    pub meta: &'a PacketMeta,
+}
+
+/// Decoder of an application-layer protocol carried over TCP/UDP
+pub trait Dissector: Send + Sync {
+    /// Name of the protocol, used to look the dissector up
+    fn name(&self) -> &str;
+
+    /// Ports the protocol is associated with
+    fn ports(&self) -> &[u16] {
+        &[]
+    }
+
+    /// Transport protocols the protocol is carried over
+    fn transports(&self) -> &[Transport] {
+        &[Transport::Tcp, Transport::Udp]
+    }
+
+    /// Check if a payload on ports not associated with any protocol belongs to this protocol
+    fn probe(&self, payload: &[u8]) -> bool {
+        let _ = payload;
+        false
+    }
+
+    /// Check if a payload recognized by [`Dissector::probe`] is sent by the client
+    fn is_request(&self, payload: &[u8]) -> bool {
+        let _ = payload;
+        true
+    }
+
+    /// Decode a payload, returning none when there is nothing to show yet (e.g. while a message
+    /// spanning multiple segments is being reassembled)
+    ///
+    /// The parser context of the capture holds the state kept across its packets: dissectors
+    /// relying on other ones (e.g. the built-in ones) give it to them.
+    fn dissect(
+        &self,
+        state: &mut ParserContext,
+        context: &DissectionContext,
+        payload: &[u8],
+    ) -> Option<SerializablePacket>;
+}
+
+/// Ordered set of dissectors, from the highest precedence to the lowest
+#[derive(Clone, Default)]
+pub struct DissectorRegistry {
+    dissectors: Vec<Arc<dyn Dissector>>,
+}
+
+impl DissectorRegistry {
+    /// Create an empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a registry of the protocols supported by wirefish
+    pub fn with_builtin_dissectors() -> Self {
+        Self {
+            dissectors: builtin_dissectors()
+                .into_iter()
+                .map(|dissector| Arc::new(dissector) as Arc<dyn Dissector>)
+                .collect(),
+        }
+    }
+
+    /// Add a dissector, with precedence over the ones already registered
+    ///
+    /// A dissector with the same name of a registered one replaces it.
+    pub fn register(&mut self, dissector: Arc<dyn Dissector>) {
+        self.dissectors
+            .retain(|registered| registered.name() != dissector.name());
+        self.dissectors.insert(0, dissector);
+    }
+
+    /// Get the dissector of a protocol by its name
+    pub fn get(&self, name: &str) -> Option<Arc<dyn Dissector>> {
+        self.dissectors
+            .iter()
+            .find(|dissector| dissector.name() == name)
+            .cloned()
+    }
+
+    /// Get the dissector associated with a port of a transport protocol, along with its
+    /// precedence (lower is higher)
+    pub fn by_port(&self, port: u16, transport: Transport) -> Option<(usize, Arc<dyn Dissector>)> {
+        self.dissectors
+            .iter()
+            .position(|dissector| {
+                dissector.ports().contains(&port) && dissector.transports().contains(&transport)
+            })
+            .map(|precedence| (precedence, self.dissectors[precedence].clone()))
+    }
+
+    /// Get the first dissector recognizing a payload carried over a transport protocol
+    pub fn probe(&self, payload: &[u8], transport: Transport) -> Option<Arc<dyn Dissector>> {
+        self.dissectors
+            .iter()
+            .find(|dissector| {
+                dissector.transports().contains(&transport) && dissector.probe(payload)
+            })
+            .cloned()
+    }
+
+    /// Get the names of the registered dissectors, in order of precedence
+    pub fn names(&self) -> Vec<String> {
+        self.dissectors
+            .iter()
+            .map(|dissector| dissector.name().to_owned())
+            .collect()
+    }
+}
+
+/// Add a dissector to the ones used to decode the application layer of the parsed packets
+pub fn register_dissector(dissector: Arc<dyn Dissector>) {
+    dissectors().write().unwrap().register(dissector);
+}
+
+/// Get the names of the dissectors used to decode the application layer, in order of precedence
+pub fn get_dissectors() -> Vec<String> {
+    dissectors().read().unwrap().names()
+}
+
+pub(crate) fn dissectors() -> &'static RwLock<DissectorRegistry> {
+    DISSECTORS.get_or_init(|| RwLock::new(DissectorRegistry::with_builtin_dissectors()))
+}
+
+/// Dissector of a protocol supported by wirefish, wrapping its packet handler
+struct BuiltinDissector {
+    protocol: ApplicationProtocol,
+    name: String,
+    ports: &'static [u16],
+    probe: fn(&[u8]) -> bool,
+    handle: fn(&mut ParserContext, &DissectionContext, &[u8], &mut ParsedPacket),
+}
+
+impl Dissector for BuiltinDissector {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn ports(&self) -> &[u16] {
+        self.ports
+    }
+
+    fn transports(&self) -> &[Transport] {
+        self.protocol.transports()
+    }
+
+    fn probe(&self, payload: &[u8]) -> bool {
+        (self.probe)(payload)
+    }
+
+    fn is_request(&self, payload: &[u8]) -> bool {
+        !(self.protocol == ApplicationProtocol::Http && is_http_response(payload))
+    }
+
+    fn dissect(
+        &self,
+        state: &mut ParserContext,
+        context: &DissectionContext,
+        payload: &[u8],
+    ) -> Option<SerializablePacket> {
+        let mut parsed_packet = ParsedPacket::with_meta(context.meta.clone());
+        (self.handle)(state, context, payload, &mut parsed_packet);
+
+        parsed_packet.get_application_layer_packet().cloned()
+    }
+}
+
+/// Dissectors of the protocols supported by wirefish, in order of precedence when both the ports
+/// of a packet are well known
+fn builtin_dissectors() -> Vec<BuiltinDissector> {
+    let dissector =
+        |protocol: ApplicationProtocol,
+         ports: &'static [u16],
+         probe: fn(&[u8]) -> bool,
+         handle: fn(&mut ParserContext, &DissectionContext, &[u8], &mut ParsedPacket)| {
+            BuiltinDissector {
+                protocol,
+                name: protocol.name(),
+                ports,
+                probe,
+                handle,
+            }
+        };
+    let unrecognized = |_: &[u8]| false;
+
+    vec![
+        dissector(
+            ApplicationProtocol::Http,
+            &[
+                WellKnownPorts::HTTP_PORT,
+                WellKnownPorts::HTTP_PROXY_PORT,
+                WellKnownPorts::DOCKER_REGISTRY_PORT,
+                WellKnownPorts::HTTP_ALT_PORT,
+            ],
+            |payload| is_http_request(payload) || is_http_response(payload),
+            |state, c, payload, parsed_packet| {
+                let http_type = if c.is_request {
+                    HttpPacketType::Request
+                } else {
+                    HttpPacketType::Response
+                };
+
+                handle_http_packet(
+                    state,
+                    c.source_ip,
+                    c.source_port,
+                    c.dest_ip,
+                    c.dest_port,
+                    http_type,
+                    c.is_fin,
+                    payload,
+                    parsed_packet,
+                )
+            },
+        ),
+        dissector(
+            ApplicationProtocol::Tls,
+            &[WellKnownPorts::TLS_PORT],
+            is_tls_record,
+            |state, c, payload, parsed_packet| {
+                handle_tls_packet(
+                    state,
+                    c.source_ip,
+                    c.source_port,
+                    c.dest_ip,
+                    c.dest_port,
+                    payload,
+                    parsed_packet,
+                )
+            },
+        ),
+        dissector(
+            ApplicationProtocol::Dns,
+            &[WellKnownPorts::DNS_PORT, WellKnownPorts::MDNS_PORT],
+            is_dns_message,
+            |_, c, payload, parsed_packet| {
+                handle_dns_packet(
+                    c.source_ip,
+                    c.source_port,
+                    c.dest_ip,
+                    c.dest_port,
+                    payload,
+                    parsed_packet,
+                )
+            },
+        ),
+        dissector(
+            ApplicationProtocol::Ptp,
+            &[
+                WellKnownPorts::PTP_EVENT_PORT,
+                WellKnownPorts::PTP_GENERAL_PORT,
+            ],
+            unrecognized,
+            |state, _, payload, parsed_packet| handle_ptp_packet(state, payload, parsed_packet),
+        ),
+        dissector(
+            ApplicationProtocol::S7comm,
+            &[WellKnownPorts::ISO_TSAP_PORT],
+            unrecognized,
+            |_, c, payload, parsed_packet| {
+                handle_s7comm_packet(
+                    c.source_ip,
+                    c.source_port,
+                    c.dest_ip,
+                    c.dest_port,
+                    payload,
+                    parsed_packet,
+                )
+            },
+        ),
+        dissector(
+            ApplicationProtocol::Iscsi,
+            &[WellKnownPorts::ISCSI_PORT],
+            unrecognized,
+            |_, c, payload, parsed_packet| {
+                handle_iscsi_packet(
+                    c.source_ip,
+                    c.source_port,
+                    c.dest_ip,
+                    c.dest_port,
+                    payload,
+                    parsed_packet,
+                )
+            },
+        ),
+        dissector(
+            ApplicationProtocol::NvmeTcp,
+            &[WellKnownPorts::NVME_TCP_PORT],
+            unrecognized,
+            |_, c, payload, parsed_packet| {
+                handle_nvme_tcp_packet(
+                    c.source_ip,
+                    c.source_port,
+                    c.dest_ip,
+                    c.dest_port,
+                    payload,
+                    parsed_packet,
+                )
+            },
+        ),
+        dissector(
+            ApplicationProtocol::Cql,
+            &[WellKnownPorts::CQL_PORT],
+            unrecognized,
+            |_, c, payload, parsed_packet| {
+                handle_cql_packet(
+                    c.source_ip,
+                    c.source_port,
+                    c.dest_ip,
+                    c.dest_port,
+                    payload,
+                    parsed_packet,
+                )
+            },
+        ),
+        dissector(
+            ApplicationProtocol::Kafka,
+            &[WellKnownPorts::KAFKA_PORT],
+            unrecognized,
+            |state, c, payload, parsed_packet| {
+                handle_kafka_packet(
+                    state,
+                    c.source_ip,
+                    c.source_port,
+                    c.dest_ip,
+                    c.dest_port,
+                    c.is_request,
+                    payload,
+                    parsed_packet,
+                )
+            },
+        ),
+        dissector(
+            ApplicationProtocol::Zookeeper,
+            &[WellKnownPorts::ZOOKEEPER_PORT],
+            unrecognized,
+            |state, c, payload, parsed_packet| {
+                handle_zookeeper_packet(
+                    state,
+                    c.source_ip,
+                    c.source_port,
+                    c.dest_ip,
+                    c.dest_port,
+                    c.is_request,
+                    payload,
+                    parsed_packet,
+                )
+            },
+        ),
+        dissector(
+            ApplicationProtocol::Http2,
+            &[WellKnownPorts::ETCD_PORT],
+            is_http2_preface,
+            |state, c, payload, parsed_packet| {
+                handle_http2_packet(
+                    state,
+                    c.source_ip,
+                    c.source_port,
+                    c.dest_ip,
+                    c.dest_port,
+                    c.is_request,
+                    c.is_fin,
+                    payload,
+                    parsed_packet,
+                )
+            },
+        ),
+        dissector(
+            ApplicationProtocol::Quic,
+            &[WellKnownPorts::QUIC_PORT],
+            unrecognized,
+            |state, c, payload, parsed_packet| {
+                handle_quic_packet(
+                    state,
+                    c.source_ip,
+                    c.source_port,
+                    c.dest_ip,
+                    c.dest_port,
+                    c.is_request,
+                    payload,
+                    parsed_packet,
+                )
+            },
+        ),
+        dissector(
+            ApplicationProtocol::Dhcp,
+            &[
+                WellKnownPorts::DHCP_SERVER_PORT,
+                WellKnownPorts::DHCP_CLIENT_PORT,
+            ],
+            is_dhcp_message,
+            |_, _, payload, parsed_packet| handle_dhcp_packet(payload, parsed_packet),
+        ),
+        dissector(
+            ApplicationProtocol::Snmp,
+            &[WellKnownPorts::SNMP_PORT, WellKnownPorts::SNMP_TRAP_PORT],
+            is_snmp_message,
+            |_, _, payload, parsed_packet| handle_snmp_packet(payload, parsed_packet),
+        ),
+        dissector(
+            ApplicationProtocol::Gtp,
+            &[WellKnownPorts::GTP_C_PORT, WellKnownPorts::GTP_U_PORT],
+            is_gtp_message,
+            |_, _, payload, parsed_packet| handle_gtp_packet(payload, parsed_packet),
+        ),
+        dissector(
+            ApplicationProtocol::Ike,
+            &[WellKnownPorts::IKE_PORT, WellKnownPorts::IKE_NAT_T_PORT],
+            is_ike_message,
+            |_, c, payload, parsed_packet| {
+                handle_ike_packet(
+                    c.source_ip,
+                    c.source_port,
+                    c.dest_ip,
+                    c.dest_port,
+                    payload,
+                    parsed_packet,
+                )
+            },
+        ),
+        dissector(
+            ApplicationProtocol::WireGuard,
+            &[WellKnownPorts::WIREGUARD_PORT],
+            is_wireguard_message,
+            |_, _, payload, parsed_packet| handle_wireguard_packet(payload, parsed_packet),
+        ),
+        dissector(
+            ApplicationProtocol::Ntp,
+            &[WellKnownPorts::NTP_PORT],
+            unrecognized,
+            |_, _, payload, parsed_packet| handle_ntp_packet(payload, parsed_packet),
+        ),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::serializable_packet::application::SerializableCustomPacket;
+    use crate::serializable_packet::{PacketMeta, SerializablePacket};
+    use crate::ParserContext;
+
+    use super::{DissectionContext, Dissector, DissectorRegistry, Transport};
+
+    #[test]
+    fn builtin_dissectors_by_port_and_payload() {
+        let registry = DissectorRegistry::with_builtin_dissectors();
+
+        let (precedence, dissector) = registry.by_port(8080, Transport::Tcp).unwrap();
+        assert_eq!((precedence, dissector.name()), (0, "http"));
+        assert_eq!(
+            registry.by_port(9092, Transport::Tcp).unwrap().1.name(),
+            "kafka"
+        );
+        assert_eq!(
+            registry.by_port(2379, Transport::Tcp).unwrap().1.name(),
+            "http2"
+        );
+        assert!(registry.by_port(50000, Transport::Tcp).is_none());
+        assert_eq!(
+            registry.by_port(443, Transport::Tcp).unwrap().1.name(),
+            "tls"
+        );
+        assert_eq!(
+            registry.by_port(443, Transport::Udp).unwrap().1.name(),
+            "quic"
+        );
+        assert!(registry.by_port(8080, Transport::Udp).is_none());
+        assert_eq!(
+            registry.by_port(51820, Transport::Udp).unwrap().1.name(),
+            "wireguard"
+        );
+        assert_eq!(
+            registry.by_port(123, Transport::Udp).unwrap().1.name(),
+            "ntp"
+        );
+
+        let dissector = registry
+            .probe(b"HTTP/1.1 200 OK\r\n\r\n", Transport::Tcp)
+            .unwrap();
+        assert_eq!(dissector.name(), "http");
+        assert!(!dissector.is_request(b"HTTP/1.1 200 OK\r\n\r\n"));
+        assert_eq!(
+            registry
+                .probe(&[0x16, 0x03, 0x01, 0x02, 0x00, 0x01], Transport::Tcp)
+                .unwrap()
+                .name(),
+            "tls"
+        );
+        assert_eq!(
+            registry
+                .probe(b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n", Transport::Tcp)
+                .unwrap()
+                .name(),
+            "http2"
+        );
+        assert!(registry.probe(b"\x00\x01\x02", Transport::Tcp).is_none());
+    }
+
+    #[test]
+    fn custom_dissector() {
+        let mut registry = DissectorRegistry::with_builtin_dissectors();
+        registry.register(Arc::new(EchoDissector));
+
+        assert_eq!(registry.names()[..3], ["echo", "http", "tls"]);
+        assert_eq!(
+            registry.by_port(8080, Transport::Tcp).unwrap().1.name(),
+            "echo"
+        );
+        assert_eq!(
+            registry
+                .probe(b"ECHO hello", Transport::Udp)
+                .unwrap()
+                .name(),
+            "echo"
+        );
+        assert_eq!(registry.get("kafka").unwrap().name(), "kafka");
+
+        let context = DissectionContext {
+            source_ip: "10.0.0.1".parse().unwrap(),
+            source_port: 50000,
+            dest_ip: "10.0.0.2".parse().unwrap(),
+            dest_port: 8080,
+            is_request: true,
+            is_fin: false,
+            meta: &PacketMeta {
+                timestamp: 1663000000000000,
+                ..PacketMeta::default()
+            },
+        };
+        match registry.get("echo").unwrap().dissect(
+            &mut ParserContext::new(),
+            &context,
+            b"ECHO hello",
+        ) {
+            Some(SerializablePacket::CustomPacket(packet)) => {
+                assert_eq!(packet.protocol, "echo");
+                assert_eq!(packet.fields["text"], "hello");
+                assert_eq!(packet.fields["timestamp"], 1663000000000000u64);
+            }
+            _ => unreachable!(),
+        }
+
+        // Registering a dissector again replaces it
+        registry.register(Arc::new(EchoDissector));
+        assert_eq!(registry.names().len(), 19);
+    }
+
+    ///////////////////// Utils
+
+    struct EchoDissector;
+
+    impl Dissector for EchoDissector {
+        fn name(&self) -> &str {
+            "echo"
+        }
+
+        fn ports(&self) -> &[u16] {
+            &[8080]
+        }
+
+        fn probe(&self, payload: &[u8]) -> bool {
+            payload.starts_with(b"ECHO ")
+        }
+
+        fn dissect(
+            &self,
+            _state: &mut ParserContext,
+            context: &DissectionContext,
+            payload: &[u8],
+        ) -> Option<SerializablePacket> {
+            let text = std::str::from_utf8(payload.strip_prefix(b"ECHO ")?).ok()?;
+
+            Some(SerializablePacket::CustomPacket(SerializableCustomPacket {
+                protocol: self.name().to_owned(),
+                fields: serde_json::json!({ "text": text, "timestamp": context.meta.timestamp }),
+            }))
+        }
+    }
+}
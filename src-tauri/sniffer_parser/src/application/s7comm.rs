@@ -0,0 +1,468 @@
+//! S7comm Packet parsing
+//!
+//! Siemens S7 communication runs over ISO-on-TCP (TCP port 102): a TPKT header (RFC 1006)
+//! precedes a COTP TPDU (ISO 8073), whose data TPDUs carry the S7comm PDUs.
+//! Only the first TPKT of a segment is dissected.
+
+use std::net::IpAddr;
+
+use tracing::debug;
+
+use crate::serializable_packet::application::{S7Header, S7Item, SerializableS7commPacket};
+use crate::serializable_packet::{ParsedPacket, SerializablePacket};
+
+const TPKT_HEADER_LENGTH: usize = 4;
+const TPKT_VERSION: u8 = 3;
+const S7_PROTOCOL_ID: u8 = 0x32;
+const S7_HEADER_LENGTH: usize = 10;
+const S7_ERROR_LENGTH: usize = 2;
+const S7_ITEM_LENGTH: usize = 12;
+
+/// COTP data TPDU
+const COTP_DATA: u8 = 0xf0;
+
+/// S7comm ROSCTR (PDU types)
+#[allow(non_snake_case)]
+mod Rosctr {
+    pub const JOB: u8 = 0x01;
+    pub const ACK: u8 = 0x02;
+    pub const ACK_DATA: u8 = 0x03;
+    pub const USERDATA: u8 = 0x07;
+}
+
+/// S7comm function codes
+#[allow(non_snake_case)]
+mod FunctionCodes {
+    pub const READ_VAR: u8 = 0x04;
+    pub const WRITE_VAR: u8 = 0x05;
+}
+
+/// S7comm variable specification with S7ANY addressing
+const VARIABLE_SPECIFICATION: u8 = 0x12;
+const SYNTAX_ID_S7ANY: u8 = 0x10;
+const AREA_DATA_BLOCKS: u8 = 0x84;
+
+/// Build an S7comm packet from a transport-layer packet, save it in a Parsed Packet
+pub fn handle_s7comm_packet(
+    source_ip: IpAddr,
+    source_port: u16,
+    dest_ip: IpAddr,
+    dest_port: u16,
+    packet: &[u8],
+    parsed_packet: &mut ParsedPacket,
+) {
+    // Segments without data (e.g. plain ACKs)
+    if packet.is_empty() {
+        return;
+    }
+
+    match parse_s7comm_packet(packet) {
+        Some(s7comm_packet) => {
+            debug!(
+                "S7comm Packet: {}:{} > {}:{}; COTP: {}, ROSCTR: {:?}, Function: {:?}, Items: {}",
+                source_ip,
+                source_port,
+                dest_ip,
+                dest_port,
+                s7comm_packet.cotp_pdu_type,
+                s7comm_packet.header.as_ref().map(|header| &header.rosctr),
+                s7comm_packet.function,
+                s7comm_packet.items.len()
+            );
+
+            parsed_packet.set_application_layer_packet(Some(SerializablePacket::S7commPacket(
+                s7comm_packet,
+            )));
+        }
+        None => {
+            debug!("Malformed S7comm Packet");
+            parsed_packet.set_application_layer_packet(Some(SerializablePacket::MalformedPacket(
+                "Malformed S7comm Packet".to_string(),
+            )));
+        }
+    }
+}
+
+fn parse_s7comm_packet(packet: &[u8]) -> Option<SerializableS7commPacket> {
+    // TPKT
+    if packet.len() < TPKT_HEADER_LENGTH || packet[0] != TPKT_VERSION {
+        return None;
+    }
+    let tpkt_length = u16::from_be_bytes([packet[2], packet[3]]);
+    let tpkt = packet.get(TPKT_HEADER_LENGTH..tpkt_length as usize)?;
+
+    // COTP
+    let cotp_length = *tpkt.first()? as usize;
+    let cotp_pdu_type = *tpkt.get(1)?;
+    let payload = tpkt.get(1 + cotp_length..)?;
+
+    let mut s7comm_packet = SerializableS7commPacket {
+        tpkt_length,
+        cotp_pdu_type: cotp_pdu_type_name(cotp_pdu_type),
+        header: None,
+        function: None,
+        items: vec![],
+        return_codes: vec![],
+    };
+
+    // Connection management TPDUs and fragments without the S7comm header
+    if cotp_pdu_type & 0xf0 != COTP_DATA || payload.first() != Some(&S7_PROTOCOL_ID) {
+        return Some(s7comm_packet);
+    }
+
+    // S7comm header
+    if payload.len() < S7_HEADER_LENGTH {
+        return None;
+    }
+    let rosctr = payload[1];
+    let parameter_length = u16::from_be_bytes([payload[6], payload[7]]);
+    let data_length = u16::from_be_bytes([payload[8], payload[9]]);
+
+    let (error_class, error_code, header_length) = match rosctr {
+        Rosctr::ACK | Rosctr::ACK_DATA => {
+            let error = payload.get(S7_HEADER_LENGTH..S7_HEADER_LENGTH + S7_ERROR_LENGTH)?;
+            (
+                Some(error[0]),
+                Some(error[1]),
+                S7_HEADER_LENGTH + S7_ERROR_LENGTH,
+            )
+        }
+        _ => (None, None, S7_HEADER_LENGTH),
+    };
+
+    s7comm_packet.header = Some(S7Header {
+        rosctr: rosctr_name(rosctr),
+        pdu_reference: u16::from_be_bytes([payload[4], payload[5]]),
+        parameter_length,
+        data_length,
+        error_class,
+        error_code,
+    });
+
+    let parameters = payload.get(header_length..header_length + parameter_length as usize)?;
+    let data = payload.get(
+        header_length + parameter_length as usize
+            ..header_length + parameter_length as usize + data_length as usize,
+    )?;
+
+    let function = match parameters.first() {
+        Some(function) => *function,
+        None => return Some(s7comm_packet),
+    };
+    s7comm_packet.function = Some(function_name(rosctr, function));
+
+    if function == FunctionCodes::READ_VAR || function == FunctionCodes::WRITE_VAR {
+        let item_count = *parameters.get(1)? as usize;
+
+        match rosctr {
+            Rosctr::JOB => {
+                s7comm_packet.items = parameters[2..]
+                    .chunks(S7_ITEM_LENGTH)
+                    .take(item_count)
+                    .map(parse_item)
+                    .collect::<Option<Vec<S7Item>>>()?;
+            }
+            Rosctr::ACK_DATA => {
+                s7comm_packet.return_codes = parse_return_codes(function, item_count, data)?;
+            }
+            _ => (),
+        }
+    }
+
+    Some(s7comm_packet)
+}
+
+/// Parse the address of a read/write item
+fn parse_item(item: &[u8]) -> Option<S7Item> {
+    if item.len() < S7_ITEM_LENGTH
+        || item[0] != VARIABLE_SPECIFICATION
+        || item[2] != SYNTAX_ID_S7ANY
+    {
+        return None;
+    }
+
+    let transport_size = transport_size_name(item[3]);
+    let count = u16::from_be_bytes([item[4], item[5]]);
+    let db_number = u16::from_be_bytes([item[6], item[7]]);
+    let area = item[8];
+    let address = u32::from_be_bytes([0, item[9], item[10], item[11]]);
+    let byte_address = address >> 3;
+    let bit_address = (address & 0x07) as u8;
+
+    let (area_name, db_number) = match area {
+        AREA_DATA_BLOCKS => ("DB".to_owned(), Some(db_number)),
+        _ => (area_name(area), None),
+    };
+
+    let location = match db_number {
+        Some(db_number) => format!("DB{}.DBX{}.{}", db_number, byte_address, bit_address),
+        None => format!("{}{}.{}", area_name, byte_address, bit_address),
+    };
+
+    Some(S7Item {
+        area: area_name,
+        db_number,
+        byte_address,
+        bit_address,
+        address: format!("{} {} {}", location, transport_size, count),
+        transport_size,
+        count,
+    })
+}
+
+/// Parse the return codes of the items in a read/write response
+fn parse_return_codes(function: u8, item_count: usize, mut data: &[u8]) -> Option<Vec<String>> {
+    let mut return_codes = vec![];
+
+    for index in 0..item_count {
+        let return_code = *data.first()?;
+        return_codes.push(return_code_name(return_code));
+
+        if function == FunctionCodes::WRITE_VAR {
+            data = &data[1..];
+            continue;
+        }
+
+        // Read items: return code, transport size, length and value (padded to even length)
+        let header = data.get(..4)?;
+        let length = u16::from_be_bytes([header[2], header[3]]) as usize;
+        let length = match header[1] {
+            // Length in bits
+            0x03..=0x05 => length.div_ceil(8),
+            _ => length,
+        };
+        let mut item_length = 4 + length;
+        if index + 1 < item_count && item_length % 2 != 0 {
+            item_length += 1;
+        }
+        data = data.get(item_length.min(data.len())..)?;
+    }
+
+    Some(return_codes)
+}
+
+fn cotp_pdu_type_name(pdu_type: u8) -> String {
+    match pdu_type & 0xf0 {
+        0xe0 => "Connection Request",
+        0xd0 => "Connection Confirm",
+        0x80 => "Disconnect Request",
+        0xc0 => "Disconnect Confirm",
+        0xf0 => "Data",
+        0x50 => "Expedited Data",
+        0x70 => "TPDU Error",
+        _ => "Unknown",
+    }
+    .to_owned()
+}
+
+fn rosctr_name(rosctr: u8) -> String {
+    match rosctr {
+        Rosctr::JOB => "Job",
+        Rosctr::ACK => "Ack",
+        Rosctr::ACK_DATA => "Ack_Data",
+        Rosctr::USERDATA => "Userdata",
+        _ => "Unknown",
+    }
+    .to_owned()
+}
+
+fn function_name(rosctr: u8, function: u8) -> String {
+    // Userdata PDUs carry a parameter head instead of a function code
+    if rosctr == Rosctr::USERDATA {
+        return "Userdata".to_owned();
+    }
+
+    match function {
+        0x00 => "CPU services",
+        0xf0 => "Setup communication",
+        FunctionCodes::READ_VAR => "Read Var",
+        FunctionCodes::WRITE_VAR => "Write Var",
+        0x1a => "Request download",
+        0x1b => "Download block",
+        0x1c => "Download ended",
+        0x1d => "Start upload",
+        0x1e => "Upload",
+        0x1f => "End upload",
+        0x28 => "PI-Service",
+        0x29 => "PLC Stop",
+        _ => "Unknown",
+    }
+    .to_owned()
+}
+
+fn area_name(area: u8) -> String {
+    match area {
+        0x03 => "SYSINFO",
+        0x05 => "SYSFLAGS",
+        0x06 => "ANAIN",
+        0x07 => "ANAOUT",
+        0x1c => "C",
+        0x1d => "T",
+        0x80 => "P",
+        0x81 => "I",
+        0x82 => "Q",
+        0x83 => "M",
+        0x84 => "DB",
+        0x85 => "DI",
+        0x86 => "L",
+        0x87 => "V",
+        _ => "Unknown",
+    }
+    .to_owned()
+}
+
+fn transport_size_name(transport_size: u8) -> String {
+    match transport_size {
+        0x01 => "BIT",
+        0x02 => "BYTE",
+        0x03 => "CHAR",
+        0x04 => "WORD",
+        0x05 => "INT",
+        0x06 => "DWORD",
+        0x07 => "DINT",
+        0x08 => "REAL",
+        0x1c => "COUNTER",
+        0x1d => "TIMER",
+        _ => "Unknown",
+    }
+    .to_owned()
+}
+
+fn return_code_name(return_code: u8) -> String {
+    match return_code {
+        0xff => "Success",
+        0x01 => "Hardware error",
+        0x03 => "Accessing the object not allowed",
+        0x05 => "Invalid address",
+        0x06 => "Data type not supported",
+        0x07 => "Data type inconsistent",
+        0x0a => "Object does not exist",
+        _ => "Reserved",
+    }
+    .to_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr};
+
+    use crate::serializable_packet::{ParsedPacket, SerializablePacket};
+
+    use super::handle_s7comm_packet;
+
+    #[test]
+    fn read_var_job() {
+        let parameters = [
+            &[0x04, 0x02][..],
+            // DB1.DBX10.0 BYTE 4
+            &[
+                0x12, 0x0a, 0x10, 0x02, 0x00, 0x04, 0x00, 0x01, 0x84, 0x00, 0x00, 0x50,
+            ],
+            // M2.3 BIT 1
+            &[
+                0x12, 0x0a, 0x10, 0x01, 0x00, 0x01, 0x00, 0x00, 0x83, 0x00, 0x00, 0x13,
+            ],
+        ]
+        .concat();
+        let packet = build_test_packet(0x01, &parameters, &[]);
+
+        let parsed_packet = parse(&packet);
+        match parsed_packet.get_application_layer_packet().unwrap() {
+            SerializablePacket::S7commPacket(s7comm_packet) => {
+                assert_eq!(s7comm_packet.header.as_ref().unwrap().rosctr, "Job");
+                assert_eq!(s7comm_packet.function.as_deref(), Some("Read Var"));
+                assert_eq!(s7comm_packet.items.len(), 2);
+                assert_eq!(s7comm_packet.items[0].address, "DB1.DBX10.0 BYTE 4");
+                assert_eq!(s7comm_packet.items[1].address, "M2.3 BIT 1");
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn read_var_ack_data() {
+        let data = [
+            &[0xff, 0x04, 0x00, 0x08, 0x2a][..],
+            &[0x00],
+            &[0x05, 0x00, 0x00, 0x00],
+        ]
+        .concat();
+        let packet = build_test_packet(0x03, &[0x04, 0x02], &data);
+
+        let parsed_packet = parse(&packet);
+        match parsed_packet.get_application_layer_packet().unwrap() {
+            SerializablePacket::S7commPacket(s7comm_packet) => {
+                assert_eq!(s7comm_packet.header.as_ref().unwrap().error_class, Some(0));
+                assert_eq!(
+                    s7comm_packet.return_codes,
+                    vec!["Success".to_owned(), "Invalid address".to_owned()]
+                );
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn cotp_connection_request() {
+        let packet = [
+            0x03, 0x00, 0x00, 0x16, 0x11, 0xe0, 0x00, 0x00, 0x00, 0x01, 0x00, 0xc0, 0x01, 0x0a,
+            0xc1, 0x02, 0x01, 0x00, 0xc2, 0x02, 0x01, 0x02,
+        ];
+
+        let parsed_packet = parse(&packet);
+        match parsed_packet.get_application_layer_packet().unwrap() {
+            SerializablePacket::S7commPacket(s7comm_packet) => {
+                assert_eq!(s7comm_packet.cotp_pdu_type, "Connection Request");
+                assert!(s7comm_packet.header.is_none());
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn malformed_s7comm_packet() {
+        // TPKT length larger than the segment
+        let parsed_packet = parse(&[0x03, 0x00, 0x00, 0x40, 0x02, 0xf0, 0x80, 0x32]);
+
+        assert!(matches!(
+            parsed_packet.get_application_layer_packet(),
+            Some(SerializablePacket::MalformedPacket(_))
+        ));
+    }
+
+    ///////////////////// Utils
+
+    fn parse(packet: &[u8]) -> ParsedPacket {
+        let mut parsed_packet = ParsedPacket::new(0);
+        handle_s7comm_packet(
+            IpAddr::V4(Ipv4Addr::new(192, 168, 0, 10)),
+            49152,
+            IpAddr::V4(Ipv4Addr::new(192, 168, 0, 1)),
+            102,
+            packet,
+            &mut parsed_packet,
+        );
+
+        parsed_packet
+    }
+
+    fn build_test_packet(rosctr: u8, parameters: &[u8], data: &[u8]) -> Vec<u8> {
+        let mut s7comm = vec![0x32, rosctr, 0x00, 0x00, 0x00, 0x01];
+        s7comm.extend_from_slice(&(parameters.len() as u16).to_be_bytes());
+        s7comm.extend_from_slice(&(data.len() as u16).to_be_bytes());
+        if rosctr == 0x02 || rosctr == 0x03 {
+            s7comm.extend_from_slice(&[0x00, 0x00]);
+        }
+        s7comm.extend_from_slice(parameters);
+        s7comm.extend_from_slice(data);
+
+        let length = (4 + 3 + s7comm.len()) as u16;
+        [
+            &[0x03, 0x00][..],
+            &length.to_be_bytes(),
+            &[0x02, 0xf0, 0x80],
+            &s7comm,
+        ]
+        .concat()
+    }
+}
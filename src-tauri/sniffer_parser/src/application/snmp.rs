@@ -0,0 +1,534 @@
+//! SNMP Packet parsing
+//!
+//! SNMP messages are carried over UDP, from the managers to the agents on port 161 and from the
+//! agents to the managers (traps and informs) on port 162. Each message is BER-encoded:
+//! - SNMPv1 and SNMPv2c: the version and the community, followed by the PDU
+//! - SNMPv3: the version, the global data (message ID, flags, security model), the security
+//!   parameters (the USM user and engine), followed by the scoped PDU, unless it is encrypted
+//!
+//! The PDU carries the request ID, the error status and index (the non-repeaters and maximum
+//! repetitions of GetBulk requests) and the variable bindings, each an OID with its value. The
+//! SNMPv1 traps carry the enterprise, agent address and trap type instead of the request fields.
+//!
+//! Messages are decoded with the minimal BER decoder shared with the other ASN.1 protocols.
+
+use std::net::Ipv4Addr;
+
+use tracing::debug;
+
+use super::ber::{
+    parse_element, parse_elements, to_oid, to_signed, to_string, to_unsigned, BerElement,
+};
+use crate::serializable_packet::application::{SerializableSnmpPacket, SnmpVariableBinding};
+use crate::serializable_packet::{ParsedPacket, SerializablePacket};
+
+/// BER and SNMP Tags
+#[allow(non_snake_case)]
+mod Tags {
+    pub const INTEGER: u8 = 0x02;
+    pub const OCTET_STRING: u8 = 0x04;
+    pub const NULL: u8 = 0x05;
+    pub const OBJECT_IDENTIFIER: u8 = 0x06;
+    pub const SEQUENCE: u8 = 0x30;
+    pub const IP_ADDRESS: u8 = 0x40;
+    pub const COUNTER32: u8 = 0x41;
+    pub const GAUGE32: u8 = 0x42;
+    pub const TIME_TICKS: u8 = 0x43;
+    pub const OPAQUE: u8 = 0x44;
+    pub const COUNTER64: u8 = 0x46;
+    pub const NO_SUCH_OBJECT: u8 = 0x80;
+    pub const NO_SUCH_INSTANCE: u8 = 0x81;
+    pub const END_OF_MIB_VIEW: u8 = 0x82;
+}
+
+/// SNMP PDU Types
+#[allow(non_snake_case)]
+mod PduTypes {
+    pub const TRAP_V1: u8 = 0xa4;
+    pub const GET_BULK_REQUEST: u8 = 0xa5;
+}
+
+/// User-based Security Model of SNMPv3
+const USM_SECURITY_MODEL: i64 = 3;
+
+const FLAG_AUTHENTICATED: u8 = 0x01;
+const FLAG_ENCRYPTED: u8 = 0x02;
+
+/// Build an SNMP packet from a transport-layer packet, save it in a Parsed Packet
+pub fn handle_snmp_packet(packet: &[u8], parsed_packet: &mut ParsedPacket) {
+    match parse_snmp_message(packet) {
+        Some(snmp_packet) => {
+            debug!(
+                "SNMP Packet: {}; PDU: {:?}, Request: {:?}, Bindings: {}",
+                snmp_packet.version,
+                snmp_packet.pdu_type,
+                snmp_packet.request_id,
+                snmp_packet.variable_bindings.len()
+            );
+
+            parsed_packet
+                .set_application_layer_packet(Some(SerializablePacket::SnmpPacket(snmp_packet)));
+        }
+        None => {
+            debug!("Malformed SNMP Packet");
+            parsed_packet.set_application_layer_packet(Some(SerializablePacket::MalformedPacket(
+                "Malformed SNMP Packet".to_string(),
+            )));
+        }
+    }
+}
+
+/// Check if a payload is an SNMP message: a sequence starting with a known version
+pub fn is_snmp_message(payload: &[u8]) -> bool {
+    let version = sequence(payload).and_then(|elements| integer(elements.first()?));
+
+    matches!(version, Some(0 | 1 | 3))
+}
+
+/// Parse an SNMP message of any version, without decrypting the encrypted SNMPv3 PDUs
+pub fn parse_snmp_message(packet: &[u8]) -> Option<SerializableSnmpPacket> {
+    let elements = sequence(packet)?;
+    let version = integer(elements.first()?)?;
+
+    let mut snmp_packet = SerializableSnmpPacket {
+        version: format!("{} ({})", version_name(version)?, version),
+        community: None,
+        message_id: None,
+        authenticated: false,
+        encrypted: false,
+        user_name: None,
+        engine_id: None,
+        context_name: None,
+        pdu_type: None,
+        request_id: None,
+        error_status: None,
+        error_index: None,
+        non_repeaters: None,
+        max_repetitions: None,
+        enterprise: None,
+        agent_address: None,
+        generic_trap: None,
+        specific_trap: None,
+        timestamp: None,
+        variable_bindings: vec![],
+    };
+
+    let pdu = if version == 3 {
+        let global_data = parse_elements(content(elements.get(1)?, Tags::SEQUENCE)?)?;
+        snmp_packet.message_id = Some(integer(global_data.first()?)?);
+        let flags = *content(global_data.get(2)?, Tags::OCTET_STRING)?.first()?;
+        snmp_packet.authenticated = flags & FLAG_AUTHENTICATED != 0;
+        snmp_packet.encrypted = flags & FLAG_ENCRYPTED != 0;
+
+        let security_parameters = content(elements.get(2)?, Tags::OCTET_STRING)?;
+        if integer(global_data.get(3)?)? == USM_SECURITY_MODEL {
+            parse_usm_parameters(security_parameters, &mut snmp_packet)?;
+        }
+
+        let data = elements.into_iter().nth(3)?;
+        match data.tag {
+            Tags::SEQUENCE => {
+                let scoped_pdu = parse_elements(data.value)?;
+                snmp_packet.context_name =
+                    Some(to_string(content(scoped_pdu.get(1)?, Tags::OCTET_STRING)?));
+                scoped_pdu.into_iter().nth(2)?
+            }
+            // Encrypted scoped PDU
+            Tags::OCTET_STRING => return Some(snmp_packet),
+            _ => return None,
+        }
+    } else {
+        snmp_packet.community = Some(to_string(content(elements.get(1)?, Tags::OCTET_STRING)?));
+        elements.into_iter().nth(2)?
+    };
+
+    parse_pdu(pdu, &mut snmp_packet)?;
+
+    Some(snmp_packet)
+}
+
+/// Parse the security parameters of the User-based Security Model
+fn parse_usm_parameters(parameters: &[u8], snmp_packet: &mut SerializableSnmpPacket) -> Option<()> {
+    let parameters = sequence(parameters)?;
+    let engine_id = content(parameters.first()?, Tags::OCTET_STRING)?;
+    let user_name = content(parameters.get(3)?, Tags::OCTET_STRING)?;
+
+    // Discovery requests don't know the engine yet
+    snmp_packet.engine_id = (!engine_id.is_empty()).then(|| to_hex(engine_id));
+    snmp_packet.user_name = (!user_name.is_empty()).then(|| to_string(user_name));
+
+    Some(())
+}
+
+/// Parse a PDU, with its variable bindings
+fn parse_pdu(pdu: BerElement, snmp_packet: &mut SerializableSnmpPacket) -> Option<()> {
+    snmp_packet.pdu_type = Some(format!("{} ({})", pdu_type_name(pdu.tag)?, pdu.tag & 0x1f));
+    let fields = parse_elements(pdu.value)?;
+
+    let bindings = if pdu.tag == PduTypes::TRAP_V1 {
+        snmp_packet.enterprise = Some(to_oid(content(fields.first()?, Tags::OBJECT_IDENTIFIER)?)?);
+        let agent_address: [u8; 4] = content(fields.get(1)?, Tags::IP_ADDRESS)?.try_into().ok()?;
+        snmp_packet.agent_address = Some(Ipv4Addr::from(agent_address));
+        let generic_trap = integer(fields.get(2)?)?;
+        snmp_packet.generic_trap = Some(format!(
+            "{} ({})",
+            generic_trap_name(generic_trap),
+            generic_trap
+        ));
+        snmp_packet.specific_trap = Some(integer(fields.get(3)?)?);
+        snmp_packet.timestamp = Some(to_unsigned(content(fields.get(4)?, Tags::TIME_TICKS)?)?);
+        fields.get(5)?
+    } else {
+        snmp_packet.request_id = Some(integer(fields.first()?)?);
+        let (first, second) = (integer(fields.get(1)?)?, integer(fields.get(2)?)?);
+        if pdu.tag == PduTypes::GET_BULK_REQUEST {
+            snmp_packet.non_repeaters = Some(first);
+            snmp_packet.max_repetitions = Some(second);
+        } else {
+            snmp_packet.error_status = Some(format!("{} ({})", error_status_name(first), first));
+            snmp_packet.error_index = Some(second);
+        }
+        fields.get(3)?
+    };
+
+    for binding in parse_elements(content(bindings, Tags::SEQUENCE)?)? {
+        let binding = parse_elements(content(&binding, Tags::SEQUENCE)?)?;
+        let value = binding.get(1)?;
+
+        snmp_packet.variable_bindings.push(SnmpVariableBinding {
+            oid: to_oid(content(binding.first()?, Tags::OBJECT_IDENTIFIER)?)?,
+            value_type: value_type_name(value.tag).to_owned(),
+            value: read_value(value)?,
+        });
+    }
+
+    Some(())
+}
+
+/// Parse the elements of a sequence, the whole data
+fn sequence(data: &[u8]) -> Option<Vec<BerElement<'_>>> {
+    match parse_element(data)? {
+        (element, []) if element.tag == Tags::SEQUENCE => parse_elements(element.value),
+        _ => None,
+    }
+}
+
+/// Get the content of an element, none if it has another tag
+fn content<'a>(element: &BerElement<'a>, tag: u8) -> Option<&'a [u8]> {
+    (element.tag == tag).then_some(element.value)
+}
+
+fn integer(element: &BerElement) -> Option<i64> {
+    to_signed(content(element, Tags::INTEGER)?)
+}
+
+/// Read the value of a variable binding, according to its type
+fn read_value(element: &BerElement) -> Option<String> {
+    let value = element.value;
+
+    Some(match element.tag {
+        Tags::INTEGER => to_signed(value)?.to_string(),
+        Tags::OCTET_STRING => {
+            let is_printable = std::str::from_utf8(value)
+                .is_ok_and(|string| !string.chars().any(|c| c.is_control() && !c.is_whitespace()));
+            if is_printable {
+                to_string(value)
+            } else {
+                to_hex(value)
+            }
+        }
+        Tags::OBJECT_IDENTIFIER => to_oid(value)?,
+        Tags::IP_ADDRESS => {
+            let address: [u8; 4] = value.try_into().ok()?;
+            Ipv4Addr::from(address).to_string()
+        }
+        Tags::COUNTER32 | Tags::GAUGE32 | Tags::TIME_TICKS | Tags::COUNTER64 => {
+            to_unsigned(value)?.to_string()
+        }
+        Tags::NULL | Tags::NO_SUCH_OBJECT | Tags::NO_SUCH_INSTANCE | Tags::END_OF_MIB_VIEW => {
+            String::new()
+        }
+        _ => to_hex(value),
+    })
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn version_name(version: i64) -> Option<&'static str> {
+    match version {
+        0 => Some("v1"),
+        1 => Some("v2c"),
+        3 => Some("v3"),
+        _ => None,
+    }
+}
+
+fn pdu_type_name(tag: u8) -> Option<&'static str> {
+    match tag {
+        0xa0 => Some("GetRequest"),
+        0xa1 => Some("GetNextRequest"),
+        0xa2 => Some("Response"),
+        0xa3 => Some("SetRequest"),
+        0xa4 => Some("Trap"),
+        0xa5 => Some("GetBulkRequest"),
+        0xa6 => Some("InformRequest"),
+        0xa7 => Some("SNMPv2-Trap"),
+        0xa8 => Some("Report"),
+        _ => None,
+    }
+}
+
+fn value_type_name(value_type: u8) -> &'static str {
+    match value_type {
+        Tags::INTEGER => "INTEGER",
+        Tags::OCTET_STRING => "OCTET STRING",
+        Tags::NULL => "NULL",
+        Tags::OBJECT_IDENTIFIER => "OBJECT IDENTIFIER",
+        Tags::IP_ADDRESS => "IpAddress",
+        Tags::COUNTER32 => "Counter32",
+        Tags::GAUGE32 => "Gauge32",
+        Tags::TIME_TICKS => "TimeTicks",
+        Tags::OPAQUE => "Opaque",
+        Tags::COUNTER64 => "Counter64",
+        Tags::NO_SUCH_OBJECT => "noSuchObject",
+        Tags::NO_SUCH_INSTANCE => "noSuchInstance",
+        Tags::END_OF_MIB_VIEW => "endOfMibView",
+        _ => "Unknown",
+    }
+}
+
+fn error_status_name(error_status: i64) -> &'static str {
+    match error_status {
+        0 => "noError",
+        1 => "tooBig",
+        2 => "noSuchName",
+        3 => "badValue",
+        4 => "readOnly",
+        5 => "genErr",
+        6 => "noAccess",
+        7 => "wrongType",
+        8 => "wrongLength",
+        9 => "wrongEncoding",
+        10 => "wrongValue",
+        11 => "noCreation",
+        12 => "inconsistentValue",
+        13 => "resourceUnavailable",
+        14 => "commitFailed",
+        15 => "undoFailed",
+        16 => "authorizationError",
+        17 => "notWritable",
+        18 => "inconsistentName",
+        _ => "Unknown",
+    }
+}
+
+fn generic_trap_name(generic_trap: i64) -> &'static str {
+    match generic_trap {
+        0 => "coldStart",
+        1 => "warmStart",
+        2 => "linkDown",
+        3 => "linkUp",
+        4 => "authenticationFailure",
+        5 => "egpNeighborLoss",
+        6 => "enterpriseSpecific",
+        _ => "Unknown",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use crate::serializable_packet::application::SnmpVariableBinding;
+    use crate::serializable_packet::{ParsedPacket, SerializablePacket};
+
+    use super::{handle_snmp_packet, is_snmp_message, parse_snmp_message};
+
+    #[test]
+    fn snmp_v2c_response() {
+        let message = tlv(
+            0x30,
+            &[
+                tlv(0x02, &[1]),
+                tlv(0x04, b"public"),
+                tlv(
+                    0xa2,
+                    &[
+                        tlv(0x02, &[0x1e, 0x47]),
+                        tlv(0x02, &[0]),
+                        tlv(0x02, &[0]),
+                        tlv(
+                            0x30,
+                            &[
+                                binding(&[0x2b, 6, 1, 2, 1, 1, 1, 0], tlv(0x04, b"Linux router")),
+                                binding(&[0x2b, 6, 1, 2, 1, 1, 3, 0], tlv(0x43, &[0, 0x9c, 0x40])),
+                                binding(
+                                    &[0x2b, 6, 1, 4, 1, 0x82, 0x37, 1],
+                                    tlv(0x41, &[0xff, 0xff]),
+                                ),
+                                binding(&[0x2b, 6, 1, 2, 1, 1, 9, 0], tlv(0x81, &[])),
+                            ]
+                            .concat(),
+                        ),
+                    ]
+                    .concat(),
+                ),
+            ]
+            .concat(),
+        );
+        assert!(is_snmp_message(&message));
+        let snmp_packet = parse_snmp_message(&message).unwrap();
+
+        assert_eq!(snmp_packet.version, "v2c (1)");
+        assert_eq!(snmp_packet.community.as_deref(), Some("public"));
+        assert_eq!(snmp_packet.pdu_type.as_deref(), Some("Response (2)"));
+        assert_eq!(snmp_packet.request_id, Some(7751));
+        assert_eq!(snmp_packet.error_status.as_deref(), Some("noError (0)"));
+        assert_eq!(
+            snmp_packet.variable_bindings,
+            vec![
+                SnmpVariableBinding {
+                    oid: "1.3.6.1.2.1.1.1.0".to_owned(),
+                    value_type: "OCTET STRING".to_owned(),
+                    value: "Linux router".to_owned(),
+                },
+                SnmpVariableBinding {
+                    oid: "1.3.6.1.2.1.1.3.0".to_owned(),
+                    value_type: "TimeTicks".to_owned(),
+                    value: "40000".to_owned(),
+                },
+                SnmpVariableBinding {
+                    oid: "1.3.6.1.4.1.311.1".to_owned(),
+                    value_type: "Counter32".to_owned(),
+                    value: "65535".to_owned(),
+                },
+                SnmpVariableBinding {
+                    oid: "1.3.6.1.2.1.1.9.0".to_owned(),
+                    value_type: "noSuchInstance".to_owned(),
+                    value: "".to_owned(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn snmp_v1_trap() {
+        let message = tlv(
+            0x30,
+            &[
+                tlv(0x02, &[0]),
+                tlv(0x04, b"traps"),
+                tlv(
+                    0xa4,
+                    &[
+                        tlv(0x06, &[0x2b, 6, 1, 4, 1, 9]),
+                        tlv(0x40, &[192, 168, 1, 1]),
+                        tlv(0x02, &[2]),
+                        tlv(0x02, &[0]),
+                        tlv(0x43, &[0x01, 0x00]),
+                        tlv(
+                            0x30,
+                            &binding(&[0x2b, 6, 1, 2, 1, 2, 2, 1, 1, 3], tlv(0x02, &[3])),
+                        ),
+                    ]
+                    .concat(),
+                ),
+            ]
+            .concat(),
+        );
+        let snmp_packet = parse_snmp_message(&message).unwrap();
+
+        assert_eq!(snmp_packet.version, "v1 (0)");
+        assert_eq!(snmp_packet.pdu_type.as_deref(), Some("Trap (4)"));
+        assert_eq!(snmp_packet.enterprise.as_deref(), Some("1.3.6.1.4.1.9"));
+        assert_eq!(
+            snmp_packet.agent_address,
+            Some(Ipv4Addr::new(192, 168, 1, 1))
+        );
+        assert_eq!(snmp_packet.generic_trap.as_deref(), Some("linkDown (2)"));
+        assert_eq!(snmp_packet.timestamp, Some(256));
+        assert_eq!(snmp_packet.request_id, None);
+        assert_eq!(snmp_packet.variable_bindings[0].value, "3");
+    }
+
+    #[test]
+    fn snmp_v3_encrypted() {
+        let usm = tlv(
+            0x30,
+            &[
+                tlv(0x04, &[0x80, 0x00, 0x1f, 0x88]),
+                tlv(0x02, &[5]),
+                tlv(0x02, &[0x10, 0x00]),
+                tlv(0x04, b"admin"),
+                tlv(0x04, &[0xaa; 12]),
+                tlv(0x04, &[0xbb; 8]),
+            ]
+            .concat(),
+        );
+        let message = tlv(
+            0x30,
+            &[
+                tlv(0x02, &[3]),
+                tlv(
+                    0x30,
+                    &[
+                        tlv(0x02, &[0x42]),
+                        tlv(0x02, &[0x05, 0xdc]),
+                        tlv(0x04, &[0x07]),
+                        tlv(0x02, &[3]),
+                    ]
+                    .concat(),
+                ),
+                tlv(0x04, &usm),
+                tlv(0x04, &[0xcc; 40]),
+            ]
+            .concat(),
+        );
+        let snmp_packet = parse_snmp_message(&message).unwrap();
+
+        assert_eq!(snmp_packet.version, "v3 (3)");
+        assert_eq!(snmp_packet.message_id, Some(0x42));
+        assert!(snmp_packet.authenticated);
+        assert!(snmp_packet.encrypted);
+        assert_eq!(snmp_packet.user_name.as_deref(), Some("admin"));
+        assert_eq!(snmp_packet.engine_id.as_deref(), Some("80001f88"));
+        assert_eq!(snmp_packet.pdu_type, None);
+        assert!(snmp_packet.variable_bindings.is_empty());
+    }
+
+    #[test]
+    fn malformed_snmp_message() {
+        let message = tlv(0x30, &[tlv(0x02, &[1]), tlv(0x04, b"public")].concat());
+        let mut parsed_packet = ParsedPacket::new(0);
+
+        assert!(is_snmp_message(&message));
+        assert!(!is_snmp_message(&tlv(0x30, &tlv(0x02, &[2]))));
+        handle_snmp_packet(&message[..message.len() - 1], &mut parsed_packet);
+
+        assert!(matches!(
+            parsed_packet.get_application_layer_packet(),
+            Some(SerializablePacket::MalformedPacket(_))
+        ));
+    }
+
+    ///////////////////// Utils
+
+    fn tlv(tag: u8, content: &[u8]) -> Vec<u8> {
+        let mut value = vec![tag];
+        if content.len() < 0x80 {
+            value.push(content.len() as u8);
+        } else {
+            value.push(0x82);
+            value.extend((content.len() as u16).to_be_bytes());
+        }
+        value.extend(content);
+
+        value
+    }
+
+    fn binding(oid: &[u8], value: Vec<u8>) -> Vec<u8> {
+        tlv(0x30, &[tlv(0x06, oid), value].concat())
+    }
+}
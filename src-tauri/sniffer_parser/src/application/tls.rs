@@ -1,22 +1,25 @@
 //! TLS Packet parsing
 
+use std::collections::HashMap;
 use std::net::IpAddr;
 
-use log::debug;
-use log::error;
-use log::warn;
 use tls_parser::nom::error::ErrorKind;
 use tls_parser::parse_tls_plaintext;
 use tls_parser::parse_tls_record_header;
 use tls_parser::{parse_tls_encrypted, TlsMessage, TlsMessageHandshake};
+use tracing::debug;
+use tracing::error;
+use tracing::warn;
 
+use crate::flows::{exceeds_flow_limit, get_buffer_limits};
 use crate::serializable_packet::application::*;
 use crate::serializable_packet::ParsedPacket;
 use crate::serializable_packet::SerializablePacket;
-use crate::ACTIVE_TLS_PARSERS;
+use crate::{FlowDirection, FlowKey, ParserContext};
 
 /// Build a TLS packet from a transport-layer packet, save it in a Parsed Packet
 pub fn handle_tls_packet(
+    state: &mut ParserContext,
     source_ip: IpAddr,
     source_port: u16,
     dest_ip: IpAddr,
@@ -24,200 +27,328 @@ pub fn handle_tls_packet(
     packet: &[u8],
     parsed_packet: &mut ParsedPacket,
 ) {
-    ACTIVE_TLS_PARSERS.with(|parsers| {
-        let mut parsers = parsers.borrow_mut();
-        let current_payload = parsers
-            .entry(((source_ip, source_port), (dest_ip, dest_port)))
-            .and_modify(|payload| payload.append(packet.to_vec().as_mut()))
-            .or_insert(packet.to_vec());
-
-        let mut tls_packet = SerializableTlsPacket::default();
-        let mut custom_messages = vec![];
-
-        while !current_payload.is_empty() {
-            let result = parse_tls_plaintext(current_payload);
-            match result {
-                Ok((rem, record)) => {
-                    for (i, msg) in record.msg.iter().enumerate() {
-                        debug!(
-                            "[{i}]: TLS Record Packet: {}:{} > {}:{}; Version: {}, Record Type: {:?}, Len: {}, Payload: {:?}",
-                            source_ip, source_port, dest_ip, dest_port, record.hdr.version, record.hdr.record_type, record.hdr.len, msg
-                        );
-                    }
+    let flow = FlowKey::of((source_ip, source_port), (dest_ip, dest_port));
+
+    let parsers = &mut state.tls_parsers;
+    let indexes = &mut state.tls_record_indexes;
+    let current_payload = parsers
+        .entry(flow)
+        .and_modify(|payload| payload.append(packet.to_vec().as_mut()))
+        .or_insert(packet.to_vec());
+
+    if exceeds_flow_limit(current_payload.len()) {
+        let message = format!(
+            "TLS records exceed the buffer limit of {} bytes",
+            get_buffer_limits().flow_bytes
+        );
+        debug!(
+            "{}: {}:{} > {}:{}",
+            message, source_ip, source_port, dest_ip, dest_port
+        );
+        parsed_packet
+            .set_application_layer_packet(Some(SerializablePacket::MalformedPacket(message)));
+        parsers.remove(&flow);
+        return;
+    }
 
-                    parse_messages(record.msg, &mut custom_messages);
+    let mut tls_packet = SerializableTlsPacket::default();
+    let mut custom_messages = vec![];
+    let mut records = vec![];
 
-                    if rem.is_empty() {
-                        tls_packet.set_version(record.hdr.version);
-                        tls_packet.set_length(record.hdr.len);
+    while !current_payload.is_empty() {
+        let result = parse_tls_plaintext(current_payload);
+        match result {
+            Ok((rem, record)) => {
+                for (i, msg) in record.msg.iter().enumerate() {
+                    debug!(
+                        "[{i}]: TLS Record Packet: {}:{} > {}:{}; Version: {}, Record Type: {:?}, Len: {}, Payload: {:?}",
+                        source_ip, source_port, dest_ip, dest_port, record.hdr.version, record.hdr.record_type, record.hdr.len, msg
+                    );
+                }
 
-                        current_payload.clear();
-                        parsers.remove(&((source_ip, source_port), (dest_ip, dest_port)));
-                        break;
-                    } else {
-                        let end = current_payload.len() - rem.len();
-                        current_payload.drain(..end);
-                        continue;
-                    }
-                },
-                Err(tls_parser::nom::Err::Incomplete(_)) => break,
-                Err(tls_parser::nom::Err::Error(e)) => {
-                    match e.code {
-                        ErrorKind::Switch => {
-                            warn!(
-                                "TLS Ignored unknown record: {}:{} > {}:{}; Length: {}",
-                                source_ip, source_port, dest_ip, dest_port, current_payload.len()
-                            );
-                            custom_messages.push(CustomTlsMessage::Malformed(
-                                CustomMalformedMessage::new(
-                                    None,
-                                    None,
-                                    TlsMalformedError::UnknownRecord(
-                                        "Unknown record type".to_owned()
-                                    ),
-                                    current_payload
-                                )
-                            ));
+                records.push(SerializableTlsRecord::new(
+                    record.hdr.version,
+                    record.hdr.record_type,
+                    record.hdr.len,
+                    next_record_index(indexes, &flow),
+                ));
+                if let Some(handshake) = record.msg.iter().find_map(handshake_details) {
+                    tls_packet.set_handshake(handshake);
+                }
+                if let Some(certificates) = record.msg.iter().find_map(certificate_chain) {
+                    tls_packet.set_certificates(certificates);
+                }
+                parse_messages(record.msg, &mut custom_messages);
 
-                            current_payload.clear();
-                            parsers.remove(&((source_ip, source_port), (dest_ip, dest_port)));
-                            break;
-                        },
-                        ErrorKind::TooLarge => {
-                            let result = parse_tls_record_header(current_payload);
-                            match result {
-                                Ok((_, record)) => {
-                                    warn!(
-                                        "TLS Length Error: {}:{} > {}:{}; Length: {}",
-                                        source_ip, source_port, dest_ip, dest_port, current_payload.len()
-                                    );
-
-                                    custom_messages.push(CustomTlsMessage::Malformed(
-                                        CustomMalformedMessage::new(
-                                            Some(record.version),
-                                            Some(record.record_type),
-                                            TlsMalformedError::LengthTooLarge(
-                                                "Max Record size exceeded (RFC8446 5.1)".to_owned()
-                                            ),
-                                            current_payload
-                                        )
-                                    ));
-                                },
-                                _ => ()
-                            }
+                if rem.is_empty() {
+                    tls_packet.set_version(record.hdr.version);
+                    tls_packet.set_length(record.hdr.len);
 
-                            current_payload.clear();
-                            parsers.remove(&((source_ip, source_port), (dest_ip, dest_port)));
-                            break;
-                        },
-                        _ => ()
-                    }
-                }
-                Err(tls_parser::nom::Err::Failure(_)) => {
-                    error!("[FAILURE] Malformed TLS");
                     current_payload.clear();
-                    parsers.remove(&((source_ip, source_port), (dest_ip, dest_port)));
+                    parsers.remove(&flow);
                     break;
+                } else {
+                    let end = current_payload.len() - rem.len();
+                    current_payload.drain(..end);
+                    continue;
                 }
-            };
-
-            let result = parse_tls_encrypted(current_payload);
-            match result {
-                Ok((rem, record)) => {
-                    debug!(
-                        "TLS Encrypted Packet: {}:{} > {}:{}; Version: {}, Record Type: {:?}, Len: {}",
-                        source_ip, source_port, dest_ip, dest_port, record.hdr.version, record.hdr.record_type, record.hdr.len
+            }
+            Err(tls_parser::nom::Err::Incomplete(_)) => break,
+            Err(tls_parser::nom::Err::Error(e)) => match e.code {
+                ErrorKind::Switch => {
+                    warn!(
+                        "TLS Ignored unknown record: {}:{} > {}:{}; Length: {}",
+                        source_ip,
+                        source_port,
+                        dest_ip,
+                        dest_port,
+                        current_payload.len()
                     );
+                    custom_messages.push(CustomTlsMessage::Malformed(CustomMalformedMessage::new(
+                        None,
+                        None,
+                        TlsMalformedError::UnknownRecord("Unknown record type".to_owned()),
+                        current_payload,
+                    )));
 
-                    custom_messages.push(CustomTlsMessage::Encrypted(
-                        CustomEncryptedMessage::new(record.msg.blob, record.hdr.version, record.hdr.record_type)
-                    ));
-
-                    if rem.is_empty() {
-                        tls_packet.set_version(record.hdr.version);
-                        tls_packet.set_length(record.hdr.len);
-
-                        current_payload.clear();
-                        parsers.remove(&((source_ip, source_port), (dest_ip, dest_port)));
-                        break;
-                    } else {
-                        let end = current_payload.len() - rem.len();
-                        current_payload.drain(..end);
-                        continue;
-                    }
+                    current_payload.clear();
+                    parsers.remove(&flow);
+                    break;
                 }
-                Err(tls_parser::nom::Err::Incomplete(_)) => break,
-                Err(tls_parser::nom::Err::Error(e)) => {
-                    match e.code {
-                        ErrorKind::Switch => {
+                ErrorKind::TooLarge => {
+                    let result = parse_tls_record_header(current_payload);
+                    match result {
+                        Ok((_, record)) => {
                             warn!(
-                                "TLS Ignored unknown record: {}:{} > {}:{}; Length: {}",
-                                source_ip, source_port, dest_ip, dest_port, current_payload.len()
+                                "TLS Length Error: {}:{} > {}:{}; Length: {}",
+                                source_ip,
+                                source_port,
+                                dest_ip,
+                                dest_port,
+                                current_payload.len()
                             );
 
+                            records.push(SerializableTlsRecord::new(
+                                record.version,
+                                record.record_type,
+                                record.len,
+                                next_record_index(indexes, &flow),
+                            ));
                             custom_messages.push(CustomTlsMessage::Malformed(
                                 CustomMalformedMessage::new(
-                                    None,
-                                    None,
-                                    TlsMalformedError::UnknownRecord(
-                                        "Unknown record type".to_owned()
+                                    Some(record.version),
+                                    Some(record.record_type),
+                                    TlsMalformedError::LengthTooLarge(
+                                        "Max Record size exceeded (RFC8446 5.1)".to_owned(),
                                     ),
-                                    current_payload
-                                )
+                                    current_payload,
+                                ),
                             ));
-                        },
-                        ErrorKind::TooLarge => {
-                            let result = parse_tls_record_header(current_payload);
-                            match result {
-                                Ok((_, record)) => {
-                                    warn!(
-                                        "TLS Length Error: {}:{} > {}:{}; Length: {}",
-                                        source_ip, source_port, dest_ip, dest_port, current_payload.len()
-                                    );
-
-                                    custom_messages.push(CustomTlsMessage::Malformed(
-                                        CustomMalformedMessage::new(
-                                            Some(record.version),
-                                            Some(record.record_type),
-                                            TlsMalformedError::LengthTooLarge(
-                                                "Max Record size exceeded (RFC8446 5.1)".to_owned()
-                                            ),
-                                            current_payload
-                                        )
-                                    ));
-                                },
-                                _ => ()
-                            }
-                        },
-                        e => {
-                            warn!("ENC [{:?}] {}:{} > {}:{}; Malformed TLS", e, source_ip, source_port, dest_ip, dest_port);
                         }
+                        _ => (),
                     }
 
                     current_payload.clear();
-                    parsers.remove(&((source_ip, source_port), (dest_ip, dest_port)));
+                    parsers.remove(&flow);
                     break;
-                },
-                Err(_) => {
+                }
+                _ => (),
+            },
+            Err(tls_parser::nom::Err::Failure(_)) => {
+                error!("[FAILURE] Malformed TLS");
+                push_record_from_header(current_payload, indexes, &flow, &mut records);
+                current_payload.clear();
+                parsers.remove(&flow);
+                break;
+            }
+        };
+
+        let result = parse_tls_encrypted(current_payload);
+        match result {
+            Ok((rem, record)) => {
+                debug!(
+                    "TLS Encrypted Packet: {}:{} > {}:{}; Version: {}, Record Type: {:?}, Len: {}",
+                    source_ip,
+                    source_port,
+                    dest_ip,
+                    dest_port,
+                    record.hdr.version,
+                    record.hdr.record_type,
+                    record.hdr.len
+                );
+
+                records.push(SerializableTlsRecord::new(
+                    record.hdr.version,
+                    record.hdr.record_type,
+                    record.hdr.len,
+                    next_record_index(indexes, &flow),
+                ));
+                custom_messages.push(CustomTlsMessage::Encrypted(CustomEncryptedMessage::new(
+                    record.msg.blob,
+                    record.hdr.version,
+                    record.hdr.record_type,
+                )));
+
+                if rem.is_empty() {
+                    tls_packet.set_version(record.hdr.version);
+                    tls_packet.set_length(record.hdr.len);
+
                     current_payload.clear();
-                    parsers.remove(&((source_ip, source_port), (dest_ip, dest_port)));
+                    parsers.remove(&flow);
                     break;
-                },
+                } else {
+                    let end = current_payload.len() - rem.len();
+                    current_payload.drain(..end);
+                    continue;
+                }
             }
-        }
+            Err(tls_parser::nom::Err::Incomplete(_)) => break,
+            Err(tls_parser::nom::Err::Error(e)) => {
+                match e.code {
+                    ErrorKind::Switch => {
+                        warn!(
+                            "TLS Ignored unknown record: {}:{} > {}:{}; Length: {}",
+                            source_ip,
+                            source_port,
+                            dest_ip,
+                            dest_port,
+                            current_payload.len()
+                        );
 
-        if !custom_messages.is_empty() {
-            parsed_packet.set_application_layer_packet(Some(
-                SerializablePacket::TlsPacket(
-                    SerializableTlsPacket {
-                        version: tls_packet.version,
-                        messages: custom_messages,
-                        length: tls_packet.length,
+                        custom_messages.push(CustomTlsMessage::Malformed(
+                            CustomMalformedMessage::new(
+                                None,
+                                None,
+                                TlsMalformedError::UnknownRecord("Unknown record type".to_owned()),
+                                current_payload,
+                            ),
+                        ));
+                    }
+                    ErrorKind::TooLarge => {
+                        let result = parse_tls_record_header(current_payload);
+                        match result {
+                            Ok((_, record)) => {
+                                warn!(
+                                    "TLS Length Error: {}:{} > {}:{}; Length: {}",
+                                    source_ip,
+                                    source_port,
+                                    dest_ip,
+                                    dest_port,
+                                    current_payload.len()
+                                );
+
+                                records.push(SerializableTlsRecord::new(
+                                    record.version,
+                                    record.record_type,
+                                    record.len,
+                                    next_record_index(indexes, &flow),
+                                ));
+                                custom_messages.push(CustomTlsMessage::Malformed(
+                                    CustomMalformedMessage::new(
+                                        Some(record.version),
+                                        Some(record.record_type),
+                                        TlsMalformedError::LengthTooLarge(
+                                            "Max Record size exceeded (RFC8446 5.1)".to_owned(),
+                                        ),
+                                        current_payload,
+                                    ),
+                                ));
+                            }
+                            _ => (),
+                        }
                     }
-                ),
-            ));
+                    e => {
+                        warn!(
+                            "ENC [{:?}] {}:{} > {}:{}; Malformed TLS",
+                            e, source_ip, source_port, dest_ip, dest_port
+                        );
+                        push_record_from_header(current_payload, indexes, &flow, &mut records);
+                    }
+                }
+
+                current_payload.clear();
+                parsers.remove(&flow);
+                break;
+            }
+            Err(_) => {
+                warn!(
+                    "ENC {}:{} > {}:{}; Malformed TLS",
+                    source_ip, source_port, dest_ip, dest_port
+                );
+                push_record_from_header(current_payload, indexes, &flow, &mut records);
+                current_payload.clear();
+                parsers.remove(&flow);
+                break;
+            }
         }
-    });
+    }
+
+    if !custom_messages.is_empty() || !records.is_empty() {
+        parsed_packet.set_application_layer_packet(Some(SerializablePacket::TlsPacket(
+            SerializableTlsPacket {
+                version: tls_packet.version,
+                messages: custom_messages,
+                records,
+                length: tls_packet.length,
+                handshake: tls_packet.handshake,
+                certificates: tls_packet.certificates,
+            },
+        )));
+    }
+}
+
+/// Get the sequence index of the next record sent in the direction of the flow
+fn next_record_index(
+    indexes: &mut HashMap<(FlowKey, FlowDirection), usize>,
+    flow: &(FlowKey, FlowDirection),
+) -> usize {
+    let index = indexes.entry(*flow).or_insert(0);
+    *index += 1;
+
+    *index - 1
+}
+
+/// Save the record metadata whenever at least its header can be parsed
+fn push_record_from_header(
+    payload: &[u8],
+    indexes: &mut HashMap<(FlowKey, FlowDirection), usize>,
+    flow: &(FlowKey, FlowDirection),
+    records: &mut Vec<SerializableTlsRecord>,
+) {
+    if let Ok((_, header)) = parse_tls_record_header(payload) {
+        records.push(SerializableTlsRecord::new(
+            header.version,
+            header.record_type,
+            header.len,
+            next_record_index(indexes, flow),
+        ));
+    }
+}
+
+/// Get the details of a ClientHello or ServerHello message
+pub(super) fn handshake_details(message: &TlsMessage) -> Option<SerializableTlsHandshakePacket> {
+    match message {
+        TlsMessage::Handshake(TlsMessageHandshake::ClientHello(hello)) => {
+            Some(SerializableTlsHandshakePacket::from_client_hello(hello))
+        }
+        TlsMessage::Handshake(TlsMessageHandshake::ServerHello(hello)) => {
+            Some(SerializableTlsHandshakePacket::from_server_hello(hello))
+        }
+        _ => None,
+    }
+}
+
+fn certificate_chain(message: &TlsMessage) -> Option<Vec<SerializableTlsCertificate>> {
+    match message {
+        TlsMessage::Handshake(TlsMessageHandshake::Certificate(certificate)) => Some(
+            certificate
+                .cert_chain
+                .iter()
+                .filter_map(|cert| SerializableTlsCertificate::from_der(cert.data))
+                .collect(),
+        ),
+        _ => None,
+    }
 }
 
 fn parse_messages(messages: Vec<TlsMessage>, custom_messages: &mut Vec<CustomTlsMessage>) {
@@ -366,6 +497,7 @@ mod tests {
         },
         ParsedPacket, SerializablePacket,
     };
+    use crate::ParserContext;
 
     use super::handle_tls_packet;
 
@@ -495,16 +627,56 @@ mod tests {
         0x29, 0x05, 0x28, 0x52,
     ];
 
+    const CERTIFICATE: &[u8] = &[
+        0x16, 0x03, 0x03, 0x01, 0xd4, 0x0b, 0x00, 0x01, 0xd0, 0x00, 0x01, 0xcd, 0x00, 0x01, 0xca,
+        0x30, 0x82, 0x01, 0xc6, 0x30, 0x82, 0x01, 0x6c, 0xa0, 0x03, 0x02, 0x01, 0x02, 0x02, 0x02,
+        0x12, 0x34, 0x30, 0x0a, 0x06, 0x08, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x02, 0x30,
+        0x29, 0x31, 0x14, 0x30, 0x12, 0x06, 0x03, 0x55, 0x04, 0x03, 0x0c, 0x0b, 0x65, 0x78, 0x61,
+        0x6d, 0x70, 0x6c, 0x65, 0x2e, 0x63, 0x6f, 0x6d, 0x31, 0x11, 0x30, 0x0f, 0x06, 0x03, 0x55,
+        0x04, 0x0a, 0x0c, 0x08, 0x57, 0x69, 0x72, 0x65, 0x66, 0x69, 0x73, 0x68, 0x30, 0x1e, 0x17,
+        0x0d, 0x32, 0x36, 0x31, 0x30, 0x31, 0x36, 0x31, 0x30, 0x34, 0x36, 0x33, 0x38, 0x5a, 0x17,
+        0x0d, 0x32, 0x37, 0x31, 0x30, 0x31, 0x36, 0x31, 0x30, 0x34, 0x36, 0x33, 0x38, 0x5a, 0x30,
+        0x29, 0x31, 0x14, 0x30, 0x12, 0x06, 0x03, 0x55, 0x04, 0x03, 0x0c, 0x0b, 0x65, 0x78, 0x61,
+        0x6d, 0x70, 0x6c, 0x65, 0x2e, 0x63, 0x6f, 0x6d, 0x31, 0x11, 0x30, 0x0f, 0x06, 0x03, 0x55,
+        0x04, 0x0a, 0x0c, 0x08, 0x57, 0x69, 0x72, 0x65, 0x66, 0x69, 0x73, 0x68, 0x30, 0x59, 0x30,
+        0x13, 0x06, 0x07, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01, 0x06, 0x08, 0x2a, 0x86, 0x48,
+        0xce, 0x3d, 0x03, 0x01, 0x07, 0x03, 0x42, 0x00, 0x04, 0x46, 0x68, 0xba, 0x10, 0x36, 0xe6,
+        0xed, 0xcd, 0xc5, 0xc9, 0x08, 0x62, 0xf3, 0x8a, 0x92, 0xe6, 0x0f, 0x64, 0xeb, 0xbd, 0x46,
+        0x58, 0xa3, 0x8e, 0xb3, 0x1d, 0xd9, 0x48, 0x86, 0xa6, 0x5a, 0x71, 0x71, 0xf2, 0xad, 0xc5,
+        0x2c, 0xe4, 0xa4, 0x11, 0xbd, 0xb0, 0x72, 0xfd, 0xbc, 0x4e, 0x3e, 0x6c, 0x0b, 0x78, 0x01,
+        0x6f, 0xa3, 0x8b, 0x2c, 0x70, 0xd2, 0xd0, 0xf5, 0x41, 0x98, 0x84, 0x09, 0xc9, 0xa3, 0x81,
+        0x83, 0x30, 0x81, 0x80, 0x30, 0x1d, 0x06, 0x03, 0x55, 0x1d, 0x0e, 0x04, 0x16, 0x04, 0x14,
+        0x87, 0x6e, 0xd0, 0x43, 0x47, 0xa0, 0x48, 0x57, 0xac, 0x85, 0xf0, 0xb1, 0x34, 0x8b, 0x31,
+        0xc7, 0xdf, 0x3b, 0x18, 0xeb, 0x30, 0x1f, 0x06, 0x03, 0x55, 0x1d, 0x23, 0x04, 0x18, 0x30,
+        0x16, 0x80, 0x14, 0x87, 0x6e, 0xd0, 0x43, 0x47, 0xa0, 0x48, 0x57, 0xac, 0x85, 0xf0, 0xb1,
+        0x34, 0x8b, 0x31, 0xc7, 0xdf, 0x3b, 0x18, 0xeb, 0x30, 0x0f, 0x06, 0x03, 0x55, 0x1d, 0x13,
+        0x01, 0x01, 0xff, 0x04, 0x05, 0x30, 0x03, 0x01, 0x01, 0xff, 0x30, 0x2d, 0x06, 0x03, 0x55,
+        0x1d, 0x11, 0x04, 0x26, 0x30, 0x24, 0x82, 0x0b, 0x65, 0x78, 0x61, 0x6d, 0x70, 0x6c, 0x65,
+        0x2e, 0x63, 0x6f, 0x6d, 0x82, 0x0f, 0x77, 0x77, 0x77, 0x2e, 0x65, 0x78, 0x61, 0x6d, 0x70,
+        0x6c, 0x65, 0x2e, 0x63, 0x6f, 0x6d, 0x87, 0x04, 0x0a, 0x00, 0x00, 0x01, 0x30, 0x0a, 0x06,
+        0x08, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x02, 0x03, 0x48, 0x00, 0x30, 0x45, 0x02,
+        0x20, 0x34, 0xae, 0xd9, 0xdb, 0x4e, 0x8b, 0x09, 0x14, 0xe6, 0x7f, 0xb5, 0x25, 0xfc, 0x5d,
+        0x67, 0x43, 0xe2, 0x25, 0xae, 0x49, 0xbb, 0xd9, 0x1e, 0xe0, 0xde, 0x72, 0x8a, 0x1e, 0xdf,
+        0xbb, 0x68, 0xb2, 0x02, 0x21, 0x00, 0xa0, 0x40, 0xfb, 0xa5, 0x86, 0x5d, 0x9a, 0xf5, 0x0b,
+        0xb6, 0x9a, 0x61, 0x0e, 0xb4, 0xcc, 0x69, 0x4c, 0x3a, 0xf6, 0x5b, 0xb2, 0x92, 0x68, 0x98,
+        0xcb, 0x0d, 0x17, 0xd2, 0xcc, 0x1a, 0xbe, 0x51,
+    ];
+
     const ALERT: &[u8] = &[0x15, 0x03, 0x01, 0x00, 0x02, 0x02, 0x46];
 
     const UNKNOWN_RECORD: &[u8] = &[0x63, 0x0e, 0x00, 0x00, 0x03, 0x0f, 0xf8, 0xec];
 
     const TOO_LARGE_RECORD: &[u8] = &[0x17, 0x03, 0x03, 0x40, 0x11, 0x0f, 0xf8, 0xec];
 
+    const APPLICATION_DATA: &[u8] = &[0x17, 0x03, 0x03, 0x00, 0x03, 0x0f, 0xf8, 0xec];
+
     #[test]
     fn valid_server_hello_tls_packet() {
+        let mut state = ParserContext::new();
+
         let mut parsed_packet = ParsedPacket::new(0);
         handle_tls_packet(
+            &mut state,
             IpAddr::V4(Ipv4Addr::new(10, 10, 10, 10)),
             4444,
             IpAddr::V4(Ipv4Addr::new(11, 11, 11, 11)),
@@ -560,8 +732,11 @@ mod tests {
 
     #[test]
     fn valid_server_done_tls_packet() {
+        let mut state = ParserContext::new();
+
         let mut parsed_packet = ParsedPacket::new(0);
         handle_tls_packet(
+            &mut state,
             IpAddr::V4(Ipv4Addr::new(10, 10, 10, 10)),
             4444,
             IpAddr::V4(Ipv4Addr::new(11, 11, 11, 11)),
@@ -598,8 +773,11 @@ mod tests {
 
     #[test]
     fn valid_server_key_exchange_tls_packet() {
+        let mut state = ParserContext::new();
+
         let mut parsed_packet = ParsedPacket::new(0);
         handle_tls_packet(
+            &mut state,
             IpAddr::V4(Ipv4Addr::new(10, 10, 10, 10)),
             4444,
             IpAddr::V4(Ipv4Addr::new(11, 11, 11, 11)),
@@ -666,8 +844,11 @@ mod tests {
 
     #[test]
     fn valid_change_cipher_spec_tls_packet() {
+        let mut state = ParserContext::new();
+
         let mut parsed_packet = ParsedPacket::new(0);
         handle_tls_packet(
+            &mut state,
             IpAddr::V4(Ipv4Addr::new(10, 10, 10, 10)),
             4444,
             IpAddr::V4(Ipv4Addr::new(11, 11, 11, 11)),
@@ -700,8 +881,11 @@ mod tests {
 
     #[test]
     fn valid_client_hello_tls_packet() {
+        let mut state = ParserContext::new();
+
         let mut parsed_packet = ParsedPacket::new(0);
         handle_tls_packet(
+            &mut state,
             IpAddr::V4(Ipv4Addr::new(10, 10, 10, 10)),
             4444,
             IpAddr::V4(Ipv4Addr::new(11, 11, 11, 11)),
@@ -768,8 +952,11 @@ mod tests {
 
     #[test]
     fn valid_client_key_exchange_tls_packet() {
+        let mut state = ParserContext::new();
+
         let mut parsed_packet = ParsedPacket::new(0);
         handle_tls_packet(
+            &mut state,
             IpAddr::V4(Ipv4Addr::new(10, 10, 10, 10)),
             4444,
             IpAddr::V4(Ipv4Addr::new(11, 11, 11, 11)),
@@ -814,8 +1001,11 @@ mod tests {
 
     #[test]
     fn valid_certificate_status_tls_packet() {
+        let mut state = ParserContext::new();
+
         let mut parsed_packet = ParsedPacket::new(0);
         handle_tls_packet(
+            &mut state,
             IpAddr::V4(Ipv4Addr::new(10, 10, 10, 10)),
             4444,
             IpAddr::V4(Ipv4Addr::new(11, 11, 11, 11)),
@@ -853,8 +1043,11 @@ mod tests {
 
     #[test]
     fn valid_alert_tls_packet() {
+        let mut state = ParserContext::new();
+
         let mut parsed_packet = ParsedPacket::new(0);
         handle_tls_packet(
+            &mut state,
             IpAddr::V4(Ipv4Addr::new(10, 10, 10, 10)),
             4444,
             IpAddr::V4(Ipv4Addr::new(11, 11, 11, 11)),
@@ -878,6 +1071,8 @@ mod tests {
                         TlsMessage::Alert(message) => {
                             assert_eq!(new_message.severity, message.severity.to_string());
                             assert_eq!(new_message.description, message.code.to_string());
+                            assert_eq!(new_message.level, message.severity.0);
+                            assert_eq!(new_message.code, message.code.0);
                         }
                         _ => unreachable!(),
                     },
@@ -890,8 +1085,11 @@ mod tests {
 
     #[test]
     fn unknown_tls_record() {
+        let mut state = ParserContext::new();
+
         let mut parsed_packet = ParsedPacket::new(0);
         handle_tls_packet(
+            &mut state,
             IpAddr::V4(Ipv4Addr::new(10, 10, 10, 10)),
             4444,
             IpAddr::V4(Ipv4Addr::new(11, 11, 11, 11)),
@@ -928,8 +1126,11 @@ mod tests {
 
     #[test]
     fn too_large_tls_record() {
+        let mut state = ParserContext::new();
+
         let mut parsed_packet = ParsedPacket::new(0);
         handle_tls_packet(
+            &mut state,
             IpAddr::V4(Ipv4Addr::new(10, 10, 10, 10)),
             4444,
             IpAddr::V4(Ipv4Addr::new(11, 11, 11, 11)),
@@ -963,4 +1164,234 @@ mod tests {
             _ => unreachable!(),
         }
     }
+
+    #[test]
+    fn application_data_tls_record() {
+        let mut state = ParserContext::new();
+
+        let mut parsed_packet = ParsedPacket::new(0);
+        handle_tls_packet(
+            &mut state,
+            IpAddr::V4(Ipv4Addr::new(10, 10, 10, 10)),
+            4444,
+            IpAddr::V4(Ipv4Addr::new(11, 11, 11, 11)),
+            443,
+            APPLICATION_DATA,
+            &mut parsed_packet,
+        );
+
+        match parsed_packet.get_application_layer_packet().unwrap() {
+            SerializablePacket::TlsPacket(new_tls_packet) => {
+                assert_eq!(new_tls_packet.records.len(), 1);
+                assert_eq!(new_tls_packet.records[0].version, "Tls12");
+                assert_eq!(new_tls_packet.records[0].record_type, "ApplicationData");
+                assert_eq!(new_tls_packet.records[0].length, 3);
+                assert_eq!(new_tls_packet.records[0].index, 0);
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn tls_records_sequence_index() {
+        let mut state = ParserContext::new();
+
+        let mut parsed_packet = ParsedPacket::new(0);
+        handle_tls_packet(
+            &mut state,
+            IpAddr::V4(Ipv4Addr::new(10, 10, 10, 10)),
+            4444,
+            IpAddr::V4(Ipv4Addr::new(11, 11, 11, 11)),
+            443,
+            &[CHANGE_CIPHER_SPEC, ALERT].concat(),
+            &mut parsed_packet,
+        );
+
+        match parsed_packet.get_application_layer_packet().unwrap() {
+            SerializablePacket::TlsPacket(new_tls_packet) => {
+                assert_eq!(new_tls_packet.records.len(), 2);
+                assert_eq!(new_tls_packet.records[0].record_type, "ChangeCipherSpec");
+                assert_eq!(new_tls_packet.records[0].index, 0);
+                assert_eq!(new_tls_packet.records[1].record_type, "Alert");
+                assert_eq!(new_tls_packet.records[1].index, 1);
+            }
+            _ => unreachable!(),
+        }
+
+        let mut parsed_packet = ParsedPacket::new(1);
+        handle_tls_packet(
+            &mut state,
+            IpAddr::V4(Ipv4Addr::new(10, 10, 10, 10)),
+            4444,
+            IpAddr::V4(Ipv4Addr::new(11, 11, 11, 11)),
+            443,
+            APPLICATION_DATA,
+            &mut parsed_packet,
+        );
+
+        match parsed_packet.get_application_layer_packet().unwrap() {
+            SerializablePacket::TlsPacket(new_tls_packet) => {
+                assert_eq!(new_tls_packet.records.len(), 1);
+                assert_eq!(new_tls_packet.records[0].index, 2);
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn too_large_tls_record_metadata() {
+        let mut state = ParserContext::new();
+
+        let mut parsed_packet = ParsedPacket::new(0);
+        handle_tls_packet(
+            &mut state,
+            IpAddr::V4(Ipv4Addr::new(10, 10, 10, 10)),
+            4444,
+            IpAddr::V4(Ipv4Addr::new(11, 11, 11, 11)),
+            443,
+            TOO_LARGE_RECORD,
+            &mut parsed_packet,
+        );
+
+        match parsed_packet.get_application_layer_packet().unwrap() {
+            SerializablePacket::TlsPacket(new_tls_packet) => {
+                assert_eq!(new_tls_packet.records.len(), 1);
+                assert_eq!(new_tls_packet.records[0].version, "Tls12");
+                assert_eq!(new_tls_packet.records[0].length, 0x4011);
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn client_hello_handshake_details() {
+        let mut state = ParserContext::new();
+
+        let mut parsed_packet = ParsedPacket::new(0);
+        handle_tls_packet(
+            &mut state,
+            IpAddr::V4(Ipv4Addr::new(10, 10, 10, 10)),
+            4444,
+            IpAddr::V4(Ipv4Addr::new(11, 11, 11, 11)),
+            443,
+            CLIENT_HELLO,
+            &mut parsed_packet,
+        );
+
+        match parsed_packet.get_application_layer_packet().unwrap() {
+            SerializablePacket::TlsPacket(new_tls_packet) => {
+                let handshake = new_tls_packet.handshake.as_ref().unwrap();
+                assert_eq!(handshake.message_type, "ClientHello");
+                assert_eq!(handshake.server_name.as_deref(), Some("www.google.com"));
+                assert_eq!(handshake.cipher_suites.len(), 17);
+                assert_eq!(handshake.cipher_suites[0], "TLS_AES_128_GCM_SHA256");
+                assert_eq!(handshake.selected_cipher_suite, None);
+                assert_eq!(handshake.alpn_protocols, vec!["h2", "http/1.1"]);
+                assert_eq!(handshake.supported_versions, vec!["Tls13", "Tls12"]);
+                assert_eq!(handshake.extensions.len(), 15);
+                assert_eq!(handshake.extensions[0], "server_name");
+                assert_eq!(handshake.extensions[7], "delegated_credentials");
+
+                let ja3 = handshake.ja3.as_ref().unwrap();
+                assert_eq!(
+                    ja3.text,
+                    "771,4865-4867-4866-49195-49199-52393-52392-49196-49200-49162-49161-49171-\
+                     49172-156-157-47-53,0-23-65281-10-11-16-5-34-51-42-43-13-45-28-41,\
+                     29-23-24-25-256-257,0"
+                );
+                assert_eq!(ja3.hash, "02aa4679df284f240695da144b70c288");
+                assert!(handshake.ja3s.is_none());
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn server_hello_handshake_details() {
+        let mut state = ParserContext::new();
+
+        let mut parsed_packet = ParsedPacket::new(0);
+        handle_tls_packet(
+            &mut state,
+            IpAddr::V4(Ipv4Addr::new(11, 11, 11, 11)),
+            443,
+            IpAddr::V4(Ipv4Addr::new(10, 10, 10, 10)),
+            4444,
+            SERVER_HELLO,
+            &mut parsed_packet,
+        );
+
+        match parsed_packet.get_application_layer_packet().unwrap() {
+            SerializablePacket::TlsPacket(new_tls_packet) => {
+                let handshake = new_tls_packet.handshake.as_ref().unwrap();
+                assert_eq!(handshake.message_type, "ServerHello");
+                assert_eq!(handshake.server_name, None);
+                assert!(handshake.cipher_suites.is_empty());
+                assert_eq!(
+                    handshake.selected_cipher_suite.as_deref(),
+                    Some("TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256")
+                );
+                assert_eq!(handshake.alpn_protocols, vec!["h2"]);
+                assert!(handshake.supported_versions.is_empty());
+                assert_eq!(
+                    handshake.extensions,
+                    vec![
+                        "renegotiation_info",
+                        "server_name",
+                        "ec_point_formats",
+                        "session_ticket",
+                        "status_request",
+                        "extended_master_secret",
+                        "application_layer_protocol_negotiation",
+                    ]
+                );
+
+                let ja3s = handshake.ja3s.as_ref().unwrap();
+                assert_eq!(ja3s.text, "771,49199,65281-0-11-35-5-23-16");
+                assert_eq!(ja3s.hash, "860fcf58fd757e26aa8911e5eaff6b53");
+                assert!(handshake.ja3.is_none());
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn certificate_chain_details() {
+        let mut state = ParserContext::new();
+
+        let mut parsed_packet = ParsedPacket::new(0);
+        handle_tls_packet(
+            &mut state,
+            IpAddr::V4(Ipv4Addr::new(11, 11, 11, 11)),
+            443,
+            IpAddr::V4(Ipv4Addr::new(10, 10, 10, 10)),
+            4444,
+            CERTIFICATE,
+            &mut parsed_packet,
+        );
+
+        match parsed_packet.get_application_layer_packet().unwrap() {
+            SerializablePacket::TlsPacket(new_tls_packet) => {
+                assert_eq!(new_tls_packet.certificates.len(), 1);
+                let certificate = &new_tls_packet.certificates[0];
+                assert_eq!(certificate.subject, "CN=example.com, O=Wirefish");
+                assert_eq!(certificate.issuer, "CN=example.com, O=Wirefish");
+                assert_eq!(
+                    certificate.subject_alt_names,
+                    vec!["example.com", "www.example.com", "10.0.0.1"]
+                );
+                assert_eq!(certificate.serial, "12:34");
+                assert_eq!(certificate.not_before, "Fri, 16 Oct 2026 10:46:38 +0000");
+                assert_eq!(certificate.not_after, "Sat, 16 Oct 2027 10:46:38 +0000");
+                assert_eq!(certificate.key_type, "EC");
+                assert_eq!(certificate.key_size, 256);
+                assert_eq!(
+                    certificate.fingerprint,
+                    "4C:EB:AA:E3:59:FE:5E:27:D0:C4:6F:E6:67:F9:EA:24:0C:F2:86:29"
+                );
+                assert!(new_tls_packet.handshake.is_none());
+            }
+            _ => unreachable!(),
+        }
+    }
 }
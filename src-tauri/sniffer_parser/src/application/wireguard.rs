@@ -0,0 +1,175 @@
+//! WireGuard Packet parsing
+//!
+//! WireGuard tunnels IP packets over UDP, on any port (51820 by convention). Its messages start
+//! with a type and three zero reserved bytes, and each type but the transport data one has a fixed
+//! length: the messages are recognized by these, whatever their port. Only the indexes chosen by
+//! the peers to identify their session, and the counter of the transport data messages, are in
+//! the clear; the keys, the timestamps and the tunneled packets are encrypted.
+
+use tracing::debug;
+
+use crate::serializable_packet::application::SerializableWireGuardPacket;
+use crate::serializable_packet::{ParsedPacket, SerializablePacket};
+
+/// Message Types
+#[allow(non_snake_case)]
+mod MessageTypes {
+    pub const HANDSHAKE_INITIATION: u8 = 1;
+    pub const HANDSHAKE_RESPONSE: u8 = 2;
+    pub const COOKIE_REPLY: u8 = 3;
+    pub const TRANSPORT_DATA: u8 = 4;
+}
+
+/// Lengths of the handshake messages
+const HANDSHAKE_INITIATION_LENGTH: usize = 148;
+const HANDSHAKE_RESPONSE_LENGTH: usize = 92;
+const COOKIE_REPLY_LENGTH: usize = 64;
+
+/// Length of the header of a transport data message: type, reserved bytes, receiver and counter
+const TRANSPORT_HEADER_LENGTH: usize = 16;
+
+/// Length of the authentication tag of the encrypted packets
+const TAG_LENGTH: usize = 16;
+
+/// Build a WireGuard packet from a transport-layer packet, save it in a Parsed Packet
+pub fn handle_wireguard_packet(packet: &[u8], parsed_packet: &mut ParsedPacket) {
+    match parse_wireguard_message(packet) {
+        Some(wireguard_packet) => {
+            debug!(
+                "WireGuard Packet: {}; receiver: {:?}",
+                wireguard_packet.message_type, wireguard_packet.receiver_index
+            );
+
+            parsed_packet.set_application_layer_packet(Some(SerializablePacket::WireGuardPacket(
+                wireguard_packet,
+            )));
+        }
+        None => {
+            debug!("Malformed WireGuard Packet");
+            parsed_packet.set_application_layer_packet(Some(SerializablePacket::MalformedPacket(
+                "Malformed WireGuard Packet".to_string(),
+            )));
+        }
+    }
+}
+
+/// Check if a payload is a WireGuard message, with the length of its type
+pub fn is_wireguard_message(payload: &[u8]) -> bool {
+    match payload {
+        [message_type, 0, 0, 0, ..] => match *message_type {
+            MessageTypes::HANDSHAKE_INITIATION => payload.len() == HANDSHAKE_INITIATION_LENGTH,
+            MessageTypes::HANDSHAKE_RESPONSE => payload.len() == HANDSHAKE_RESPONSE_LENGTH,
+            MessageTypes::COOKIE_REPLY => payload.len() == COOKIE_REPLY_LENGTH,
+            // The tunneled packets are padded to a multiple of 16 bytes
+            MessageTypes::TRANSPORT_DATA => {
+                payload.len() >= TRANSPORT_HEADER_LENGTH + TAG_LENGTH && payload.len() & 0x0f == 0
+            }
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+/// Parse a WireGuard message
+pub fn parse_wireguard_message(packet: &[u8]) -> Option<SerializableWireGuardPacket> {
+    if !is_wireguard_message(packet) {
+        return None;
+    }
+
+    let index = |offset: usize| u32::from_le_bytes(packet[offset..offset + 4].try_into().unwrap());
+    let (sender_index, receiver_index, counter) = match packet[0] {
+        MessageTypes::HANDSHAKE_INITIATION => (Some(index(4)), None, None),
+        MessageTypes::HANDSHAKE_RESPONSE => (Some(index(4)), Some(index(8)), None),
+        MessageTypes::COOKIE_REPLY => (None, Some(index(4)), None),
+        _ => (
+            None,
+            Some(index(4)),
+            Some(u64::from_le_bytes(packet[8..16].try_into().unwrap())),
+        ),
+    };
+
+    Some(SerializableWireGuardPacket {
+        message_type: message_type_to_string(packet[0]),
+        sender_index,
+        receiver_index,
+        counter,
+        length: match packet[0] {
+            MessageTypes::TRANSPORT_DATA => packet.len() - TRANSPORT_HEADER_LENGTH - TAG_LENGTH,
+            _ => 0,
+        },
+    })
+}
+
+/// Get the name of a message type
+fn message_type_to_string(message_type: u8) -> String {
+    let name = match message_type {
+        MessageTypes::HANDSHAKE_INITIATION => "Handshake Initiation",
+        MessageTypes::HANDSHAKE_RESPONSE => "Handshake Response",
+        MessageTypes::COOKIE_REPLY => "Cookie Reply",
+        _ => "Transport Data",
+    };
+
+    name.to_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_wireguard_message, parse_wireguard_message};
+
+    #[test]
+    fn handshake_messages() {
+        let initiation = message(1, &[0x01, 0x02, 0x03, 0x04], 148);
+        let initiation = parse_wireguard_message(&initiation).unwrap();
+        assert_eq!(initiation.message_type, "Handshake Initiation");
+        assert_eq!(initiation.sender_index, Some(0x04030201));
+        assert_eq!(initiation.receiver_index, None);
+
+        let mut response = message(2, &[0xaa, 0, 0, 0], 92);
+        response[8..12].copy_from_slice(&[0x01, 0x02, 0x03, 0x04]);
+        let response = parse_wireguard_message(&response).unwrap();
+        assert_eq!(response.message_type, "Handshake Response");
+        assert_eq!(response.sender_index, Some(0xaa));
+        assert_eq!(response.receiver_index, Some(0x04030201));
+        assert_eq!(response.counter, None);
+
+        let cookie = parse_wireguard_message(&message(3, &[0x01, 0, 0, 0], 64)).unwrap();
+        assert_eq!(cookie.message_type, "Cookie Reply");
+        assert_eq!(cookie.receiver_index, Some(1));
+    }
+
+    #[test]
+    fn transport_data_messages() {
+        let mut data = message(4, &[0xaa, 0, 0, 0], 32 + 96);
+        data[8..16].copy_from_slice(&7u64.to_le_bytes());
+        let data = parse_wireguard_message(&data).unwrap();
+        assert_eq!(data.message_type, "Transport Data");
+        assert_eq!(data.receiver_index, Some(0xaa));
+        assert_eq!(data.counter, Some(7));
+        assert_eq!(data.length, 96);
+
+        // Keepalive
+        let keepalive = parse_wireguard_message(&message(4, &[0xaa, 0, 0, 0], 32)).unwrap();
+        assert_eq!(keepalive.length, 0);
+    }
+
+    #[test]
+    fn not_wireguard_messages() {
+        assert!(!is_wireguard_message(&message(1, &[1, 0, 0, 0], 147)));
+        assert!(!is_wireguard_message(&message(4, &[1, 0, 0, 0], 40)));
+        assert!(!is_wireguard_message(&message(5, &[1, 0, 0, 0], 64)));
+        let mut reserved = message(3, &[1, 0, 0, 0], 64);
+        reserved[2] = 1;
+        assert!(!is_wireguard_message(&reserved));
+        assert!(parse_wireguard_message(&[4, 0, 0]).is_none());
+    }
+
+    ///////////////////// Utils
+
+    /// Build a message of a type, with the given first index and length
+    fn message(message_type: u8, index: &[u8; 4], length: usize) -> Vec<u8> {
+        let mut message = vec![message_type, 0, 0, 0];
+        message.extend(index);
+        message.resize(length, 0xee);
+        message
+    }
+}
@@ -0,0 +1,511 @@
+//! Zookeeper client protocol Packet parsing
+//!
+//! Zookeeper messages (TCP port 2181) are jute records preceded by a 4-bytes length. A session
+//! starts with a connect request; the following requests carry xid and operation type, while
+//! replies only the xid (together with zxid and error code), so the operation of a reply is taken
+//! from the request with the same xid. Watch notifications are replies with the reserved xid -1.
+//!
+//! Paths are decoded from the requests operating on a single znode, together with the watch flag
+//! of the reads; segments without a message header are not dissected.
+
+use std::net::IpAddr;
+
+use tracing::debug;
+
+use crate::serializable_packet::application::SerializableZookeeperPacket;
+use crate::serializable_packet::{ParsedPacket, SerializablePacket};
+use crate::ParserContext;
+
+/// Reserved xids
+#[allow(non_snake_case)]
+mod Xids {
+    pub const NOTIFICATION: i32 = -1;
+    pub const PING: i32 = -2;
+    pub const AUTH: i32 = -4;
+    pub const SET_WATCHES: i32 = -8;
+    /// Not sent on the wire: marks the pending connect request of a session
+    pub const CONNECT: i32 = i32::MIN;
+}
+
+/// Zookeeper operation codes with a particular body layout
+#[allow(non_snake_case)]
+mod OpCodes {
+    pub const CONNECT: i32 = -10;
+    pub const EXISTS: i32 = 3;
+    pub const GET_DATA: i32 = 4;
+    pub const GET_CHILDREN: i32 = 8;
+    pub const GET_CHILDREN2: i32 = 12;
+    pub const ADD_WATCH: i32 = 106;
+}
+
+/// Client and server of a request, with its xid
+pub(crate) type ZookeeperRequestKey = ((IpAddr, u16), (IpAddr, u16), i32);
+
+/// Upper bound of the message size (jute.maxbuffer is 1 MiB by default)
+const MAX_MESSAGE_SIZE: i32 = 16 * 1024 * 1024;
+/// Length of a connect request without password and read-only flag
+const CONNECT_REQUEST_LENGTH: i32 = 4 + 8 + 4 + 8 + 4;
+/// Length of a connect response without password and read-only flag
+const CONNECT_RESPONSE_LENGTH: i32 = 4 + 4 + 8 + 4;
+
+/// Build a Zookeeper packet from a transport-layer packet, save it in a Parsed Packet
+#[allow(clippy::too_many_arguments)]
+pub fn handle_zookeeper_packet(
+    state: &mut ParserContext,
+    source_ip: IpAddr,
+    source_port: u16,
+    dest_ip: IpAddr,
+    dest_port: u16,
+    is_request: bool,
+    packet: &[u8],
+    parsed_packet: &mut ParsedPacket,
+) {
+    let zookeeper_packet = match is_request {
+        true => parse_zookeeper_request(packet).inspect(|zookeeper_packet| {
+            state.zookeeper_requests.insert(
+                (
+                    (source_ip, source_port),
+                    (dest_ip, dest_port),
+                    zookeeper_packet.xid.unwrap_or(Xids::CONNECT),
+                ),
+                zookeeper_packet.opcode.unwrap(),
+            );
+        }),
+        false => {
+            let mut take_request = |xid: i32| {
+                state.zookeeper_requests.remove(&(
+                    (dest_ip, dest_port),
+                    (source_ip, source_port),
+                    xid,
+                ))
+            };
+
+            match take_request(Xids::CONNECT) {
+                Some(_) => parse_connect_response(packet),
+                None => parse_zookeeper_reply(packet, take_request),
+            }
+        }
+    };
+
+    if let Some(zookeeper_packet) = zookeeper_packet {
+        debug!(
+            "Zookeeper Packet: {}:{} > {}:{}; Operation: {:?}, Xid: {:?}, Path: {:?}",
+            source_ip,
+            source_port,
+            dest_ip,
+            dest_port,
+            zookeeper_packet.operation,
+            zookeeper_packet.xid,
+            zookeeper_packet.path
+        );
+
+        parsed_packet.set_application_layer_packet(Some(SerializablePacket::ZookeeperPacket(
+            zookeeper_packet,
+        )));
+    }
+}
+
+fn parse_zookeeper_request(packet: &[u8]) -> Option<SerializableZookeeperPacket> {
+    if let Some(zookeeper_packet) = parse_connect_request(packet) {
+        return Some(zookeeper_packet);
+    }
+
+    let mut reader = Reader(packet);
+    let length = reader.i32()?;
+    let xid = reader.i32()?;
+    let opcode = reader.i32()?;
+
+    let operation = operation_name(opcode)?;
+    if !(8..=MAX_MESSAGE_SIZE).contains(&length) {
+        return None;
+    }
+
+    let mut zookeeper_packet = SerializableZookeeperPacket {
+        length,
+        request: true,
+        xid: Some(xid),
+        opcode: Some(opcode),
+        operation: Some(operation.to_owned()),
+        path: None,
+        watch: None,
+        zxid: None,
+        error: None,
+        event: None,
+        session_id: None,
+    };
+
+    // Requests with reserved xids carry no znode path
+    if xid >= 0 && has_path(opcode) {
+        // The path may continue in the following segments
+        zookeeper_packet.path = reader.string();
+        zookeeper_packet.watch = match opcode {
+            OpCodes::EXISTS
+            | OpCodes::GET_DATA
+            | OpCodes::GET_CHILDREN
+            | OpCodes::GET_CHILDREN2 => reader.bool(),
+            OpCodes::ADD_WATCH => zookeeper_packet.path.as_ref().map(|_| true),
+            _ => None,
+        };
+    }
+
+    Some(zookeeper_packet)
+}
+
+/// Parse the first request of a session, which has no request header
+fn parse_connect_request(packet: &[u8]) -> Option<SerializableZookeeperPacket> {
+    let mut reader = Reader(packet);
+    let length = reader.i32()?;
+    let protocol_version = reader.i32()?;
+    let _last_zxid_seen = reader.i64()?;
+    let _timeout = reader.i32()?;
+    let session_id = reader.i64()?;
+    let password_length = reader.i32()?;
+
+    if !(0..=MAX_MESSAGE_SIZE).contains(&password_length) {
+        return None;
+    }
+
+    // Client xids start from 1, so the protocol version can't be mistaken for one
+    let expected_length = CONNECT_REQUEST_LENGTH + password_length;
+    if protocol_version != 0 || !(expected_length..=expected_length + 1).contains(&length) {
+        return None;
+    }
+
+    Some(SerializableZookeeperPacket {
+        length,
+        request: true,
+        xid: None,
+        opcode: Some(OpCodes::CONNECT),
+        operation: operation_name(OpCodes::CONNECT).map(str::to_owned),
+        path: None,
+        watch: None,
+        zxid: None,
+        error: None,
+        event: None,
+        session_id: Some(session_id),
+    })
+}
+
+fn parse_connect_response(packet: &[u8]) -> Option<SerializableZookeeperPacket> {
+    let mut reader = Reader(packet);
+    let length = reader.i32()?;
+    let protocol_version = reader.i32()?;
+    let _timeout = reader.i32()?;
+    let session_id = reader.i64()?;
+
+    if protocol_version != 0 || !(CONNECT_RESPONSE_LENGTH..=MAX_MESSAGE_SIZE).contains(&length) {
+        return None;
+    }
+
+    Some(SerializableZookeeperPacket {
+        length,
+        request: false,
+        xid: None,
+        opcode: Some(OpCodes::CONNECT),
+        operation: operation_name(OpCodes::CONNECT).map(str::to_owned),
+        path: None,
+        watch: None,
+        zxid: None,
+        error: None,
+        event: None,
+        session_id: Some(session_id),
+    })
+}
+
+fn parse_zookeeper_reply(
+    packet: &[u8],
+    take_request: impl FnOnce(i32) -> Option<i32>,
+) -> Option<SerializableZookeeperPacket> {
+    let mut reader = Reader(packet);
+    let length = reader.i32()?;
+    let xid = reader.i32()?;
+    let zxid = reader.i64()?;
+    let error = reader.i32()?;
+
+    if !(16..=MAX_MESSAGE_SIZE).contains(&length) {
+        return None;
+    }
+
+    let mut zookeeper_packet = SerializableZookeeperPacket {
+        length,
+        request: false,
+        xid: Some(xid),
+        opcode: None,
+        operation: None,
+        path: None,
+        watch: None,
+        zxid: Some(zxid),
+        error: Some(error),
+        event: None,
+        session_id: None,
+    };
+
+    match xid {
+        Xids::NOTIFICATION => {
+            zookeeper_packet.operation = Some("notification".to_owned());
+            zookeeper_packet.event = reader.i32().map(|event| event_name(event).to_owned());
+            let _state = reader.i32();
+            zookeeper_packet.path = reader.string();
+        }
+        Xids::PING | Xids::AUTH | Xids::SET_WATCHES => {
+            // Replies to reserved xids are not tracked, the requests being implied by the xid
+            let _request = take_request(xid);
+            zookeeper_packet.operation = Some(
+                match xid {
+                    Xids::PING => "ping",
+                    Xids::AUTH => "auth",
+                    _ => "setWatches",
+                }
+                .to_owned(),
+            );
+        }
+        _ => {
+            let opcode = take_request(xid)?;
+            zookeeper_packet.opcode = Some(opcode);
+            zookeeper_packet.operation = operation_name(opcode).map(str::to_owned);
+        }
+    }
+
+    Some(zookeeper_packet)
+}
+
+/// Big endian reader of jute primitive types
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, length: usize) -> Option<&'a [u8]> {
+        let bytes = self.0.get(..length)?;
+        self.0 = &self.0[length..];
+        Some(bytes)
+    }
+
+    fn bool(&mut self) -> Option<bool> {
+        self.take(1).map(|bytes| bytes[0] != 0)
+    }
+
+    fn i32(&mut self) -> Option<i32> {
+        self.take(4)
+            .map(|bytes| i32::from_be_bytes(bytes.try_into().unwrap()))
+    }
+
+    fn i64(&mut self) -> Option<i64> {
+        self.take(8)
+            .map(|bytes| i64::from_be_bytes(bytes.try_into().unwrap()))
+    }
+
+    /// Read a string: 4-bytes length (-1 for null) followed by the UTF-8 string
+    fn string(&mut self) -> Option<String> {
+        let length = self.i32()?;
+        let string = self.take(length.try_into().ok()?)?;
+        Some(String::from_utf8_lossy(string).into_owned())
+    }
+}
+
+/// Check if the request of an operation starts with a znode path
+fn has_path(opcode: i32) -> bool {
+    matches!(opcode, 1..=9 | 12 | 13 | 15 | 17..=21 | 103 | 104 | 106)
+}
+
+fn operation_name(opcode: i32) -> Option<&'static str> {
+    Some(match opcode {
+        1 => "create",
+        2 => "delete",
+        3 => "exists",
+        4 => "getData",
+        5 => "setData",
+        6 => "getACL",
+        7 => "setACL",
+        8 => "getChildren",
+        9 => "sync",
+        11 => "ping",
+        12 => "getChildren2",
+        13 => "check",
+        14 => "multi",
+        15 => "create2",
+        16 => "reconfig",
+        17 => "checkWatches",
+        18 => "removeWatches",
+        19 => "createContainer",
+        20 => "deleteContainer",
+        21 => "createTTL",
+        22 => "multiRead",
+        100 => "auth",
+        101 => "setWatches",
+        102 => "sasl",
+        103 => "getEphemerals",
+        104 => "getAllChildrenNumber",
+        105 => "setWatches2",
+        106 => "addWatch",
+        107 => "whoAmI",
+        -10 => "connect",
+        -11 => "closeSession",
+        _ => return None,
+    })
+}
+
+fn event_name(event: i32) -> &'static str {
+    match event {
+        -1 => "None",
+        1 => "NodeCreated",
+        2 => "NodeDeleted",
+        3 => "NodeDataChanged",
+        4 => "NodeChildrenChanged",
+        5 => "DataWatchRemoved",
+        6 => "ChildWatchRemoved",
+        7 => "PersistentWatchRemoved",
+        _ => "Unknown",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr};
+
+    use crate::serializable_packet::{ParsedPacket, SerializablePacket};
+    use crate::ParserContext;
+
+    use super::handle_zookeeper_packet;
+
+    const CLIENT: (IpAddr, u16) = (IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)), 50000);
+    const SERVER: (IpAddr, u16) = (IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2)), 2181);
+
+    #[test]
+    fn get_data_request_and_reply() {
+        let mut state = ParserContext::new();
+
+        let request = build_test_message(&[
+            &5i32.to_be_bytes()[..],
+            &4i32.to_be_bytes(),
+            &string(b"/config/db"),
+            &[0x01],
+        ]);
+        match parse(&mut state, CLIENT, SERVER, true, &request) {
+            Some(SerializablePacket::ZookeeperPacket(zookeeper_packet)) => {
+                assert_eq!(zookeeper_packet.operation.as_deref(), Some("getData"));
+                assert_eq!(zookeeper_packet.xid, Some(5));
+                assert_eq!(zookeeper_packet.path.as_deref(), Some("/config/db"));
+                assert_eq!(zookeeper_packet.watch, Some(true));
+            }
+            _ => unreachable!(),
+        }
+
+        let reply = build_test_message(&[
+            &5i32.to_be_bytes()[..],
+            &0x1_0000_0002i64.to_be_bytes(),
+            &(-101i32).to_be_bytes(),
+        ]);
+        match parse(&mut state, SERVER, CLIENT, false, &reply) {
+            Some(SerializablePacket::ZookeeperPacket(zookeeper_packet)) => {
+                assert!(!zookeeper_packet.request);
+                assert_eq!(zookeeper_packet.operation.as_deref(), Some("getData"));
+                assert_eq!(zookeeper_packet.zxid, Some(0x1_0000_0002));
+                assert_eq!(zookeeper_packet.error, Some(-101));
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn connect_request_and_response() {
+        let mut state = ParserContext::new();
+
+        let request = build_test_message(&[
+            &0i32.to_be_bytes()[..],
+            &0i64.to_be_bytes(),
+            &30000i32.to_be_bytes(),
+            &0i64.to_be_bytes(),
+            &16i32.to_be_bytes(),
+            &[0u8; 16],
+            &[0x00],
+        ]);
+        match parse(&mut state, CLIENT, SERVER, true, &request) {
+            Some(SerializablePacket::ZookeeperPacket(zookeeper_packet)) => {
+                assert_eq!(zookeeper_packet.operation.as_deref(), Some("connect"));
+                assert_eq!(zookeeper_packet.xid, None);
+            }
+            _ => unreachable!(),
+        }
+
+        let response = build_test_message(&[
+            &0i32.to_be_bytes()[..],
+            &30000i32.to_be_bytes(),
+            &0x0100_0000_0000_0001i64.to_be_bytes(),
+            &16i32.to_be_bytes(),
+            &[0xaa; 16],
+        ]);
+        match parse(&mut state, SERVER, CLIENT, false, &response) {
+            Some(SerializablePacket::ZookeeperPacket(zookeeper_packet)) => {
+                assert_eq!(zookeeper_packet.operation.as_deref(), Some("connect"));
+                assert_eq!(zookeeper_packet.session_id, Some(0x0100_0000_0000_0001));
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn watch_notification() {
+        let mut state = ParserContext::new();
+
+        let notification = build_test_message(&[
+            &(-1i32).to_be_bytes()[..],
+            &(-1i64).to_be_bytes(),
+            &0i32.to_be_bytes(),
+            &3i32.to_be_bytes(),
+            &3i32.to_be_bytes(),
+            &string(b"/config/db"),
+        ]);
+        match parse(&mut state, SERVER, CLIENT, false, &notification) {
+            Some(SerializablePacket::ZookeeperPacket(zookeeper_packet)) => {
+                assert_eq!(zookeeper_packet.operation.as_deref(), Some("notification"));
+                assert_eq!(zookeeper_packet.event.as_deref(), Some("NodeDataChanged"));
+                assert_eq!(zookeeper_packet.path.as_deref(), Some("/config/db"));
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn reply_without_request() {
+        let mut state = ParserContext::new();
+
+        let reply = build_test_message(&[
+            &9i32.to_be_bytes()[..],
+            &1i64.to_be_bytes(),
+            &0i32.to_be_bytes(),
+        ]);
+        assert!(parse(&mut state, SERVER, CLIENT, false, &reply).is_none());
+    }
+
+    ///////////////////// Utils
+
+    fn parse(
+        state: &mut ParserContext,
+        source: (IpAddr, u16),
+        destination: (IpAddr, u16),
+        is_request: bool,
+        packet: &[u8],
+    ) -> Option<SerializablePacket> {
+        let mut parsed_packet = ParsedPacket::new(0);
+        handle_zookeeper_packet(
+            state,
+            source.0,
+            source.1,
+            destination.0,
+            destination.1,
+            is_request,
+            packet,
+            &mut parsed_packet,
+        );
+
+        parsed_packet.get_application_layer_packet().cloned()
+    }
+
+    fn string(value: &[u8]) -> Vec<u8> {
+        [&(value.len() as i32).to_be_bytes()[..], value].concat()
+    }
+
+    fn build_test_message(fields: &[&[u8]]) -> Vec<u8> {
+        let message = fields.concat();
+        [&(message.len() as i32).to_be_bytes()[..], &message].concat()
+    }
+}
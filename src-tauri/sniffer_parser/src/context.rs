@@ -0,0 +1,235 @@
+//! State kept by the parsers across the frames of a capture
+//!
+//! Parsers keep per-flow state: tracked flows, reassembled TCP streams, partial application
+//! messages, packets that later packets reference... It all belongs to a [`ParserContext`], owned
+//! by the caller and given to the parsers of each frame. A context can be moved to another thread
+//! between frames, so parsing can be distributed across threads (e.g. one context for each shard
+//! of the flows), and its state can be inspected and cleared regardless of the thread that parsed
+//! them.
+//!
+//! The functions parsing frames without a context (e.g. [`crate::parse_frame`]) use a default
+//! one of the current thread, deleted by [`crate::cleanup_sniffing_state`]. The parsers never
+//! use it themselves: they are given the context of the frame they parse.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use pnet::packet::ethernet::EthernetPacket;
+use pnet::packet::Packet;
+
+use crate::application::health::forget_buffers;
+use crate::application::registry::Dissector;
+use crate::application::{
+    http::HttpConnection, http2::Http2Direction, kafka::KafkaRequestKey, ptp::PtpExchanges,
+    quic::QuicConnection, zookeeper::ZookeeperRequestKey,
+};
+use crate::flows::FlowTracker;
+use crate::reassembly::TcpStream;
+use crate::references::PacketLinks;
+use crate::serializable_packet::ParsedPacket;
+use crate::tcp_analysis::TcpDirection;
+use crate::{
+    dissect_frame, get_dissection_depth, DissectionDepth, FlowDirection, FlowKey, FrameMeta,
+    LinkType,
+};
+
+/// Identifier of the next context created
+static NEXT_CONTEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+thread_local!(
+    static THREAD_CONTEXT: RefCell<ParserContext> = RefCell::new(ParserContext::new());
+);
+
+/// State of the parsers, owned by the caller instead of the thread parsing the frames
+pub struct ParserContext {
+    /// Identifier of the context, to tell its buffers from the ones of the other contexts
+    pub(crate) id: usize,
+    pub(crate) flows: FlowTracker,
+    pub(crate) links: PacketLinks,
+    pub(crate) tcp_streams: HashMap<(FlowKey, FlowDirection), TcpStream>,
+    pub(crate) tcp_directions: HashMap<(FlowKey, FlowDirection), TcpDirection>,
+    pub(crate) http_parsers: HashMap<FlowKey, HttpConnection>,
+    pub(crate) tls_parsers: HashMap<(FlowKey, FlowDirection), Vec<u8>>,
+    pub(crate) tls_record_indexes: HashMap<(FlowKey, FlowDirection), usize>,
+    pub(crate) kafka_requests: HashMap<KafkaRequestKey, (i16, i16)>,
+    pub(crate) zookeeper_requests: HashMap<ZookeeperRequestKey, i32>,
+    pub(crate) ptp_exchanges: PtpExchanges,
+    pub(crate) http2_connections: HashMap<(FlowKey, FlowDirection), Http2Direction>,
+    pub(crate) quic_connections: HashMap<FlowKey, QuicConnection>,
+    pub(crate) detected_protocols: HashMap<(FlowKey, FlowDirection), (Arc<dyn Dissector>, bool)>,
+    /// Layers the packets are dissected up to, instead of the ones chosen by the user
+    pub(crate) dissection_depth: Option<DissectionDepth>,
+    /// Capture time of the frames parsed without one in their metadata, instead of the time they
+    /// are parsed at
+    pub(crate) capture_time: Option<Duration>,
+}
+
+/// Run the parsers with the default context of the current thread
+///
+/// The context is not available to the parsers already running with it: they must give theirs to
+/// the parsers they rely on (e.g. a dissector registered at runtime to a built-in one).
+pub(crate) fn with_thread_context<T>(parse: impl FnOnce(&mut ParserContext) -> T) -> T {
+    THREAD_CONTEXT.with(|context| {
+        let mut context = context
+            .try_borrow_mut()
+            .expect("Default parser context of the thread already in use by the parsers");
+        parse(&mut context)
+    })
+}
+
+impl Default for ParserContext {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for ParserContext {
+    fn drop(&mut self) {
+        forget_buffers(self.id);
+    }
+}
+
+impl ParserContext {
+    pub fn new() -> Self {
+        ParserContext {
+            id: NEXT_CONTEXT_ID.fetch_add(1, Ordering::Relaxed),
+            flows: FlowTracker::default(),
+            links: PacketLinks::default(),
+            tcp_streams: HashMap::new(),
+            tcp_directions: HashMap::new(),
+            http_parsers: HashMap::new(),
+            tls_parsers: HashMap::new(),
+            tls_record_indexes: HashMap::new(),
+            kafka_requests: HashMap::new(),
+            zookeeper_requests: HashMap::new(),
+            ptp_exchanges: PtpExchanges::default(),
+            http2_connections: HashMap::new(),
+            quic_connections: HashMap::new(),
+            detected_protocols: HashMap::new(),
+            dissection_depth: None,
+            capture_time: None,
+        }
+    }
+
+    /// Dissect the packets up to the given layers, whatever the ones chosen by the user, or up to
+    /// the chosen ones
+    pub fn set_dissection_depth(&mut self, depth: Option<DissectionDepth>) {
+        self.dissection_depth = depth;
+    }
+
+    /// Get the layers the packets are dissected up to
+    pub fn dissection_depth(&self) -> DissectionDepth {
+        self.dissection_depth.unwrap_or_else(get_dissection_depth)
+    }
+
+    /// Parse a frame with the state of the context, instead of the one of the current thread
+    pub fn parse_frame(
+        &mut self,
+        link_type: LinkType,
+        frame: &[u8],
+        meta: FrameMeta,
+    ) -> ParsedPacket {
+        dissect_frame(self, link_type, frame, meta)
+    }
+
+    /// Parse an Ethernet frame with the state of the context
+    pub fn parse_ethernet_frame(&mut self, ethernet: &EthernetPacket, id: usize) -> ParsedPacket {
+        self.parse_frame(LinkType::Ethernet, ethernet.packet(), FrameMeta::new(id))
+    }
+
+    /// Number of flows tracked
+    pub fn tracked_flows(&self) -> usize {
+        self.flows.len()
+    }
+
+    /// Check if the context keeps no state
+    pub fn is_empty(&self) -> bool {
+        self.flows.is_empty()
+            && self.links.is_empty()
+            && self.tcp_streams.is_empty()
+            && self.tcp_directions.is_empty()
+            && self.http_parsers.is_empty()
+            && self.tls_parsers.is_empty()
+            && self.tls_record_indexes.is_empty()
+            && self.kafka_requests.is_empty()
+            && self.zookeeper_requests.is_empty()
+            && self.ptp_exchanges.is_empty()
+            && self.http2_connections.is_empty()
+            && self.quic_connections.is_empty()
+            && self.detected_protocols.is_empty()
+    }
+
+    /// Delete the state of the context, forgetting the buffers it published, but not the
+    /// settings of its parsing (dissection depth and capture time)
+    pub fn clear(&mut self) {
+        let (dissection_depth, capture_time) = (self.dissection_depth, self.capture_time);
+        *self = ParserContext::new();
+        self.dissection_depth = dissection_depth;
+        self.capture_time = capture_time;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use pnet::packet::ethernet::EthernetPacket;
+
+    use super::{with_thread_context, ParserContext};
+    use crate::{cleanup_sniffing_state, parse_ethernet_frame};
+
+    // Ethernet (14) + IPv4 (20) + TCP (20) + start of an HTTP request
+    const HTTP_FRAME: &[u8] = &[
+        0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x08, 0x00, 0x45,
+        0x00, 0x00, 0x38, 0x00, 0x00, 0x00, 0x00, 0x40, 0x06, 0x00, 0x00, 0x0a, 0x0a, 0x0a, 0x0a,
+        0x0b, 0x0b, 0x0b, 0x0b, 0xc3, 0x50, 0x00, 0x50, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00,
+        0x00, 0x50, 0x18, 0xff, 0xff, 0x00, 0x00, 0x00, 0x00, 0x47, 0x45, 0x54, 0x20, 0x2f, 0x20,
+        0x48, 0x54, 0x54, 0x50, 0x2f, 0x31, 0x2e, 0x31, 0x0d, 0x0a,
+    ];
+
+    #[test]
+    fn state_kept_by_context() {
+        cleanup_sniffing_state();
+        let mut context = ParserContext::new();
+        assert!(context.is_empty());
+
+        context.parse_ethernet_frame(&EthernetPacket::new(HTTP_FRAME).unwrap(), 0);
+        assert_eq!(context.tracked_flows(), 1);
+        assert!(!context.is_empty());
+
+        // The default context of the thread is left untouched
+        assert!(with_thread_context(|state| state.is_empty()));
+        parse_ethernet_frame(&EthernetPacket::new(HTTP_FRAME).unwrap(), 0);
+        assert_eq!(with_thread_context(|state| state.tracked_flows()), 1);
+        assert_eq!(context.tracked_flows(), 1);
+
+        context.clear();
+        assert!(context.is_empty());
+        cleanup_sniffing_state();
+    }
+
+    #[test]
+    fn context_moved_across_threads() {
+        let mut context = ParserContext::new();
+        context.parse_ethernet_frame(&EthernetPacket::new(HTTP_FRAME).unwrap(), 0);
+
+        let context = thread::spawn(move || {
+            context.parse_ethernet_frame(&EthernetPacket::new(HTTP_FRAME).unwrap(), 1);
+            context
+        })
+        .join()
+        .unwrap();
+
+        assert_eq!(context.tracked_flows(), 1);
+        assert_eq!(with_thread_context(|state| state.tracked_flows()), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "already in use")]
+    fn thread_context_not_given_twice() {
+        with_thread_context(|_| parse_ethernet_frame(&EthernetPacket::new(HTTP_FRAME).unwrap(), 0));
+    }
+}
@@ -0,0 +1,393 @@
+//! Linux cooked, loopback and raw IP frame parsing, for the captures without Ethernet headers
+//!
+//! Captures on the `any` pseudo-interface of Linux replace the link-layer header of each frame by
+//! a "cooked" one (SLL, or SLL2 with the index of the interface): the direction of the frame, the
+//! address of its sender and the Ethertype of its payload. Captures on the loopback interfaces of
+//! the BSDs and macOS (null and loop link types) precede each packet by its address family, in the
+//! byte order of the capturing host for the former. Raw IP captures have no link-layer header at
+//! all. The packets carried by all of them go through the usual network-layer dissection.
+
+use pnet::packet::ethernet::{EtherType, EtherTypes};
+use pnet::util::MacAddr;
+use tracing::debug;
+
+use crate::serializable_packet::{
+    PacketMeta, ParsedPacket, SerializableLinuxSllPacket, SerializableLoopbackPacket,
+    SerializablePacket, SerializableRawPacket,
+};
+use crate::{
+    handle_arp_packet, handle_ipv4_packet, handle_ipv6_packet, parse_frame, FrameMeta, LinkType,
+    ParserContext,
+};
+
+/// Header of the Linux cooked frames: packet type, hardware type, address length, address (8 bytes)
+/// and protocol
+const SLL_HEADER_LENGTH: usize = 16;
+
+/// Header of the Linux cooked frames v2: protocol, reserved bytes, interface index, hardware type,
+/// packet type, address length and address (8 bytes)
+const SLL2_HEADER_LENGTH: usize = 20;
+
+/// Address family header of the loopback frames
+const LOOPBACK_HEADER_LENGTH: usize = 4;
+
+/// Address families of the loopback frames, which differ among the operating systems
+#[allow(non_snake_case)]
+mod AddressFamilies {
+    pub const INET: u32 = 2;
+    /// Linux
+    pub const INET6_LINUX: u32 = 10;
+    /// NetBSD, OpenBSD and BSD/OS
+    pub const INET6_BSD: u32 = 24;
+    pub const INET6_FREEBSD: u32 = 28;
+    pub const INET6_DARWIN: u32 = 30;
+}
+
+/// Parse a Linux cooked (SLL) frame obtaining the representation of its content
+pub fn parse_linux_sll_frame(frame: &[u8], id: usize) -> ParsedPacket {
+    parse_frame(LinkType::LinuxSll, frame, FrameMeta::new(id))
+}
+
+/// Dissect a Linux cooked (SLL) frame with the state of a parser context
+pub(crate) fn dissect_linux_sll_frame(
+    state: &mut ParserContext,
+    frame: &[u8],
+    meta: PacketMeta,
+) -> ParsedPacket {
+    let mut parsed_packet = ParsedPacket::with_meta(meta);
+
+    if frame.len() < SLL_HEADER_LENGTH {
+        debug!("Malformed Linux cooked Frame");
+        parsed_packet.set_link_layer_packet(Some(SerializablePacket::MalformedPacket(
+            "Malformed Linux cooked Frame".to_owned(),
+        )));
+        return parsed_packet;
+    }
+
+    let packet_type = u16::from_be_bytes([frame[0], frame[1]]);
+    let hardware_type = u16::from_be_bytes([frame[2], frame[3]]);
+    let address = &frame[6..6 + (frame[5] as usize).min(8)];
+    let protocol = EtherType(u16::from_be_bytes([frame[14], frame[15]]));
+
+    handle_cooked_frame(
+        state,
+        SerializableLinuxSllPacket {
+            packet_type: packet_type_to_string(packet_type),
+            hardware_type,
+            address: address_to_string(address),
+            ethertype: protocol.to_string(),
+            interface_index: None,
+            length: frame.len(),
+        },
+        protocol,
+        address,
+        &frame[SLL_HEADER_LENGTH..],
+        &mut parsed_packet,
+    );
+
+    parsed_packet
+}
+
+/// Parse a Linux cooked v2 (SLL2) frame obtaining the representation of its content
+pub fn parse_linux_sll2_frame(frame: &[u8], id: usize) -> ParsedPacket {
+    parse_frame(LinkType::LinuxSll2, frame, FrameMeta::new(id))
+}
+
+/// Dissect a Linux cooked v2 (SLL2) frame with the state of a parser context
+pub(crate) fn dissect_linux_sll2_frame(
+    state: &mut ParserContext,
+    frame: &[u8],
+    meta: PacketMeta,
+) -> ParsedPacket {
+    let mut parsed_packet = ParsedPacket::with_meta(meta);
+
+    if frame.len() < SLL2_HEADER_LENGTH {
+        debug!("Malformed Linux cooked v2 Frame");
+        parsed_packet.set_link_layer_packet(Some(SerializablePacket::MalformedPacket(
+            "Malformed Linux cooked v2 Frame".to_owned(),
+        )));
+        return parsed_packet;
+    }
+
+    let protocol = EtherType(u16::from_be_bytes([frame[0], frame[1]]));
+    let interface_index = u32::from_be_bytes([frame[4], frame[5], frame[6], frame[7]]);
+    let hardware_type = u16::from_be_bytes([frame[8], frame[9]]);
+    let address = &frame[12..12 + (frame[11] as usize).min(8)];
+
+    handle_cooked_frame(
+        state,
+        SerializableLinuxSllPacket {
+            packet_type: packet_type_to_string(frame[10] as u16),
+            hardware_type,
+            address: address_to_string(address),
+            ethertype: protocol.to_string(),
+            interface_index: Some(interface_index),
+            length: frame.len(),
+        },
+        protocol,
+        address,
+        &frame[SLL2_HEADER_LENGTH..],
+        &mut parsed_packet,
+    );
+
+    parsed_packet
+}
+
+/// Parse a loopback (null or loop) frame obtaining the representation of its content
+///
+/// The address family of the null frames is in the byte order of the capturing host, the one of the
+/// loop frames in network byte order: since families are small numbers, the byte order is the one
+/// giving the smallest of them.
+pub fn parse_loopback_frame(frame: &[u8], id: usize) -> ParsedPacket {
+    parse_frame(LinkType::Null, frame, FrameMeta::new(id))
+}
+
+/// Dissect a loopback (null or loop) frame with the state of a parser context
+pub(crate) fn dissect_loopback_frame(
+    state: &mut ParserContext,
+    frame: &[u8],
+    meta: PacketMeta,
+) -> ParsedPacket {
+    let mut parsed_packet = ParsedPacket::with_meta(meta);
+
+    if frame.len() < LOOPBACK_HEADER_LENGTH {
+        debug!("Malformed Loopback Frame");
+        parsed_packet.set_link_layer_packet(Some(SerializablePacket::MalformedPacket(
+            "Malformed Loopback Frame".to_owned(),
+        )));
+        return parsed_packet;
+    }
+
+    let header = [frame[0], frame[1], frame[2], frame[3]];
+    let family = u32::from_be_bytes(header).min(u32::from_le_bytes(header));
+    debug!(
+        "Loopback Frame: {}; length: {}",
+        family_to_string(family),
+        frame.len()
+    );
+
+    parsed_packet.set_link_layer_packet(Some(SerializablePacket::LoopbackPacket(
+        SerializableLoopbackPacket {
+            family,
+            family_name: family_to_string(family),
+            length: frame.len(),
+        },
+    )));
+
+    let payload = &frame[LOOPBACK_HEADER_LENGTH..];
+    match family {
+        AddressFamilies::INET => handle_ipv4_packet(state, payload, &mut parsed_packet),
+        AddressFamilies::INET6_LINUX
+        | AddressFamilies::INET6_BSD
+        | AddressFamilies::INET6_FREEBSD
+        | AddressFamilies::INET6_DARWIN => handle_ipv6_packet(state, payload, &mut parsed_packet),
+        _ => debug!("Loopback Frame without IP packet"),
+    }
+
+    parsed_packet
+}
+
+/// Parse a raw IP frame, made of an IPv4 or IPv6 packet alone, obtaining the representation of its
+/// content
+pub fn parse_raw_ip_frame(frame: &[u8], id: usize) -> ParsedPacket {
+    parse_frame(LinkType::Raw, frame, FrameMeta::new(id))
+}
+
+/// Dissect a raw IP frame with the state of a parser context
+pub(crate) fn dissect_raw_ip_frame(
+    state: &mut ParserContext,
+    frame: &[u8],
+    meta: PacketMeta,
+) -> ParsedPacket {
+    let mut parsed_packet = ParsedPacket::with_meta(meta);
+
+    parsed_packet.set_link_layer_packet(Some(SerializablePacket::RawPacket(
+        SerializableRawPacket {
+            length: frame.len(),
+        },
+    )));
+
+    match frame.first().map(|byte| byte >> 4) {
+        Some(4) => handle_ipv4_packet(state, frame, &mut parsed_packet),
+        Some(6) => handle_ipv6_packet(state, frame, &mut parsed_packet),
+        _ => {
+            debug!("Malformed Raw IP Frame");
+            parsed_packet.set_network_layer_packet(Some(SerializablePacket::MalformedPacket(
+                "Malformed Raw IP Frame".to_owned(),
+            )));
+        }
+    }
+
+    parsed_packet
+}
+
+/// Save the representation of a Linux cooked frame, and dissect the packet it carries
+fn handle_cooked_frame(
+    state: &mut ParserContext,
+    sll_packet: SerializableLinuxSllPacket,
+    protocol: EtherType,
+    address: &[u8],
+    payload: &[u8],
+    parsed_packet: &mut ParsedPacket,
+) {
+    debug!(
+        "Linux cooked Frame: {} from {}; protocol: {}; length: {}",
+        sll_packet.packet_type, sll_packet.address, protocol, sll_packet.length
+    );
+    parsed_packet.set_link_layer_packet(Some(SerializablePacket::LinuxSllPacket(sll_packet)));
+
+    match protocol {
+        EtherTypes::Ipv4 => handle_ipv4_packet(state, payload, parsed_packet),
+        EtherTypes::Ipv6 => handle_ipv6_packet(state, payload, parsed_packet),
+        EtherTypes::Arp => {
+            // Only the address of the sender is known
+            let source = match address {
+                [a, b, c, d, e, f] => MacAddr::new(*a, *b, *c, *d, *e, *f),
+                _ => MacAddr::zero(),
+            };
+            handle_arp_packet(payload, source, MacAddr::zero(), parsed_packet)
+        }
+        _ => debug!("Linux cooked Frame without IP packet"),
+    }
+}
+
+fn packet_type_to_string(packet_type: u16) -> String {
+    match packet_type {
+        0 => "Unicast to us".to_owned(),
+        1 => "Broadcast".to_owned(),
+        2 => "Multicast".to_owned(),
+        3 => "Unicast to another host".to_owned(),
+        4 => "Sent by us".to_owned(),
+        _ => format!("Unknown ({})", packet_type),
+    }
+}
+
+fn family_to_string(family: u32) -> String {
+    match family {
+        AddressFamilies::INET => "IPv4".to_owned(),
+        AddressFamilies::INET6_LINUX
+        | AddressFamilies::INET6_BSD
+        | AddressFamilies::INET6_FREEBSD
+        | AddressFamilies::INET6_DARWIN => "IPv6".to_owned(),
+        _ => format!("Unknown ({})", family),
+    }
+}
+
+fn address_to_string(address: &[u8]) -> String {
+    address
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::serializable_packet::SerializablePacket;
+
+    use super::{
+        parse_linux_sll2_frame, parse_linux_sll_frame, parse_loopback_frame, parse_raw_ip_frame,
+    };
+
+    /// IPv4 packet carrying an empty UDP datagram
+    const IPV4_PACKET: [u8; 28] = [
+        0x45, 0x00, 0x00, 0x1c, 0x00, 0x00, 0x00, 0x00, 0x40, 0x11, 0x00, 0x00, 0x7f, 0x00, 0x00,
+        0x01, 0x7f, 0x00, 0x00, 0x01, 0x30, 0x39, 0x30, 0x3a, 0x00, 0x08, 0x00, 0x00,
+    ];
+
+    #[test]
+    fn linux_cooked_frames() {
+        let mut sll_frame = vec![0x00, 0x04, 0x00, 0x01, 0x00, 0x06];
+        sll_frame.extend([0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x00, 0x00, 0x08, 0x00]);
+        sll_frame.extend(IPV4_PACKET);
+        let parsed_packet = parse_linux_sll_frame(&sll_frame, 0);
+        match parsed_packet.get_link_layer_packet() {
+            Some(SerializablePacket::LinuxSllPacket(sll_packet)) => {
+                assert_eq!(sll_packet.packet_type, "Sent by us");
+                assert_eq!(sll_packet.hardware_type, 1);
+                assert_eq!(sll_packet.address, "00:11:22:33:44:55");
+                assert_eq!(sll_packet.interface_index, None);
+                assert_eq!(sll_packet.length, 44);
+            }
+            _ => unreachable!(),
+        }
+        assert!(matches!(
+            parsed_packet.get_transport_layer_packet(),
+            Some(SerializablePacket::UdpPacket(_))
+        ));
+
+        let mut sll2_frame = vec![0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x03, 0x03, 0x04];
+        sll2_frame.extend([0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
+        sll2_frame.extend(IPV4_PACKET);
+        let parsed_packet = parse_linux_sll2_frame(&sll2_frame, 1);
+        match parsed_packet.get_link_layer_packet() {
+            Some(SerializablePacket::LinuxSllPacket(sll_packet)) => {
+                assert_eq!(sll_packet.packet_type, "Unicast to us");
+                assert_eq!(sll_packet.hardware_type, 772);
+                assert_eq!(sll_packet.address, "");
+                assert_eq!(sll_packet.interface_index, Some(3));
+            }
+            _ => unreachable!(),
+        }
+        assert!(matches!(
+            parsed_packet.get_transport_layer_packet(),
+            Some(SerializablePacket::UdpPacket(_))
+        ));
+
+        assert!(matches!(
+            parse_linux_sll2_frame(&sll_frame[..12], 2).get_link_layer_packet(),
+            Some(SerializablePacket::MalformedPacket(_))
+        ));
+    }
+
+    #[test]
+    fn loopback_frames() {
+        // Address families in the byte order of little endian hosts, and in network byte order
+        for header in [[0x02, 0x00, 0x00, 0x00], [0x00, 0x00, 0x00, 0x02]] {
+            let mut frame = header.to_vec();
+            frame.extend(IPV4_PACKET);
+            let parsed_packet = parse_loopback_frame(&frame, 0);
+            match parsed_packet.get_link_layer_packet() {
+                Some(SerializablePacket::LoopbackPacket(loopback_packet)) => {
+                    assert_eq!(loopback_packet.family, 2);
+                    assert_eq!(loopback_packet.family_name, "IPv4");
+                }
+                _ => unreachable!(),
+            }
+            assert!(matches!(
+                parsed_packet.get_network_layer_packet(),
+                Some(SerializablePacket::Ipv4Packet(_))
+            ));
+        }
+
+        let parsed_packet = parse_loopback_frame(&[0x1e, 0x00, 0x00, 0x00], 1);
+        match parsed_packet.get_link_layer_packet() {
+            Some(SerializablePacket::LoopbackPacket(loopback_packet)) => {
+                assert_eq!(loopback_packet.family_name, "IPv6");
+            }
+            _ => unreachable!(),
+        }
+        assert!(matches!(
+            parsed_packet.get_network_layer_packet(),
+            Some(SerializablePacket::MalformedPacket(_))
+        ));
+    }
+
+    #[test]
+    fn raw_ip_frames() {
+        let parsed_packet = parse_raw_ip_frame(&IPV4_PACKET, 0);
+        assert!(matches!(
+            parsed_packet.get_link_layer_packet(),
+            Some(SerializablePacket::RawPacket(raw_packet)) if raw_packet.length == 28
+        ));
+        assert!(matches!(
+            parsed_packet.get_transport_layer_packet(),
+            Some(SerializablePacket::UdpPacket(_))
+        ));
+
+        assert!(matches!(
+            parse_raw_ip_frame(&[0x00; 20], 1).get_network_layer_packet(),
+            Some(SerializablePacket::MalformedPacket(_))
+        ));
+    }
+}
@@ -0,0 +1,542 @@
+//! Human-readable descriptions of the values of protocol fields, in each supported locale
+//!
+//! The values described are grouped by field:
+//! - ICMP types
+//! - ICMPv6 types
+//! - TCP flags, by their bit
+//! - TLS alert levels
+//! - TLS alert descriptions
+//!
+//! Each value has a name, independent of the locale, which is the one used in the dissected packets
+//! (e.g. `EchoReply (0)`), and a description in each locale. The frontend gets the descriptions of
+//! its locale all at once, and looks them up by the field and the value found in a packet.
+
+use serde::{Deserialize, Serialize};
+
+/// Languages the descriptions are available in
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum Locale {
+    En,
+    It,
+}
+
+/// Fields whose values are described
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum Field {
+    IcmpType,
+    Icmpv6Type,
+    TcpFlag,
+    TlsAlertLevel,
+    TlsAlertDescription,
+}
+
+impl Field {
+    const ALL: [Field; 5] = [
+        Field::IcmpType,
+        Field::Icmpv6Type,
+        Field::TcpFlag,
+        Field::TlsAlertLevel,
+        Field::TlsAlertDescription,
+    ];
+
+    fn entries(&self) -> &'static [Entry] {
+        match self {
+            Field::IcmpType => ICMP_TYPES,
+            Field::Icmpv6Type => ICMPV6_TYPES,
+            Field::TcpFlag => TCP_FLAGS,
+            Field::TlsAlertLevel => TLS_ALERT_LEVELS,
+            Field::TlsAlertDescription => TLS_ALERT_DESCRIPTIONS,
+        }
+    }
+}
+
+/// Value of a field, with its name and its description in English and Italian
+type Entry = (u16, &'static str, &'static str, &'static str);
+
+/// Description of a value, as returned to the frontend
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Description {
+    pub value: u16,
+    pub name: &'static str,
+    pub description: &'static str,
+}
+
+/// Descriptions of the values of a field, as returned to the frontend
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct FieldDescriptions {
+    pub field: Field,
+    pub descriptions: Vec<Description>,
+}
+
+const ICMP_TYPES: &[Entry] = &[
+    (0, "EchoReply", "Echo reply", "Risposta echo"),
+    (
+        3,
+        "DestinationUnreachable",
+        "Destination unreachable",
+        "Destinazione irraggiungibile",
+    ),
+    (4, "SourceQuench", "Source quench", "Rallentamento sorgente"),
+    (5, "RedirectMessage", "Redirect", "Reindirizzamento"),
+    (8, "EchoRequest", "Echo request", "Richiesta echo"),
+    (
+        9,
+        "RouterAdvertisement",
+        "Router advertisement",
+        "Annuncio del router",
+    ),
+    (
+        10,
+        "RouterSolicitation",
+        "Router solicitation",
+        "Sollecitazione del router",
+    ),
+    (11, "TimeExceeded", "Time exceeded", "Tempo scaduto"),
+    (
+        12,
+        "ParameterProblem",
+        "Parameter problem",
+        "Problema nei parametri",
+    ),
+    (13, "Timestamp", "Timestamp request", "Richiesta timestamp"),
+    (
+        14,
+        "TimestampReply",
+        "Timestamp reply",
+        "Risposta timestamp",
+    ),
+    (
+        15,
+        "InformationRequest",
+        "Information request",
+        "Richiesta di informazioni",
+    ),
+    (
+        16,
+        "InformationReply",
+        "Information reply",
+        "Risposta di informazioni",
+    ),
+    (
+        17,
+        "AddressMaskRequest",
+        "Address mask request",
+        "Richiesta della maschera di rete",
+    ),
+    (
+        18,
+        "AddressMaskReply",
+        "Address mask reply",
+        "Risposta della maschera di rete",
+    ),
+    (30, "Traceroute", "Traceroute", "Traceroute"),
+];
+
+const ICMPV6_TYPES: &[Entry] = &[
+    (
+        1,
+        "DestinationUnreachable",
+        "Destination unreachable",
+        "Destinazione irraggiungibile",
+    ),
+    (
+        2,
+        "PacketTooBig",
+        "Packet too big",
+        "Pacchetto troppo grande",
+    ),
+    (3, "TimeExceeded", "Time exceeded", "Tempo scaduto"),
+    (
+        4,
+        "ParameterProblem",
+        "Parameter problem",
+        "Problema nei parametri",
+    ),
+    (128, "EchoRequest", "Echo request", "Richiesta echo"),
+    (129, "EchoReply", "Echo reply", "Risposta echo"),
+    (
+        133,
+        "RouterSolicit",
+        "Router solicitation",
+        "Sollecitazione del router",
+    ),
+    (
+        134,
+        "RouterAdvert",
+        "Router advertisement",
+        "Annuncio del router",
+    ),
+    (
+        135,
+        "NeighborSolicit",
+        "Neighbor solicitation",
+        "Sollecitazione del vicino",
+    ),
+    (
+        136,
+        "NeighborAdvert",
+        "Neighbor advertisement",
+        "Annuncio del vicino",
+    ),
+    (137, "Redirect", "Redirect", "Reindirizzamento"),
+];
+
+const TCP_FLAGS: &[Entry] = &[
+    (
+        0x001,
+        "FIN",
+        "No more data from the sender",
+        "Il mittente non ha altri dati da inviare",
+    ),
+    (
+        0x002,
+        "SYN",
+        "Synchronize the sequence numbers to open a connection",
+        "Sincronizza i numeri di sequenza per aprire una connessione",
+    ),
+    (
+        0x004,
+        "RST",
+        "Reset the connection",
+        "Reimposta la connessione",
+    ),
+    (
+        0x008,
+        "PSH",
+        "Push the buffered data to the receiving application",
+        "Consegna i dati bufferizzati all'applicazione ricevente",
+    ),
+    (
+        0x010,
+        "ACK",
+        "The acknowledgement number is significant",
+        "Il numero di riscontro è significativo",
+    ),
+    (
+        0x020,
+        "URG",
+        "The urgent pointer is significant",
+        "Il puntatore urgente è significativo",
+    ),
+    (
+        0x040,
+        "ECE",
+        "ECN capable, or congestion experienced",
+        "Supporto ECN, o congestione rilevata",
+    ),
+    (
+        0x080,
+        "CWR",
+        "Congestion window reduced",
+        "Finestra di congestione ridotta",
+    ),
+    (
+        0x100,
+        "NS",
+        "ECN nonce concealment protection",
+        "Protezione ECN nonce",
+    ),
+];
+
+const TLS_ALERT_LEVELS: &[Entry] = &[
+    (
+        1,
+        "Warning",
+        "Warning, the connection may continue",
+        "Avviso, la connessione può proseguire",
+    ),
+    (
+        2,
+        "Fatal",
+        "Fatal, the connection is terminated",
+        "Fatale, la connessione viene terminata",
+    ),
+];
+
+const TLS_ALERT_DESCRIPTIONS: &[Entry] = &[
+    (
+        0,
+        "CloseNotify",
+        "The sender will not send any more messages",
+        "Il mittente non invierà altri messaggi",
+    ),
+    (
+        10,
+        "UnexpectedMessage",
+        "An inappropriate message was received",
+        "È stato ricevuto un messaggio inatteso",
+    ),
+    (
+        20,
+        "BadRecordMac",
+        "A record could not be authenticated",
+        "Non è stato possibile autenticare un record",
+    ),
+    (
+        21,
+        "DecryptionFailed",
+        "A record could not be decrypted",
+        "Non è stato possibile decifrare un record",
+    ),
+    (
+        22,
+        "RecordOverflow",
+        "A record exceeded the maximum length",
+        "Un record ha superato la lunghezza massima",
+    ),
+    (
+        30,
+        "DecompressionFailure",
+        "A record could not be decompressed",
+        "Non è stato possibile decomprimere un record",
+    ),
+    (
+        40,
+        "HandshakeFailure",
+        "No acceptable set of security parameters could be negotiated",
+        "Non è stato possibile negoziare parametri di sicurezza accettabili",
+    ),
+    (
+        41,
+        "NoCertificate",
+        "No certificate was available",
+        "Nessun certificato disponibile",
+    ),
+    (
+        42,
+        "BadCertificate",
+        "A certificate was corrupt or could not be verified",
+        "Un certificato è corrotto o non è stato possibile verificarlo",
+    ),
+    (
+        43,
+        "UnsupportedCertificate",
+        "A certificate was of an unsupported type",
+        "Un certificato è di un tipo non supportato",
+    ),
+    (
+        44,
+        "CertificateRevoked",
+        "A certificate was revoked by its signer",
+        "Un certificato è stato revocato dal firmatario",
+    ),
+    (
+        45,
+        "CertificateExpired",
+        "A certificate has expired or is not yet valid",
+        "Un certificato è scaduto o non è ancora valido",
+    ),
+    (
+        46,
+        "CertificateUnknown",
+        "A certificate could not be accepted",
+        "Non è stato possibile accettare un certificato",
+    ),
+    (
+        47,
+        "IllegalParameter",
+        "A field of the handshake was out of range or inconsistent",
+        "Un campo dell'handshake è fuori intervallo o incoerente",
+    ),
+    (
+        48,
+        "UnknownCa",
+        "The certificate authority could not be found or trusted",
+        "L'autorità di certificazione è sconosciuta o non attendibile",
+    ),
+    (
+        49,
+        "AccessDenied",
+        "The sender refused the handshake by policy",
+        "Il mittente ha rifiutato l'handshake per policy",
+    ),
+    (
+        50,
+        "DecodeError",
+        "A message could not be decoded",
+        "Non è stato possibile decodificare un messaggio",
+    ),
+    (
+        51,
+        "DecryptError",
+        "A cryptographic operation of the handshake failed",
+        "Un'operazione crittografica dell'handshake è fallita",
+    ),
+    (
+        60,
+        "ExportRestriction",
+        "An export-restricted key exchange was attempted",
+        "È stato tentato uno scambio di chiavi soggetto a restrizioni di esportazione",
+    ),
+    (
+        70,
+        "ProtocolVersion",
+        "The protocol version is not supported",
+        "La versione del protocollo non è supportata",
+    ),
+    (
+        71,
+        "InsufficientSecurity",
+        "The server requires stronger ciphers than the ones offered",
+        "Il server richiede cifrari più robusti di quelli offerti",
+    ),
+    (
+        80,
+        "InternalError",
+        "An internal error of the sender",
+        "Errore interno del mittente",
+    ),
+    (
+        86,
+        "InappropriateFallback",
+        "A fallback to a lower protocol version was refused",
+        "È stato rifiutato il ritorno a una versione inferiore del protocollo",
+    ),
+    (
+        90,
+        "UserCancelled",
+        "The handshake was cancelled by the user",
+        "L'handshake è stato annullato dall'utente",
+    ),
+    (
+        100,
+        "NoRenegotiation",
+        "The renegotiation was refused",
+        "La rinegoziazione è stata rifiutata",
+    ),
+    (
+        109,
+        "MissingExtension",
+        "A mandatory extension was not sent",
+        "Non è stata inviata un'estensione obbligatoria",
+    ),
+    (
+        110,
+        "UnsupportedExtension",
+        "An extension was not expected in the message",
+        "Un'estensione non era prevista nel messaggio",
+    ),
+    (
+        111,
+        "CertificateUnobtainable",
+        "A certificate could not be obtained from its URL",
+        "Non è stato possibile ottenere un certificato dal suo URL",
+    ),
+    (
+        112,
+        "UnrecognizedName",
+        "No server is known by the requested name",
+        "Nessun server è noto con il nome richiesto",
+    ),
+    (
+        113,
+        "BadCertificateStatusResponse",
+        "An invalid certificate status response was received",
+        "È stata ricevuta una risposta di stato del certificato non valida",
+    ),
+    (
+        114,
+        "BadCertificateHashValue",
+        "A certificate hash did not match",
+        "L'hash di un certificato non corrisponde",
+    ),
+    (
+        115,
+        "UnknownPskIdentity",
+        "No acceptable pre-shared key identity was offered",
+        "Non è stata offerta un'identità PSK accettabile",
+    ),
+    (
+        116,
+        "CertificateRequired",
+        "A certificate was required but not sent",
+        "Era richiesto un certificato, ma non è stato inviato",
+    ),
+    (
+        120,
+        "NoApplicationProtocol",
+        "None of the application protocols offered is supported",
+        "Nessuno dei protocolli applicativi offerti è supportato",
+    ),
+];
+
+/// Get the name of a value of a field, if known
+pub fn name(field: Field, value: u16) -> Option<&'static str> {
+    find(field, value).map(|entry| entry.1)
+}
+
+/// Get the description of a value of a field in a locale, if known
+pub fn description(field: Field, value: u16, locale: Locale) -> Option<&'static str> {
+    find(field, value).map(|entry| localized(entry, locale))
+}
+
+/// Get the descriptions of the values of all the fields in a locale
+pub fn get_descriptions(locale: Locale) -> Vec<FieldDescriptions> {
+    Field::ALL
+        .iter()
+        .map(|field| FieldDescriptions {
+            field: *field,
+            descriptions: field
+                .entries()
+                .iter()
+                .map(|entry| Description {
+                    value: entry.0,
+                    name: entry.1,
+                    description: localized(entry, locale),
+                })
+                .collect(),
+        })
+        .collect()
+}
+
+fn find(field: Field, value: u16) -> Option<&'static Entry> {
+    field.entries().iter().find(|entry| entry.0 == value)
+}
+
+fn localized(entry: &Entry, locale: Locale) -> &'static str {
+    match locale {
+        Locale::En => entry.2,
+        Locale::It => entry.3,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::{description, get_descriptions, name, Field, Locale};
+
+    #[test]
+    fn described_values() {
+        assert_eq!(name(Field::IcmpType, 8), Some("EchoRequest"));
+        assert_eq!(name(Field::Icmpv6Type, 135), Some("NeighborSolicit"));
+        assert_eq!(name(Field::IcmpType, 99), None);
+        assert_eq!(
+            description(Field::TlsAlertDescription, 112, Locale::En),
+            Some("No server is known by the requested name")
+        );
+        assert_eq!(
+            description(Field::TcpFlag, 0x004, Locale::It),
+            Some("Reimposta la connessione")
+        );
+    }
+
+    #[test]
+    fn complete_catalogs() {
+        for locale in [Locale::En, Locale::It] {
+            let catalogs = get_descriptions(locale);
+            assert_eq!(catalogs.len(), Field::ALL.len());
+
+            for catalog in catalogs {
+                let values: HashSet<u16> = catalog.descriptions.iter().map(|d| d.value).collect();
+                assert_eq!(values.len(), catalog.descriptions.len());
+                assert!(catalog
+                    .descriptions
+                    .iter()
+                    .all(|d| !d.name.is_empty() && !d.description.is_empty()));
+            }
+        }
+    }
+}
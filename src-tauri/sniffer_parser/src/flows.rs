@@ -0,0 +1,785 @@
+//! Expiration of the state kept for each flow
+//!
+//! The parsers of the application layer keep the state of each flow (e.g. the segments being
+//! reassembled, the requests waiting for their responses) until the sniffing state is cleaned up.
+//! The flow tracker records when each flow was last seen, and drops the state of the flows idle
+//! for longer than the timeout of their protocol:
+//! - TCP established: connections without a FIN or RST
+//! - TCP closed: connections after a FIN or RST, whose last segments may still be in flight
+//! - UDP
+//! - ICMP
+//!
+//! Longer timeouts keep parsing flows with long pauses correctly, at the cost of more memory.
+//!
+//! The bytes buffered by the parsers are bounded as well, so that sniffing can run for days:
+//! - a message buffered for a single direction of a flow beyond the flow limit is dropped
+//! - once the bytes buffered by a parser context exceed the total limit, the state of its least
+//!   recently seen flows is dropped until they are back under it
+//!
+//! The flows dropped for each reason are counted.
+//!
+//! The flows tracked by a parser context are bounded too, so that scans and floods of new flows
+//! degrade the parsing predictably: beyond the maximum, the packets of new flows are parsed up to
+//! the transport layer only, without keeping any state for them, until the tracked flows expire.
+//! A [`CapacityWarning`] is raised the first time the maximum is exceeded.
+//!
+//! A flow is identified by a [`FlowKey`], its endpoints in ascending order, so that both the
+//! directions of a connection share the same flow; the state kept for each direction is keyed by
+//! the flow key and the [`FlowDirection`].
+//!
+//! The non-zero IPv6 flow labels of the tracked flows are recorded as well, so that the packets
+//! whose ports cannot be read (e.g. the fragments after the first one) are still grouped with the
+//! other packets of their flow.
+
+use std::{
+    collections::{HashMap, HashSet},
+    net::IpAddr,
+    sync::{Mutex, RwLock},
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::{
+    health::publish_buffers, references::drop_links, serializable_packet::network::LabelledFlow,
+    Flow, ParserContext,
+};
+
+/// Minimum time between two sweeps of the expired flows
+pub(crate) const SWEEP_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Timeouts used to expire the flows, chosen by the user
+///
+/// Shared by all the threads, since the timeouts are set while packets are being parsed.
+static FLOW_TIMEOUTS: RwLock<FlowTimeouts> = RwLock::new(FlowTimeouts::DEFAULT);
+
+/// Limits of the bytes buffered by the parsers, chosen by the user
+static BUFFER_LIMITS: RwLock<BufferLimits> = RwLock::new(BufferLimits::DEFAULT);
+
+/// Maximum flows tracked by each parser context, chosen by the user
+static MAX_FLOWS: RwLock<usize> = RwLock::new(DEFAULT_MAX_FLOWS);
+
+/// Flows dropped so far by all the threads
+static FLOW_EVICTIONS: Mutex<FlowEvictions> = Mutex::new(FlowEvictions {
+    expired: 0,
+    oversized: 0,
+    evicted: 0,
+    untracked: 0,
+});
+
+/// Warning raised by a parser context exceeding the maximum flows, not taken yet
+static CAPACITY_WARNING: Mutex<Option<CapacityWarning>> = Mutex::new(None);
+
+/// Flows tracked by each parser context by default
+pub const DEFAULT_MAX_FLOWS: usize = 100_000;
+
+/// Idle time after which the flows of each protocol expire, in seconds
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct FlowTimeouts {
+    pub tcp_established: u64,
+    pub tcp_closed: u64,
+    pub udp: u64,
+    pub icmp: u64,
+}
+
+impl FlowTimeouts {
+    pub const DEFAULT: FlowTimeouts = FlowTimeouts {
+        tcp_established: 3600,
+        tcp_closed: 120,
+        udp: 60,
+        icmp: 30,
+    };
+
+    fn timeout(&self, kind: FlowKind) -> Duration {
+        let seconds = match kind {
+            FlowKind::TcpEstablished => self.tcp_established,
+            FlowKind::TcpClosed => self.tcp_closed,
+            FlowKind::Udp => self.udp,
+            FlowKind::Icmp => self.icmp,
+        };
+
+        Duration::from_secs(seconds)
+    }
+}
+
+impl Default for FlowTimeouts {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// Bytes the parsers can buffer before dropping the state of the flows
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct BufferLimits {
+    /// Bytes buffered for a single direction of a flow (HTTP and TLS messages)
+    pub flow_bytes: usize,
+    /// Bytes buffered for all the flows of a parser context (HTTP, TLS, HTTP/2 and QUIC)
+    pub total_bytes: usize,
+}
+
+impl BufferLimits {
+    pub const DEFAULT: BufferLimits = BufferLimits {
+        flow_bytes: 16 << 20,
+        total_bytes: 256 << 20,
+    };
+}
+
+impl Default for BufferLimits {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// Flows whose state was dropped, for each reason
+#[derive(Serialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct FlowEvictions {
+    /// Flows idle for longer than their timeout
+    pub expired: u64,
+    /// Messages exceeding the flow limit
+    pub oversized: u64,
+    /// Least recently seen flows, dropped when exceeding the total limit
+    pub evicted: u64,
+    /// Packets of new flows exceeding the maximum flows, parsed up to the transport layer only
+    pub untracked: u64,
+}
+
+/// Maximum flows exceeded by a parser context: the application layer of its new flows is not
+/// parsed until the tracked ones expire
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct CapacityWarning {
+    pub max_flows: usize,
+    /// Capture time of the first packet not tracked
+    pub capture_time: Duration,
+}
+
+/// Protocol and state of a flow, determining its timeout
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum FlowKind {
+    TcpEstablished,
+    TcpClosed,
+    Udp,
+    Icmp,
+}
+
+/// Endpoints of a flow in ascending order, the same for both its directions
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct FlowKey {
+    lower: (IpAddr, u16),
+    upper: (IpAddr, u16),
+}
+
+/// Direction of a packet in its flow
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FlowDirection {
+    /// Sent by the lower endpoint of the flow key
+    Forward,
+    /// Sent by the upper endpoint of the flow key
+    Reverse,
+}
+
+impl FlowKey {
+    /// Get the key of the flow between two endpoints, in any order
+    pub fn new(source: (IpAddr, u16), destination: (IpAddr, u16)) -> Self {
+        FlowKey::of(source, destination).0
+    }
+
+    /// Get the key of the flow of a packet, and the direction it is sent in
+    pub fn of(source: (IpAddr, u16), destination: (IpAddr, u16)) -> (Self, FlowDirection) {
+        if source <= destination {
+            let key = FlowKey {
+                lower: source,
+                upper: destination,
+            };
+            (key, FlowDirection::Forward)
+        } else {
+            let key = FlowKey {
+                lower: destination,
+                upper: source,
+            };
+            (key, FlowDirection::Reverse)
+        }
+    }
+
+    /// Get the endpoints of the flow, in ascending order
+    pub fn endpoints(&self) -> ((IpAddr, u16), (IpAddr, u16)) {
+        (self.lower, self.upper)
+    }
+}
+
+impl FlowDirection {
+    pub fn reverse(self) -> Self {
+        match self {
+            FlowDirection::Forward => FlowDirection::Reverse,
+            FlowDirection::Reverse => FlowDirection::Forward,
+        }
+    }
+}
+
+/// Outcome of recording a packet in the flow tracker
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Tracking {
+    Tracked,
+    /// New flow exceeding the maximum flows
+    Untracked,
+    /// First new flow exceeding the maximum flows since the tracked ones were back under it
+    Saturated,
+}
+
+/// Flows seen by a parser context
+#[derive(Debug, Default)]
+pub(crate) struct FlowTracker {
+    flows: HashMap<FlowKey, (FlowKind, Duration)>,
+    last_sweep: Duration,
+    /// Whether a new flow exceeded the maximum flows since the last sweep under it
+    saturated: bool,
+    /// Transport flows of the IPv6 flow labels, by source, destination and label
+    labels: HashMap<(IpAddr, IpAddr, u32), LabelledFlow>,
+}
+
+impl FlowTracker {
+    pub(crate) fn len(&self) -> usize {
+        self.flows.len()
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.flows.is_empty()
+    }
+
+    /// Record a packet of a flow, unless it starts a new flow exceeding the maximum flows
+    fn track(
+        &mut self,
+        kind: FlowKind,
+        flow: FlowKey,
+        now: Duration,
+        max_flows: usize,
+    ) -> Tracking {
+        if let Some((current, last_seen)) = self.flows.get_mut(&flow) {
+            if *current != FlowKind::TcpClosed {
+                *current = kind;
+            }
+            *last_seen = now;
+            return Tracking::Tracked;
+        }
+
+        if self.flows.len() < max_flows {
+            self.flows.insert(flow, (kind, now));
+            Tracking::Tracked
+        } else if self.saturated {
+            Tracking::Untracked
+        } else {
+            self.saturated = true;
+            Tracking::Saturated
+        }
+    }
+
+    /// Forget the flows idle for longer than their timeout, returning them
+    fn expire(
+        &mut self,
+        now: Duration,
+        timeouts: &FlowTimeouts,
+        max_flows: usize,
+    ) -> HashSet<FlowKey> {
+        let mut expired = HashSet::new();
+        self.flows.retain(|flow, (kind, last_seen)| {
+            let alive = now.saturating_sub(*last_seen) <= timeouts.timeout(*kind);
+            if !alive {
+                expired.insert(*flow);
+            }
+
+            alive
+        });
+        if self.flows.len() < max_flows {
+            self.saturated = false;
+        }
+        let flows = &self.flows;
+        self.labels.retain(|(source, destination, _), flow| {
+            flows.contains_key(&flow.key(*source, *destination))
+        });
+
+        expired
+    }
+}
+
+impl LabelledFlow {
+    fn key(&self, source: IpAddr, destination: IpAddr) -> FlowKey {
+        FlowKey::new(
+            (source, self.source_port),
+            (destination, self.destination_port),
+        )
+    }
+}
+
+/// Replace the timeouts used to expire the flows
+pub fn set_flow_timeouts(timeouts: FlowTimeouts) {
+    *FLOW_TIMEOUTS.write().unwrap() = timeouts;
+}
+
+/// Get the timeouts used to expire the flows
+pub fn get_flow_timeouts() -> FlowTimeouts {
+    *FLOW_TIMEOUTS.read().unwrap()
+}
+
+/// Replace the limits of the bytes buffered by the parsers
+pub fn set_buffer_limits(limits: BufferLimits) {
+    *BUFFER_LIMITS.write().unwrap() = limits;
+}
+
+/// Get the limits of the bytes buffered by the parsers
+pub fn get_buffer_limits() -> BufferLimits {
+    *BUFFER_LIMITS.read().unwrap()
+}
+
+/// Get the number of flows dropped so far, for each reason
+pub fn get_flow_evictions() -> FlowEvictions {
+    *FLOW_EVICTIONS.lock().unwrap()
+}
+
+/// Replace the maximum flows tracked by each parser context
+pub fn set_max_flows(max_flows: usize) {
+    *MAX_FLOWS.write().unwrap() = max_flows;
+}
+
+/// Get the maximum flows tracked by each parser context
+pub fn get_max_flows() -> usize {
+    *MAX_FLOWS.read().unwrap()
+}
+
+/// Take the warning raised by the last parser context exceeding the maximum flows, if any
+pub fn take_capacity_warning() -> Option<CapacityWarning> {
+    CAPACITY_WARNING.lock().unwrap().take()
+}
+
+/// Check if a message buffered for a direction of a flow exceeds the flow limit, counting it as
+/// oversized if so: its buffer must then be dropped
+pub(crate) fn exceeds_flow_limit(buffered_bytes: usize) -> bool {
+    let exceeds = buffered_bytes > get_buffer_limits().flow_bytes;
+    if exceeds {
+        FLOW_EVICTIONS.lock().unwrap().oversized += 1;
+    }
+
+    exceeds
+}
+
+/// Record a packet of a flow seen at the given time, dropping the state of the expired flows and
+/// of the least recently seen ones exceeding the buffer limit, then publishing the size of the
+/// buffers left
+///
+/// Once closed, a TCP connection stays closed until it expires.
+///
+/// Returns whether the flow is tracked: the packets of the new flows exceeding the maximum flows
+/// must be parsed up to the transport layer only, without keeping any state for them.
+pub(crate) fn track_flow(
+    state: &mut ParserContext,
+    kind: FlowKind,
+    source: (IpAddr, u16),
+    destination: (IpAddr, u16),
+    now: Duration,
+) -> bool {
+    let max_flows = get_max_flows();
+    let tracking = state
+        .flows
+        .track(kind, FlowKey::new(source, destination), now, max_flows);
+
+    let swept = now.saturating_sub(state.flows.last_sweep) >= SWEEP_INTERVAL;
+    let (expired, evicted) = if swept {
+        state.flows.last_sweep = now;
+
+        let expired = state.flows.expire(now, &get_flow_timeouts(), max_flows);
+
+        let buffers: HashMap<FlowKey, usize> = buffered_bytes(state)
+            .into_iter()
+            .filter(|(flow, _)| !expired.contains(flow))
+            .collect();
+        let evicted =
+            least_recently_seen(&state.flows.flows, buffers, get_buffer_limits().total_bytes);
+
+        (expired, evicted)
+    } else {
+        (HashSet::new(), HashSet::new())
+    };
+
+    if tracking == Tracking::Saturated {
+        warn!(
+            "Maximum flows exceeded ({}): new flows parsed up to the transport layer",
+            max_flows
+        );
+        *CAPACITY_WARNING.lock().unwrap() = Some(CapacityWarning {
+            max_flows,
+            capture_time: now,
+        });
+    }
+    if tracking != Tracking::Tracked {
+        FLOW_EVICTIONS.lock().unwrap().untracked += 1;
+    }
+    if !expired.is_empty() || !evicted.is_empty() {
+        let mut evictions = FLOW_EVICTIONS.lock().unwrap();
+        evictions.expired += expired.len() as u64;
+        evictions.evicted += evicted.len() as u64;
+        drop(evictions);
+
+        drop_flows_state(state, &expired.union(&evicted).copied().collect());
+    }
+    if swept {
+        publish_buffers(state);
+    }
+
+    tracking == Tracking::Tracked
+}
+
+/// Record the transport flow of the IPv6 packets a source sends to a destination with a flow
+/// label, if the flow is tracked
+pub(crate) fn learn_flow_label(
+    state: &mut ParserContext,
+    source: IpAddr,
+    destination: IpAddr,
+    label: u32,
+    flow: LabelledFlow,
+) {
+    let tracker = &mut state.flows;
+    if tracker.flows.contains_key(&flow.key(source, destination)) {
+        tracker.labels.insert((source, destination, label), flow);
+    }
+}
+
+/// Get the transport flow of the IPv6 packets a source sends to a destination with a flow label,
+/// if learned from the earlier packets of the flow
+pub(crate) fn labelled_flow(
+    state: &ParserContext,
+    source: IpAddr,
+    destination: IpAddr,
+    label: u32,
+) -> Option<LabelledFlow> {
+    state
+        .flows
+        .labels
+        .get(&(source, destination, label))
+        .copied()
+}
+
+/// Delete the state kept by the parsers for both the directions of a connection
+pub(crate) fn drop_flow_state(
+    state: &mut ParserContext,
+    source: (IpAddr, u16),
+    destination: (IpAddr, u16),
+) {
+    drop_flows_state(state, &HashSet::from([FlowKey::new(source, destination)]));
+}
+
+/// Delete the state kept by the parsers for both the directions of the given connections
+fn drop_flows_state(state: &mut ParserContext, expired: &HashSet<FlowKey>) {
+    let is_alive = |flow: &Flow| !expired.contains(&FlowKey::new(flow.0, flow.1));
+
+    state
+        .tcp_streams
+        .retain(|(key, _), _| !expired.contains(key));
+    state
+        .tcp_directions
+        .retain(|(key, _), _| !expired.contains(key));
+    drop_links(state, is_alive);
+    state.http_parsers.retain(|key, _| !expired.contains(key));
+    state
+        .tls_parsers
+        .retain(|(key, _), _| !expired.contains(key));
+    state
+        .http2_connections
+        .retain(|(key, _), _| !expired.contains(key));
+    state
+        .quic_connections
+        .retain(|key, _| !expired.contains(key));
+    state
+        .tls_record_indexes
+        .retain(|(key, _), _| !expired.contains(key));
+    state
+        .detected_protocols
+        .retain(|(key, _), _| !expired.contains(key));
+    state
+        .kafka_requests
+        .retain(|(source, destination, _), _| is_alive(&(*source, *destination)));
+    state
+        .zookeeper_requests
+        .retain(|(source, destination, _), _| is_alive(&(*source, *destination)));
+}
+
+/// Get the bytes buffered by the parsers of a context for each connection
+fn buffered_bytes(state: &ParserContext) -> HashMap<FlowKey, usize> {
+    let mut buffers = HashMap::new();
+    let mut add = |key: &FlowKey, bytes: usize| {
+        *buffers.entry(*key).or_default() += bytes;
+    };
+
+    for ((key, _), buffer) in state.tls_parsers.iter() {
+        add(key, buffer.len());
+    }
+    for ((key, _), direction) in state.http2_connections.iter() {
+        add(key, direction.buffered_bytes());
+    }
+    for (key, quic_connection) in state.quic_connections.iter() {
+        add(key, quic_connection.buffered_bytes());
+    }
+    for (key, connection) in state.http_parsers.iter() {
+        add(key, connection.buffered_bytes());
+    }
+
+    buffers
+}
+
+/// Choose the least recently seen connections to drop, so that the bytes buffered for the others
+/// do not exceed the limit
+///
+/// Connections never seen by the tracker are the first ones to be dropped.
+fn least_recently_seen(
+    flows: &HashMap<FlowKey, (FlowKind, Duration)>,
+    buffers: HashMap<FlowKey, usize>,
+    limit: usize,
+) -> HashSet<FlowKey> {
+    let mut total: usize = buffers.values().sum();
+    if total <= limit {
+        return HashSet::new();
+    }
+
+    let mut buffers: Vec<(FlowKey, usize)> = buffers.into_iter().collect();
+    buffers.sort_by_key(|(flow, _)| (flows.get(flow).map(|(_, last_seen)| *last_seen), *flow));
+
+    let mut evicted = HashSet::new();
+    for (flow, bytes) in buffers {
+        if total <= limit {
+            break;
+        }
+        total -= bytes;
+        evicted.insert(flow);
+    }
+
+    evicted
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr};
+    use std::time::Duration;
+
+    use std::collections::{HashMap, HashSet};
+
+    use super::{
+        exceeds_flow_limit, get_buffer_limits, get_flow_evictions, least_recently_seen,
+        set_buffer_limits, set_flow_timeouts, track_flow, BufferLimits, FlowDirection, FlowKey,
+        FlowKind, FlowTimeouts, FlowTracker, Tracking,
+    };
+    use crate::ParserContext;
+
+    #[test]
+    fn flows_expire_by_protocol() {
+        let mut state = ParserContext::new();
+
+        set_flow_timeouts(FlowTimeouts {
+            tcp_established: 100,
+            tcp_closed: 10,
+            udp: 30,
+            icmp: 5,
+        });
+
+        let (client, server) = (endpoint(1, 50000), endpoint(2, 80));
+        let (closing_client, closing_server) = (endpoint(3, 50001), endpoint(2, 80));
+        track_flow(&mut state, FlowKind::TcpEstablished, client, server, at(0));
+        track_flow(
+            &mut state,
+            FlowKind::TcpEstablished,
+            closing_client,
+            closing_server,
+            at(0),
+        );
+        track_flow(
+            &mut state,
+            FlowKind::TcpClosed,
+            closing_server,
+            closing_client,
+            at(1),
+        );
+        add_http_parser(&mut state, client, server);
+        add_http_parser(&mut state, server, client);
+        add_http_parser(&mut state, closing_client, closing_server);
+
+        // The closed connection expires first, in both directions
+        track_flow(
+            &mut state,
+            FlowKind::Udp,
+            endpoint(4, 53),
+            endpoint(5, 53),
+            at(20),
+        );
+        assert_eq!(state.http_parsers.len(), 1);
+
+        // Segments following a FIN do not reopen the connection
+        track_flow(&mut state, FlowKind::TcpClosed, client, server, at(21));
+        track_flow(&mut state, FlowKind::TcpEstablished, server, client, at(22));
+        track_flow(
+            &mut state,
+            FlowKind::Icmp,
+            endpoint(4, 0),
+            endpoint(5, 0),
+            at(40),
+        );
+        assert_eq!(state.http_parsers.len(), 0);
+
+        set_flow_timeouts(FlowTimeouts::default());
+    }
+
+    #[test]
+    fn flow_keys() {
+        let (client, server) = (endpoint(2, 50000), endpoint(1, 80));
+
+        assert_eq!(FlowKey::new(client, server), FlowKey::new(server, client));
+        assert_eq!(FlowKey::new(client, server).endpoints(), (server, client));
+        assert_eq!(
+            FlowKey::of(client, server),
+            (FlowKey::new(client, server), FlowDirection::Reverse)
+        );
+        assert_eq!(FlowKey::of(server, client).1, FlowDirection::Forward);
+        assert_eq!(FlowDirection::Forward.reverse(), FlowDirection::Reverse);
+    }
+
+    #[test]
+    fn least_recently_seen_flows_evicted() {
+        let (old, recent, unknown) = (
+            FlowKey::new(endpoint(1, 50000), endpoint(2, 80)),
+            FlowKey::new(endpoint(2, 80), endpoint(1, 50001)),
+            FlowKey::new(endpoint(1, 50002), endpoint(2, 80)),
+        );
+        let flows = HashMap::from([
+            (old, (FlowKind::TcpEstablished, at(0))),
+            (recent, (FlowKind::TcpEstablished, at(10))),
+        ]);
+        let buffers = HashMap::from([(old, 600), (recent, 500), (unknown, 100)]);
+
+        assert!(least_recently_seen(&flows, buffers.clone(), 1200).is_empty());
+        assert_eq!(
+            least_recently_seen(&flows, buffers.clone(), 1100),
+            HashSet::from([unknown])
+        );
+        assert_eq!(
+            least_recently_seen(&flows, buffers.clone(), 1000),
+            HashSet::from([unknown, old])
+        );
+        assert_eq!(
+            least_recently_seen(&flows, buffers, 0),
+            HashSet::from([unknown, old, recent])
+        );
+    }
+
+    #[test]
+    fn buffers_exceeding_limits_evicted() {
+        let mut state = ParserContext::new();
+
+        set_buffer_limits(BufferLimits {
+            flow_bytes: 1000,
+            total_bytes: 1500,
+        });
+        let evictions = get_flow_evictions();
+
+        assert!(!exceeds_flow_limit(1000));
+        assert!(exceeds_flow_limit(1001));
+
+        let (old, recent) = (
+            (endpoint(1, 50000), endpoint(2, 80)),
+            (endpoint(1, 50001), endpoint(2, 80)),
+        );
+        track_flow(&mut state, FlowKind::TcpEstablished, old.0, old.1, at(0));
+        track_flow(
+            &mut state,
+            FlowKind::TcpEstablished,
+            recent.0,
+            recent.1,
+            at(0),
+        );
+        add_http_buffer(&mut state, old.0, old.1, 1000);
+        add_http_buffer(&mut state, recent.0, recent.1, 1000);
+
+        track_flow(
+            &mut state,
+            FlowKind::TcpEstablished,
+            recent.1,
+            recent.0,
+            at(2),
+        );
+        assert_eq!(state.http_parsers.len(), 1);
+        let recent = FlowKey::new(recent.0, recent.1);
+        assert!(state.http_parsers.contains_key(&recent));
+
+        // Counters are shared with the other threads, which may drop flows in the meantime
+        let current = get_flow_evictions();
+        assert!(current.oversized > evictions.oversized);
+        assert!(current.evicted > evictions.evicted);
+
+        set_buffer_limits(BufferLimits::default());
+        assert_eq!(get_buffer_limits(), BufferLimits::DEFAULT);
+    }
+
+    #[test]
+    fn new_flows_exceeding_maximum_untracked() {
+        let mut tracker = FlowTracker::default();
+        let flow = |port| FlowKey::new(endpoint(1, port), endpoint(2, 80));
+        let track = |tracker: &mut FlowTracker, port, time| {
+            tracker.track(FlowKind::TcpEstablished, flow(port), at(time), 2)
+        };
+
+        assert_eq!(track(&mut tracker, 50000, 0), Tracking::Tracked);
+        assert_eq!(track(&mut tracker, 50001, 50), Tracking::Tracked);
+        assert_eq!(track(&mut tracker, 50002, 60), Tracking::Saturated);
+        assert_eq!(track(&mut tracker, 50003, 60), Tracking::Untracked);
+        // Flows already tracked are still updated
+        assert_eq!(track(&mut tracker, 50000, 70), Tracking::Tracked);
+        assert_eq!(tracker.flows.len(), 2);
+
+        let timeouts = FlowTimeouts {
+            tcp_established: 100,
+            ..FlowTimeouts::DEFAULT
+        };
+        assert!(tracker.expire(at(100), &timeouts, 2).is_empty());
+        assert_eq!(track(&mut tracker, 50002, 100), Tracking::Untracked);
+
+        // New flows are tracked again once the others expire, warning again when exceeding
+        assert_eq!(
+            tracker.expire(at(160), &timeouts, 2),
+            HashSet::from([flow(50001)])
+        );
+        assert_eq!(track(&mut tracker, 50002, 160), Tracking::Tracked);
+        assert_eq!(track(&mut tracker, 50003, 160), Tracking::Saturated);
+    }
+
+    ///////////////////// Utils
+
+    fn endpoint(host: u8, port: u16) -> (IpAddr, u16) {
+        (IpAddr::V4(Ipv4Addr::new(10, 0, 0, host)), port)
+    }
+
+    fn at(seconds: u64) -> Duration {
+        Duration::from_secs(1_700_000_000 + seconds)
+    }
+
+    fn add_http_parser(
+        state: &mut ParserContext,
+        source: (IpAddr, u16),
+        destination: (IpAddr, u16),
+    ) {
+        add_http_buffer(state, source, destination, 0);
+    }
+
+    fn add_http_buffer(
+        state: &mut ParserContext,
+        source: (IpAddr, u16),
+        destination: (IpAddr, u16),
+        bytes: usize,
+    ) {
+        let (key, direction) = FlowKey::of(source, destination);
+        let buffer = state
+            .http_parsers
+            .entry(key)
+            .or_default()
+            .buffer_mut(direction);
+        buffer.extend(vec![0; bytes]);
+    }
+}
@@ -1,26 +1,58 @@
 //! Packet Parsing library from Ethernet frame to Application-layer representation
 //!
 //! This library parses an Ethernet frame extracting all fields and data from it
-//! and represents the parsed packet data at the different levels of the TCP/IP stack
+//! (802.11 frames of monitor-mode captures are converted to Ethernet ones, decrypting them if needed,
+//! and the IP packets of PPP and SLIP frames of serial captures, as well as the ones of Linux cooked,
+//! loopback and raw IP captures, are dissected the same way)
+//! and represents the parsed packet data at the different levels of the TCP/IP stack.
+//! Frames of any supported link type are dispatched to their decoder by [`parse_frame`].
+//! The state kept across frames belongs to a [`ParserContext`] owned by the caller, or by default
+//! to the parsing thread.
 
 mod application;
+mod context;
+mod cooked;
+mod flows;
+mod link;
+mod mptcp;
+mod multicast;
 mod network;
+mod ppp;
+mod reassembly;
+mod references;
+mod tcp_analysis;
 mod transport;
+mod wifi;
 
+use crate::application::ethercat::handle_ethercat_packet;
+use crate::application::iec61850::{handle_goose_packet, handle_sv_packet};
+use crate::application::profinet::handle_profinet_packet;
+use crate::application::ptp::handle_ptp_packet;
 pub use crate::application::*;
+use crate::context::with_thread_context;
+pub use crate::context::*;
+pub use crate::cooked::*;
+pub use crate::flows::*;
+pub use crate::link::*;
 pub use crate::network::*;
+pub use crate::ppp::*;
 use crate::serializable_packet::SerializableUnknownPacket;
 pub use crate::transport::*;
+pub use crate::wifi::*;
 
+pub mod descriptions;
+pub mod oui;
 pub mod serializable_packet;
 
-use log::debug;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
 use pnet::packet::ethernet::{EtherTypes, EthernetPacket};
 use pnet::packet::Packet;
 use pnet::util::MacAddr;
-use serializable_packet::ParsedPacket;
 use serializable_packet::SerializableEthernetPacket;
 use serializable_packet::SerializablePacket;
+use serializable_packet::{PacketMeta, ParsedPacket};
+use tracing::debug;
 
 /// Ethernet Header Length
 #[allow(non_snake_case)]
@@ -28,29 +60,81 @@ pub mod HeaderLength {
     pub const ETHERNET: usize = 14;
 }
 
-/// Delete active parsers
+/// EtherTypes of protocols carried directly over Ethernet, not defined by pnet
+#[allow(non_snake_case)]
+pub mod CustomEtherTypes {
+    use pnet::packet::ethernet::EtherType;
+
+    pub const PTP: EtherType = EtherType(0x88f7);
+    pub const GOOSE: EtherType = EtherType(0x88b8);
+    pub const SV: EtherType = EtherType(0x88ba);
+    pub const PROFINET: EtherType = EtherType(0x8892);
+    pub const ETHERCAT: EtherType = EtherType(0x88a4);
+}
+
+/// Parse packets captured at the given time (since the UNIX epoch), as the ones of a capture file,
+/// with the default parser context of the current thread
+///
+/// Without it, packets are considered captured while parsing them. Frames parsed with a context of
+/// their own are given their capture time with their metadata.
+pub fn at_capture_time<T>(capture_time: Duration, parse: impl FnOnce() -> T) -> T {
+    let previous = with_thread_context(|state| state.capture_time.replace(capture_time));
+    let parsed = parse();
+    with_thread_context(|state| state.capture_time = previous);
+
+    parsed
+}
+
+/// Get the current time, since the UNIX epoch
+pub(crate) fn now() -> Duration {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+}
+
+/// Delete active parsers, reassembled streams, tracked flows and packets to reference, of the
+/// default parser context of the current thread
 pub fn cleanup_sniffing_state() {
-    ACTIVE_HTTP_PARSERS.with(|parsers| parsers.borrow_mut().clear());
-    ACTIVE_TLS_PARSERS.with(|parsers| parsers.borrow_mut().clear());
+    with_thread_context(ParserContext::clear);
 }
 
 /// Parse ethernet frame obtaining the packet link-layer and network-layer representations
+///
+/// Kept for compatibility: frames go through [`parse_frame`], with the metadata of their capture.
 pub fn parse_ethernet_frame(ethernet: &EthernetPacket, id: usize) -> ParsedPacket {
-    let mut parsed_packet = ParsedPacket::new(id);
+    parse_frame(LinkType::Ethernet, ethernet.packet(), FrameMeta::new(id))
+}
+
+/// Dissect an Ethernet frame, given the metadata of its capture
+pub(crate) fn dissect_ethernet_frame(
+    state: &mut ParserContext,
+    ethernet: &EthernetPacket,
+    meta: PacketMeta,
+) -> ParsedPacket {
+    let mut parsed_packet = ParsedPacket::with_meta(meta);
 
     parsed_packet.set_link_layer_packet(Some(SerializablePacket::EthernetPacket(
-        SerializableEthernetPacket::from(ethernet),
+        SerializableEthernetPacket::new(ethernet, &parsed_packet.get_meta().frame),
     )));
 
     match ethernet.get_ethertype() {
-        EtherTypes::Ipv4 => handle_ipv4_packet(ethernet.payload(), &mut parsed_packet),
-        EtherTypes::Ipv6 => handle_ipv6_packet(ethernet.payload(), &mut parsed_packet),
+        EtherTypes::Ipv4 => handle_ipv4_packet(state, ethernet.payload(), &mut parsed_packet),
+        EtherTypes::Ipv6 => handle_ipv6_packet(state, ethernet.payload(), &mut parsed_packet),
         EtherTypes::Arp => handle_arp_packet(
             ethernet.payload(),
             ethernet.get_source(),
             ethernet.get_destination(),
             &mut parsed_packet,
         ),
+        CustomEtherTypes::PTP => handle_ptp_packet(state, ethernet.payload(), &mut parsed_packet),
+        CustomEtherTypes::GOOSE => handle_goose_packet(ethernet.payload(), &mut parsed_packet),
+        CustomEtherTypes::SV => handle_sv_packet(ethernet.payload(), &mut parsed_packet),
+        CustomEtherTypes::PROFINET => {
+            handle_profinet_packet(ethernet.payload(), &mut parsed_packet)
+        }
+        CustomEtherTypes::ETHERCAT => {
+            handle_ethercat_packet(ethernet.payload(), &mut parsed_packet)
+        }
         _ => {
             debug!(
                 "Unknown packet: {} > {}; ethertype: {:?} length: {}",
@@ -71,8 +155,12 @@ pub fn parse_ethernet_frame(ethernet: &EthernetPacket, id: usize) -> ParsedPacke
 
 #[cfg(test)]
 mod tests {
-    use crate::parse_ethernet_frame;
+    use std::fs;
+    use std::path::Path;
+    use std::time::Duration;
+
     use crate::serializable_packet::SerializablePacket;
+    use crate::{at_capture_time, cleanup_sniffing_state, parse_ethernet_frame};
     use pnet::packet::ethernet::EtherType;
     use pnet::packet::ethernet::{EtherTypes, EthernetPacket, MutableEthernetPacket};
     use pnet::packet::Packet;
@@ -104,6 +192,31 @@ mod tests {
         }
     }
 
+    #[test]
+    fn packets_stamped_with_capture_time() {
+        let mut ethernet_buffer = [0u8; 42];
+        let ethernet_packet = build_test_ethernet_packet(ethernet_buffer.as_mut_slice());
+
+        let parsed_packet = at_capture_time(Duration::new(1_700_000_000, 250_000), || {
+            parse_ethernet_frame(&ethernet_packet, 0)
+        });
+        assert_eq!(parsed_packet.get_timestamp(), 1_700_000_000_000_250);
+    }
+
+    #[test]
+    fn nested_capture_times() {
+        let mut ethernet_buffer = [0u8; 42];
+        let ethernet_packet = build_test_ethernet_packet(ethernet_buffer.as_mut_slice());
+
+        let parsed_packet = at_capture_time(Duration::new(1_700_000_000, 0), || {
+            at_capture_time(Duration::new(1_600_000_000, 0), || {
+                parse_ethernet_frame(&ethernet_packet, 0)
+            });
+            parse_ethernet_frame(&ethernet_packet, 1)
+        });
+        assert_eq!(parsed_packet.get_timestamp(), 1_700_000_000_000_000);
+    }
+
     #[test]
     fn unknown_ethernet_packet() {
         let mut ethernet_buffer = [0u8; 42];
@@ -127,6 +240,27 @@ mod tests {
         }
     }
 
+    #[test]
+    fn fuzz_corpus_replay() {
+        // Inputs saved by `cargo fuzz run wirefish-fuzz`, replayed in order as a single capture
+        let fuzz = Path::new(env!("CARGO_MANIFEST_DIR")).join("../fuzz");
+        let mut inputs: Vec<_> = ["corpus/wirefish-fuzz", "artifacts/wirefish-fuzz"]
+            .iter()
+            .filter_map(|directory| fs::read_dir(fuzz.join(directory)).ok())
+            .flatten()
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .collect();
+        inputs.sort();
+
+        for input in inputs {
+            let data = fs::read(&input).unwrap();
+            if let Some(ethernet_packet) = EthernetPacket::new(&data) {
+                parse_ethernet_frame(&ethernet_packet, 0);
+            }
+        }
+        cleanup_sniffing_state();
+    }
+
     ///////////////////// Utils
 
     fn build_test_ethernet_packet<'a>(ethernet_buffer: &'a mut [u8]) -> EthernetPacket<'a> {
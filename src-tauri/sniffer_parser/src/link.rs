@@ -0,0 +1,328 @@
+//! Dispatch of the captured frames to the decoder of their link type
+//!
+//! Capture sources deliver frames of different link-layer header types (the `LINKTYPE_` values of
+//! pcap): Ethernet for most interfaces, but also 802.11 for monitor-mode ones, PPP and SLIP for
+//! serial and VPN links, Linux cooked headers for the `any` pseudo-interface, the address family
+//! of the packets for the BSD loopback interfaces, or no header at all for raw IP links.
+//! [`parse_frame`] selects the decoder of a frame from its link type, so that callers (live
+//! captures, capture files, the fuzzer) do not assume Ethernet frames.
+//!
+//! The metadata of the capture of a frame (time, interface, direction, captured and wire lengths)
+//! is given to the packets of all its layers (see [`PacketMeta`]), so that the dissectors can rely
+//! on it, e.g. for timing-based analysis.
+//!
+//! Frames are copied once, into a buffer given to the decoders with the metadata of their packets
+//! and shared with the payloads of the packets (see [`FrameBytes`]).
+
+use std::time::Duration;
+
+use pnet::packet::ethernet::EthernetPacket;
+use tracing::debug;
+
+use crate::context::with_thread_context;
+use crate::serializable_packet::bytes::FrameBytes;
+use crate::serializable_packet::{Direction, PacketMeta, ParsedPacket, SerializablePacket};
+use crate::{
+    dissect_ethernet_frame, dissect_ieee80211_frame, dissect_linux_sll2_frame,
+    dissect_linux_sll_frame, dissect_loopback_frame, dissect_ppp_frame, dissect_radiotap_frame,
+    dissect_raw_ip_frame, dissect_slip_frame, now, ParserContext, Wpa2Decryptor,
+};
+
+/// Link-layer header type of the captured frames
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LinkType {
+    Ethernet,
+    Slip,
+    Ppp,
+    /// PPP in HDLC-like framing
+    PppHdlc,
+    Ieee80211,
+    /// 802.11 preceded by a Radiotap header
+    Ieee80211Radiotap,
+    /// BSD loopback, the address family in the byte order of the capturing host
+    Null,
+    /// OpenBSD loopback, the address family in network byte order
+    Loop,
+    /// IPv4 or IPv6 packets without a link-layer header
+    Raw,
+    Ipv4,
+    Ipv6,
+    /// Linux cooked capture (SLL)
+    LinuxSll,
+    /// Linux cooked capture v2 (SLL2)
+    LinuxSll2,
+}
+
+impl LinkType {
+    /// All the supported link types
+    pub const ALL: [LinkType; 13] = [
+        LinkType::Ethernet,
+        LinkType::Slip,
+        LinkType::Ppp,
+        LinkType::PppHdlc,
+        LinkType::Ieee80211,
+        LinkType::Ieee80211Radiotap,
+        LinkType::Null,
+        LinkType::Loop,
+        LinkType::Raw,
+        LinkType::Ipv4,
+        LinkType::Ipv6,
+        LinkType::LinuxSll,
+        LinkType::LinuxSll2,
+    ];
+
+    /// Get the link type of a pcap `LINKTYPE_` value, if supported
+    pub fn from_linktype(linktype: u32) -> Option<Self> {
+        LinkType::ALL
+            .iter()
+            .copied()
+            .find(|link_type| link_type.linktype() == linktype)
+    }
+
+    /// Get the pcap `LINKTYPE_` value of the link type
+    pub fn linktype(self) -> u32 {
+        match self {
+            LinkType::Ethernet => 1,
+            LinkType::Slip => 8,
+            LinkType::Ppp => 9,
+            LinkType::PppHdlc => 50,
+            LinkType::Ieee80211 => 105,
+            LinkType::Ieee80211Radiotap => 127,
+            LinkType::Null => 0,
+            LinkType::Loop => 108,
+            LinkType::Raw => 101,
+            LinkType::Ipv4 => 228,
+            LinkType::Ipv6 => 229,
+            LinkType::LinuxSll => 113,
+            LinkType::LinuxSll2 => 276,
+        }
+    }
+}
+
+/// Metadata of a captured frame
+#[derive(Default)]
+pub struct FrameMeta<'a> {
+    /// Identifier of the packet, its position in the capture
+    pub id: usize,
+    /// Capture time of the frame (since the UNIX epoch), when not captured while parsing it
+    pub capture_time: Option<Duration>,
+    /// Interface the frame was captured on
+    pub interface: Option<String>,
+    pub direction: Option<Direction>,
+    /// Bytes of the frame on the wire, when truncated by the capture
+    pub wire_length: Option<usize>,
+    /// Decryptor of the protected 802.11 frames
+    pub decryptor: Option<&'a mut Wpa2Decryptor>,
+}
+
+impl<'a> FrameMeta<'a> {
+    pub fn new(id: usize) -> Self {
+        FrameMeta {
+            id,
+            ..Default::default()
+        }
+    }
+}
+
+/// Parse a frame with the decoder of its link type, obtaining the representation of its content
+pub fn parse_frame(link_type: LinkType, frame: &[u8], meta: FrameMeta) -> ParsedPacket {
+    with_thread_context(|state| dissect_frame(state, link_type, frame, meta))
+}
+
+/// Dissect a frame with the decoder of its link type and the state of a parser context
+pub(crate) fn dissect_frame(
+    state: &mut ParserContext,
+    link_type: LinkType,
+    frame: &[u8],
+    meta: FrameMeta,
+) -> ParsedPacket {
+    let FrameMeta {
+        id,
+        capture_time,
+        interface,
+        direction,
+        wire_length,
+        decryptor,
+    } = meta;
+    // The frame is copied once, into the buffer shared by the packets of all its layers
+    let frame = FrameBytes::from(frame);
+    // Time-based dissection (e.g. PTP offsets) must refer to the capture time
+    let meta = PacketMeta {
+        interface,
+        direction,
+        captured_length: frame.len(),
+        wire_length: wire_length.unwrap_or(frame.len()).max(frame.len()),
+        frame: frame.clone(),
+        ..PacketMeta::new(id, capture_time.or(state.capture_time).unwrap_or_else(now))
+    };
+
+    match link_type {
+        LinkType::Ethernet => match EthernetPacket::new(&frame) {
+            Some(ethernet_packet) => dissect_ethernet_frame(state, &ethernet_packet, meta),
+            None => {
+                debug!("Malformed Ethernet Frame");
+                let mut parsed_packet = ParsedPacket::with_meta(meta);
+                parsed_packet.set_link_layer_packet(Some(SerializablePacket::MalformedPacket(
+                    "Malformed Ethernet Frame".to_owned(),
+                )));
+
+                parsed_packet
+            }
+        },
+        LinkType::Slip => dissect_slip_frame(state, &frame, meta),
+        LinkType::Ppp | LinkType::PppHdlc => dissect_ppp_frame(state, &frame, meta),
+        LinkType::Ieee80211 => dissect_ieee80211_frame(state, &frame, decryptor, meta),
+        LinkType::Ieee80211Radiotap => dissect_radiotap_frame(state, &frame, decryptor, meta),
+        LinkType::Null | LinkType::Loop => dissect_loopback_frame(state, &frame, meta),
+        LinkType::Raw | LinkType::Ipv4 | LinkType::Ipv6 => {
+            dissect_raw_ip_frame(state, &frame, meta)
+        }
+        LinkType::LinuxSll => dissect_linux_sll_frame(state, &frame, meta),
+        LinkType::LinuxSll2 => dissect_linux_sll2_frame(state, &frame, meta),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::serializable_packet::{Direction, SerializablePacket};
+
+    use super::{parse_frame, FrameMeta, LinkType};
+
+    /// IPv4 packet carrying an empty UDP datagram
+    const IPV4_PACKET: [u8; 28] = [
+        0x45, 0x00, 0x00, 0x1c, 0x00, 0x00, 0x00, 0x00, 0x40, 0x11, 0x00, 0x00, 0x0a, 0x00, 0x00,
+        0x01, 0x0a, 0x00, 0x00, 0x02, 0x30, 0x39, 0x30, 0x3a, 0x00, 0x08, 0x00, 0x00,
+    ];
+
+    #[test]
+    fn linktypes() {
+        for link_type in LinkType::ALL {
+            assert_eq!(
+                LinkType::from_linktype(link_type.linktype()),
+                Some(link_type)
+            );
+        }
+        assert_eq!(LinkType::from_linktype(1), Some(LinkType::Ethernet));
+        assert_eq!(LinkType::from_linktype(113), Some(LinkType::LinuxSll));
+        assert_eq!(LinkType::from_linktype(147), None);
+    }
+
+    #[test]
+    fn frames_of_each_link_type() {
+        let mut ethernet_frame = vec![0xff; 6];
+        ethernet_frame.extend([0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x08, 0x00]);
+        ethernet_frame.extend(IPV4_PACKET);
+        let parsed_packet = parse_frame(LinkType::Ethernet, &ethernet_frame, FrameMeta::new(1));
+        assert_eq!(parsed_packet.get_id(), 1);
+        assert!(matches!(
+            parsed_packet.get_link_layer_packet(),
+            Some(SerializablePacket::EthernetPacket(_))
+        ));
+        assert!(matches!(
+            parsed_packet.get_transport_layer_packet(),
+            Some(SerializablePacket::UdpPacket(_))
+        ));
+
+        let mut ppp_frame = vec![0xff, 0x03, 0x00, 0x21];
+        ppp_frame.extend(IPV4_PACKET);
+        let parsed_packet = parse_frame(LinkType::PppHdlc, &ppp_frame, FrameMeta::new(2));
+        assert!(matches!(
+            parsed_packet.get_link_layer_packet(),
+            Some(SerializablePacket::PppPacket(_))
+        ));
+        assert!(matches!(
+            parsed_packet.get_transport_layer_packet(),
+            Some(SerializablePacket::UdpPacket(_))
+        ));
+
+        let mut loopback_frame = vec![0x00, 0x00, 0x00, 0x02];
+        loopback_frame.extend(IPV4_PACKET);
+        let parsed_packet = parse_frame(LinkType::Loop, &loopback_frame, FrameMeta::new(5));
+        assert!(matches!(
+            parsed_packet.get_link_layer_packet(),
+            Some(SerializablePacket::LoopbackPacket(_))
+        ));
+        assert!(matches!(
+            parsed_packet.get_transport_layer_packet(),
+            Some(SerializablePacket::UdpPacket(_))
+        ));
+        let parsed_packet = parse_frame(LinkType::Ipv4, &IPV4_PACKET, FrameMeta::new(6));
+        assert!(matches!(
+            parsed_packet.get_transport_layer_packet(),
+            Some(SerializablePacket::UdpPacket(_))
+        ));
+
+        // Frames too short for their link type are malformed, not decoded as another one
+        let parsed_packet = parse_frame(LinkType::Ethernet, &[0x00; 4], FrameMeta::new(3));
+        assert!(matches!(
+            parsed_packet.get_link_layer_packet(),
+            Some(SerializablePacket::MalformedPacket(_))
+        ));
+        let parsed_packet = parse_frame(LinkType::Ieee80211Radiotap, &[0x00], FrameMeta::new(4));
+        assert!(matches!(
+            parsed_packet.get_link_layer_packet(),
+            Some(SerializablePacket::MalformedPacket(_))
+        ));
+    }
+
+    #[test]
+    fn frames_stamped_with_capture_time() {
+        let meta = FrameMeta {
+            capture_time: Some(Duration::new(1_700_000_000, 250_000)),
+            ..FrameMeta::new(0)
+        };
+        let parsed_packet = parse_frame(LinkType::Ethernet, &[0x00; 4], meta);
+
+        assert_eq!(parsed_packet.get_timestamp(), 1_700_000_000_000_250);
+        assert_eq!(
+            parsed_packet.get_meta().capture_time,
+            Duration::new(1_700_000_000, 250_000)
+        );
+    }
+
+    #[test]
+    fn capture_metadata() {
+        let mut ppp_frame = vec![0x00, 0x21];
+        ppp_frame.extend(IPV4_PACKET);
+        let meta = FrameMeta {
+            interface: Some("ppp0".to_owned()),
+            direction: Some(Direction::Outbound),
+            wire_length: Some(1500),
+            ..FrameMeta::new(7)
+        };
+        let parsed_packet = parse_frame(LinkType::Ppp, &ppp_frame, meta);
+
+        let meta = parsed_packet.get_meta();
+        assert_eq!(meta.id, 7);
+        assert_eq!(meta.interface.as_deref(), Some("ppp0"));
+        assert_eq!(meta.direction, Some(Direction::Outbound));
+        assert_eq!((meta.captured_length, meta.wire_length), (30, 1500));
+        assert!(meta.is_truncated());
+
+        // Without a wire length, the frame was captured whole
+        let parsed_packet = parse_frame(LinkType::Ppp, &ppp_frame, FrameMeta::new(8));
+        assert_eq!(parsed_packet.get_meta().wire_length, 30);
+        assert!(!parsed_packet.get_meta().is_truncated());
+        assert_eq!(parsed_packet.get_meta().interface, None);
+    }
+
+    #[test]
+    fn payloads_shared_with_frame() {
+        let mut ethernet_frame = vec![0xff; 6];
+        ethernet_frame.extend([0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x08, 0x00]);
+        ethernet_frame.extend(IPV4_PACKET);
+        let parsed_packet = parse_frame(LinkType::Ethernet, &ethernet_frame, FrameMeta::new(0));
+
+        let frame = &parsed_packet.get_meta().frame;
+        assert_eq!(*frame, ethernet_frame);
+        match parsed_packet.get_link_layer_packet() {
+            Some(SerializablePacket::EthernetPacket(ethernet_packet)) => {
+                assert_eq!(ethernet_packet.payload, IPV4_PACKET.to_vec());
+                assert!(frame.share(&ethernet_packet.payload).is_some());
+            }
+            _ => unreachable!(),
+        }
+    }
+}
@@ -0,0 +1,284 @@
+//! Decoding of the Multipath TCP options (RFC 6824 and RFC 8684)
+//!
+//! MPTCP options share the TCP option kind 30, their subtype being the upper nibble of the first
+//! byte after the length:
+//! - MP_CAPABLE: the keys of the hosts, exchanged by the handshake of the first subflow
+//! - MP_JOIN: the token of the connection a new subflow joins, and its address ID
+//! - DSS: the data-level acknowledgement and sequence mapping of the segment
+//!
+//! The other subtypes are only named. The token of a connection on each host is derived from the
+//! key of the host: the most significant 32 bits of its SHA-1 (version 0) or SHA-256 (version 1)
+//! hash, so that MP_JOIN subflows can be matched with the connection they join.
+
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+
+use crate::serializable_packet::transport::MptcpOption;
+
+/// Kind of the MPTCP options
+const MPTCP_OPTION: u8 = 30;
+
+/// MPTCP Option Subtypes
+#[allow(non_snake_case)]
+mod MptcpSubtypes {
+    pub const MP_CAPABLE: u8 = 0x0;
+    pub const MP_JOIN: u8 = 0x1;
+    pub const DSS: u8 = 0x2;
+}
+
+const FLAG_BACKUP: u8 = 0x01;
+const FLAG_DATA_FIN: u8 = 0x10;
+const FLAG_DSN_8_OCTETS: u8 = 0x08;
+const FLAG_DSN_PRESENT: u8 = 0x04;
+const FLAG_DATA_ACK_8_OCTETS: u8 = 0x02;
+const FLAG_DATA_ACK_PRESENT: u8 = 0x01;
+
+/// Decode the MPTCP options among the options of a TCP segment, skipping the malformed ones
+pub(crate) fn parse_mptcp_options(options: &[u8]) -> Vec<MptcpOption> {
+    let mut mptcp_options = vec![];
+
+    let mut i = 0;
+    while i < options.len() {
+        match options[i] {
+            // End of options
+            0 => break,
+            // No operation
+            1 => {
+                i += 1;
+                continue;
+            }
+            _ => (),
+        }
+
+        let length = match options.get(i + 1) {
+            Some(&length) if length >= 2 && i + length as usize <= options.len() => length as usize,
+            _ => break,
+        };
+        if options[i] == MPTCP_OPTION {
+            if let Some(option) = parse_mptcp_option(&options[i + 2..i + length]) {
+                mptcp_options.push(option);
+            }
+        }
+        i += length;
+    }
+
+    mptcp_options
+}
+
+/// Decode an MPTCP option, given the bytes following its kind and length
+fn parse_mptcp_option(data: &[u8]) -> Option<MptcpOption> {
+    let subtype = data.first()? >> 4;
+
+    match subtype {
+        MptcpSubtypes::MP_CAPABLE => {
+            let version = data[0] & 0x0f;
+            let sender_key = data.get(2..10).map(read_u64);
+            let receiver_key = data.get(10..18).map(read_u64);
+
+            Some(MptcpOption::MpCapable {
+                version,
+                sender_key: sender_key.map(|key| format!("{:016x}", key)),
+                receiver_key: receiver_key.map(|key| format!("{:016x}", key)),
+                sender_token: sender_key.map(|key| token(key, version)),
+                receiver_token: receiver_key.map(|key| token(key, version)),
+            })
+        }
+        MptcpSubtypes::MP_JOIN => {
+            let backup = data[0] & FLAG_BACKUP != 0;
+
+            // SYN, SYN-ACK and ACK of the subflow handshake, told apart by their length
+            Some(match data.len() {
+                10 => MptcpOption::MpJoin {
+                    backup,
+                    address_id: Some(data[1]),
+                    receiver_token: Some(read_u32(&data[2..6])),
+                    sender_nonce: Some(read_u32(&data[6..10])),
+                },
+                14 => MptcpOption::MpJoin {
+                    backup,
+                    address_id: Some(data[1]),
+                    receiver_token: None,
+                    sender_nonce: Some(read_u32(&data[10..14])),
+                },
+                _ => MptcpOption::MpJoin {
+                    backup,
+                    address_id: None,
+                    receiver_token: None,
+                    sender_nonce: None,
+                },
+            })
+        }
+        MptcpSubtypes::DSS => {
+            let flags = *data.get(1)?;
+            let mut fields = &data[2..];
+
+            let data_ack = if flags & FLAG_DATA_ACK_PRESENT != 0 {
+                let (data_ack, rest) = read_sequence(fields, flags & FLAG_DATA_ACK_8_OCTETS != 0)?;
+                fields = rest;
+                Some(data_ack)
+            } else {
+                None
+            };
+
+            let (data_sequence, subflow_sequence, data_length) = if flags & FLAG_DSN_PRESENT != 0 {
+                let (data_sequence, rest) = read_sequence(fields, flags & FLAG_DSN_8_OCTETS != 0)?;
+                let subflow_sequence = read_u32(rest.get(..4)?);
+                let data_length = u16::from_be_bytes(rest.get(4..6)?.try_into().unwrap());
+                (
+                    Some(data_sequence),
+                    Some(subflow_sequence),
+                    Some(data_length),
+                )
+            } else {
+                (None, None, None)
+            };
+
+            Some(MptcpOption::Dss {
+                data_ack,
+                data_sequence,
+                subflow_sequence,
+                data_length,
+                data_fin: flags & FLAG_DATA_FIN != 0,
+            })
+        }
+        _ => Some(MptcpOption::Other {
+            name: subtype_name(subtype).to_owned(),
+            code: subtype,
+        }),
+    }
+}
+
+/// Get the token of a connection on a host, given the key of the host
+fn token(key: u64, version: u8) -> u32 {
+    let key = key.to_be_bytes();
+    let hash = if version == 0 {
+        Sha1::digest(key).to_vec()
+    } else {
+        Sha256::digest(key).to_vec()
+    };
+
+    read_u32(&hash[..4])
+}
+
+/// Read a sequence number of 4 or 8 bytes, followed by the rest of the data
+fn read_sequence(data: &[u8], eight_octets: bool) -> Option<(u64, &[u8])> {
+    if eight_octets {
+        Some((read_u64(data.get(..8)?), &data[8..]))
+    } else {
+        Some((read_u32(data.get(..4)?) as u64, &data[4..]))
+    }
+}
+
+fn read_u32(data: &[u8]) -> u32 {
+    u32::from_be_bytes(data[..4].try_into().unwrap())
+}
+
+fn read_u64(data: &[u8]) -> u64 {
+    u64::from_be_bytes(data[..8].try_into().unwrap())
+}
+
+fn subtype_name(subtype: u8) -> &'static str {
+    match subtype {
+        0x3 => "ADD_ADDR",
+        0x4 => "REMOVE_ADDR",
+        0x5 => "MP_PRIO",
+        0x6 => "MP_FAIL",
+        0x7 => "MP_FASTCLOSE",
+        0x8 => "MP_TCPRST",
+        _ => "Unknown",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_mptcp_options, token};
+    use crate::serializable_packet::transport::MptcpOption;
+
+    #[test]
+    fn mp_capable_keys_and_tokens() {
+        // Third ACK of the handshake, with the keys of both the hosts
+        let options = [
+            1, 1, 30, 20, 0x01, 0x81, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88, 0x99, 0xaa,
+            0xbb, 0xcc, 0xdd, 0xee, 0xff, 0x00,
+        ];
+
+        assert_eq!(
+            parse_mptcp_options(&options),
+            vec![MptcpOption::MpCapable {
+                version: 1,
+                sender_key: Some("1122334455667788".to_owned()),
+                receiver_key: Some("99aabbccddeeff00".to_owned()),
+                sender_token: Some(token(0x1122334455667788, 1)),
+                receiver_token: Some(token(0x99aabbccddeeff00, 1)),
+            }]
+        );
+        // SYN of version 1, without keys
+        assert_eq!(
+            parse_mptcp_options(&[30, 4, 0x01, 0x81]),
+            vec![MptcpOption::MpCapable {
+                version: 1,
+                sender_key: None,
+                receiver_key: None,
+                sender_token: None,
+                receiver_token: None,
+            }]
+        );
+    }
+
+    #[test]
+    fn token_of_key() {
+        // Most significant 32 bits of the SHA-1 and SHA-256 hashes of 8 zero bytes
+        assert_eq!(token(0, 0), 0x05fe4057);
+        assert_eq!(token(0, 1), 0xaf5570f5);
+    }
+
+    #[test]
+    fn mp_join_subflow() {
+        assert_eq!(
+            parse_mptcp_options(&[30, 12, 0x11, 0x02, 0xde, 0xad, 0xbe, 0xef, 0, 0, 0, 7]),
+            vec![MptcpOption::MpJoin {
+                backup: true,
+                address_id: Some(2),
+                receiver_token: Some(0xdeadbeef),
+                sender_nonce: Some(7),
+            }]
+        );
+    }
+
+    #[test]
+    fn dss_mapping() {
+        // Data ACK of 4 bytes, DSN of 8 bytes, subflow sequence, data-level length, checksum
+        let options = [
+            30, 24, 0x20, 0x1d, 0, 0, 0x10, 0x00, 0, 0, 0, 0, 0, 0, 0x20, 0x00, 0, 0, 0, 1, 0x05,
+            0xdc, 0xab, 0xcd,
+        ];
+
+        assert_eq!(
+            parse_mptcp_options(&options),
+            vec![MptcpOption::Dss {
+                data_ack: Some(0x1000),
+                data_sequence: Some(0x2000),
+                subflow_sequence: Some(1),
+                data_length: Some(1500),
+                data_fin: true,
+            }]
+        );
+    }
+
+    #[test]
+    fn other_and_malformed_options() {
+        // MSS, ADD_ADDR, then a truncated DSS
+        let options = [
+            2, 4, 0x05, 0xb4, 30, 8, 0x30, 0x01, 10, 0, 0, 1, 30, 4, 0x20, 0x01,
+        ];
+
+        assert_eq!(
+            parse_mptcp_options(&options),
+            vec![MptcpOption::Other {
+                code: 3,
+                name: "ADD_ADDR".to_owned(),
+            }]
+        );
+        assert!(parse_mptcp_options(&[30, 40, 0x00]).is_empty());
+    }
+}
@@ -0,0 +1,123 @@
+//! Names of the well-known multicast groups (IANA IPv4 and IPv6 Multicast Address registries)
+//!
+//! Most of the multicast traffic of a LAN is sent by the hosts announcing themselves or looking
+//! for services and routers, so the destination of a multicast packet already tells its purpose.
+//! Only the groups commonly seen on a LAN are named:
+//! - name resolution: mDNS, LLMNR
+//! - service discovery: SSDP
+//! - routing: OSPF, RIP, PIM, VRRP
+//! - time synchronization: NTP, PTP
+//! - host and group management: all nodes and routers, IGMP, MLD, DHCPv6, solicited-node groups
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+/// IPv4 multicast groups, with their registered purpose
+const IPV4_GROUPS: &[(Ipv4Addr, &str)] = &[
+    (Ipv4Addr::new(224, 0, 0, 1), "All Systems"),
+    (Ipv4Addr::new(224, 0, 0, 2), "All Routers"),
+    (Ipv4Addr::new(224, 0, 0, 5), "OSPF AllSPFRouters"),
+    (Ipv4Addr::new(224, 0, 0, 6), "OSPF AllDRouters"),
+    (Ipv4Addr::new(224, 0, 0, 9), "RIPv2 Routers"),
+    (Ipv4Addr::new(224, 0, 0, 13), "PIM Routers"),
+    (Ipv4Addr::new(224, 0, 0, 18), "VRRP"),
+    (Ipv4Addr::new(224, 0, 0, 22), "IGMPv3"),
+    (Ipv4Addr::new(224, 0, 0, 107), "PTP Peer Delay"),
+    (Ipv4Addr::new(224, 0, 0, 251), "mDNS"),
+    (Ipv4Addr::new(224, 0, 0, 252), "LLMNR"),
+    (Ipv4Addr::new(224, 0, 1, 1), "NTP"),
+    (Ipv4Addr::new(224, 0, 1, 129), "PTP Primary"),
+    (Ipv4Addr::new(239, 255, 255, 250), "SSDP"),
+];
+
+/// IPv6 multicast groups, with their registered purpose
+const IPV6_GROUPS: &[(Ipv6Addr, &str)] = &[
+    (Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 0x1), "All Nodes"),
+    (Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 0x2), "All Routers"),
+    (
+        Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 0x5),
+        "OSPFv3 AllSPFRouters",
+    ),
+    (
+        Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 0x6),
+        "OSPFv3 AllDRouters",
+    ),
+    (
+        Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 0x9),
+        "RIPng Routers",
+    ),
+    (Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 0xc), "SSDP"),
+    (Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 0xd), "PIM Routers"),
+    (Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 0x12), "VRRP"),
+    (
+        Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 0x16),
+        "MLDv2 Reports",
+    ),
+    (
+        Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 0x6b),
+        "PTP Peer Delay",
+    ),
+    (Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 0xfb), "mDNS"),
+    (
+        Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0x1, 0x2),
+        "DHCPv6 Agents and Servers",
+    ),
+    (Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0x1, 0x3), "LLMNR"),
+    (
+        Ipv6Addr::new(0xff05, 0, 0, 0, 0, 0, 0x1, 0x3),
+        "DHCPv6 Servers",
+    ),
+    (Ipv6Addr::new(0xff05, 0, 0, 0, 0, 0, 0, 0xc), "SSDP"),
+    (Ipv6Addr::new(0xff0e, 0, 0, 0, 0, 0, 0, 0x101), "NTP"),
+    (
+        Ipv6Addr::new(0xff0e, 0, 0, 0, 0, 0, 0, 0x181),
+        "PTP Primary",
+    ),
+];
+
+/// Get the registered purpose of a multicast group, none if the address is not a well-known group
+pub(crate) fn multicast_group(address: IpAddr) -> Option<String> {
+    match address {
+        IpAddr::V4(address) => IPV4_GROUPS
+            .iter()
+            .find(|(group, _)| *group == address)
+            .map(|(_, name)| name.to_string()),
+        IpAddr::V6(address) => {
+            // Solicited-node groups, ff02::1:ffXX:XXXX, used by the Neighbor Discovery
+            let segments = address.segments();
+            if segments[..6] == [0xff02, 0, 0, 0, 0, 1] && segments[6] >> 8 == 0xff {
+                return Some("Solicited-Node".to_owned());
+            }
+
+            IPV6_GROUPS
+                .iter()
+                .find(|(group, _)| *group == address)
+                .map(|(_, name)| name.to_string())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+    use super::multicast_group;
+
+    #[test]
+    fn well_known_groups() {
+        let ipv4 = |address: [u8; 4]| multicast_group(IpAddr::V4(Ipv4Addr::from(address)));
+        assert_eq!(ipv4([224, 0, 0, 251]), Some("mDNS".to_owned()));
+        assert_eq!(ipv4([239, 255, 255, 250]), Some("SSDP".to_owned()));
+        assert_eq!(ipv4([224, 0, 0, 5]), Some("OSPF AllSPFRouters".to_owned()));
+        assert_eq!(ipv4([224, 0, 1, 129]), Some("PTP Primary".to_owned()));
+        assert_eq!(ipv4([224, 0, 0, 252]), Some("LLMNR".to_owned()));
+        assert_eq!(ipv4([239, 1, 2, 3]), None);
+        assert_eq!(ipv4([192, 168, 1, 1]), None);
+
+        let ipv6 = |address: &str| multicast_group(IpAddr::V6(address.parse().unwrap()));
+        assert_eq!(ipv6("ff02::fb"), Some("mDNS".to_owned()));
+        assert_eq!(ipv6("ff02::1:3"), Some("LLMNR".to_owned()));
+        assert_eq!(ipv6("ff02::1:ff12:3456"), Some("Solicited-Node".to_owned()));
+        assert_eq!(ipv6("ff02::1:1234:5678"), None);
+        assert_eq!(multicast_group(IpAddr::V6(Ipv6Addr::LOCALHOST)), None);
+    }
+}
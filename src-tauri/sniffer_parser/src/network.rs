@@ -1,25 +1,33 @@
 //! IPv4, IPv6, and ARP Packet parsing
 
 use pnet::packet::arp::ArpPacket;
+use pnet::packet::ip::{IpNextHeaderProtocol, IpNextHeaderProtocols};
 use pnet::packet::ipv4::Ipv4Packet;
 use pnet::packet::ipv6::Ipv6Packet;
 use pnet::packet::Packet;
 use std::net::IpAddr;
 
 use super::*;
+use crate::flows::{labelled_flow, learn_flow_label, track_flow, FlowKind};
+use crate::references::link_flow_packet;
 use crate::serializable_packet::network::{
-    SerializableArpPacket, SerializableIpv4Packet, SerializableIpv6Packet,
+    LabelledFlow, SerializableArpPacket, SerializableIpv4Packet, SerializableIpv6Packet,
 };
 use crate::transport::*;
 
 /// Build a IPv4 packet from a data-link packet, save it in a Parsed Packet
-pub fn handle_ipv4_packet(packet: &[u8], parsed_packet: &mut ParsedPacket) {
+pub fn handle_ipv4_packet(
+    state: &mut ParserContext,
+    packet: &[u8],
+    parsed_packet: &mut ParsedPacket,
+) {
     let header = Ipv4Packet::new(packet);
     if let Some(header) = header {
         parsed_packet.set_network_layer_packet(Some(SerializablePacket::Ipv4Packet(
             SerializableIpv4Packet::from(&header),
         )));
         handle_transport_protocol(
+            state,
             IpAddr::V4(header.get_source()),
             IpAddr::V4(header.get_destination()),
             header.get_next_level_protocol(),
@@ -35,19 +43,45 @@ pub fn handle_ipv4_packet(packet: &[u8], parsed_packet: &mut ParsedPacket) {
 }
 
 /// Build a IPv6 packet from a data-link packet, save it in a Parsed Packet
-pub fn handle_ipv6_packet(packet: &[u8], parsed_packet: &mut ParsedPacket) {
+///
+/// The extension headers are skipped up to the upper-layer protocol.
+pub fn handle_ipv6_packet(
+    state: &mut ParserContext,
+    packet: &[u8],
+    parsed_packet: &mut ParsedPacket,
+) {
     let header = Ipv6Packet::new(packet);
     if let Some(header) = header {
-        parsed_packet.set_network_layer_packet(Some(SerializablePacket::Ipv6Packet(
-            SerializableIpv6Packet::from(&header),
-        )));
-        handle_transport_protocol(
-            IpAddr::V6(header.get_source()),
-            IpAddr::V6(header.get_destination()),
-            header.get_next_header(),
-            header.payload(),
+        let source = IpAddr::V6(header.get_source());
+        let destination = IpAddr::V6(header.get_destination());
+        let upper_layer = ipv6_upper_layer(header.get_next_header(), header.payload());
+
+        if !upper_layer.trailing_fragment {
+            handle_transport_protocol(
+                state,
+                source,
+                destination,
+                upper_layer.protocol,
+                upper_layer.payload,
+                parsed_packet,
+            );
+        }
+
+        let mut ipv6_packet = SerializableIpv6Packet::from(&header);
+        ipv6_packet.extension_headers = upper_layer
+            .extension_headers
+            .iter()
+            .map(|extension_header| format!("{} ({})", extension_header, extension_header.0))
+            .collect();
+        ipv6_packet.labelled_flow = correlate_flow_label(
+            state,
+            source,
+            destination,
+            header.get_flow_label(),
+            upper_layer.protocol,
             parsed_packet,
         );
+        parsed_packet.set_network_layer_packet(Some(SerializablePacket::Ipv6Packet(ipv6_packet)));
     } else {
         debug!("Malformed IPv6 Packet");
         parsed_packet.set_network_layer_packet(Some(SerializablePacket::MalformedPacket(
@@ -56,6 +90,117 @@ pub fn handle_ipv6_packet(packet: &[u8], parsed_packet: &mut ParsedPacket) {
     }
 }
 
+/// Upper-layer protocol of an IPv6 packet, found after its extension headers
+struct Ipv6UpperLayer<'a> {
+    protocol: IpNextHeaderProtocol,
+    payload: &'a [u8],
+    extension_headers: Vec<IpNextHeaderProtocol>,
+    /// Whether the payload is a fragment after the first one, without the upper-layer header
+    trailing_fragment: bool,
+}
+
+/// Skip the extension headers of an IPv6 packet, up to its upper-layer protocol
+///
+/// A truncated extension header is left as the protocol of the payload.
+fn ipv6_upper_layer(next_header: IpNextHeaderProtocol, payload: &[u8]) -> Ipv6UpperLayer<'_> {
+    let mut upper_layer = Ipv6UpperLayer {
+        protocol: next_header,
+        payload,
+        extension_headers: vec![],
+        trailing_fragment: false,
+    };
+
+    loop {
+        let length = match (upper_layer.protocol, upper_layer.payload.get(..4)) {
+            (
+                IpNextHeaderProtocols::Hopopt
+                | IpNextHeaderProtocols::Ipv6Route
+                | IpNextHeaderProtocols::Ipv6Opts,
+                Some(header),
+            ) => (header[1] as usize + 1) * 8,
+            (IpNextHeaderProtocols::Ipv6Frag, Some(header)) => {
+                upper_layer.trailing_fragment =
+                    u16::from_be_bytes([header[2], header[3]]) >> 3 != 0;
+                8
+            }
+            _ => return upper_layer,
+        };
+        if upper_layer.payload.len() < length {
+            upper_layer.trailing_fragment = false;
+            return upper_layer;
+        }
+
+        upper_layer.extension_headers.push(upper_layer.protocol);
+        upper_layer.protocol = IpNextHeaderProtocol(upper_layer.payload[0]);
+        upper_layer.payload = &upper_layer.payload[length..];
+        if upper_layer.trailing_fragment {
+            return upper_layer;
+        }
+    }
+}
+
+/// Group the IPv6 packets of a flow by their flow label, when non-zero
+///
+/// The transport flow of the label is learned from the packets whose ports are readable, and the
+/// packets of the same protocol whose ports are not (e.g. the fragments after the first one) are
+/// recorded as packets of that flow.
+fn correlate_flow_label(
+    state: &mut ParserContext,
+    source: IpAddr,
+    destination: IpAddr,
+    label: u32,
+    protocol: IpNextHeaderProtocol,
+    parsed_packet: &ParsedPacket,
+) -> Option<LabelledFlow> {
+    if label == 0 {
+        return None;
+    }
+
+    let ports = match parsed_packet.get_transport_layer_packet() {
+        Some(SerializablePacket::TcpPacket(tcp)) => Some((tcp.source, tcp.destination)),
+        Some(SerializablePacket::UdpPacket(udp)) => Some((udp.source, udp.destination)),
+        Some(_) => return None,
+        None => None,
+    };
+    if let Some((source_port, destination_port)) = ports {
+        learn_flow_label(
+            state,
+            source,
+            destination,
+            label,
+            LabelledFlow {
+                source_port,
+                destination_port,
+                protocol: protocol.0,
+            },
+        );
+        return labelled_flow(state, source, destination, label);
+    }
+
+    let flow = labelled_flow(state, source, destination, label)
+        .filter(|flow| flow.protocol == protocol.0)?;
+    let kind = if protocol == IpNextHeaderProtocols::Tcp {
+        FlowKind::TcpEstablished
+    } else {
+        FlowKind::Udp
+    };
+    let (source, destination) = (
+        (source, flow.source_port),
+        (destination, flow.destination_port),
+    );
+    if track_flow(
+        state,
+        kind,
+        source,
+        destination,
+        parsed_packet.get_meta().capture_time,
+    ) {
+        link_flow_packet(state, parsed_packet, source, destination);
+    }
+
+    Some(flow)
+}
+
 /// Build a ARP packet from a data-link packet, save it in a Parsed Packet
 pub fn handle_arp_packet(
     packet: &[u8],
@@ -91,14 +236,15 @@ pub mod tests {
 
     use pnet::packet::arp::{ArpHardwareTypes, ArpOperations, ArpPacket, MutableArpPacket};
     use pnet::packet::ethernet::{EtherTypes, EthernetPacket, MutableEthernetPacket};
-    use pnet::packet::ip::IpNextHeaderProtocols;
+    use pnet::packet::ip::{IpNextHeaderProtocol, IpNextHeaderProtocols};
     use pnet::packet::ipv4::{Ipv4Packet, MutableIpv4Packet};
     use pnet::packet::ipv6::{Ipv6Packet, MutableIpv6Packet};
     use pnet::packet::Packet;
     use pnet::util::MacAddr;
 
+    use crate::serializable_packet::network::{LabelledFlow, SerializableIpv6Packet};
     use crate::serializable_packet::{ParsedPacket, SerializablePacket};
-    use crate::{handle_ipv4_packet, handle_ipv6_packet};
+    use crate::{handle_ipv4_packet, handle_ipv6_packet, ParserContext};
 
     use super::handle_arp_packet;
 
@@ -175,11 +321,13 @@ pub mod tests {
 
     #[test]
     fn valid_ip_packet() {
+        let mut state = ParserContext::new();
+
         let mut ethernet_buffer = [0u8; 42];
         let ethernet_packet = build_test_ip_packet(ethernet_buffer.as_mut_slice());
 
         let mut parsed_packet = ParsedPacket::new(0);
-        handle_ipv4_packet(ethernet_packet.payload(), &mut parsed_packet);
+        handle_ipv4_packet(&mut state, ethernet_packet.payload(), &mut parsed_packet);
 
         let ip_packet = Ipv4Packet::new(ethernet_packet.payload()).unwrap();
         match parsed_packet.get_network_layer_packet().unwrap() {
@@ -215,8 +363,10 @@ pub mod tests {
 
     #[test]
     fn malformed_ip_packet() {
+        let mut state = ParserContext::new();
+
         let mut parsed_packet = ParsedPacket::new(0);
-        handle_ipv4_packet(&[], &mut parsed_packet);
+        handle_ipv4_packet(&mut state, &[], &mut parsed_packet);
 
         match parsed_packet.get_network_layer_packet().unwrap() {
             SerializablePacket::MalformedPacket(str) => assert_eq!(str, "Malformed IPv4 Packet"),
@@ -226,11 +376,13 @@ pub mod tests {
 
     #[test]
     fn valid_ipv6_packet() {
+        let mut state = ParserContext::new();
+
         let mut ethernet_buffer = [0u8; 256];
         let ethernet_packet = build_test_ipv6_packet(ethernet_buffer.as_mut_slice());
 
         let mut parsed_packet = ParsedPacket::new(0);
-        handle_ipv6_packet(ethernet_packet.payload(), &mut parsed_packet);
+        handle_ipv6_packet(&mut state, ethernet_packet.payload(), &mut parsed_packet);
 
         let ipv6_packet = Ipv6Packet::new(ethernet_packet.payload()).unwrap();
         match parsed_packet.get_network_layer_packet().unwrap() {
@@ -264,8 +416,10 @@ pub mod tests {
 
     #[test]
     fn malformed_ipv6_packet() {
+        let mut state = ParserContext::new();
+
         let mut parsed_packet = ParsedPacket::new(0);
-        handle_ipv6_packet(&[], &mut parsed_packet);
+        handle_ipv6_packet(&mut state, &[], &mut parsed_packet);
 
         match parsed_packet.get_network_layer_packet().unwrap() {
             SerializablePacket::MalformedPacket(str) => assert_eq!(str, "Malformed IPv6 Packet"),
@@ -273,6 +427,93 @@ pub mod tests {
         }
     }
 
+    #[test]
+    fn packets_grouped_by_flow_label() {
+        let mut state = ParserContext::new();
+
+        let udp_header = [0x14, 0xe9, 0x00, 0x35, 0x00, 0x08, 0x00, 0x00];
+
+        // Hop-by-Hop Options header (8 bytes) followed by UDP, then the fragments of a datagram
+        let hop_by_hop = [17, 0, 0x01, 0x04, 0, 0, 0, 0];
+        let first_fragment = [17, 0, 0x00, 0x01, 0, 0, 0, 1];
+        let second_fragment = [17, 0, 0x00, 0x10, 0, 0, 0, 1];
+        let packets = [
+            (
+                0x12345,
+                IpNextHeaderProtocols::Hopopt,
+                [&hop_by_hop[..], &udp_header].concat(),
+            ),
+            (
+                0x12345,
+                IpNextHeaderProtocols::Hopopt,
+                [
+                    &[44, 0, 0x01, 0x04, 0, 0, 0, 0][..],
+                    &first_fragment,
+                    &udp_header,
+                ]
+                .concat(),
+            ),
+            (
+                0x12345,
+                IpNextHeaderProtocols::Ipv6Frag,
+                [&second_fragment[..], &[0; 16]].concat(),
+            ),
+            (
+                0x54321,
+                IpNextHeaderProtocols::Ipv6Frag,
+                [&second_fragment[..], &[0; 16]].concat(),
+            ),
+        ];
+        let parsed: Vec<ParsedPacket> = packets
+            .iter()
+            .enumerate()
+            .map(|(id, (flow_label, next_header, payload))| {
+                let mut parsed_packet = ParsedPacket::new(id);
+                handle_ipv6_packet(
+                    &mut state,
+                    &build_labelled_ipv6_packet(*flow_label, *next_header, payload),
+                    &mut parsed_packet,
+                );
+                parsed_packet
+            })
+            .collect();
+
+        let ipv6 = |parsed_packet: &ParsedPacket| match parsed_packet.get_network_layer_packet() {
+            Some(SerializablePacket::Ipv6Packet(ipv6_packet)) => ipv6_packet.clone(),
+            _ => unreachable!(),
+        };
+        let flow = Some(LabelledFlow {
+            source_port: 5353,
+            destination_port: 53,
+            protocol: 17,
+        });
+
+        // The UDP header is found after the extension headers
+        for parsed_packet in &parsed[..2] {
+            assert!(matches!(
+                parsed_packet.get_transport_layer_packet(),
+                Some(SerializablePacket::UdpPacket(_))
+            ));
+            assert_eq!(ipv6(parsed_packet).labelled_flow, flow);
+        }
+        assert_eq!(
+            ipv6(&parsed[1]).extension_headers,
+            vec!["Hopopt (0)".to_owned(), "Ipv6Frag (44)".to_owned()]
+        );
+
+        // Later fragments have no UDP header, their flow comes from their label
+        let SerializableIpv6Packet {
+            extension_headers,
+            labelled_flow,
+            ..
+        } = ipv6(&parsed[2]);
+        assert!(parsed[2].get_transport_layer_packet().is_none());
+        assert_eq!(extension_headers, vec!["Ipv6Frag (44)".to_owned()]);
+        assert_eq!(labelled_flow, flow);
+
+        assert_eq!(ipv6(&parsed[3]).labelled_flow, None);
+    }
+
     ///////////////////// Utils
 
     fn build_test_arp_packet<'a>(ethernet_buffer: &'a mut [u8]) -> EthernetPacket<'a> {
@@ -353,4 +594,24 @@ pub mod tests {
 
         ethernet_packet.consume_to_immutable()
     }
+
+    fn build_labelled_ipv6_packet(
+        flow_label: u32,
+        next_header: IpNextHeaderProtocol,
+        payload: &[u8],
+    ) -> Vec<u8> {
+        let mut buffer = vec![0u8; 40 + payload.len()];
+        let mut ipv6_packet = MutableIpv6Packet::new(&mut buffer).unwrap();
+
+        ipv6_packet.set_version(6);
+        ipv6_packet.set_flow_label(flow_label);
+        ipv6_packet.set_payload_length(payload.len() as u16);
+        ipv6_packet.set_next_header(next_header);
+        ipv6_packet.set_hop_limit(64);
+        ipv6_packet.set_source(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1));
+        ipv6_packet.set_destination(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 2));
+        ipv6_packet.set_payload(payload);
+
+        buffer
+    }
 }
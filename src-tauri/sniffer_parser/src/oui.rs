@@ -0,0 +1,215 @@
+//! Vendors of the MAC addresses, from their OUI (IEEE MA-L registry)
+//!
+//! The first three octets of a universally administered MAC address are the OUI the IEEE assigned
+//! to the vendor of the interface, so that the devices of a LAN can be told apart by their maker
+//! (e.g. "Apple, Inc." laptops and phones from "Espressif Inc." IoT boards).
+//!
+//! The embedded table holds the OUIs of the vendors commonly seen on a LAN: computers and phones,
+//! virtual machines, network equipment, single-board computers and IoT devices. It is compiled in
+//! with the `oui` feature; without it, no address has a vendor. Locally administered addresses
+//! (e.g. the randomized ones of the phones) and group addresses have no vendor either.
+
+use pnet::util::MacAddr;
+
+/// Vendors of the OUIs, sorted by OUI
+#[cfg(feature = "oui")]
+const VENDORS: &[([u8; 3], &str)] = &[
+    ([0x00, 0x00, 0x0c], "Cisco Systems, Inc"),
+    ([0x00, 0x01, 0x42], "Cisco Systems, Inc"),
+    ([0x00, 0x02, 0xb3], "Intel Corporate"),
+    ([0x00, 0x03, 0x93], "Apple, Inc."),
+    ([0x00, 0x03, 0xff], "Microsoft Corporation"),
+    ([0x00, 0x05, 0x02], "Apple, Inc."),
+    ([0x00, 0x05, 0x69], "VMware, Inc."),
+    ([0x00, 0x05, 0x85], "Juniper Networks"),
+    ([0x00, 0x07, 0xe9], "Intel Corporate"),
+    ([0x00, 0x09, 0x5b], "NETGEAR"),
+    ([0x00, 0x0a, 0x27], "Apple, Inc."),
+    ([0x00, 0x0a, 0x95], "Apple, Inc."),
+    ([0x00, 0x0c, 0x29], "VMware, Inc."),
+    ([0x00, 0x0c, 0x42], "Routerboard.com"),
+    ([0x00, 0x0d, 0x3a], "Microsoft Corporation"),
+    ([0x00, 0x0d, 0x93], "Apple, Inc."),
+    ([0x00, 0x0e, 0x58], "Sonos, Inc."),
+    ([0x00, 0x10, 0x18], "Broadcom"),
+    ([0x00, 0x10, 0xfa], "Apple, Inc."),
+    ([0x00, 0x11, 0x24], "Apple, Inc."),
+    ([0x00, 0x11, 0x32], "Synology Incorporated"),
+    ([0x00, 0x12, 0x4b], "Texas Instruments"),
+    ([0x00, 0x14, 0x22], "Dell Inc."),
+    ([0x00, 0x14, 0x51], "Apple, Inc."),
+    ([0x00, 0x14, 0x6c], "NETGEAR"),
+    ([0x00, 0x15, 0x5d], "Microsoft Corporation"),
+    ([0x00, 0x15, 0x6d], "Ubiquiti Networks Inc."),
+    ([0x00, 0x16, 0x3e], "Xensource, Inc."),
+    ([0x00, 0x16, 0xcb], "Apple, Inc."),
+    ([0x00, 0x17, 0x88], "Philips Lighting BV"),
+    ([0x00, 0x17, 0xf2], "Apple, Inc."),
+    ([0x00, 0x18, 0x0a], "Cisco Meraki"),
+    ([0x00, 0x18, 0x82], "Huawei Technologies Co.,Ltd"),
+    ([0x00, 0x19, 0xe3], "Apple, Inc."),
+    ([0x00, 0x1a, 0x11], "Google, Inc."),
+    ([0x00, 0x1b, 0x21], "Intel Corporate"),
+    ([0x00, 0x1b, 0x63], "Apple, Inc."),
+    ([0x00, 0x1c, 0x14], "VMware, Inc."),
+    ([0x00, 0x1c, 0x42], "Parallels, Inc."),
+    ([0x00, 0x1c, 0x73], "Arista Networks"),
+    ([0x00, 0x1c, 0xb3], "Apple, Inc."),
+    ([0x00, 0x1d, 0x4f], "Apple, Inc."),
+    ([0x00, 0x1e, 0x52], "Apple, Inc."),
+    ([0x00, 0x1e, 0xc2], "Apple, Inc."),
+    ([0x00, 0x1f, 0x5b], "Apple, Inc."),
+    ([0x00, 0x1f, 0xf3], "Apple, Inc."),
+    ([0x00, 0x21, 0xe9], "Apple, Inc."),
+    ([0x00, 0x22, 0x41], "Apple, Inc."),
+    ([0x00, 0x23, 0x12], "Apple, Inc."),
+    ([0x00, 0x23, 0x32], "Apple, Inc."),
+    ([0x00, 0x23, 0x6c], "Apple, Inc."),
+    ([0x00, 0x23, 0xdf], "Apple, Inc."),
+    ([0x00, 0x24, 0x36], "Apple, Inc."),
+    ([0x00, 0x25, 0x00], "Apple, Inc."),
+    ([0x00, 0x25, 0x4b], "Apple, Inc."),
+    ([0x00, 0x25, 0xbc], "Apple, Inc."),
+    ([0x00, 0x26, 0x08], "Apple, Inc."),
+    ([0x00, 0x26, 0x4a], "Apple, Inc."),
+    ([0x00, 0x26, 0xb0], "Apple, Inc."),
+    ([0x00, 0x26, 0xbb], "Apple, Inc."),
+    ([0x00, 0x27, 0x22], "Ubiquiti Networks Inc."),
+    ([0x00, 0x50, 0x56], "VMware, Inc."),
+    ([0x00, 0x50, 0xf2], "Microsoft Corporation"),
+    ([0x00, 0xe0, 0x4c], "Realtek Semiconductor Corp."),
+    ([0x00, 0xe0, 0xfc], "Huawei Technologies Co.,Ltd"),
+    ([0x04, 0x18, 0xd6], "Ubiquiti Networks Inc."),
+    ([0x08, 0x00, 0x27], "PCS Systemtechnik GmbH"),
+    ([0x14, 0xcc, 0x20], "TP-LINK TECHNOLOGIES CO.,LTD."),
+    ([0x18, 0xb4, 0x30], "Nest Labs Inc."),
+    ([0x18, 0xfe, 0x34], "Espressif Inc."),
+    ([0x24, 0x0a, 0xc4], "Espressif Inc."),
+    ([0x24, 0x62, 0xab], "Espressif Inc."),
+    ([0x24, 0x6f, 0x28], "Espressif Inc."),
+    ([0x24, 0xa4, 0x3c], "Ubiquiti Networks Inc."),
+    (
+        [0x28, 0x57, 0xbe],
+        "Hangzhou Hikvision Digital Technology Co.,Ltd.",
+    ),
+    ([0x28, 0x6c, 0x07], "Xiaomi Communications Co Ltd"),
+    ([0x28, 0xcd, 0xc1], "Raspberry Pi Trading Ltd"),
+    ([0x2c, 0x3a, 0xe8], "Espressif Inc."),
+    ([0x30, 0xae, 0xa4], "Espressif Inc."),
+    ([0x3c, 0x07, 0x54], "Apple, Inc."),
+    ([0x3c, 0x5a, 0xb4], "Google, Inc."),
+    ([0x3c, 0x71, 0xbf], "Espressif Inc."),
+    ([0x3c, 0xd9, 0x2b], "Hewlett Packard"),
+    (
+        [0x44, 0x19, 0xb6],
+        "Hangzhou Hikvision Digital Technology Co.,Ltd.",
+    ),
+    ([0x44, 0x65, 0x0d], "Amazon Technologies Inc."),
+    ([0x44, 0xd9, 0xe7], "Ubiquiti Networks Inc."),
+    ([0x4c, 0x5e, 0x0c], "Routerboard.com"),
+    ([0x50, 0xc7, 0xbf], "TP-LINK TECHNOLOGIES CO.,LTD."),
+    ([0x54, 0x60, 0x09], "Google, Inc."),
+    ([0x5c, 0xaa, 0xfd], "Sonos, Inc."),
+    ([0x5c, 0xcf, 0x7f], "Espressif Inc."),
+    ([0x60, 0x01, 0x94], "Espressif Inc."),
+    ([0x64, 0x09, 0x80], "Xiaomi Communications Co Ltd"),
+    ([0x64, 0x16, 0x66], "Nest Labs Inc."),
+    ([0x68, 0x37, 0xe9], "Amazon Technologies Inc."),
+    ([0x68, 0x72, 0x51], "Ubiquiti Networks Inc."),
+    ([0x6c, 0x3b, 0x6b], "Routerboard.com"),
+    ([0x74, 0xc2, 0x46], "Amazon Technologies Inc."),
+    ([0x78, 0x8a, 0x20], "Ubiquiti Networks Inc."),
+    ([0x80, 0x2a, 0xa8], "Ubiquiti Networks Inc."),
+    ([0x84, 0x0d, 0x8e], "Espressif Inc."),
+    ([0x84, 0xf3, 0xeb], "Espressif Inc."),
+    ([0x8c, 0xaa, 0xb5], "Espressif Inc."),
+    ([0x94, 0x9f, 0x3e], "Sonos, Inc."),
+    ([0x98, 0xda, 0xc4], "TP-LINK TECHNOLOGIES CO.,LTD."),
+    ([0xa0, 0x20, 0xa6], "Espressif Inc."),
+    ([0xa0, 0x40, 0xa0], "NETGEAR"),
+    ([0xa4, 0x83, 0xe7], "Apple, Inc."),
+    ([0xa4, 0xcf, 0x12], "Espressif Inc."),
+    ([0xac, 0xbc, 0x32], "Apple, Inc."),
+    ([0xb0, 0xa7, 0x37], "Roku, Inc"),
+    ([0xb8, 0x27, 0xeb], "Raspberry Pi Foundation"),
+    ([0xb8, 0x69, 0xf4], "Routerboard.com"),
+    ([0xb8, 0xac, 0x6f], "Dell Inc."),
+    ([0xb8, 0xe9, 0x37], "Sonos, Inc."),
+    (
+        [0xbc, 0xad, 0x28],
+        "Hangzhou Hikvision Digital Technology Co.,Ltd.",
+    ),
+    ([0xbc, 0xdd, 0xc2], "Espressif Inc."),
+    ([0xc0, 0x25, 0xe9], "TP-LINK TECHNOLOGIES CO.,LTD."),
+    (
+        [0xc0, 0x56, 0xe3],
+        "Hangzhou Hikvision Digital Technology Co.,Ltd.",
+    ),
+    ([0xc4, 0x4f, 0x33], "Espressif Inc."),
+    ([0xcc, 0x50, 0xe3], "Espressif Inc."),
+    ([0xd4, 0xca, 0x6d], "Routerboard.com"),
+    ([0xd8, 0x3a, 0xdd], "Raspberry Pi Trading Ltd"),
+    ([0xdc, 0x4f, 0x22], "Espressif Inc."),
+    ([0xdc, 0x9f, 0xdb], "Ubiquiti Networks Inc."),
+    ([0xdc, 0xa6, 0x32], "Raspberry Pi Trading Ltd"),
+    ([0xe4, 0x5f, 0x01], "Raspberry Pi Trading Ltd"),
+    ([0xe4, 0x8d, 0x8c], "Routerboard.com"),
+    ([0xec, 0x08, 0x6b], "TP-LINK TECHNOLOGIES CO.,LTD."),
+    ([0xec, 0xfa, 0xbc], "Espressif Inc."),
+    ([0xf0, 0x18, 0x98], "Apple, Inc."),
+    ([0xf0, 0x9f, 0xc2], "Ubiquiti Networks Inc."),
+    ([0xf4, 0xf2, 0x6d], "TP-LINK TECHNOLOGIES CO.,LTD."),
+    ([0xf4, 0xf5, 0xd8], "Google, Inc."),
+    ([0xf8, 0xbc, 0x12], "Dell Inc."),
+    ([0xfc, 0x65, 0xde], "Amazon Technologies Inc."),
+    ([0xfc, 0xec, 0xda], "Ubiquiti Networks Inc."),
+];
+
+#[cfg(not(feature = "oui"))]
+const VENDORS: &[([u8; 3], &str)] = &[];
+
+/// Get the vendor of a MAC address from its OUI, none if it is not in the table
+pub fn mac_vendor(address: MacAddr) -> Option<&'static str> {
+    // Locally administered and group addresses
+    if address.0 & 0x03 != 0 {
+        return None;
+    }
+
+    VENDORS
+        .binary_search_by_key(&[address.0, address.1, address.2], |(oui, _)| *oui)
+        .ok()
+        .map(|index| VENDORS[index].1)
+}
+
+#[cfg(all(test, feature = "oui"))]
+mod tests {
+    use pnet::util::MacAddr;
+
+    use super::{mac_vendor, VENDORS};
+
+    #[test]
+    fn sorted_vendors() {
+        assert!(VENDORS.windows(2).all(|pair| pair[0].0 < pair[1].0));
+    }
+
+    #[test]
+    fn vendors_of_ouis() {
+        assert_eq!(
+            mac_vendor(MacAddr::new(0x00, 0x03, 0x93, 0x12, 0x34, 0x56)),
+            Some("Apple, Inc.")
+        );
+        assert_eq!(
+            mac_vendor(MacAddr::new(0x24, 0x0a, 0xc4, 0x00, 0x00, 0x01)),
+            Some("Espressif Inc.")
+        );
+        assert_eq!(
+            mac_vendor(MacAddr::new(0xb8, 0x27, 0xeb, 0xaa, 0xbb, 0xcc)),
+            Some("Raspberry Pi Foundation")
+        );
+        assert_eq!(mac_vendor(MacAddr::new(0x00, 0x00, 0x01, 0, 0, 1)), None);
+        // Randomized, multicast and broadcast addresses
+        assert_eq!(mac_vendor(MacAddr::new(0x02, 0x03, 0x93, 0, 0, 1)), None);
+        assert_eq!(mac_vendor(MacAddr::new(0x01, 0x00, 0x5e, 0, 0, 1)), None);
+        assert_eq!(mac_vendor(MacAddr::broadcast()), None);
+    }
+}
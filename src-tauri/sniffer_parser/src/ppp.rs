@@ -0,0 +1,409 @@
+//! PPP and SLIP frame parsing, for captures of serial and VPN links
+//!
+//! PPP frames may start with the address and control fields of the HDLC-like framing (0xff 0x03),
+//! and their protocol field may be compressed to a single byte: the IP packets they carry go
+//! through the usual dissection, while the messages of the control protocols negotiating the link
+//! (LCP) and the network layers (IPCP, IPV6CP) are decoded along with their options.
+//!
+//! SLIP frames are captured with a 16-byte header: the direction of the frame, and its header as
+//! possibly compressed by Van Jacobson's compression. The IP packet following it is the one sent or
+//! received, decompressed.
+
+use std::net::Ipv4Addr;
+
+use tracing::debug;
+
+use crate::serializable_packet::{
+    PacketMeta, ParsedPacket, PppControlMessage, PppOption, SerializablePacket,
+    SerializablePppPacket, SerializableSlipPacket,
+};
+use crate::{
+    handle_ipv4_packet, handle_ipv6_packet, parse_frame, FrameMeta, LinkType, ParserContext,
+};
+
+/// PPP Protocol Numbers
+#[allow(non_snake_case)]
+mod PppProtocols {
+    pub const IPV4: u16 = 0x0021;
+    pub const VJ_COMPRESSED_TCP: u16 = 0x002d;
+    pub const VJ_UNCOMPRESSED_TCP: u16 = 0x002f;
+    pub const IPV6: u16 = 0x0057;
+    pub const IPCP: u16 = 0x8021;
+    pub const IPV6CP: u16 = 0x8057;
+    pub const CCP: u16 = 0x80fd;
+    pub const LCP: u16 = 0xc021;
+    pub const PAP: u16 = 0xc023;
+    pub const LQR: u16 = 0xc025;
+    pub const CHAP: u16 = 0xc223;
+    pub const EAP: u16 = 0xc227;
+}
+
+/// Codes of the control protocol messages
+#[allow(non_snake_case)]
+mod ControlCodes {
+    pub const CONFIGURE_REQUEST: u8 = 1;
+    pub const CONFIGURE_REJECT: u8 = 4;
+    pub const ECHO_REQUEST: u8 = 9;
+    pub const DISCARD_REQUEST: u8 = 11;
+}
+
+/// Address and control fields of the HDLC-like framing
+const HDLC_ADDRESS_CONTROL: [u8; 2] = [0xff, 0x03];
+
+/// Header of a control protocol message: code, identifier and length
+const CONTROL_HEADER_LENGTH: usize = 4;
+
+/// Header of the captured SLIP frames: direction and (compressed) header
+const SLIP_HEADER_LENGTH: usize = 16;
+
+/// Parse a PPP frame obtaining the representation of its content
+pub fn parse_ppp_frame(frame: &[u8], id: usize) -> ParsedPacket {
+    parse_frame(LinkType::Ppp, frame, FrameMeta::new(id))
+}
+
+/// Dissect a PPP frame with the state of a parser context
+pub(crate) fn dissect_ppp_frame(
+    state: &mut ParserContext,
+    frame: &[u8],
+    meta: PacketMeta,
+) -> ParsedPacket {
+    let mut parsed_packet = ParsedPacket::with_meta(meta);
+
+    let header = frame.strip_prefix(&HDLC_ADDRESS_CONTROL).unwrap_or(frame);
+    // Protocol numbers are odd in their last byte: a compressed field is that byte alone
+    let (protocol, payload) = match header {
+        [protocol, payload @ ..] if protocol & 0x01 == 1 => (*protocol as u16, payload),
+        [high, low, payload @ ..] if low & 0x01 == 1 => {
+            (u16::from_be_bytes([*high, *low]), payload)
+        }
+        _ => {
+            debug!("Malformed PPP Frame");
+            parsed_packet.set_link_layer_packet(Some(SerializablePacket::MalformedPacket(
+                "Malformed PPP Frame".to_owned(),
+            )));
+            return parsed_packet;
+        }
+    };
+
+    let control = match protocol {
+        PppProtocols::LCP | PppProtocols::IPCP | PppProtocols::IPV6CP => {
+            parse_control_message(protocol, payload)
+        }
+        _ => None,
+    };
+    debug!(
+        "PPP Frame: {}; length: {}",
+        protocol_to_string(protocol),
+        frame.len()
+    );
+
+    parsed_packet.set_link_layer_packet(Some(SerializablePacket::PppPacket(
+        SerializablePppPacket {
+            protocol,
+            protocol_name: protocol_to_string(protocol),
+            control,
+            length: frame.len(),
+        },
+    )));
+
+    match protocol {
+        PppProtocols::IPV4 => handle_ipv4_packet(state, payload, &mut parsed_packet),
+        PppProtocols::IPV6 => handle_ipv6_packet(state, payload, &mut parsed_packet),
+        _ => {}
+    }
+
+    parsed_packet
+}
+
+/// Parse a SLIP frame, preceded by its capture header, obtaining the representation of its content
+pub fn parse_slip_frame(frame: &[u8], id: usize) -> ParsedPacket {
+    parse_frame(LinkType::Slip, frame, FrameMeta::new(id))
+}
+
+/// Dissect a SLIP frame with the state of a parser context
+pub(crate) fn dissect_slip_frame(
+    state: &mut ParserContext,
+    frame: &[u8],
+    meta: PacketMeta,
+) -> ParsedPacket {
+    let mut parsed_packet = ParsedPacket::with_meta(meta);
+
+    if frame.len() < SLIP_HEADER_LENGTH {
+        debug!("Malformed SLIP Frame");
+        parsed_packet.set_link_layer_packet(Some(SerializablePacket::MalformedPacket(
+            "Malformed SLIP Frame".to_owned(),
+        )));
+        return parsed_packet;
+    }
+
+    let packet_type = match frame[1] & 0xf0 {
+        0x40 => "IP",
+        0x70 => "Uncompressed TCP",
+        header if header & 0x80 != 0 => "Compressed TCP",
+        _ => "Unknown",
+    };
+    parsed_packet.set_link_layer_packet(Some(SerializablePacket::SlipPacket(
+        SerializableSlipPacket {
+            direction: match frame[0] {
+                0 => "Received".to_owned(),
+                _ => "Sent".to_owned(),
+            },
+            packet_type: packet_type.to_owned(),
+            length: frame.len(),
+        },
+    )));
+
+    let payload = &frame[SLIP_HEADER_LENGTH..];
+    match payload.first().map(|byte| byte >> 4) {
+        Some(4) => handle_ipv4_packet(state, payload, &mut parsed_packet),
+        Some(6) => handle_ipv6_packet(state, payload, &mut parsed_packet),
+        _ => debug!("SLIP Frame without IP packet"),
+    }
+
+    parsed_packet
+}
+
+/// Parse a message of a control protocol (LCP, IPCP or IPV6CP)
+fn parse_control_message(protocol: u16, packet: &[u8]) -> Option<PppControlMessage> {
+    if packet.len() < CONTROL_HEADER_LENGTH {
+        return None;
+    }
+
+    let code = packet[0];
+    let length = u16::from_be_bytes([packet[2], packet[3]]) as usize;
+    let data = packet.get(CONTROL_HEADER_LENGTH..length)?;
+
+    let mut options = vec![];
+    let mut magic_number = None;
+    match code {
+        ControlCodes::CONFIGURE_REQUEST..=ControlCodes::CONFIGURE_REJECT => {
+            let mut rest = data;
+            while !rest.is_empty() {
+                let option_length = *rest.get(1)? as usize;
+                if option_length < 2 || option_length > rest.len() {
+                    return None;
+                }
+                options.push(parse_option(protocol, rest[0], &rest[2..option_length]));
+                rest = &rest[option_length..];
+            }
+        }
+        ControlCodes::ECHO_REQUEST..=ControlCodes::DISCARD_REQUEST
+            if protocol == PppProtocols::LCP =>
+        {
+            magic_number = Some(u32::from_be_bytes(data.get(..4)?.try_into().unwrap()));
+        }
+        _ => {}
+    }
+
+    Some(PppControlMessage {
+        code,
+        code_name: code_to_string(code),
+        identifier: packet[1],
+        options,
+        magic_number,
+    })
+}
+
+/// Parse a configuration option, given the control protocol it belongs to
+fn parse_option(protocol: u16, option_type: u8, value: &[u8]) -> PppOption {
+    let number = || match value {
+        [high, low] => u16::from_be_bytes([*high, *low]).to_string(),
+        _ => to_hex(value),
+    };
+    let address = || match value {
+        [a, b, c, d] => Ipv4Addr::new(*a, *b, *c, *d).to_string(),
+        _ => to_hex(value),
+    };
+
+    let (name, value) = match (protocol, option_type) {
+        (PppProtocols::LCP, 1) => ("Maximum-Receive-Unit", number()),
+        (PppProtocols::LCP, 2) => ("Async-Control-Character-Map", to_hex(value)),
+        (PppProtocols::LCP, 3) => ("Authentication-Protocol", auth_protocol_to_string(value)),
+        (PppProtocols::LCP, 4) => ("Quality-Protocol", to_hex(value)),
+        (PppProtocols::LCP, 5) => ("Magic-Number", to_hex(value)),
+        (PppProtocols::LCP, 7) => ("Protocol-Field-Compression", String::new()),
+        (PppProtocols::LCP, 8) => ("Address-and-Control-Field-Compression", String::new()),
+        (PppProtocols::IPCP, 2) => ("IP-Compression-Protocol", to_hex(value)),
+        (PppProtocols::IPCP, 3) => ("IP-Address", address()),
+        (PppProtocols::IPCP, 129) => ("Primary-DNS-Address", address()),
+        (PppProtocols::IPCP, 130) => ("Primary-NBNS-Address", address()),
+        (PppProtocols::IPCP, 131) => ("Secondary-DNS-Address", address()),
+        (PppProtocols::IPCP, 132) => ("Secondary-NBNS-Address", address()),
+        (PppProtocols::IPV6CP, 1) => ("Interface-Identifier", to_hex(value)),
+        _ => ("Unknown", to_hex(value)),
+    };
+
+    PppOption {
+        option_type,
+        name: name.to_owned(),
+        value,
+    }
+}
+
+/// Get the authentication protocol of an LCP option, with the algorithm of CHAP
+fn auth_protocol_to_string(value: &[u8]) -> String {
+    match value {
+        [0xc2, 0x23, algorithm] => {
+            let algorithm = match algorithm {
+                5 => "MD5",
+                0x80 => "MS-CHAP",
+                0x81 => "MS-CHAPv2",
+                _ => "Unknown",
+            };
+            format!("CHAP ({})", algorithm)
+        }
+        [high, low, ..] => protocol_to_string(u16::from_be_bytes([*high, *low])),
+        _ => to_hex(value),
+    }
+}
+
+/// Get the name of a PPP protocol
+fn protocol_to_string(protocol: u16) -> String {
+    let name = match protocol {
+        PppProtocols::IPV4 => "IPv4",
+        PppProtocols::VJ_COMPRESSED_TCP => "Van Jacobson Compressed TCP/IP",
+        PppProtocols::VJ_UNCOMPRESSED_TCP => "Van Jacobson Uncompressed TCP/IP",
+        PppProtocols::IPV6 => "IPv6",
+        PppProtocols::IPCP => "IPCP",
+        PppProtocols::IPV6CP => "IPV6CP",
+        PppProtocols::CCP => "CCP",
+        PppProtocols::LCP => "LCP",
+        PppProtocols::PAP => "PAP",
+        PppProtocols::LQR => "LQR",
+        PppProtocols::CHAP => "CHAP",
+        PppProtocols::EAP => "EAP",
+        _ => return format!("Unknown (0x{:04x})", protocol),
+    };
+
+    name.to_owned()
+}
+
+/// Get the name of a control protocol message code
+fn code_to_string(code: u8) -> String {
+    let name = match code {
+        1 => "Configure-Request",
+        2 => "Configure-Ack",
+        3 => "Configure-Nak",
+        4 => "Configure-Reject",
+        5 => "Terminate-Request",
+        6 => "Terminate-Ack",
+        7 => "Code-Reject",
+        8 => "Protocol-Reject",
+        9 => "Echo-Request",
+        10 => "Echo-Reply",
+        11 => "Discard-Request",
+        _ => "Unknown",
+    };
+
+    name.to_owned()
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::serializable_packet::SerializablePacket;
+
+    use super::{parse_ppp_frame, parse_slip_frame};
+
+    #[test]
+    fn lcp_configure_request() {
+        let frame = [
+            0xff, 0x03, 0xc0, 0x21, 0x01, 0x07, 0x00, 0x13, 0x01, 0x04, 0x05, 0xdc, 0x03, 0x05,
+            0xc2, 0x23, 0x05, 0x05, 0x06, 0x12, 0x34, 0x56, 0x78,
+        ];
+
+        let parsed_packet = parse_ppp_frame(&frame, 0);
+        match parsed_packet.get_link_layer_packet().unwrap() {
+            SerializablePacket::PppPacket(ppp_packet) => {
+                assert_eq!(ppp_packet.protocol_name, "LCP");
+                let control = ppp_packet.control.as_ref().unwrap();
+                assert_eq!(control.code_name, "Configure-Request");
+                assert_eq!(control.identifier, 7);
+                assert_eq!(control.options.len(), 3);
+                assert_eq!(control.options[0].name, "Maximum-Receive-Unit");
+                assert_eq!(control.options[0].value, "1500");
+                assert_eq!(control.options[1].value, "CHAP (MD5)");
+                assert_eq!(control.options[2].value, "12345678");
+            }
+            _ => unreachable!(),
+        }
+        assert!(parsed_packet.get_network_layer_packet().is_none());
+    }
+
+    #[test]
+    fn ipcp_configure_ack() {
+        let frame = [
+            0x80, 0x21, 0x02, 0x01, 0x00, 0x10, 0x03, 0x06, 0x0a, 0x40, 0x00, 0x07, 0x81, 0x06,
+            0x08, 0x08, 0x08, 0x08,
+        ];
+
+        match parse_ppp_frame(&frame, 0).get_link_layer_packet().unwrap() {
+            SerializablePacket::PppPacket(ppp_packet) => {
+                let control = ppp_packet.control.as_ref().unwrap();
+                assert_eq!(control.code_name, "Configure-Ack");
+                assert_eq!(control.options[0].name, "IP-Address");
+                assert_eq!(control.options[0].value, "10.64.0.7");
+                assert_eq!(control.options[1].name, "Primary-DNS-Address");
+                assert_eq!(control.options[1].value, "8.8.8.8");
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn ip_packets() {
+        // Compressed protocol field, without address and control fields
+        let ppp_frame = [&[0x21][..], &build_test_ipv4_packet()].concat();
+        let parsed_packet = parse_ppp_frame(&ppp_frame, 0);
+        assert!(matches!(
+            parsed_packet.get_link_layer_packet(),
+            Some(SerializablePacket::PppPacket(_))
+        ));
+        assert!(matches!(
+            parsed_packet.get_network_layer_packet(),
+            Some(SerializablePacket::Ipv4Packet(_))
+        ));
+
+        let mut slip_frame = vec![0x01, 0x45];
+        slip_frame.resize(16, 0);
+        slip_frame.extend(build_test_ipv4_packet());
+        let parsed_packet = parse_slip_frame(&slip_frame, 0);
+        match parsed_packet.get_link_layer_packet().unwrap() {
+            SerializablePacket::SlipPacket(slip_packet) => {
+                assert_eq!(slip_packet.direction, "Sent");
+                assert_eq!(slip_packet.packet_type, "IP");
+                assert_eq!(slip_packet.length, 44);
+            }
+            _ => unreachable!(),
+        }
+        assert!(matches!(
+            parsed_packet.get_network_layer_packet(),
+            Some(SerializablePacket::Ipv4Packet(_))
+        ));
+    }
+
+    #[test]
+    fn malformed_frames() {
+        assert!(matches!(
+            parse_ppp_frame(&[0xff, 0x03, 0x00], 0).get_link_layer_packet(),
+            Some(SerializablePacket::MalformedPacket(_))
+        ));
+        assert!(matches!(
+            parse_slip_frame(&[0x00, 0x45], 0).get_link_layer_packet(),
+            Some(SerializablePacket::MalformedPacket(_))
+        ));
+    }
+
+    ///////////////////// Utils
+
+    /// IPv4 packet from 10.64.0.7 to 8.8.8.8, carrying an empty UDP datagram
+    fn build_test_ipv4_packet() -> Vec<u8> {
+        vec![
+            0x45, 0x00, 0x00, 0x1c, 0x00, 0x00, 0x00, 0x00, 0x40, 0x11, 0x00, 0x00, 0x0a, 0x40,
+            0x00, 0x07, 0x08, 0x08, 0x08, 0x08, 0xc3, 0x50, 0x00, 0x09, 0x00, 0x08, 0x00, 0x00,
+        ]
+    }
+}
@@ -0,0 +1,438 @@
+//! Reassembly of the TCP byte streams
+//!
+//! The payloads of the TCP segments are handed to the application-layer parsers in the order of
+//! their sequence numbers, whatever the order they are captured in:
+//! - retransmitted and duplicated bytes are handed over only once
+//! - segments following missing bytes are held until these arrive, then handed over with them
+//! - once the held segments exceed `MAX_HELD_BYTES`, the missing bytes are given up on, and the
+//!   gap they leave in the stream is reported with the payload following it
+//!
+//! Each direction of a connection is a separate stream, starting after its SYN or, for the
+//! connections opened before the capture, at the first segment captured.
+
+use std::{borrow::Cow, net::IpAddr};
+
+use crate::flows::FlowKey;
+use crate::serializable_packet::ReassemblyGap;
+use crate::ParserContext;
+
+/// Maximum size of the segments held for each stream while waiting for missing bytes
+const MAX_HELD_BYTES: usize = 1 << 20;
+
+/// Direction of a TCP connection being reassembled
+#[derive(Debug)]
+pub(crate) struct TcpStream {
+    /// Sequence number of the first byte of the stream
+    initial_sequence: u32,
+    /// Sequence number of the next byte to hand over
+    next_sequence: u32,
+    /// Segments received after missing bytes, by their sequence number
+    held: Vec<(u32, Vec<u8>)>,
+    held_bytes: usize,
+    /// Position in the stream of the next byte to hand over, missing bytes included
+    offset: u64,
+    /// Missing bytes given up on since the last payload handed over
+    gap: Option<ReassemblyGap>,
+}
+
+impl TcpStream {
+    fn new(initial_sequence: u32) -> Self {
+        TcpStream {
+            initial_sequence,
+            next_sequence: initial_sequence,
+            held: vec![],
+            held_bytes: 0,
+            offset: 0,
+            gap: None,
+        }
+    }
+
+    /// Add the payload of a segment, getting the bytes following the ones handed over so far
+    fn push<'a>(&mut self, sequence: u32, payload: &'a [u8]) -> Cow<'a, [u8]> {
+        // Sequence numbers wrap around: distances are compared as in RFC 1982
+        let ahead = sequence.wrapping_sub(self.next_sequence) as i32;
+        if ahead > 0 {
+            return self.hold(sequence, payload);
+        }
+
+        let behind = ahead.unsigned_abs() as usize;
+        if behind >= payload.len() {
+            return Cow::Borrowed(&[]);
+        }
+        let payload = &payload[behind..];
+        self.next_sequence = self.next_sequence.wrapping_add(payload.len() as u32);
+        self.offset += payload.len() as u64;
+
+        if self.held.is_empty() {
+            Cow::Borrowed(payload)
+        } else {
+            let mut data = payload.to_vec();
+            self.release(&mut data);
+            Cow::Owned(data)
+        }
+    }
+
+    /// Hold a segment following missing bytes, giving up on them if too many bytes are held
+    fn hold<'a>(&mut self, sequence: u32, payload: &[u8]) -> Cow<'a, [u8]> {
+        let is_held = self
+            .held
+            .iter()
+            .any(|(held, segment)| *held == sequence && segment.len() >= payload.len());
+        if payload.is_empty() || is_held {
+            return Cow::Borrowed(&[]);
+        }
+
+        self.held.push((sequence, payload.to_vec()));
+        self.held_bytes += payload.len();
+        if self.held_bytes <= MAX_HELD_BYTES {
+            return Cow::Borrowed(&[]);
+        }
+
+        let next_sequence = self.next_sequence;
+        if let Some(first) = self
+            .held
+            .iter()
+            .map(|(held, _)| *held)
+            .min_by_key(|held| held.wrapping_sub(next_sequence))
+        {
+            let length = first.wrapping_sub(next_sequence);
+            self.gap = Some(ReassemblyGap {
+                offset: self.offset,
+                length,
+            });
+            self.next_sequence = first;
+            self.offset += length as u64;
+        }
+        let mut data = vec![];
+        self.release(&mut data);
+        Cow::Owned(data)
+    }
+
+    /// Append the held segments no longer following missing bytes
+    fn release(&mut self, data: &mut Vec<u8>) {
+        while let Some(index) = self
+            .held
+            .iter()
+            .position(|(held, _)| held.wrapping_sub(self.next_sequence) as i32 <= 0)
+        {
+            let (sequence, segment) = self.held.swap_remove(index);
+            self.held_bytes -= segment.len();
+
+            let behind = self.next_sequence.wrapping_sub(sequence) as usize;
+            if behind < segment.len() {
+                data.extend_from_slice(&segment[behind..]);
+                self.next_sequence = self
+                    .next_sequence
+                    .wrapping_add((segment.len() - behind) as u32);
+                self.offset += (segment.len() - behind) as u64;
+            }
+        }
+    }
+}
+
+/// Add the payload of a TCP segment to the stream of its direction, getting the bytes to hand
+/// over to the application-layer parsers and the missing bytes given up on before them, if any
+///
+/// A SYN starts the stream again, unless it is a retransmission of the one starting it.
+pub(crate) fn reassemble<'a>(
+    state: &mut ParserContext,
+    source: (IpAddr, u16),
+    destination: (IpAddr, u16),
+    sequence: u32,
+    is_syn: bool,
+    payload: &'a [u8],
+) -> (Cow<'a, [u8]>, Option<ReassemblyGap>) {
+    // The SYN takes up a sequence number before the data
+    let sequence = sequence.wrapping_add(is_syn as u32);
+
+    let stream = state
+        .tcp_streams
+        .entry(FlowKey::of(source, destination))
+        .or_insert_with(|| TcpStream::new(sequence));
+    if is_syn && stream.initial_sequence != sequence {
+        *stream = TcpStream::new(sequence);
+    }
+
+    let data = stream.push(sequence, payload);
+    (data, stream.gap.take())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr};
+
+    use pnet::packet::tcp::MutableTcpPacket;
+    use pnet::packet::Packet;
+    use proptest::collection::vec;
+    use proptest::prelude::*;
+    use proptest::sample::Index;
+
+    use super::{reassemble, TcpStream, MAX_HELD_BYTES};
+    use crate::serializable_packet::{ParsedPacket, ReassemblyGap, SerializablePacket};
+    use crate::{handle_tcp_packet, ParserContext};
+
+    const CLIENT: (IpAddr, u16) = (IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)), 50000);
+
+    proptest! {
+        #[test]
+        fn stream_is_reassembled(
+            (segments, data) in vec(any::<u8>(), 1..2000)
+                .prop_flat_map(|data| (segments(data.len()), Just(data))),
+            initial_sequence in any::<u32>(),
+        ) {
+            let mut stream = TcpStream::new(initial_sequence);
+            let mut reassembled = vec![];
+            for (start, end) in segments {
+                let sequence = initial_sequence.wrapping_add(start as u32);
+                reassembled.extend_from_slice(&stream.push(sequence, &data[start..end]));
+            }
+
+            prop_assert_eq!(reassembled, data);
+            prop_assert!(stream.held.is_empty());
+        }
+
+        #[test]
+        fn http_request_is_reassembled(
+            (segments, (path, request)) in http_request()
+                .prop_flat_map(|request| (segments(request.1.len()), Just(request))),
+            initial_sequence in any::<u32>(),
+        ) {
+            let server = (IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2)), 80);
+            let packets = replay(server, initial_sequence, &request, &segments);
+
+            let requests: Vec<String> = packets
+                .iter()
+                .filter_map(|packet| match packet.get_application_layer_packet() {
+                    Some(SerializablePacket::HttpRequestPacket(request)) => {
+                        Some(request.path.clone())
+                    }
+                    _ => None,
+                })
+                .collect();
+            prop_assert_eq!(requests, vec![path]);
+        }
+
+        #[test]
+        fn tls_records_are_reassembled(
+            (segments, lengths) in vec(1..300usize, 1..5)
+                .prop_flat_map(|lengths| (segments(tls_records(&lengths).len()), Just(lengths))),
+            initial_sequence in any::<u32>(),
+        ) {
+            let server = (IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2)), 443);
+            let packets = replay(server, initial_sequence, &tls_records(&lengths), &segments);
+
+            let parsed: Vec<(usize, u16)> = packets
+                .iter()
+                .filter_map(|packet| match packet.get_application_layer_packet() {
+                    Some(SerializablePacket::TlsPacket(tls)) => Some(tls.records.clone()),
+                    _ => None,
+                })
+                .flatten()
+                .map(|record| (record.index, record.length))
+                .collect();
+            let expected: Vec<(usize, u16)> = lengths
+                .iter()
+                .enumerate()
+                .map(|(index, length)| (index, *length as u16))
+                .collect();
+            prop_assert_eq!(parsed, expected);
+        }
+
+        #[test]
+        fn dns_message_survives_retransmissions(
+            id in any::<u16>(),
+            labels in vec("[a-z0-9]{1,20}", 1..4),
+            retransmissions in 0..4usize,
+            initial_sequence in any::<u32>(),
+        ) {
+            let server = (IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2)), 53);
+            let message = dns_query(id, &labels);
+            let segments = vec![(0, message.len()); retransmissions + 1];
+            let packets = replay(server, initial_sequence, &message, &segments);
+
+            let ids: Vec<u16> = packets
+                .iter()
+                .filter_map(|packet| match packet.get_application_layer_packet() {
+                    Some(SerializablePacket::DnsPacket(dns)) => Some(dns.header.id),
+                    _ => None,
+                })
+                .collect();
+            prop_assert_eq!(ids, vec![id]);
+        }
+    }
+
+    #[test]
+    fn missing_bytes_are_given_up() {
+        let mut stream = TcpStream::new(0);
+        let segment = vec![1; MAX_HELD_BYTES / 2];
+
+        assert!(stream.push(10, &segment).is_empty());
+        assert!(stream.push(10 + segment.len() as u32, &segment).is_empty());
+        // The first 10 bytes are never captured
+        let released = stream.push(10 + 2 * segment.len() as u32, &[2]);
+        assert_eq!(released.len(), 2 * segment.len() + 1);
+        assert_eq!(
+            stream.gap.take(),
+            Some(ReassemblyGap {
+                offset: 0,
+                length: 10
+            })
+        );
+        assert_eq!(stream.push(0, &[0; 10]).len(), 0);
+    }
+
+    #[test]
+    fn syn_starts_stream() {
+        let mut state = ParserContext::new();
+
+        let server = (IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2)), 80);
+
+        assert!(reassemble(&mut state, CLIENT, server, 100, true, &[])
+            .0
+            .is_empty());
+        assert_eq!(
+            &*reassemble(&mut state, CLIENT, server, 101, false, b"GET").0,
+            b"GET"
+        );
+        // Retransmitted SYN
+        assert!(reassemble(&mut state, CLIENT, server, 100, true, &[])
+            .0
+            .is_empty());
+        assert_eq!(
+            &*reassemble(&mut state, CLIENT, server, 101, false, b"GET /").0,
+            b" /"
+        );
+        // New connection on the same ports
+        assert!(reassemble(&mut state, CLIENT, server, 5000, true, &[])
+            .0
+            .is_empty());
+        assert_eq!(
+            &*reassemble(&mut state, CLIENT, server, 5001, false, b"GET").0,
+            b"GET"
+        );
+    }
+
+    ///////////////////// Utils
+
+    /// Split data of the given length into segments, some of them duplicated or overlapping, in
+    /// any order
+    fn segments(len: usize) -> impl Strategy<Value = Vec<(usize, usize)>> {
+        (
+            vec(1..len.max(2), 0..8),
+            vec((0..len, 0..len), 0..4),
+            vec(any::<Index>(), 0..4),
+        )
+            .prop_flat_map(move |(cuts, overlapping, duplicates)| {
+                let mut bounds = cuts;
+                bounds.retain(|bound| *bound < len);
+                bounds.extend([0, len]);
+                bounds.sort_unstable();
+                bounds.dedup();
+
+                let mut segments: Vec<(usize, usize)> = bounds
+                    .windows(2)
+                    .map(|bound| (bound[0], bound[1]))
+                    .collect();
+                for index in duplicates {
+                    segments.push(*index.get(&segments));
+                }
+                for (start, end) in overlapping {
+                    segments.push((start.min(end), start.max(end) + 1));
+                }
+
+                Just(segments).prop_shuffle()
+            })
+    }
+
+    fn http_request() -> impl Strategy<Value = (String, Vec<u8>)> {
+        ("/[a-z0-9]{0,30}", vec(any::<u8>(), 0..500)).prop_map(|(path, body)| {
+            let mut request = format!(
+                "POST {} HTTP/1.1\r\nHost: example.com\r\nContent-Type: application/octet-stream\r\nContent-Length: {}\r\n\r\n",
+                path,
+                body.len()
+            )
+            .into_bytes();
+            request.extend(body);
+
+            (path, request)
+        })
+    }
+
+    fn tls_records(lengths: &[usize]) -> Vec<u8> {
+        lengths
+            .iter()
+            .flat_map(|length| {
+                let mut record = vec![0x17, 0x03, 0x03];
+                record.extend((*length as u16).to_be_bytes());
+                record.extend(vec![0xaa; *length]);
+                record
+            })
+            .collect()
+    }
+
+    fn dns_query(id: u16, labels: &[String]) -> Vec<u8> {
+        let mut message = id.to_be_bytes().to_vec();
+        message.extend([0x01, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
+        for label in labels {
+            message.push(label.len() as u8);
+            message.extend(label.as_bytes());
+        }
+        message.extend([0x00, 0x00, 0x01, 0x00, 0x01]);
+
+        let mut framed = (message.len() as u16).to_be_bytes().to_vec();
+        framed.extend(message);
+        framed
+    }
+
+    /// Parse the segments of a client stream, after a SYN, as captured
+    fn replay(
+        server: (IpAddr, u16),
+        initial_sequence: u32,
+        data: &[u8],
+        segments: &[(usize, usize)],
+    ) -> Vec<ParsedPacket> {
+        let mut state = ParserContext::new();
+
+        let mut packets = vec![tcp_segment(&mut state, server, initial_sequence, true, &[])];
+        for (start, end) in segments {
+            let sequence = initial_sequence.wrapping_add(1 + *start as u32);
+            packets.push(tcp_segment(
+                &mut state,
+                server,
+                sequence,
+                false,
+                &data[*start..*end],
+            ));
+        }
+
+        packets
+    }
+
+    fn tcp_segment(
+        state: &mut ParserContext,
+        server: (IpAddr, u16),
+        sequence: u32,
+        is_syn: bool,
+        payload: &[u8],
+    ) -> ParsedPacket {
+        let mut buffer = vec![0u8; 20 + payload.len()];
+        let mut tcp_packet = MutableTcpPacket::new(&mut buffer).unwrap();
+        tcp_packet.set_source(CLIENT.1);
+        tcp_packet.set_destination(server.1);
+        tcp_packet.set_sequence(sequence);
+        tcp_packet.set_data_offset(5);
+        tcp_packet.set_flags(if is_syn { 0b10 } else { 0b1_0000 });
+        tcp_packet.set_window(65535);
+        tcp_packet.set_payload(payload);
+
+        let mut parsed_packet = ParsedPacket::new(0);
+        handle_tcp_packet(
+            state,
+            CLIENT.0,
+            server.0,
+            tcp_packet.packet(),
+            &mut parsed_packet,
+        );
+        parsed_packet
+    }
+}
@@ -0,0 +1,274 @@
+//! Cross-references between the packets of a sniffing session
+//!
+//! Packets are identified by the id they are parsed with. Each packet references the earlier
+//! packets it is related to, so that the frontend can link them:
+//! - responses reference their request (HTTP, DNS, ICMP echo)
+//! - reassembled messages reference the earlier TCP segments carrying them
+//! - ICMP errors reference the last packet of the flow they are about
+//!
+//! Packets are handed over as soon as they are parsed, so only earlier packets are referenced:
+//! the references the other way round are obtained by reversing these.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    net::IpAddr,
+};
+
+use crate::{
+    serializable_packet::{PacketRelation, ParsedPacket, SerializablePacket},
+    Flow, FlowKey, ParserContext,
+};
+
+/// Maximum number of HTTP requests of a flow waiting for their responses
+const MAX_PENDING_REQUESTS: usize = 64;
+
+/// Packets that later packets may reference, by the flow they belong to
+#[derive(Debug, Default)]
+pub(crate) struct PacketLinks {
+    /// Last packet of each flow
+    last_packets: HashMap<Flow, usize>,
+    /// HTTP requests of each flow waiting for their responses, the oldest first
+    http_requests: HashMap<Flow, VecDeque<usize>>,
+    /// DNS queries waiting for their responses, by flow and DNS id
+    dns_queries: HashMap<(Flow, u16), usize>,
+    /// ICMP echo requests waiting for their replies, by flow, identifier and sequence number
+    echo_requests: HashMap<(Flow, u16, u16), usize>,
+    /// TCP segments carrying the message being reassembled on each flow
+    segments: HashMap<Flow, Vec<usize>>,
+}
+
+impl PacketLinks {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.last_packets.is_empty()
+            && self.http_requests.is_empty()
+            && self.dns_queries.is_empty()
+            && self.echo_requests.is_empty()
+            && self.segments.is_empty()
+    }
+}
+
+/// Record a packet as the last one of its flow
+pub(crate) fn link_flow_packet(
+    state: &mut ParserContext,
+    parsed_packet: &ParsedPacket,
+    source: (IpAddr, u16),
+    destination: (IpAddr, u16),
+) {
+    state
+        .links
+        .last_packets
+        .insert((source, destination), parsed_packet.get_id());
+}
+
+/// Reference the TCP segments carrying the message reassembled by a packet or, if the message is
+/// still being reassembled, record the packet as one of them
+pub(crate) fn link_segment(
+    state: &mut ParserContext,
+    parsed_packet: &mut ParsedPacket,
+    source: (IpAddr, u16),
+    destination: (IpAddr, u16),
+) {
+    let flow = (source, destination);
+
+    if parsed_packet.get_application_layer_packet().is_some() {
+        for id in state.links.segments.remove(&flow).unwrap_or_default() {
+            parsed_packet.add_reference(id, PacketRelation::Segment);
+        }
+    } else if is_buffered(state, &flow) {
+        state
+            .links
+            .segments
+            .entry(flow)
+            .or_default()
+            .push(parsed_packet.get_id());
+    }
+}
+
+/// Reference the request answered by the application-layer packet of a packet, or record it if
+/// it is a request
+pub(crate) fn link_application_packet(
+    state: &mut ParserContext,
+    parsed_packet: &mut ParsedPacket,
+    source: (IpAddr, u16),
+    destination: (IpAddr, u16),
+) {
+    let id = parsed_packet.get_id();
+    let links = &mut state.links;
+
+    let request = match parsed_packet.get_application_layer_packet() {
+        Some(SerializablePacket::HttpRequestPacket(_)) => {
+            let requests = links
+                .http_requests
+                .entry((source, destination))
+                .or_default();
+            if requests.len() == MAX_PENDING_REQUESTS {
+                requests.pop_front();
+            }
+            requests.push_back(id);
+            None
+        }
+        Some(SerializablePacket::HttpResponsePacket(_)) => links
+            .http_requests
+            .get_mut(&(destination, source))
+            .and_then(VecDeque::pop_front),
+        Some(SerializablePacket::DnsPacket(dns)) if dns.header.query => {
+            links
+                .dns_queries
+                .insert(((source, destination), dns.header.id), id);
+            None
+        }
+        Some(SerializablePacket::DnsPacket(dns)) => links
+            .dns_queries
+            .remove(&((destination, source), dns.header.id)),
+        _ => None,
+    };
+
+    if let Some(request) = request {
+        parsed_packet.add_reference(request, PacketRelation::Request);
+    }
+}
+
+/// Reference the ICMP echo request answered by a reply, or record the request
+pub(crate) fn link_echo(
+    state: &mut ParserContext,
+    parsed_packet: &mut ParsedPacket,
+    source: IpAddr,
+    destination: IpAddr,
+    identifier: u16,
+    sequence: u16,
+    is_reply: bool,
+) {
+    let id = parsed_packet.get_id();
+    let links = &mut state.links;
+
+    let request = if is_reply {
+        let flow = ((destination, 0), (source, 0));
+        links.echo_requests.remove(&(flow, identifier, sequence))
+    } else {
+        let flow = ((source, 0), (destination, 0));
+        links.echo_requests.insert((flow, identifier, sequence), id);
+        None
+    };
+
+    if let Some(request) = request {
+        parsed_packet.add_reference(request, PacketRelation::Request);
+    }
+}
+
+/// Reference the last packet of the flow an ICMP error is about
+pub(crate) fn link_icmp_error(
+    state: &ParserContext,
+    parsed_packet: &mut ParsedPacket,
+    original: Flow,
+) {
+    if let Some(last_packet) = state.links.last_packets.get(&original) {
+        parsed_packet.add_reference(*last_packet, PacketRelation::Original);
+    }
+}
+
+/// Forget the packets of the flows for which `is_alive` is false
+pub(crate) fn drop_links(state: &mut ParserContext, is_alive: impl Fn(&Flow) -> bool) {
+    let links = &mut state.links;
+    links.last_packets.retain(|flow, _| is_alive(flow));
+    links.http_requests.retain(|flow, _| is_alive(flow));
+    links.dns_queries.retain(|(flow, _), _| is_alive(flow));
+    links.echo_requests.retain(|(flow, _, _), _| is_alive(flow));
+    links.segments.retain(|flow, _| is_alive(flow));
+}
+
+/// Check if a parser holds bytes of a message still being reassembled on a flow
+fn is_buffered(state: &ParserContext, flow: &Flow) -> bool {
+    let (key, direction) = FlowKey::of(flow.0, flow.1);
+
+    state
+        .http_parsers
+        .get(&key)
+        .is_some_and(|connection| !connection.buffer(direction).is_empty())
+        || state
+            .tls_parsers
+            .get(&(key, direction))
+            .is_some_and(|buffer| !buffer.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr};
+
+    use super::{link_echo, link_flow_packet, link_icmp_error, link_segment};
+    use crate::serializable_packet::{
+        PacketReference, PacketRelation, ParsedPacket, SerializablePacket,
+    };
+    use crate::{FlowKey, ParserContext};
+
+    const CLIENT: (IpAddr, u16) = (IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)), 50000);
+    const SERVER: (IpAddr, u16) = (IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2)), 80);
+
+    #[test]
+    fn segments_of_message() {
+        let mut state = ParserContext::new();
+
+        let (key, direction) = FlowKey::of(CLIENT, SERVER);
+        for id in 1..=2 {
+            state
+                .http_parsers
+                .entry(key)
+                .or_default()
+                .buffer_mut(direction)
+                .push(0);
+            link_segment(&mut state, &mut ParsedPacket::new(id), CLIENT, SERVER);
+        }
+
+        let mut parsed_packet = ParsedPacket::new(3);
+        parsed_packet
+            .set_application_layer_packet(Some(SerializablePacket::MalformedPacket("".into())));
+        link_segment(&mut state, &mut parsed_packet, CLIENT, SERVER);
+        assert_eq!(
+            parsed_packet.get_references(),
+            &[
+                PacketReference {
+                    id: 1,
+                    relation: PacketRelation::Segment
+                },
+                PacketReference {
+                    id: 2,
+                    relation: PacketRelation::Segment
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn echo_reply_and_icmp_error() {
+        let mut state = ParserContext::new();
+
+        link_echo(
+            &mut state,
+            &mut ParsedPacket::new(1),
+            CLIENT.0,
+            SERVER.0,
+            7,
+            1,
+            false,
+        );
+        let mut reply = ParsedPacket::new(2);
+        link_echo(&mut state, &mut reply, SERVER.0, CLIENT.0, 7, 1, true);
+        assert_eq!(
+            reply.get_references(),
+            &[PacketReference {
+                id: 1,
+                relation: PacketRelation::Request
+            }]
+        );
+
+        link_flow_packet(&mut state, &ParsedPacket::new(3), CLIENT, SERVER);
+        let mut error = ParsedPacket::new(4);
+        link_icmp_error(&state, &mut error, (CLIENT, SERVER));
+        assert_eq!(
+            error.get_references(),
+            &[PacketReference {
+                id: 3,
+                relation: PacketRelation::Original
+            }]
+        );
+    }
+}
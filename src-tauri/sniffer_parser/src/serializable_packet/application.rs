@@ -1,23 +1,29 @@
 //! Application level Packets Representation
 
 use std::{
-    net::{Ipv4Addr, Ipv6Addr},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
     str::from_utf8,
 };
 
 use dns_parser::{Header as DnsHeader, Packet as DnsPacket, Question, RData, ResourceRecord};
 use httparse::{Request, Response};
+use md5::{Digest, Md5};
 use serde::Serialize;
+use sha1::Sha1;
 use tls_parser::{
     parse_dh_params, parse_ec_parameters, parse_ecdh_params, parse_tls_extensions, ECParameters,
     ECParametersContent, ECPoint, ExplicitPrimeContent, NamedGroup, ServerDHParams,
     ServerECDHParams, TlsCertificateContents, TlsCertificateRequestContents,
-    TlsCertificateStatusContents, TlsClientHelloContents, TlsClientKeyExchangeContents,
-    TlsExtension, TlsHelloRetryRequestContents, TlsMessageAlert, TlsMessageHeartbeat,
-    TlsNewSessionTicketContent, TlsNextProtocolContent, TlsRecordType, TlsServerHelloContents,
-    TlsServerHelloV13Draft18Contents, TlsServerKeyExchangeContents, TlsVersion,
+    TlsCertificateStatusContents, TlsCipherSuite, TlsClientHelloContents,
+    TlsClientKeyExchangeContents, TlsExtension, TlsHelloRetryRequestContents, TlsMessageAlert,
+    TlsMessageHeartbeat, TlsNewSessionTicketContent, TlsNextProtocolContent, TlsRecordType,
+    TlsServerHelloContents, TlsServerHelloV13Draft18Contents, TlsServerKeyExchangeContents,
+    TlsVersion,
+};
+use x509_parser::{
+    extensions::GeneralName, parse_x509_certificate, prelude::X509Certificate,
+    public_key::PublicKey, time::ASN1Time,
 };
-use x509_parser::{parse_x509_certificate, prelude::X509Certificate};
 
 /// HTTP Body content
 #[derive(Serialize, Debug, Clone)]
@@ -39,9 +45,15 @@ pub enum HttpContentType {
 pub struct SerializableHttpRequestPacket {
     pub method: String,
     pub path: String,
+    /// Minor version of HTTP/1.x, or 9 for HTTP/0.9
     pub version: u8,
     pub headers: Vec<(String, String)>,
+    /// Number of the last headers, sent as trailer fields after a chunked body
+    pub trailers: usize,
     pub payload: HttpContentType,
+    pub registry: Option<DockerRegistryRequest>,
+    /// Deviations from the HTTP/1.x syntax tolerated while parsing the request
+    pub quirks: Vec<String>,
 }
 
 impl<'a, 'b> SerializableHttpRequestPacket {
@@ -62,19 +74,47 @@ impl<'a, 'b> SerializableHttpRequestPacket {
                     )
                 })
                 .collect(),
+            trailers: 0,
             payload,
+            registry: None,
+            quirks: vec![],
         }
     }
 }
 
+/// Docker registry (distribution v2 API) endpoints
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub enum DockerRegistryEndpoint {
+    Base,
+    Catalog,
+    Tags,
+    Manifest,
+    Blob,
+    BlobUpload,
+    Referrers,
+}
+
+/// Docker registry request, as identified by its path
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct DockerRegistryRequest {
+    pub endpoint: DockerRegistryEndpoint,
+    pub image: Option<String>,
+    pub reference: Option<String>,
+}
+
 /// HTTP Response Packet Representation
 #[derive(Serialize, Debug, Clone)]
 pub struct SerializableHttpResponsePacket {
+    /// Minor version of HTTP/1.x, or 9 for HTTP/0.9
     pub version: u8,
     pub code: u16,
     pub reason: String,
     pub headers: Vec<(String, String)>,
+    /// Number of the last headers, sent as trailer fields after a chunked body
+    pub trailers: usize,
     pub payload: HttpContentType,
+    /// Deviations from the HTTP/1.x syntax tolerated while parsing the response
+    pub quirks: Vec<String>,
 }
 
 impl<'a, 'b> SerializableHttpResponsePacket {
@@ -95,7 +135,9 @@ impl<'a, 'b> SerializableHttpResponsePacket {
                     )
                 })
                 .collect(),
+            trailers: 0,
             payload,
+            quirks: vec![],
         }
     }
 }
@@ -113,7 +155,11 @@ pub enum TlsMalformedError {
 pub struct SerializableTlsPacket {
     pub version: String,
     pub messages: Vec<CustomTlsMessage>,
+    pub records: Vec<SerializableTlsRecord>,
     pub length: u16,
+    pub handshake: Option<SerializableTlsHandshakePacket>,
+    /// Certificate chain sent by the peer, the leaf certificate first
+    pub certificates: Vec<SerializableTlsCertificate>,
 }
 
 impl SerializableTlsPacket {
@@ -127,14 +173,34 @@ impl SerializableTlsPacket {
         self.messages = messages;
     }
 
+    /// Set the transported TLS records metadata
+    pub fn set_records(&mut self, records: Vec<SerializableTlsRecord>) {
+        self.records = records;
+    }
+
     /// Set the TLS packet length
     pub fn set_length(&mut self, length: u16) {
         self.length = length;
     }
 
+    /// Set the details of the ClientHello or ServerHello carried by the packet
+    pub fn set_handshake(&mut self, handshake: SerializableTlsHandshakePacket) {
+        self.handshake = Some(handshake);
+    }
+
+    /// Set the certificate chain carried by the packet
+    pub fn set_certificates(&mut self, certificates: Vec<SerializableTlsCertificate>) {
+        self.certificates = certificates;
+    }
+
     /// Check if TLS packet is not initialized
     pub fn is_default(&self) -> bool {
-        self.length == 0 && self.messages.is_empty() && self.version == "".to_owned()
+        self.length == 0
+            && self.messages.is_empty()
+            && self.records.is_empty()
+            && self.handshake.is_none()
+            && self.certificates.is_empty()
+            && self.version == "".to_owned()
     }
 }
 
@@ -143,7 +209,298 @@ impl Default for SerializableTlsPacket {
         SerializableTlsPacket {
             version: "".to_owned(),
             messages: vec![],
+            records: vec![],
             length: 0,
+            handshake: None,
+            certificates: vec![],
+        }
+    }
+}
+
+/// TLS Extension Types (IANA)
+#[allow(non_snake_case)]
+mod TlsExtensionTypes {
+    pub const SERVER_NAME: u16 = 0;
+    pub const SUPPORTED_GROUPS: u16 = 10;
+    pub const EC_POINT_FORMATS: u16 = 11;
+    pub const ALPN: u16 = 16;
+    pub const SUPPORTED_VERSIONS: u16 = 43;
+}
+
+/// Details of a TLS ClientHello or ServerHello, the messages readable even if the rest of the
+/// connection is encrypted
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct SerializableTlsHandshakePacket {
+    pub message_type: String,
+    pub version: String,
+    pub server_name: Option<String>,
+    /// Cipher suites offered by the client
+    pub cipher_suites: Vec<String>,
+    /// Cipher suite selected by the server
+    pub selected_cipher_suite: Option<String>,
+    pub alpn_protocols: Vec<String>,
+    pub supported_versions: Vec<String>,
+    /// Names of the extensions, in the order they are sent
+    pub extensions: Vec<String>,
+    /// Fingerprint of the client, for a ClientHello
+    pub ja3: Option<TlsFingerprint>,
+    /// Fingerprint of the server, for a ServerHello
+    pub ja3s: Option<TlsFingerprint>,
+}
+
+/// JA3/JA3S fingerprint: the hello fields it is computed from, and their MD5 hash
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct TlsFingerprint {
+    pub text: String,
+    pub hash: String,
+}
+
+impl TlsFingerprint {
+    fn new(fields: &[String]) -> Self {
+        let text = fields.join(",");
+
+        TlsFingerprint {
+            hash: format!("{:x}", Md5::digest(text.as_bytes())),
+            text,
+        }
+    }
+}
+
+impl SerializableTlsHandshakePacket {
+    pub fn from_client_hello(message: &TlsClientHelloContents) -> Self {
+        let extensions = raw_tls_extensions(message.ext.unwrap_or(b""));
+
+        SerializableTlsHandshakePacket {
+            message_type: "ClientHello".to_owned(),
+            version: format!("{}", message.version),
+            server_name: server_name(&extensions),
+            cipher_suites: message
+                .ciphers
+                .iter()
+                .map(|cipher| cipher_suite_name(cipher.0))
+                .collect(),
+            selected_cipher_suite: None,
+            alpn_protocols: alpn_protocols(&extensions),
+            supported_versions: supported_versions(&extensions, true),
+            extensions: extensions
+                .iter()
+                .map(|(ext_type, _)| tls_extension_name(*ext_type))
+                .collect(),
+            ja3: Some(TlsFingerprint::new(&[
+                message.version.0.to_string(),
+                fingerprint_list(message.ciphers.iter().map(|cipher| cipher.0)),
+                fingerprint_list(extensions.iter().map(|(ext_type, _)| *ext_type)),
+                fingerprint_list(
+                    raw_tls_extension(&extensions, TlsExtensionTypes::SUPPORTED_GROUPS)
+                        .and_then(|data| data.get(2..))
+                        .unwrap_or_default()
+                        .chunks_exact(2)
+                        .map(|group| u16::from_be_bytes([group[0], group[1]])),
+                ),
+                fingerprint_list(
+                    raw_tls_extension(&extensions, TlsExtensionTypes::EC_POINT_FORMATS)
+                        .and_then(|data| data.get(1..))
+                        .unwrap_or_default()
+                        .iter()
+                        .map(|format| *format as u16),
+                ),
+            ])),
+            ja3s: None,
+        }
+    }
+
+    pub fn from_server_hello(message: &TlsServerHelloContents) -> Self {
+        let extensions = raw_tls_extensions(message.ext.unwrap_or(b""));
+
+        SerializableTlsHandshakePacket {
+            message_type: "ServerHello".to_owned(),
+            version: format!("{}", message.version),
+            server_name: None,
+            cipher_suites: vec![],
+            selected_cipher_suite: Some(cipher_suite_name(message.cipher.0)),
+            alpn_protocols: alpn_protocols(&extensions),
+            supported_versions: supported_versions(&extensions, false),
+            extensions: extensions
+                .iter()
+                .map(|(ext_type, _)| tls_extension_name(*ext_type))
+                .collect(),
+            ja3: None,
+            ja3s: Some(TlsFingerprint::new(&[
+                message.version.0.to_string(),
+                message.cipher.0.to_string(),
+                fingerprint_list(extensions.iter().map(|(ext_type, _)| *ext_type)),
+            ])),
+        }
+    }
+}
+
+/// Split a block of TLS extensions into their types and data, up to the first truncated one
+pub(crate) fn raw_tls_extensions(mut data: &[u8]) -> Vec<(u16, &[u8])> {
+    let mut extensions = vec![];
+
+    while data.len() >= 4 {
+        let ext_type = u16::from_be_bytes([data[0], data[1]]);
+        let length = u16::from_be_bytes([data[2], data[3]]) as usize;
+        match data.get(4..4 + length) {
+            Some(ext_data) => extensions.push((ext_type, ext_data)),
+            None => break,
+        }
+        data = &data[4 + length..];
+    }
+
+    extensions
+}
+
+/// Join the values of a fingerprint field with dashes, skipping the GREASE ones
+fn fingerprint_list(values: impl Iterator<Item = u16>) -> String {
+    values
+        .filter(|value| !is_grease(*value))
+        .map(|value| value.to_string())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// Check if a value is reserved by GREASE (RFC 8701), sent to keep the ecosystem extensible
+pub(crate) fn is_grease(value: u16) -> bool {
+    value & 0x0f0f == 0x0a0a && value >> 8 == value & 0xff
+}
+
+fn raw_tls_extension<'a>(extensions: &[(u16, &'a [u8])], ext_type: u16) -> Option<&'a [u8]> {
+    extensions
+        .iter()
+        .find(|(current, _)| *current == ext_type)
+        .map(|(_, data)| *data)
+}
+
+/// Get the host name of the Server Name Indication extension
+fn server_name(extensions: &[(u16, &[u8])]) -> Option<String> {
+    let data = raw_tls_extension(extensions, TlsExtensionTypes::SERVER_NAME)?;
+
+    // List length, then entries of name type (0: host name), length and name
+    let mut entries = data.get(2..)?;
+    while entries.len() >= 3 {
+        let length = u16::from_be_bytes([entries[1], entries[2]]) as usize;
+        let name = entries.get(3..3 + length)?;
+        if entries[0] == 0 {
+            return from_utf8(name).ok().map(str::to_owned);
+        }
+        entries = &entries[3 + length..];
+    }
+
+    None
+}
+
+/// Get the protocols of the Application-Layer Protocol Negotiation extension
+fn alpn_protocols(extensions: &[(u16, &[u8])]) -> Vec<String> {
+    let mut protocols = vec![];
+    let mut entries = match raw_tls_extension(extensions, TlsExtensionTypes::ALPN) {
+        Some(data) if data.len() >= 2 => &data[2..],
+        _ => return protocols,
+    };
+
+    while let Some(length) = entries.first() {
+        let length = *length as usize;
+        match entries.get(1..1 + length) {
+            Some(protocol) => {
+                protocols.push(String::from_utf8_lossy(protocol).into_owned());
+                entries = &entries[1 + length..];
+            }
+            None => break,
+        }
+    }
+
+    protocols
+}
+
+/// Get the versions of the Supported Versions extension: a list sent by the client, the selected
+/// one sent by the server
+fn supported_versions(extensions: &[(u16, &[u8])], is_client: bool) -> Vec<String> {
+    let versions = match raw_tls_extension(extensions, TlsExtensionTypes::SUPPORTED_VERSIONS) {
+        Some(data) if is_client && !data.is_empty() => &data[1..],
+        Some(data) => data,
+        None => return vec![],
+    };
+
+    versions
+        .chunks_exact(2)
+        .map(|version| u16::from_be_bytes([version[0], version[1]]))
+        .filter(|version| !is_grease(*version))
+        .map(|version| format!("{}", TlsVersion(version)))
+        .collect()
+}
+
+/// Get the IANA name of a cipher suite
+fn cipher_suite_name(id: u16) -> String {
+    if is_grease(id) {
+        return "GREASE".to_owned();
+    }
+
+    match TlsCipherSuite::from_id(id) {
+        Some(cipher_suite) => cipher_suite.name.to_owned(),
+        None => format!("Unknown (0x{:04x})", id),
+    }
+}
+
+/// Get the IANA name of an extension type
+fn tls_extension_name(ext_type: u16) -> String {
+    if is_grease(ext_type) {
+        return "GREASE".to_owned();
+    }
+
+    match ext_type {
+        0 => "server_name",
+        1 => "max_fragment_length",
+        5 => "status_request",
+        10 => "supported_groups",
+        11 => "ec_point_formats",
+        13 => "signature_algorithms",
+        14 => "use_srtp",
+        15 => "heartbeat",
+        16 => "application_layer_protocol_negotiation",
+        18 => "signed_certificate_timestamp",
+        21 => "padding",
+        22 => "encrypt_then_mac",
+        23 => "extended_master_secret",
+        27 => "compress_certificate",
+        28 => "record_size_limit",
+        34 => "delegated_credentials",
+        35 => "session_ticket",
+        41 => "pre_shared_key",
+        42 => "early_data",
+        43 => "supported_versions",
+        44 => "cookie",
+        45 => "psk_key_exchange_modes",
+        47 => "certificate_authorities",
+        48 => "oid_filters",
+        49 => "post_handshake_auth",
+        50 => "signature_algorithms_cert",
+        51 => "key_share",
+        57 => "quic_transport_parameters",
+        13172 => "next_protocol_negotiation",
+        17513 => "application_settings",
+        65037 => "encrypted_client_hello",
+        65281 => "renegotiation_info",
+        _ => return format!("Unknown ({})", ext_type),
+    }
+    .to_owned()
+}
+
+/// TLS Record metadata, available even if the record content is encrypted
+#[derive(Serialize, Debug, Clone)]
+pub struct SerializableTlsRecord {
+    pub version: String,
+    pub record_type: String,
+    pub length: u16,
+    pub index: usize,
+}
+
+impl SerializableTlsRecord {
+    pub fn new(version: TlsVersion, record_type: TlsRecordType, length: u16, index: usize) -> Self {
+        SerializableTlsRecord {
+            version: format!("{}", version),
+            record_type: record_type.to_string(),
+            length,
+            index,
         }
     }
 }
@@ -186,10 +543,14 @@ pub enum CustomHandshakeMessage {
 }
 
 /// TLS Alert Message
+///
+/// The level and the code identify the localized descriptions of the severity and of the alert.
 #[derive(Serialize, Debug, Clone)]
 pub struct CustomAlertMessage {
     pub severity: String,
     pub description: String,
+    pub level: u8,
+    pub code: u8,
 }
 
 impl CustomAlertMessage {
@@ -197,6 +558,8 @@ impl CustomAlertMessage {
         CustomAlertMessage {
             severity: message.severity.to_string(),
             description: message.code.to_string(),
+            level: message.severity.0,
+            code: message.code.0,
         }
     }
 }
@@ -528,6 +891,83 @@ impl CertificateMessage {
     }
 }
 
+/// Details of a certificate of the chain sent in a TLS Certificate message, telling who a host
+/// is talking to
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct SerializableTlsCertificate {
+    pub subject: String,
+    pub issuer: String,
+    /// DNS names, IP addresses, e-mails and URIs the certificate is valid for
+    pub subject_alt_names: Vec<String>,
+    pub serial: String,
+    pub not_before: String,
+    pub not_after: String,
+    pub key_type: String,
+    /// Size of the public key, in bits
+    pub key_size: usize,
+    /// SHA-1 hash of the DER-encoded certificate, as colon-separated bytes
+    pub fingerprint: String,
+}
+
+impl SerializableTlsCertificate {
+    /// Parse a DER-encoded certificate, `None` if it is malformed
+    pub fn from_der(data: &[u8]) -> Option<Self> {
+        let (_, cert) = parse_x509_certificate(data).ok()?;
+        let public_key = cert.public_key();
+        let (key_type, key_size) = match public_key.parsed() {
+            Ok(PublicKey::RSA(key)) => ("RSA".to_owned(), key.key_size()),
+            Ok(PublicKey::EC(key)) => ("EC".to_owned(), key.key_size()),
+            Ok(key @ PublicKey::DSA(_)) => ("DSA".to_owned(), key.key_size()),
+            Ok(key @ PublicKey::GostR3410(_)) | Ok(key @ PublicKey::GostR3410_2012(_)) => {
+                ("GOST R 34.10".to_owned(), key.key_size())
+            }
+            Ok(PublicKey::Unknown(_)) | Err(_) => (public_key.algorithm.oid().to_id_string(), 0),
+        };
+
+        Some(SerializableTlsCertificate {
+            subject: cert.subject().to_string(),
+            issuer: cert.issuer().to_string(),
+            subject_alt_names: subject_alt_names(&cert),
+            serial: cert.raw_serial_as_string(),
+            not_before: certificate_time(cert.validity().not_before),
+            not_after: certificate_time(cert.validity().not_after),
+            key_type,
+            key_size,
+            fingerprint: Sha1::digest(data)
+                .iter()
+                .map(|byte| format!("{:02X}", byte))
+                .collect::<Vec<String>>()
+                .join(":"),
+        })
+    }
+}
+
+fn subject_alt_names(cert: &X509Certificate) -> Vec<String> {
+    let names = match cert.subject_alternative_name() {
+        Ok(Some(extension)) => &extension.value.general_names,
+        _ => return vec![],
+    };
+
+    names
+        .iter()
+        .map(|name| match name {
+            GeneralName::DNSName(name) | GeneralName::RFC822Name(name) | GeneralName::URI(name) => {
+                name.to_string()
+            }
+            GeneralName::IPAddress(&[a, b, c, d]) => Ipv4Addr::new(a, b, c, d).to_string(),
+            GeneralName::IPAddress(bytes) => match <[u8; 16]>::try_from(*bytes) {
+                Ok(bytes) => IpAddr::from(bytes).to_string(),
+                Err(_) => name.to_string(),
+            },
+            _ => name.to_string(),
+        })
+        .collect()
+}
+
+fn certificate_time(time: ASN1Time) -> String {
+    time.to_rfc2822().unwrap_or_else(|_| time.to_string())
+}
+
 /// TLS Certificate Revocation Request: list hash algorithms
 #[derive(Serialize, Debug, Clone)]
 pub struct CertificateRequestMessage {
@@ -1095,7 +1535,7 @@ impl From<&RData<'_>> for CustomResourceData {
             }),
             RData::MX(mx) => CustomResourceData::MX(Mx {
                 preference: mx.preference,
-                exchange: mx.preference.to_string(),
+                exchange: mx.exchange.to_string(),
             }),
             RData::NS(ns) => CustomResourceData::NS(Ns {
                 name: ns.0.to_string(),
@@ -1123,6 +1563,10 @@ impl From<&RData<'_>> for CustomResourceData {
                     acc.extend_from_slice(x);
                     acc
                 }),
+                strings: txt
+                    .iter()
+                    .map(|x| String::from_utf8_lossy(x).into_owned())
+                    .collect(),
             }),
             RData::Unknown(unknown) => CustomResourceData::Unknown(Unknown {
                 data: unknown.to_vec(),
@@ -1193,6 +1637,7 @@ pub struct Srv {
 #[derive(Serialize, Debug, Clone)]
 pub struct Txt {
     pub data: Vec<u8>,
+    pub strings: Vec<String>,
 }
 
 /// DNS Unknown Resource Data
@@ -1200,3 +1645,597 @@ pub struct Txt {
 pub struct Unknown {
     pub data: Vec<u8>,
 }
+
+/// PTP (IEEE 1588) and gPTP (IEEE 802.1AS) Packet Representation
+#[derive(Serialize, Debug, Clone)]
+pub struct SerializablePtpPacket {
+    pub transport_specific: u8,
+    pub gptp: bool,
+    pub message_type: String,
+    pub version: u8,
+    pub length: u16,
+    pub domain: u8,
+    pub flags: u16,
+    pub two_step: bool,
+    pub correction_ns: f64,
+    pub source_port: PtpPortIdentity,
+    pub sequence_id: u16,
+    pub log_message_interval: i8,
+    pub timestamp: Option<PtpTimestamp>,
+    pub requesting_port: Option<PtpPortIdentity>,
+    pub announce: Option<PtpAnnounce>,
+    pub offset_estimate: Option<PtpOffsetEstimate>,
+}
+
+/// PTP clock identity and port number
+#[derive(Serialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PtpPortIdentity {
+    pub clock_identity: String,
+    pub port_number: u16,
+}
+
+/// PTP timestamp (origin, precise origin or receive one, depending on the message type)
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PtpTimestamp {
+    pub seconds: u64,
+    pub nanoseconds: u32,
+}
+
+impl PtpTimestamp {
+    /// Get the timestamp in nanoseconds
+    pub fn as_nanos(&self) -> i128 {
+        self.seconds as i128 * 1_000_000_000 + self.nanoseconds as i128
+    }
+}
+
+/// PTP Announce message content
+#[derive(Serialize, Debug, Clone)]
+pub struct PtpAnnounce {
+    pub current_utc_offset: i16,
+    pub grandmaster_priority1: u8,
+    pub grandmaster_clock_class: u8,
+    pub grandmaster_clock_accuracy: u8,
+    pub grandmaster_clock_variance: u16,
+    pub grandmaster_priority2: u8,
+    pub grandmaster_identity: String,
+    pub steps_removed: u16,
+    pub time_source: u8,
+}
+
+/// Estimate of the offset of the capture clock from the master clock, and of the path delay
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PtpOffsetEstimate {
+    pub offset_ns: i64,
+    pub mean_path_delay_ns: i64,
+}
+
+/// IEC 61850 GOOSE Packet Representation
+#[derive(Serialize, Debug, Clone)]
+pub struct SerializableGoosePacket {
+    pub appid: u16,
+    pub length: u16,
+    pub simulation: bool,
+    pub gocb_ref: String,
+    pub time_allowed_to_live: u32,
+    pub dataset: String,
+    pub go_id: Option<String>,
+    pub timestamp: Option<Iec61850Timestamp>,
+    pub st_num: u32,
+    pub sq_num: u32,
+    pub test: bool,
+    pub conf_rev: u32,
+    pub nds_com: bool,
+    pub num_dataset_entries: u32,
+    pub all_data_entries: usize,
+}
+
+/// IEC 61850 Sampled Values Packet Representation
+#[derive(Serialize, Debug, Clone)]
+pub struct SerializableSvPacket {
+    pub appid: u16,
+    pub length: u16,
+    pub simulation: bool,
+    pub asdus: Vec<SvAsdu>,
+}
+
+/// Sampled Values Application Service Data Unit
+#[derive(Serialize, Debug, Clone)]
+pub struct SvAsdu {
+    pub sv_id: String,
+    pub dataset: Option<String>,
+    pub smp_cnt: u16,
+    pub conf_rev: u32,
+    pub refr_tm: Option<Iec61850Timestamp>,
+    pub smp_synch: u8,
+    pub smp_rate: Option<u16>,
+    pub samples: Vec<SvSample>,
+    pub smp_mod: Option<u16>,
+}
+
+/// Sampled value with its quality, as encoded by IEC 61850-9-2 LE
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct SvSample {
+    pub value: i32,
+    pub quality: u32,
+}
+
+/// IEC 61850 UtcTime
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Iec61850Timestamp {
+    pub seconds: u32,
+    pub nanoseconds: u32,
+    pub quality: u8,
+}
+
+/// PROFINET Real-Time Packet Representation
+#[derive(Serialize, Debug, Clone)]
+pub struct SerializableProfinetPacket {
+    pub frame_id: u16,
+    pub frame_type: String,
+    pub cycle_counter: Option<u16>,
+    pub data_status: Option<u8>,
+    pub data_valid: Option<bool>,
+    pub provider_running: Option<bool>,
+    pub transfer_status: Option<u8>,
+    pub data_length: usize,
+}
+
+/// EtherCAT Packet Representation
+#[derive(Serialize, Debug, Clone)]
+pub struct SerializableEthercatPacket {
+    pub length: u16,
+    pub frame_type: u8,
+    pub datagrams: Vec<EthercatDatagram>,
+}
+
+/// EtherCAT datagram header, with its working counter
+#[derive(Serialize, Debug, Clone)]
+pub struct EthercatDatagram {
+    pub command: String,
+    pub index: u8,
+    pub slave_address: Option<u16>,
+    pub offset_address: Option<u16>,
+    pub logical_address: Option<u32>,
+    pub length: u16,
+    pub circulating: bool,
+    pub more_follows: bool,
+    pub irq: u16,
+    pub working_counter: u16,
+}
+
+/// S7comm Packet Representation, with its TPKT/COTP encapsulation
+#[derive(Serialize, Debug, Clone)]
+pub struct SerializableS7commPacket {
+    pub tpkt_length: u16,
+    pub cotp_pdu_type: String,
+    pub header: Option<S7Header>,
+    pub function: Option<String>,
+    pub items: Vec<S7Item>,
+    pub return_codes: Vec<String>,
+}
+
+/// S7comm header
+#[derive(Serialize, Debug, Clone)]
+pub struct S7Header {
+    pub rosctr: String,
+    pub pdu_reference: u16,
+    pub parameter_length: u16,
+    pub data_length: u16,
+    pub error_class: Option<u8>,
+    pub error_code: Option<u8>,
+}
+
+/// S7comm read/write item address
+#[derive(Serialize, Debug, Clone)]
+pub struct S7Item {
+    pub area: String,
+    pub db_number: Option<u16>,
+    pub byte_address: u32,
+    pub bit_address: u8,
+    pub transport_size: String,
+    pub count: u16,
+    pub address: String,
+}
+
+/// iSCSI Packet Representation: the PDUs starting in a TCP segment
+#[derive(Serialize, Debug, Clone)]
+pub struct SerializableIscsiPacket {
+    pub pdus: Vec<IscsiPdu>,
+}
+
+/// iSCSI PDU basic header segment
+#[derive(Serialize, Debug, Clone)]
+pub struct IscsiPdu {
+    pub opcode: String,
+    pub immediate: bool,
+    pub final_pdu: bool,
+    pub total_ahs_length: u8,
+    pub data_segment_length: u32,
+    pub lun: Option<u16>,
+    pub initiator_task_tag: u32,
+    pub cmd_sn: Option<u32>,
+    pub stat_sn: Option<u32>,
+    pub scsi_command: Option<String>,
+    pub scsi_status: Option<u8>,
+}
+
+/// NVMe/TCP Packet Representation: the PDUs starting in a TCP segment
+#[derive(Serialize, Debug, Clone)]
+pub struct SerializableNvmeTcpPacket {
+    pub pdus: Vec<NvmeTcpPdu>,
+}
+
+/// NVMe/TCP PDU common header, with the capsule or data transfer fields
+#[derive(Serialize, Debug, Clone)]
+pub struct NvmeTcpPdu {
+    pub pdu_type: String,
+    pub flags: u8,
+    pub header_length: u8,
+    pub pdu_data_offset: u8,
+    pub pdu_length: u32,
+    pub command_id: Option<u16>,
+    pub opcode: Option<u8>,
+    pub command: Option<String>,
+    pub namespace_id: Option<u32>,
+    pub status: Option<u16>,
+    pub data_offset: Option<u32>,
+    pub data_length: Option<u32>,
+}
+
+/// Cassandra CQL native protocol Packet Representation
+#[derive(Serialize, Debug, Clone)]
+pub struct SerializableCqlPacket {
+    pub version: u8,
+    pub response: bool,
+    pub flags: u8,
+    pub stream: i16,
+    pub opcode: String,
+    pub length: u32,
+    pub query: Option<String>,
+    pub consistency: Option<String>,
+    pub prepared_id: Option<String>,
+    pub result_kind: Option<String>,
+    pub error_code: Option<u32>,
+    pub error_message: Option<String>,
+}
+
+/// Kafka wire protocol Packet Representation
+#[derive(Serialize, Debug, Clone)]
+pub struct SerializableKafkaPacket {
+    pub length: i32,
+    pub request: bool,
+    pub api_key: Option<i16>,
+    pub api_name: Option<String>,
+    pub api_version: Option<i16>,
+    pub correlation_id: i32,
+    pub client_id: Option<String>,
+    pub topics: Vec<String>,
+}
+
+/// Zookeeper client protocol Packet Representation
+#[derive(Serialize, Debug, Clone)]
+pub struct SerializableZookeeperPacket {
+    pub length: i32,
+    pub request: bool,
+    pub xid: Option<i32>,
+    pub opcode: Option<i32>,
+    pub operation: Option<String>,
+    pub path: Option<String>,
+    pub watch: Option<bool>,
+    pub zxid: Option<i64>,
+    pub error: Option<i32>,
+    pub event: Option<String>,
+    pub session_id: Option<i64>,
+}
+
+/// HTTP/2 Packet Representation, with the frames of a segment and the messages they complete
+#[derive(Serialize, Debug, Clone)]
+pub struct SerializableHttp2Packet {
+    pub frames: Vec<Http2Frame>,
+    pub messages: Vec<Http2Message>,
+}
+
+/// HTTP/2 frame, with the fields of its type
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct Http2Frame {
+    pub frame_type: String,
+    pub flags: u8,
+    pub stream_id: u32,
+    pub length: u32,
+    pub headers: Vec<(String, String)>,
+    pub settings: Vec<(String, u32)>,
+    pub error_code: Option<String>,
+    pub last_stream_id: Option<u32>,
+}
+
+/// HTTP/2 request or response, rebuilt from the frames of its stream
+///
+/// The method and path of a response are the ones of its request, when seen.
+#[derive(Serialize, Debug, Clone)]
+pub struct Http2Message {
+    pub stream_id: u32,
+    pub request: bool,
+    pub method: Option<String>,
+    pub path: Option<String>,
+    pub authority: Option<String>,
+    pub status: Option<u16>,
+    pub headers: Vec<(String, String)>,
+    pub trailers: Vec<(String, String)>,
+    pub payload: HttpContentType,
+    pub body_length: usize,
+    pub grpc_method: Option<String>,
+    pub grpc_status: Option<u32>,
+}
+
+/// QUIC Packet Representation: the QUIC packets coalesced in a UDP datagram
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct SerializableQuicPacket {
+    pub packets: Vec<QuicPacket>,
+    /// ClientHello or ServerHello completed by the Initial packets of the datagram
+    pub handshake: Option<SerializableTlsHandshakePacket>,
+}
+
+/// Header of a QUIC packet, and frames of the decrypted Initial packets
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct QuicPacket {
+    pub header_form: String,
+    pub packet_type: String,
+    pub version: Option<String>,
+    pub destination_connection_id: Option<String>,
+    pub source_connection_id: Option<String>,
+    /// Length of the packet, header included
+    pub length: usize,
+    pub packet_number: Option<u64>,
+    pub frames: Vec<String>,
+    /// Versions offered by a Version Negotiation packet
+    pub supported_versions: Vec<String>,
+}
+
+/// DHCP (BOOTP) Packet Representation
+///
+/// Times are in seconds.
+#[derive(Serialize, Debug, Clone)]
+pub struct SerializableDhcpPacket {
+    pub op: String,
+    pub hardware_type: u8,
+    pub hardware_address_length: u8,
+    pub hops: u8,
+    pub transaction_id: u32,
+    pub seconds: u16,
+    pub broadcast: bool,
+    /// Address of a client renewing its lease
+    pub client_ip: Ipv4Addr,
+    /// Address offered or assigned to the client
+    pub your_ip: Ipv4Addr,
+    pub server_ip: Ipv4Addr,
+    pub relay_ip: Ipv4Addr,
+    pub client_hardware_address: String,
+    pub server_name: Option<String>,
+    pub boot_file: Option<String>,
+    pub message_type: Option<String>,
+    pub subnet_mask: Option<Ipv4Addr>,
+    pub routers: Vec<Ipv4Addr>,
+    pub dns_servers: Vec<Ipv4Addr>,
+    pub hostname: Option<String>,
+    pub domain_name: Option<String>,
+    pub requested_ip: Option<Ipv4Addr>,
+    pub lease_time: Option<u32>,
+    pub renewal_time: Option<u32>,
+    pub rebinding_time: Option<u32>,
+    pub server_identifier: Option<Ipv4Addr>,
+    /// Options requested by the client (option 55)
+    pub parameter_request_list: Vec<String>,
+    pub vendor_class: Option<String>,
+    pub client_identifier: Option<String>,
+    /// All the options of the message, in order
+    pub options: Vec<DhcpOption>,
+}
+
+/// DHCP option carried by a message
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct DhcpOption {
+    pub code: u8,
+    pub name: String,
+    pub length: usize,
+}
+
+/// SNMP Packet Representation
+///
+/// The fields of the PDU are none for the encrypted SNMPv3 messages.
+#[derive(Serialize, Debug, Clone)]
+pub struct SerializableSnmpPacket {
+    pub version: String,
+    /// Community of SNMPv1 and SNMPv2c
+    pub community: Option<String>,
+    /// SNMPv3 message ID
+    pub message_id: Option<i64>,
+    pub authenticated: bool,
+    pub encrypted: bool,
+    /// SNMPv3 USM user
+    pub user_name: Option<String>,
+    /// SNMPv3 authoritative engine ID, in hexadecimal
+    pub engine_id: Option<String>,
+    pub context_name: Option<String>,
+    pub pdu_type: Option<String>,
+    pub request_id: Option<i64>,
+    pub error_status: Option<String>,
+    pub error_index: Option<i64>,
+    /// GetBulk request fields
+    pub non_repeaters: Option<i64>,
+    pub max_repetitions: Option<i64>,
+    /// SNMPv1 trap fields
+    pub enterprise: Option<String>,
+    pub agent_address: Option<Ipv4Addr>,
+    pub generic_trap: Option<String>,
+    pub specific_trap: Option<i64>,
+    /// Time since the agent started, in hundredths of a second
+    pub timestamp: Option<u64>,
+    pub variable_bindings: Vec<SnmpVariableBinding>,
+}
+
+/// SNMP variable binding: an OID with its value
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct SnmpVariableBinding {
+    /// OID, in dotted notation
+    pub oid: String,
+    pub value_type: String,
+    pub value: String,
+}
+
+/// GTP Packet Representation
+///
+/// GTPv2-C messages carry the information elements of the control plane, GTP-U (version 1) ones
+/// the packets of the subscribers.
+#[derive(Serialize, Debug, Clone)]
+pub struct SerializableGtpPacket {
+    pub version: u8,
+    pub message_type: String,
+    /// Tunnel the message is sent to, none for the GTPv2-C messages sent before it is known
+    pub teid: Option<u32>,
+    pub sequence: Option<u32>,
+    pub imsi: Option<String>,
+    pub msisdn: Option<String>,
+    pub apn: Option<String>,
+    pub cause: Option<String>,
+    /// Address allocated to the subscriber
+    pub pdn_address: Option<IpAddr>,
+    /// F-TEIDs of the sender, outside of the bearer contexts
+    pub f_teids: Vec<GtpFTeid>,
+    pub bearers: Vec<GtpBearer>,
+    /// EPS bearer IDs outside of the bearer contexts (e.g. the linked one of a session)
+    pub bearer_ids: Vec<u8>,
+    /// Addresses and protocol of the packet tunnelled by a G-PDU
+    pub inner_source: Option<IpAddr>,
+    pub inner_destination: Option<IpAddr>,
+    pub inner_protocol: Option<String>,
+    pub length: usize,
+}
+
+/// Fully qualified TEID: the tunnel endpoint of a node on an interface
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct GtpFTeid {
+    pub interface: String,
+    pub teid: u32,
+    pub ipv4: Option<Ipv4Addr>,
+    pub ipv6: Option<Ipv6Addr>,
+}
+
+/// Bearer context of a GTPv2-C message
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct GtpBearer {
+    /// EPS bearer ID
+    pub bearer_id: Option<u8>,
+    pub cause: Option<String>,
+    pub f_teids: Vec<GtpFTeid>,
+}
+
+/// IKE Packet Representation
+///
+/// The payloads of IKEv2 messages are decoded until the Encrypted one, i.e. those of the
+/// IKE_SA_INIT exchange; only the header of IKEv1 messages is.
+#[derive(Serialize, Debug, Clone)]
+pub struct SerializableIkePacket {
+    pub major_version: u8,
+    pub minor_version: u8,
+    /// SPIs of the IKE SA, hexadecimal
+    pub initiator_spi: String,
+    pub responder_spi: String,
+    pub exchange_type: String,
+    /// Whether the message is sent by the original initiator of the IKE SA
+    pub initiator: bool,
+    pub response: bool,
+    pub message_id: u32,
+    /// Payloads of the message, in order
+    pub payloads: Vec<String>,
+    /// Proposals of the SA payload
+    pub proposals: Vec<IkeProposal>,
+    /// Diffie-Hellman group of the Key Exchange payload
+    pub dh_group: Option<String>,
+    /// Types of the Notify payloads
+    pub notifications: Vec<String>,
+    /// Whether the address of the sender, or of the receiver, is translated between the peers and
+    /// the capture, by the NAT detection notifications of the message
+    pub source_nat: Option<bool>,
+    pub destination_nat: Option<bool>,
+    /// Whether the message is encapsulated in UDP along with ESP, on the NAT traversal port
+    pub udp_encapsulated: bool,
+    pub length: usize,
+}
+
+/// Proposal of the SA payload of an IKEv2 message
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct IkeProposal {
+    pub number: u8,
+    /// Protocol of the SA negotiated: IKE, AH or ESP
+    pub protocol: String,
+    /// SPI of the SA, hexadecimal, none for the initial IKE SA
+    pub spi: Option<String>,
+    pub transforms: Vec<IkeTransform>,
+}
+
+/// Transform (algorithm) of an IKEv2 proposal
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct IkeTransform {
+    /// Type of the transform: encryption, PRF, integrity, Diffie-Hellman group or ESN
+    pub transform_type: String,
+    pub id: String,
+    pub key_length: Option<u16>,
+}
+
+/// WireGuard Packet Representation
+///
+/// Indexes identify the session on the side of each peer: the sender index is the one chosen by
+/// the peer sending the message, the receiver index the one chosen by the peer it is sent to.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct SerializableWireGuardPacket {
+    pub message_type: String,
+    pub sender_index: Option<u32>,
+    pub receiver_index: Option<u32>,
+    /// Counter of the transport data messages, used as the nonce of their encryption
+    pub counter: Option<u64>,
+    /// Length of the encrypted tunneled packet, with its padding, zero for the keepalives
+    pub length: usize,
+}
+
+/// NTP Packet Representation
+///
+/// The clock fields and the timestamps are the ones of the time messages (modes 1 to 5), the
+/// request and the items the ones of the control (mode 6) and private (mode 7) messages.
+/// Durations and timestamps are in seconds, the latter since the NTP epoch (1900).
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct SerializableNtpPacket {
+    pub leap_indicator: Option<u8>,
+    pub version: u8,
+    pub mode: String,
+    pub stratum: Option<u8>,
+    /// Log2 of the poll interval and of the precision of the clock, in seconds
+    pub poll: Option<i8>,
+    pub precision: Option<i8>,
+    pub root_delay: Option<f64>,
+    pub root_dispersion: Option<f64>,
+    /// Reference clock code, or address of the upstream server
+    pub reference_id: Option<String>,
+    pub reference_timestamp: Option<f64>,
+    pub origin_timestamp: Option<f64>,
+    pub receive_timestamp: Option<f64>,
+    pub transmit_timestamp: Option<f64>,
+    /// Whether a control or private message is a response
+    pub response: bool,
+    pub sequence: Option<u16>,
+    /// Opcode of a control message, or request code of a private one (e.g. MON_GETLIST_1)
+    pub request: Option<String>,
+    /// Implementation of a private message (3 for ntpd)
+    pub implementation: Option<u8>,
+    /// Items of a private message, and their size
+    pub item_count: Option<u16>,
+    pub item_size: Option<u16>,
+    /// Length of the message
+    pub length: usize,
+}
+
+/// Packet Representation of a protocol decoded by a dissector registered at runtime
+#[derive(Serialize, Debug, Clone)]
+pub struct SerializableCustomPacket {
+    pub protocol: String,
+    pub fields: serde_json::Value,
+}
@@ -0,0 +1,152 @@
+//! Bytes of the captured frames, shared by the packets built from them
+//!
+//! Each frame is copied once when parsed, into a buffer given to the parsers with the metadata of
+//! its packets (see [`PacketMeta`](super::PacketMeta)) and shared by the representations of all its
+//! layers: payloads are ranges of it, so building, cloning and truncating packets copy no bytes.
+//! Bytes that are not part of the frame (e.g. decrypted 802.11 frames, reassembled TCP streams)
+//! are moved into a buffer of their own instead.
+//!
+//! The bytes are materialized only when serialized, as an array of numbers like a `Vec<u8>`.
+
+use std::fmt;
+use std::ops::Deref;
+use std::sync::Arc;
+
+use serde::{Serialize, Serializer};
+
+/// Range of the bytes of a captured frame, or of a buffer of its own
+#[derive(Clone, Default)]
+pub struct FrameBytes {
+    buffer: Arc<[u8]>,
+    start: usize,
+    end: usize,
+}
+
+impl FrameBytes {
+    /// Get a slice of the bytes, sharing their buffer, or none if the slice is not taken from them
+    pub fn share(&self, bytes: &[u8]) -> Option<Self> {
+        let start = (bytes.as_ptr() as usize).checked_sub(self.as_ptr() as usize)?;
+        let end = start + bytes.len();
+
+        (end <= self.len()).then(|| FrameBytes {
+            buffer: Arc::clone(&self.buffer),
+            start: self.start + start,
+            end: self.start + end,
+        })
+    }
+
+    /// Get a slice of the bytes, sharing their buffer if it is taken from them, or copying it
+    pub fn share_or_copy(&self, bytes: &[u8]) -> Self {
+        self.share(bytes).unwrap_or_else(|| FrameBytes::from(bytes))
+    }
+
+    /// Keep the first bytes only
+    pub fn truncate(&mut self, length: usize) {
+        self.end = self.end.min(self.start + length);
+    }
+
+    pub fn clear(&mut self) {
+        self.truncate(0);
+    }
+}
+
+impl Deref for FrameBytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.buffer[self.start..self.end]
+    }
+}
+
+impl From<&[u8]> for FrameBytes {
+    fn from(bytes: &[u8]) -> Self {
+        FrameBytes {
+            buffer: Arc::from(bytes),
+            start: 0,
+            end: bytes.len(),
+        }
+    }
+}
+
+impl From<Vec<u8>> for FrameBytes {
+    fn from(bytes: Vec<u8>) -> Self {
+        let end = bytes.len();
+        FrameBytes {
+            buffer: Arc::from(bytes),
+            start: 0,
+            end,
+        }
+    }
+}
+
+impl fmt::Debug for FrameBytes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl PartialEq for FrameBytes {
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+impl Eq for FrameBytes {}
+
+impl PartialEq<[u8]> for FrameBytes {
+    fn eq(&self, other: &[u8]) -> bool {
+        **self == *other
+    }
+}
+
+impl PartialEq<Vec<u8>> for FrameBytes {
+    fn eq(&self, other: &Vec<u8>) -> bool {
+        **self == **other
+    }
+}
+
+impl Serialize for FrameBytes {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.iter())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::FrameBytes;
+
+    #[test]
+    fn bytes_shared_with_frame() {
+        let frame = FrameBytes::from(vec![1, 2, 3, 4, 5, 6]);
+
+        let payload = frame.share(&frame[2..5]).unwrap();
+        let copied = frame.share_or_copy(&[3, 4, 5]);
+        assert_eq!(payload, vec![3, 4, 5]);
+        assert_eq!(copied, payload);
+        assert!(Arc::ptr_eq(&payload.buffer, &frame.buffer));
+        assert!(!Arc::ptr_eq(&copied.buffer, &frame.buffer));
+
+        // Slices of a slice share the buffer of the frame too, but not the bytes around them
+        assert_eq!(payload.share(&payload[1..]).unwrap(), vec![4, 5]);
+        assert!(payload.share(&frame[..3]).is_none());
+        assert!(payload.share(&frame[4..]).is_none());
+
+        let mut truncated = payload.clone();
+        truncated.truncate(2);
+        assert_eq!(truncated, vec![3, 4]);
+        assert_eq!(payload.len(), 3);
+        truncated.clear();
+        assert!(truncated.is_empty());
+    }
+
+    #[test]
+    fn serialized_as_vec() {
+        let bytes = FrameBytes::from(vec![0, 1, 255]);
+        assert_eq!(
+            serde_json::to_string(&bytes).unwrap(),
+            serde_json::to_string(&vec![0u8, 1, 255]).unwrap()
+        );
+    }
+}
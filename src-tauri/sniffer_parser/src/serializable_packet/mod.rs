@@ -6,52 +6,227 @@
 //! - transport_layer_packet
 //! - application_layer_packet
 //!
+//! along with the capture metadata of the packet, available to the dissectors of every layer, the
+//! references to the earlier packets it is related to and, for TCP segments, the bytes of the
+//! stream missing before their payload.
 
 pub mod application;
+pub mod bytes;
 pub mod network;
 pub mod transport;
 #[cfg(feature = "utils")]
 pub mod util;
 
+use std::time::Duration;
+
 use pnet::packet::Packet;
 use pnet::{packet::ethernet::EthernetPacket, util::MacAddr};
 use serde::Serialize;
 
+use crate::oui::mac_vendor;
+
 use self::application::{
-    SerializableDnsPacket, SerializableHttpRequestPacket, SerializableHttpResponsePacket,
-    SerializableTlsPacket,
+    SerializableCqlPacket, SerializableCustomPacket, SerializableDhcpPacket, SerializableDnsPacket,
+    SerializableEthercatPacket, SerializableGoosePacket, SerializableGtpPacket,
+    SerializableHttp2Packet, SerializableHttpRequestPacket, SerializableHttpResponsePacket,
+    SerializableIkePacket, SerializableIscsiPacket, SerializableKafkaPacket, SerializableNtpPacket,
+    SerializableNvmeTcpPacket, SerializableProfinetPacket, SerializablePtpPacket,
+    SerializableQuicPacket, SerializableS7commPacket, SerializableSnmpPacket, SerializableSvPacket,
+    SerializableTlsPacket, SerializableWireGuardPacket, SerializableZookeeperPacket,
 };
+use self::bytes::FrameBytes;
 use self::network::{SerializableArpPacket, SerializableIpv4Packet, SerializableIpv6Packet};
 use self::transport::{
-    SerializableEchoReplyPacket, SerializableEchoRequestPacket, SerializableIcmpPacket,
-    SerializableIcmpv6Packet, SerializableTcpPacket, SerializableUdpPacket,
+    SerializableAhPacket, SerializableEchoReplyPacket, SerializableEchoRequestPacket,
+    SerializableEspPacket, SerializableIcmpAddressMaskPacket, SerializableIcmpPacket,
+    SerializableIcmpTimestampPacket, SerializableIcmpv6Packet,
+    SerializableRouterAdvertisementPacket, SerializableTcpPacket, SerializableUdpPacket,
 };
 
+/// Direction of a packet, as seen from the capturing interface
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum Direction {
+    Inbound,
+    Outbound,
+}
+
+/// Capture metadata of a packet
+#[derive(Serialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct PacketMeta {
+    pub id: usize,
+    /// Capture time, in microseconds since the Unix epoch
+    pub timestamp: u64,
+    /// Capture time since the Unix epoch, with the nanoseconds of the capture files
+    #[serde(skip)]
+    pub capture_time: Duration,
+    /// Interface the packet was captured on, if known
+    pub interface: Option<String>,
+    pub direction: Option<Direction>,
+    /// Bytes of the frame captured, fewer than the ones on the wire when truncated by the capture
+    pub captured_length: usize,
+    pub wire_length: usize,
+    /// Radio information of the frames captured in monitor mode with a Radiotap header
+    pub radio: Option<RadioInfo>,
+    /// Bytes of the captured frame, shared with the payloads taken from it
+    #[serde(skip)]
+    pub frame: FrameBytes,
+}
+
+impl PacketMeta {
+    /// Get the metadata of a packet captured at the given time (since the Unix epoch), nothing else
+    /// being known about its capture
+    pub fn new(id: usize, capture_time: Duration) -> Self {
+        PacketMeta {
+            id,
+            timestamp: capture_time.as_micros() as u64,
+            capture_time,
+            ..Default::default()
+        }
+    }
+
+    /// Check if the frame was truncated by the capture
+    pub fn is_truncated(&self) -> bool {
+        self.captured_length < self.wire_length
+    }
+}
+
+/// Radio information of an 802.11 frame, as reported by the Radiotap header of its capture
+#[derive(Serialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct RadioInfo {
+    /// Signal and noise power at the antenna, in dBm
+    pub signal: Option<i8>,
+    pub noise: Option<i8>,
+    /// Channel frequency in MHz, and its number
+    pub frequency: Option<u16>,
+    pub channel: Option<u16>,
+    /// Data rate, in kbps
+    pub rate: Option<u32>,
+}
+
 /// Data structure containing representations of the packet at each TCP/IP layer
 #[derive(Serialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct ParsedPacket {
-    id: usize,
+    #[serde(flatten)]
+    meta: PacketMeta,
     link_layer_packet: Option<SerializablePacket>,
     network_layer_packet: Option<SerializablePacket>,
     transport_layer_packet: Option<SerializablePacket>,
     application_layer_packet: Option<SerializablePacket>,
+    references: Vec<PacketReference>,
+    reassembly_gap: Option<ReassemblyGap>,
+    /// Bytes of the TCP stream handed over to the application-layer parsers with the packet
+    #[serde(skip)]
+    stream_payload: FrameBytes,
+}
+
+/// Relation of a packet with an earlier packet it references
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PacketRelation {
+    /// The request answered by the packet (HTTP, DNS, ICMP echo)
+    Request,
+    /// A TCP segment carrying part of the message reassembled by the packet
+    Segment,
+    /// The packet of the flow an ICMP error is about
+    Original,
+}
+
+/// Reference to an earlier packet, by its id
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PacketReference {
+    pub id: usize,
+    pub relation: PacketRelation,
+}
+
+/// Bytes of a TCP stream never captured, given up on by the reassembly
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReassemblyGap {
+    /// Position of the first missing byte in the stream
+    pub offset: u64,
+    /// Number of missing bytes
+    pub length: u32,
 }
 
 impl ParsedPacket {
+    /// Build an empty packet, given its id: a sequence number unique in the sniffing session,
+    /// used by the other packets to reference it
+    ///
+    /// The packet is stamped with the current time: the packets of the parsed frames are built
+    /// with the metadata of their capture by [`ParsedPacket::with_meta`].
     pub fn new(id: usize) -> Self {
+        Self::with_meta(PacketMeta::new(id, crate::now()))
+    }
+
+    /// Build an empty packet with the given metadata
+    pub fn with_meta(meta: PacketMeta) -> Self {
         ParsedPacket {
-            id,
+            meta,
             link_layer_packet: None,
             network_layer_packet: None,
             transport_layer_packet: None,
             application_layer_packet: None,
+            references: vec![],
+            reassembly_gap: None,
+            stream_payload: FrameBytes::default(),
         }
     }
 
     /// Get packet unique Identifier
     pub fn get_id(&self) -> usize {
-        self.id
+        self.meta.id
+    }
+
+    /// Get the time the packet was captured, in microseconds since the Unix epoch
+    pub fn get_timestamp(&self) -> u64 {
+        self.meta.timestamp
+    }
+
+    /// Get the capture metadata of the packet
+    pub fn get_meta(&self) -> &PacketMeta {
+        &self.meta
+    }
+
+    /// Get the references to the earlier packets related to this one
+    pub fn get_references(&self) -> &[PacketReference] {
+        &self.references
+    }
+
+    /// Reference an earlier packet related to this one
+    pub fn add_reference(&mut self, id: usize, relation: PacketRelation) {
+        let reference = PacketReference { id, relation };
+        if id != self.meta.id && !self.references.contains(&reference) {
+            self.references.push(reference);
+        }
+    }
+
+    /// Get the bytes of the TCP stream missing before the payload handed to the application-layer
+    /// parsers, if any
+    pub fn get_reassembly_gap(&self) -> Option<ReassemblyGap> {
+        self.reassembly_gap
+    }
+
+    /// Set the bytes of the TCP stream missing before the payload of the packet
+    pub fn set_reassembly_gap(&mut self, reassembly_gap: Option<ReassemblyGap>) {
+        self.reassembly_gap = reassembly_gap;
+    }
+
+    /// Get the bytes of the TCP stream handed over to the application-layer parsers with the
+    /// packet, in order and without the retransmitted ones
+    pub fn get_stream_payload(&self) -> &[u8] {
+        &self.stream_payload
+    }
+
+    /// Take the bytes of the TCP stream handed over with the packet, leaving none
+    pub fn take_stream_payload(&mut self) -> FrameBytes {
+        std::mem::take(&mut self.stream_payload)
+    }
+
+    /// Set the bytes of the TCP stream handed over with the packet
+    pub fn set_stream_payload(&mut self, stream_payload: FrameBytes) {
+        self.stream_payload = stream_payload;
     }
 
     /// Get link layer packet representation
@@ -106,20 +281,53 @@ impl ParsedPacket {
 #[serde(tag = "type", content = "packet")]
 pub enum SerializablePacket {
     EthernetPacket(SerializableEthernetPacket),
+    PppPacket(SerializablePppPacket),
+    SlipPacket(SerializableSlipPacket),
+    LinuxSllPacket(SerializableLinuxSllPacket),
+    LoopbackPacket(SerializableLoopbackPacket),
+    RawPacket(SerializableRawPacket),
+    Ieee80211Packet(SerializableIeee80211Packet),
     ArpPacket(SerializableArpPacket),
     Ipv4Packet(SerializableIpv4Packet),
     Ipv6Packet(SerializableIpv6Packet),
     EchoReplyPacket(SerializableEchoReplyPacket),
     EchoRequestPacket(SerializableEchoRequestPacket),
+    IcmpTimestampPacket(SerializableIcmpTimestampPacket),
+    IcmpAddressMaskPacket(SerializableIcmpAddressMaskPacket),
+    RouterAdvertisementPacket(SerializableRouterAdvertisementPacket),
     IcmpPacket(SerializableIcmpPacket),
     Icmpv6Packet(SerializableIcmpv6Packet),
     TcpPacket(SerializableTcpPacket),
     UdpPacket(SerializableUdpPacket),
+    EspPacket(SerializableEspPacket),
+    AhPacket(SerializableAhPacket),
     HttpRequestPacket(SerializableHttpRequestPacket),
     HttpResponsePacket(SerializableHttpResponsePacket),
+    Http2Packet(SerializableHttp2Packet),
     TlsPacket(SerializableTlsPacket),
+    QuicPacket(SerializableQuicPacket),
     DnsPacket(SerializableDnsPacket),
+    DhcpPacket(SerializableDhcpPacket),
+    SnmpPacket(SerializableSnmpPacket),
+    GtpPacket(SerializableGtpPacket),
+    IkePacket(SerializableIkePacket),
+    WireGuardPacket(SerializableWireGuardPacket),
+    NtpPacket(SerializableNtpPacket),
+    PtpPacket(SerializablePtpPacket),
+    GoosePacket(SerializableGoosePacket),
+    SvPacket(SerializableSvPacket),
+    ProfinetPacket(SerializableProfinetPacket),
+    EthercatPacket(SerializableEthercatPacket),
+    S7commPacket(SerializableS7commPacket),
+    IscsiPacket(SerializableIscsiPacket),
+    NvmeTcpPacket(SerializableNvmeTcpPacket),
+    CqlPacket(SerializableCqlPacket),
+    KafkaPacket(SerializableKafkaPacket),
+    ZookeeperPacket(SerializableZookeeperPacket),
+    CustomPacket(SerializableCustomPacket),
 
+    /// Application message that could not be parsed, as bytes of its TCP stream were missing
+    PartiallyReassembled(ReassemblyGap),
     MalformedPacket(String),
     UnknownPacket(SerializableUnknownPacket),
 }
@@ -129,21 +337,122 @@ pub enum SerializablePacket {
 pub struct SerializableEthernetPacket {
     pub destination: MacAddr,
     pub source: MacAddr,
+    /// Vendors of the addresses, from their OUI
+    pub destination_vendor: Option<&'static str>,
+    pub source_vendor: Option<&'static str>,
     pub ethertype: String,
-    pub payload: Vec<u8>,
+    /// Bytes after the header, shared with the captured frame
+    pub payload: FrameBytes,
 }
 
-impl<'a> From<&EthernetPacket<'a>> for SerializableEthernetPacket {
-    fn from(packet: &EthernetPacket<'a>) -> Self {
+impl SerializableEthernetPacket {
+    /// Build the representation of an Ethernet packet, sharing its payload with the frame it is
+    /// taken from
+    pub fn new(packet: &EthernetPacket, frame: &FrameBytes) -> Self {
         SerializableEthernetPacket {
             destination: packet.get_destination(),
             source: packet.get_source(),
+            destination_vendor: mac_vendor(packet.get_destination()),
+            source_vendor: mac_vendor(packet.get_source()),
             ethertype: packet.get_ethertype().to_string(),
-            payload: packet.payload().to_vec(),
+            payload: frame.share_or_copy(packet.payload()),
         }
     }
 }
 
+/// PPP Packet Representation
+#[derive(Serialize, Debug, Clone)]
+pub struct SerializablePppPacket {
+    pub protocol: u16,
+    pub protocol_name: String,
+    /// LCP, IPCP or IPV6CP message carried by the frame
+    pub control: Option<PppControlMessage>,
+    pub length: usize,
+}
+
+/// Message of a PPP control protocol
+#[derive(Serialize, Debug, Clone)]
+pub struct PppControlMessage {
+    pub code: u8,
+    pub code_name: String,
+    pub identifier: u8,
+    /// Options of the Configure messages
+    pub options: Vec<PppOption>,
+    /// Magic number of the LCP Echo and Discard messages
+    pub magic_number: Option<u32>,
+}
+
+/// Configuration option of a PPP control protocol
+#[derive(Serialize, Debug, Clone)]
+pub struct PppOption {
+    pub option_type: u8,
+    pub name: String,
+    pub value: String,
+}
+
+/// SLIP Packet Representation
+#[derive(Serialize, Debug, Clone)]
+pub struct SerializableSlipPacket {
+    /// Received or Sent by the capturing host
+    pub direction: String,
+    pub packet_type: String,
+    pub length: usize,
+}
+
+/// Linux cooked (SLL and SLL2) Packet Representation
+#[derive(Serialize, Debug, Clone)]
+pub struct SerializableLinuxSllPacket {
+    /// Sent by or to the capturing host, or to another one
+    pub packet_type: String,
+    /// ARPHRD_ type of the interface the frame was captured on
+    pub hardware_type: u16,
+    /// Link-layer address of the sender
+    pub address: String,
+    pub ethertype: String,
+    /// Index of the interface the frame was captured on, in SLL2 frames only
+    pub interface_index: Option<u32>,
+    pub length: usize,
+}
+
+/// Loopback (null and loop) Packet Representation
+#[derive(Serialize, Debug, Clone)]
+pub struct SerializableLoopbackPacket {
+    /// Address family of the packet, as numbered by the operating system of the capturing host
+    pub family: u32,
+    pub family_name: String,
+    pub length: usize,
+}
+
+/// Raw IP Packet Representation, captured without a link-layer header
+#[derive(Serialize, Debug, Clone)]
+pub struct SerializableRawPacket {
+    pub length: usize,
+}
+
+/// IEEE 802.11 Packet Representation, of the frames not converted to Ethernet ones
+#[derive(Serialize, Debug, Clone)]
+pub struct SerializableIeee80211Packet {
+    /// Management, Control, Data or Extension
+    pub frame_type: String,
+    pub subtype: String,
+    pub receiver: MacAddr,
+    /// Address of the transmitter, missing in ACK and CTS frames
+    pub transmitter: Option<MacAddr>,
+    pub bssid: Option<MacAddr>,
+    pub protected: bool,
+    pub retry: bool,
+    /// Network name of the beacons, probe requests and responses, and association requests
+    pub ssid: Option<String>,
+    /// Channel announced by the beacons and probe responses
+    pub channel: Option<u8>,
+    /// Beacon interval, in time units of 1024 microseconds
+    pub beacon_interval: Option<u16>,
+    /// Reason of the deauthentication and disassociation frames
+    pub reason_code: Option<u16>,
+    pub reason: Option<String>,
+    pub length: usize,
+}
+
 /// Unknown Packet Representation
 #[derive(Serialize, Debug, Clone)]
 pub struct SerializableUnknownPacket {
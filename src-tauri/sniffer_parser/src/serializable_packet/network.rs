@@ -1,6 +1,6 @@
 //! Network level Packets Representation
 
-use std::net::{Ipv4Addr, Ipv6Addr};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 
 use pnet::packet::arp::{ArpOperations, ArpPacket};
 use pnet::packet::ipv4::Ipv4Packet;
@@ -9,6 +9,9 @@ use pnet::packet::Packet;
 use pnet::util::MacAddr;
 use serde::Serialize;
 
+use crate::multicast::multicast_group;
+use crate::oui::mac_vendor;
+
 /// ARP Packet Representation
 #[derive(Serialize, Debug, Clone)]
 pub struct SerializableArpPacket {
@@ -21,6 +24,9 @@ pub struct SerializableArpPacket {
     pub sender_proto_addr: Ipv4Addr,
     pub target_hw_addr: MacAddr,
     pub target_proto_addr: Ipv4Addr,
+    /// Vendors of the hardware addresses, from their OUI
+    pub sender_vendor: Option<&'static str>,
+    pub target_vendor: Option<&'static str>,
     pub length: usize,
 }
 
@@ -40,11 +46,25 @@ impl<'a> From<&ArpPacket<'a>> for SerializableArpPacket {
             sender_proto_addr: packet.get_sender_proto_addr(),
             target_hw_addr: packet.get_target_hw_addr(),
             target_proto_addr: packet.get_target_proto_addr(),
+            sender_vendor: mac_vendor(packet.get_sender_hw_addr()),
+            target_vendor: mac_vendor(packet.get_target_hw_addr()),
             length: packet.payload().len(),
         }
     }
 }
 
+/// Location and network of an IP address, as found in the GeoIP databases
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct GeoLocation {
+    /// ISO 3166-1 code and English name of the country
+    pub country_code: Option<String>,
+    pub country: Option<String>,
+    pub city: Option<String>,
+    /// Autonomous system of the network, and the organization operating it
+    pub asn: Option<u32>,
+    pub organization: Option<String>,
+}
+
 /// IPv6 Packet Representation
 #[derive(Serialize, Debug, Clone)]
 pub struct SerializableIpv6Packet {
@@ -56,7 +76,26 @@ pub struct SerializableIpv6Packet {
     pub hop_limit: u8,
     pub source: Ipv6Addr,
     pub destination: Ipv6Addr,
+    /// Registered purpose of the multicast destination (e.g. mDNS)
+    pub multicast_group: Option<String>,
+    /// Locations of the addresses, filled in from the GeoIP databases when set
+    pub source_location: Option<GeoLocation>,
+    pub destination_location: Option<GeoLocation>,
     pub length: usize,
+    /// Extension headers preceding the upper-layer protocol, in order
+    pub extension_headers: Vec<String>,
+    /// Transport flow of the packets sent with the same non-zero flow label, learned from the ones
+    /// whose ports are readable
+    pub labelled_flow: Option<LabelledFlow>,
+}
+
+/// Transport flow of the IPv6 packets a source sends to a destination with a flow label
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LabelledFlow {
+    pub source_port: u16,
+    pub destination_port: u16,
+    /// Transport protocol number (TCP or UDP)
+    pub protocol: u8,
 }
 
 impl<'a> From<&Ipv6Packet<'a>> for SerializableIpv6Packet {
@@ -74,7 +113,12 @@ impl<'a> From<&Ipv6Packet<'a>> for SerializableIpv6Packet {
             hop_limit: packet.get_hop_limit(),
             source: packet.get_source(),
             destination: packet.get_destination(),
+            multicast_group: multicast_group(IpAddr::V6(packet.get_destination())),
+            source_location: None,
+            destination_location: None,
             length: packet.payload().len(),
+            extension_headers: vec![],
+            labelled_flow: None,
         }
     }
 }
@@ -95,6 +139,11 @@ pub struct SerializableIpv4Packet {
     pub checksum: u16,
     pub source: Ipv4Addr,
     pub destination: Ipv4Addr,
+    /// Registered purpose of the multicast destination (e.g. mDNS)
+    pub multicast_group: Option<String>,
+    /// Locations of the addresses, filled in from the GeoIP databases when set
+    pub source_location: Option<GeoLocation>,
+    pub destination_location: Option<GeoLocation>,
     pub length: usize,
 }
 
@@ -118,6 +167,9 @@ impl<'a> From<&Ipv4Packet<'a>> for SerializableIpv4Packet {
             checksum: packet.get_checksum(),
             source: packet.get_source(),
             destination: packet.get_destination(),
+            multicast_group: multicast_group(IpAddr::V4(packet.get_destination())),
+            source_location: None,
+            destination_location: None,
             length: packet.payload().len(),
         }
     }
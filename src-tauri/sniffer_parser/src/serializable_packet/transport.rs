@@ -1,14 +1,19 @@
 //! Transport level Packets Representation
 
+use std::net::{IpAddr, Ipv4Addr};
+
 use pnet::packet::icmp::echo_reply::EchoReplyPacket;
 use pnet::packet::icmp::echo_request::EchoRequestPacket;
-use pnet::packet::icmp::{IcmpPacket, IcmpType, IcmpTypes};
-use pnet::packet::icmpv6::{Icmpv6Packet, Icmpv6Type, Icmpv6Types};
+use pnet::packet::icmp::{IcmpPacket, IcmpType};
+use pnet::packet::icmpv6::{Icmpv6Packet, Icmpv6Type};
 use pnet::packet::tcp::TcpPacket;
 use pnet::packet::udp::UdpPacket;
 use pnet::packet::Packet;
 use serde::Serialize;
 
+use crate::descriptions::{self, Field};
+use crate::mptcp::parse_mptcp_options;
+
 /// TCP Packet Representation
 #[derive(Serialize, Debug, Clone)]
 pub struct SerializableTcpPacket {
@@ -23,7 +28,65 @@ pub struct SerializableTcpPacket {
     pub checksum: u16,
     pub urgent_ptr: u16,
     pub options: Vec<u8>,
+    /// Multipath TCP options, decoded
+    pub mptcp: Vec<MptcpOption>,
     pub length: usize,
+    /// Expert analysis of the segment, from the earlier ones of its connection
+    pub analysis: Vec<TcpAnalysisFlag>,
+    /// Round-trip time from the data acknowledged by the segment, in microseconds
+    pub ack_rtt: Option<u64>,
+    /// Round-trip time of the handshake completed by the segment, in microseconds
+    pub handshake_rtt: Option<u64>,
+}
+
+/// Expert analysis flag of a TCP segment
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum TcpAnalysisFlag {
+    Retransmission,
+    /// Retransmission asked for by duplicate ACKs
+    FastRetransmission,
+    DuplicateAck,
+    OutOfOrder,
+    ZeroWindow,
+    WindowFull,
+    KeepAlive,
+}
+
+/// Multipath TCP option
+///
+/// Keys are hexadecimal, tokens are derived from the keys.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "subtype")]
+pub enum MptcpOption {
+    #[serde(rename = "MP_CAPABLE")]
+    MpCapable {
+        version: u8,
+        sender_key: Option<String>,
+        receiver_key: Option<String>,
+        sender_token: Option<u32>,
+        receiver_token: Option<u32>,
+    },
+    #[serde(rename = "MP_JOIN")]
+    MpJoin {
+        backup: bool,
+        address_id: Option<u8>,
+        /// Token of the connection joined, on the host receiving the SYN
+        receiver_token: Option<u32>,
+        sender_nonce: Option<u32>,
+    },
+    #[serde(rename = "DSS")]
+    Dss {
+        data_ack: Option<u64>,
+        data_sequence: Option<u64>,
+        subflow_sequence: Option<u32>,
+        data_length: Option<u16>,
+        data_fin: bool,
+    },
+    Other {
+        code: u8,
+        name: String,
+    },
 }
 
 impl<'a> From<&TcpPacket<'a>> for SerializableTcpPacket {
@@ -40,7 +103,11 @@ impl<'a> From<&TcpPacket<'a>> for SerializableTcpPacket {
             checksum: packet.get_checksum(),
             urgent_ptr: packet.get_urgent_ptr(),
             options: packet.get_options_raw().to_vec(),
+            mptcp: parse_mptcp_options(packet.get_options_raw()),
             length: packet.payload().len(),
+            analysis: vec![],
+            ack_rtt: None,
+            handshake_rtt: None,
         }
     }
 }
@@ -87,20 +154,8 @@ impl<'a> From<&Icmpv6Packet<'a>> for SerializableIcmpv6Packet {
 
 /// Get ICMPv6 Message Type
 pub fn icmpv6_type_to_string(icmp_type: Icmpv6Type) -> String {
-    return match icmp_type {
-        Icmpv6Types::DestinationUnreachable => format!("DestinationUnreachable ({})", icmp_type.0),
-        Icmpv6Types::PacketTooBig => format!("PacketTooBig ({})", icmp_type.0),
-        Icmpv6Types::TimeExceeded => format!("TimeExceeded ({})", icmp_type.0),
-        Icmpv6Types::ParameterProblem => format!("ParameterProblem ({})", icmp_type.0),
-        Icmpv6Types::EchoRequest => format!("EchoRequest ({})", icmp_type.0),
-        Icmpv6Types::EchoReply => format!("EchoReply ({})", icmp_type.0),
-        Icmpv6Types::RouterSolicit => format!("RouterSolicit ({})", icmp_type.0),
-        Icmpv6Types::RouterAdvert => format!("RouterAdvert ({})", icmp_type.0),
-        Icmpv6Types::NeighborSolicit => format!("NeighborSolicit ({})", icmp_type.0),
-        Icmpv6Types::NeighborAdvert => format!("NeighborAdvert ({})", icmp_type.0),
-        Icmpv6Types::Redirect => format!("Redirect ({})", icmp_type.0),
-        _ => format!("Unknown ({})", icmp_type.0),
-    };
+    let name = descriptions::name(Field::Icmpv6Type, icmp_type.0 as u16);
+    format!("{} ({})", name.unwrap_or("Unknown"), icmp_type.0)
 }
 
 /// ICMP Packet Representation
@@ -125,25 +180,8 @@ impl<'a> From<&IcmpPacket<'a>> for SerializableIcmpPacket {
 
 /// Get ICMPv4 Message Type
 pub fn icmp_type_to_string(icmp_type: IcmpType) -> String {
-    return match icmp_type {
-        IcmpTypes::EchoReply => format!("EchoReply ({})", icmp_type.0),
-        IcmpTypes::DestinationUnreachable => format!("DestinationUnreachable ({})", icmp_type.0),
-        IcmpTypes::SourceQuench => format!("SourceQuench ({})", icmp_type.0),
-        IcmpTypes::RedirectMessage => format!("RedirectMessage ({})", icmp_type.0),
-        IcmpTypes::EchoRequest => format!("EchoRequest ({})", icmp_type.0),
-        IcmpTypes::RouterAdvertisement => format!("RouterAdvertisement ({})", icmp_type.0),
-        IcmpTypes::RouterSolicitation => format!("RouterSolicitation ({})", icmp_type.0),
-        IcmpTypes::TimeExceeded => format!("TimeExceeded ({})", icmp_type.0),
-        IcmpTypes::ParameterProblem => format!("ParameterProblem ({})", icmp_type.0),
-        IcmpTypes::Timestamp => format!("Timestamp ({})", icmp_type.0),
-        IcmpTypes::TimestampReply => format!("TimestampReply ({})", icmp_type.0),
-        IcmpTypes::InformationRequest => format!("InformationRequest ({})", icmp_type.0),
-        IcmpTypes::InformationReply => format!("InformationReply ({})", icmp_type.0),
-        IcmpTypes::AddressMaskRequest => format!("AddressMaskRequest ({})", icmp_type.0),
-        IcmpTypes::AddressMaskReply => format!("AddressMaskReply ({})", icmp_type.0),
-        IcmpTypes::Traceroute => format!("Traceroute ({})", icmp_type.0),
-        _ => format!("Unknown ({})", icmp_type.0),
-    };
+    let name = descriptions::name(Field::IcmpType, icmp_type.0 as u16);
+    format!("{} ({})", name.unwrap_or("Unknown"), icmp_type.0)
 }
 
 /// ICMP Echo Reply Packet Representation
@@ -193,3 +231,96 @@ impl<'a> From<&EchoRequestPacket<'a>> for SerializableEchoRequestPacket {
         }
     }
 }
+
+/// ICMP Timestamp Request or Reply Packet Representation
+///
+/// Timestamps are the milliseconds since midnight UT, unless their most significant bit is set
+/// (non-standard values).
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct SerializableIcmpTimestampPacket {
+    pub icmp_type: u8,
+    pub icmp_code: u8,
+    pub checksum: u16,
+    pub identifier: u16,
+    pub sequence_number: u16,
+    pub originate_timestamp: u32,
+    pub receive_timestamp: u32,
+    pub transmit_timestamp: u32,
+}
+
+/// ICMP Address Mask Request or Reply Packet Representation
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct SerializableIcmpAddressMaskPacket {
+    pub icmp_type: u8,
+    pub icmp_code: u8,
+    pub checksum: u16,
+    pub identifier: u16,
+    pub sequence_number: u16,
+    pub address_mask: Ipv4Addr,
+}
+
+/// ICMP Router Advertisement Packet Representation
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct SerializableRouterAdvertisementPacket {
+    pub icmp_code: u8,
+    pub checksum: u16,
+    /// Seconds the router addresses are valid for
+    pub lifetime: u16,
+    pub routers: Vec<RouterAddress>,
+}
+
+/// Address advertised by a router, with its preference as default router (higher is preferred)
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct RouterAddress {
+    pub address: Ipv4Addr,
+    pub preference: i32,
+}
+
+/// IPsec ESP Packet Representation
+///
+/// Only the header is readable: the payload, and the protocol it carries, are encrypted, unless the
+/// SA uses NULL encryption.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct SerializableEspPacket {
+    /// Security Parameters Index of the SA protecting the packet
+    pub spi: u32,
+    pub sequence: u32,
+    /// Whether the packet is encapsulated in UDP to traverse a NAT (RFC 3948)
+    pub udp_encapsulated: bool,
+    /// Length of the encrypted payload, along with its padding and integrity check value
+    pub length: usize,
+    /// Payload of the packets with NULL encryption, readable in the clear
+    pub cleartext: Option<EspCleartextPayload>,
+}
+
+/// Payload of an ESP packet with NULL encryption
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct EspCleartextPayload {
+    /// Protocol of the payload
+    pub next_header: String,
+    /// Tunnel, for whole IP packets, or Transport
+    pub mode: String,
+    /// Length of the payload, without padding and integrity check value
+    pub length: usize,
+    pub icv_length: usize,
+    /// Addresses and protocol of the packet carried in tunnel mode
+    pub inner_source: Option<IpAddr>,
+    pub inner_destination: Option<IpAddr>,
+    pub inner_protocol: Option<String>,
+    /// Ports of the TCP or UDP segment carried
+    pub source_port: Option<u16>,
+    pub destination_port: Option<u16>,
+}
+
+/// IPsec AH Packet Representation
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct SerializableAhPacket {
+    /// Protocol of the authenticated payload
+    pub next_header: String,
+    /// Security Parameters Index of the SA protecting the packet
+    pub spi: u32,
+    pub sequence: u32,
+    /// Integrity check value, hexadecimal
+    pub icv: String,
+    pub length: usize,
+}
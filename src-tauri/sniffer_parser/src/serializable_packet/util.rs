@@ -143,7 +143,11 @@ pub fn contains_udp(packet: &ParsedPacket) -> bool {
 pub fn contains_icmp(packet: &ParsedPacket) -> bool {
     if let Some(SerializablePacket::IcmpPacket(_))
     | Some(SerializablePacket::EchoReplyPacket(_))
-    | Some(SerializablePacket::EchoRequestPacket(_)) = packet.get_transport_layer_packet()
+    | Some(SerializablePacket::EchoRequestPacket(_))
+    | Some(SerializablePacket::IcmpTimestampPacket(_))
+    | Some(SerializablePacket::IcmpAddressMaskPacket(_))
+    | Some(SerializablePacket::RouterAdvertisementPacket(_)) =
+        packet.get_transport_layer_packet()
     {
         return true;
     }
@@ -187,6 +191,15 @@ pub fn contains_ipv4(packet: &ParsedPacket) -> bool {
     return false;
 }
 
+/// Check if packet contains HTTP/2 protocol (Application layer)
+pub fn contains_http2(packet: &ParsedPacket) -> bool {
+    if let Some(SerializablePacket::Http2Packet(_)) = packet.get_application_layer_packet() {
+        return true;
+    }
+
+    return false;
+}
+
 /// Check if packet contains TLS protocol (Application layer)
 pub fn contains_tls(packet: &ParsedPacket) -> bool {
     if let Some(SerializablePacket::TlsPacket(_)) = packet.get_application_layer_packet() {
@@ -196,6 +209,15 @@ pub fn contains_tls(packet: &ParsedPacket) -> bool {
     return false;
 }
 
+/// Check if packet contains QUIC protocol (Application layer)
+pub fn contains_quic(packet: &ParsedPacket) -> bool {
+    if let Some(SerializablePacket::QuicPacket(_)) = packet.get_application_layer_packet() {
+        return true;
+    }
+
+    return false;
+}
+
 /// Check if packet contains DNS protocol (Application layer)
 pub fn contains_dns(packet: &ParsedPacket) -> bool {
     if let Some(SerializablePacket::DnsPacket(_)) = packet.get_application_layer_packet() {
@@ -205,6 +227,81 @@ pub fn contains_dns(packet: &ParsedPacket) -> bool {
     return false;
 }
 
+/// Check if packet contains DHCP protocol (Application layer)
+pub fn contains_dhcp(packet: &ParsedPacket) -> bool {
+    if let Some(SerializablePacket::DhcpPacket(_)) = packet.get_application_layer_packet() {
+        return true;
+    }
+
+    return false;
+}
+
+/// Check if packet contains SNMP protocol (Application layer)
+pub fn contains_snmp(packet: &ParsedPacket) -> bool {
+    if let Some(SerializablePacket::SnmpPacket(_)) = packet.get_application_layer_packet() {
+        return true;
+    }
+
+    return false;
+}
+
+/// Check if packet contains GTP protocol (Application layer)
+pub fn contains_gtp(packet: &ParsedPacket) -> bool {
+    if let Some(SerializablePacket::GtpPacket(_)) = packet.get_application_layer_packet() {
+        return true;
+    }
+
+    return false;
+}
+
+/// Check if packet contains IKE protocol (Application layer)
+pub fn contains_ike(packet: &ParsedPacket) -> bool {
+    if let Some(SerializablePacket::IkePacket(_)) = packet.get_application_layer_packet() {
+        return true;
+    }
+
+    return false;
+}
+
+/// Check if packet contains WireGuard protocol (Application layer)
+pub fn contains_wireguard(packet: &ParsedPacket) -> bool {
+    if let Some(SerializablePacket::WireGuardPacket(_)) = packet.get_application_layer_packet() {
+        return true;
+    }
+
+    return false;
+}
+
+/// Check if packet contains NTP protocol (Application layer)
+pub fn contains_ntp(packet: &ParsedPacket) -> bool {
+    if let Some(SerializablePacket::NtpPacket(_)) = packet.get_application_layer_packet() {
+        return true;
+    }
+
+    return false;
+}
+
+/// Check if packet contains IPsec ESP, over IP or encapsulated in UDP
+pub fn contains_esp(packet: &ParsedPacket) -> bool {
+    if let Some(SerializablePacket::EspPacket(_)) = packet.get_transport_layer_packet() {
+        return true;
+    }
+    if let Some(SerializablePacket::EspPacket(_)) = packet.get_application_layer_packet() {
+        return true;
+    }
+
+    return false;
+}
+
+/// Check if packet contains IPsec AH
+pub fn contains_ah(packet: &ParsedPacket) -> bool {
+    if let Some(SerializablePacket::AhPacket(_)) = packet.get_transport_layer_packet() {
+        return true;
+    }
+
+    return false;
+}
+
 /// Check if packet contains HTTP protocol (Application layer)
 pub fn contains_http(packet: &ParsedPacket) -> bool {
     if let Some(SerializablePacket::HttpRequestPacket(_))
@@ -215,3 +312,111 @@ pub fn contains_http(packet: &ParsedPacket) -> bool {
 
     return false;
 }
+
+/// Check if packet contains PTP protocol (Application layer)
+pub fn contains_ptp(packet: &ParsedPacket) -> bool {
+    if let Some(SerializablePacket::PtpPacket(_)) = packet.get_application_layer_packet() {
+        return true;
+    }
+
+    return false;
+}
+
+/// Check if packet contains GOOSE protocol (Application layer)
+pub fn contains_goose(packet: &ParsedPacket) -> bool {
+    if let Some(SerializablePacket::GoosePacket(_)) = packet.get_application_layer_packet() {
+        return true;
+    }
+
+    return false;
+}
+
+/// Check if packet contains Sampled Values protocol (Application layer)
+pub fn contains_sv(packet: &ParsedPacket) -> bool {
+    if let Some(SerializablePacket::SvPacket(_)) = packet.get_application_layer_packet() {
+        return true;
+    }
+
+    return false;
+}
+
+/// Check if packet contains PROFINET protocol (Application layer)
+pub fn contains_profinet(packet: &ParsedPacket) -> bool {
+    if let Some(SerializablePacket::ProfinetPacket(_)) = packet.get_application_layer_packet() {
+        return true;
+    }
+
+    return false;
+}
+
+/// Check if packet contains EtherCAT protocol (Application layer)
+pub fn contains_ethercat(packet: &ParsedPacket) -> bool {
+    if let Some(SerializablePacket::EthercatPacket(_)) = packet.get_application_layer_packet() {
+        return true;
+    }
+
+    return false;
+}
+
+/// Check if packet contains S7comm protocol (Application layer)
+pub fn contains_s7comm(packet: &ParsedPacket) -> bool {
+    if let Some(SerializablePacket::S7commPacket(_)) = packet.get_application_layer_packet() {
+        return true;
+    }
+
+    return false;
+}
+
+/// Check if packet contains iSCSI protocol (Application layer)
+pub fn contains_iscsi(packet: &ParsedPacket) -> bool {
+    if let Some(SerializablePacket::IscsiPacket(_)) = packet.get_application_layer_packet() {
+        return true;
+    }
+
+    return false;
+}
+
+/// Check if packet contains NVMe/TCP protocol (Application layer)
+pub fn contains_nvme_tcp(packet: &ParsedPacket) -> bool {
+    if let Some(SerializablePacket::NvmeTcpPacket(_)) = packet.get_application_layer_packet() {
+        return true;
+    }
+
+    return false;
+}
+
+/// Check if packet contains CQL protocol (Application layer)
+pub fn contains_cql(packet: &ParsedPacket) -> bool {
+    if let Some(SerializablePacket::CqlPacket(_)) = packet.get_application_layer_packet() {
+        return true;
+    }
+
+    return false;
+}
+
+/// Check if packet contains Kafka protocol (Application layer)
+pub fn contains_kafka(packet: &ParsedPacket) -> bool {
+    if let Some(SerializablePacket::KafkaPacket(_)) = packet.get_application_layer_packet() {
+        return true;
+    }
+
+    return false;
+}
+
+/// Check if packet contains Zookeeper protocol (Application layer)
+pub fn contains_zookeeper(packet: &ParsedPacket) -> bool {
+    if let Some(SerializablePacket::ZookeeperPacket(_)) = packet.get_application_layer_packet() {
+        return true;
+    }
+
+    return false;
+}
+
+/// Check if packet contains a protocol decoded by a dissector registered at runtime (Application layer)
+pub fn contains_custom(packet: &ParsedPacket) -> bool {
+    if let Some(SerializablePacket::CustomPacket(_)) = packet.get_application_layer_packet() {
+        return true;
+    }
+
+    return false;
+}
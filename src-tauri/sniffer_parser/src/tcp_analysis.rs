@@ -0,0 +1,528 @@
+//! Expert analysis of the TCP segments
+//!
+//! The sequence and acknowledgement numbers and the windows of each direction of a connection are
+//! tracked, so that the segments can be flagged as in Wireshark, to diagnose slow transfers:
+//! - retransmission: data sent again, after the data following it
+//! - fast retransmission: data sent again, after at least two duplicate ACKs asking for it
+//! - out of order: data sent again shortly after the data following it, likely reordered rather
+//!   than lost
+//! - duplicate ACK: acknowledgement without data of the same bytes, with the same window
+//! - zero window: the host can't receive any byte
+//! - window full: the data fills the window advertised by the receiver
+//! - keep-alive: segment of at most a byte, one byte before the data sent so far
+//!
+//! Windows are scaled when both the SYNs of the connection carry the window scale option. Each
+//! direction of the connections opened before the capture is analyzed from its second segment.
+//!
+//! The round-trip times of the connections are measured as well, in microseconds:
+//! - handshake: from the SYN to the ACK of the SYN-ACK, i.e. between the client and the server
+//! - ACK: from data sent to its acknowledgement, i.e. between the capture point and the receiver of
+//!   the data; data sent again gives no sample, since it is unknown which copy is acknowledged
+
+use std::{collections::VecDeque, net::IpAddr, time::Duration};
+
+use pnet::packet::tcp::TcpFlags;
+
+use crate::flows::FlowKey;
+use crate::serializable_packet::transport::{SerializableTcpPacket, TcpAnalysisFlag};
+use crate::ParserContext;
+
+/// Kind of the window scale option
+const WINDOW_SCALE_OPTION: u8 = 3;
+
+/// Maximum shift of the window scale option (RFC 7323)
+const MAX_WINDOW_SCALE: u8 = 14;
+
+/// Time since the last data of a direction under which data sent again is considered out of order
+const OUT_OF_ORDER_THRESHOLD: Duration = Duration::from_millis(3);
+
+/// Segments waiting for their acknowledgement kept for each direction, to measure round-trip times
+const MAX_UNACKNOWLEDGED: usize = 1024;
+
+/// State of a direction of a TCP connection, as seen so far
+#[derive(Debug, Default)]
+pub(crate) struct TcpDirection {
+    /// Sequence number following the data sent so far
+    next_sequence: u32,
+    /// Last number acknowledged
+    acknowledgement: Option<u32>,
+    /// Last window advertised, unscaled
+    window: u16,
+    /// Window scale option of the SYN, if any
+    window_scale: Option<u8>,
+    /// Whether the last window was advertised by a SYN, never scaled
+    syn_window: bool,
+    /// Duplicate ACKs of the last number acknowledged
+    duplicate_acks: u32,
+    /// Capture time of the last data sent
+    last_data: Duration,
+    /// End sequence number and capture time of the data sent once and not acknowledged yet
+    unacknowledged: VecDeque<(u32, Duration)>,
+    /// Capture time of the SYN, until the handshake it starts is completed
+    syn_time: Option<Duration>,
+    /// Whether the SYN of the direction acknowledged the one of the other direction
+    syn_acknowledged: bool,
+}
+
+impl TcpDirection {
+    /// Get the last window advertised, in bytes
+    fn scaled_window(&self, reverse_scale: Option<u8>) -> u32 {
+        match (self.window_scale, reverse_scale) {
+            (Some(scale), Some(_)) if !self.syn_window => (self.window as u32) << scale,
+            _ => self.window as u32,
+        }
+    }
+}
+
+/// Analyze a TCP segment captured at the given time, given the addresses of its endpoints, filling
+/// in its flags and the round-trip times it completes
+pub(crate) fn analyze_segment(
+    state: &mut ParserContext,
+    source: IpAddr,
+    destination: IpAddr,
+    tcp: &mut SerializableTcpPacket,
+    now: Duration,
+) {
+    let flags = tcp.flags;
+    let is_syn = flags & TcpFlags::SYN != 0;
+    let is_fin = flags & TcpFlags::FIN != 0;
+    let is_rst = flags & TcpFlags::RST != 0;
+    let is_ack = flags & TcpFlags::ACK != 0;
+    let (sequence, acknowledgement, window) = (tcp.sequence, tcp.acknowledgement, tcp.window);
+    // SYN and FIN take up a sequence number each
+    let length = tcp.length as u32 + is_syn as u32 + is_fin as u32;
+
+    let (key, sent) = FlowKey::of((source, tcp.source), (destination, tcp.destination));
+    let (sending, receiving) = ((key, sent), (key, sent.reverse()));
+
+    let directions = &mut state.tcp_directions;
+    let sender_scale = directions
+        .get(&sending)
+        .and_then(|direction| direction.window_scale);
+    let reverse = directions.get(&receiving).map(|reverse| {
+        (
+            reverse.acknowledgement,
+            reverse.scaled_window(sender_scale),
+            reverse.duplicate_acks,
+        )
+    });
+
+    // The data acknowledged gives a sample of the round-trip time from the capture point
+    let mut completes_handshake = false;
+    if let Some(receiver) = directions.get_mut(&receiving).filter(|_| is_ack) {
+        let mut acknowledged = None;
+        while let Some(&(end, time)) = receiver.unacknowledged.front() {
+            if (acknowledgement.wrapping_sub(end) as i32) < 0 {
+                break;
+            }
+            acknowledged = Some(time);
+            receiver.unacknowledged.pop_front();
+        }
+        tcp.ack_rtt = acknowledged.map(|time| micros(now.saturating_sub(time)));
+        completes_handshake =
+            receiver.syn_acknowledged && acknowledgement == receiver.next_sequence;
+    }
+
+    // A SYN other than the one sent before starts a new connection on the same ports
+    let direction = directions.get(&sending);
+    let is_new = match direction {
+        Some(direction) => is_syn && sequence.wrapping_add(1) != direction.next_sequence,
+        None => true,
+    };
+    if is_new {
+        directions.remove(&sending);
+    }
+    let direction = directions.entry(sending).or_default();
+    if is_syn {
+        direction.window_scale = window_scale(&tcp.options);
+        if is_ack {
+            direction.syn_acknowledged = true;
+        } else {
+            direction.syn_time = Some(now);
+        }
+    } else if completes_handshake {
+        // The ACK of the SYN-ACK ends the handshake seen from the client side: SYN to ACK
+        tcp.handshake_rtt = direction
+            .syn_time
+            .take()
+            .map(|syn_time| micros(now.saturating_sub(syn_time)));
+    }
+
+    let mut analysis = vec![];
+    let is_control = is_syn || is_fin || is_rst;
+    if window == 0 && !is_control {
+        analysis.push(TcpAnalysisFlag::ZeroWindow);
+    }
+
+    let is_keep_alive = !is_new
+        && !is_control
+        && length <= 1
+        && sequence == direction.next_sequence.wrapping_sub(1);
+    if is_keep_alive {
+        analysis.push(TcpAnalysisFlag::KeepAlive);
+    }
+
+    if let Some((Some(reverse_acknowledgement), reverse_window, _)) = reverse {
+        let window_end = reverse_acknowledgement.wrapping_add(reverse_window);
+        let fills_window = sequence.wrapping_add(length) == window_end;
+        if length > 0 && reverse_window > 0 && !is_syn && !is_keep_alive && fills_window {
+            analysis.push(TcpAnalysisFlag::WindowFull);
+        }
+    }
+
+    let is_duplicate_ack = !is_new
+        && !is_control
+        && is_ack
+        && length == 0
+        && sequence == direction.next_sequence
+        && direction.acknowledgement == Some(acknowledgement)
+        // The window of a SYN is never scaled, unlike the following ones
+        && !direction.syn_window
+        && direction.window == window;
+    if is_duplicate_ack {
+        analysis.push(TcpAnalysisFlag::DuplicateAck);
+        direction.duplicate_acks += 1;
+    } else if is_ack && direction.acknowledgement != Some(acknowledgement) {
+        direction.duplicate_acks = 0;
+    }
+
+    // Sequence numbers wrap around: distances are compared as in RFC 1982
+    let behind = (sequence.wrapping_sub(direction.next_sequence) as i32) < 0;
+    if !is_new && !is_keep_alive && length > 0 && behind {
+        let asked = reverse.is_some_and(|(reverse_acknowledgement, _, duplicate_acks)| {
+            duplicate_acks >= 2 && reverse_acknowledgement == Some(sequence)
+        });
+        if asked {
+            analysis.push(TcpAnalysisFlag::FastRetransmission);
+        } else if now.saturating_sub(direction.last_data) < OUT_OF_ORDER_THRESHOLD {
+            analysis.push(TcpAnalysisFlag::OutOfOrder);
+        } else {
+            analysis.push(TcpAnalysisFlag::Retransmission);
+        }
+        // Data sent again gives no sample: its acknowledgement may be of either copy
+        direction
+            .unacknowledged
+            .retain(|(end, _)| (end.wrapping_sub(sequence) as i32) <= 0);
+    }
+
+    let end = sequence.wrapping_add(length);
+    if is_new || (end.wrapping_sub(direction.next_sequence) as i32) > 0 {
+        direction.next_sequence = end;
+        if length > 0 && !is_syn {
+            if direction.unacknowledged.len() >= MAX_UNACKNOWLEDGED {
+                direction.unacknowledged.pop_front();
+            }
+            direction.unacknowledged.push_back((end, now));
+        }
+    }
+    if length > 0 {
+        direction.last_data = now;
+    }
+    if is_ack {
+        direction.acknowledgement = Some(acknowledgement);
+    }
+    direction.window = window;
+    direction.syn_window = is_syn;
+
+    tcp.analysis = analysis;
+}
+
+/// Get a duration in microseconds
+fn micros(duration: Duration) -> u64 {
+    duration.as_micros() as u64
+}
+
+/// Get the shift of the window scale option among the options of a TCP segment, if any
+fn window_scale(options: &[u8]) -> Option<u8> {
+    let mut i = 0;
+    while i < options.len() {
+        match options[i] {
+            // End of options
+            0 => break,
+            // No operation
+            1 => {
+                i += 1;
+                continue;
+            }
+            _ => (),
+        }
+
+        let length = match options.get(i + 1) {
+            Some(&length) if length >= 2 && i + length as usize <= options.len() => length as usize,
+            _ => break,
+        };
+        if options[i] == WINDOW_SCALE_OPTION && length == 3 {
+            return Some(options[i + 2].min(MAX_WINDOW_SCALE));
+        }
+        i += length;
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr};
+    use std::time::Duration;
+
+    use pnet::packet::tcp::{MutableTcpPacket, TcpFlags, TcpPacket};
+
+    use super::{analyze_segment, window_scale};
+    use crate::serializable_packet::transport::SerializableTcpPacket;
+    use crate::serializable_packet::transport::TcpAnalysisFlag::{self, *};
+    use crate::ParserContext;
+
+    const CLIENT: (IpAddr, u16) = (IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)), 50000);
+    const SERVER: (IpAddr, u16) = (IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2)), 80);
+
+    #[test]
+    fn retransmissions() {
+        let mut state = ParserContext::new();
+
+        let ack = TcpFlags::ACK;
+
+        assert!(client(&mut state, 0, 1000, 0, TcpFlags::SYN, 0, 0).is_empty());
+        assert!(server(&mut state, 1, 5000, 1001, TcpFlags::SYN | ack, 0).is_empty());
+        assert!(client(&mut state, 2, 1001, 5001, ack, 0, 0).is_empty());
+        assert!(client(&mut state, 3, 1001, 5001, ack, 100, 0).is_empty());
+        assert!(client(&mut state, 4, 1101, 5001, ack, 100, 0).is_empty());
+        // Sent again long after: retransmission
+        assert_eq!(
+            client(&mut state, 500, 1001, 5001, ack, 100, 0),
+            vec![Retransmission]
+        );
+        // Sent again right after the following data: out of order
+        assert!(client(&mut state, 600, 1201, 5001, ack, 100, 0).is_empty());
+        assert_eq!(
+            client(&mut state, 601, 1101, 5001, ack, 100, 0),
+            vec![OutOfOrder]
+        );
+
+        // The server asks again for the bytes following 1301
+        assert!(server(&mut state, 700, 5001, 1301, ack, 0).is_empty());
+        assert_eq!(
+            server(&mut state, 701, 5001, 1301, ack, 0),
+            vec![DuplicateAck]
+        );
+        assert_eq!(
+            server(&mut state, 702, 5001, 1301, ack, 0),
+            vec![DuplicateAck]
+        );
+        assert!(client(&mut state, 703, 1401, 5001, ack, 100, 0).is_empty());
+        assert_eq!(
+            client(&mut state, 800, 1301, 5001, ack, 100, 0),
+            vec![FastRetransmission]
+        );
+        // Acknowledging new data ends the duplicate ACKs
+        assert!(server(&mut state, 801, 5001, 1501, ack, 0).is_empty());
+        assert!(client(&mut state, 900, 1501, 5001, ack, 100, 0).is_empty());
+        assert_eq!(
+            client(&mut state, 1000, 1501, 5001, ack, 100, 0),
+            vec![Retransmission]
+        );
+    }
+
+    #[test]
+    fn windows_and_keep_alives() {
+        let mut state = ParserContext::new();
+
+        let ack = TcpFlags::ACK;
+
+        // Windows of 1000 << 2 bytes
+        assert!(client(&mut state, 0, 1000, 0, TcpFlags::SYN, 0, 2).is_empty());
+        assert!(server(&mut state, 1, 5000, 1001, TcpFlags::SYN | ack, 2).is_empty());
+        assert!(server(&mut state, 2, 5001, 1001, ack, 0).is_empty());
+        assert!(client(&mut state, 3, 1001, 5001, ack, 3000, 0).is_empty());
+        assert_eq!(
+            client(&mut state, 4, 4001, 5001, ack, 1000, 0),
+            vec![WindowFull]
+        );
+
+        assert_eq!(
+            segment(&mut state, SERVER, CLIENT, 5, 5001, 5001, ack, 0, 0, 0).analysis,
+            vec![ZeroWindow]
+        );
+        assert_eq!(
+            client(&mut state, 100, 5000, 5001, ack, 1, 0),
+            vec![KeepAlive]
+        );
+        assert_eq!(
+            client(&mut state, 200, 5000, 5001, ack, 0, 0),
+            vec![KeepAlive]
+        );
+    }
+
+    #[test]
+    fn round_trip_times() {
+        let mut state = ParserContext::new();
+
+        let (syn, ack) = (TcpFlags::SYN, TcpFlags::ACK);
+        let rtts =
+            |tcp_packet: SerializableTcpPacket| (tcp_packet.handshake_rtt, tcp_packet.ack_rtt);
+
+        assert_eq!(
+            rtts(segment(
+                &mut state, CLIENT, SERVER, 0, 1000, 0, syn, 0, 1000, 0
+            )),
+            (None, None)
+        );
+        assert_eq!(
+            rtts(segment(
+                &mut state,
+                SERVER,
+                CLIENT,
+                10,
+                5000,
+                1001,
+                syn | ack,
+                0,
+                1000,
+                0
+            )),
+            (None, None)
+        );
+        // From the SYN to the ACK of the SYN-ACK
+        assert_eq!(
+            rtts(segment(
+                &mut state, CLIENT, SERVER, 12, 1001, 5001, ack, 0, 1000, 0
+            )),
+            (Some(12_000), None)
+        );
+
+        segment(
+            &mut state, CLIENT, SERVER, 13, 1001, 5001, ack, 100, 1000, 0,
+        );
+        segment(
+            &mut state, CLIENT, SERVER, 14, 1101, 5001, ack, 100, 1000, 0,
+        );
+        // From the last data acknowledged
+        assert_eq!(
+            rtts(segment(
+                &mut state, SERVER, CLIENT, 20, 5001, 1201, ack, 0, 1000, 0
+            )),
+            (None, Some(6_000))
+        );
+
+        // Data sent again gives no sample
+        segment(
+            &mut state, CLIENT, SERVER, 30, 1201, 5001, ack, 100, 1000, 0,
+        );
+        segment(
+            &mut state, CLIENT, SERVER, 300, 1201, 5001, ack, 100, 1000, 0,
+        );
+        assert_eq!(
+            rtts(segment(
+                &mut state, SERVER, CLIENT, 310, 5001, 1301, ack, 0, 1000, 0
+            )),
+            (None, None)
+        );
+        assert_eq!(
+            rtts(segment(
+                &mut state, CLIENT, SERVER, 320, 1301, 5001, ack, 0, 1000, 0
+            )),
+            (None, None)
+        );
+    }
+
+    #[test]
+    fn window_scale_option() {
+        assert_eq!(
+            window_scale(&[0x02, 0x04, 0x05, 0xb4, 0x01, 0x03, 0x03, 0x07]),
+            Some(7)
+        );
+        assert_eq!(window_scale(&[0x03, 0x03, 0x20]), Some(14));
+        assert_eq!(window_scale(&[0x01, 0x01, 0x00, 0x03, 0x03, 0x07]), None);
+        assert_eq!(window_scale(&[0x03, 0x03]), None);
+    }
+
+    ///////////////////// Utils
+
+    fn client(
+        state: &mut ParserContext,
+        milliseconds: u64,
+        sequence: u32,
+        acknowledgement: u32,
+        flags: u16,
+        length: usize,
+        scale: u8,
+    ) -> Vec<TcpAnalysisFlag> {
+        segment(
+            state,
+            CLIENT,
+            SERVER,
+            milliseconds,
+            sequence,
+            acknowledgement,
+            flags,
+            length,
+            1000,
+            scale,
+        )
+        .analysis
+    }
+
+    fn server(
+        state: &mut ParserContext,
+        milliseconds: u64,
+        sequence: u32,
+        acknowledgement: u32,
+        flags: u16,
+        scale: u8,
+    ) -> Vec<TcpAnalysisFlag> {
+        segment(
+            state,
+            SERVER,
+            CLIENT,
+            milliseconds,
+            sequence,
+            acknowledgement,
+            flags,
+            0,
+            1000,
+            scale,
+        )
+        .analysis
+    }
+
+    /// Analyze a segment, with the window scale option if the scale is not 0
+    #[allow(clippy::too_many_arguments)]
+    fn segment(
+        state: &mut ParserContext,
+        source: (IpAddr, u16),
+        destination: (IpAddr, u16),
+        milliseconds: u64,
+        sequence: u32,
+        acknowledgement: u32,
+        flags: u16,
+        length: usize,
+        window: u16,
+        scale: u8,
+    ) -> SerializableTcpPacket {
+        let options: &[u8] = if scale == 0 {
+            &[]
+        } else {
+            &[0x01, 0x03, 0x03, scale]
+        };
+        let mut buffer = vec![0u8; 20 + options.len() + length];
+        let mut tcp_packet = MutableTcpPacket::new(&mut buffer).unwrap();
+        tcp_packet.set_source(source.1);
+        tcp_packet.set_destination(destination.1);
+        tcp_packet.set_sequence(sequence);
+        tcp_packet.set_acknowledgement(acknowledgement);
+        tcp_packet.set_data_offset(5 + options.len() as u8 / 4);
+        tcp_packet.set_flags(flags);
+        tcp_packet.set_window(window);
+        buffer[20..20 + options.len()].copy_from_slice(options);
+
+        let mut tcp_packet = SerializableTcpPacket::from(&TcpPacket::new(&buffer).unwrap());
+        analyze_segment(
+            state,
+            source.0,
+            destination.0,
+            &mut tcp_packet,
+            Duration::from_millis(milliseconds),
+        );
+
+        tcp_packet
+    }
+}
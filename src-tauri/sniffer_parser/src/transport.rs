@@ -1,26 +1,76 @@
-//! UDP, TCP, ICMP, and ICMPv6 Packet parsing
+//! UDP, TCP, ICMP, ICMPv6, and IPsec (ESP, AH) Packet parsing
 
-use pnet::packet::icmp::{echo_reply, echo_request, IcmpPacket, IcmpTypes};
-use pnet::packet::icmpv6::Icmpv6Packet;
+use pnet::packet::icmp::{echo_reply, echo_request, IcmpPacket, IcmpType, IcmpTypes};
+use pnet::packet::icmpv6::{Icmpv6Packet, Icmpv6Types};
 use pnet::packet::ip::{IpNextHeaderProtocol, IpNextHeaderProtocols};
+use pnet::packet::ipv4::Ipv4Packet;
+use pnet::packet::ipv6::Ipv6Packet;
 use pnet::packet::tcp::TcpPacket;
 use pnet::packet::udp::UdpPacket;
 
-use std::net::IpAddr;
+use std::borrow::Cow;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::sync::RwLock;
+
+use serde::{Deserialize, Serialize};
 
 use crate::application::handle_application_protocol;
+use crate::application::registry::Transport;
+use crate::flows::{track_flow, FlowKind};
+use crate::reassembly::reassemble;
+use crate::references::{link_echo, link_flow_packet, link_icmp_error, link_segment};
+use crate::serializable_packet::bytes::FrameBytes;
 use crate::serializable_packet::transport::{
-    SerializableEchoReplyPacket, SerializableEchoRequestPacket, SerializableIcmpPacket,
-    SerializableIcmpv6Packet, SerializableTcpPacket, SerializableUdpPacket,
+    icmp_type_to_string, EspCleartextPayload, RouterAddress, SerializableAhPacket,
+    SerializableEchoReplyPacket, SerializableEchoRequestPacket, SerializableEspPacket,
+    SerializableIcmpAddressMaskPacket, SerializableIcmpPacket, SerializableIcmpTimestampPacket,
+    SerializableIcmpv6Packet, SerializableRouterAdvertisementPacket, SerializableTcpPacket,
+    SerializableUdpPacket,
 };
+use crate::tcp_analysis::analyze_segment;
 
 const ACK_BIT_SHIFT: usize = 4;
+const RST_BIT_SHIFT: usize = 2;
+const SYN_BIT_SHIFT: usize = 1;
 const FIN_BIT_SHIFT: usize = 0;
 
+/// Lengths of the integrity check values of the usual ESP integrity algorithms (HMAC-SHA1-96,
+/// HMAC-SHA2-256-128, HMAC-SHA2-384-192, HMAC-SHA2-512-256), or of none
+const ESP_ICV_LENGTHS: [usize; 5] = [12, 16, 24, 32, 0];
+
 use super::*;
 
+/// Layers the packets are dissected up to, chosen by the user
+///
+/// Shared by all the threads, since the depth is set while packets are being parsed. Parser
+/// contexts can dissect their packets up to other layers (see
+/// [`ParserContext::set_dissection_depth`]).
+static DISSECTION_DEPTH: RwLock<DissectionDepth> = RwLock::new(DissectionDepth::Application);
+
+/// Layers the packets are dissected up to
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum DissectionDepth {
+    /// All the layers, reassembling the TCP streams for the application-layer parsers
+    Application,
+    /// Up to the transport layer: the payloads are neither buffered nor dissected, and the TCP and
+    /// UDP packets are not linked to the earlier ones of their flow
+    Transport,
+}
+
+/// Replace the layers the packets are dissected up to
+pub fn set_dissection_depth(depth: DissectionDepth) {
+    *DISSECTION_DEPTH.write().unwrap() = depth;
+}
+
+/// Get the layers the packets are dissected up to
+pub fn get_dissection_depth() -> DissectionDepth {
+    *DISSECTION_DEPTH.read().unwrap()
+}
+
 /// Build a UDP packet from a network-layer packet, save it in a Parsed Packet
 pub fn handle_udp_packet(
+    state: &mut ParserContext,
     source: IpAddr,
     destination: IpAddr,
     packet: &[u8],
@@ -42,11 +92,30 @@ pub fn handle_udp_packet(
             SerializableUdpPacket::from(&udp),
         )));
 
+        let tracked = track_flow(
+            state,
+            FlowKind::Udp,
+            (source, udp.get_source()),
+            (destination, udp.get_destination()),
+            parsed_packet.get_meta().capture_time,
+        );
+        if !tracked || state.dissection_depth() == DissectionDepth::Transport {
+            return;
+        }
+        link_flow_packet(
+            state,
+            parsed_packet,
+            (source, udp.get_source()),
+            (destination, udp.get_destination()),
+        );
+
         handle_application_protocol(
+            state,
             source,
             udp.get_source(),
             destination,
             udp.get_destination(),
+            Transport::Udp,
             false,
             udp.payload(),
             parsed_packet,
@@ -61,6 +130,7 @@ pub fn handle_udp_packet(
 
 /// Build a TCP packet from a network-layer packet, save it in a Parsed Packet
 pub fn handle_tcp_packet(
+    state: &mut ParserContext,
     source: IpAddr,
     destination: IpAddr,
     packet: &[u8],
@@ -77,22 +147,86 @@ pub fn handle_tcp_packet(
             packet.len()
         );
 
-        parsed_packet.set_transport_layer_packet(Some(SerializablePacket::TcpPacket(
-            SerializableTcpPacket::from(&tcp),
-        )));
-
         let flags = tcp.get_flags();
         let is_fin = (flags & (1 << ACK_BIT_SHIFT)) != 0 && (flags & (1 << FIN_BIT_SHIFT)) != 0;
+        let is_rst = (flags & (1 << RST_BIT_SHIFT)) != 0;
+        let is_syn = (flags & (1 << SYN_BIT_SHIFT)) != 0;
+
+        let capture_time = parsed_packet.get_meta().capture_time;
+        let tracked = track_flow(
+            state,
+            if is_fin || is_rst {
+                FlowKind::TcpClosed
+            } else {
+                FlowKind::TcpEstablished
+            },
+            (source, tcp.get_source()),
+            (destination, tcp.get_destination()),
+            capture_time,
+        );
+
+        let mut tcp_packet = SerializableTcpPacket::from(&tcp);
+        // Untracked flows keep no state, not even the one of their segments
+        if tracked {
+            analyze_segment(state, source, destination, &mut tcp_packet, capture_time);
+        }
+        parsed_packet.set_transport_layer_packet(Some(SerializablePacket::TcpPacket(tcp_packet)));
+
+        if !tracked || state.dissection_depth() == DissectionDepth::Transport {
+            return;
+        }
+        link_flow_packet(
+            state,
+            parsed_packet,
+            (source, tcp.get_source()),
+            (destination, tcp.get_destination()),
+        );
+
+        let (payload, gap) = reassemble(
+            state,
+            (source, tcp.get_source()),
+            (destination, tcp.get_destination()),
+            tcp.get_sequence(),
+            is_syn,
+            tcp.payload(),
+        );
+        // In-order payloads are shared with the frame, reassembled ones moved
+        let payload = match payload {
+            Cow::Borrowed(payload) => parsed_packet.get_meta().frame.share_or_copy(payload),
+            Cow::Owned(payload) => FrameBytes::from(payload),
+        };
+        parsed_packet.set_reassembly_gap(gap);
+        parsed_packet.set_stream_payload(payload.clone());
 
         handle_application_protocol(
+            state,
             source,
             tcp.get_source(),
             destination,
             tcp.get_destination(),
+            Transport::Tcp,
             is_fin,
-            tcp.payload(),
+            &payload,
             parsed_packet,
         );
+        // Messages spanning the missing bytes can't be parsed: report them as such
+        if let Some(gap) = gap {
+            if let Some(SerializablePacket::MalformedPacket(_)) =
+                parsed_packet.get_application_layer_packet()
+            {
+                parsed_packet.set_application_layer_packet(Some(
+                    SerializablePacket::PartiallyReassembled(gap),
+                ));
+            }
+        }
+        if !payload.is_empty() {
+            link_segment(
+                state,
+                parsed_packet,
+                (source, tcp.get_source()),
+                (destination, tcp.get_destination()),
+            );
+        }
     } else {
         debug!("Malformed TCP Packet");
         parsed_packet.set_transport_layer_packet(Some(SerializablePacket::MalformedPacket(
@@ -103,6 +237,7 @@ pub fn handle_tcp_packet(
 
 /// Build a Transport-layer packet from a network-layer packet, save it in a Parsed Packet
 pub fn handle_transport_protocol(
+    state: &mut ParserContext,
     source: IpAddr,
     destination: IpAddr,
     protocol: IpNextHeaderProtocol,
@@ -110,14 +245,20 @@ pub fn handle_transport_protocol(
     parsed_packet: &mut ParsedPacket,
 ) {
     return match protocol {
-        IpNextHeaderProtocols::Udp => handle_udp_packet(source, destination, packet, parsed_packet),
-        IpNextHeaderProtocols::Tcp => handle_tcp_packet(source, destination, packet, parsed_packet),
+        IpNextHeaderProtocols::Udp => {
+            handle_udp_packet(state, source, destination, packet, parsed_packet)
+        }
+        IpNextHeaderProtocols::Tcp => {
+            handle_tcp_packet(state, source, destination, packet, parsed_packet)
+        }
         IpNextHeaderProtocols::Icmp => {
-            handle_icmp_packet(source, destination, packet, parsed_packet)
+            handle_icmp_packet(state, source, destination, packet, parsed_packet)
         }
         IpNextHeaderProtocols::Icmpv6 => {
-            handle_icmpv6_packet(source, destination, packet, parsed_packet)
+            handle_icmpv6_packet(state, source, destination, packet, parsed_packet)
         }
+        IpNextHeaderProtocols::Esp => handle_esp_packet(source, destination, packet, parsed_packet),
+        IpNextHeaderProtocols::Ah => handle_ah_packet(source, destination, packet, parsed_packet),
         _ => {
             debug!(
                 "Unknown {} packet: {} > {}; protocol: {:?} length: {}",
@@ -136,6 +277,7 @@ pub fn handle_transport_protocol(
 
 /// Build a ICMP packet from a network-layer packet, save it in a Parsed Packet
 pub fn handle_icmp_packet(
+    state: &mut ParserContext,
     source: IpAddr,
     destination: IpAddr,
     packet: &[u8],
@@ -143,6 +285,17 @@ pub fn handle_icmp_packet(
 ) {
     let icmp_packet = IcmpPacket::new(packet);
     if let Some(icmp_packet) = icmp_packet {
+        let tracked = track_flow(
+            state,
+            FlowKind::Icmp,
+            (source, 0),
+            (destination, 0),
+            parsed_packet.get_meta().capture_time,
+        );
+        if tracked {
+            link_flow_packet(state, parsed_packet, (source, 0), (destination, 0));
+        }
+
         match icmp_packet.get_icmp_type() {
             IcmpTypes::EchoReply => {
                 let echo_reply_packet = echo_reply::EchoReplyPacket::new(packet).unwrap();
@@ -153,6 +306,17 @@ pub fn handle_icmp_packet(
                     echo_reply_packet.get_sequence_number(),
                     echo_reply_packet.get_identifier(),
                 );
+                if tracked {
+                    link_echo(
+                        state,
+                        parsed_packet,
+                        source,
+                        destination,
+                        echo_reply_packet.get_identifier(),
+                        echo_reply_packet.get_sequence_number(),
+                        true,
+                    );
+                }
 
                 parsed_packet.set_transport_layer_packet(Some(
                     SerializablePacket::EchoReplyPacket(SerializableEchoReplyPacket::from(
@@ -169,6 +333,17 @@ pub fn handle_icmp_packet(
                     echo_request_packet.get_sequence_number(),
                     echo_request_packet.get_identifier()
                 );
+                if tracked {
+                    link_echo(
+                        state,
+                        parsed_packet,
+                        source,
+                        destination,
+                        echo_request_packet.get_identifier(),
+                        echo_request_packet.get_sequence_number(),
+                        false,
+                    );
+                }
 
                 parsed_packet.set_transport_layer_packet(Some(
                     SerializablePacket::EchoRequestPacket(SerializableEchoRequestPacket::from(
@@ -176,6 +351,24 @@ pub fn handle_icmp_packet(
                     )),
                 ));
             }
+            IcmpTypes::Timestamp
+            | IcmpTypes::TimestampReply
+            | IcmpTypes::AddressMaskRequest
+            | IcmpTypes::AddressMaskReply
+            | IcmpTypes::RouterAdvertisement => {
+                debug!(
+                    "ICMP {} {} -> {}",
+                    icmp_type_to_string(icmp_packet.get_icmp_type()),
+                    source,
+                    destination
+                );
+
+                parsed_packet.set_transport_layer_packet(Some(
+                    parse_icmp_message(packet).unwrap_or_else(|| {
+                        SerializablePacket::MalformedPacket("Malformed ICMP Packet".to_string())
+                    }),
+                ));
+            }
             _ => {
                 debug!(
                     "ICMP packet {} -> {} (code={:?}, type={:?})",
@@ -184,6 +377,17 @@ pub fn handle_icmp_packet(
                     icmp_packet.get_icmp_code(),
                     icmp_packet.get_icmp_type()
                 );
+                match icmp_packet.get_icmp_type() {
+                    IcmpTypes::DestinationUnreachable
+                    | IcmpTypes::SourceQuench
+                    | IcmpTypes::TimeExceeded
+                    | IcmpTypes::ParameterProblem => {
+                        if let Some(original) = packet.get(8..).and_then(original_flow) {
+                            link_icmp_error(state, parsed_packet, original);
+                        }
+                    }
+                    _ => (),
+                }
 
                 parsed_packet.set_transport_layer_packet(Some(SerializablePacket::IcmpPacket(
                     SerializableIcmpPacket::from(&icmp_packet),
@@ -200,6 +404,7 @@ pub fn handle_icmp_packet(
 
 /// Build a ICMPv6 packet from a network-layer packet, save it in a Parsed Packet
 pub fn handle_icmpv6_packet(
+    state: &mut ParserContext,
     source: IpAddr,
     destination: IpAddr,
     packet: &[u8],
@@ -207,6 +412,17 @@ pub fn handle_icmpv6_packet(
 ) {
     let icmpv6_packet = Icmpv6Packet::new(packet);
     if let Some(icmpv6_packet) = icmpv6_packet {
+        let tracked = track_flow(
+            state,
+            FlowKind::Icmp,
+            (source, 0),
+            (destination, 0),
+            parsed_packet.get_meta().capture_time,
+        );
+        if tracked {
+            link_flow_packet(state, parsed_packet, (source, 0), (destination, 0));
+        }
+
         debug!(
             "ICMPv6 packet {} -> {} (type={:?})",
             source,
@@ -214,6 +430,29 @@ pub fn handle_icmpv6_packet(
             icmpv6_packet.get_icmpv6_type()
         );
 
+        match icmpv6_packet.get_icmpv6_type() {
+            Icmpv6Types::EchoRequest | Icmpv6Types::EchoReply if tracked && packet.len() >= 8 => {
+                link_echo(
+                    state,
+                    parsed_packet,
+                    source,
+                    destination,
+                    u16::from_be_bytes([packet[4], packet[5]]),
+                    u16::from_be_bytes([packet[6], packet[7]]),
+                    icmpv6_packet.get_icmpv6_type() == Icmpv6Types::EchoReply,
+                )
+            }
+            Icmpv6Types::DestinationUnreachable
+            | Icmpv6Types::PacketTooBig
+            | Icmpv6Types::TimeExceeded
+            | Icmpv6Types::ParameterProblem => {
+                if let Some(original) = packet.get(8..).and_then(original_flow) {
+                    link_icmp_error(state, parsed_packet, original);
+                }
+            }
+            _ => (),
+        }
+
         parsed_packet.set_transport_layer_packet(Some(SerializablePacket::Icmpv6Packet(
             SerializableIcmpv6Packet::from(&icmpv6_packet),
         )));
@@ -225,6 +464,320 @@ pub fn handle_icmpv6_packet(
     }
 }
 
+/// Build an ESP packet from a network-layer packet, save it in a Parsed Packet
+pub fn handle_esp_packet(
+    source: IpAddr,
+    destination: IpAddr,
+    packet: &[u8],
+    parsed_packet: &mut ParsedPacket,
+) {
+    match parse_esp_header(packet, false) {
+        Some(esp_packet) => {
+            debug!(
+                "ESP packet {} -> {} (spi={:#010x}, seq={})",
+                source, destination, esp_packet.spi, esp_packet.sequence
+            );
+
+            parsed_packet
+                .set_transport_layer_packet(Some(SerializablePacket::EspPacket(esp_packet)));
+        }
+        None => {
+            debug!("Malformed ESP Packet");
+            parsed_packet.set_transport_layer_packet(Some(SerializablePacket::MalformedPacket(
+                "Malformed ESP Packet".to_string(),
+            )));
+        }
+    }
+}
+
+/// Build an AH packet from a network-layer packet, save it in a Parsed Packet
+///
+/// The payload authenticated by AH is not decoded.
+pub fn handle_ah_packet(
+    source: IpAddr,
+    destination: IpAddr,
+    packet: &[u8],
+    parsed_packet: &mut ParsedPacket,
+) {
+    match parse_ah_header(packet) {
+        Some(ah_packet) => {
+            debug!(
+                "AH packet {} -> {} (spi={:#010x}, seq={}, next header={})",
+                source, destination, ah_packet.spi, ah_packet.sequence, ah_packet.next_header
+            );
+
+            parsed_packet.set_transport_layer_packet(Some(SerializablePacket::AhPacket(ah_packet)));
+        }
+        None => {
+            debug!("Malformed AH Packet");
+            parsed_packet.set_transport_layer_packet(Some(SerializablePacket::MalformedPacket(
+                "Malformed AH Packet".to_string(),
+            )));
+        }
+    }
+}
+
+/// Parse the header of an ESP packet: its SPI and sequence number, the rest is encrypted
+pub(crate) fn parse_esp_header(
+    packet: &[u8],
+    udp_encapsulated: bool,
+) -> Option<SerializableEspPacket> {
+    let header = packet.get(..8)?;
+    let spi = u32::from_be_bytes(header[..4].try_into().unwrap());
+    // SPIs up to 255 are reserved, 0 marks the IKE messages on the NAT traversal port
+    if spi == 0 {
+        return None;
+    }
+
+    Some(SerializableEspPacket {
+        spi,
+        sequence: u32::from_be_bytes(header[4..].try_into().unwrap()),
+        udp_encapsulated,
+        length: packet.len() - 8,
+        cleartext: parse_esp_cleartext(&packet[8..]),
+    })
+}
+
+/// Parse the payload of an ESP packet as a NULL-encrypted one
+///
+/// The trailer of the payload (its padding, their length and the next header) precedes the
+/// integrity check value, whose length depends on the algorithm of the SA: the usual lengths are
+/// tried in turn. The payload is in the clear if the padding is the default one (1, 2, 3, ...) and
+/// the payload is a packet of the next header protocol, with a consistent length.
+fn parse_esp_cleartext(payload: &[u8]) -> Option<EspCleartextPayload> {
+    ESP_ICV_LENGTHS.iter().find_map(|&icv_length| {
+        let trailer = payload.len().checked_sub(icv_length)?;
+        // The payload and its trailer are aligned on 4 bytes
+        if trailer < 2 || trailer & 0x03 != 0 {
+            return None;
+        }
+        let next_header = payload[trailer - 1];
+        let padding_length = payload[trailer - 2] as usize;
+        let data_end = (trailer - 2).checked_sub(padding_length)?;
+        let padding = &payload[data_end..trailer - 2];
+        if !padding
+            .iter()
+            .enumerate()
+            .all(|(index, &byte)| byte as usize == index + 1)
+        {
+            return None;
+        }
+
+        parse_cleartext_data(next_header, &payload[..data_end], icv_length)
+    })
+}
+
+/// Check that the payload of a NULL-encrypted ESP packet is a packet of its next header protocol
+fn parse_cleartext_data(
+    next_header: u8,
+    data: &[u8],
+    icv_length: usize,
+) -> Option<EspCleartextPayload> {
+    let mut cleartext = EspCleartextPayload {
+        next_header: format!("{} ({})", IpNextHeaderProtocol(next_header), next_header),
+        mode: "Transport".to_owned(),
+        length: data.len(),
+        icv_length,
+        inner_source: None,
+        inner_destination: None,
+        inner_protocol: None,
+        source_port: None,
+        destination_port: None,
+    };
+
+    let (protocol, segment) = match IpNextHeaderProtocol(next_header) {
+        IpNextHeaderProtocols::Ipv4 => {
+            let header_length = (*data.first()? & 0x0f) as usize * 4;
+            if data[0] >> 4 != 4
+                || header_length < 20
+                || u16::from_be_bytes(data.get(2..4)?.try_into().unwrap()) as usize != data.len()
+            {
+                return None;
+            }
+            let addresses = data.get(12..20)?;
+            cleartext.mode = "Tunnel".to_owned();
+            cleartext.inner_source = Some(IpAddr::V4(Ipv4Addr::new(
+                addresses[0],
+                addresses[1],
+                addresses[2],
+                addresses[3],
+            )));
+            cleartext.inner_destination = Some(IpAddr::V4(Ipv4Addr::new(
+                addresses[4],
+                addresses[5],
+                addresses[6],
+                addresses[7],
+            )));
+            (IpNextHeaderProtocol(data[9]), data.get(header_length..)?)
+        }
+        IpNextHeaderProtocols::Ipv6 => {
+            if data.first()? >> 4 != 6
+                || u16::from_be_bytes(data.get(4..6)?.try_into().unwrap()) as usize + 40
+                    != data.len()
+            {
+                return None;
+            }
+            let source: [u8; 16] = data[8..24].try_into().unwrap();
+            let destination: [u8; 16] = data[24..40].try_into().unwrap();
+            cleartext.mode = "Tunnel".to_owned();
+            cleartext.inner_source = Some(IpAddr::V6(Ipv6Addr::from(source)));
+            cleartext.inner_destination = Some(IpAddr::V6(Ipv6Addr::from(destination)));
+            (IpNextHeaderProtocol(data[6]), &data[40..])
+        }
+        protocol => (protocol, data),
+    };
+
+    let valid = match protocol {
+        IpNextHeaderProtocols::Tcp => {
+            let data_offset = (*segment.get(12)? >> 4) as usize * 4;
+            data_offset >= 20 && data_offset <= segment.len()
+        }
+        IpNextHeaderProtocols::Udp => {
+            segment.len() >= 8
+                && u16::from_be_bytes(segment[4..6].try_into().unwrap()) as usize == segment.len()
+        }
+        IpNextHeaderProtocols::Icmp | IpNextHeaderProtocols::Icmpv6 => segment.len() >= 8,
+        // The protocols carried by the inner packets are not checked
+        _ => cleartext.inner_source.is_some(),
+    };
+    if !valid {
+        return None;
+    }
+
+    if cleartext.inner_source.is_some() {
+        cleartext.inner_protocol = Some(format!("{} ({})", protocol, protocol.0));
+    }
+    if [IpNextHeaderProtocols::Tcp, IpNextHeaderProtocols::Udp].contains(&protocol) {
+        cleartext.source_port = Some(u16::from_be_bytes(segment[..2].try_into().unwrap()));
+        cleartext.destination_port = Some(u16::from_be_bytes(segment[2..4].try_into().unwrap()));
+    }
+
+    Some(cleartext)
+}
+
+/// Parse the header of an AH packet, whose length is given in 32-bit words, minus 2
+fn parse_ah_header(packet: &[u8]) -> Option<SerializableAhPacket> {
+    let next_header = *packet.first()?;
+    let header_length = (*packet.get(1)? as usize + 2) * 4;
+    let header = packet
+        .get(..header_length)
+        .filter(|_| header_length >= 12)?;
+
+    Some(SerializableAhPacket {
+        next_header: format!("{} ({})", IpNextHeaderProtocol(next_header), next_header),
+        spi: u32::from_be_bytes(header[4..8].try_into().unwrap()),
+        sequence: u32::from_be_bytes(header[8..12].try_into().unwrap()),
+        icv: header[12..]
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect(),
+        length: packet.len() - header_length,
+    })
+}
+
+/// Parse an ICMP timestamp, address mask or router advertisement message, none if it is truncated
+fn parse_icmp_message(packet: &[u8]) -> Option<SerializablePacket> {
+    let word = |offset: usize| {
+        packet
+            .get(offset..offset + 4)
+            .map(|bytes| u32::from_be_bytes(bytes.try_into().unwrap()))
+    };
+    let (icmp_type, icmp_code) = (*packet.first()?, *packet.get(1)?);
+    let checksum = u16::from_be_bytes([*packet.get(2)?, *packet.get(3)?]);
+    // Identifier and sequence number, or number and size of the router addresses and lifetime
+    let rest_of_header = word(4)?;
+    let (identifier, sequence_number) = ((rest_of_header >> 16) as u16, rest_of_header as u16);
+
+    match IcmpType(icmp_type) {
+        IcmpTypes::Timestamp | IcmpTypes::TimestampReply => Some(
+            SerializablePacket::IcmpTimestampPacket(SerializableIcmpTimestampPacket {
+                icmp_type,
+                icmp_code,
+                checksum,
+                identifier,
+                sequence_number,
+                originate_timestamp: word(8)?,
+                receive_timestamp: word(12)?,
+                transmit_timestamp: word(16)?,
+            }),
+        ),
+        IcmpTypes::AddressMaskRequest | IcmpTypes::AddressMaskReply => Some(
+            SerializablePacket::IcmpAddressMaskPacket(SerializableIcmpAddressMaskPacket {
+                icmp_type,
+                icmp_code,
+                checksum,
+                identifier,
+                sequence_number,
+                address_mask: Ipv4Addr::from(word(8)?),
+            }),
+        ),
+        IcmpTypes::RouterAdvertisement => {
+            // 32-bit words of each entry: at least the address and its preference
+            let addresses = (rest_of_header >> 24) as usize;
+            let entry_size = (rest_of_header >> 16) as u8 as usize;
+            if entry_size < 2 {
+                return None;
+            }
+            let routers = (0..addresses)
+                .map(|i| {
+                    let offset = 8 + i * entry_size * 4;
+                    Some(RouterAddress {
+                        address: Ipv4Addr::from(word(offset)?),
+                        preference: word(offset + 4)? as i32,
+                    })
+                })
+                .collect::<Option<Vec<_>>>()?;
+
+            Some(SerializablePacket::RouterAdvertisementPacket(
+                SerializableRouterAdvertisementPacket {
+                    icmp_code,
+                    checksum,
+                    lifetime: rest_of_header as u16,
+                    routers,
+                },
+            ))
+        }
+        _ => None,
+    }
+}
+
+/// Get the flow of the original packet carried by an ICMP or ICMPv6 error, from its IP header and
+/// the start of its transport-layer header
+fn original_flow(original: &[u8]) -> Option<Flow> {
+    let (source, destination, protocol, payload) = match original.first()? >> 4 {
+        4 => {
+            let ipv4 = Ipv4Packet::new(original)?;
+            let header_length = ipv4.get_header_length() as usize * 4;
+            (
+                IpAddr::V4(ipv4.get_source()),
+                IpAddr::V4(ipv4.get_destination()),
+                ipv4.get_next_level_protocol(),
+                original.get(header_length..)?,
+            )
+        }
+        6 => {
+            let ipv6 = Ipv6Packet::new(original)?;
+            (
+                IpAddr::V6(ipv6.get_source()),
+                IpAddr::V6(ipv6.get_destination()),
+                ipv6.get_next_header(),
+                original.get(40..)?,
+            )
+        }
+        _ => return None,
+    };
+
+    let (source_port, destination_port) = match protocol {
+        IpNextHeaderProtocols::Tcp | IpNextHeaderProtocols::Udp => (
+            u16::from_be_bytes([*payload.first()?, *payload.get(1)?]),
+            u16::from_be_bytes([*payload.get(2)?, *payload.get(3)?]),
+        ),
+        _ => (0, 0),
+    };
+
+    Some(((source, source_port), (destination, destination_port)))
+}
+
 #[cfg(test)]
 mod tests {
     use std::net::IpAddr;
@@ -243,16 +796,20 @@ mod tests {
 
     use crate::serializable_packet::transport::icmp_type_to_string;
     use crate::serializable_packet::transport::icmpv6_type_to_string;
+    use crate::serializable_packet::{PacketReference, PacketRelation};
 
     use super::*;
 
     #[test]
     fn valid_udp_packet() {
+        let mut state = ParserContext::new();
+
         let mut udp_buffer = [0u8; 42];
 
         let udp_packet = build_test_udp_packet(udp_buffer.as_mut_slice());
         let mut parsed_packet = ParsedPacket::new(0);
         handle_udp_packet(
+            &mut state,
             IpAddr::V4(Ipv4Addr::new(10, 10, 10, 10)),
             IpAddr::V4(Ipv4Addr::new(11, 11, 11, 11)),
             udp_packet.packet(),
@@ -273,8 +830,11 @@ mod tests {
 
     #[test]
     fn malformed_udp_packet() {
+        let mut state = ParserContext::new();
+
         let mut parsed_packet = ParsedPacket::new(0);
         handle_udp_packet(
+            &mut state,
             IpAddr::V4(Ipv4Addr::new(10, 10, 10, 10)),
             IpAddr::V4(Ipv4Addr::new(11, 11, 11, 11)),
             &[],
@@ -289,11 +849,14 @@ mod tests {
 
     #[test]
     fn valid_tcp_packet() {
+        let mut state = ParserContext::new();
+
         let mut tcp_buffer = [0u8; 42];
 
         let tcp_packet = build_test_tcp_packet(tcp_buffer.as_mut_slice());
         let mut parsed_packet = ParsedPacket::new(0);
         handle_tcp_packet(
+            &mut state,
             IpAddr::V4(Ipv4Addr::new(10, 10, 10, 10)),
             IpAddr::V4(Ipv4Addr::new(11, 11, 11, 11)),
             tcp_packet.packet(),
@@ -324,8 +887,11 @@ mod tests {
 
     #[test]
     fn malformed_tcp_packet() {
+        let mut state = ParserContext::new();
+
         let mut parsed_packet = ParsedPacket::new(0);
         handle_tcp_packet(
+            &mut state,
             IpAddr::V4(Ipv4Addr::new(10, 10, 10, 10)),
             IpAddr::V4(Ipv4Addr::new(11, 11, 11, 11)),
             &[],
@@ -340,11 +906,14 @@ mod tests {
 
     #[test]
     fn valid_icmp_echo_reply_packet() {
+        let mut state = ParserContext::new();
+
         let mut icmp_buffer = [0u8; 42];
 
         let echo_reply_packet = echo_reply::EchoReplyPacket::new(&mut icmp_buffer).unwrap();
         let mut parsed_packet = ParsedPacket::new(0);
         handle_icmp_packet(
+            &mut state,
             IpAddr::V4(Ipv4Addr::new(10, 10, 10, 10)),
             IpAddr::V4(Ipv4Addr::new(11, 11, 11, 11)),
             echo_reply_packet.packet(),
@@ -384,6 +953,8 @@ mod tests {
 
     #[test]
     fn valid_icmp_echo_request_packet() {
+        let mut state = ParserContext::new();
+
         let mut icmp_buffer = [0u8; 42];
         let mut echo_request_packet =
             echo_request::MutableEchoRequestPacket::new(&mut icmp_buffer).unwrap();
@@ -392,6 +963,7 @@ mod tests {
 
         let mut parsed_packet = ParsedPacket::new(0);
         handle_icmp_packet(
+            &mut state,
             IpAddr::V4(Ipv4Addr::new(10, 10, 10, 10)),
             IpAddr::V4(Ipv4Addr::new(11, 11, 11, 11)),
             echo_request_packet.packet(),
@@ -431,6 +1003,8 @@ mod tests {
 
     #[test]
     fn unrecognized_icmp_packet() {
+        let mut state = ParserContext::new();
+
         let mut icmp_buffer = [0u8; 42];
 
         let mut icmp_packet = MutableIcmpPacket::new(&mut icmp_buffer).unwrap();
@@ -438,6 +1012,7 @@ mod tests {
 
         let mut parsed_packet = ParsedPacket::new(0);
         handle_icmp_packet(
+            &mut state,
             IpAddr::V4(Ipv4Addr::new(10, 10, 10, 10)),
             IpAddr::V4(Ipv4Addr::new(11, 11, 11, 11)),
             icmp_packet.packet(),
@@ -460,8 +1035,11 @@ mod tests {
 
     #[test]
     fn malformed_icmp_packet() {
+        let mut state = ParserContext::new();
+
         let mut parsed_packet = ParsedPacket::new(0);
         handle_icmp_packet(
+            &mut state,
             IpAddr::V4(Ipv4Addr::new(10, 10, 10, 10)),
             IpAddr::V4(Ipv4Addr::new(11, 11, 11, 11)),
             &[],
@@ -474,13 +1052,111 @@ mod tests {
         }
     }
 
+    #[test]
+    fn icmp_timestamp_and_address_mask_packets() {
+        let mut state = ParserContext::new();
+
+        let timestamp_reply = [
+            0x0e, 0x00, 0xab, 0xcd, 0x00, 0x01, 0x00, 0x02, 0x00, 0x00, 0x03, 0xe8, 0x00, 0x00,
+            0x07, 0xd0, 0x00, 0x00, 0x0b, 0xb8,
+        ];
+        match parse_icmp_message(&timestamp_reply).unwrap() {
+            SerializablePacket::IcmpTimestampPacket(timestamp) => assert_eq!(
+                timestamp,
+                SerializableIcmpTimestampPacket {
+                    icmp_type: 14,
+                    icmp_code: 0,
+                    checksum: 0xabcd,
+                    identifier: 1,
+                    sequence_number: 2,
+                    originate_timestamp: 1000,
+                    receive_timestamp: 2000,
+                    transmit_timestamp: 3000,
+                }
+            ),
+            _ => unreachable!(),
+        }
+
+        let address_mask_reply = [
+            0x12, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x01, 0xff, 0xff, 0xff, 0x00,
+        ];
+        match parse_icmp_message(&address_mask_reply).unwrap() {
+            SerializablePacket::IcmpAddressMaskPacket(address_mask) => {
+                assert_eq!(address_mask.icmp_type, 18);
+                assert_eq!(address_mask.address_mask, Ipv4Addr::new(255, 255, 255, 0));
+            }
+            _ => unreachable!(),
+        }
+
+        // Timestamps missing
+        let mut parsed_packet = ParsedPacket::new(0);
+        handle_icmp_packet(
+            &mut state,
+            IpAddr::V4(Ipv4Addr::new(10, 10, 10, 10)),
+            IpAddr::V4(Ipv4Addr::new(11, 11, 11, 11)),
+            &timestamp_reply[..12],
+            &mut parsed_packet,
+        );
+        match parsed_packet.get_transport_layer_packet().unwrap() {
+            SerializablePacket::MalformedPacket(str) => assert_eq!(str, "Malformed ICMP Packet"),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn icmp_router_advertisement_packet() {
+        let mut state = ParserContext::new();
+
+        // Two addresses of 2 words each, valid for 1800 seconds
+        let router_advertisement = [
+            0x09, 0x00, 0x12, 0x34, 0x02, 0x02, 0x07, 0x08, 0x0a, 0x00, 0x00, 0x01, 0x00, 0x00,
+            0x00, 0x0a, 0x0a, 0x00, 0x00, 0x02, 0xff, 0xff, 0xff, 0xff,
+        ];
+
+        let mut parsed_packet = ParsedPacket::new(0);
+        handle_icmp_packet(
+            &mut state,
+            IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)),
+            IpAddr::V4(Ipv4Addr::new(224, 0, 0, 1)),
+            &router_advertisement,
+            &mut parsed_packet,
+        );
+
+        match parsed_packet.get_transport_layer_packet().unwrap() {
+            SerializablePacket::RouterAdvertisementPacket(advertisement) => {
+                assert_eq!(advertisement.checksum, 0x1234);
+                assert_eq!(advertisement.lifetime, 1800);
+                assert_eq!(
+                    advertisement.routers,
+                    vec![
+                        RouterAddress {
+                            address: Ipv4Addr::new(10, 0, 0, 1),
+                            preference: 10,
+                        },
+                        RouterAddress {
+                            address: Ipv4Addr::new(10, 0, 0, 2),
+                            preference: -1,
+                        },
+                    ]
+                );
+            }
+            _ => unreachable!(),
+        }
+
+        // Second address missing
+        assert!(parse_icmp_message(&router_advertisement[..16]).is_none());
+    }
+
     #[test]
     fn valid_icmpv6_packet() {
+        let mut state = ParserContext::new();
+
         let mut icmpv6_buffer = [0u8; 42];
 
         let icmpv6_packet = build_test_icmpv6_packet(&mut icmpv6_buffer);
         let mut parsed_packet = ParsedPacket::new(0);
         handle_icmpv6_packet(
+            &mut state,
             IpAddr::V4(Ipv4Addr::new(10, 10, 10, 10)),
             IpAddr::V4(Ipv4Addr::new(11, 11, 11, 11)),
             icmpv6_packet.packet(),
@@ -506,8 +1182,11 @@ mod tests {
 
     #[test]
     fn malformed_icmpv6_packet() {
+        let mut state = ParserContext::new();
+
         let mut parsed_packet = ParsedPacket::new(0);
         handle_icmpv6_packet(
+            &mut state,
             IpAddr::V4(Ipv4Addr::new(10, 10, 10, 10)),
             IpAddr::V4(Ipv4Addr::new(11, 11, 11, 11)),
             &[],
@@ -520,8 +1199,200 @@ mod tests {
         }
     }
 
+    #[test]
+    fn valid_esp_packet() {
+        let mut state = ParserContext::new();
+
+        let mut esp_packet = vec![0x00, 0x00, 0x10, 0x01, 0x00, 0x00, 0x00, 0x2a];
+        esp_packet.extend_from_slice(&[0xab; 32]);
+        let mut parsed_packet = ParsedPacket::new(0);
+        handle_transport_protocol(
+            &mut state,
+            IpAddr::V4(Ipv4Addr::new(10, 10, 10, 10)),
+            IpAddr::V4(Ipv4Addr::new(11, 11, 11, 11)),
+            IpNextHeaderProtocols::Esp,
+            &esp_packet,
+            &mut parsed_packet,
+        );
+
+        match parsed_packet.get_transport_layer_packet().unwrap() {
+            SerializablePacket::EspPacket(new_esp_packet) => {
+                assert_eq!(new_esp_packet.spi, 0x1001);
+                assert_eq!(new_esp_packet.sequence, 42);
+                assert!(!new_esp_packet.udp_encapsulated);
+                assert_eq!(new_esp_packet.length, 32);
+                assert!(new_esp_packet.cleartext.is_none());
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn null_encrypted_esp_packets() {
+        // Tunnel mode, HMAC-SHA1-96: IPv4 packet carrying a UDP datagram, 2 bytes of padding
+        let mut tunnel = vec![0x00, 0x00, 0x10, 0x01, 0x00, 0x00, 0x00, 0x01];
+        tunnel.extend([
+            0x45, 0, 0, 32, 0, 0, 0, 0, 64, 17, 0, 0, 192, 168, 0, 2, 8, 8, 8, 8,
+        ]);
+        tunnel.extend([
+            0xc3, 0x50, 0x00, 0x35, 0x00, 0x0c, 0x00, 0x00, 0xde, 0xad, 0xbe, 0xef,
+        ]);
+        tunnel.extend([0x01, 0x02, 0x02, 0x04]);
+        tunnel.extend([0xcc; 12]);
+
+        let cleartext = parse_esp_header(&tunnel, false).unwrap().cleartext.unwrap();
+        assert_eq!(cleartext.mode, "Tunnel");
+        assert_eq!(cleartext.next_header, "Ipv4 (4)");
+        assert_eq!(cleartext.length, 32);
+        assert_eq!(cleartext.icv_length, 12);
+        assert_eq!(
+            cleartext.inner_destination,
+            Some(IpAddr::V4(Ipv4Addr::new(8, 8, 8, 8)))
+        );
+        assert_eq!(cleartext.inner_protocol, Some("Udp (17)".to_owned()));
+        assert_eq!(cleartext.destination_port, Some(53));
+
+        // Transport mode, HMAC-SHA2-256-128: TCP segment without data
+        let mut transport = vec![0x00, 0x00, 0x10, 0x02, 0x00, 0x00, 0x00, 0x01];
+        transport.extend([0x11, 0x5c, 0x01, 0xbb, 0, 0, 0, 0, 0, 0, 0, 0, 0x50, 0x02]);
+        transport.extend([0xff, 0xff, 0, 0, 0, 0]);
+        transport.extend([0x01, 0x02, 0x02, 0x06]);
+        transport.extend([0xcc; 16]);
+
+        let cleartext = parse_esp_header(&transport, false)
+            .unwrap()
+            .cleartext
+            .unwrap();
+        assert_eq!(cleartext.mode, "Transport");
+        assert_eq!(cleartext.icv_length, 16);
+        assert_eq!(cleartext.inner_source, None);
+        assert_eq!(cleartext.source_port, Some(4444));
+        assert_eq!(cleartext.destination_port, Some(443));
+
+        // Not the default padding
+        transport[28] = 0x07;
+        assert!(parse_esp_header(&transport, false)
+            .unwrap()
+            .cleartext
+            .is_none());
+    }
+
+    #[test]
+    fn valid_ah_packet() {
+        let mut state = ParserContext::new();
+
+        // HMAC-SHA1-96: 12 bytes of ICV, a 24 bytes long header
+        let mut ah_packet = vec![0x06, 0x04, 0x00, 0x00, 0x00, 0x00, 0x20, 0x02, 0, 0, 0, 7];
+        ah_packet.extend_from_slice(&[0x11; 12]);
+        ah_packet.extend_from_slice(&[0; 20]);
+        let mut parsed_packet = ParsedPacket::new(0);
+        handle_transport_protocol(
+            &mut state,
+            IpAddr::V4(Ipv4Addr::new(10, 10, 10, 10)),
+            IpAddr::V4(Ipv4Addr::new(11, 11, 11, 11)),
+            IpNextHeaderProtocols::Ah,
+            &ah_packet,
+            &mut parsed_packet,
+        );
+
+        match parsed_packet.get_transport_layer_packet().unwrap() {
+            SerializablePacket::AhPacket(new_ah_packet) => {
+                assert_eq!(new_ah_packet.next_header, "Tcp (6)");
+                assert_eq!(new_ah_packet.spi, 0x2002);
+                assert_eq!(new_ah_packet.sequence, 7);
+                assert_eq!(new_ah_packet.icv, "11".repeat(12));
+                assert_eq!(new_ah_packet.length, 20);
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn malformed_ipsec_packets() {
+        let mut parsed_packet = ParsedPacket::new(0);
+        handle_esp_packet(
+            IpAddr::V4(Ipv4Addr::new(10, 10, 10, 10)),
+            IpAddr::V4(Ipv4Addr::new(11, 11, 11, 11)),
+            &[0, 0, 0, 1, 0],
+            &mut parsed_packet,
+        );
+
+        match parsed_packet.get_transport_layer_packet().unwrap() {
+            SerializablePacket::MalformedPacket(str) => assert_eq!(str, "Malformed ESP Packet"),
+            _ => unreachable!(),
+        }
+
+        // Header longer than the packet
+        handle_ah_packet(
+            IpAddr::V4(Ipv4Addr::new(10, 10, 10, 10)),
+            IpAddr::V4(Ipv4Addr::new(11, 11, 11, 11)),
+            &[0x06, 0x04, 0, 0, 0, 0, 0x20, 0x02, 0, 0, 0, 7],
+            &mut parsed_packet,
+        );
+
+        match parsed_packet.get_transport_layer_packet().unwrap() {
+            SerializablePacket::MalformedPacket(str) => assert_eq!(str, "Malformed AH Packet"),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn icmp_error_references_original_flow() {
+        let mut state = ParserContext::new();
+
+        let client = IpAddr::V4(Ipv4Addr::new(10, 10, 10, 10));
+        let server = IpAddr::V4(Ipv4Addr::new(11, 11, 11, 11));
+        let mut udp_packet = ParsedPacket::new(1);
+        handle_udp_packet(&mut state, client, server, &UDP_DATAGRAM, &mut udp_packet);
+
+        // Port unreachable, carrying the IPv4 header and the UDP header of the datagram
+        let mut icmp_error = [3, 3, 0, 0, 0, 0, 0, 0].to_vec();
+        icmp_error.extend_from_slice(&[
+            0x45, 0, 0, 28, 0, 0, 0, 0, 64, 17, 0, 0, 10, 10, 10, 10, 11, 11, 11, 11,
+        ]);
+        icmp_error.extend_from_slice(&UDP_DATAGRAM);
+        let mut parsed_packet = ParsedPacket::new(2);
+        handle_icmp_packet(&mut state, server, client, &icmp_error, &mut parsed_packet);
+
+        assert_eq!(
+            parsed_packet.get_references(),
+            &[PacketReference {
+                id: 1,
+                relation: PacketRelation::Original
+            }]
+        );
+    }
+
+    #[test]
+    fn dissection_depth_of_the_context() {
+        let mut state = ParserContext::new();
+        state.set_dissection_depth(Some(DissectionDepth::Transport));
+        assert_eq!(state.dissection_depth(), DissectionDepth::Transport);
+        assert_eq!(get_dissection_depth(), DissectionDepth::Application);
+
+        // Packets of the context are not linked to the later ones of their flow
+        let client = IpAddr::V4(Ipv4Addr::new(10, 10, 10, 10));
+        let server = IpAddr::V4(Ipv4Addr::new(11, 11, 11, 11));
+        let mut parsed_packet = ParsedPacket::new(0);
+        handle_udp_packet(
+            &mut state,
+            client,
+            server,
+            &UDP_DATAGRAM,
+            &mut parsed_packet,
+        );
+        assert!(state.links.is_empty());
+
+        state.clear();
+        assert_eq!(state.dissection_depth(), DissectionDepth::Transport);
+        state.set_dissection_depth(None);
+        assert_eq!(state.dissection_depth(), DissectionDepth::Application);
+    }
+
     ///////////////////// Utils
 
+    const UDP_DATAGRAM: [u8; 8] = [0xc3, 0x50, 0x00, 0x35, 0x00, 0x08, 0x00, 0x00];
+
     fn build_test_udp_packet<'a>(udp_buffer: &'a mut [u8]) -> UdpPacket<'a> {
         let mut udp_packet = MutableUdpPacket::new(udp_buffer).unwrap();
 
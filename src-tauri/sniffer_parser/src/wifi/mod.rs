@@ -0,0 +1,686 @@
+//! IEEE 802.11 frame parsing, for monitor-mode captures
+//!
+//! Data frames are converted to Ethernet frames, so that their content goes through the usual
+//! dissection. Protected data frames are decrypted when a [`Wpa2Decryptor`] is provided and the
+//! 4-way handshake of the station has been captured.
+//!
+//! Other frames are dissected as 802.11 ones: their type, addresses and flags, the SSID, channel
+//! and beacon interval of the beacons and probes, and the reason of the deauthentications.
+//! The radio information of the Radiotap header (signal, channel, rate) is added to the metadata of
+//! the packets of the frame.
+
+mod radiotap;
+pub mod wpa2;
+
+pub use self::wpa2::Wpa2Decryptor;
+
+use pnet::packet::ethernet::EthernetPacket;
+use pnet::util::MacAddr;
+use tracing::debug;
+
+use self::radiotap::parse_radiotap_header;
+use self::wpa2::CCMP_HEADER_LENGTH;
+use crate::serializable_packet::{
+    PacketMeta, ParsedPacket, SerializableIeee80211Packet, SerializablePacket,
+};
+use crate::{dissect_ethernet_frame, parse_frame, FrameMeta, LinkType, ParserContext};
+
+/// 802.11 Frame Control fields
+#[allow(non_snake_case)]
+mod FrameControl {
+    pub const TYPE_MASK: u8 = 0x0c;
+    pub const TYPE_MANAGEMENT: u8 = 0x00;
+    pub const TYPE_CONTROL: u8 = 0x04;
+    pub const TYPE_DATA: u8 = 0x08;
+    pub const SUBTYPE_QOS: u8 = 0x80;
+    pub const SUBTYPE_NULL: u8 = 0x40;
+
+    pub const TO_DS: u8 = 0x01;
+    pub const FROM_DS: u8 = 0x02;
+    pub const RETRY: u8 = 0x08;
+    pub const PROTECTED: u8 = 0x40;
+    pub const ORDER: u8 = 0x80;
+}
+
+/// 802.11 management frame subtypes
+#[allow(non_snake_case)]
+mod ManagementSubtypes {
+    pub const ASSOCIATION_REQUEST: u8 = 0;
+    pub const ASSOCIATION_RESPONSE: u8 = 1;
+    pub const REASSOCIATION_REQUEST: u8 = 2;
+    pub const REASSOCIATION_RESPONSE: u8 = 3;
+    pub const PROBE_REQUEST: u8 = 4;
+    pub const PROBE_RESPONSE: u8 = 5;
+    pub const BEACON: u8 = 8;
+    pub const DISASSOCIATION: u8 = 10;
+    pub const DEAUTHENTICATION: u8 = 12;
+}
+
+/// 802.11 element ids
+#[allow(non_snake_case)]
+mod ElementIds {
+    pub const SSID: u8 = 0;
+    pub const DS_PARAMETER_SET: u8 = 3;
+}
+
+const HEADER_LENGTH: usize = 24;
+/// Length of the shortest frames, ACK and CTS: frame control, duration and receiver address
+const MIN_LENGTH: usize = 10;
+const ADDRESS_LENGTH: usize = 6;
+const QOS_LENGTH: usize = 2;
+const HT_CONTROL_LENGTH: usize = 4;
+
+const LLC_SNAP: &[u8] = &[0xaa, 0xaa, 0x03, 0x00, 0x00, 0x00];
+const ETHERTYPE_EAPOL: [u8; 2] = [0x88, 0x8e];
+
+/// Addressing and payload of an 802.11 data frame
+struct DataFrame<'a> {
+    header: &'a [u8],
+    body: &'a [u8],
+    destination: [u8; 6],
+    source: [u8; 6],
+    bssid: [u8; 6],
+    station: [u8; 6],
+    protected: bool,
+}
+
+/// Parse an 802.11 frame (without FCS) obtaining the representation of its content
+///
+/// Frames other than data ones carrying an LLC payload (management, control, protected data
+/// without its key, mesh) are represented as 802.11 packets.
+pub fn parse_ieee80211_frame(
+    frame: &[u8],
+    decryptor: Option<&mut Wpa2Decryptor>,
+    id: usize,
+) -> ParsedPacket {
+    let meta = FrameMeta {
+        decryptor,
+        ..FrameMeta::new(id)
+    };
+    parse_frame(LinkType::Ieee80211, frame, meta)
+}
+
+/// Dissect an 802.11 frame (without FCS) with the state of a parser context
+pub(crate) fn dissect_ieee80211_frame(
+    state: &mut ParserContext,
+    frame: &[u8],
+    decryptor: Option<&mut Wpa2Decryptor>,
+    meta: PacketMeta,
+) -> ParsedPacket {
+    if let Some(ethernet_frame) = to_ethernet_frame(frame, decryptor) {
+        if let Some(ethernet) = EthernetPacket::new(&ethernet_frame) {
+            return dissect_ethernet_frame(state, &ethernet, meta);
+        }
+    }
+
+    let mut parsed_packet = ParsedPacket::with_meta(meta);
+    match dissect_ieee80211_packet(frame) {
+        Some(ieee80211_packet) => {
+            parsed_packet
+                .set_link_layer_packet(Some(SerializablePacket::Ieee80211Packet(ieee80211_packet)));
+        }
+        None => {
+            debug!("Malformed IEEE 802.11 Frame");
+            parsed_packet.set_link_layer_packet(Some(SerializablePacket::MalformedPacket(
+                "Malformed IEEE 802.11 Frame".to_owned(),
+            )));
+        }
+    }
+
+    parsed_packet
+}
+
+/// Parse an 802.11 frame preceded by a Radiotap header
+pub fn parse_radiotap_frame(
+    frame: &[u8],
+    decryptor: Option<&mut Wpa2Decryptor>,
+    id: usize,
+) -> ParsedPacket {
+    let meta = FrameMeta {
+        decryptor,
+        ..FrameMeta::new(id)
+    };
+    parse_frame(LinkType::Ieee80211Radiotap, frame, meta)
+}
+
+/// Dissect an 802.11 frame preceded by a Radiotap header with the state of a parser context
+pub(crate) fn dissect_radiotap_frame(
+    state: &mut ParserContext,
+    frame: &[u8],
+    decryptor: Option<&mut Wpa2Decryptor>,
+    meta: PacketMeta,
+) -> ParsedPacket {
+    match parse_radiotap_header(frame) {
+        Some((radio, frame)) => {
+            let meta = PacketMeta {
+                radio: Some(radio),
+                ..meta
+            };
+            dissect_ieee80211_frame(state, frame, decryptor, meta)
+        }
+        None => {
+            debug!("Malformed Radiotap Header");
+            let mut parsed_packet = ParsedPacket::with_meta(meta);
+            parsed_packet.set_link_layer_packet(Some(SerializablePacket::MalformedPacket(
+                "Malformed Radiotap Header".to_owned(),
+            )));
+
+            parsed_packet
+        }
+    }
+}
+
+/// Get the 802.11 frame following a Radiotap header, without its FCS
+pub fn strip_radiotap_header(frame: &[u8]) -> Option<&[u8]> {
+    parse_radiotap_header(frame).map(|(_, frame)| frame)
+}
+
+/// Get the offset of the packet carried by an unprotected 802.11 data frame, after its LLC/SNAP
+/// header and EtherType
+pub fn ieee80211_network_offset(frame: &[u8]) -> Option<usize> {
+    let data_frame = parse_data_frame(frame)?;
+    if data_frame.protected || !data_frame.body.starts_with(LLC_SNAP) {
+        return None;
+    }
+
+    Some(data_frame.header.len() + LLC_SNAP.len() + 2)
+}
+
+/// Get the offset of the packet carried by an unprotected 802.11 data frame preceded by a Radiotap
+/// header
+pub fn radiotap_network_offset(frame: &[u8]) -> Option<usize> {
+    parse_radiotap_header(frame)?;
+    let length = u16::from_le_bytes([frame[2], frame[3]]) as usize;
+
+    ieee80211_network_offset(&frame[length..]).map(|offset| length + offset)
+}
+
+/// Dissect the header of an 802.11 frame, and the body of its management frames
+fn dissect_ieee80211_packet(frame: &[u8]) -> Option<SerializableIeee80211Packet> {
+    if frame.len() < MIN_LENGTH {
+        return None;
+    }
+
+    let frame_type = frame[0] & FrameControl::TYPE_MASK;
+    let subtype = frame[0] >> 4;
+    let flags = frame[1];
+    let address = |index: usize| {
+        let start = 4 + index * ADDRESS_LENGTH;
+        frame.get(start..start + ADDRESS_LENGTH).map(mac_address)
+    };
+    // Control frames carry at most two addresses
+    if frame_type != FrameControl::TYPE_CONTROL && frame.len() < HEADER_LENGTH {
+        return None;
+    }
+
+    let bssid = match frame_type {
+        FrameControl::TYPE_MANAGEMENT => address(2),
+        FrameControl::TYPE_DATA => match (
+            flags & FrameControl::TO_DS != 0,
+            flags & FrameControl::FROM_DS != 0,
+        ) {
+            (false, false) => address(2),
+            (true, false) => address(0),
+            (false, true) => address(1),
+            (true, true) => None,
+        },
+        _ => None,
+    };
+
+    let mut ieee80211_packet = SerializableIeee80211Packet {
+        frame_type: frame_type_name(frame_type).to_owned(),
+        subtype: subtype_name(frame_type, subtype),
+        receiver: mac_address(&frame[4..10]),
+        transmitter: address(1),
+        bssid,
+        protected: flags & FrameControl::PROTECTED != 0,
+        retry: flags & FrameControl::RETRY != 0,
+        ssid: None,
+        channel: None,
+        beacon_interval: None,
+        reason_code: None,
+        reason: None,
+        length: frame.len(),
+    };
+
+    // The body of the protected management frames is encrypted
+    if frame_type == FrameControl::TYPE_MANAGEMENT && !ieee80211_packet.protected {
+        let mut header_length = HEADER_LENGTH;
+        if flags & FrameControl::ORDER != 0 {
+            header_length += HT_CONTROL_LENGTH;
+        }
+        if let Some(body) = frame.get(header_length..) {
+            dissect_management_body(subtype, body, &mut ieee80211_packet);
+        }
+    }
+
+    Some(ieee80211_packet)
+}
+
+/// Dissect the fixed fields and the elements of the body of a management frame
+fn dissect_management_body(
+    subtype: u8,
+    body: &[u8],
+    ieee80211_packet: &mut SerializableIeee80211Packet,
+) {
+    let u16_at = |offset: usize| {
+        body.get(offset..offset + 2)
+            .map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]))
+    };
+
+    // Fixed fields come before the elements
+    let elements_offset = match subtype {
+        ManagementSubtypes::PROBE_REQUEST => 0,
+        ManagementSubtypes::ASSOCIATION_REQUEST => 4,
+        ManagementSubtypes::ASSOCIATION_RESPONSE | ManagementSubtypes::REASSOCIATION_RESPONSE => 6,
+        ManagementSubtypes::REASSOCIATION_REQUEST => 10,
+        ManagementSubtypes::BEACON | ManagementSubtypes::PROBE_RESPONSE => {
+            // After the timestamp
+            ieee80211_packet.beacon_interval = u16_at(8);
+            12
+        }
+        ManagementSubtypes::DISASSOCIATION | ManagementSubtypes::DEAUTHENTICATION => {
+            ieee80211_packet.reason_code = u16_at(0);
+            ieee80211_packet.reason = ieee80211_packet.reason_code.map(reason_name);
+            return;
+        }
+        _ => return,
+    };
+
+    let mut elements = body.get(elements_offset..).unwrap_or_default();
+    while elements.len() >= 2 {
+        let (id, length) = (elements[0], elements[1] as usize);
+        let value = match elements.get(2..2 + length) {
+            Some(value) => value,
+            None => break,
+        };
+        match id {
+            ElementIds::SSID => {
+                ieee80211_packet.ssid = Some(String::from_utf8_lossy(value).into_owned());
+            }
+            ElementIds::DS_PARAMETER_SET if length == 1 => {
+                ieee80211_packet.channel = Some(value[0]);
+            }
+            _ => (),
+        }
+        elements = &elements[2 + length..];
+    }
+}
+
+fn frame_type_name(frame_type: u8) -> &'static str {
+    match frame_type {
+        FrameControl::TYPE_MANAGEMENT => "Management",
+        FrameControl::TYPE_CONTROL => "Control",
+        FrameControl::TYPE_DATA => "Data",
+        _ => "Extension",
+    }
+}
+
+fn subtype_name(frame_type: u8, subtype: u8) -> String {
+    let name = match (frame_type, subtype) {
+        (FrameControl::TYPE_MANAGEMENT, 0) => "Association Request",
+        (FrameControl::TYPE_MANAGEMENT, 1) => "Association Response",
+        (FrameControl::TYPE_MANAGEMENT, 2) => "Reassociation Request",
+        (FrameControl::TYPE_MANAGEMENT, 3) => "Reassociation Response",
+        (FrameControl::TYPE_MANAGEMENT, 4) => "Probe Request",
+        (FrameControl::TYPE_MANAGEMENT, 5) => "Probe Response",
+        (FrameControl::TYPE_MANAGEMENT, 6) => "Timing Advertisement",
+        (FrameControl::TYPE_MANAGEMENT, 8) => "Beacon",
+        (FrameControl::TYPE_MANAGEMENT, 9) => "ATIM",
+        (FrameControl::TYPE_MANAGEMENT, 10) => "Disassociation",
+        (FrameControl::TYPE_MANAGEMENT, 11) => "Authentication",
+        (FrameControl::TYPE_MANAGEMENT, 12) => "Deauthentication",
+        (FrameControl::TYPE_MANAGEMENT, 13) => "Action",
+        (FrameControl::TYPE_MANAGEMENT, 14) => "Action No Ack",
+        (FrameControl::TYPE_CONTROL, 4) => "Beamforming Report Poll",
+        (FrameControl::TYPE_CONTROL, 5) => "VHT NDP Announcement",
+        (FrameControl::TYPE_CONTROL, 6) => "Control Frame Extension",
+        (FrameControl::TYPE_CONTROL, 7) => "Control Wrapper",
+        (FrameControl::TYPE_CONTROL, 8) => "Block Ack Request",
+        (FrameControl::TYPE_CONTROL, 9) => "Block Ack",
+        (FrameControl::TYPE_CONTROL, 10) => "PS-Poll",
+        (FrameControl::TYPE_CONTROL, 11) => "RTS",
+        (FrameControl::TYPE_CONTROL, 12) => "CTS",
+        (FrameControl::TYPE_CONTROL, 13) => "ACK",
+        (FrameControl::TYPE_CONTROL, 14) => "CF-End",
+        (FrameControl::TYPE_CONTROL, 15) => "CF-End + CF-Ack",
+        (FrameControl::TYPE_DATA, 0) => "Data",
+        (FrameControl::TYPE_DATA, 4) => "Null",
+        (FrameControl::TYPE_DATA, 8) => "QoS Data",
+        (FrameControl::TYPE_DATA, 12) => "QoS Null",
+        (FrameControl::TYPE_DATA, 1..=7) => "Data + CF",
+        (FrameControl::TYPE_DATA, 9..=15) => "QoS Data + CF",
+        _ => return format!("Unknown ({})", subtype),
+    };
+
+    name.to_owned()
+}
+
+fn reason_name(reason_code: u16) -> String {
+    let name = match reason_code {
+        1 => "Unspecified reason",
+        2 => "Previous authentication no longer valid",
+        3 => "Station is leaving the BSS",
+        4 => "Disassociated due to inactivity",
+        5 => "Disassociated because the AP is unable to handle all the associated stations",
+        6 => "Class 2 frame received from nonauthenticated station",
+        7 => "Class 3 frame received from nonassociated station",
+        8 => "Disassociated because the station is leaving the BSS",
+        9 => "Station requesting (re)association is not authenticated",
+        14 => "Message integrity code (MIC) failure",
+        15 => "4-Way Handshake timeout",
+        16 => "Group Key Handshake timeout",
+        23 => "IEEE 802.1X authentication failed",
+        _ => return format!("Unknown ({})", reason_code),
+    };
+
+    name.to_owned()
+}
+
+/// Convert an 802.11 data frame to an Ethernet frame, decrypting it if needed
+fn to_ethernet_frame(frame: &[u8], decryptor: Option<&mut Wpa2Decryptor>) -> Option<Vec<u8>> {
+    let data_frame = parse_data_frame(frame)?;
+    let pairing = (data_frame.bssid, data_frame.station);
+
+    let decrypted;
+    let body = if data_frame.protected {
+        decrypted = decryptor
+            .as_ref()?
+            .decrypt(&pairing, data_frame.header, data_frame.body)?;
+        decrypted.as_slice()
+    } else {
+        data_frame.body
+    };
+
+    if body.len() < LLC_SNAP.len() + 2 || !body.starts_with(LLC_SNAP) {
+        return None;
+    }
+    let ethertype = &body[LLC_SNAP.len()..LLC_SNAP.len() + 2];
+    let payload = &body[LLC_SNAP.len() + 2..];
+
+    if ethertype == ETHERTYPE_EAPOL {
+        if let Some(decryptor) = decryptor {
+            decryptor.handle_eapol(pairing, payload);
+        }
+    }
+
+    Some(
+        [
+            &data_frame.destination[..],
+            &data_frame.source,
+            ethertype,
+            payload,
+        ]
+        .concat(),
+    )
+}
+
+fn parse_data_frame(frame: &[u8]) -> Option<DataFrame<'_>> {
+    if frame.len() < HEADER_LENGTH || frame[0] & FrameControl::TYPE_MASK != FrameControl::TYPE_DATA
+    {
+        return None;
+    }
+
+    // Null data frames carry no payload
+    if frame[0] & FrameControl::SUBTYPE_NULL != 0 {
+        return None;
+    }
+
+    let flags = frame[1];
+    let address = |index: usize| -> [u8; 6] {
+        let mut address = [0u8; 6];
+        let start = 4 + index * ADDRESS_LENGTH;
+        address.copy_from_slice(&frame[start..start + ADDRESS_LENGTH]);
+        address
+    };
+
+    // Addresses meaning depends on the distribution system direction
+    let (destination, source, bssid, station) = match (
+        flags & FrameControl::TO_DS != 0,
+        flags & FrameControl::FROM_DS != 0,
+    ) {
+        (false, false) => (address(0), address(1), address(2), address(1)),
+        (true, false) => (address(2), address(1), address(0), address(1)),
+        (false, true) => (address(0), address(2), address(1), address(0)),
+        // Mesh and WDS frames are not supported
+        (true, true) => return None,
+    };
+
+    let mut header_length = HEADER_LENGTH;
+    if frame[0] & FrameControl::SUBTYPE_QOS != 0 {
+        header_length += QOS_LENGTH;
+    }
+    if frame.len() < header_length {
+        return None;
+    }
+
+    let protected = flags & FrameControl::PROTECTED != 0;
+    if protected && frame.len() < header_length + CCMP_HEADER_LENGTH {
+        return None;
+    }
+
+    Some(DataFrame {
+        header: &frame[..header_length],
+        body: &frame[header_length..],
+        destination,
+        source,
+        bssid,
+        station,
+        protected,
+    })
+}
+
+fn mac_address(bytes: &[u8]) -> MacAddr {
+    MacAddr::new(bytes[0], bytes[1], bytes[2], bytes[3], bytes[4], bytes[5])
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::serializable_packet::SerializablePacket;
+
+    use super::wpa2::Wpa2Decryptor;
+    use super::{
+        ieee80211_network_offset, parse_ieee80211_frame, parse_radiotap_frame,
+        radiotap_network_offset, strip_radiotap_header,
+    };
+
+    const AP: [u8; 6] = [0x00, 0x11, 0x22, 0x33, 0x44, 0x55];
+    const STATION: [u8; 6] = [0x66, 0x77, 0x88, 0x99, 0xaa, 0xbb];
+
+    #[test]
+    fn unprotected_data_frame() {
+        let frame = build_test_data_frame(0x01, &[0x08, 0x06], &[0u8; 28]);
+
+        let parsed_packet = parse_ieee80211_frame(&frame, None, 0);
+        match parsed_packet.get_link_layer_packet().unwrap() {
+            SerializablePacket::EthernetPacket(ethernet_packet) => {
+                assert_eq!(ethernet_packet.source.octets(), STATION);
+                assert_eq!(ethernet_packet.destination.octets(), [0xff; 6]);
+            }
+            _ => unreachable!(),
+        }
+        assert!(matches!(
+            parsed_packet.get_network_layer_packet(),
+            Some(SerializablePacket::ArpPacket(_))
+        ));
+    }
+
+    #[test]
+    fn protected_data_frame_without_key() {
+        let frame = build_test_data_frame(0x41, &[0x08, 0x00], &[0u8; 40]);
+        let mut decryptor = Wpa2Decryptor::new("IEEE", "password");
+
+        let parsed_packet = parse_ieee80211_frame(&frame, Some(&mut decryptor), 0);
+        match parsed_packet.get_link_layer_packet().unwrap() {
+            SerializablePacket::Ieee80211Packet(ieee80211_packet) => {
+                assert_eq!(ieee80211_packet.subtype, "Data");
+                assert!(ieee80211_packet.protected);
+                assert_eq!(ieee80211_packet.bssid.unwrap().octets(), AP);
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn beacon_frame() {
+        let body = [
+            &[0u8; 8][..],
+            &[0x64, 0x00, 0x11, 0x04],
+            &[0x00, 0x04],
+            b"IEEE",
+            &[0x01, 0x02, 0x82, 0x84],
+            &[0x03, 0x01, 0x06],
+        ]
+        .concat();
+        let frame = build_test_management_frame(0x80, [0xff; 6], &body);
+
+        match parse_ieee80211_frame(&frame, None, 0)
+            .get_link_layer_packet()
+            .unwrap()
+        {
+            SerializablePacket::Ieee80211Packet(ieee80211_packet) => {
+                assert_eq!(ieee80211_packet.frame_type, "Management");
+                assert_eq!(ieee80211_packet.subtype, "Beacon");
+                assert_eq!(ieee80211_packet.transmitter.unwrap().octets(), AP);
+                assert_eq!(ieee80211_packet.bssid.unwrap().octets(), AP);
+                assert_eq!(ieee80211_packet.ssid.as_deref(), Some("IEEE"));
+                assert_eq!(ieee80211_packet.channel, Some(6));
+                assert_eq!(ieee80211_packet.beacon_interval, Some(100));
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn deauthentication_frame() {
+        let frame = build_test_management_frame(0xc0, STATION, &[0x07, 0x00]);
+
+        match parse_ieee80211_frame(&frame, None, 0)
+            .get_link_layer_packet()
+            .unwrap()
+        {
+            SerializablePacket::Ieee80211Packet(ieee80211_packet) => {
+                assert_eq!(ieee80211_packet.subtype, "Deauthentication");
+                assert_eq!(ieee80211_packet.receiver.octets(), STATION);
+                assert_eq!(ieee80211_packet.reason_code, Some(7));
+                assert_eq!(
+                    ieee80211_packet.reason.as_deref(),
+                    Some("Class 3 frame received from nonassociated station")
+                );
+                assert!(ieee80211_packet.ssid.is_none());
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn ack_frame() {
+        let frame = [&[0xd4, 0x00, 0x00, 0x00][..], &STATION].concat();
+
+        match parse_ieee80211_frame(&frame, None, 0)
+            .get_link_layer_packet()
+            .unwrap()
+        {
+            SerializablePacket::Ieee80211Packet(ieee80211_packet) => {
+                assert_eq!(ieee80211_packet.frame_type, "Control");
+                assert_eq!(ieee80211_packet.subtype, "ACK");
+                assert_eq!(ieee80211_packet.receiver.octets(), STATION);
+                assert!(ieee80211_packet.transmitter.is_none());
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn malformed_ieee80211_frame() {
+        let parsed_packet = parse_ieee80211_frame(&[0x08, 0x01, 0x00], None, 0);
+        assert!(matches!(
+            parsed_packet.get_link_layer_packet(),
+            Some(SerializablePacket::MalformedPacket(_))
+        ));
+    }
+
+    #[test]
+    fn radiotap_header_with_fcs() {
+        // Present: TSFT and flags (FCS included)
+        let radiotap = [
+            0x00, 0x00, 0x11, 0x00, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x10,
+        ];
+        let frame = build_test_data_frame(0x01, &[0x08, 0x06], &[0u8; 28]);
+        let captured = [&radiotap[..], &frame, &[0xde, 0xad, 0xbe, 0xef]].concat();
+
+        assert_eq!(strip_radiotap_header(&captured).unwrap(), frame.as_slice());
+        assert!(matches!(
+            parse_radiotap_frame(&captured, None, 0).get_network_layer_packet(),
+            Some(SerializablePacket::ArpPacket(_))
+        ));
+    }
+
+    #[test]
+    fn radiotap_radio_information() {
+        // Present: channel and antenna signal
+        let radiotap = [
+            0x00, 0x00, 0x0d, 0x00, 0x28, 0x00, 0x00, 0x00, 0x3c, 0x14, 0x40, 0x01, 0xbf,
+        ];
+        let frame = build_test_management_frame(0xc0, STATION, &[0x03, 0x00]);
+        let captured = [&radiotap[..], &frame].concat();
+
+        let parsed_packet = parse_radiotap_frame(&captured, None, 0);
+        let radio = parsed_packet.get_meta().radio.as_ref().unwrap();
+        assert_eq!(radio.frequency, Some(5180));
+        assert_eq!(radio.channel, Some(36));
+        assert_eq!(radio.signal, Some(-65));
+        assert!(matches!(
+            parsed_packet.get_link_layer_packet(),
+            Some(SerializablePacket::Ieee80211Packet(_))
+        ));
+    }
+
+    #[test]
+    fn network_offsets() {
+        let frame = build_test_data_frame(0x01, &[0x08, 0x00], &[0x45; 20]);
+        assert_eq!(ieee80211_network_offset(&frame), Some(32));
+
+        // Present: flags (FCS included)
+        let radiotap = [0x00, 0x00, 0x09, 0x00, 0x02, 0x00, 0x00, 0x00, 0x10];
+        let captured = [&radiotap[..], &frame, &[0xde, 0xad, 0xbe, 0xef]].concat();
+        assert_eq!(radiotap_network_offset(&captured), Some(41));
+
+        // The payload of protected and management frames is not in clear
+        let protected = build_test_data_frame(0x41, &[0x08, 0x00], &[0u8; 40]);
+        assert_eq!(ieee80211_network_offset(&protected), None);
+        let beacon = build_test_management_frame(0x80, [0xff; 6], &[0u8; 12]);
+        assert_eq!(ieee80211_network_offset(&beacon), None);
+    }
+
+    ///////////////////// Utils
+
+    /// Data frame from the station to the broadcast address, through the access point
+    fn build_test_data_frame(flags: u8, ethertype: &[u8], payload: &[u8]) -> Vec<u8> {
+        [
+            &[0x08, flags, 0x00, 0x00][..],
+            &AP,
+            &STATION,
+            &[0xff; 6],
+            &[0x10, 0x00],
+            &[0xaa, 0xaa, 0x03, 0x00, 0x00, 0x00],
+            ethertype,
+            payload,
+        ]
+        .concat()
+    }
+
+    /// Management frame of the given subtype from the access point
+    fn build_test_management_frame(subtype: u8, receiver: [u8; 6], body: &[u8]) -> Vec<u8> {
+        [
+            &[subtype, 0x00, 0x00, 0x00][..],
+            &receiver,
+            &AP,
+            &AP,
+            &[0x10, 0x00],
+            body,
+        ]
+        .concat()
+    }
+}
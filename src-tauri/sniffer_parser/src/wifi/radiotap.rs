@@ -0,0 +1,148 @@
+//! Radiotap header parsing, for the radio information of the frames captured in monitor mode
+//!
+//! Fields are aligned to their natural size from the start of the header, so the ones present
+//! before the signal and noise are walked through even when not reported.
+
+use crate::serializable_packet::RadioInfo;
+
+/// Alignment and size of the fields preceding the antenna noise, by their bit in the present word
+const FIELDS: [(usize, usize); 7] = [
+    // TSFT
+    (8, 8),
+    // Flags
+    (1, 1),
+    // Rate, in units of 500 kbps
+    (1, 1),
+    // Channel frequency and flags
+    (2, 4),
+    // FHSS
+    (1, 2),
+    // Antenna signal, in dBm
+    (1, 1),
+    // Antenna noise, in dBm
+    (1, 1),
+];
+
+const FLAGS: usize = 1;
+const RATE: usize = 2;
+const CHANNEL: usize = 3;
+const ANTENNA_SIGNAL: usize = 5;
+const ANTENNA_NOISE: usize = 6;
+
+/// Flags field: the frame includes the FCS
+const FLAGS_FCS: u8 = 0x10;
+const FCS_LENGTH: usize = 4;
+
+/// Present words are followed by another one when this bit is set
+const PRESENT_EXT: u32 = 0x8000_0000;
+
+/// Parse a Radiotap header, obtaining the radio information of the 802.11 frame following it,
+/// without its FCS
+pub(crate) fn parse_radiotap_header(frame: &[u8]) -> Option<(RadioInfo, &[u8])> {
+    if frame.len() < 8 {
+        return None;
+    }
+
+    let length = u16::from_le_bytes([frame[2], frame[3]]) as usize;
+    if length < 8 || frame.len() < length {
+        return None;
+    }
+    let header = &frame[..length];
+
+    // Skip the extended present bitmaps
+    let present = u32::from_le_bytes([frame[4], frame[5], frame[6], frame[7]]);
+    let mut offset = 4;
+    while offset + 4 <= length
+        && u32::from_le_bytes([
+            frame[offset],
+            frame[offset + 1],
+            frame[offset + 2],
+            frame[offset + 3],
+        ]) & PRESENT_EXT
+            != 0
+    {
+        offset += 4;
+    }
+    offset += 4;
+
+    let mut radio = RadioInfo::default();
+    let mut has_fcs = false;
+    for (bit, &(alignment, size)) in FIELDS.iter().enumerate() {
+        if present & (1 << bit) == 0 {
+            continue;
+        }
+
+        offset = (offset + alignment - 1) & !(alignment - 1);
+        let field = match header.get(offset..offset + size) {
+            Some(field) => field,
+            None => break,
+        };
+        match bit {
+            FLAGS => has_fcs = field[0] & FLAGS_FCS != 0,
+            RATE => radio.rate = Some(field[0] as u32 * 500),
+            CHANNEL => {
+                let frequency = u16::from_le_bytes([field[0], field[1]]);
+                radio.frequency = Some(frequency);
+                radio.channel = channel_number(frequency);
+            }
+            ANTENNA_SIGNAL => radio.signal = Some(field[0] as i8),
+            ANTENNA_NOISE => radio.noise = Some(field[0] as i8),
+            _ => (),
+        }
+        offset += size;
+    }
+
+    let frame = &frame[length..];
+    let frame = if has_fcs {
+        frame.get(..frame.len().checked_sub(FCS_LENGTH)?)?
+    } else {
+        frame
+    };
+
+    Some((radio, frame))
+}
+
+/// Get the number of the channel of the given frequency in MHz, in the 2.4, 5 and 6 GHz bands
+fn channel_number(frequency: u16) -> Option<u16> {
+    match frequency {
+        2484 => Some(14),
+        2412..=2472 => Some((frequency - 2407) / 5),
+        4910..=4980 => Some((frequency - 4000) / 5),
+        5000..=5900 => Some((frequency - 5000) / 5),
+        5935 => Some(2),
+        5955..=7115 => Some((frequency - 5950) / 5),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{channel_number, parse_radiotap_header};
+
+    #[test]
+    fn radio_information() {
+        // Present: flags, rate, channel, antenna signal and noise
+        let radiotap = [
+            0x00, 0x00, 0x10, 0x00, 0x6e, 0x00, 0x00, 0x00, 0x00, 0x0c, 0x85, 0x09, 0xa0, 0x00,
+            0xc4, 0xa0,
+        ];
+        let captured = [&radiotap[..], &[0x80, 0x00]].concat();
+
+        let (radio, frame) = parse_radiotap_header(&captured).unwrap();
+        assert_eq!(frame, [0x80, 0x00]);
+        assert_eq!(radio.rate, Some(6000));
+        assert_eq!(radio.frequency, Some(2437));
+        assert_eq!(radio.channel, Some(6));
+        assert_eq!(radio.signal, Some(-60));
+        assert_eq!(radio.noise, Some(-96));
+    }
+
+    #[test]
+    fn channel_numbers() {
+        assert_eq!(channel_number(2412), Some(1));
+        assert_eq!(channel_number(2484), Some(14));
+        assert_eq!(channel_number(5180), Some(36));
+        assert_eq!(channel_number(5955), Some(1));
+        assert_eq!(channel_number(3000), None);
+    }
+}
@@ -0,0 +1,381 @@
+//! WPA2-PSK decryption of 802.11 data frames
+//!
+//! The Pairwise Master Key is derived from the network SSID and passphrase. When the first two
+//! messages of the 4-way handshake between an access point and a station are captured,
+//! the Pairwise Transient Key is derived (and verified with the MIC of the second message),
+//! and the unicast frames they exchange afterwards are decrypted (CCMP).
+//! Group traffic, protected with the Group Temporal Key, is not decrypted.
+
+use std::collections::HashMap;
+
+use aes::Aes128;
+use ccm::aead::generic_array::GenericArray;
+use ccm::aead::AeadInPlace;
+use ccm::consts::{U13, U8};
+use ccm::{Ccm, KeyInit};
+use hmac::{Hmac, Mac};
+use pbkdf2::pbkdf2_hmac;
+use sha1::Sha1;
+use tracing::debug;
+
+type Aes128Ccm = Ccm<Aes128, U8, U13>;
+
+/// Access point (BSSID) and station addresses
+pub type Pairing = ([u8; 6], [u8; 6]);
+
+const PMK_ITERATIONS: u32 = 4096;
+const PTK_LABEL: &[u8] = b"Pairwise key expansion";
+
+pub const CCMP_HEADER_LENGTH: usize = 8;
+const CCMP_MIC_LENGTH: usize = 8;
+
+/// EAPOL-Key frame fields
+#[allow(non_snake_case)]
+mod EapolKey {
+    pub const TYPE: u8 = 3;
+    pub const INFO: usize = 5;
+    pub const NONCE: usize = 17;
+    pub const MIC: usize = 81;
+    pub const MIC_END: usize = 97;
+    pub const MIN_LENGTH: usize = 99;
+
+    pub const INFO_VERSION_MASK: u16 = 0x0007;
+    pub const INFO_PAIRWISE: u16 = 0x0008;
+    pub const INFO_ACK: u16 = 0x0080;
+    pub const INFO_MIC: u16 = 0x0100;
+    pub const INFO_SECURE: u16 = 0x0200;
+
+    /// HMAC-SHA1-128 MIC and AES key wrap
+    pub const VERSION_AES: u16 = 2;
+}
+
+/// Decrypts the traffic of a WPA2-PSK network
+pub struct Wpa2Decryptor {
+    pmk: [u8; 32],
+    anonces: HashMap<Pairing, [u8; 32]>,
+    keys: HashMap<Pairing, [u8; 16]>,
+}
+
+impl Wpa2Decryptor {
+    pub fn new(ssid: &str, passphrase: &str) -> Self {
+        Wpa2Decryptor {
+            pmk: derive_pmk(ssid, passphrase),
+            anonces: HashMap::new(),
+            keys: HashMap::new(),
+        }
+    }
+
+    /// Check if the temporal key of a pairing has been derived
+    pub fn has_key(&self, pairing: &Pairing) -> bool {
+        self.keys.contains_key(pairing)
+    }
+
+    /// Track the 4-way handshake through the EAPOL frames exchanged by a pairing
+    pub fn handle_eapol(&mut self, pairing: Pairing, eapol: &[u8]) {
+        if eapol.len() < EapolKey::MIN_LENGTH || eapol[1] != EapolKey::TYPE {
+            return;
+        }
+
+        let info = u16::from_be_bytes([eapol[EapolKey::INFO], eapol[EapolKey::INFO + 1]]);
+        if info & EapolKey::INFO_PAIRWISE == 0
+            || info & EapolKey::INFO_VERSION_MASK != EapolKey::VERSION_AES
+        {
+            return;
+        }
+
+        let mut nonce = [0u8; 32];
+        nonce.copy_from_slice(&eapol[EapolKey::NONCE..EapolKey::NONCE + 32]);
+
+        let ack = info & EapolKey::INFO_ACK != 0;
+        let mic = info & EapolKey::INFO_MIC != 0;
+        let secure = info & EapolKey::INFO_SECURE != 0;
+
+        match (ack, mic, secure) {
+            // Message 1: ANonce sent by the access point
+            (true, false, _) => {
+                self.anonces.insert(pairing, nonce);
+            }
+            // Message 2: SNonce sent by the station, protected by the MIC
+            (false, true, false) => {
+                let anonce = match self.anonces.get(&pairing) {
+                    Some(anonce) => anonce,
+                    None => return,
+                };
+
+                let ptk = derive_ptk(&self.pmk, &pairing.0, &pairing.1, anonce, &nonce);
+                if eapol_mic(&ptk[..16], eapol) == eapol[EapolKey::MIC..EapolKey::MIC_END] {
+                    let mut tk = [0u8; 16];
+                    tk.copy_from_slice(&ptk[32..48]);
+                    self.keys.insert(pairing, tk);
+                    debug!("WPA2 temporal key derived for {:02x?}", pairing);
+                } else {
+                    debug!("WPA2 handshake MIC mismatch: wrong passphrase?");
+                }
+            }
+            _ => (),
+        }
+    }
+
+    /// Decrypt the body of a protected frame exchanged by a pairing, given its 802.11 header
+    pub fn decrypt(&self, pairing: &Pairing, header: &[u8], body: &[u8]) -> Option<Vec<u8>> {
+        let tk = self.keys.get(pairing)?;
+        ccmp_decrypt(tk, header, body)
+    }
+}
+
+/// Derive the Pairwise Master Key from the network SSID and passphrase
+pub fn derive_pmk(ssid: &str, passphrase: &str) -> [u8; 32] {
+    let mut pmk = [0u8; 32];
+    pbkdf2_hmac::<Sha1>(
+        passphrase.as_bytes(),
+        ssid.as_bytes(),
+        PMK_ITERATIONS,
+        &mut pmk,
+    );
+
+    pmk
+}
+
+/// Derive the Pairwise Transient Key (KCK, KEK and TK) from the handshake data
+pub fn derive_ptk(
+    pmk: &[u8],
+    authenticator: &[u8; 6],
+    supplicant: &[u8; 6],
+    anonce: &[u8; 32],
+    snonce: &[u8; 32],
+) -> [u8; 64] {
+    let mut data = Vec::with_capacity(76);
+    data.extend_from_slice(authenticator.min(supplicant));
+    data.extend_from_slice(authenticator.max(supplicant));
+    data.extend_from_slice(anonce.min(snonce));
+    data.extend_from_slice(anonce.max(snonce));
+
+    prf_512(pmk, PTK_LABEL, &data)
+}
+
+/// IEEE 802.11i pseudo-random function, producing 512 bits
+fn prf_512(key: &[u8], label: &[u8], data: &[u8]) -> [u8; 64] {
+    let mut result = [0u8; 64];
+
+    for (i, chunk) in result.chunks_mut(20).enumerate() {
+        let mut mac =
+            <Hmac<Sha1> as Mac>::new_from_slice(key).expect("HMAC accepts keys of any size");
+        mac.update(label);
+        mac.update(&[0]);
+        mac.update(data);
+        mac.update(&[i as u8]);
+
+        let digest = mac.finalize().into_bytes();
+        chunk.copy_from_slice(&digest[..chunk.len()]);
+    }
+
+    result
+}
+
+/// Compute the MIC of an EAPOL-Key frame, whose MIC field is considered zeroed
+pub fn eapol_mic(kck: &[u8], eapol: &[u8]) -> [u8; 16] {
+    let mut frame = eapol.to_vec();
+    frame[EapolKey::MIC..EapolKey::MIC_END].fill(0);
+
+    let mut mac = <Hmac<Sha1> as Mac>::new_from_slice(kck).expect("HMAC accepts keys of any size");
+    mac.update(&frame);
+
+    let mut mic = [0u8; 16];
+    mic.copy_from_slice(&mac.finalize().into_bytes()[..16]);
+    mic
+}
+
+/// Build CCMP nonce and additional authentication data from the 802.11 header
+fn ccmp_parameters(header: &[u8], body: &[u8]) -> Option<([u8; 13], Vec<u8>)> {
+    let four_addresses = header[1] & 0x03 == 0x03;
+    let qos = header[0] & 0x80 != 0;
+    let qos_offset = if four_addresses { 30 } else { 24 };
+    if header.len() < qos_offset + if qos { 2 } else { 0 } || body.len() < CCMP_HEADER_LENGTH {
+        return None;
+    }
+
+    let priority = if qos { header[qos_offset] & 0x0f } else { 0 };
+
+    let mut nonce = [0u8; 13];
+    nonce[0] = priority;
+    nonce[1..7].copy_from_slice(&header[10..16]);
+    // Packet number, most significant byte first
+    nonce[7..13].copy_from_slice(&[body[7], body[6], body[5], body[4], body[1], body[0]]);
+
+    let mut aad = Vec::with_capacity(30);
+    // Frame control, without subtype, retry, power management and more data bits
+    aad.push(header[0] & 0x8f);
+    aad.push((header[1] & 0xc7) | 0x40);
+    aad.extend_from_slice(&header[4..22]);
+    // Sequence control, fragment number only
+    aad.push(header[22] & 0x0f);
+    aad.push(0);
+    if four_addresses {
+        aad.extend_from_slice(&header[24..30]);
+    }
+    if qos {
+        aad.push(priority);
+        aad.push(0);
+    }
+
+    Some((nonce, aad))
+}
+
+/// Decrypt a CCMP protected body (CCMP header, ciphertext and MIC)
+pub fn ccmp_decrypt(tk: &[u8; 16], header: &[u8], body: &[u8]) -> Option<Vec<u8>> {
+    if body.len() < CCMP_HEADER_LENGTH + CCMP_MIC_LENGTH {
+        return None;
+    }
+
+    let (nonce, aad) = ccmp_parameters(header, body)?;
+    let (ciphertext, mic) = body[CCMP_HEADER_LENGTH..].split_at(body.len() - 16);
+
+    let mut plaintext = ciphertext.to_vec();
+    Aes128Ccm::new(GenericArray::from_slice(tk))
+        .decrypt_in_place_detached(
+            GenericArray::from_slice(&nonce),
+            &aad,
+            &mut plaintext,
+            GenericArray::from_slice(mic),
+        )
+        .ok()?;
+
+    Some(plaintext)
+}
+
+/// Encrypt a body with CCMP, given the CCMP header carrying the packet number
+#[cfg(test)]
+pub fn ccmp_encrypt(
+    tk: &[u8; 16],
+    header: &[u8],
+    ccmp_header: &[u8; 8],
+    plaintext: &[u8],
+) -> Vec<u8> {
+    let (nonce, aad) = ccmp_parameters(header, ccmp_header).unwrap();
+
+    let mut ciphertext = plaintext.to_vec();
+    let mic = Aes128Ccm::new(GenericArray::from_slice(tk))
+        .encrypt_in_place_detached(GenericArray::from_slice(&nonce), &aad, &mut ciphertext)
+        .unwrap();
+
+    [ccmp_header.as_slice(), &ciphertext, &mic].concat()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        ccmp_decrypt, ccmp_encrypt, derive_pmk, derive_ptk, eapol_mic, EapolKey, Wpa2Decryptor,
+    };
+
+    const SSID: &str = "IEEE";
+    const PASSPHRASE: &str = "password";
+    const AP: [u8; 6] = [0x00, 0x11, 0x22, 0x33, 0x44, 0x55];
+    const STATION: [u8; 6] = [0x66, 0x77, 0x88, 0x99, 0xaa, 0xbb];
+
+    #[test]
+    fn pmk_test_vector() {
+        // IEEE 802.11i-2004, Annex H.4.1
+        let pmk = derive_pmk(SSID, PASSPHRASE);
+        assert_eq!(
+            pmk,
+            [
+                0xf4, 0x2c, 0x6f, 0xc5, 0x2d, 0xf0, 0xeb, 0xef, 0x9e, 0xbb, 0x4b, 0x90, 0xb3, 0x8a,
+                0x5f, 0x90, 0x2e, 0x83, 0xfe, 0x1b, 0x13, 0x5a, 0x70, 0xe2, 0x3a, 0xed, 0x76, 0x2e,
+                0x97, 0x10, 0xa1, 0x2e
+            ]
+        );
+    }
+
+    #[test]
+    fn ccmp_round_trip() {
+        let tk = [0x42; 16];
+        let header = build_test_header();
+        let ccmp_header = [0x01, 0x00, 0x00, 0x20, 0x00, 0x00, 0x00, 0x00];
+
+        let body = ccmp_encrypt(&tk, &header, &ccmp_header, b"miao");
+        assert_eq!(ccmp_decrypt(&tk, &header, &body).unwrap(), b"miao");
+        assert!(ccmp_decrypt(&[0x43; 16], &header, &body).is_none());
+    }
+
+    #[test]
+    fn handshake_derives_key() {
+        let mut decryptor = Wpa2Decryptor::new(SSID, PASSPHRASE);
+        let pairing = (AP, STATION);
+
+        decryptor.handle_eapol(pairing, &build_test_eapol(0x008a, [0xaa; 32], None));
+        assert!(!decryptor.has_key(&pairing));
+
+        let ptk = derive_ptk(
+            &derive_pmk(SSID, PASSPHRASE),
+            &AP,
+            &STATION,
+            &[0xaa; 32],
+            &[0xbb; 32],
+        );
+        decryptor.handle_eapol(
+            pairing,
+            &build_test_eapol(0x010a, [0xbb; 32], Some(&ptk[..16])),
+        );
+        assert!(decryptor.has_key(&pairing));
+
+        let tk = &ptk[32..48].try_into().unwrap();
+        let header = build_test_header();
+        let ccmp_header = [0x01, 0x00, 0x00, 0x20, 0x00, 0x00, 0x00, 0x00];
+        let body = ccmp_encrypt(tk, &header, &ccmp_header, b"miao");
+        assert_eq!(
+            decryptor.decrypt(&pairing, &header, &body).unwrap(),
+            b"miao"
+        );
+    }
+
+    #[test]
+    fn handshake_wrong_passphrase() {
+        let mut decryptor = Wpa2Decryptor::new(SSID, "wrong passphrase");
+        let pairing = (AP, STATION);
+
+        let ptk = derive_ptk(
+            &derive_pmk(SSID, PASSPHRASE),
+            &AP,
+            &STATION,
+            &[0xaa; 32],
+            &[0xbb; 32],
+        );
+        decryptor.handle_eapol(pairing, &build_test_eapol(0x008a, [0xaa; 32], None));
+        decryptor.handle_eapol(
+            pairing,
+            &build_test_eapol(0x010a, [0xbb; 32], Some(&ptk[..16])),
+        );
+        assert!(!decryptor.has_key(&pairing));
+    }
+
+    ///////////////////// Utils
+
+    /// QoS data frame from the station to the access point
+    fn build_test_header() -> Vec<u8> {
+        [
+            &[0x88, 0x41, 0x00, 0x00][..],
+            &AP,
+            &STATION,
+            &AP,
+            &[0x10, 0x00, 0x00, 0x00],
+        ]
+        .concat()
+    }
+
+    fn build_test_eapol(info: u16, nonce: [u8; 32], kck: Option<&[u8]>) -> Vec<u8> {
+        let mut eapol = vec![0u8; EapolKey::MIN_LENGTH];
+        eapol[0] = 2;
+        eapol[1] = EapolKey::TYPE;
+        eapol[2..4].copy_from_slice(&((EapolKey::MIN_LENGTH - 4) as u16).to_be_bytes());
+        eapol[4] = 2;
+        eapol[EapolKey::INFO..EapolKey::INFO + 2].copy_from_slice(&info.to_be_bytes());
+        eapol[EapolKey::NONCE..EapolKey::NONCE + 32].copy_from_slice(&nonce);
+
+        if let Some(kck) = kck {
+            let mic = eapol_mic(kck, &eapol);
+            eapol[EapolKey::MIC..EapolKey::MIC_END].copy_from_slice(&mic);
+        }
+
+        eapol
+    }
+}
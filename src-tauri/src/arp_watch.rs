@@ -0,0 +1,326 @@
+//! Detection of ARP spoofing on the local network
+//!
+//! The MAC address announced for each IPv4 address by the ARP replies and the gratuitous ARP
+//! packets (announcing the address of their own sender) is tracked, raising a security alert when:
+//! - the MAC address bound to an IPv4 address changes, as a host poisoning the ARP caches of the
+//!   others to intercept their traffic would do
+//! - a host sends a storm of gratuitous ARP packets, to keep the poisoned caches from recovering
+//!
+//! Alerts are emitted to the frontend as `security_alert` events while sniffing, and the last ones
+//! are kept, imported captures included.
+
+use std::collections::{HashMap, VecDeque};
+use std::net::{IpAddr, Ipv4Addr};
+
+use chrono::{DateTime, Local};
+use pnet::util::MacAddr;
+use serde::Serialize;
+use sniffer_parser::serializable_packet::{ParsedPacket, SerializablePacket};
+use tracing::warn;
+
+use crate::icmp_watch::IcmpErrorKind;
+use crate::latency_watch::LatencyKind;
+use crate::statistics::Timestamp;
+use crate::SniffingState;
+
+/// Gratuitous ARP packets of a host, within the storm window, raising an alert
+const GRATUITOUS_STORM_COUNT: usize = 10;
+
+/// Window of the gratuitous ARP packets counted for a storm, in milliseconds
+const GRATUITOUS_STORM_WINDOW: i64 = 1000;
+
+/// Alerts kept by each detector
+pub(crate) const ALERTS_SIZE: usize = 1000;
+
+/// Suspicious activity detected on the local network
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum SecurityAlert {
+    /// MAC address bound to an IPv4 address replaced by another one
+    #[serde(rename_all = "camelCase")]
+    ArpBindingChanged {
+        time: Timestamp,
+        ip: Ipv4Addr,
+        previous_mac: String,
+        mac: String,
+    },
+    /// Gratuitous ARP packets sent by a host within the storm window
+    #[serde(rename_all = "camelCase")]
+    GratuitousArpStorm {
+        time: Timestamp,
+        ip: Ipv4Addr,
+        mac: String,
+        count: usize,
+    },
+    /// ICMP errors of a kind sent by a source within the storm window
+    IcmpErrorStorm {
+        time: Timestamp,
+        source: IpAddr,
+        kind: IcmpErrorKind,
+        count: usize,
+    },
+    /// PAC file served to a client by a source which is not trusted
+    UnexpectedProxyConfiguration {
+        time: Timestamp,
+        client: IpAddr,
+        server: IpAddr,
+        host: String,
+        uri: String,
+        wpad: bool,
+    },
+    /// Response of an NTP server to a private (mode 7) request, as monlist
+    NtpPrivateResponse {
+        time: Timestamp,
+        server: IpAddr,
+        client: IpAddr,
+        request: String,
+        items: usize,
+    },
+    /// NTP responses of a server to a client outweighing its requests by the amplification ratio
+    #[serde(rename_all = "camelCase")]
+    NtpAmplification {
+        time: Timestamp,
+        server: IpAddr,
+        client: IpAddr,
+        request_bytes: usize,
+        response_bytes: usize,
+    },
+    /// Large responses of a reflection protocol received by a host which never requested them,
+    /// from several reflectors
+    ReflectionAttack {
+        time: Timestamp,
+        victim: IpAddr,
+        protocol: String,
+        reflectors: usize,
+        responses: usize,
+        bytes: usize,
+    },
+    /// Recent latency of a destination exceeding its baseline by the regression ratio, in
+    /// milliseconds
+    LatencyRegression {
+        time: Timestamp,
+        destination: IpAddr,
+        kind: LatencyKind,
+        baseline: f64,
+        latency: f64,
+    },
+}
+
+impl SecurityAlert {
+    pub fn time(&self) -> i64 {
+        match self {
+            SecurityAlert::ArpBindingChanged { time, .. }
+            | SecurityAlert::GratuitousArpStorm { time, .. }
+            | SecurityAlert::IcmpErrorStorm { time, .. }
+            | SecurityAlert::UnexpectedProxyConfiguration { time, .. }
+            | SecurityAlert::NtpPrivateResponse { time, .. }
+            | SecurityAlert::NtpAmplification { time, .. }
+            | SecurityAlert::ReflectionAttack { time, .. }
+            | SecurityAlert::LatencyRegression { time, .. } => *time,
+        }
+    }
+}
+
+/// Tracker of the ARP bindings of the local network, learned from the collected packets
+#[derive(Debug, Default)]
+pub struct ArpWatch {
+    /// MAC address last announced for each IPv4 address
+    bindings: HashMap<Ipv4Addr, MacAddr>,
+    /// Times of the gratuitous ARP packets of each IPv4 address, within the storm window
+    gratuitous: HashMap<Ipv4Addr, VecDeque<i64>>,
+    /// Last alerts raised, the oldest first
+    alerts: VecDeque<SecurityAlert>,
+}
+
+impl ArpWatch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Track the binding announced by a packet, given the time it was received, returning the
+    /// alerts raised
+    pub fn update(&mut self, packet: &ParsedPacket, time: DateTime<Local>) -> Vec<SecurityAlert> {
+        let arp = match packet.get_network_layer_packet() {
+            Some(SerializablePacket::ArpPacket(arp)) => arp,
+            _ => return vec![],
+        };
+
+        let is_gratuitous = arp.sender_proto_addr == arp.target_proto_addr;
+        // ARP probes have no sender address yet
+        if arp.sender_proto_addr.is_unspecified()
+            || !(is_gratuitous || arp.operation.starts_with("ARP Reply"))
+        {
+            return vec![];
+        }
+
+        let time = time.timestamp_millis();
+        let (ip, mac) = (arp.sender_proto_addr, arp.sender_hw_addr);
+        let mut alerts = vec![];
+
+        if let Some(previous_mac) = self.bindings.insert(ip, mac) {
+            if previous_mac != mac {
+                alerts.push(SecurityAlert::ArpBindingChanged {
+                    time,
+                    ip,
+                    previous_mac: previous_mac.to_string(),
+                    mac: mac.to_string(),
+                });
+            }
+        }
+
+        if is_gratuitous {
+            let times = self.gratuitous.entry(ip).or_default();
+            while times
+                .front()
+                .is_some_and(|first| time - first >= GRATUITOUS_STORM_WINDOW)
+            {
+                times.pop_front();
+            }
+            times.push_back(time);
+
+            // Raised once per storm, when the count is first reached
+            if times.len() == GRATUITOUS_STORM_COUNT {
+                alerts.push(SecurityAlert::GratuitousArpStorm {
+                    time,
+                    ip,
+                    mac: mac.to_string(),
+                    count: times.len(),
+                });
+            }
+        }
+
+        for alert in &alerts {
+            warn!("Security alert: {:?}", alert);
+            if self.alerts.len() >= ALERTS_SIZE {
+                self.alerts.pop_front();
+            }
+            self.alerts.push_back(alert.clone());
+        }
+
+        alerts
+    }
+
+    /// Get the last alerts raised, the oldest first
+    pub fn alerts(&self) -> Vec<SecurityAlert> {
+        self.alerts.iter().cloned().collect()
+    }
+
+    pub fn clear(&mut self) {
+        self.bindings.clear();
+        self.gratuitous.clear();
+        self.alerts.clear();
+    }
+}
+
+/// Returns the last security alerts raised by the collected packets, the oldest first
+#[tauri::command]
+pub fn get_security_alerts(state: tauri::State<SniffingState>) -> Vec<SecurityAlert> {
+    let packets = state.packets.lock().unwrap();
+    let mut alerts = packets.arp_watch.alerts();
+    alerts.extend(packets.icmp_watch.alerts());
+    alerts.extend(packets.proxy_config_watch.alerts());
+    alerts.extend(packets.ntp_watch.alerts());
+    alerts.extend(packets.reflection_watch.alerts());
+    alerts.extend(packets.latency_watch.alerts());
+    alerts.sort_by_key(SecurityAlert::time);
+
+    alerts
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use pnet::packet::ethernet::EthernetPacket;
+    use sniffer_parser::parse_ethernet_frame;
+    use sniffer_parser::serializable_packet::ParsedPacket;
+
+    use super::{ArpWatch, SecurityAlert, GRATUITOUS_STORM_COUNT};
+    use crate::test_utils::at;
+
+    const GATEWAY: [u8; 4] = [10, 10, 10, 1];
+    const HOST: [u8; 4] = [10, 10, 10, 10];
+    const GATEWAY_MAC: [u8; 6] = [0x00, 0x00, 0x0c, 0x01, 0x02, 0x03];
+    const ATTACKER_MAC: [u8; 6] = [0x08, 0x00, 0x27, 0xaa, 0xbb, 0xcc];
+
+    #[test]
+    fn binding_changed() {
+        let mut watch = ArpWatch::new();
+
+        assert!(watch
+            .update(&arp_packet(2, GATEWAY_MAC, GATEWAY, HOST), at(0))
+            .is_empty());
+        assert!(watch
+            .update(&arp_packet(2, GATEWAY_MAC, GATEWAY, HOST), at(1000))
+            .is_empty());
+        // Requests do not rebind the address
+        assert!(watch
+            .update(&arp_packet(1, ATTACKER_MAC, GATEWAY, HOST), at(2000))
+            .is_empty());
+
+        let alert = SecurityAlert::ArpBindingChanged {
+            time: at(3000).timestamp_millis(),
+            ip: Ipv4Addr::from(GATEWAY),
+            previous_mac: "00:00:0c:01:02:03".to_owned(),
+            mac: "08:00:27:aa:bb:cc".to_owned(),
+        };
+        assert_eq!(
+            watch.update(&arp_packet(2, ATTACKER_MAC, GATEWAY, HOST), at(3000)),
+            vec![alert.clone()]
+        );
+        assert_eq!(watch.alerts(), vec![alert]);
+
+        watch.clear();
+        assert!(watch.alerts().is_empty());
+    }
+
+    #[test]
+    fn gratuitous_arp_storm() {
+        let mut watch = ArpWatch::new();
+
+        // Announcements spread over time are not a storm
+        for i in 0..GRATUITOUS_STORM_COUNT as i64 {
+            assert!(watch
+                .update(&arp_packet(1, GATEWAY_MAC, GATEWAY, GATEWAY), at(i * 1000))
+                .is_empty());
+        }
+
+        let mut alerts = vec![];
+        for i in 0..2 * GRATUITOUS_STORM_COUNT as i64 {
+            alerts.extend(watch.update(
+                &arp_packet(2, GATEWAY_MAC, GATEWAY, GATEWAY),
+                at(20_000 + i * 10),
+            ));
+        }
+        assert_eq!(
+            alerts,
+            vec![SecurityAlert::GratuitousArpStorm {
+                time: at(20_090).timestamp_millis(),
+                ip: Ipv4Addr::from(GATEWAY),
+                mac: "00:00:0c:01:02:03".to_owned(),
+                count: GRATUITOUS_STORM_COUNT,
+            }]
+        );
+    }
+
+    ///////////////////// Utils
+
+    fn arp_packet(
+        operation: u8,
+        sender_mac: [u8; 6],
+        sender_ip: [u8; 4],
+        target_ip: [u8; 4],
+    ) -> ParsedPacket {
+        let mut frame = vec![0xff; 6];
+        frame.extend(sender_mac);
+        frame.extend([
+            0x08, 0x06, 0x00, 0x01, 0x08, 0x00, 0x06, 0x04, 0x00, operation,
+        ]);
+        frame.extend(sender_mac);
+        frame.extend(sender_ip);
+        frame.extend([0x00; 6]);
+        frame.extend(target_ip);
+
+        parse_ethernet_frame(&EthernetPacket::new(&frame).unwrap(), 0)
+    }
+}
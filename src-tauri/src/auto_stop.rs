@@ -0,0 +1,260 @@
+//! Conditions stopping the live capture on their own
+//!
+//! Unattended captures can be stopped as soon as any of the chosen conditions is met:
+//! - Packets: after the given number of packets
+//! - Megabytes: after the given megabytes of frames (in their original length)
+//! - Seconds: after the given seconds since the capture was started
+//! - Display filter: after the first packet satisfying a display filter expression
+//!
+//! Packets, bytes and time are counted since the capture is started, across its pauses, and only
+//! for the frames dissected: the ones left out by the capture filter or the sampling don't count.
+//! Conditions are checked as each frame is received, the frame meeting them being the last one
+//! collected. Stopping closes the capture file, if any, and emits a `capture_stopped` event
+//! carrying the condition met. Imported captures are never stopped.
+
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use sniffer_parser::serializable_packet::ParsedPacket;
+use tracing::info;
+
+use crate::display_filter::DisplayFilter;
+use crate::{SniffingError, SniffingState};
+
+/// Conditions stopping the live capture, none set by default
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct StopConditions {
+    pub packets: Option<u64>,
+    pub megabytes: Option<u64>,
+    pub seconds: Option<u64>,
+    pub display_filter: Option<String>,
+}
+
+/// Condition met by the live capture, stopping it
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "type", content = "value", rename_all = "camelCase")]
+pub enum StopReason {
+    Packets(u64),
+    Megabytes(u64),
+    Seconds(u64),
+    DisplayFilter(String),
+}
+
+/// Checker of the conditions stopping the live capture
+#[derive(Debug, Default)]
+pub struct AutoStop {
+    conditions: StopConditions,
+    display_filter: Option<DisplayFilter>,
+    /// Packets and bytes counted since the capture was started
+    packets: u64,
+    bytes: u64,
+    started: Option<Instant>,
+}
+
+impl AutoStop {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn conditions(&self) -> &StopConditions {
+        &self.conditions
+    }
+
+    /// Replace the stop conditions, checking their values and compiling their display filter
+    fn set_conditions(&mut self, conditions: StopConditions) -> Result<(), String> {
+        if [conditions.packets, conditions.megabytes, conditions.seconds].contains(&Some(0)) {
+            return Err("The packets, megabytes and seconds must be at least 1".to_owned());
+        }
+
+        let conditions = StopConditions {
+            display_filter: conditions
+                .display_filter
+                .filter(|filter| !filter.trim().is_empty()),
+            ..conditions
+        };
+        self.display_filter = match conditions.display_filter.as_deref() {
+            Some(filter) => Some(
+                DisplayFilter::compile(filter)
+                    .map_err(|e| format!("Invalid display filter: {}", e))?,
+            ),
+            None => None,
+        };
+        self.conditions = conditions;
+
+        Ok(())
+    }
+
+    /// Start counting the packets, bytes and time of a new capture, unless resuming the current
+    /// one
+    pub fn start(&mut self, is_resume: bool, now: Instant) {
+        if !is_resume || self.started.is_none() {
+            self.packets = 0;
+            self.bytes = 0;
+            self.started = Some(now);
+        }
+    }
+
+    /// Count a received frame, checking if its packet meets any of the stop conditions
+    pub fn check(
+        &mut self,
+        frame_length: usize,
+        packet: &ParsedPacket,
+        now: Instant,
+    ) -> Option<StopReason> {
+        self.packets += 1;
+        self.bytes += frame_length as u64;
+        let elapsed = now.saturating_duration_since(*self.started.get_or_insert(now));
+
+        match &self.conditions {
+            StopConditions {
+                packets: Some(packets),
+                ..
+            } if self.packets >= *packets => Some(StopReason::Packets(*packets)),
+            StopConditions {
+                megabytes: Some(megabytes),
+                ..
+            } if self.bytes >= megabytes.saturating_mul(1_000_000) => {
+                Some(StopReason::Megabytes(*megabytes))
+            }
+            StopConditions {
+                seconds: Some(seconds),
+                ..
+            } if elapsed >= Duration::from_secs(*seconds) => Some(StopReason::Seconds(*seconds)),
+            _ => self
+                .display_filter
+                .as_ref()
+                .filter(|filter| filter.matches(packet))
+                .map(|filter| StopReason::DisplayFilter(filter.text().to_owned())),
+        }
+    }
+}
+
+/// Sets the conditions stopping the live capture on their own
+#[tauri::command]
+pub fn configure_capture(
+    stop_conditions: StopConditions,
+    state: tauri::State<SniffingState>,
+) -> Result<(), SniffingError> {
+    info!("Stop conditions set: {:?}", stop_conditions);
+
+    state
+        .auto_stop
+        .lock()
+        .unwrap()
+        .set_conditions(stop_conditions)
+        .map_err(SniffingError::InvalidStopConditions)
+}
+
+/// Returns the conditions stopping the live capture on their own
+#[tauri::command]
+pub fn get_capture_configuration(state: tauri::State<SniffingState>) -> StopConditions {
+    state.auto_stop.lock().unwrap().conditions().clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use pnet::packet::ethernet::EthernetPacket;
+    use sniffer_parser::parse_ethernet_frame;
+    use sniffer_parser::serializable_packet::ParsedPacket;
+
+    use super::{AutoStop, StopConditions, StopReason};
+
+    // Ethernet (14) + IPv4 (20) + TCP (20) + 3 bytes of payload
+    const TCP_FRAME: &[u8] = &[
+        0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x08, 0x00, 0x45,
+        0x00, 0x00, 0x2b, 0x00, 0x00, 0x00, 0x00, 0x40, 0x06, 0x00, 0x00, 0x0a, 0x0a, 0x0a, 0x0a,
+        0x0b, 0x0b, 0x0b, 0x0b, 0x11, 0x5c, 0x01, 0xbb, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x50, 0x18, 0xff, 0xff, 0x00, 0x00, 0x00, 0x00, 0x47, 0x45, 0x54,
+    ];
+
+    #[test]
+    fn packets_bytes_and_time() {
+        let (start, packet) = (Instant::now(), ParsedPacket::new(0));
+        let mut auto_stop = AutoStop::new();
+        auto_stop.start(false, start);
+        assert_eq!(auto_stop.check(1_000_000, &packet, start), None);
+
+        auto_stop
+            .set_conditions(StopConditions {
+                packets: Some(3),
+                megabytes: Some(2),
+                seconds: Some(60),
+                ..StopConditions::default()
+            })
+            .unwrap();
+        assert_eq!(
+            auto_stop.check(1_000_000, &packet, start),
+            Some(StopReason::Megabytes(2))
+        );
+        assert_eq!(
+            auto_stop.check(0, &packet, start),
+            Some(StopReason::Packets(3))
+        );
+
+        // Resuming keeps counting, starting again does not
+        auto_stop.start(true, start + Duration::from_secs(30));
+        assert_eq!(
+            auto_stop.check(0, &packet, start + Duration::from_secs(60)),
+            Some(StopReason::Packets(3))
+        );
+        auto_stop.start(false, start + Duration::from_secs(30));
+        assert_eq!(
+            auto_stop.check(0, &packet, start + Duration::from_secs(60)),
+            None
+        );
+        assert_eq!(
+            auto_stop.check(0, &packet, start + Duration::from_secs(90)),
+            Some(StopReason::Seconds(60))
+        );
+    }
+
+    #[test]
+    fn display_filter_match() {
+        let tcp_packet = parse_ethernet_frame(&EthernetPacket::new(TCP_FRAME).unwrap(), 0);
+        let mut auto_stop = AutoStop::new();
+        auto_stop
+            .set_conditions(StopConditions {
+                display_filter: Some("tcp.dstport == 443".to_owned()),
+                ..StopConditions::default()
+            })
+            .unwrap();
+
+        let now = Instant::now();
+        auto_stop.start(false, now);
+        assert_eq!(auto_stop.check(57, &ParsedPacket::new(0), now), None);
+        assert_eq!(
+            auto_stop.check(57, &tcp_packet, now),
+            Some(StopReason::DisplayFilter("tcp.dstport == 443".to_owned()))
+        );
+    }
+
+    #[test]
+    fn invalid_conditions() {
+        let mut auto_stop = AutoStop::new();
+        let invalid = [
+            StopConditions {
+                seconds: Some(0),
+                ..StopConditions::default()
+            },
+            StopConditions {
+                display_filter: Some("tcp.port ==".to_owned()),
+                ..StopConditions::default()
+            },
+        ];
+        for conditions in invalid {
+            assert!(auto_stop.set_conditions(conditions).is_err());
+        }
+
+        // An empty display filter sets none
+        auto_stop
+            .set_conditions(StopConditions {
+                display_filter: Some(" ".to_owned()),
+                ..StopConditions::default()
+            })
+            .unwrap();
+        assert_eq!(auto_stop.conditions(), &StopConditions::default());
+    }
+}
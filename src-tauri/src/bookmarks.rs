@@ -0,0 +1,348 @@
+//! Bookmarks and named packet ranges set by the user
+//!
+//! Packets are referred to by their id, i.e. their position in the capture. Each bookmark may have
+//! a note, and each named range spans the packets between its first and last id (both included).
+//! The frontend moves between them with the jump commands, which return the closest bookmark or
+//! range after (or before) the packet currently selected.
+//!
+//! Bookmarks and ranges are saved in a JSON session file chosen by the user, and can be loaded
+//! back once the same capture is imported again. They are dropped together with the collected
+//! packets, when a new capture is started or imported.
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::{SniffingError, SniffingState};
+
+/// Format version of the session files
+const SESSION_VERSION: u32 = 1;
+
+/// Range of consecutive packets, from the first id to the last one (both included)
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct PacketRange {
+    pub first: usize,
+    pub last: usize,
+}
+
+/// Direction of a jump between bookmarks or ranges
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum JumpDirection {
+    Next,
+    Previous,
+}
+
+/// Bookmarked packets and named packet ranges
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Bookmarks {
+    /// Notes of the bookmarked packets, by packet id
+    marks: BTreeMap<usize, String>,
+    /// Packet ranges, by name
+    ranges: BTreeMap<String, PacketRange>,
+}
+
+/// Content of a session file
+#[derive(Serialize, Deserialize, Debug)]
+struct Session {
+    version: u32,
+    bookmarks: Bookmarks,
+}
+
+impl Bookmarks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Remove all the bookmarks and ranges
+    pub fn clear(&mut self) {
+        self.marks.clear();
+        self.ranges.clear();
+    }
+
+    /// Bookmark a packet, replacing the note of an existing bookmark
+    fn mark(&mut self, id: usize, note: String) {
+        self.marks.insert(id, note);
+    }
+
+    /// Remove the bookmark of a packet, returning whether it was bookmarked
+    fn unmark(&mut self, id: usize) -> bool {
+        self.marks.remove(&id).is_some()
+    }
+
+    /// Name a range of packets, replacing the range with the same name
+    fn add_range(&mut self, name: String, range: PacketRange) -> Result<(), String> {
+        if name.is_empty() {
+            return Err("The name of the range is empty".to_owned());
+        }
+        if range.first > range.last {
+            return Err(format!(
+                "The range starts at packet {} after its end at packet {}",
+                range.first, range.last
+            ));
+        }
+
+        self.ranges.insert(name, range);
+        Ok(())
+    }
+
+    /// Remove a named range, returning whether it existed
+    fn remove_range(&mut self, name: &str) -> bool {
+        self.ranges.remove(name).is_some()
+    }
+
+    /// Get the closest bookmarked packet after or before a packet
+    fn jump_to_mark(&self, from: usize, direction: JumpDirection) -> Option<usize> {
+        match direction {
+            JumpDirection::Next => self.marks.range(from + 1..).next(),
+            JumpDirection::Previous => self.marks.range(..from).next_back(),
+        }
+        .map(|(&id, _)| id)
+    }
+
+    /// Get the closest range starting after or before a packet, with its name
+    ///
+    /// Ranges starting at the same packet are ordered by name.
+    fn jump_to_range(
+        &self,
+        from: usize,
+        direction: JumpDirection,
+    ) -> Option<(&String, &PacketRange)> {
+        let ranges = self.ranges.iter();
+        match direction {
+            JumpDirection::Next => ranges
+                .filter(|(_, range)| range.first > from)
+                .min_by_key(|(name, range)| (range.first, *name)),
+            JumpDirection::Previous => ranges
+                .filter(|(_, range)| range.first < from)
+                .max_by_key(|(name, range)| (range.first, *name)),
+        }
+    }
+}
+
+/// Save bookmarks and ranges to a session file, replacing it
+fn write_session<P: AsRef<Path>>(path: P, bookmarks: &Bookmarks) -> io::Result<()> {
+    let session = Session {
+        version: SESSION_VERSION,
+        bookmarks: bookmarks.clone(),
+    };
+
+    let writer = BufWriter::new(File::create(path)?);
+    serde_json::to_writer_pretty(writer, &session).map_err(io::Error::from)
+}
+
+/// Read bookmarks and ranges from a session file
+fn read_session<P: AsRef<Path>>(path: P) -> io::Result<Bookmarks> {
+    let reader = BufReader::new(File::open(path)?);
+    let session: Session = serde_json::from_reader(reader).map_err(io::Error::from)?;
+    if session.version != SESSION_VERSION {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("unsupported session version {}", session.version),
+        ));
+    }
+
+    Ok(session.bookmarks)
+}
+
+/// Bookmarks a packet with an optional note, replacing the note of an existing bookmark
+#[tauri::command]
+pub fn set_bookmark(id: usize, note: Option<String>, state: tauri::State<SniffingState>) {
+    state
+        .bookmarks
+        .lock()
+        .unwrap()
+        .mark(id, note.unwrap_or_default());
+}
+
+/// Removes the bookmark of a packet, returning whether the packet was bookmarked
+#[tauri::command]
+pub fn remove_bookmark(id: usize, state: tauri::State<SniffingState>) -> bool {
+    state.bookmarks.lock().unwrap().unmark(id)
+}
+
+/// Names a range of packets, replacing the range with the same name
+#[tauri::command]
+pub fn set_packet_range(
+    name: String,
+    first: usize,
+    last: usize,
+    state: tauri::State<SniffingState>,
+) -> Result<(), SniffingError> {
+    state
+        .bookmarks
+        .lock()
+        .unwrap()
+        .add_range(name, PacketRange { first, last })
+        .map_err(SniffingError::InvalidPacketRange)
+}
+
+/// Removes a named range, returning whether it existed
+#[tauri::command]
+pub fn remove_packet_range(name: String, state: tauri::State<SniffingState>) -> bool {
+    state.bookmarks.lock().unwrap().remove_range(&name)
+}
+
+/// Returns the bookmarks and the named ranges
+#[tauri::command]
+pub fn get_bookmarks(state: tauri::State<SniffingState>) -> Bookmarks {
+    state.bookmarks.lock().unwrap().clone()
+}
+
+/// Returns the id of the closest bookmarked packet after or before a packet, if any
+#[tauri::command]
+pub fn jump_to_bookmark(
+    from: usize,
+    direction: JumpDirection,
+    state: tauri::State<SniffingState>,
+) -> Option<usize> {
+    state
+        .bookmarks
+        .lock()
+        .unwrap()
+        .jump_to_mark(from, direction)
+}
+
+/// Returns the name and the packets of the closest range starting after or before a packet, if any
+#[tauri::command]
+pub fn jump_to_packet_range(
+    from: usize,
+    direction: JumpDirection,
+    state: tauri::State<SniffingState>,
+) -> Option<(String, PacketRange)> {
+    state
+        .bookmarks
+        .lock()
+        .unwrap()
+        .jump_to_range(from, direction)
+        .map(|(name, range)| (name.clone(), *range))
+}
+
+/// Saves the bookmarks and the named ranges to a session file
+#[tauri::command]
+pub fn save_session(path: String, state: tauri::State<SniffingState>) -> Result<(), SniffingError> {
+    let bookmarks = state.bookmarks.lock().unwrap();
+    write_session(&path, &bookmarks)
+        .map_err(|e| SniffingError::SessionFileFailed(format!("Cannot write {}: {}", path, e)))?;
+
+    info!("[{}] Session saved", path);
+
+    Ok(())
+}
+
+/// Loads the bookmarks and the named ranges of a session file, replacing the current ones
+#[tauri::command]
+pub fn load_session(
+    path: String,
+    state: tauri::State<SniffingState>,
+) -> Result<Bookmarks, SniffingError> {
+    let bookmarks = read_session(&path)
+        .map_err(|e| SniffingError::SessionFileFailed(format!("Cannot read {}: {}", path, e)))?;
+    *state.bookmarks.lock().unwrap() = bookmarks.clone();
+
+    info!("[{}] Session loaded", path);
+
+    Ok(bookmarks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{read_session, write_session, Bookmarks, JumpDirection, PacketRange};
+
+    #[test]
+    fn jump_between_bookmarks() {
+        let mut bookmarks = Bookmarks::new();
+        bookmarks.mark(3, "".to_owned());
+        bookmarks.mark(10, "handshake".to_owned());
+        bookmarks.mark(42, "reset".to_owned());
+
+        assert_eq!(bookmarks.jump_to_mark(3, JumpDirection::Next), Some(10));
+        assert_eq!(bookmarks.jump_to_mark(0, JumpDirection::Next), Some(3));
+        assert_eq!(bookmarks.jump_to_mark(42, JumpDirection::Next), None);
+        assert_eq!(bookmarks.jump_to_mark(10, JumpDirection::Previous), Some(3));
+        assert_eq!(bookmarks.jump_to_mark(3, JumpDirection::Previous), None);
+
+        assert!(bookmarks.unmark(10));
+        assert!(!bookmarks.unmark(10));
+        assert_eq!(bookmarks.jump_to_mark(3, JumpDirection::Next), Some(42));
+    }
+
+    #[test]
+    fn jump_between_ranges() {
+        let bookmarks = build_test_bookmarks();
+
+        let next = bookmarks.jump_to_range(5, JumpDirection::Next);
+        assert_eq!(
+            next,
+            Some((
+                &"login".to_owned(),
+                &PacketRange {
+                    first: 20,
+                    last: 35
+                }
+            ))
+        );
+        let previous = bookmarks.jump_to_range(20, JumpDirection::Previous);
+        assert_eq!(
+            previous,
+            Some((&"dns".to_owned(), &PacketRange { first: 5, last: 8 }))
+        );
+        assert_eq!(bookmarks.jump_to_range(20, JumpDirection::Next), None);
+    }
+
+    #[test]
+    fn invalid_ranges() {
+        let mut bookmarks = Bookmarks::new();
+
+        assert!(bookmarks
+            .add_range("".to_owned(), PacketRange { first: 0, last: 1 })
+            .is_err());
+        assert!(bookmarks
+            .add_range("reversed".to_owned(), PacketRange { first: 2, last: 1 })
+            .is_err());
+        assert!(bookmarks
+            .add_range("single".to_owned(), PacketRange { first: 2, last: 2 })
+            .is_ok());
+        assert!(bookmarks.remove_range("single"));
+        assert!(!bookmarks.remove_range("single"));
+    }
+
+    #[test]
+    fn session_round_trip() {
+        let bookmarks = build_test_bookmarks();
+        let path = std::env::temp_dir().join(format!("wirefish-{}.wfsession", std::process::id()));
+
+        write_session(&path, &bookmarks).unwrap();
+        let read = read_session(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(read.unwrap(), bookmarks);
+    }
+
+    ///////////////////// Utils
+
+    fn build_test_bookmarks() -> Bookmarks {
+        let mut bookmarks = Bookmarks::new();
+        bookmarks.mark(7, "retransmission".to_owned());
+        bookmarks
+            .add_range("dns".to_owned(), PacketRange { first: 5, last: 8 })
+            .unwrap();
+        bookmarks
+            .add_range(
+                "login".to_owned(),
+                PacketRange {
+                    first: 20,
+                    last: 35,
+                },
+            )
+            .unwrap();
+
+        bookmarks
+    }
+}
@@ -0,0 +1,422 @@
+//! Memory-mapped reading of .pcap capture files
+//!
+//! When a capture file is opened, only its global header is read: records are located
+//! lazily by walking their headers, and the frames are accessed directly from the mapped memory.
+//! Encrypted capture files are instead decrypted in memory, and .pcapng ones (as the files saved by
+//! the captures to file) converted in memory to .pcap.
+
+use std::fs::File;
+use std::io::{self, Write};
+use std::ops::Deref;
+use std::path::Path;
+
+use memmap2::Mmap;
+
+use crate::encryption::{decrypt, is_encrypted};
+use crate::pcapng::{convert_to_pcap, is_pcapng};
+
+/// PCAP Global and Record header lengths
+#[allow(non_snake_case)]
+pub mod PcapHeaderLength {
+    pub const GLOBAL: usize = 24;
+    pub const RECORD: usize = 16;
+}
+
+/// PCAP link types of the supported frames
+#[allow(non_snake_case)]
+pub mod LinkTypes {
+    pub const ETHERNET: u32 = 1;
+    pub const SLIP: u32 = 8;
+    pub const PPP: u32 = 9;
+    /// PPP in HDLC-like framing
+    pub const PPP_HDLC: u32 = 50;
+    pub const IEEE802_11: u32 = 105;
+    pub const IEEE802_11_RADIOTAP: u32 = 127;
+    pub const NULL: u32 = 0;
+    pub const LOOP: u32 = 108;
+    pub const RAW: u32 = 101;
+    pub const IPV4: u32 = 228;
+    pub const IPV6: u32 = 229;
+    pub const LINUX_SLL: u32 = 113;
+    pub const LINUX_SLL2: u32 = 276;
+}
+
+/// PCAP magic numbers, as read in big endian order
+#[allow(non_snake_case)]
+mod PcapMagicNumbers {
+    pub const MICROSECONDS: u32 = 0xa1b2c3d4;
+    pub const MICROSECONDS_SWAPPED: u32 = 0xd4c3b2a1;
+    pub const NANOSECONDS: u32 = 0xa1b23c4d;
+    pub const NANOSECONDS_SWAPPED: u32 = 0x4d3cb2a1;
+}
+
+/// Global header of a .pcap file
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GlobalHeader {
+    pub big_endian: bool,
+    pub nanoseconds: bool,
+    pub snap_length: u32,
+    pub link_type: u32,
+}
+
+/// Position and metadata of a single record (frame) in a .pcap file
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CaptureRecord {
+    pub offset: usize,
+    pub seconds: u32,
+    pub nanoseconds: u32,
+    pub captured_length: u32,
+    pub original_length: u32,
+}
+
+impl CaptureRecord {
+    /// Offset of the first byte after the record
+    pub fn end(&self) -> usize {
+        self.offset + self.captured_length as usize
+    }
+}
+
+/// Content of a .pcap file
+enum CaptureData {
+    Mapped(Mmap),
+    Decrypted(Vec<u8>),
+    Converted(Vec<u8>),
+}
+
+impl Deref for CaptureData {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            CaptureData::Mapped(data) => data,
+            CaptureData::Decrypted(data) => data,
+            CaptureData::Converted(data) => data,
+        }
+    }
+}
+
+/// A .pcap file mapped in memory
+pub struct CaptureFile {
+    data: CaptureData,
+    header: GlobalHeader,
+}
+
+impl CaptureFile {
+    /// Map the file in memory and read its global header
+    ///
+    /// Files encrypted by wirefish can be opened only providing their passphrase
+    pub fn open<P: AsRef<Path>>(path: P, passphrase: Option<&str>) -> io::Result<Self> {
+        let file = File::open(path)?;
+
+        // The mapped file is only read, a concurrent truncation of the file by another
+        // process is the only thing that could invalidate the mapping
+        let mapped = unsafe { Mmap::map(&file)? };
+
+        let data = if is_encrypted(&mapped) {
+            let passphrase = passphrase.ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::PermissionDenied,
+                    "The file is encrypted, a passphrase is required",
+                )
+            })?;
+
+            CaptureData::Decrypted(decrypt(&mapped, passphrase)?)
+        } else {
+            CaptureData::Mapped(mapped)
+        };
+        let data = if is_pcapng(&data) {
+            // Decrypted files stay out of the index, whatever their format
+            match data {
+                CaptureData::Decrypted(_) => CaptureData::Decrypted(convert_to_pcap(&data)?),
+                _ => CaptureData::Converted(convert_to_pcap(&data)?),
+            }
+        } else {
+            data
+        };
+        let header = parse_global_header(&data)?;
+
+        Ok(CaptureFile { data, header })
+    }
+
+    /// Check if the file content was decrypted
+    pub fn is_decrypted(&self) -> bool {
+        matches!(self.data, CaptureData::Decrypted(_))
+    }
+
+    /// Get the global header of the file
+    pub fn header(&self) -> &GlobalHeader {
+        &self.header
+    }
+
+    /// Get the file size in bytes
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Check if the file is empty
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// Iterate over the records contained in the file
+    pub fn records(&self) -> Records<'_> {
+        Records {
+            data: &self.data,
+            header: self.header,
+            offset: PcapHeaderLength::GLOBAL,
+        }
+    }
+
+    /// Get the frame bytes of a record
+    pub fn frame(&self, record: &CaptureRecord) -> &[u8] {
+        &self.data[record.offset..record.end()]
+    }
+}
+
+/// Iterator over the records of a .pcap file
+pub struct Records<'a> {
+    data: &'a [u8],
+    header: GlobalHeader,
+    offset: usize,
+}
+
+impl<'a> Records<'a> {
+    /// Offset of the next record header
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+}
+
+impl<'a> Iterator for Records<'a> {
+    type Item = io::Result<CaptureRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.offset >= self.data.len() {
+            return None;
+        }
+
+        let result = parse_record_header(self.data, self.offset, &self.header);
+        match &result {
+            Ok(record) => self.offset = record.end(),
+            Err(_) => self.offset = self.data.len(),
+        }
+
+        Some(result)
+    }
+}
+
+/// Parse the global header placed at the beginning of a .pcap file
+pub fn parse_global_header(data: &[u8]) -> io::Result<GlobalHeader> {
+    if data.len() < PcapHeaderLength::GLOBAL {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "File too short for a pcap global header",
+        ));
+    }
+
+    let (big_endian, nanoseconds) = match read_u32(data, 0, true) {
+        PcapMagicNumbers::MICROSECONDS => (true, false),
+        PcapMagicNumbers::MICROSECONDS_SWAPPED => (false, false),
+        PcapMagicNumbers::NANOSECONDS => (true, true),
+        PcapMagicNumbers::NANOSECONDS_SWAPPED => (false, true),
+        magic => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Unknown pcap magic number: {:#x}", magic),
+            ))
+        }
+    };
+
+    Ok(GlobalHeader {
+        big_endian,
+        nanoseconds,
+        snap_length: read_u32(data, 16, big_endian),
+        link_type: read_u32(data, 20, big_endian),
+    })
+}
+
+/// Parse the record header placed at the provided offset
+pub fn parse_record_header(
+    data: &[u8],
+    offset: usize,
+    header: &GlobalHeader,
+) -> io::Result<CaptureRecord> {
+    if data.len() < offset + PcapHeaderLength::RECORD {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            format!("Truncated record header at offset {}", offset),
+        ));
+    }
+
+    let fraction = read_u32(data, offset + 4, header.big_endian);
+    let record = CaptureRecord {
+        offset: offset + PcapHeaderLength::RECORD,
+        seconds: read_u32(data, offset, header.big_endian),
+        nanoseconds: if header.nanoseconds {
+            fraction
+        } else {
+            fraction.saturating_mul(1000)
+        },
+        captured_length: read_u32(data, offset + 8, header.big_endian),
+        original_length: read_u32(data, offset + 12, header.big_endian),
+    };
+
+    if record.end() > data.len() {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            format!("Truncated record at offset {}", offset),
+        ));
+    }
+
+    Ok(record)
+}
+
+/// Write a little endian global header, keeping the timestamp resolution of the provided one
+pub fn write_global_header<W: Write>(writer: &mut W, header: &GlobalHeader) -> io::Result<()> {
+    let magic = if header.nanoseconds {
+        PcapMagicNumbers::NANOSECONDS
+    } else {
+        PcapMagicNumbers::MICROSECONDS
+    };
+
+    writer.write_all(&magic.to_le_bytes())?;
+    // Version 2.4, GMT offset and timestamps accuracy
+    writer.write_all(&[0x02, 0x00, 0x04, 0x00])?;
+    writer.write_all(&[0; 8])?;
+    writer.write_all(&header.snap_length.to_le_bytes())?;
+    writer.write_all(&header.link_type.to_le_bytes())
+}
+
+/// Write a little endian record, whose captured length is the one of the provided frame
+pub fn write_record<W: Write>(
+    writer: &mut W,
+    header: &GlobalHeader,
+    record: &CaptureRecord,
+    frame: &[u8],
+) -> io::Result<()> {
+    let fraction = if header.nanoseconds {
+        record.nanoseconds
+    } else {
+        record.nanoseconds / 1000
+    };
+
+    writer.write_all(&record.seconds.to_le_bytes())?;
+    writer.write_all(&fraction.to_le_bytes())?;
+    writer.write_all(&(frame.len() as u32).to_le_bytes())?;
+    writer.write_all(&record.original_length.to_le_bytes())?;
+    writer.write_all(frame)
+}
+
+fn read_u32(data: &[u8], offset: usize, big_endian: bool) -> u32 {
+    let bytes = [
+        data[offset],
+        data[offset + 1],
+        data[offset + 2],
+        data[offset + 3],
+    ];
+
+    if big_endian {
+        u32::from_be_bytes(bytes)
+    } else {
+        u32::from_le_bytes(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use super::{
+        parse_global_header, parse_record_header, write_global_header, write_record,
+        PcapHeaderLength, Records,
+    };
+
+    const GLOBAL_HEADER: &[u8] = &[
+        0xd4, 0xc3, 0xb2, 0xa1, 0x02, 0x00, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0xff, 0xff, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00,
+    ];
+
+    const RECORD: &[u8] = &[
+        0x10, 0x00, 0x00, 0x00, 0x05, 0x00, 0x00, 0x00, 0x04, 0x00, 0x00, 0x00, 0x3c, 0x00, 0x00,
+        0x00, 0xde, 0xad, 0xbe, 0xef,
+    ];
+
+    #[test]
+    fn valid_global_header() {
+        let header = parse_global_header(GLOBAL_HEADER).unwrap();
+
+        assert!(!header.big_endian);
+        assert!(!header.nanoseconds);
+        assert_eq!(header.snap_length, 0xffff);
+        assert_eq!(header.link_type, 1);
+    }
+
+    #[test]
+    fn unknown_magic_number() {
+        let mut data = GLOBAL_HEADER.to_vec();
+        data[0] = 0x00;
+
+        match parse_global_header(&data) {
+            Err(e) => assert_eq!(e.kind(), io::ErrorKind::InvalidData),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn valid_record() {
+        let data = [GLOBAL_HEADER, RECORD, RECORD].concat();
+        let header = parse_global_header(&data).unwrap();
+
+        let records = Records {
+            data: &data,
+            header,
+            offset: PcapHeaderLength::GLOBAL,
+        }
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+
+        assert_eq!(records.len(), 2);
+        assert_eq!(
+            records[0].offset,
+            PcapHeaderLength::GLOBAL + PcapHeaderLength::RECORD
+        );
+        assert_eq!(records[0].seconds, 16);
+        assert_eq!(records[0].nanoseconds, 5000);
+        assert_eq!(records[0].captured_length, 4);
+        assert_eq!(records[0].original_length, 60);
+        assert_eq!(
+            &data[records[1].offset..records[1].end()],
+            &[0xde, 0xad, 0xbe, 0xef]
+        );
+    }
+
+    #[test]
+    fn truncated_record() {
+        let data = [GLOBAL_HEADER, &RECORD[..18]].concat();
+        let header = parse_global_header(&data).unwrap();
+
+        match parse_record_header(&data, PcapHeaderLength::GLOBAL, &header) {
+            Err(e) => assert_eq!(e.kind(), io::ErrorKind::UnexpectedEof),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn write_read_round_trip() {
+        let data = [GLOBAL_HEADER, RECORD].concat();
+        let header = parse_global_header(&data).unwrap();
+        let record = parse_record_header(&data, PcapHeaderLength::GLOBAL, &header).unwrap();
+
+        let mut written = vec![];
+        write_global_header(&mut written, &header).unwrap();
+        write_record(&mut written, &header, &record, &[0xde, 0xad]).unwrap();
+
+        assert_eq!(parse_global_header(&written).unwrap(), header);
+        let written_record =
+            parse_record_header(&written, PcapHeaderLength::GLOBAL, &header).unwrap();
+        assert_eq!(written_record.seconds, record.seconds);
+        assert_eq!(written_record.nanoseconds, record.nanoseconds);
+        assert_eq!(written_record.captured_length, 2);
+        assert_eq!(written_record.original_length, record.original_length);
+    }
+}
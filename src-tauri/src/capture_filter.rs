@@ -0,0 +1,550 @@
+//! Capture filter derived from the selected conversations and endpoints
+//!
+//! To follow a single conversation on a busy link, the live capture can be narrowed to the
+//! conversations or endpoints selected in the collected data. Frames are read at layer 2 without
+//! libpcap, so the filter is evaluated in software on the received frames, but its expression is
+//! derived in the tcpdump syntax as well, to be reused with other tools.
+//!
+//! Frames left out are not dissected, stored, saved to the capture file nor counted by the
+//! statistics: only their number is kept. Imported captures are never filtered.
+
+use std::net::IpAddr;
+
+use pnet::packet::ethernet::{EtherTypes, EthernetPacket};
+use pnet::packet::ip::{IpNextHeaderProtocol, IpNextHeaderProtocols};
+use pnet::packet::ipv4::Ipv4Packet;
+use pnet::packet::ipv6::Ipv6Packet;
+use pnet::packet::vlan::VlanPacket;
+use pnet::packet::Packet;
+use serde::{Deserialize, Serialize};
+use sniffer_parser::{HeaderLength, LinkType};
+use tracing::info;
+
+use crate::packet_edits::network_offset;
+use crate::{SniffingError, SniffingState};
+
+/// Conversation or endpoint the capture is narrowed to
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "type")]
+pub enum CaptureSelection {
+    /// Conversation between two endpoints, in both directions
+    ///
+    /// The protocol is the one listed with the conversations (e.g. "TCP", "ICMPv6", "IP").
+    #[serde(rename_all = "camelCase")]
+    Conversation {
+        protocol: String,
+        initiator: IpAddr,
+        initiator_port: Option<u16>,
+        responder: IpAddr,
+        responder_port: Option<u16>,
+    },
+    /// Every frame sent or received by an address, on a port if given
+    #[serde(rename_all = "camelCase")]
+    Endpoint { address: IpAddr, port: Option<u16> },
+}
+
+/// Capture filter applied to the received frames
+#[derive(Serialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct CaptureFilter {
+    selections: Vec<CaptureSelection>,
+    /// Expression of the filter in the tcpdump syntax, none if the capture is not filtered
+    expression: Option<String>,
+    /// Frames left out since the filter was set
+    filtered: u64,
+}
+
+impl CaptureFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replace the selections the capture is narrowed to, returning the expression of the filter
+    ///
+    /// No selection removes the filter.
+    fn set_selections(&mut self, selections: Vec<CaptureSelection>) -> Result<String, String> {
+        let expression = derive_expression(&selections)?;
+
+        self.expression = (!selections.is_empty()).then(|| expression.clone());
+        self.selections = selections;
+        self.filtered = 0;
+        Ok(expression)
+    }
+
+    /// Check if a frame received on an interface of the given link type is kept by the filter,
+    /// counting the ones left out
+    pub fn matches(&mut self, link_type: LinkType, frame: &[u8]) -> bool {
+        if self.selections.is_empty() {
+            return true;
+        }
+
+        let matched = FrameKey::parse(link_type, frame).is_some_and(|key| {
+            self.selections
+                .iter()
+                .any(|selection| key.matches(selection))
+        });
+        if !matched {
+            self.filtered += 1;
+        }
+        matched
+    }
+}
+
+/// Derive the expression of the filter of the given selections, in the tcpdump syntax
+pub fn derive_expression(selections: &[CaptureSelection]) -> Result<String, String> {
+    let expressions = selections
+        .iter()
+        .map(selection_expression)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(match expressions.as_slice() {
+        [expression] => expression.clone(),
+        _ => expressions
+            .iter()
+            .map(|expression| format!("({})", expression))
+            .collect::<Vec<_>>()
+            .join(" or "),
+    })
+}
+
+/// Derive the expression of a single selection
+fn selection_expression(selection: &CaptureSelection) -> Result<String, String> {
+    match selection {
+        CaptureSelection::Conversation {
+            protocol,
+            initiator,
+            initiator_port,
+            responder,
+            responder_port,
+        } => {
+            if initiator.is_ipv4() != responder.is_ipv4() {
+                return Err(format!(
+                    "The endpoints {} and {} belong to different IP versions",
+                    initiator, responder
+                ));
+            }
+            let (primitive, transport) = protocol_primitive(protocol, initiator.is_ipv6())?;
+            if !transport && (initiator_port.is_some() || responder_port.is_some()) {
+                return Err(format!("The {} conversations have no ports", protocol));
+            }
+
+            let direction = |source: &IpAddr,
+                             source_port: &Option<u16>,
+                             destination: &IpAddr,
+                             destination_port: &Option<u16>| {
+                let mut terms = vec![format!("src host {}", source)];
+                terms.extend(source_port.map(|port| format!("src port {}", port)));
+                terms.push(format!("dst host {}", destination));
+                terms.extend(destination_port.map(|port| format!("dst port {}", port)));
+                terms.join(" and ")
+            };
+            let directions = format!(
+                "({}) or ({})",
+                direction(initiator, initiator_port, responder, responder_port),
+                direction(responder, responder_port, initiator, initiator_port)
+            );
+
+            Ok(match primitive {
+                Some(primitive) => format!("{} and ({})", primitive, directions),
+                None => directions,
+            })
+        }
+        CaptureSelection::Endpoint { address, port } => Ok(match port {
+            Some(port) => format!(
+                "(src host {address} and src port {port}) or (dst host {address} and dst port {port})"
+            ),
+            None => format!("host {}", address),
+        }),
+    }
+}
+
+/// Get the primitive matching the protocol of a conversation, and whether it has ports
+fn protocol_primitive(protocol: &str, ipv6: bool) -> Result<(Option<String>, bool), String> {
+    let ip = if ipv6 { "ip6" } else { "ip" };
+    let primitive = match protocol {
+        "TCP" => ("tcp".to_owned(), true),
+        "UDP" => ("udp".to_owned(), true),
+        "ICMP" => ("icmp".to_owned(), false),
+        "ICMPv6" => ("icmp6".to_owned(), false),
+        "ESP" => (format!("{} proto 50", ip), false),
+        "AH" => (format!("{} proto 51", ip), false),
+        "IP" => return Ok((None, false)),
+        _ => {
+            return Err(format!(
+                "The {} conversations cannot be captured alone",
+                protocol
+            ))
+        }
+    };
+
+    Ok((Some(primitive.0), primitive.1))
+}
+
+/// Addresses, protocol and ports of a received frame
+#[derive(Debug, PartialEq, Eq)]
+struct FrameKey {
+    source: IpAddr,
+    destination: IpAddr,
+    protocol: IpNextHeaderProtocol,
+    /// Ports of the TCP and UDP segments, unless carried by a fragment other than the first
+    ports: Option<(u16, u16)>,
+}
+
+impl FrameKey {
+    /// Parse the key of a frame of the given link type
+    ///
+    /// The IP packet follows the link header found as when snapping the frames, along with a VLAN
+    /// tag on Ethernet: 802.11 frames captured in monitor mode are matched when not protected.
+    fn parse(link_type: LinkType, frame: &[u8]) -> Option<Self> {
+        let payload = network_packet(link_type, frame)?;
+
+        let (source, destination, protocol, first_fragment, payload) = match payload[0] >> 4 {
+            4 => {
+                let ipv4 = Ipv4Packet::new(payload)?;
+                let header_length = ipv4.get_header_length() as usize * 4;
+                (
+                    IpAddr::V4(ipv4.get_source()),
+                    IpAddr::V4(ipv4.get_destination()),
+                    ipv4.get_next_level_protocol(),
+                    ipv4.get_fragment_offset() == 0,
+                    payload.get(header_length..)?,
+                )
+            }
+            6 => {
+                let ipv6 = Ipv6Packet::new(payload)?;
+                let (protocol, first_fragment, payload) =
+                    skip_extension_headers(ipv6.get_next_header(), &payload[40..])?;
+                (
+                    IpAddr::V6(ipv6.get_source()),
+                    IpAddr::V6(ipv6.get_destination()),
+                    protocol,
+                    first_fragment,
+                    payload,
+                )
+            }
+            _ => return None,
+        };
+
+        let ports = match protocol {
+            IpNextHeaderProtocols::Tcp | IpNextHeaderProtocols::Udp
+                if first_fragment && payload.len() >= 4 =>
+            {
+                Some((
+                    u16::from_be_bytes([payload[0], payload[1]]),
+                    u16::from_be_bytes([payload[2], payload[3]]),
+                ))
+            }
+            _ => None,
+        };
+
+        Some(FrameKey {
+            source,
+            destination,
+            protocol,
+            ports,
+        })
+    }
+
+    /// Check if the frame belongs to a selection
+    fn matches(&self, selection: &CaptureSelection) -> bool {
+        match selection {
+            CaptureSelection::Conversation {
+                protocol,
+                initiator,
+                initiator_port,
+                responder,
+                responder_port,
+            } => {
+                let protocol_matches = match protocol.as_str() {
+                    "TCP" => self.protocol == IpNextHeaderProtocols::Tcp,
+                    "UDP" => self.protocol == IpNextHeaderProtocols::Udp,
+                    "ICMP" => self.protocol == IpNextHeaderProtocols::Icmp,
+                    "ICMPv6" => self.protocol == IpNextHeaderProtocols::Icmpv6,
+                    "ESP" => self.protocol == IpNextHeaderProtocols::Esp,
+                    "AH" => self.protocol == IpNextHeaderProtocols::Ah,
+                    _ => true,
+                };
+
+                protocol_matches
+                    && (self.sent(initiator, initiator_port, responder, responder_port)
+                        || self.sent(responder, responder_port, initiator, initiator_port))
+            }
+            CaptureSelection::Endpoint { address, port } => {
+                let (source_port, destination_port) = self.ports.unzip();
+                (self.source == *address && port_matches(source_port, port))
+                    || (self.destination == *address && port_matches(destination_port, port))
+            }
+        }
+    }
+
+    /// Check if the frame is sent from an endpoint to another
+    fn sent(
+        &self,
+        source: &IpAddr,
+        source_port: &Option<u16>,
+        destination: &IpAddr,
+        destination_port: &Option<u16>,
+    ) -> bool {
+        let (frame_source_port, frame_destination_port) = self.ports.unzip();
+
+        self.source == *source
+            && self.destination == *destination
+            && port_matches(frame_source_port, source_port)
+            && port_matches(frame_destination_port, destination_port)
+    }
+}
+
+/// Get the IP packet carried by a frame of the given link type, skipping a VLAN tag on Ethernet
+fn network_packet(link_type: LinkType, frame: &[u8]) -> Option<&[u8]> {
+    let offset = match EthernetPacket::new(frame) {
+        Some(ethernet)
+            if link_type == LinkType::Ethernet && ethernet.get_ethertype() == EtherTypes::Vlan =>
+        {
+            let ethertype = VlanPacket::new(ethernet.payload())?.get_ethertype();
+            [EtherTypes::Ipv4, EtherTypes::Ipv6]
+                .contains(&ethertype)
+                .then(|| HeaderLength::ETHERNET + 4)
+        }
+        _ => network_offset(link_type.linktype(), frame),
+    }?;
+
+    frame.get(offset..).filter(|packet| !packet.is_empty())
+}
+
+/// Check if the port of a frame is the one selected, if any
+fn port_matches(port: Option<u16>, selected: &Option<u16>) -> bool {
+    selected.is_none() || port == *selected
+}
+
+/// Skip the hop-by-hop, routing, destination and fragment extension headers of an IPv6 packet
+///
+/// Returns the upper-layer protocol, whether the packet is not a fragment other than the first,
+/// and the upper-layer payload.
+fn skip_extension_headers(
+    mut next_header: IpNextHeaderProtocol,
+    mut payload: &[u8],
+) -> Option<(IpNextHeaderProtocol, bool, &[u8])> {
+    let mut first_fragment = true;
+
+    loop {
+        let length = match next_header {
+            IpNextHeaderProtocols::Hopopt
+            | IpNextHeaderProtocols::Ipv6Route
+            | IpNextHeaderProtocols::Ipv6Opts => (*payload.get(1)? as usize + 1) * 8,
+            IpNextHeaderProtocols::Ipv6Frag => {
+                let offset = u16::from_be_bytes([*payload.get(2)?, *payload.get(3)?]) >> 3;
+                first_fragment &= offset == 0;
+                8
+            }
+            _ => return Some((next_header, first_fragment, payload)),
+        };
+
+        next_header = IpNextHeaderProtocol(*payload.first()?);
+        payload = payload.get(length..)?;
+    }
+}
+
+/// Derives the capture filter of the given conversations and endpoints, in the tcpdump syntax
+#[tauri::command]
+pub fn derive_capture_filter(selections: Vec<CaptureSelection>) -> Result<String, SniffingError> {
+    derive_expression(&selections).map_err(SniffingError::InvalidCaptureFilter)
+}
+
+/// Narrows the live capture to the given conversations and endpoints, returning the expression of
+/// the filter
+///
+/// No selection removes the filter.
+#[tauri::command]
+pub fn set_capture_filter(
+    selections: Vec<CaptureSelection>,
+    state: tauri::State<SniffingState>,
+) -> Result<String, SniffingError> {
+    let expression = state
+        .capture_filter
+        .lock()
+        .unwrap()
+        .set_selections(selections)
+        .map_err(SniffingError::InvalidCaptureFilter)?;
+
+    info!("Capture filter set: {}", expression);
+
+    Ok(expression)
+}
+
+/// Returns the capture filter, along with the frames it left out
+#[tauri::command]
+pub fn get_capture_filter(state: tauri::State<SniffingState>) -> CaptureFilter {
+    state.capture_filter.lock().unwrap().clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+    use sniffer_parser::LinkType;
+
+    use super::{derive_expression, CaptureFilter, CaptureSelection};
+
+    const CLIENT: IpAddr = IpAddr::V4(Ipv4Addr::new(192, 168, 1, 10));
+    const SERVER: IpAddr = IpAddr::V4(Ipv4Addr::new(93, 184, 216, 34));
+
+    #[test]
+    fn derived_expressions() {
+        let https = conversation("TCP", CLIENT, Some(50000), SERVER, Some(443));
+        assert_eq!(
+            derive_expression(&[https]).unwrap(),
+            "tcp and ((src host 192.168.1.10 and src port 50000 and dst host 93.184.216.34 and dst port 443) or (src host 93.184.216.34 and src port 443 and dst host 192.168.1.10 and dst port 50000))"
+        );
+
+        let ping = conversation("ICMP", CLIENT, None, SERVER, None);
+        let host = CaptureSelection::Endpoint {
+            address: IpAddr::V6(Ipv6Addr::LOCALHOST),
+            port: None,
+        };
+        assert_eq!(
+            derive_expression(&[ping, host]).unwrap(),
+            "(icmp and ((src host 192.168.1.10 and dst host 93.184.216.34) or (src host 93.184.216.34 and dst host 192.168.1.10))) or (host ::1)"
+        );
+
+        let dns = CaptureSelection::Endpoint {
+            address: SERVER,
+            port: Some(53),
+        };
+        assert_eq!(
+            derive_expression(&[dns]).unwrap(),
+            "(src host 93.184.216.34 and src port 53) or (dst host 93.184.216.34 and dst port 53)"
+        );
+
+        let ipv6 = IpAddr::V6(Ipv6Addr::LOCALHOST);
+        let esp = conversation("ESP", ipv6, None, ipv6, None);
+        assert!(derive_expression(&[esp])
+            .unwrap()
+            .starts_with("ip6 proto 50 and"));
+        assert_eq!(derive_expression(&[]).unwrap(), "");
+
+        // Invalid selections
+        assert!(derive_expression(&[conversation("ARP", CLIENT, None, SERVER, None)]).is_err());
+        assert!(derive_expression(&[conversation("ICMP", CLIENT, Some(1), SERVER, None)]).is_err());
+        assert!(derive_expression(&[conversation("IP", CLIENT, None, ipv6, None)]).is_err());
+    }
+
+    #[test]
+    fn filtered_frames() {
+        let link_type = LinkType::Ethernet;
+        let mut filter = CaptureFilter::new();
+        assert!(filter.matches(link_type, &tcp_frame(CLIENT, 50000, SERVER, 80, false)));
+
+        let expression = filter
+            .set_selections(vec![conversation(
+                "TCP",
+                CLIENT,
+                Some(50000),
+                SERVER,
+                Some(443),
+            )])
+            .unwrap();
+        assert_eq!(filter.expression, Some(expression));
+
+        assert!(filter.matches(link_type, &tcp_frame(CLIENT, 50000, SERVER, 443, false)));
+        assert!(filter.matches(link_type, &tcp_frame(SERVER, 443, CLIENT, 50000, true)));
+        assert!(!filter.matches(link_type, &tcp_frame(CLIENT, 50001, SERVER, 443, false)));
+        assert!(!filter.matches(
+            link_type,
+            &tcp_frame(SERVER, 443, CLIENT, 50000, false)[..20]
+        ));
+        assert_eq!(filter.filtered, 2);
+
+        filter
+            .set_selections(vec![CaptureSelection::Endpoint {
+                address: SERVER,
+                port: None,
+            }])
+            .unwrap();
+        assert!(filter.matches(link_type, &tcp_frame(SERVER, 443, CLIENT, 50000, true)));
+        assert!(!filter.matches(link_type, &tcp_frame(CLIENT, 50000, CLIENT, 443, false)));
+        assert_eq!(filter.filtered, 1);
+
+        filter.set_selections(Vec::new()).unwrap();
+        assert_eq!(filter.expression, None);
+        assert!(filter.matches(link_type, &tcp_frame(CLIENT, 50000, CLIENT, 443, false)));
+    }
+
+    #[test]
+    fn monitor_mode_frames() {
+        let mut filter = CaptureFilter::new();
+        filter
+            .set_selections(vec![CaptureSelection::Endpoint {
+                address: SERVER,
+                port: Some(443),
+            }])
+            .unwrap();
+
+        // Radiotap (8) + 802.11 data (24) + LLC/SNAP (8), carrying the IPv4 packet of the frame
+        let radiotap_frame = |frame: &[u8]| {
+            [
+                &[0x00, 0x00, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00][..],
+                &[0x08, 0x01, 0x00, 0x00],
+                &[0x00, 0x11, 0x22, 0x33, 0x44, 0x55],
+                &[0x66, 0x77, 0x88, 0x99, 0xaa, 0xbb],
+                &[0xff; 6],
+                &[0x10, 0x00],
+                &[0xaa, 0xaa, 0x03, 0x00, 0x00, 0x00, 0x08, 0x00],
+                &frame[14..],
+            ]
+            .concat()
+        };
+        let https = radiotap_frame(&tcp_frame(CLIENT, 50000, SERVER, 443, false));
+        let http = radiotap_frame(&tcp_frame(CLIENT, 50000, SERVER, 80, false));
+
+        assert!(filter.matches(LinkType::Ieee80211Radiotap, &https));
+        assert!(!filter.matches(LinkType::Ieee80211Radiotap, &http));
+        assert!(filter.matches(LinkType::Ieee80211, &https[8..]));
+        // Not decoded as Ethernet frames
+        assert!(!filter.matches(LinkType::Ethernet, &https));
+        assert_eq!(filter.filtered, 2);
+    }
+
+    ///////////////////// Utils
+
+    fn conversation(
+        protocol: &str,
+        initiator: IpAddr,
+        initiator_port: Option<u16>,
+        responder: IpAddr,
+        responder_port: Option<u16>,
+    ) -> CaptureSelection {
+        CaptureSelection::Conversation {
+            protocol: protocol.to_owned(),
+            initiator,
+            initiator_port,
+            responder,
+            responder_port,
+        }
+    }
+
+    /// Build an Ethernet frame carrying the ports of a TCP segment over IPv4, optionally tagged
+    fn tcp_frame(
+        source: IpAddr,
+        source_port: u16,
+        destination: IpAddr,
+        destination_port: u16,
+        vlan: bool,
+    ) -> Vec<u8> {
+        let mut frame = vec![0; 12];
+        if vlan {
+            frame.extend([0x81, 0x00, 0x00, 0x0a]);
+        }
+        frame.extend([0x08, 0x00]);
+        frame.extend([0x45, 0, 0, 40, 0, 0, 0x40, 0, 64, 6, 0, 0]);
+        for address in [source, destination] {
+            match address {
+                IpAddr::V4(address) => frame.extend(address.octets()),
+                IpAddr::V6(_) => unreachable!(),
+            }
+        }
+        frame.extend(source_port.to_be_bytes());
+        frame.extend(destination_port.to_be_bytes());
+        frame.extend([0; 16]);
+        frame
+    }
+}
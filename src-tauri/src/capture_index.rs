@@ -0,0 +1,435 @@
+//! On-disk index of .pcap capture files
+//!
+//! The first time a capture file is imported, the position, timestamp and 5-tuple of all the
+//! frames it contains are saved in a sidecar file (`<capture>.wfidx`) placed next to it.
+//! Re-opening the same capture reads the sidecar instead of walking the whole file again.
+//!
+//! Sidecar layout (little endian):
+//! - Magic `WFIDX`, format version (u8)
+//! - Capture file size (u64), capture file modification time in seconds (u64)
+//! - Number of entries (u64)
+//! - Entries, each one made of the record fields and of an optional 5-tuple
+
+use std::fs::{File, Metadata};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use pnet::packet::ethernet::{EtherTypes, EthernetPacket};
+use pnet::packet::ip::{IpNextHeaderProtocol, IpNextHeaderProtocols};
+use pnet::packet::ipv4::Ipv4Packet;
+use pnet::packet::ipv6::Ipv6Packet;
+use pnet::packet::Packet;
+
+use crate::capture_file::CaptureRecord;
+
+const INDEX_MAGIC: &[u8; 5] = b"WFIDX";
+const INDEX_VERSION: u8 = 1;
+const INDEX_EXTENSION: &str = "wfidx";
+
+/// Source and destination addresses and ports, and transport protocol of a frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FiveTuple {
+    pub source: IpAddr,
+    pub destination: IpAddr,
+    pub source_port: u16,
+    pub destination_port: u16,
+    pub protocol: u8,
+}
+
+impl FiveTuple {
+    /// Get the 5-tuple of the opposite direction
+    pub fn reversed(&self) -> Self {
+        FiveTuple {
+            source: self.destination,
+            destination: self.source,
+            source_port: self.destination_port,
+            destination_port: self.source_port,
+            protocol: self.protocol,
+        }
+    }
+}
+
+/// Indexed frame of a capture file
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IndexEntry {
+    pub record: CaptureRecord,
+    pub flow: Option<FiveTuple>,
+}
+
+/// Get the sidecar index path of a capture file
+pub fn index_path<P: AsRef<Path>>(capture_path: P) -> PathBuf {
+    let mut path = capture_path.as_ref().as_os_str().to_owned();
+    path.push(".");
+    path.push(INDEX_EXTENSION);
+
+    PathBuf::from(path)
+}
+
+/// Extract the 5-tuple of an Ethernet frame carrying TCP or UDP over IP
+pub fn extract_five_tuple(frame: &[u8]) -> Option<FiveTuple> {
+    let ethernet = EthernetPacket::new(frame)?;
+
+    match ethernet.get_ethertype() {
+        EtherTypes::Ipv4 => {
+            let ipv4 = Ipv4Packet::new(ethernet.payload())?;
+            build_five_tuple(
+                IpAddr::V4(ipv4.get_source()),
+                IpAddr::V4(ipv4.get_destination()),
+                ipv4.get_next_level_protocol(),
+                ipv4.payload(),
+            )
+        }
+        EtherTypes::Ipv6 => {
+            let ipv6 = Ipv6Packet::new(ethernet.payload())?;
+            build_five_tuple(
+                IpAddr::V6(ipv6.get_source()),
+                IpAddr::V6(ipv6.get_destination()),
+                ipv6.get_next_header(),
+                ipv6.payload(),
+            )
+        }
+        _ => None,
+    }
+}
+
+/// TCP and UDP headers both start with the source and destination ports
+fn build_five_tuple(
+    source: IpAddr,
+    destination: IpAddr,
+    protocol: IpNextHeaderProtocol,
+    payload: &[u8],
+) -> Option<FiveTuple> {
+    if (protocol != IpNextHeaderProtocols::Tcp && protocol != IpNextHeaderProtocols::Udp)
+        || payload.len() < 4
+    {
+        return None;
+    }
+
+    Some(FiveTuple {
+        source,
+        destination,
+        source_port: u16::from_be_bytes([payload[0], payload[1]]),
+        destination_port: u16::from_be_bytes([payload[2], payload[3]]),
+        protocol: protocol.0,
+    })
+}
+
+/// Save the index of a capture file, whose metadata are used to detect later modifications
+pub fn write_index<P: AsRef<Path>>(
+    path: P,
+    capture_metadata: &Metadata,
+    entries: &[IndexEntry],
+) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    write_entries(
+        &mut writer,
+        capture_metadata.len(),
+        modified_secs(capture_metadata),
+        entries,
+    )?;
+    writer.flush()
+}
+
+/// Load the index of a capture file, if it still describes the current content of the file
+pub fn read_index<P: AsRef<Path>>(
+    path: P,
+    capture_metadata: &Metadata,
+) -> io::Result<Option<Vec<IndexEntry>>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    read_entries(
+        &mut reader,
+        capture_metadata.len(),
+        modified_secs(capture_metadata),
+    )
+}
+
+fn modified_secs(metadata: &Metadata) -> u64 {
+    metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
+
+fn write_entries<W: Write>(
+    writer: &mut W,
+    capture_size: u64,
+    capture_modified: u64,
+    entries: &[IndexEntry],
+) -> io::Result<()> {
+    writer.write_all(INDEX_MAGIC)?;
+    writer.write_all(&[INDEX_VERSION])?;
+    writer.write_all(&capture_size.to_le_bytes())?;
+    writer.write_all(&capture_modified.to_le_bytes())?;
+    writer.write_all(&(entries.len() as u64).to_le_bytes())?;
+
+    for entry in entries {
+        writer.write_all(&(entry.record.offset as u64).to_le_bytes())?;
+        writer.write_all(&entry.record.seconds.to_le_bytes())?;
+        writer.write_all(&entry.record.nanoseconds.to_le_bytes())?;
+        writer.write_all(&entry.record.captured_length.to_le_bytes())?;
+        writer.write_all(&entry.record.original_length.to_le_bytes())?;
+
+        match entry.flow {
+            Some(flow) => {
+                writer.write_all(&[1, flow.protocol])?;
+                writer.write_all(&ip_to_bytes(flow.source))?;
+                writer.write_all(&ip_to_bytes(flow.destination))?;
+                writer.write_all(&flow.source_port.to_le_bytes())?;
+                writer.write_all(&flow.destination_port.to_le_bytes())?;
+            }
+            None => writer.write_all(&[0])?,
+        }
+    }
+
+    Ok(())
+}
+
+fn read_entries<R: Read>(
+    reader: &mut R,
+    capture_size: u64,
+    capture_modified: u64,
+) -> io::Result<Option<Vec<IndexEntry>>> {
+    let mut magic = [0u8; 5];
+    reader.read_exact(&mut magic)?;
+    if &magic != INDEX_MAGIC || read_u8(reader)? != INDEX_VERSION {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Not a wirefish index file",
+        ));
+    }
+
+    // The capture file changed after the index creation
+    if read_u64(reader)? != capture_size || read_u64(reader)? != capture_modified {
+        return Ok(None);
+    }
+
+    let count = read_u64(reader)?;
+    let mut entries = Vec::with_capacity(count.min(1 << 20) as usize);
+
+    for _ in 0..count {
+        let offset = read_u64(reader)?;
+        let record = CaptureRecord {
+            offset: offset as usize,
+            seconds: read_u32(reader)?,
+            nanoseconds: read_u32(reader)?,
+            captured_length: read_u32(reader)?,
+            original_length: read_u32(reader)?,
+        };
+
+        // Frames must lie inside the capture file, whatever the index says
+        match offset.checked_add(record.captured_length as u64) {
+            Some(end) if end <= capture_size => (),
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "Frame outside of the capture file",
+                ))
+            }
+        }
+
+        let flow = match read_u8(reader)? {
+            0 => None,
+            _ => {
+                let protocol = read_u8(reader)?;
+                Some(FiveTuple {
+                    protocol,
+                    source: read_ip(reader)?,
+                    destination: read_ip(reader)?,
+                    source_port: read_u16(reader)?,
+                    destination_port: read_u16(reader)?,
+                })
+            }
+        };
+
+        entries.push(IndexEntry { record, flow });
+    }
+
+    Ok(Some(entries))
+}
+
+/// IPv4 addresses are stored as IPv4-mapped IPv6 addresses
+fn ip_to_bytes(ip: IpAddr) -> [u8; 17] {
+    let mut bytes = [0u8; 17];
+    match ip {
+        IpAddr::V4(ip) => bytes[1..].copy_from_slice(&ip.to_ipv6_mapped().octets()),
+        IpAddr::V6(ip) => {
+            bytes[0] = 1;
+            bytes[1..].copy_from_slice(&ip.octets());
+        }
+    }
+
+    bytes
+}
+
+fn read_ip<R: Read>(reader: &mut R) -> io::Result<IpAddr> {
+    let mut bytes = [0u8; 17];
+    reader.read_exact(&mut bytes)?;
+
+    let mut octets = [0u8; 16];
+    octets.copy_from_slice(&bytes[1..]);
+    let ip = Ipv6Addr::from(octets);
+
+    Ok(match (bytes[0], ip.to_ipv4()) {
+        (0, Some(ip)) => IpAddr::V4(ip),
+        (0, None) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+        _ => IpAddr::V6(ip),
+    })
+}
+
+fn read_u8<R: Read>(reader: &mut R) -> io::Result<u8> {
+    let mut bytes = [0u8; 1];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes[0])
+}
+
+fn read_u16<R: Read>(reader: &mut R) -> io::Result<u16> {
+    let mut bytes = [0u8; 2];
+    reader.read_exact(&mut bytes)?;
+    Ok(u16::from_le_bytes(bytes))
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> io::Result<u32> {
+    let mut bytes = [0u8; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64<R: Read>(reader: &mut R) -> io::Result<u64> {
+    let mut bytes = [0u8; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+    use std::path::PathBuf;
+
+    use crate::capture_file::CaptureRecord;
+
+    use super::{
+        extract_five_tuple, index_path, read_entries, write_entries, FiveTuple, IndexEntry,
+    };
+
+    const CAPTURE_SIZE: u64 = 1024;
+    const CAPTURE_MODIFIED: u64 = 1663000000;
+
+    #[test]
+    fn sidecar_index_path() {
+        assert_eq!(
+            index_path("/tmp/capture.pcap"),
+            PathBuf::from("/tmp/capture.pcap.wfidx")
+        );
+    }
+
+    #[test]
+    fn tcp_five_tuple() {
+        let frame = [
+            0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x08, 0x00,
+            0x45, 0x00, 0x00, 0x28, 0x00, 0x00, 0x00, 0x00, 0x40, 0x06, 0x00, 0x00, 0x0a, 0x0a,
+            0x0a, 0x0a, 0x0b, 0x0b, 0x0b, 0x0b, 0x11, 0x5c, 0x01, 0xbb, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x50, 0x02, 0xff, 0xff, 0x00, 0x00, 0x00, 0x00,
+        ];
+
+        assert_eq!(
+            extract_five_tuple(&frame),
+            Some(FiveTuple {
+                source: IpAddr::V4(Ipv4Addr::new(10, 10, 10, 10)),
+                destination: IpAddr::V4(Ipv4Addr::new(11, 11, 11, 11)),
+                source_port: 4444,
+                destination_port: 443,
+                protocol: 6,
+            })
+        );
+        assert_eq!(extract_five_tuple(&frame[..30]), None);
+    }
+
+    #[test]
+    fn index_round_trip() {
+        let entries = build_test_entries();
+        let mut buffer = vec![];
+        write_entries(&mut buffer, CAPTURE_SIZE, CAPTURE_MODIFIED, &entries).unwrap();
+
+        let read = read_entries(&mut buffer.as_slice(), CAPTURE_SIZE, CAPTURE_MODIFIED)
+            .unwrap()
+            .unwrap();
+        assert_eq!(read, entries);
+    }
+
+    #[test]
+    fn outdated_index() {
+        let entries = build_test_entries();
+        let mut buffer = vec![];
+        write_entries(&mut buffer, CAPTURE_SIZE, CAPTURE_MODIFIED, &entries).unwrap();
+
+        let read = read_entries(&mut buffer.as_slice(), CAPTURE_SIZE + 1, CAPTURE_MODIFIED);
+        assert!(read.unwrap().is_none());
+    }
+
+    #[test]
+    fn frames_outside_of_capture() {
+        for offset in [CAPTURE_SIZE as usize - 10, usize::MAX - 10] {
+            let mut entries = build_test_entries();
+            entries[1].record.offset = offset;
+            let mut buffer = vec![];
+            write_entries(&mut buffer, CAPTURE_SIZE, CAPTURE_MODIFIED, &entries).unwrap();
+
+            let read = read_entries(&mut buffer.as_slice(), CAPTURE_SIZE, CAPTURE_MODIFIED);
+            assert_eq!(read.unwrap_err().kind(), io::ErrorKind::InvalidData);
+        }
+    }
+
+    #[test]
+    fn not_an_index() {
+        let read = read_entries(&mut b"miao miao miao".as_slice(), 0, 0);
+        assert!(read.is_err());
+    }
+
+    fn build_test_entries() -> Vec<IndexEntry> {
+        let record = CaptureRecord {
+            offset: 40,
+            seconds: 16,
+            nanoseconds: 5000,
+            captured_length: 60,
+            original_length: 60,
+        };
+
+        vec![
+            IndexEntry {
+                record,
+                flow: Some(FiveTuple {
+                    source: IpAddr::V4(Ipv4Addr::new(10, 10, 10, 10)),
+                    destination: IpAddr::V4(Ipv4Addr::new(11, 11, 11, 11)),
+                    source_port: 4444,
+                    destination_port: 443,
+                    protocol: 6,
+                }),
+            },
+            IndexEntry {
+                record: CaptureRecord {
+                    offset: 116,
+                    ..record
+                },
+                flow: Some(FiveTuple {
+                    source: IpAddr::V6(Ipv6Addr::LOCALHOST),
+                    destination: IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+                    source_port: 53,
+                    destination_port: 5353,
+                    protocol: 17,
+                }),
+            },
+            IndexEntry {
+                record: CaptureRecord {
+                    offset: 192,
+                    ..record
+                },
+                flow: None,
+            },
+        ]
+    }
+}
@@ -0,0 +1,244 @@
+//! Statistics of the live capture, telling whether the sniffer keeps up with the traffic
+//!
+//! - Received: frames read from the interface by the sniffer
+//! - Undissected: frames the parser could not dissect, i.e. with a malformed layer
+//! - Dropped by the kernel: frames received by the interface but dropped before being delivered
+//!   (`rx_dropped`)
+//! - Dropped by the interface: frames the interface had no room for (`rx_missed_errors` and
+//!   `rx_fifo_errors`)
+//!
+//! The capture channel does not expose the counters of its socket, so the drops are the ones of
+//! the whole interface, read from sysfs (Linux only) and counted since the capture was started.
+//! While sniffing, the statistics are emitted as `capture_stats` events at most once per interval.
+
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use sniffer_parser::serializable_packet::util::contains_malformed;
+use sniffer_parser::serializable_packet::ParsedPacket;
+
+use crate::SniffingState;
+
+/// Minimum time between two capture statistics events
+const STATS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Statistics of the live capture, as returned to the frontend
+#[derive(Serialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct CaptureStatsReport {
+    pub interface: Option<String>,
+    pub received: u64,
+    pub undissected: u64,
+    /// Drops since the capture was started, none when the interface counters are not available
+    pub dropped_by_kernel: Option<u64>,
+    pub dropped_by_interface: Option<u64>,
+}
+
+/// Drop counters of a network interface
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct InterfaceCounters {
+    dropped: u64,
+    missed: u64,
+}
+
+impl InterfaceCounters {
+    /// Read the counters of an interface from its sysfs statistics directory
+    fn read(statistics: &Path) -> Option<Self> {
+        let counter = |name: &str| {
+            fs::read_to_string(statistics.join(name))
+                .ok()
+                .and_then(|value| value.trim().parse::<u64>().ok())
+        };
+
+        Some(InterfaceCounters {
+            dropped: counter("rx_dropped")?,
+            missed: counter("rx_missed_errors")? + counter("rx_fifo_errors").unwrap_or(0),
+        })
+    }
+
+    fn of_interface(interface_name: &str) -> Option<Self> {
+        InterfaceCounters::read(
+            &Path::new("/sys/class/net")
+                .join(interface_name)
+                .join("statistics"),
+        )
+    }
+}
+
+/// Counters of the live capture
+#[derive(Debug, Default)]
+pub struct CaptureStats {
+    interface: Option<String>,
+    received: u64,
+    undissected: u64,
+    /// Counters of the interface when the capture was started
+    baseline: Option<InterfaceCounters>,
+    last_event: Option<Instant>,
+}
+
+impl CaptureStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start counting the frames of a new capture, unless resuming the current one
+    pub fn start(&mut self, interface_name: &str, is_resume: bool) {
+        let baseline = InterfaceCounters::of_interface(interface_name);
+        self.restart(interface_name, is_resume, baseline);
+    }
+
+    fn restart(
+        &mut self,
+        interface_name: &str,
+        is_resume: bool,
+        baseline: Option<InterfaceCounters>,
+    ) {
+        if is_resume && self.interface.as_deref() == Some(interface_name) {
+            return;
+        }
+
+        *self = CaptureStats {
+            interface: Some(interface_name.to_owned()),
+            baseline,
+            ..CaptureStats::default()
+        };
+    }
+
+    /// Count a frame read from the interface
+    pub fn receive(&mut self) {
+        self.received += 1;
+    }
+
+    /// Count the dissection of a frame, undissected if any of its layers is malformed
+    pub fn dissect(&mut self, packet: &ParsedPacket) {
+        if contains_malformed(packet) {
+            self.undissected += 1;
+        }
+    }
+
+    /// Get the statistics of the capture, if the interval since the last event has elapsed
+    pub fn poll(&mut self, now: Instant) -> Option<CaptureStatsReport> {
+        if self
+            .last_event
+            .is_some_and(|last_event| now.saturating_duration_since(last_event) < STATS_INTERVAL)
+        {
+            return None;
+        }
+        self.last_event = Some(now);
+
+        Some(self.report())
+    }
+
+    /// Get the statistics of the capture
+    pub fn report(&self) -> CaptureStatsReport {
+        let current = self
+            .interface
+            .as_deref()
+            .and_then(InterfaceCounters::of_interface);
+        self.report_with(current)
+    }
+
+    fn report_with(&self, current: Option<InterfaceCounters>) -> CaptureStatsReport {
+        let drops = self.baseline.zip(current).map(|(baseline, current)| {
+            (
+                current.dropped.saturating_sub(baseline.dropped),
+                current.missed.saturating_sub(baseline.missed),
+            )
+        });
+
+        CaptureStatsReport {
+            interface: self.interface.clone(),
+            received: self.received,
+            undissected: self.undissected,
+            dropped_by_kernel: drops.map(|(dropped, _)| dropped),
+            dropped_by_interface: drops.map(|(_, missed)| missed),
+        }
+    }
+}
+
+/// Returns the statistics of the live capture: frames received, undissected and dropped
+#[tauri::command]
+pub fn get_capture_stats(state: tauri::State<SniffingState>) -> CaptureStatsReport {
+    state.capture_stats.lock().unwrap().report()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::time::{Duration, Instant};
+
+    use pnet::packet::ethernet::EthernetPacket;
+    use sniffer_parser::parse_ethernet_frame;
+    use sniffer_parser::serializable_packet::ParsedPacket;
+
+    use super::{CaptureStats, InterfaceCounters, STATS_INTERVAL};
+
+    #[test]
+    fn interface_counters() {
+        let directory = std::env::temp_dir().join(format!("wirefish-stats-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(directory.join("rx_dropped"), "12\n").unwrap();
+        fs::write(directory.join("rx_missed_errors"), "3\n").unwrap();
+        fs::write(directory.join("rx_fifo_errors"), "1\n").unwrap();
+
+        assert_eq!(
+            InterfaceCounters::read(&directory),
+            Some(InterfaceCounters {
+                dropped: 12,
+                missed: 4
+            })
+        );
+
+        fs::remove_file(directory.join("rx_dropped")).unwrap();
+        assert_eq!(InterfaceCounters::read(&directory), None);
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn frames_received_undissected_and_dropped() {
+        let mut stats = CaptureStats::new();
+        let baseline = InterfaceCounters {
+            dropped: 10,
+            missed: 2,
+        };
+        stats.restart("eth0", false, Some(baseline));
+
+        // Ethernet header of an IPv4 packet missing
+        let mut frame = [0x00; 14];
+        frame[12] = 0x08;
+        let malformed = parse_ethernet_frame(&EthernetPacket::new(&frame).unwrap(), 0);
+        for packet in [&malformed, &ParsedPacket::new(1)] {
+            stats.receive();
+            stats.dissect(packet);
+        }
+        stats.receive();
+
+        let report = stats.report_with(Some(InterfaceCounters {
+            dropped: 15,
+            missed: 2,
+        }));
+        assert_eq!(report.interface.as_deref(), Some("eth0"));
+        assert_eq!((report.received, report.undissected), (3, 1));
+        assert_eq!(report.dropped_by_kernel, Some(5));
+        assert_eq!(report.dropped_by_interface, Some(0));
+        assert_eq!(stats.report_with(None).dropped_by_kernel, None);
+
+        // Resuming keeps counting, starting again does not
+        stats.restart("eth0", true, None);
+        assert_eq!(stats.report_with(None).received, 3);
+        stats.restart("eth0", false, None);
+        assert_eq!(stats.report_with(None).received, 0);
+    }
+
+    #[test]
+    fn events_throttled() {
+        let mut stats = CaptureStats::new();
+        let now = Instant::now();
+
+        assert!(stats.poll(now).is_some());
+        assert!(stats.poll(now + Duration::from_millis(500)).is_none());
+        assert!(stats.poll(now + STATS_INTERVAL).is_some());
+    }
+}
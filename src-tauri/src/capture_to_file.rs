@@ -0,0 +1,448 @@
+//! Saving of the sniffed frames to a .pcapng file
+//!
+//! While a capture to file is active, every frame received by the sniffing process is appended
+//! to the file together with its nanosecond timestamp, so that it can be opened with Wireshark.
+//! Optionally, each frame carries a comment listing the protocols dissected by wirefish, as in the
+//! .pcapng exports.
+//! Frames of wireless interfaces in monitor mode are saved as 802.11 ones.
+//! The file is closed when the capture to file is stopped, or when the sniffing is terminated.
+//!
+//! For long-running captures, the frames can be saved to a ring buffer of files instead, as with
+//! the `-b` option of dumpcap: a new file is started once the current one reaches a size, a
+//! duration or a number of frames, and only the last files are kept. The files are named after the
+//! path of the capture, with their number and start time (`capture_00001_20240101120000.pcapng`),
+//! and can be listed to import them.
+
+use std::collections::VecDeque;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use sniffer_parser::serializable_packet::ParsedPacket;
+use tracing::{error, info, warn};
+
+use crate::export::packet_comment;
+use crate::pcapng::{write_enhanced_packet, write_interface_description, write_section_header};
+use crate::{interface_link_type, SniffingError, SniffingState, CONFIG};
+
+/// Rotation of the files of a capture to file; a new file is started once any limit is reached
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct RingBuffer {
+    /// Bytes of each file (`filesize`)
+    pub file_size: Option<u64>,
+    /// Seconds of frames of each file (`duration`)
+    pub duration: Option<u64>,
+    /// Frames of each file (`packets`)
+    pub packets: Option<u64>,
+    /// Files kept, the oldest ones being deleted (`files`); all of them when not set
+    pub files: Option<usize>,
+}
+
+impl RingBuffer {
+    /// Check that the files are rotated, and that no limit is zero
+    fn validate(&self) -> Result<(), String> {
+        let limits = [
+            self.file_size,
+            self.duration,
+            self.packets,
+            self.files.map(|files| files as u64),
+        ];
+        if limits.contains(&Some(0)) {
+            return Err("The limits of the ring buffer must be positive".to_owned());
+        }
+        if self.file_size.is_none() && self.duration.is_none() && self.packets.is_none() {
+            return Err(
+                "The ring buffer needs a file size, a duration or a number of frames".to_owned(),
+            );
+        }
+
+        Ok(())
+    }
+}
+
+/// A file of a ring buffer
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct RingBufferFile {
+    pub path: String,
+    /// Position of the file in the ring buffer, from 1
+    pub number: usize,
+    pub size: u64,
+}
+
+/// A .pcapng file receiving the sniffed frames
+pub struct CaptureToFile {
+    path: String,
+    writer: BufWriter<File>,
+    comments: bool,
+    interface_name: String,
+    ring_buffer: Option<RingBuffer>,
+    /// Files of the ring buffer kept so far, the current one last
+    files: VecDeque<PathBuf>,
+    /// Number of the current file of the ring buffer
+    file_number: usize,
+    /// Bytes and frames written to the current file, and the time it was started
+    file_size: u64,
+    file_packets: u64,
+    file_start: Instant,
+}
+
+impl CaptureToFile {
+    /// Create the file, describing the interface the frames are captured from
+    ///
+    /// With a ring buffer, the first file of the ring buffer is created instead.
+    pub fn create(
+        path: &str,
+        interface_name: &str,
+        comments: bool,
+        ring_buffer: Option<RingBuffer>,
+    ) -> io::Result<Self> {
+        let file_path = match ring_buffer {
+            Some(_) => ring_buffer_path(Path::new(path), 1),
+            None => PathBuf::from(path),
+        };
+        let (writer, file_size) = create_file(&file_path, interface_name)?;
+
+        Ok(CaptureToFile {
+            path: path.to_owned(),
+            writer,
+            comments,
+            interface_name: interface_name.to_owned(),
+            ring_buffer,
+            files: VecDeque::from(vec![file_path]),
+            file_number: 1,
+            file_size,
+            file_packets: 0,
+            file_start: Instant::now(),
+        })
+    }
+
+    /// Append a frame received now, snapped to the given length, together with its dissected
+    /// representation
+    pub fn write(
+        &mut self,
+        frame: &[u8],
+        snap_length: usize,
+        packet: &ParsedPacket,
+    ) -> io::Result<()> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+
+        let comment = self.comments.then(|| packet_comment(packet)).flatten();
+
+        if self.is_rotation_due() {
+            self.rotate()?;
+        }
+
+        let mut block = vec![];
+        write_enhanced_packet(
+            &mut block,
+            0,
+            timestamp,
+            &frame[..snap_length.min(frame.len())],
+            frame.len() as u32,
+            comment.as_deref(),
+        )?;
+        self.writer.write_all(&block)?;
+        self.file_size += block.len() as u64;
+        self.file_packets += 1;
+
+        Ok(())
+    }
+
+    /// Check if the current file of the ring buffer has reached any of its limits
+    fn is_rotation_due(&self) -> bool {
+        let ring_buffer = match self.ring_buffer {
+            Some(ring_buffer) if self.file_packets > 0 => ring_buffer,
+            _ => return false,
+        };
+
+        ring_buffer
+            .file_size
+            .is_some_and(|file_size| self.file_size >= file_size)
+            || ring_buffer
+                .duration
+                .is_some_and(|duration| self.file_start.elapsed().as_secs() >= duration)
+            || ring_buffer
+                .packets
+                .is_some_and(|packets| self.file_packets >= packets)
+    }
+
+    /// Close the current file of the ring buffer and start the next one, deleting the oldest
+    /// files beyond the ones kept
+    fn rotate(&mut self) -> io::Result<()> {
+        self.writer.flush()?;
+
+        let file_path = ring_buffer_path(Path::new(&self.path), self.file_number + 1);
+        let (writer, file_size) = create_file(&file_path, &self.interface_name)?;
+        self.writer = writer;
+        self.file_number += 1;
+        self.file_size = file_size;
+        self.file_packets = 0;
+        self.file_start = Instant::now();
+        self.files.push_back(file_path);
+
+        let kept = self.ring_buffer.and_then(|ring_buffer| ring_buffer.files);
+        while kept.is_some_and(|kept| self.files.len() > kept) {
+            if let Some(oldest) = self.files.pop_front() {
+                if let Err(e) = fs::remove_file(&oldest) {
+                    warn!("[{}] Ring buffer file not deleted: {}", oldest.display(), e);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Flush the frames still buffered and close the file
+    pub fn finish(mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// Create a .pcapng file, writing the headers describing the interface the frames are captured from
+///
+/// The number of bytes written is returned together with the file writer.
+fn create_file(path: &Path, interface_name: &str) -> io::Result<(BufWriter<File>, u64)> {
+    let mut headers = vec![];
+    write_section_header(&mut headers, "wirefish")?;
+    write_interface_description(
+        &mut headers,
+        interface_link_type(interface_name).linktype() as u16,
+        CONFIG.read_buffer_size as u32,
+        Some(interface_name),
+    )?;
+
+    let mut writer = BufWriter::new(File::create(path)?);
+    writer.write_all(&headers)?;
+
+    Ok((writer, headers.len() as u64))
+}
+
+/// Get the path of a file of the ring buffer of a capture, given its number
+fn ring_buffer_path(path: &Path, number: usize) -> PathBuf {
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let extension = path
+        .extension()
+        .map(|extension| extension.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "pcapng".to_owned());
+
+    path.with_file_name(format!(
+        "{}_{:05}_{}.{}",
+        stem,
+        number,
+        Local::now().format("%Y%m%d%H%M%S"),
+        extension
+    ))
+}
+
+/// Get the number of a file of the ring buffer of a capture from its name, if it is one of them
+fn ring_buffer_number(capture: &Path, file_name: &str) -> Option<usize> {
+    let stem = capture.file_stem()?.to_str()?;
+    let extension = match capture.extension() {
+        Some(extension) => extension.to_str()?,
+        None => "pcapng",
+    };
+
+    let suffix = file_name
+        .strip_prefix(stem)?
+        .strip_prefix('_')?
+        .strip_suffix(extension)?
+        .strip_suffix('.')?;
+    let (number, start_time) = suffix.split_once('_')?;
+    let is_digits = |text: &str, len: usize| {
+        text.len() == len && text.bytes().all(|byte| byte.is_ascii_digit())
+    };
+    if !is_digits(number, 5) || !is_digits(start_time, 14) {
+        return None;
+    }
+
+    number.parse().ok()
+}
+
+/// Starts saving the frames sniffed from now on to a .pcapng file, replacing any capture to file
+/// already active
+///
+/// With a ring buffer, the frames are saved to files rotated as it sets.
+#[tauri::command]
+pub fn start_capture_to_file(
+    path: String,
+    comments: bool,
+    ring_buffer: Option<RingBuffer>,
+    state: tauri::State<SniffingState>,
+) -> Result<(), SniffingError> {
+    if let Some(ring_buffer) = &ring_buffer {
+        ring_buffer
+            .validate()
+            .map_err(SniffingError::CaptureToFileFailed)?;
+    }
+
+    let info = state.info.lock().unwrap();
+    let interface_name = info.interface_name.as_ref().ok_or_else(|| {
+        SniffingError::StartSniffingWithoutInterfaceSelection(
+            "Capture to file without prior selection of the interface".to_owned(),
+        )
+    })?;
+
+    let capture =
+        CaptureToFile::create(&path, interface_name, comments, ring_buffer).map_err(|e| {
+            SniffingError::CaptureToFileFailed(format!("Cannot create {}: {}", path, e))
+        })?;
+
+    if let Some(previous) = state.capture_to_file.lock().unwrap().replace(capture) {
+        close(previous);
+    }
+    info!("[{}] Capture to file started", path);
+
+    Ok(())
+}
+
+/// Stops saving the sniffed frames, closing the .pcapng file
+#[tauri::command]
+pub fn stop_capture_to_file(state: tauri::State<SniffingState>) -> Result<(), SniffingError> {
+    let capture = state
+        .capture_to_file
+        .lock()
+        .unwrap()
+        .take()
+        .ok_or_else(|| {
+            SniffingError::CaptureToFileFailed("No capture to file is active".to_owned())
+        })?;
+
+    let path = capture.path.clone();
+    if let Err(e) = capture.finish() {
+        return Err(SniffingError::CaptureToFileFailed(format!(
+            "Cannot write {}: {}",
+            path, e
+        )));
+    }
+    info!("[{}] Capture to file stopped", path);
+
+    Ok(())
+}
+
+/// Lists the files of the ring buffer of a capture to file, given the path of the capture, ordered by
+/// their number
+///
+/// The files are imported as any other capture file.
+#[tauri::command]
+pub fn list_ring_buffer_files(path: String) -> Result<Vec<RingBufferFile>, SniffingError> {
+    let capture = Path::new(&path);
+    let directory = match capture.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+
+    let entries = fs::read_dir(directory).map_err(|e| {
+        SniffingError::CaptureToFileFailed(format!("Cannot list {}: {}", directory.display(), e))
+    })?;
+    let mut files: Vec<RingBufferFile> = entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let number = ring_buffer_number(capture, entry.file_name().to_str()?)?;
+            Some(RingBufferFile {
+                path: entry.path().to_string_lossy().into_owned(),
+                number,
+                size: entry.metadata().ok()?.len(),
+            })
+        })
+        .collect();
+    files.sort_by_key(|file| file.number);
+    info!("[{}] Ring buffer files: {}", path, files.len());
+
+    Ok(files)
+}
+
+/// Close a capture to file which is no longer needed, logging any failure
+pub fn close(capture: CaptureToFile) {
+    let path = capture.path.clone();
+    match capture.finish() {
+        Ok(()) => info!("[{}] Capture to file stopped", path),
+        Err(e) => error!("[{}] Capture to file failed: {}", path, e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+
+    use sniffer_parser::serializable_packet::ParsedPacket;
+
+    use super::{
+        list_ring_buffer_files, ring_buffer_number, ring_buffer_path, CaptureToFile, RingBuffer,
+    };
+
+    #[test]
+    fn ring_buffer_file_names() {
+        let capture = Path::new("/tmp/capture.pcapng");
+        let path = ring_buffer_path(capture, 12);
+        let file_name = path.file_name().unwrap().to_str().unwrap();
+
+        assert!(file_name.starts_with("capture_00012_"));
+        assert_eq!(ring_buffer_number(capture, file_name), Some(12));
+        assert_eq!(ring_buffer_number(capture, "capture.pcapng"), None);
+        assert_eq!(
+            ring_buffer_number(capture, "capture_00001_2024.pcapng"),
+            None
+        );
+        assert_eq!(
+            ring_buffer_number(capture, "other_00001_20240101120000.pcapng"),
+            None
+        );
+    }
+
+    #[test]
+    fn ring_buffer_rotation() {
+        let directory = std::env::temp_dir().join(format!("wirefish-ring-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("capture.pcapng");
+        let path = path.to_str().unwrap();
+
+        let ring_buffer = RingBuffer {
+            packets: Some(2),
+            files: Some(2),
+            ..Default::default()
+        };
+        let mut capture = CaptureToFile::create(path, "lo", false, Some(ring_buffer)).unwrap();
+        for _ in 0..5 {
+            capture
+                .write(&[0u8; 60], 60, &ParsedPacket::new(0))
+                .unwrap();
+        }
+        capture.finish().unwrap();
+
+        // Frames 1-2, 3-4 and 5: the first file is deleted
+        let files = list_ring_buffer_files(path.to_owned()).unwrap();
+        fs::remove_dir_all(&directory).unwrap();
+        assert_eq!(
+            files.iter().map(|file| file.number).collect::<Vec<_>>(),
+            vec![2, 3]
+        );
+        assert!(files[0].size > files[1].size);
+    }
+
+    #[test]
+    fn ring_buffer_limits() {
+        assert!(RingBuffer::default().validate().is_err());
+        let ring_buffer = RingBuffer {
+            file_size: Some(1 << 20),
+            files: Some(0),
+            ..Default::default()
+        };
+        assert!(ring_buffer.validate().is_err());
+        let ring_buffer = RingBuffer {
+            duration: Some(60),
+            ..Default::default()
+        };
+        assert!(ring_buffer.validate().is_ok());
+    }
+}
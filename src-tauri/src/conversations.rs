@@ -0,0 +1,1038 @@
+//! Tracking of the conversations between endpoints
+//!
+//! A conversation groups the packets exchanged in both directions by two endpoints over a transport
+//! protocol, i.e. the packets sharing the same 5-tuple once their direction is ignored. The endpoint
+//! sending the first packet is the initiator. For each conversation are kept:
+//! - packets and bytes sent in each direction
+//! - first and last time a packet was seen, and the duration in between
+//! - state of the connection, for TCP
+//! - requests and responses paired, for UDP
+//! - service most likely reached, for TLS over TCP or QUIC (see [`crate::tls_destination`])
+//! - handshakes and transport data messages of the session between two peers, for WireGuard
+//! - reassembled byte stream, for TCP (see [`crate::streams`])
+//! - round-trip times of the handshake and of the data acknowledged, for TCP
+//! - locations of the endpoints, when GeoIP databases are set (see [`crate::geoip`])
+//! - host names of the endpoints, when reverse DNS is enabled (see [`crate::reverse_dns`])
+//!
+//! UDP has no connection: a UDP conversation ends once idle for longer than the UDP flow timeout,
+//! a later datagram between the same endpoints starting a new one. The datagrams of DNS, NTP and
+//! QUIC are paired as requests and responses, so that the client is the initiator even when its
+//! first datagram was missed, and the time taken by the server to respond is known:
+//! - DNS: queries and responses, by their transaction ID
+//! - NTP: datagrams sent to and from the server port
+//! - QUIC: datagrams with an Initial packet, sent by the client and then by the server
+//!
+//! ICMP messages carry no ports, so all the ones exchanged by two hosts form a single conversation.
+//! Packets without an IP layer (e.g. ARP) belong to no conversation.
+
+use std::cmp::Ordering;
+use std::collections::{HashMap, VecDeque};
+use std::net::IpAddr;
+
+use chrono::{DateTime, Local};
+use pnet::packet::tcp::TcpFlags;
+use serde::{Deserialize, Serialize};
+use sniffer_parser::serializable_packet::application::SerializableWireGuardPacket;
+use sniffer_parser::serializable_packet::bytes::FrameBytes;
+use sniffer_parser::serializable_packet::network::GeoLocation;
+use sniffer_parser::serializable_packet::{ParsedPacket, SerializablePacket};
+use sniffer_parser::{get_flow_timeouts, FlowKey};
+
+use crate::statistics::{Counters, Timestamp};
+use crate::streams::{FollowedStream, StreamDirection, TcpStreamData};
+use crate::tls_destination::{DnsNames, TlsDestination, TlsNames};
+use crate::SniffingState;
+
+/// State of a TCP connection, as seen from its packets
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum TcpState {
+    /// SYN sent by the initiator
+    SynSent,
+    /// SYN acknowledged by the responder
+    SynReceived,
+    /// Handshake completed, or connection already open when its first packet was seen
+    Established,
+    /// FIN sent by one of the endpoints
+    Closing,
+    /// FIN sent by both the endpoints
+    Closed,
+    /// RST sent by one of the endpoints
+    Reset,
+}
+
+/// Requests and responses of a UDP conversation, paired by their application protocol
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct UdpExchanges {
+    pub application: String,
+    pub requests: usize,
+    /// Responses paired with a request
+    pub responses: usize,
+    /// Average time between a request and its response, in milliseconds
+    pub response_time: Option<i64>,
+}
+
+/// Messages exchanged by two WireGuard peers
+#[derive(Serialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct WireGuardSession {
+    pub handshake_initiations: usize,
+    /// Handshake responses, each starting a new session
+    pub handshakes: usize,
+    /// Cookie replies, sent by a peer under load instead of a handshake response
+    pub cookie_replies: usize,
+    pub last_handshake: Option<Timestamp>,
+    /// Indexes of the current session, chosen by the initiator and by the responder
+    pub initiator_index: Option<u32>,
+    pub responder_index: Option<u32>,
+    /// Transport data messages, keepalives excluded
+    pub data_messages: usize,
+    /// Bytes of the encrypted tunneled packets, with their padding
+    pub data_bytes: usize,
+    pub keepalives: usize,
+}
+
+impl WireGuardSession {
+    /// Count a WireGuard message sent by one of the peers
+    fn update(&mut self, message: &SerializableWireGuardPacket, time: i64) {
+        match message.message_type.as_str() {
+            "Handshake Initiation" => self.handshake_initiations += 1,
+            "Handshake Response" => {
+                self.handshakes += 1;
+                self.last_handshake = Some(time);
+                self.initiator_index = message.receiver_index;
+                self.responder_index = message.sender_index;
+            }
+            "Cookie Reply" => self.cookie_replies += 1,
+            _ if message.length == 0 => self.keepalives += 1,
+            _ => {
+                self.data_messages += 1;
+                self.data_bytes += message.length;
+            }
+        }
+    }
+}
+
+/// Round-trip times of a TCP connection, in milliseconds
+///
+/// The handshake takes a round trip between the client and the server, while the data is
+/// acknowledged a round trip after being seen, between the capture point and its receiver.
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RoundTripTimes {
+    /// From the SYN to the ACK of the SYN-ACK
+    pub handshake: Option<f64>,
+    /// Round-trip times from the data to its acknowledgement
+    pub samples: usize,
+    pub min: Option<f64>,
+    pub average: Option<f64>,
+    pub max: Option<f64>,
+    #[serde(skip)]
+    total: f64,
+}
+
+impl RoundTripTimes {
+    /// Add the round-trip times completed by a TCP segment, in microseconds
+    fn update(&mut self, handshake_rtt: Option<u64>, ack_rtt: Option<u64>) {
+        if let Some(handshake_rtt) = handshake_rtt {
+            self.handshake = Some(handshake_rtt as f64 / 1000.0);
+        }
+
+        if let Some(ack_rtt) = ack_rtt {
+            let rtt = ack_rtt as f64 / 1000.0;
+            self.samples += 1;
+            self.total += rtt;
+            self.min = Some(self.min.map_or(rtt, |min| min.min(rtt)));
+            self.average = Some(self.total / self.samples as f64);
+            self.max = Some(self.max.map_or(rtt, |max| max.max(rtt)));
+        }
+    }
+}
+
+/// Field the conversations are sorted by
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum ConversationOrder {
+    Packets,
+    Bytes,
+    Duration,
+    FirstSeen,
+    LastSeen,
+}
+
+/// Conversation between two endpoints, as returned to the frontend
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Conversation {
+    /// Identifier of the conversation, unique in the sniffing session
+    pub id: usize,
+    pub protocol: String,
+    pub initiator: IpAddr,
+    pub initiator_port: Option<u16>,
+    pub responder: IpAddr,
+    pub responder_port: Option<u16>,
+    /// Traffic sent by the initiator
+    pub sent: Counters,
+    /// Traffic sent by the responder
+    pub received: Counters,
+    pub first_seen: Timestamp,
+    pub last_seen: Timestamp,
+    /// Time between the first and the last packet, in milliseconds
+    pub duration: i64,
+    pub tcp_state: Option<TcpState>,
+    pub udp_exchanges: Option<UdpExchanges>,
+    pub tls_destination: Option<TlsDestination>,
+    pub wireguard: Option<WireGuardSession>,
+    pub round_trip_times: Option<RoundTripTimes>,
+    /// Locations of the endpoints, filled in from the GeoIP databases when set
+    pub initiator_location: Option<GeoLocation>,
+    pub responder_location: Option<GeoLocation>,
+    /// Host names of the endpoints, filled in once resolved when reverse DNS is enabled
+    pub initiator_name: Option<String>,
+    pub responder_name: Option<String>,
+}
+
+impl Conversation {
+    fn packets(&self) -> usize {
+        self.sent.packets + self.received.packets
+    }
+
+    fn bytes(&self) -> usize {
+        self.sent.bytes + self.received.bytes
+    }
+
+    /// Check if an endpoint of the conversation has the given address or port
+    fn involves(&self, address: Option<IpAddr>, port: Option<u16>) -> bool {
+        let endpoints = [
+            (self.initiator, self.initiator_port),
+            (self.responder, self.responder_port),
+        ];
+
+        endpoints.iter().any(|&(ip, ip_port)| {
+            (address.is_none() || address == Some(ip)) && (port.is_none() || port == ip_port)
+        })
+    }
+
+    fn compare(&self, other: &Conversation, order: ConversationOrder) -> Ordering {
+        match order {
+            ConversationOrder::Packets => self.packets().cmp(&other.packets()),
+            ConversationOrder::Bytes => self.bytes().cmp(&other.bytes()),
+            ConversationOrder::Duration => self.duration.cmp(&other.duration),
+            ConversationOrder::FirstSeen => self.first_seen.cmp(&other.first_seen),
+            ConversationOrder::LastSeen => self.last_seen.cmp(&other.last_seen),
+        }
+    }
+}
+
+/// Transport protocol and flow of a conversation
+type ConversationKey = (String, FlowKey);
+
+/// UDP datagram of an application whose requests and responses are paired
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct UdpMessage {
+    application: &'static str,
+    /// Whether the datagram is a request, none if only its direction tells
+    request: Option<bool>,
+    /// Transaction ID pairing a response with its request
+    id: Option<u16>,
+}
+
+/// Transport layer of a packet, as far as its conversation is concerned
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TransportDetails {
+    /// TCP segment, with its flags
+    Tcp(u16),
+    /// UDP datagram, with the request or response it carries
+    Udp(Option<UdpMessage>),
+    Other,
+}
+
+/// NTP server port
+const NTP_PORT: u16 = 123;
+
+/// QUIC server port
+const QUIC_PORT: u16 = 443;
+
+/// Requests waiting for a response kept for each UDP conversation
+const PENDING_REQUESTS_SIZE: usize = 64;
+
+/// Conversation being tracked, with the endpoints that sent a FIN, the UDP requests waiting for a
+/// response, the names of the TLS server and the TCP stream
+#[derive(Debug)]
+struct TrackedConversation {
+    conversation: Conversation,
+    initiator_fin: bool,
+    responder_fin: bool,
+    /// Transaction ID and time of the requests, the oldest first
+    pending: VecDeque<(Option<u16>, i64)>,
+    /// Time taken by all the paired responses, in milliseconds
+    total_response_time: i64,
+    tls_names: TlsNames,
+    stream: TcpStreamData,
+}
+
+impl TrackedConversation {
+    /// Move the state of the TCP connection forward with a segment sent by the initiator or by
+    /// the responder
+    fn update_tcp_state(&mut self, flags: u16, from_initiator: bool) {
+        let is_syn = flags & TcpFlags::SYN != 0;
+        let is_ack = flags & TcpFlags::ACK != 0;
+        let state = self.conversation.tcp_state;
+
+        let next = if flags & TcpFlags::RST != 0 {
+            TcpState::Reset
+        } else if is_syn && !is_ack {
+            // A new connection may reuse the endpoints of a finished one
+            self.initiator_fin = false;
+            self.responder_fin = false;
+            TcpState::SynSent
+        } else if is_syn {
+            TcpState::SynReceived
+        } else if flags & TcpFlags::FIN != 0 {
+            if from_initiator {
+                self.initiator_fin = true;
+            } else {
+                self.responder_fin = true;
+            }
+            if self.initiator_fin && self.responder_fin {
+                TcpState::Closed
+            } else {
+                TcpState::Closing
+            }
+        } else {
+            match state {
+                None | Some(TcpState::SynReceived) => TcpState::Established,
+                Some(state) => state,
+            }
+        };
+
+        self.conversation.tcp_state = Some(next);
+    }
+
+    /// Pair a UDP datagram with the previous ones, as a request or as the response to a pending
+    /// request
+    fn update_udp_exchanges(&mut self, message: UdpMessage, from_initiator: bool, time: i64) {
+        let exchanges = self
+            .conversation
+            .udp_exchanges
+            .get_or_insert_with(|| UdpExchanges {
+                application: message.application.to_owned(),
+                requests: 0,
+                responses: 0,
+                response_time: None,
+            });
+
+        if message.request.unwrap_or(from_initiator) {
+            exchanges.requests += 1;
+            if self.pending.len() >= PENDING_REQUESTS_SIZE {
+                self.pending.pop_front();
+            }
+            self.pending.push_back((message.id, time));
+            return;
+        }
+
+        // Responses without a pending request (e.g. retransmitted ones) are not paired
+        let paired = self.pending.iter().position(|(id, _)| *id == message.id);
+        if let Some((_, request_time)) = paired.and_then(|index| self.pending.remove(index)) {
+            exchanges.responses += 1;
+            self.total_response_time += (time - request_time).max(0);
+            exchanges.response_time = Some(self.total_response_time / exchanges.responses as i64);
+        }
+    }
+}
+
+/// Conversations of the collected packets, by their 5-tuple
+#[derive(Debug, Default)]
+pub struct ConnectionTracker {
+    conversations: HashMap<ConversationKey, TrackedConversation>,
+    /// UDP conversations ended by their idle timeout
+    ended: Vec<Conversation>,
+    /// Names resolved by the DNS answers, telling the services of the TLS flows
+    dns_names: DnsNames,
+    /// Identifier of the next conversation
+    next_id: usize,
+}
+
+impl ConnectionTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Update the conversation of a packet, given its size and the time it was received
+    pub fn update(&mut self, packet: &ParsedPacket, bytes: usize, time: DateTime<Local>) {
+        let (source, destination) = match ip_addresses(packet) {
+            Some(addresses) => addresses,
+            None => return,
+        };
+
+        let (protocol, ports, details) = match packet.get_transport_layer_packet() {
+            Some(SerializablePacket::TcpPacket(tcp)) => (
+                "TCP",
+                Some((tcp.source, tcp.destination)),
+                TransportDetails::Tcp(tcp.flags),
+            ),
+            Some(SerializablePacket::UdpPacket(udp)) => (
+                "UDP",
+                Some((udp.source, udp.destination)),
+                TransportDetails::Udp(udp_message(packet, (udp.source, udp.destination))),
+            ),
+            Some(
+                SerializablePacket::IcmpPacket(_)
+                | SerializablePacket::EchoReplyPacket(_)
+                | SerializablePacket::EchoRequestPacket(_)
+                | SerializablePacket::IcmpTimestampPacket(_)
+                | SerializablePacket::IcmpAddressMaskPacket(_)
+                | SerializablePacket::RouterAdvertisementPacket(_),
+            ) => ("ICMP", None, TransportDetails::Other),
+            Some(SerializablePacket::Icmpv6Packet(_)) => ("ICMPv6", None, TransportDetails::Other),
+            Some(SerializablePacket::EspPacket(_)) => ("ESP", None, TransportDetails::Other),
+            Some(SerializablePacket::AhPacket(_)) => ("AH", None, TransportDetails::Other),
+            _ => ("IP", None, TransportDetails::Other),
+        };
+
+        self.dns_names.update(packet);
+
+        let source = (source, ports.map(|ports| ports.0));
+        let destination = (destination, ports.map(|ports| ports.1));
+        self.track(
+            protocol,
+            source,
+            destination,
+            details,
+            bytes,
+            time.timestamp_millis(),
+        );
+
+        if TlsNames::is_tls(packet) {
+            let key = conversation_key(protocol, source, destination);
+            if let Some(tracked) = self.conversations.get_mut(&key) {
+                let conversation = &mut tracked.conversation;
+                let from_client = (conversation.initiator, conversation.initiator_port) == source;
+                tracked.tls_names.update(packet, from_client);
+                let dns_names = self.dns_names.names(&conversation.responder);
+                conversation.tls_destination = tracked.tls_names.infer(dns_names);
+            }
+        }
+
+        if let Some(SerializablePacket::TcpPacket(tcp)) = packet.get_transport_layer_packet() {
+            let key = conversation_key(protocol, source, destination);
+            let completed = tcp.handshake_rtt.is_some() || tcp.ack_rtt.is_some();
+            if let Some(tracked) = self.conversations.get_mut(&key).filter(|_| completed) {
+                tracked
+                    .conversation
+                    .round_trip_times
+                    .get_or_insert_with(RoundTripTimes::default)
+                    .update(tcp.handshake_rtt, tcp.ack_rtt);
+            }
+        }
+
+        if let Some(SerializablePacket::WireGuardPacket(wireguard)) =
+            packet.get_application_layer_packet()
+        {
+            let key = conversation_key(protocol, source, destination);
+            if let Some(tracked) = self.conversations.get_mut(&key) {
+                tracked
+                    .conversation
+                    .wireguard
+                    .get_or_insert_with(WireGuardSession::default)
+                    .update(wireguard, time.timestamp_millis());
+            }
+        }
+    }
+
+    /// Append the bytes of the TCP stream handed over with a packet to its conversation, once the
+    /// conversation is updated with the packet
+    pub fn append_stream(&mut self, packet: &ParsedPacket, payload: FrameBytes) {
+        let (source, destination) =
+            match (ip_addresses(packet), packet.get_transport_layer_packet()) {
+                (Some((source, destination)), Some(SerializablePacket::TcpPacket(tcp))) => (
+                    (source, Some(tcp.source)),
+                    (destination, Some(tcp.destination)),
+                ),
+                _ => return,
+            };
+
+        let key = conversation_key("TCP", source, destination);
+        if let Some(tracked) = self.conversations.get_mut(&key) {
+            let conversation = &tracked.conversation;
+            let direction = if (conversation.initiator, conversation.initiator_port) == source {
+                StreamDirection::ClientToServer
+            } else {
+                StreamDirection::ServerToClient
+            };
+            let missing = packet.get_reassembly_gap().map(|gap| gap.length);
+            tracked
+                .stream
+                .append(direction, packet.get_id(), missing, payload);
+        }
+    }
+
+    /// Get the TCP stream of a conversation, given its id
+    pub fn follow_stream(&self, id: usize) -> Option<FollowedStream> {
+        let tracked = self.conversations.values().find(|tracked| {
+            tracked.conversation.id == id && tracked.conversation.protocol == "TCP"
+        })?;
+        let conversation = &tracked.conversation;
+
+        Some(tracked.stream.render(
+            id,
+            (conversation.initiator, conversation.initiator_port),
+            (conversation.responder, conversation.responder_port),
+        ))
+    }
+
+    /// Add a packet to the conversation between two endpoints, creating it if needed
+    ///
+    /// A UDP conversation idle for longer than the UDP flow timeout is ended first.
+    fn track(
+        &mut self,
+        protocol: &str,
+        source: (IpAddr, Option<u16>),
+        destination: (IpAddr, Option<u16>),
+        details: TransportDetails,
+        bytes: usize,
+        time: i64,
+    ) {
+        let key = conversation_key(protocol, source, destination);
+
+        if let TransportDetails::Udp(_) = details {
+            let timeout = get_flow_timeouts().udp as i64 * 1000;
+            let idle = self
+                .conversations
+                .get(&key)
+                .is_some_and(|tracked| time - tracked.conversation.last_seen > timeout);
+            if idle {
+                let tracked = self.conversations.remove(&key).unwrap();
+                self.ended.push(tracked.conversation);
+            }
+        }
+
+        // The client is the initiator, even when the first datagram seen is a response
+        let (initiator, responder) = match details {
+            TransportDetails::Udp(Some(UdpMessage {
+                request: Some(false),
+                ..
+            })) => (destination, source),
+            _ => (source, destination),
+        };
+
+        let id = self.next_id;
+        if !self.conversations.contains_key(&key) {
+            self.next_id += 1;
+        }
+
+        let tracked = self
+            .conversations
+            .entry(key)
+            .or_insert_with(|| TrackedConversation {
+                conversation: Conversation {
+                    id,
+                    protocol: protocol.to_owned(),
+                    initiator: initiator.0,
+                    initiator_port: initiator.1,
+                    responder: responder.0,
+                    responder_port: responder.1,
+                    sent: Counters::default(),
+                    received: Counters::default(),
+                    first_seen: time,
+                    last_seen: time,
+                    duration: 0,
+                    tcp_state: None,
+                    udp_exchanges: None,
+                    tls_destination: None,
+                    wireguard: None,
+                    round_trip_times: None,
+                    initiator_location: None,
+                    responder_location: None,
+                    initiator_name: None,
+                    responder_name: None,
+                },
+                initiator_fin: false,
+                responder_fin: false,
+                pending: VecDeque::new(),
+                total_response_time: 0,
+                tls_names: TlsNames::default(),
+                stream: TcpStreamData::default(),
+            });
+
+        let conversation = &mut tracked.conversation;
+        let from_initiator = (conversation.initiator, conversation.initiator_port) == source;
+        if from_initiator {
+            conversation.sent.add(bytes);
+        } else {
+            conversation.received.add(bytes);
+        }
+        conversation.first_seen = conversation.first_seen.min(time);
+        conversation.last_seen = conversation.last_seen.max(time);
+        conversation.duration = conversation.last_seen - conversation.first_seen;
+
+        match details {
+            TransportDetails::Tcp(flags) => tracked.update_tcp_state(flags, from_initiator),
+            TransportDetails::Udp(Some(message)) => {
+                tracked.update_udp_exchanges(message, from_initiator, time)
+            }
+            _ => (),
+        }
+    }
+
+    /// Get the conversations of a protocol involving an address and a port, sorted by a field
+    pub fn conversations(
+        &self,
+        order: ConversationOrder,
+        descending: bool,
+        protocol: Option<&str>,
+        address: Option<IpAddr>,
+        port: Option<u16>,
+    ) -> Vec<Conversation> {
+        let mut conversations: Vec<Conversation> = self
+            .conversations
+            .values()
+            .map(|tracked| &tracked.conversation)
+            .chain(self.ended.iter())
+            .filter(|conversation| {
+                let same_protocol = match protocol {
+                    Some(protocol) => conversation.protocol.eq_ignore_ascii_case(protocol),
+                    None => true,
+                };
+                same_protocol && conversation.involves(address, port)
+            })
+            .cloned()
+            .collect();
+
+        // Conversations with the same value keep the order in which they started
+        conversations.sort_by(|a, b| {
+            let ordering = a.compare(b, order);
+            let ordering = if descending {
+                ordering.reverse()
+            } else {
+                ordering
+            };
+            ordering.then_with(|| a.first_seen.cmp(&b.first_seen))
+        });
+
+        conversations
+    }
+
+    pub fn clear(&mut self) {
+        self.conversations.clear();
+        self.ended.clear();
+        self.dns_names.clear();
+        self.next_id = 0;
+    }
+}
+
+/// Get the source and destination addresses of a packet, if it has an IP layer
+fn ip_addresses(packet: &ParsedPacket) -> Option<(IpAddr, IpAddr)> {
+    match packet.get_network_layer_packet() {
+        Some(SerializablePacket::Ipv4Packet(ipv4)) => {
+            Some((IpAddr::V4(ipv4.source), IpAddr::V4(ipv4.destination)))
+        }
+        Some(SerializablePacket::Ipv6Packet(ipv6)) => {
+            Some((IpAddr::V6(ipv6.source), IpAddr::V6(ipv6.destination)))
+        }
+        _ => None,
+    }
+}
+
+/// Get the key of the conversation between two endpoints
+fn conversation_key(
+    protocol: &str,
+    source: (IpAddr, Option<u16>),
+    destination: (IpAddr, Option<u16>),
+) -> ConversationKey {
+    // Without ports, all the packets of a protocol exchanged by two hosts share a conversation
+    let endpoint = |(address, port): (IpAddr, Option<u16>)| (address, port.unwrap_or(0));
+
+    (
+        protocol.to_owned(),
+        FlowKey::new(endpoint(source), endpoint(destination)),
+    )
+}
+
+/// Get the request or response carried by a UDP datagram, if its application is paired
+fn udp_message(packet: &ParsedPacket, (source, destination): (u16, u16)) -> Option<UdpMessage> {
+    // Sent to the server port by a client on another port
+    let to_server = |port: u16| match (source == port, destination == port) {
+        (false, true) => Some(true),
+        (true, false) => Some(false),
+        _ => None,
+    };
+
+    match packet.get_application_layer_packet() {
+        Some(SerializablePacket::DnsPacket(dns)) => Some(UdpMessage {
+            application: "DNS",
+            request: Some(dns.header.query),
+            id: Some(dns.header.id),
+        }),
+        Some(SerializablePacket::QuicPacket(quic))
+            if quic
+                .packets
+                .iter()
+                .any(|quic_packet| quic_packet.packet_type == "Initial") =>
+        {
+            Some(UdpMessage {
+                application: "QUIC",
+                request: to_server(QUIC_PORT),
+                id: None,
+            })
+        }
+        _ if source == NTP_PORT || destination == NTP_PORT => Some(UdpMessage {
+            application: "NTP",
+            request: to_server(NTP_PORT),
+            id: None,
+        }),
+        _ => None,
+    }
+}
+
+/// Get the conversations of the collected packets sorted by a field, optionally keeping only the
+/// ones of a protocol, or involving an address or a port
+#[tauri::command]
+pub fn get_conversations(
+    order: ConversationOrder,
+    descending: bool,
+    protocol: Option<String>,
+    address: Option<IpAddr>,
+    port: Option<u16>,
+    state: tauri::State<SniffingState>,
+) -> Vec<Conversation> {
+    let mut conversations = state.packets.lock().unwrap().conversations.conversations(
+        order,
+        descending,
+        protocol.as_deref(),
+        address,
+        port,
+    );
+    state
+        .geoip
+        .lock()
+        .unwrap()
+        .locate_conversations(&mut conversations);
+    state
+        .reverse_dns
+        .lock()
+        .unwrap()
+        .name_conversations(&mut conversations);
+
+    conversations
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr};
+
+    use chrono::{Local, TimeZone};
+    use pnet::packet::ethernet::EthernetPacket;
+    use pnet::packet::tcp::TcpFlags;
+    use sniffer_parser::parse_ethernet_frame;
+    use sniffer_parser::serializable_packet::application::SerializableWireGuardPacket;
+
+    use super::{
+        ConnectionTracker, ConversationOrder, RoundTripTimes, TcpState, TransportDetails,
+        UdpExchanges, UdpMessage, WireGuardSession,
+    };
+    use crate::statistics::Counters;
+
+    const TCP_FRAME: &[u8] = &[
+        0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x08, 0x00, 0x45,
+        0x00, 0x00, 0x2b, 0x00, 0x00, 0x00, 0x00, 0x40, 0x06, 0x00, 0x00, 0x0a, 0x0a, 0x0a, 0x0a,
+        0x0b, 0x0b, 0x0b, 0x0b, 0x11, 0x5c, 0x01, 0xbb, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x50, 0x18, 0xff, 0xff, 0x00, 0x00, 0x00, 0x00, 0x47, 0x45, 0x54,
+    ];
+
+    #[test]
+    fn tcp_connection_lifecycle() {
+        let mut tracker = ConnectionTracker::new();
+        let (client, server) = (endpoint(1, 50000), endpoint(2, 80));
+        let mut segment = |flags: u16, from_client: bool, time: i64| {
+            let (source, destination) = if from_client {
+                (client, server)
+            } else {
+                (server, client)
+            };
+            tracker.track(
+                "TCP",
+                source,
+                destination,
+                TransportDetails::Tcp(flags),
+                60,
+                time,
+            );
+            tracker.conversations(ConversationOrder::Packets, false, None, None, None)[0].tcp_state
+        };
+
+        assert_eq!(segment(TcpFlags::SYN, true, 0), Some(TcpState::SynSent));
+        assert_eq!(
+            segment(TcpFlags::SYN | TcpFlags::ACK, false, 10),
+            Some(TcpState::SynReceived)
+        );
+        assert_eq!(
+            segment(TcpFlags::ACK, true, 20),
+            Some(TcpState::Established)
+        );
+        assert_eq!(
+            segment(TcpFlags::PSH | TcpFlags::ACK, false, 30),
+            Some(TcpState::Established)
+        );
+        assert_eq!(
+            segment(TcpFlags::FIN | TcpFlags::ACK, true, 40),
+            Some(TcpState::Closing)
+        );
+        assert_eq!(
+            segment(TcpFlags::FIN | TcpFlags::ACK, false, 50),
+            Some(TcpState::Closed)
+        );
+        assert_eq!(segment(TcpFlags::SYN, true, 60), Some(TcpState::SynSent));
+        assert_eq!(segment(TcpFlags::RST, false, 70), Some(TcpState::Reset));
+
+        let conversation =
+            &tracker.conversations(ConversationOrder::Packets, false, None, None, None)[0];
+        assert_eq!(conversation.initiator, host(1));
+        assert_eq!(conversation.responder_port, Some(80));
+        assert_eq!(
+            conversation.sent,
+            Counters {
+                packets: 4,
+                bytes: 240
+            }
+        );
+        assert_eq!(
+            conversation.received,
+            Counters {
+                packets: 4,
+                bytes: 240
+            }
+        );
+        assert_eq!(conversation.duration, 70);
+    }
+
+    #[test]
+    fn sorted_and_filtered_conversations() {
+        let mut tracker = ConnectionTracker::new();
+        tracker.track("UDP", endpoint(1, 5353), endpoint(2, 53), udp(None), 80, 0);
+        tracker.track(
+            "UDP",
+            endpoint(2, 53),
+            endpoint(1, 5353),
+            udp(None),
+            120,
+            500,
+        );
+        tracker.track(
+            "TCP",
+            endpoint(3, 40000),
+            endpoint(2, 443),
+            TransportDetails::Tcp(TcpFlags::SYN),
+            60,
+            100,
+        );
+        tracker.track(
+            "ICMP",
+            (host(1), None),
+            (host(4), None),
+            TransportDetails::Other,
+            1000,
+            200,
+        );
+
+        let by_bytes = tracker.conversations(ConversationOrder::Bytes, true, None, None, None);
+        let protocols: Vec<&str> = by_bytes.iter().map(|c| c.protocol.as_str()).collect();
+        assert_eq!(protocols, vec!["ICMP", "UDP", "TCP"]);
+
+        let by_duration =
+            tracker.conversations(ConversationOrder::Duration, true, None, None, None);
+        assert_eq!(by_duration[0].protocol, "UDP");
+        assert_eq!(by_duration[0].duration, 500);
+
+        let udp =
+            tracker.conversations(ConversationOrder::FirstSeen, false, Some("udp"), None, None);
+        assert_eq!(udp.len(), 1);
+        assert_eq!(udp[0].initiator_port, Some(5353));
+
+        let host = tracker.conversations(
+            ConversationOrder::FirstSeen,
+            false,
+            None,
+            Some(host(2)),
+            None,
+        );
+        assert_eq!(host.len(), 2);
+
+        let https =
+            tracker.conversations(ConversationOrder::FirstSeen, false, None, None, Some(443));
+        assert_eq!(https.len(), 1);
+        assert_eq!(https[0].tcp_state, Some(TcpState::SynSent));
+    }
+
+    #[test]
+    fn udp_conversations_expire_when_idle() {
+        let mut tracker = ConnectionTracker::new();
+        let (client, server) = (endpoint(1, 40000), endpoint(2, 9000));
+
+        tracker.track("UDP", client, server, udp(None), 100, 0);
+        tracker.track("UDP", server, client, udp(None), 100, 1000);
+        // Idle for longer than the default UDP timeout of 60 seconds
+        tracker.track("UDP", server, client, udp(None), 100, 62_000);
+        tracker.track("UDP", client, server, udp(None), 100, 63_000);
+
+        let conversations =
+            tracker.conversations(ConversationOrder::FirstSeen, false, None, None, None);
+        assert_eq!(conversations.len(), 2);
+        assert_eq!(conversations[0].duration, 1000);
+        assert_eq!(conversations[0].initiator_port, Some(40000));
+        assert_eq!(conversations[1].first_seen, 62_000);
+        assert_eq!(conversations[1].duration, 1000);
+        assert_eq!(conversations[1].initiator_port, Some(9000));
+        assert_eq!(conversations[1].received.packets, 1);
+
+        tracker.clear();
+        assert!(tracker
+            .conversations(ConversationOrder::FirstSeen, false, None, None, None)
+            .is_empty());
+    }
+
+    #[test]
+    fn udp_requests_paired_with_responses() {
+        let mut tracker = ConnectionTracker::new();
+        let (client, resolver) = (endpoint(1, 50000), endpoint(2, 53));
+        let dns = |id: u16, query: bool| {
+            udp(Some(UdpMessage {
+                application: "DNS",
+                request: Some(query),
+                id: Some(id),
+            }))
+        };
+
+        // The response to a query sent before the capture started comes first
+        tracker.track("UDP", resolver, client, dns(1, false), 100, 0);
+        tracker.track("UDP", client, resolver, dns(2, true), 60, 100);
+        tracker.track("UDP", client, resolver, dns(3, true), 60, 110);
+        tracker.track("UDP", resolver, client, dns(3, false), 100, 130);
+        tracker.track("UDP", resolver, client, dns(2, false), 100, 150);
+        tracker.track("UDP", client, resolver, dns(4, true), 60, 200);
+
+        let conversation =
+            &tracker.conversations(ConversationOrder::FirstSeen, false, None, None, None)[0];
+        assert_eq!(conversation.initiator_port, Some(50000));
+        assert_eq!(conversation.responder_port, Some(53));
+        assert_eq!(
+            conversation.udp_exchanges,
+            Some(UdpExchanges {
+                application: "DNS".to_owned(),
+                requests: 3,
+                responses: 2,
+                response_time: Some(35),
+            })
+        );
+
+        // NTP between two servers, where only the direction tells the requests
+        let ntp = udp(Some(UdpMessage {
+            application: "NTP",
+            request: None,
+            id: None,
+        }));
+        let (peer, other_peer) = (endpoint(3, 123), endpoint(4, 123));
+        tracker.track("UDP", peer, other_peer, ntp, 90, 0);
+        tracker.track("UDP", other_peer, peer, ntp, 90, 40);
+        tracker.track("UDP", other_peer, peer, ntp, 90, 50);
+
+        let ntp = tracker.conversations(ConversationOrder::FirstSeen, false, None, None, Some(123));
+        let exchanges = ntp[0].udp_exchanges.as_ref().unwrap();
+        assert_eq!((exchanges.requests, exchanges.responses), (1, 1));
+        assert_eq!(exchanges.response_time, Some(40));
+    }
+
+    #[test]
+    fn conversation_of_packet() {
+        let mut tracker = ConnectionTracker::new();
+        let mut packet = parse_ethernet_frame(&EthernetPacket::new(TCP_FRAME).unwrap(), 0);
+        let time = Local.timestamp_opt(1_700_000_000, 0).unwrap();
+        tracker.update(&packet, TCP_FRAME.len(), time);
+        let payload = packet.take_stream_payload();
+        tracker.append_stream(&packet, payload);
+
+        let conversations =
+            tracker.conversations(ConversationOrder::Packets, false, None, None, None);
+        assert_eq!(conversations.len(), 1);
+        assert_eq!(conversations[0].protocol, "TCP");
+        assert_eq!(
+            conversations[0].initiator,
+            IpAddr::V4(Ipv4Addr::new(10, 10, 10, 10))
+        );
+        assert_eq!(conversations[0].initiator_port, Some(4444));
+        assert_eq!(conversations[0].responder_port, Some(443));
+        assert_eq!(conversations[0].first_seen, 1_700_000_000_000);
+        // The connection was already open
+        assert_eq!(conversations[0].tcp_state, Some(TcpState::Established));
+
+        let stream = tracker.follow_stream(conversations[0].id).unwrap();
+        assert_eq!(stream.client_to_server.printable, "GET");
+        assert_eq!(stream.chunks[0].packet_id, 0);
+        assert!(tracker.follow_stream(conversations[0].id + 1).is_none());
+    }
+
+    #[test]
+    fn wireguard_session() {
+        let mut session = WireGuardSession::default();
+        let message = |message_type: &str, sender, receiver, length| SerializableWireGuardPacket {
+            message_type: message_type.to_owned(),
+            sender_index: sender,
+            receiver_index: receiver,
+            counter: None,
+            length,
+        };
+
+        session.update(&message("Handshake Initiation", Some(1), None, 0), 0);
+        session.update(&message("Handshake Initiation", Some(2), None, 0), 5000);
+        session.update(&message("Handshake Response", Some(9), Some(2), 0), 5010);
+        session.update(&message("Transport Data", None, Some(9), 0), 5020);
+        session.update(&message("Transport Data", None, Some(2), 96), 5030);
+        session.update(&message("Transport Data", None, Some(9), 1424), 5040);
+
+        assert_eq!(
+            session,
+            WireGuardSession {
+                handshake_initiations: 2,
+                handshakes: 1,
+                cookie_replies: 0,
+                last_handshake: Some(5010),
+                initiator_index: Some(2),
+                responder_index: Some(9),
+                data_messages: 2,
+                data_bytes: 1520,
+                keepalives: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn round_trip_times() {
+        let mut round_trip_times = RoundTripTimes::default();
+
+        round_trip_times.update(Some(12_500), None);
+        round_trip_times.update(None, Some(4_000));
+        round_trip_times.update(None, Some(8_000));
+        round_trip_times.update(None, Some(3_000));
+
+        assert_eq!(round_trip_times.handshake, Some(12.5));
+        assert_eq!(round_trip_times.samples, 3);
+        assert_eq!(round_trip_times.min, Some(3.0));
+        assert_eq!(round_trip_times.average, Some(5.0));
+        assert_eq!(round_trip_times.max, Some(8.0));
+    }
+
+    ///////////////////// Utils
+
+    fn host(host: u8) -> IpAddr {
+        IpAddr::V4(Ipv4Addr::new(10, 0, 0, host))
+    }
+
+    fn endpoint(host_id: u8, port: u16) -> (IpAddr, Option<u16>) {
+        (host(host_id), Some(port))
+    }
+
+    fn udp(message: Option<UdpMessage>) -> TransportDetails {
+        TransportDetails::Udp(message)
+    }
+}
@@ -0,0 +1,841 @@
+//! Display filters over the parsed packets
+//!
+//! A display filter is an expression in a Wireshark-like language, evaluated against the fields
+//! of the layers of each packet:
+//! - `tcp`, `dns`, `http`: the packet contains the protocol
+//! - `http.request.method == "GET"`, `tcp.port >= 8000`: comparisons between a field and a value
+//!   (`==`, `!=`, `>`, `<`, `>=`, `<=`, `contains`, or their `eq`, `ne`, ... counterparts)
+//! - `ip.src == 192.168.1.0/24`: addresses can be compared with networks in CIDR notation
+//! - `&&`/`and`, `||`/`or`, `!`/`not` and parentheses combine the expressions
+//!
+//! Fields are named `<protocol>.<field>`, where the field is the one of the protocol
+//! representation (nested fields are separated by dots too). The most common Wireshark field
+//! names are supported as well, e.g. `ip.addr`, `tcp.dstport` and `http.response.code`.
+//! A comparison holds when any of the values of the field satisfies it, except for `!=`, which
+//! requires all of them to be different.
+
+use std::net::IpAddr;
+
+use serde_json::Value;
+use sniffer_parser::serializable_packet::ParsedPacket;
+use tracing::info;
+
+use crate::{SniffingError, SniffingState};
+
+/// Protocol names, with the type of the layers representing them
+const PROTOCOLS: &[(&str, &[&str])] = &[
+    ("eth", &["EthernetPacket"]),
+    ("ppp", &["PppPacket"]),
+    ("slip", &["SlipPacket"]),
+    ("sll", &["LinuxSllPacket"]),
+    ("null", &["LoopbackPacket"]),
+    ("raw", &["RawPacket"]),
+    ("wlan", &["Ieee80211Packet"]),
+    ("arp", &["ArpPacket"]),
+    ("ip", &["Ipv4Packet"]),
+    ("ipv6", &["Ipv6Packet"]),
+    (
+        "icmp",
+        &[
+            "IcmpPacket",
+            "EchoReplyPacket",
+            "EchoRequestPacket",
+            "IcmpTimestampPacket",
+            "IcmpAddressMaskPacket",
+            "RouterAdvertisementPacket",
+        ],
+    ),
+    ("icmpv6", &["Icmpv6Packet"]),
+    ("tcp", &["TcpPacket"]),
+    ("udp", &["UdpPacket"]),
+    ("esp", &["EspPacket"]),
+    ("ah", &["AhPacket"]),
+    ("http", &["HttpRequestPacket", "HttpResponsePacket"]),
+    ("http2", &["Http2Packet"]),
+    ("tls", &["TlsPacket"]),
+    ("quic", &["QuicPacket"]),
+    ("dns", &["DnsPacket"]),
+    ("dhcp", &["DhcpPacket"]),
+    ("snmp", &["SnmpPacket"]),
+    ("gtp", &["GtpPacket"]),
+    ("isakmp", &["IkePacket"]),
+    ("wg", &["WireGuardPacket"]),
+    ("ntp", &["NtpPacket"]),
+    ("ptp", &["PtpPacket"]),
+    ("goose", &["GoosePacket"]),
+    ("sv", &["SvPacket"]),
+    ("pn_rt", &["ProfinetPacket"]),
+    ("ecat", &["EthercatPacket"]),
+    ("s7comm", &["S7commPacket"]),
+    ("iscsi", &["IscsiPacket"]),
+    ("nvme_tcp", &["NvmeTcpPacket"]),
+    ("cql", &["CqlPacket"]),
+    ("kafka", &["KafkaPacket"]),
+    ("zookeeper", &["ZookeeperPacket"]),
+    ("custom", &["CustomPacket"]),
+    ("partial", &["PartiallyReassembled"]),
+    ("malformed", &["MalformedPacket"]),
+];
+
+/// Protocols of the link, network and transport layers, dissected without the application ones
+const TRANSPORT_PROTOCOLS: &[&str] = &[
+    "eth", "ppp", "slip", "sll", "null", "raw", "wlan", "arp", "ip", "ipv6", "icmp", "icmpv6",
+    "tcp", "udp",
+];
+
+/// Layer types containing a field, with the path of the field inside them
+type Place = (&'static [&'static str], &'static str);
+
+/// Wireshark field names, with the places of the fields they refer to
+const ALIASES: &[(&str, &[Place])] = &[
+    ("eth.src", &[(&["EthernetPacket"], "source")]),
+    ("eth.dst", &[(&["EthernetPacket"], "destination")]),
+    (
+        "eth.addr",
+        &[
+            (&["EthernetPacket"], "source"),
+            (&["EthernetPacket"], "destination"),
+        ],
+    ),
+    ("ip.src", &[(&["Ipv4Packet"], "source")]),
+    ("ip.dst", &[(&["Ipv4Packet"], "destination")]),
+    (
+        "ip.addr",
+        &[
+            (&["Ipv4Packet"], "source"),
+            (&["Ipv4Packet"], "destination"),
+        ],
+    ),
+    ("ip.proto", &[(&["Ipv4Packet"], "next_level_protocol")]),
+    ("ipv6.src", &[(&["Ipv6Packet"], "source")]),
+    ("ipv6.dst", &[(&["Ipv6Packet"], "destination")]),
+    (
+        "ipv6.addr",
+        &[
+            (&["Ipv6Packet"], "source"),
+            (&["Ipv6Packet"], "destination"),
+        ],
+    ),
+    ("tcp.srcport", &[(&["TcpPacket"], "source")]),
+    ("tcp.dstport", &[(&["TcpPacket"], "destination")]),
+    (
+        "tcp.port",
+        &[(&["TcpPacket"], "source"), (&["TcpPacket"], "destination")],
+    ),
+    ("udp.srcport", &[(&["UdpPacket"], "source")]),
+    ("udp.dstport", &[(&["UdpPacket"], "destination")]),
+    (
+        "udp.port",
+        &[(&["UdpPacket"], "source"), (&["UdpPacket"], "destination")],
+    ),
+    ("http.request", &[(&["HttpRequestPacket"], "")]),
+    ("http.request.method", &[(&["HttpRequestPacket"], "method")]),
+    ("http.request.uri", &[(&["HttpRequestPacket"], "path")]),
+    ("http.response", &[(&["HttpResponsePacket"], "")]),
+    ("http.response.code", &[(&["HttpResponsePacket"], "code")]),
+    (
+        "tls.handshake.extensions_server_name",
+        &[
+            (&["TlsPacket"], "handshake.server_name"),
+            (&["QuicPacket"], "handshake.server_name"),
+        ],
+    ),
+    (
+        "tls.handshake.ciphersuite",
+        &[
+            (&["TlsPacket"], "handshake.cipher_suites"),
+            (&["TlsPacket"], "handshake.selected_cipher_suite"),
+            (&["QuicPacket"], "handshake.cipher_suites"),
+            (&["QuicPacket"], "handshake.selected_cipher_suite"),
+        ],
+    ),
+    (
+        "tls.handshake.extensions_alpn_str",
+        &[
+            (&["TlsPacket"], "handshake.alpn_protocols"),
+            (&["QuicPacket"], "handshake.alpn_protocols"),
+        ],
+    ),
+];
+
+/// A compiled display filter
+#[derive(Debug)]
+pub struct DisplayFilter {
+    text: String,
+    expression: Expression,
+}
+
+impl DisplayFilter {
+    /// Compile the text of a display filter
+    pub fn compile(text: &str) -> Result<Self, String> {
+        let tokens = tokenize(text)?;
+        let mut parser = Parser { tokens, next: 0 };
+
+        let expression = parser.or()?;
+        if let Some(token) = parser.peek() {
+            return Err(format!("Unexpected {:?}", token));
+        }
+
+        Ok(DisplayFilter {
+            text: text.to_owned(),
+            expression,
+        })
+    }
+
+    /// Get the text the filter was compiled from
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Check whether the filter depends on the application layers of the packets
+    pub fn needs_applications(&self) -> bool {
+        self.expression.needs_applications()
+    }
+
+    /// Check whether a packet satisfies the filter
+    pub fn matches(&self, packet: &ParsedPacket) -> bool {
+        let layers: Vec<Value> = [
+            packet.get_link_layer_packet(),
+            packet.get_network_layer_packet(),
+            packet.get_transport_layer_packet(),
+            packet.get_application_layer_packet(),
+        ]
+        .into_iter()
+        .flatten()
+        .filter_map(|layer| serde_json::to_value(layer).ok())
+        .collect();
+
+        self.expression.evaluate(&layers)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    LeftParen,
+    RightParen,
+    And,
+    Or,
+    Not,
+    Operator(Operator),
+    Quoted(String),
+    Word(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Operator {
+    Equal,
+    NotEqual,
+    Greater,
+    Less,
+    GreaterEqual,
+    LessEqual,
+    Contains,
+}
+
+#[derive(Debug)]
+enum Expression {
+    Or(Box<Expression>, Box<Expression>),
+    And(Box<Expression>, Box<Expression>),
+    Not(Box<Expression>),
+    Exists(Field),
+    Compare(Field, Operator, Literal),
+}
+
+/// Places of a field in the layers of a packet
+#[derive(Debug)]
+struct Field(Vec<(&'static [&'static str], Vec<String>)>);
+
+#[derive(Debug)]
+enum Literal {
+    Number(f64),
+    Address(IpAddr),
+    Network(IpAddr, u8),
+    Text(String),
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = vec![];
+    let mut chars = text.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        let token = match c {
+            _ if c.is_whitespace() => {
+                chars.next();
+                continue;
+            }
+            '(' => Token::LeftParen,
+            ')' => Token::RightParen,
+            '&' | '|' | '=' | '!' | '<' | '>' => {
+                chars.next();
+                let followed_by = |chars: &mut std::iter::Peekable<std::str::Chars>, next| {
+                    chars.next_if_eq(&next).is_some()
+                };
+
+                tokens.push(match c {
+                    '&' if followed_by(&mut chars, '&') => Token::And,
+                    '|' if followed_by(&mut chars, '|') => Token::Or,
+                    '=' if followed_by(&mut chars, '=') => Token::Operator(Operator::Equal),
+                    '!' if followed_by(&mut chars, '=') => Token::Operator(Operator::NotEqual),
+                    '!' => Token::Not,
+                    '<' if followed_by(&mut chars, '=') => Token::Operator(Operator::LessEqual),
+                    '<' => Token::Operator(Operator::Less),
+                    '>' if followed_by(&mut chars, '=') => Token::Operator(Operator::GreaterEqual),
+                    '>' => Token::Operator(Operator::Greater),
+                    _ => return Err(format!("Unexpected character '{}'", c)),
+                });
+                continue;
+            }
+            '"' => {
+                chars.next();
+                let mut quoted = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(escaped) => quoted.push(escaped),
+                            None => return Err("Unterminated string".to_owned()),
+                        },
+                        Some(c) => quoted.push(c),
+                        None => return Err("Unterminated string".to_owned()),
+                    }
+                }
+                tokens.push(Token::Quoted(quoted));
+                continue;
+            }
+            _ => {
+                let mut word = String::new();
+                while let Some(c) =
+                    chars.next_if(|&c| !c.is_whitespace() && !"()&|=!<>\"".contains(c))
+                {
+                    word.push(c);
+                }
+
+                tokens.push(match word.as_str() {
+                    "and" => Token::And,
+                    "or" => Token::Or,
+                    "not" => Token::Not,
+                    "eq" => Token::Operator(Operator::Equal),
+                    "ne" => Token::Operator(Operator::NotEqual),
+                    "gt" => Token::Operator(Operator::Greater),
+                    "lt" => Token::Operator(Operator::Less),
+                    "ge" => Token::Operator(Operator::GreaterEqual),
+                    "le" => Token::Operator(Operator::LessEqual),
+                    "contains" => Token::Operator(Operator::Contains),
+                    _ => Token::Word(word),
+                });
+                continue;
+            }
+        };
+
+        chars.next();
+        tokens.push(token);
+    }
+
+    Ok(tokens)
+}
+
+/// Recursive descent parser of the filter expressions, in ascending order of precedence
+struct Parser {
+    tokens: Vec<Token>,
+    next: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.next)
+    }
+
+    fn advance(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.next).cloned();
+        self.next += 1;
+        token
+    }
+
+    fn or(&mut self) -> Result<Expression, String> {
+        let mut expression = self.and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.advance();
+            expression = Expression::Or(Box::new(expression), Box::new(self.and()?));
+        }
+
+        Ok(expression)
+    }
+
+    fn and(&mut self) -> Result<Expression, String> {
+        let mut expression = self.not()?;
+        while self.peek() == Some(&Token::And) {
+            self.advance();
+            expression = Expression::And(Box::new(expression), Box::new(self.not()?));
+        }
+
+        Ok(expression)
+    }
+
+    fn not(&mut self) -> Result<Expression, String> {
+        if self.peek() == Some(&Token::Not) {
+            self.advance();
+            return Ok(Expression::Not(Box::new(self.not()?)));
+        }
+
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Expression, String> {
+        match self.advance() {
+            Some(Token::LeftParen) => {
+                let expression = self.or()?;
+                match self.advance() {
+                    Some(Token::RightParen) => Ok(expression),
+                    _ => Err("Missing closing parenthesis".to_owned()),
+                }
+            }
+            Some(Token::Word(name)) => {
+                let field = resolve_field(&name)?;
+
+                let operator = match self.peek() {
+                    Some(Token::Operator(operator)) => *operator,
+                    _ => return Ok(Expression::Exists(field)),
+                };
+                self.advance();
+
+                match self.advance() {
+                    Some(Token::Word(value)) => {
+                        Ok(Expression::Compare(field, operator, parse_literal(&value)))
+                    }
+                    Some(Token::Quoted(value)) => {
+                        Ok(Expression::Compare(field, operator, Literal::Text(value)))
+                    }
+                    _ => Err(format!("Missing value to compare {} with", name)),
+                }
+            }
+            Some(token) => Err(format!("Unexpected {:?}", token)),
+            None => Err("Unexpected end of filter".to_owned()),
+        }
+    }
+}
+
+/// Find the places of a field, given its name
+fn resolve_field(name: &str) -> Result<Field, String> {
+    let split = |path: &str| -> Vec<String> {
+        path.split('.')
+            .filter(|component| !component.is_empty())
+            .map(str::to_owned)
+            .collect()
+    };
+
+    if let Some((_, places)) = ALIASES.iter().find(|(alias, _)| *alias == name) {
+        return Ok(Field(
+            places
+                .iter()
+                .map(|(types, path)| (*types, split(path)))
+                .collect(),
+        ));
+    }
+
+    let (protocol, path) = name.split_once('.').unwrap_or((name, ""));
+    match PROTOCOLS.iter().find(|(known, _)| *known == protocol) {
+        Some((_, types)) => Ok(Field(vec![(*types, split(path))])),
+        None => Err(format!("Unknown field {}", name)),
+    }
+}
+
+fn parse_literal(value: &str) -> Literal {
+    if let Some((address, prefix)) = value.split_once('/') {
+        if let (Ok(address), Ok(prefix)) = (address.parse::<IpAddr>(), prefix.parse::<u8>()) {
+            let max_prefix = if address.is_ipv4() { 32 } else { 128 };
+            if prefix <= max_prefix {
+                return Literal::Network(address, prefix);
+            }
+        }
+    }
+
+    if let Ok(address) = value.parse::<IpAddr>() {
+        return Literal::Address(address);
+    }
+
+    let number = match value.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16).ok().map(|n| n as f64),
+        None => value.parse::<f64>().ok(),
+    };
+
+    match number {
+        Some(number) => Literal::Number(number),
+        None => Literal::Text(value.to_owned()),
+    }
+}
+
+impl Expression {
+    fn evaluate(&self, layers: &[Value]) -> bool {
+        match self {
+            Expression::Or(left, right) => left.evaluate(layers) || right.evaluate(layers),
+            Expression::And(left, right) => left.evaluate(layers) && right.evaluate(layers),
+            Expression::Not(expression) => !expression.evaluate(layers),
+            Expression::Exists(field) => field.values(layers).iter().any(|value| !value.is_null()),
+            Expression::Compare(field, Operator::NotEqual, literal) => field
+                .values(layers)
+                .iter()
+                .all(|value| !compare(value, Operator::Equal, literal)),
+            Expression::Compare(field, operator, literal) => field
+                .values(layers)
+                .iter()
+                .any(|value| compare(value, *operator, literal)),
+        }
+    }
+}
+
+impl Expression {
+    fn needs_applications(&self) -> bool {
+        match self {
+            Expression::Or(left, right) | Expression::And(left, right) => {
+                left.needs_applications() || right.needs_applications()
+            }
+            Expression::Not(expression) => expression.needs_applications(),
+            Expression::Exists(field) | Expression::Compare(field, _, _) => {
+                field.needs_applications()
+            }
+        }
+    }
+}
+
+impl Field {
+    /// Check whether the field is found in layers other than the link, network and transport ones
+    fn needs_applications(&self) -> bool {
+        let is_transport = |layer_type: &&str| {
+            PROTOCOLS
+                .iter()
+                .filter(|(protocol, _)| TRANSPORT_PROTOCOLS.contains(protocol))
+                .any(|(_, types)| types.contains(layer_type))
+        };
+
+        self.0
+            .iter()
+            .flat_map(|(types, _)| types.iter())
+            .any(|layer_type| !is_transport(layer_type))
+    }
+
+    /// Get the values of the field in the layers of a packet, flattening arrays
+    fn values<'a>(&self, layers: &'a [Value]) -> Vec<&'a Value> {
+        let mut values = vec![];
+
+        for (types, path) in &self.0 {
+            let layers = layers.iter().filter(|layer| {
+                layer["type"]
+                    .as_str()
+                    .is_some_and(|layer_type| types.contains(&layer_type))
+            });
+
+            for layer in layers {
+                let value = path
+                    .iter()
+                    .try_fold(&layer["packet"], |value, component| value.get(component));
+                if let Some(value) = value {
+                    flatten(value, &mut values);
+                }
+            }
+        }
+
+        values
+    }
+}
+
+fn flatten<'a>(value: &'a Value, values: &mut Vec<&'a Value>) {
+    match value {
+        Value::Array(array) => array.iter().for_each(|value| flatten(value, values)),
+        _ => values.push(value),
+    }
+}
+
+fn compare(value: &Value, operator: Operator, literal: &Literal) -> bool {
+    use std::cmp::Ordering;
+
+    let ordering = match (value, literal) {
+        (Value::Number(number), Literal::Number(literal)) => number
+            .as_f64()
+            .and_then(|number| number.partial_cmp(literal)),
+        (Value::Bool(value), Literal::Number(literal)) => {
+            Some((*value as u8 as f64).total_cmp(literal))
+        }
+        (Value::Bool(value), Literal::Text(literal)) => match literal.as_str() {
+            "true" | "false" => Some(value.cmp(&(literal == "true"))),
+            _ => None,
+        },
+        (Value::String(value), Literal::Address(address)) => {
+            value.parse::<IpAddr>().ok().map(|value| value.cmp(address))
+        }
+        (Value::String(value), Literal::Network(network, prefix)) => {
+            let inside = value
+                .parse::<IpAddr>()
+                .is_ok_and(|address| in_network(&address, network, *prefix));
+            return match operator {
+                Operator::Equal => inside,
+                _ => false,
+            };
+        }
+        (Value::String(value), Literal::Text(literal)) => {
+            if operator == Operator::Contains {
+                return value.contains(literal.as_str());
+            }
+
+            // MAC addresses are compared regardless of the case of their digits
+            if is_mac_address(literal) {
+                Some(value.to_lowercase().cmp(&literal.to_lowercase()))
+            } else {
+                Some(value.as_str().cmp(literal.as_str()))
+            }
+        }
+        (Value::String(value), Literal::Number(literal)) => value
+            .parse::<f64>()
+            .ok()
+            .and_then(|value| value.partial_cmp(literal)),
+        _ => None,
+    };
+
+    match (ordering, operator) {
+        (Some(ordering), Operator::Equal) => ordering == Ordering::Equal,
+        (Some(ordering), Operator::NotEqual) => ordering != Ordering::Equal,
+        (Some(ordering), Operator::Greater) => ordering == Ordering::Greater,
+        (Some(ordering), Operator::Less) => ordering == Ordering::Less,
+        (Some(ordering), Operator::GreaterEqual) => ordering != Ordering::Less,
+        (Some(ordering), Operator::LessEqual) => ordering != Ordering::Greater,
+        _ => false,
+    }
+}
+
+fn in_network(address: &IpAddr, network: &IpAddr, prefix: u8) -> bool {
+    match (address, network) {
+        (IpAddr::V4(address), IpAddr::V4(network)) => {
+            let mask = u32::MAX.checked_shl(32 - prefix as u32).unwrap_or(0);
+            u32::from(*address) & mask == u32::from(*network) & mask
+        }
+        (IpAddr::V6(address), IpAddr::V6(network)) => {
+            let mask = u128::MAX.checked_shl(128 - prefix as u32).unwrap_or(0);
+            u128::from(*address) & mask == u128::from(*network) & mask
+        }
+        _ => false,
+    }
+}
+
+fn is_mac_address(text: &str) -> bool {
+    let octets: Vec<&str> = text.split(':').collect();
+    octets.len() == 6
+        && octets
+            .iter()
+            .all(|octet| octet.len() == 2 && octet.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+/// Sets the display filter applied to the packets retrieved through `get_filtered_packets`
+///
+/// An empty filter removes the current one.
+#[tauri::command]
+pub fn set_display_filter(
+    filter: String,
+    state: tauri::State<SniffingState>,
+) -> Result<(), SniffingError> {
+    let mut display_filter = state.display_filter.lock().unwrap();
+
+    if filter.trim().is_empty() {
+        display_filter.take();
+        info!("Display filter removed");
+        return Ok(());
+    }
+
+    let compiled = DisplayFilter::compile(&filter).map_err(|e| {
+        SniffingError::InvalidDisplayFilter(format!("Invalid display filter: {}", e))
+    })?;
+
+    info!("Display filter set: {}", compiled.text());
+    *display_filter = Some(compiled);
+
+    Ok(())
+}
+
+/// Returns a slice of the collected packets satisfying the display filter
+///
+/// Bounds refer to the list of the packets satisfying the filter: when no filter is set,
+/// all the collected packets are considered. When an interface is provided, only the packets
+/// received from it are considered.
+#[tauri::command]
+pub fn get_filtered_packets(
+    start: usize,
+    end: usize,
+    interface: Option<String>,
+    state: tauri::State<SniffingState>,
+) -> Result<Vec<ParsedPacket>, SniffingError> {
+    let display_filter = state.display_filter.lock().unwrap();
+
+    // Offline frames are dissected only when requested: the filter needs all of them, with the
+    // application layers of huge captures only if it depends on them
+    let mut lazy = false;
+    if let Some(offline) = state.offline.lock().unwrap().as_mut() {
+        match display_filter.as_ref() {
+            Some(display_filter) if offline.lazy_applications() => {
+                let len = offline.len();
+                offline.dissect_until(len, &state.info, &state.packets, &state.exchanged_packets);
+                if display_filter.needs_applications() {
+                    offline.parse_applications(0..len, &state.packets, &state.exchanged_packets);
+                }
+                lazy = true;
+            }
+            Some(_) => offline.dissect_all(&state.info, &state.packets, &state.exchanged_packets),
+            None => {
+                offline.dissect_until(end, &state.info, &state.packets, &state.exchanged_packets);
+                offline.parse_applications(start..end, &state.packets, &state.exchanged_packets);
+            }
+        }
+    }
+
+    let packets = state.packets.lock().unwrap();
+    let candidates = match interface.as_ref() {
+        Some(interface) => packets
+            .interface_index
+            .get(interface)
+            .map(Vec::as_slice)
+            .unwrap_or(&[]),
+        None => packets.packets.as_slice(),
+    };
+
+    let mut result: Vec<ParsedPacket> = candidates
+        .iter()
+        .filter(|packet| match display_filter.as_ref() {
+            Some(display_filter) => display_filter.matches(packet),
+            None => true,
+        })
+        .skip(start)
+        .take(end.saturating_sub(start))
+        .map(|packet| ParsedPacket::clone(packet))
+        .collect();
+    drop(packets);
+
+    if lazy {
+        if let Some(offline) = state.offline.lock().unwrap().as_mut() {
+            offline.parse_window(&mut result, &state.packets, &state.exchanged_packets);
+        }
+    }
+    state.geoip.lock().unwrap().locate_packets(&mut result);
+
+    info!(
+        "Received getFilteredPackets request ({}-{}); Len: {}, Filter: {:?}, Interface: {:?}",
+        start,
+        end,
+        result.len(),
+        display_filter.as_ref().map(DisplayFilter::text),
+        interface
+    );
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::DisplayFilter;
+
+    #[test]
+    fn protocol_presence() {
+        let layers = http_request_layers();
+
+        assert!(evaluate("tcp", &layers));
+        assert!(evaluate("http && ip", &layers));
+        assert!(!evaluate("udp", &layers));
+        assert!(evaluate("!dns", &layers));
+        assert!(evaluate("http.request and not http.response", &layers));
+    }
+
+    #[test]
+    fn field_comparisons() {
+        let layers = http_request_layers();
+
+        assert!(evaluate(r#"http.request.method == "GET""#, &layers));
+        assert!(evaluate("tcp.dstport == 80 && tcp.srcport > 1024", &layers));
+        assert!(evaluate("tcp.port eq 80", &layers));
+        assert!(!evaluate("tcp.port != 80", &layers));
+        assert!(evaluate("ip.ttl >= 64 || udp", &layers));
+        assert!(evaluate(r#"http.path contains "index""#, &layers));
+        assert!(evaluate("http.headers contains Host", &layers));
+        assert!(evaluate("eth.src == AA:BB:CC:DD:EE:FF", &layers));
+        assert!(!evaluate("tcp.flags == 0x10", &layers));
+    }
+
+    #[test]
+    fn address_comparisons() {
+        let layers = http_request_layers();
+
+        assert!(evaluate("ip.src == 192.168.1.0/24", &layers));
+        assert!(evaluate("ip.addr == 10.0.0.1", &layers));
+        assert!(!evaluate("ip.dst == 192.168.1.0/24", &layers));
+        assert!(evaluate(
+            r#"http.request.method == "GET" && ip.src == 192.168.1.0/24"#,
+            &layers
+        ));
+    }
+
+    #[test]
+    fn operators_precedence() {
+        let layers = http_request_layers();
+
+        assert!(evaluate("udp && dns || tcp", &layers));
+        assert!(!evaluate("udp && (dns || tcp)", &layers));
+        assert!(evaluate("not (udp or dns)", &layers));
+    }
+
+    #[test]
+    fn applications_needed() {
+        let needs_applications =
+            |filter: &str| DisplayFilter::compile(filter).unwrap().needs_applications();
+
+        assert!(!needs_applications(
+            "ip.src == 10.0.0.0/8 && tcp.port == 443"
+        ));
+        assert!(!needs_applications(
+            "!(udp || eth.addr == ff:ff:ff:ff:ff:ff)"
+        ));
+        assert!(needs_applications("tcp && !http.request"));
+        assert!(needs_applications("dns"));
+    }
+
+    #[test]
+    fn invalid_filters() {
+        assert!(DisplayFilter::compile("").is_err());
+        assert!(DisplayFilter::compile("foo.bar == 1").is_err());
+        assert!(DisplayFilter::compile("tcp.port ==").is_err());
+        assert!(DisplayFilter::compile("(tcp").is_err());
+        assert!(DisplayFilter::compile("tcp udp").is_err());
+        assert!(DisplayFilter::compile(r#"http.path == "index"#).is_err());
+        assert!(DisplayFilter::compile("tcp & udp").is_err());
+    }
+
+    ///////////////////// Utils
+
+    fn evaluate(filter: &str, layers: &[Value]) -> bool {
+        DisplayFilter::compile(filter)
+            .unwrap()
+            .expression
+            .evaluate(layers)
+    }
+
+    fn http_request_layers() -> Vec<Value> {
+        vec![
+            json!({"type": "EthernetPacket", "packet": {
+                "source": "aa:bb:cc:dd:ee:ff",
+                "destination": "11:22:33:44:55:66",
+                "ethertype": "Ipv4",
+            }}),
+            json!({"type": "Ipv4Packet", "packet": {
+                "source": "192.168.1.20",
+                "destination": "10.0.0.1",
+                "ttl": 64,
+            }}),
+            json!({"type": "TcpPacket", "packet": {
+                "source": 50312,
+                "destination": 80,
+                "flags": 24,
+            }}),
+            json!({"type": "HttpRequestPacket", "packet": {
+                "method": "GET",
+                "path": "/index.html",
+                "headers": [["Host", "example.com"]],
+            }}),
+        ]
+    }
+}
@@ -0,0 +1,187 @@
+//! Encryption at rest of the files produced or read by wirefish
+//!
+//! Captures frequently contain sensitive material, so they can be protected with a passphrase.
+//! The key is derived from the passphrase with Argon2id, and the content is encrypted
+//! with AES-256-GCM.
+//!
+//! Encrypted file layout:
+//! - Magic `WFENC`, format version (u8)
+//! - Argon2 salt (16 bytes)
+//! - AES-GCM nonce (12 bytes)
+//! - Ciphertext, followed by the authentication tag
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Nonce};
+use argon2::Argon2;
+use tracing::info;
+
+use crate::SniffingError;
+
+const ENCRYPTION_MAGIC: &[u8; 5] = b"WFENC";
+const ENCRYPTION_VERSION: u8 = 1;
+
+const SALT_LENGTH: usize = 16;
+const NONCE_LENGTH: usize = 12;
+const KEY_LENGTH: usize = 32;
+const HEADER_LENGTH: usize = ENCRYPTION_MAGIC.len() + 1 + SALT_LENGTH + NONCE_LENGTH;
+
+/// Check if the provided content has been encrypted by wirefish
+pub fn is_encrypted(data: &[u8]) -> bool {
+    data.len() >= HEADER_LENGTH && data.starts_with(ENCRYPTION_MAGIC)
+}
+
+/// Encrypt the provided content with a key derived from the passphrase
+pub fn encrypt(plaintext: &[u8], passphrase: &str) -> io::Result<Vec<u8>> {
+    let mut salt = [0u8; SALT_LENGTH];
+    OsRng.fill_bytes(&mut salt);
+
+    let cipher = build_cipher(passphrase, &salt)?;
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(&nonce, plaintext)
+        .map_err(|_| io::Error::new(io::ErrorKind::Other, "Encryption failed"))?;
+
+    let mut data = Vec::with_capacity(HEADER_LENGTH + ciphertext.len());
+    data.extend_from_slice(ENCRYPTION_MAGIC);
+    data.push(ENCRYPTION_VERSION);
+    data.extend_from_slice(&salt);
+    data.extend_from_slice(&nonce);
+    data.extend_from_slice(&ciphertext);
+
+    Ok(data)
+}
+
+/// Decrypt content previously encrypted with the same passphrase
+pub fn decrypt(data: &[u8], passphrase: &str) -> io::Result<Vec<u8>> {
+    if !is_encrypted(data) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Not a wirefish encrypted file",
+        ));
+    }
+
+    let version = data[ENCRYPTION_MAGIC.len()];
+    if version != ENCRYPTION_VERSION {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Unsupported encryption version: {}", version),
+        ));
+    }
+
+    let salt_start = ENCRYPTION_MAGIC.len() + 1;
+    let nonce_start = salt_start + SALT_LENGTH;
+    let cipher = build_cipher(passphrase, &data[salt_start..nonce_start])?;
+
+    cipher
+        .decrypt(
+            Nonce::from_slice(&data[nonce_start..HEADER_LENGTH]),
+            &data[HEADER_LENGTH..],
+        )
+        .map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Wrong passphrase or corrupted file",
+            )
+        })
+}
+
+/// Encrypt a file in place
+pub fn encrypt_file<P: AsRef<Path>>(path: P, passphrase: &str) -> io::Result<()> {
+    let path = path.as_ref();
+    let plaintext = fs::read(path)?;
+    if is_encrypted(&plaintext) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "File already encrypted",
+        ));
+    }
+
+    // The original file is replaced only once the encrypted copy is complete
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(".tmp");
+    fs::write(&temporary, encrypt(&plaintext, passphrase)?)?;
+    fs::rename(&temporary, path)
+}
+
+fn build_cipher(passphrase: &str, salt: &[u8]) -> io::Result<Aes256Gcm> {
+    if passphrase.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "Empty passphrase",
+        ));
+    }
+
+    let mut key = [0u8; KEY_LENGTH];
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::Other,
+                format!("Key derivation failed: {}", e),
+            )
+        })?;
+
+    Aes256Gcm::new_from_slice(&key)
+        .map_err(|_| io::Error::new(io::ErrorKind::Other, "Invalid key length"))
+}
+
+/// Encrypts a capture file produced by wirefish, so that it can be imported only with the passphrase
+#[tauri::command]
+pub fn encrypt_capture_file(path: String, passphrase: String) -> Result<(), SniffingError> {
+    encrypt_file(&path, &passphrase).map_err(|e| {
+        SniffingError::EncryptionFailed(format!("Encryption of {} failed: {}", path, e))
+    })?;
+
+    info!("[{}] File encrypted", path);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use super::{decrypt, encrypt, is_encrypted, HEADER_LENGTH};
+
+    const PASSPHRASE: &str = "correct horse battery staple";
+    const PLAINTEXT: &[u8] = b"\xd4\xc3\xb2\xa1 sensitive capture";
+
+    #[test]
+    fn encryption_round_trip() {
+        let encrypted = encrypt(PLAINTEXT, PASSPHRASE).unwrap();
+
+        assert!(is_encrypted(&encrypted));
+        assert!(!is_encrypted(PLAINTEXT));
+        assert_ne!(&encrypted[HEADER_LENGTH..], PLAINTEXT);
+        assert_eq!(decrypt(&encrypted, PASSPHRASE).unwrap(), PLAINTEXT);
+    }
+
+    #[test]
+    fn wrong_passphrase() {
+        let encrypted = encrypt(PLAINTEXT, PASSPHRASE).unwrap();
+
+        match decrypt(&encrypted, "wrong passphrase") {
+            Err(e) => assert_eq!(e.kind(), io::ErrorKind::InvalidData),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn tampered_content() {
+        let mut encrypted = encrypt(PLAINTEXT, PASSPHRASE).unwrap();
+        let last = encrypted.len() - 1;
+        encrypted[last] ^= 0xff;
+
+        assert!(decrypt(&encrypted, PASSPHRASE).is_err());
+    }
+
+    #[test]
+    fn empty_passphrase() {
+        assert!(encrypt(PLAINTEXT, "").is_err());
+    }
+}
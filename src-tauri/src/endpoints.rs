@@ -0,0 +1,333 @@
+//! Traffic of each endpoint, as in the Endpoints dialog of Wireshark
+//!
+//! An endpoint is the source or the destination of a packet at a layer:
+//! - Ethernet: a MAC address
+//! - IPv4 or IPv6: an address
+//! - TCP or UDP: an address with a port
+//!
+//! A packet is accounted to its endpoints at each of its layers: the packets and bytes sent by its
+//! source and received by its destination. Unlike the top talkers of the statistics, which only
+//! count the traffic sent, the endpoints tell the hosts receiving the most too, so that the ones
+//! saturating a link can be found whatever the direction of their traffic.
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use sniffer_parser::serializable_packet::{ParsedPacket, SerializablePacket};
+
+use crate::statistics::{Counters, Timestamp};
+use crate::SniffingState;
+
+/// Layer of an endpoint
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "camelCase")]
+pub enum EndpointKind {
+    Ethernet,
+    Ipv4,
+    Ipv6,
+    Tcp,
+    Udp,
+}
+
+/// Field the endpoints are sorted by
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum EndpointOrder {
+    Packets,
+    Bytes,
+    SentBytes,
+    ReceivedBytes,
+    FirstSeen,
+    LastSeen,
+}
+
+/// Traffic of an endpoint, as returned to the frontend
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Endpoint {
+    pub kind: EndpointKind,
+    /// MAC or IP address
+    pub address: String,
+    pub port: Option<u16>,
+    /// Traffic sent by the endpoint
+    pub sent: Counters,
+    /// Traffic received by the endpoint
+    pub received: Counters,
+    pub first_seen: Timestamp,
+    pub last_seen: Timestamp,
+    /// Host name of the IP address, filled in once resolved when reverse DNS is enabled
+    pub name: Option<String>,
+}
+
+impl Endpoint {
+    fn packets(&self) -> usize {
+        self.sent.packets + self.received.packets
+    }
+
+    fn bytes(&self) -> usize {
+        self.sent.bytes + self.received.bytes
+    }
+
+    fn compare(&self, other: &Endpoint, order: EndpointOrder) -> Ordering {
+        match order {
+            EndpointOrder::Packets => self.packets().cmp(&other.packets()),
+            EndpointOrder::Bytes => self.bytes().cmp(&other.bytes()),
+            EndpointOrder::SentBytes => self.sent.bytes.cmp(&other.sent.bytes),
+            EndpointOrder::ReceivedBytes => self.received.bytes.cmp(&other.received.bytes),
+            EndpointOrder::FirstSeen => self.first_seen.cmp(&other.first_seen),
+            EndpointOrder::LastSeen => self.last_seen.cmp(&other.last_seen),
+        }
+    }
+}
+
+/// Layer, address and port of an endpoint
+type EndpointKey = (EndpointKind, String, Option<u16>);
+
+/// Endpoints of the collected packets
+#[derive(Debug, Default)]
+pub struct EndpointTracker {
+    endpoints: HashMap<EndpointKey, Endpoint>,
+}
+
+impl EndpointTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Account a packet to its endpoints, given its size and the time it was received
+    pub fn update(&mut self, packet: &ParsedPacket, bytes: usize, time: DateTime<Local>) {
+        let time = time.timestamp_millis();
+
+        if let Some(SerializablePacket::EthernetPacket(ethernet)) = packet.get_link_layer_packet() {
+            self.track(
+                EndpointKind::Ethernet,
+                (ethernet.source.to_string(), None),
+                (ethernet.destination.to_string(), None),
+                bytes,
+                time,
+            );
+        }
+
+        let (kind, source, destination) = match packet.get_network_layer_packet() {
+            Some(SerializablePacket::Ipv4Packet(ipv4)) => (
+                EndpointKind::Ipv4,
+                ipv4.source.to_string(),
+                ipv4.destination.to_string(),
+            ),
+            Some(SerializablePacket::Ipv6Packet(ipv6)) => (
+                EndpointKind::Ipv6,
+                ipv6.source.to_string(),
+                ipv6.destination.to_string(),
+            ),
+            _ => return,
+        };
+        self.track(
+            kind,
+            (source.clone(), None),
+            (destination.clone(), None),
+            bytes,
+            time,
+        );
+
+        let (kind, ports) = match packet.get_transport_layer_packet() {
+            Some(SerializablePacket::TcpPacket(tcp)) => {
+                (EndpointKind::Tcp, (tcp.source, tcp.destination))
+            }
+            Some(SerializablePacket::UdpPacket(udp)) => {
+                (EndpointKind::Udp, (udp.source, udp.destination))
+            }
+            _ => return,
+        };
+        self.track(
+            kind,
+            (source, Some(ports.0)),
+            (destination, Some(ports.1)),
+            bytes,
+            time,
+        );
+    }
+
+    /// Account a packet sent from an endpoint to another at a layer
+    fn track(
+        &mut self,
+        kind: EndpointKind,
+        source: (String, Option<u16>),
+        destination: (String, Option<u16>),
+        bytes: usize,
+        time: i64,
+    ) {
+        self.endpoint(kind, source, time).sent.add(bytes);
+        self.endpoint(kind, destination, time).received.add(bytes);
+    }
+
+    /// Get an endpoint seen at the given time, creating it if needed
+    fn endpoint(
+        &mut self,
+        kind: EndpointKind,
+        (address, port): (String, Option<u16>),
+        time: i64,
+    ) -> &mut Endpoint {
+        let endpoint = self
+            .endpoints
+            .entry((kind, address.clone(), port))
+            .or_insert_with(|| Endpoint {
+                kind,
+                address,
+                port,
+                sent: Counters::default(),
+                received: Counters::default(),
+                first_seen: time,
+                last_seen: time,
+                name: None,
+            });
+        endpoint.first_seen = endpoint.first_seen.min(time);
+        endpoint.last_seen = endpoint.last_seen.max(time);
+
+        endpoint
+    }
+
+    /// Get the endpoints of a layer sorted by a field, at most the given number of them
+    pub fn endpoints(
+        &self,
+        kind: Option<EndpointKind>,
+        order: EndpointOrder,
+        descending: bool,
+        limit: Option<usize>,
+    ) -> Vec<Endpoint> {
+        let mut endpoints: Vec<Endpoint> = self
+            .endpoints
+            .values()
+            .filter(|endpoint| kind.is_none_or(|kind| endpoint.kind == kind))
+            .cloned()
+            .collect();
+
+        // Endpoints with the same value are sorted by layer, address and port
+        endpoints.sort_by(|a, b| {
+            let ordering = a.compare(b, order);
+            let ordering = if descending {
+                ordering.reverse()
+            } else {
+                ordering
+            };
+            ordering.then_with(|| {
+                (a.kind as u8, &a.address, a.port).cmp(&(b.kind as u8, &b.address, b.port))
+            })
+        });
+        if let Some(limit) = limit {
+            endpoints.truncate(limit);
+        }
+
+        endpoints
+    }
+
+    pub fn clear(&mut self) {
+        self.endpoints.clear();
+    }
+}
+
+/// Get the endpoints of the collected packets sorted by a field, optionally keeping only the ones
+/// of a layer, and at most the given number of them (e.g. the top talkers)
+#[tauri::command]
+pub fn get_endpoints(
+    kind: Option<EndpointKind>,
+    order: EndpointOrder,
+    descending: bool,
+    limit: Option<usize>,
+    state: tauri::State<SniffingState>,
+) -> Vec<Endpoint> {
+    let mut endpoints = state
+        .packets
+        .lock()
+        .unwrap()
+        .endpoints
+        .endpoints(kind, order, descending, limit);
+    state
+        .reverse_dns
+        .lock()
+        .unwrap()
+        .name_endpoints(&mut endpoints);
+
+    endpoints
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EndpointKind, EndpointOrder, EndpointTracker};
+    use crate::statistics::Counters;
+    use crate::test_utils::{at, udp_packet};
+
+    #[test]
+    fn endpoints_of_each_layer() {
+        let mut tracker = EndpointTracker::new();
+        tracker.update(
+            &udp_packet(0, ([10, 0, 0, 1], 4444), ([10, 0, 0, 2], 6001), &[]),
+            100,
+            at(0),
+        );
+        tracker.update(
+            &udp_packet(0, ([10, 0, 0, 2], 6001), ([10, 0, 0, 1], 4444), &[]),
+            300,
+            at(20),
+        );
+        tracker.update(
+            &udp_packet(0, ([10, 0, 0, 3], 4444), ([10, 0, 0, 2], 9), &[]),
+            50,
+            at(10),
+        );
+
+        let all = tracker.endpoints(None, EndpointOrder::Packets, true, None);
+        // 2 MAC addresses, 3 IPv4 addresses and 4 UDP endpoints
+        assert_eq!(all.len(), 9);
+
+        let hosts = tracker.endpoints(Some(EndpointKind::Ipv4), EndpointOrder::Bytes, true, None);
+        assert_eq!(hosts.len(), 3);
+        assert_eq!(hosts[0].address, "10.0.0.2");
+        assert_eq!(
+            hosts[0].sent,
+            Counters {
+                packets: 1,
+                bytes: 300
+            }
+        );
+        assert_eq!(
+            hosts[0].received,
+            Counters {
+                packets: 2,
+                bytes: 150
+            }
+        );
+        assert_eq!(hosts[0].first_seen, at(0).timestamp_millis());
+        assert_eq!(hosts[0].last_seen, at(20).timestamp_millis());
+        assert_eq!(hosts[1].address, "10.0.0.1");
+        assert_eq!(hosts[2].address, "10.0.0.3");
+
+        let top_receivers = tracker.endpoints(
+            Some(EndpointKind::Udp),
+            EndpointOrder::ReceivedBytes,
+            true,
+            Some(2),
+        );
+        assert_eq!(top_receivers.len(), 2);
+        assert_eq!(top_receivers[0].address, "10.0.0.1");
+        assert_eq!(top_receivers[0].port, Some(4444));
+        assert_eq!(top_receivers[0].received.bytes, 300);
+        assert_eq!(top_receivers[1].port, Some(6001));
+
+        let ethernet = tracker.endpoints(
+            Some(EndpointKind::Ethernet),
+            EndpointOrder::FirstSeen,
+            false,
+            None,
+        );
+        assert_eq!(ethernet[0].address, "00:00:00:00:00:01");
+        assert_eq!(ethernet[0].sent.packets, 3);
+        assert_eq!(ethernet[1].address, "ff:ff:ff:ff:ff:ff");
+
+        tracker.clear();
+        assert!(tracker
+            .endpoints(None, EndpointOrder::Packets, true, None)
+            .is_empty());
+    }
+}
@@ -0,0 +1,565 @@
+//! Export of the collected packets
+//!
+//! Every export goes through a redaction profile, which controls how much of the packets
+//! leaves the application:
+//! - Full: all the layers, payloads included
+//! - Headers only: link, network and transport headers, without any payload
+//! - Metadata only: addresses, ports and lengths
+//!
+//! Available formats
+//! - JSON, with the parsed representation of the packets
+//! - PCAP, with the raw frames (available only for imported capture files)
+//! - PCAPNG, with the raw frames too, each one commented with the protocols dissected by wirefish;
+//!   with the full profile, the addresses resolved by the captured DNS answers and by the user are
+//!   included as well
+//!
+//! The raw frames of the edited packets are exported as edited (see [`crate::packet_edits`]).
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use pnet::packet::ethernet::{EtherTypes, EthernetPacket};
+use pnet::packet::ip::{IpNextHeaderProtocol, IpNextHeaderProtocols};
+use pnet::packet::ipv4::Ipv4Packet;
+use pnet::packet::ipv6::Ipv6Packet;
+use pnet::packet::tcp::TcpPacket;
+use pnet::packet::Packet;
+use serde::{Deserialize, Serialize};
+use sniffer_parser::serializable_packet::application::CustomResourceData;
+use sniffer_parser::serializable_packet::util::{
+    contains_malformed, get_dest_ip, get_dest_mac, get_dest_port, get_source_ip, get_source_mac,
+    get_source_port,
+};
+use sniffer_parser::serializable_packet::{ParsedPacket, SerializablePacket};
+use sniffer_parser::HeaderLength;
+use tracing::info;
+
+use crate::capture_file::{write_global_header, write_record, LinkTypes};
+use crate::encryption::encrypt;
+use crate::name_resolution::NameResolver;
+use crate::packet_edits::network_offset;
+use crate::pcapng::{
+    write_enhanced_packet, write_interface_description, write_name_resolution, write_section_header,
+};
+use crate::report::get_sender_receiver;
+use crate::{SniffingError, SniffingState};
+
+const IPV6_HEADER_LENGTH: usize = 40;
+const UDP_HEADER_LENGTH: usize = 8;
+const ICMP_HEADER_LENGTH: usize = 8;
+
+/// Amount of data included in an export
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum ExportProfile {
+    Full,
+    HeadersOnly,
+    MetadataOnly,
+}
+
+/// Output format of an export
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum ExportFormat {
+    Json,
+    Pcap,
+    Pcapng,
+}
+
+/// Packet representation exported with the metadata only profile
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PacketMetadata {
+    pub id: usize,
+    pub source_mac: Option<String>,
+    pub dest_mac: Option<String>,
+    pub source_ip: Option<String>,
+    pub dest_ip: Option<String>,
+    pub source_port: Option<String>,
+    pub dest_port: Option<String>,
+    pub length: Option<usize>,
+}
+
+impl From<&ParsedPacket> for PacketMetadata {
+    fn from(packet: &ParsedPacket) -> Self {
+        let length = match packet.get_link_layer_packet() {
+            Some(SerializablePacket::EthernetPacket(link_packet)) => {
+                Some(HeaderLength::ETHERNET + link_packet.payload.len())
+            }
+            Some(SerializablePacket::PppPacket(link_packet)) => Some(link_packet.length),
+            Some(SerializablePacket::SlipPacket(link_packet)) => Some(link_packet.length),
+            Some(SerializablePacket::LinuxSllPacket(link_packet)) => Some(link_packet.length),
+            Some(SerializablePacket::LoopbackPacket(link_packet)) => Some(link_packet.length),
+            Some(SerializablePacket::RawPacket(link_packet)) => Some(link_packet.length),
+            Some(SerializablePacket::Ieee80211Packet(link_packet)) => Some(link_packet.length),
+            Some(SerializablePacket::UnknownPacket(link_packet)) => Some(link_packet.length),
+            _ => None,
+        };
+
+        PacketMetadata {
+            id: packet.get_id(),
+            source_mac: get_source_mac(packet),
+            dest_mac: get_dest_mac(packet),
+            source_ip: get_source_ip(packet),
+            dest_ip: get_dest_ip(packet),
+            source_port: get_source_port(packet),
+            dest_port: get_dest_port(packet),
+            length,
+        }
+    }
+}
+
+/// Remove from a parsed packet everything not allowed by the profile
+///
+/// The metadata only profile is applied with [`PacketMetadata`]: here it keeps the headers
+pub fn redact_packet(packet: &ParsedPacket, profile: ExportProfile) -> ParsedPacket {
+    let mut redacted = packet.clone();
+    if profile == ExportProfile::Full {
+        return redacted;
+    }
+
+    if let Some(SerializablePacket::EthernetPacket(link_packet)) = packet.get_link_layer_packet() {
+        let mut link_packet = link_packet.clone();
+        link_packet.payload.clear();
+        redacted.set_link_layer_packet(Some(SerializablePacket::EthernetPacket(link_packet)));
+    }
+    redacted.set_application_layer_packet(None);
+
+    redacted
+}
+
+/// Get the part of a raw frame of the given link type allowed by the profile
+pub fn redact_frame(link_type: u32, frame: &[u8], profile: ExportProfile) -> &[u8] {
+    match profile {
+        ExportProfile::Full => frame,
+        ExportProfile::HeadersOnly => {
+            // Frames whose headers can't be told apart from their payload are left out
+            let length = headers_length(link_type, frame).unwrap_or(0);
+            &frame[..length.min(frame.len())]
+        }
+        ExportProfile::MetadataOnly => &[],
+    }
+}
+
+/// Get the comment describing a packet in a .pcapng file: its protocols, and whether it is malformed
+pub fn packet_comment(packet: &ParsedPacket) -> Option<String> {
+    let mut comment = get_sender_receiver(packet).1;
+    if contains_malformed(packet) {
+        comment.push("Malformed".to_owned());
+    }
+
+    (!comment.is_empty()).then(|| comment.join(", "))
+}
+
+/// Get the host names of the addresses found in the DNS answers of the packets, sorted by address
+///
+/// When an address is resolved multiple times, the latest name is kept; names set by the user take
+/// precedence over all of them.
+pub fn resolved_names(
+    packets: &[Arc<ParsedPacket>],
+    resolver: &NameResolver,
+) -> Vec<(IpAddr, String)> {
+    let mut names = BTreeMap::new();
+
+    for packet in packets {
+        if let Some(SerializablePacket::DnsPacket(dns_packet)) =
+            packet.get_application_layer_packet()
+        {
+            for record in dns_packet.answers.iter().chain(&dns_packet.additional) {
+                let address = match &record.data {
+                    CustomResourceData::A(a) => IpAddr::V4(a.address),
+                    CustomResourceData::AAAA(aaaa) => IpAddr::V6(aaaa.address),
+                    _ => continue,
+                };
+                names.insert(address, record.name.clone());
+            }
+        }
+    }
+
+    names.extend(
+        resolver
+            .hosts()
+            .map(|(address, name)| (*address, name.clone())),
+    );
+
+    names.into_iter().collect()
+}
+
+/// Get the length of the link, network and transport headers of a frame of the given link type
+///
+/// Frames without an IP packet have only their Ethernet header, while the headers of the other
+/// link types can't be told apart from the payload.
+pub fn headers_length(link_type: u32, frame: &[u8]) -> Option<usize> {
+    if link_type == LinkTypes::ETHERNET {
+        match EthernetPacket::new(frame).map(|ethernet| ethernet.get_ethertype()) {
+            // ARP packets are made of headers only
+            Some(EtherTypes::Arp) | None => return Some(frame.len()),
+            Some(_) => (),
+        }
+    }
+
+    let offset = match network_offset(link_type, frame) {
+        Some(offset) => offset,
+        None if link_type == LinkTypes::ETHERNET => return Some(HeaderLength::ETHERNET),
+        None => return None,
+    };
+    let packet = &frame[offset..];
+
+    let network_and_transport = match packet[0] >> 4 {
+        4 => Ipv4Packet::new(packet).map(|ipv4| {
+            ipv4.get_header_length() as usize * 4
+                + transport_header_length(ipv4.get_next_level_protocol(), ipv4.payload())
+        }),
+        6 => Ipv6Packet::new(packet).map(|ipv6| {
+            IPV6_HEADER_LENGTH + transport_header_length(ipv6.get_next_header(), ipv6.payload())
+        }),
+        _ => Some(0),
+    };
+
+    match network_and_transport {
+        Some(length) => Some(offset + length),
+        None => Some(frame.len()),
+    }
+}
+
+fn transport_header_length(protocol: IpNextHeaderProtocol, payload: &[u8]) -> usize {
+    match protocol {
+        IpNextHeaderProtocols::Tcp => TcpPacket::new(payload)
+            .map(|tcp| tcp.get_data_offset() as usize * 4)
+            .unwrap_or(payload.len()),
+        IpNextHeaderProtocols::Udp => UDP_HEADER_LENGTH,
+        IpNextHeaderProtocols::Icmp | IpNextHeaderProtocols::Icmpv6 => ICMP_HEADER_LENGTH,
+        _ => 0,
+    }
+}
+
+/// Exports the collected packets, redacted according to the profile and optionally encrypted
+#[tauri::command]
+pub fn export_packets(
+    path: String,
+    format: ExportFormat,
+    profile: ExportProfile,
+    passphrase: Option<String>,
+    state: tauri::State<SniffingState>,
+) -> Result<usize, SniffingError> {
+    let mut offline = state.offline.lock().unwrap();
+
+    let (data, exported) = match format {
+        ExportFormat::Json => {
+            // Imported frames not dissected yet must be exported too
+            if let Some(offline) = offline.as_mut() {
+                offline.dissect_all(&state.info, &state.packets, &state.exchanged_packets);
+            }
+
+            let packets = state.packets.lock().unwrap();
+            let data = match profile {
+                ExportProfile::MetadataOnly => serde_json::to_vec(
+                    &packets
+                        .packets
+                        .iter()
+                        .map(|packet| PacketMetadata::from(&**packet))
+                        .collect::<Vec<_>>(),
+                ),
+                _ => serde_json::to_vec(
+                    &packets
+                        .packets
+                        .iter()
+                        .map(|packet| redact_packet(packet, profile))
+                        .collect::<Vec<_>>(),
+                ),
+            }
+            .map_err(|e| SniffingError::ExportFailed(format!("Serialization failed: {}", e)))?;
+
+            (data, packets.packets.len())
+        }
+        ExportFormat::Pcap => {
+            // Raw frames are kept only for imported capture files
+            let offline = offline.as_ref().ok_or_else(|| {
+                SniffingError::ExportFailed(
+                    "PCAP export is available only for imported files".to_owned(),
+                )
+            })?;
+
+            let edits = state.packet_edits.lock().unwrap();
+            let mut data = vec![];
+            write_global_header(&mut data, offline.header())
+                .and_then(|_| {
+                    offline
+                        .frames()
+                        .enumerate()
+                        .try_for_each(|(i, (record, frame))| {
+                            let (record, frame) = edits.apply(i, record, frame);
+                            write_record(
+                                &mut data,
+                                offline.header(),
+                                &record,
+                                redact_frame(offline.header().link_type, frame, profile),
+                            )
+                        })
+                })
+                .map_err(|e| SniffingError::ExportFailed(format!("Write failed: {}", e)))?;
+
+            (data, offline.len())
+        }
+        ExportFormat::Pcapng => {
+            // Raw frames are kept only for imported capture files
+            let offline = offline.as_mut().ok_or_else(|| {
+                SniffingError::ExportFailed(
+                    "PCAPNG export is available only for imported files".to_owned(),
+                )
+            })?;
+
+            // Comments come from the dissection of every frame
+            offline.dissect_all(&state.info, &state.packets, &state.exchanged_packets);
+            let packets = state.packets.lock().unwrap();
+
+            // Names come from DNS payloads, which only the full profile exports
+            let names = match profile {
+                ExportProfile::Full => {
+                    resolved_names(&packets.packets, &state.resolver.lock().unwrap())
+                }
+                _ => vec![],
+            };
+
+            let edits = state.packet_edits.lock().unwrap();
+            let header = offline.header();
+            let mut data = vec![];
+            write_section_header(&mut data, "wirefish")
+                .and_then(|_| {
+                    write_interface_description(
+                        &mut data,
+                        header.link_type as u16,
+                        header.snap_length,
+                        None,
+                    )
+                })
+                .and_then(|_| {
+                    if names.is_empty() {
+                        Ok(())
+                    } else {
+                        write_name_resolution(&mut data, &names)
+                    }
+                })
+                .and_then(|_| {
+                    offline
+                        .frames()
+                        .enumerate()
+                        .try_for_each(|(i, (record, frame))| {
+                            let (record, frame) = edits.apply(i, record, frame);
+                            write_enhanced_packet(
+                                &mut data,
+                                0,
+                                Duration::new(record.seconds as u64, record.nanoseconds),
+                                redact_frame(header.link_type, frame, profile),
+                                record.original_length,
+                                packets
+                                    .packets
+                                    .get(i)
+                                    .and_then(|packet| packet_comment(packet))
+                                    .as_deref(),
+                            )
+                        })
+                })
+                .map_err(|e| SniffingError::ExportFailed(format!("Write failed: {}", e)))?;
+
+            (data, offline.len())
+        }
+    };
+
+    let data = match passphrase.as_deref() {
+        Some(passphrase) => encrypt(&data, passphrase)
+            .map_err(|e| SniffingError::EncryptionFailed(format!("Encryption failed: {}", e)))?,
+        None => data,
+    };
+
+    fs::write(&path, data)
+        .map_err(|e| SniffingError::ExportFailed(format!("Export failed: {}", e)))?;
+
+    info!(
+        "[{}] Exported {} packets; Format: {:?}, Profile: {:?}",
+        path, exported, format, profile
+    );
+
+    Ok(exported)
+}
+
+#[cfg(test)]
+mod tests {
+    use pnet::packet::ethernet::EthernetPacket;
+    use sniffer_parser::parse_ethernet_frame;
+
+    use super::{packet_comment, redact_frame, redact_packet, ExportProfile, PacketMetadata};
+    use crate::capture_file::LinkTypes;
+
+    const TCP_FRAME: &[u8] = &[
+        0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x08, 0x00, 0x45,
+        0x00, 0x00, 0x2b, 0x00, 0x00, 0x00, 0x00, 0x40, 0x06, 0x00, 0x00, 0x0a, 0x0a, 0x0a, 0x0a,
+        0x0b, 0x0b, 0x0b, 0x0b, 0x11, 0x5c, 0x01, 0xbb, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x50, 0x18, 0xff, 0xff, 0x00, 0x00, 0x00, 0x00, 0x47, 0x45, 0x54,
+    ];
+
+    #[test]
+    fn full_profile_frame() {
+        assert_eq!(
+            redact_frame(LinkTypes::ETHERNET, TCP_FRAME, ExportProfile::Full),
+            TCP_FRAME
+        );
+    }
+
+    #[test]
+    fn headers_only_frame() {
+        let redacted = redact_frame(LinkTypes::ETHERNET, TCP_FRAME, ExportProfile::HeadersOnly);
+
+        // Ethernet (14) + IPv4 (20) + TCP (20), without the 3 bytes of payload
+        assert_eq!(redacted.len(), 54);
+        assert_eq!(redacted, &TCP_FRAME[..54]);
+    }
+
+    #[test]
+    fn metadata_only_frame() {
+        assert!(
+            redact_frame(LinkTypes::ETHERNET, TCP_FRAME, ExportProfile::MetadataOnly).is_empty()
+        );
+    }
+
+    #[test]
+    fn headers_only_raw_ip_frames() {
+        // Source address 8.6.10.10, whose first bytes read as the EtherType of ARP
+        let mut packet = TCP_FRAME[14..].to_vec();
+        packet[12..14].copy_from_slice(&[0x08, 0x06]);
+
+        for link_type in [LinkTypes::RAW, LinkTypes::IPV4] {
+            let redacted = redact_frame(link_type, &packet, ExportProfile::HeadersOnly);
+            assert_eq!(redacted, &packet[..40]);
+        }
+    }
+
+    #[test]
+    fn headers_only_ipv6_frame() {
+        // IPv6 (40) + UDP (8) + 2 bytes of payload
+        let packet = [
+            &[0x60, 0x00, 0x00, 0x00, 0x00, 0x0a, 0x11, 0x40][..],
+            &[
+                0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x01,
+            ],
+            &[
+                0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x02,
+            ],
+            &[0x30, 0x39, 0x00, 0x35, 0x00, 0x0a, 0x00, 0x00, 0x61, 0x62],
+        ]
+        .concat();
+
+        let redacted = redact_frame(LinkTypes::IPV6, &packet, ExportProfile::HeadersOnly);
+        assert_eq!(redacted, &packet[..48]);
+    }
+
+    #[test]
+    fn headers_only_loopback_frames() {
+        assert_headers_only(LinkTypes::NULL, &[0x02, 0x00, 0x00, 0x00]);
+        assert_headers_only(LinkTypes::LOOP, &[0x00, 0x00, 0x00, 0x02]);
+    }
+
+    #[test]
+    fn headers_only_linux_sll_frame() {
+        let header = [
+            &[0x00, 0x00, 0x00, 0x01, 0x00, 0x06][..],
+            &[0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00],
+            &[0x08, 0x00],
+        ]
+        .concat();
+        assert_headers_only(LinkTypes::LINUX_SLL, &header);
+    }
+
+    #[test]
+    fn headers_only_linux_sll2_frame() {
+        let header = [
+            &[0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02][..],
+            &[0x00, 0x01, 0x00, 0x06],
+            &[0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00],
+        ]
+        .concat();
+        assert_headers_only(LinkTypes::LINUX_SLL2, &header);
+    }
+
+    #[test]
+    fn headers_only_ppp_frames() {
+        assert_headers_only(LinkTypes::PPP, &[0x00, 0x21]);
+        assert_headers_only(LinkTypes::PPP, &[0x21]);
+        assert_headers_only(LinkTypes::PPP_HDLC, &[0xff, 0x03, 0x00, 0x21]);
+
+        // Without an IP packet, nothing tells the headers from the payload
+        let lcp = [
+            0xff, 0x03, 0xc0, 0x21, 0x09, 0x01, 0x00, 0x08, 0x12, 0x34, 0x56, 0x78,
+        ];
+        assert!(redact_frame(LinkTypes::PPP_HDLC, &lcp, ExportProfile::HeadersOnly).is_empty());
+    }
+
+    #[test]
+    fn headers_only_slip_frame() {
+        let mut header = [0u8; 16];
+        header[1] = 0x40;
+        assert_headers_only(LinkTypes::SLIP, &header);
+    }
+
+    #[test]
+    fn headers_only_ieee80211_frames() {
+        let header = [
+            &[0x08, 0x01, 0x00, 0x00][..],
+            &[0x00, 0x11, 0x22, 0x33, 0x44, 0x55],
+            &[0x66, 0x77, 0x88, 0x99, 0xaa, 0xbb],
+            &[0xff; 6],
+            &[0x10, 0x00],
+            &[0xaa, 0xaa, 0x03, 0x00, 0x00, 0x00, 0x08, 0x00],
+        ]
+        .concat();
+        assert_headers_only(LinkTypes::IEEE802_11, &header);
+
+        let radiotap = [0x00, 0x00, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00];
+        assert_headers_only(
+            LinkTypes::IEEE802_11_RADIOTAP,
+            &[&radiotap[..], &header].concat(),
+        );
+    }
+
+    #[test]
+    fn headers_only_packet() {
+        let packet = parse_ethernet_frame(&EthernetPacket::new(TCP_FRAME).unwrap(), 0);
+        let redacted = redact_packet(&packet, ExportProfile::HeadersOnly);
+
+        assert!(redacted.get_application_layer_packet().is_none());
+        assert!(redacted.get_transport_layer_packet().is_some());
+        assert!(format!("{:?}", redacted.get_link_layer_packet()).contains("payload: []"));
+    }
+
+    #[test]
+    fn packet_metadata() {
+        let packet = parse_ethernet_frame(&EthernetPacket::new(TCP_FRAME).unwrap(), 7);
+        let metadata = PacketMetadata::from(&packet);
+
+        assert_eq!(metadata.id, 7);
+        assert_eq!(metadata.source_ip.as_deref(), Some("10.10.10.10"));
+        assert_eq!(metadata.dest_port.as_deref(), Some("443"));
+        assert_eq!(metadata.length, Some(TCP_FRAME.len()));
+    }
+
+    #[test]
+    fn packet_comment_protocols() {
+        let packet = parse_ethernet_frame(&EthernetPacket::new(TCP_FRAME).unwrap(), 0);
+
+        assert!(packet_comment(&packet).unwrap().starts_with("IPv4, TCP"));
+    }
+
+    ///////////////////// Utils
+
+    /// Check that the IPv4 and TCP headers of the test frame are kept after the given link header,
+    /// without the payload
+    fn assert_headers_only(link_type: u32, link_header: &[u8]) {
+        let frame = [link_header, &TCP_FRAME[14..]].concat();
+
+        let redacted = redact_frame(link_type, &frame, ExportProfile::HeadersOnly);
+        assert_eq!(redacted, &frame[..link_header.len() + 40]);
+    }
+}
@@ -12,8 +12,30 @@
 //!     - TCP
 //!     - UDP
 //!     - TLS
+//!     - QUIC
 //!     - DNS
+//!     - DHCP
 //!     - HTTP
+//!     - HTTP2
+//!     - PTP
+//!     - GOOSE
+//!     - SV
+//!     - PROFINET
+//!     - ETHERCAT
+//!     - S7COMM
+//!     - ISCSI
+//!     - NVME_TCP
+//!     - CQL
+//!     - KAFKA
+//!     - ZOOKEEPER
+//!     - CUSTOM
+//!     - SNMP
+//!     - GTP
+//!     - ESP
+//!     - AH
+//!     - IKE
+//!     - WIREGUARD
+//!     - NTP
 //! - By Attributes
 //!     - SOURCE MAC
 //!     - DESTINATION MAC
@@ -21,22 +43,60 @@
 //!     - DESTINATION IP
 //!     - SOURCE PORT
 //!     - DESTINATION PORT
+//!     - INTERFACE
 //! - By Type
 //!     - MALFORMED
-
+//! - By Display Filter
+//!
+//! The collected packets stay in the backend: they are requested a window at a time, optionally
+//! sorted by id, time, source, destination, protocol or length, while the packets collected are
+//! notified in batches.
+
+use crate::arp_watch::ArpWatch;
+use crate::conversations::ConnectionTracker;
+use crate::display_filter::DisplayFilter;
+use crate::endpoints::EndpointTracker;
+use crate::gtp_sessions::GtpSessions;
+use crate::happy_eyeballs::HappyEyeballsTracker;
+use crate::http_objects::HttpObjects;
+use crate::icmp_watch::IcmpWatch;
+use crate::inventory::HostInventory;
+use crate::io_graph::IoGraph;
+use crate::latency_watch::LatencyWatch;
+use crate::mptcp::MptcpTracker;
+use crate::ntp_watch::NtpWatch;
+use crate::packet_store::PacketStore;
+use crate::proxy_config_watch::ProxyConfigWatch;
+use crate::reflection_watch::ReflectionWatch;
+use crate::registry::RegistryAnalytics;
+use crate::report::get_sender_receiver;
+use crate::security_associations::SecurityAssociations;
+use crate::service_discovery::ServiceDiscovery;
+use crate::statistics::CaptureStatistics;
+use crate::tcp_features::TcpFeatureTracker;
 use crate::{SniffingError, SniffingState};
-use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
 use sniffer_parser::serializable_packet::util::{
-    contains_arp, contains_dns, contains_ethernet, contains_http, contains_icmp, contains_icmp6,
-    contains_ipv4, contains_ipv6, contains_malformed, contains_tcp, contains_tls, contains_udp,
-    contains_unknokn,
+    contains_ah, contains_arp, contains_cql, contains_custom, contains_dhcp, contains_dns,
+    contains_esp, contains_ethercat, contains_ethernet, contains_goose, contains_gtp,
+    contains_http, contains_http2, contains_icmp, contains_icmp6, contains_ike, contains_ipv4,
+    contains_ipv6, contains_iscsi, contains_kafka, contains_malformed, contains_ntp,
+    contains_nvme_tcp, contains_profinet, contains_ptp, contains_quic, contains_s7comm,
+    contains_snmp, contains_sv, contains_tcp, contains_tls, contains_udp, contains_unknokn,
+    contains_wireguard, contains_zookeeper,
 };
 use sniffer_parser::serializable_packet::util::{
     get_dest_ip, get_dest_mac, get_dest_port, get_source_ip, get_source_mac, get_source_port,
 };
 use sniffer_parser::serializable_packet::ParsedPacket;
+use std::net::IpAddr;
 use std::slice::Iter;
+use std::time::{Duration, Instant};
 use std::{collections::BTreeMap, sync::Arc};
+use tracing::{debug, error, info, warn};
+
+/// Minimum interval between the notifications of the packets collected
+pub const NOTIFICATION_INTERVAL: Duration = Duration::from_millis(250);
 
 #[allow(non_snake_case)]
 mod FilterNamesValues {
@@ -48,11 +108,33 @@ mod FilterNamesValues {
     pub const ICMPV6: &str = "icmpv6";
     pub const ICMP: &str = "icmp";
     pub const HTTP: &str = "http";
+    pub const HTTP2: &str = "http2";
     pub const TLS: &str = "tls";
+    pub const QUIC: &str = "quic";
     pub const IPV4: &str = "ipv4";
     pub const IPV6: &str = "ipv6";
     pub const ARP: &str = "arp";
     pub const DNS: &str = "dns";
+    pub const PTP: &str = "ptp";
+    pub const DHCP: &str = "dhcp";
+    pub const GOOSE: &str = "goose";
+    pub const SV: &str = "sv";
+    pub const PROFINET: &str = "profinet";
+    pub const ETHERCAT: &str = "ethercat";
+    pub const S7COMM: &str = "s7comm";
+    pub const ISCSI: &str = "iscsi";
+    pub const NVME_TCP: &str = "nvme_tcp";
+    pub const CQL: &str = "cql";
+    pub const KAFKA: &str = "kafka";
+    pub const ZOOKEEPER: &str = "zookeeper";
+    pub const CUSTOM: &str = "custom";
+    pub const SNMP: &str = "snmp";
+    pub const GTP: &str = "gtp";
+    pub const ESP: &str = "esp";
+    pub const AH: &str = "ah";
+    pub const IKE: &str = "ike";
+    pub const WIREGUARD: &str = "wireguard";
+    pub const NTP: &str = "ntp";
 
     pub const SRC_IP: &str = "src_ip";
     pub const DST_IP: &str = "dst_ip";
@@ -60,13 +142,50 @@ mod FilterNamesValues {
     pub const DST_MAC: &str = "dst_mac";
     pub const SRC_PORT: &str = "src_port";
     pub const DST_PORT: &str = "dst_port";
+    pub const INTERFACE: &str = "interface";
 }
 
+/// Protocol filters satisfied by the link, network and transport layers of the packets
+const TRANSPORT_TYPES: &[&str] = &[
+    FilterNamesValues::ETHERNET,
+    FilterNamesValues::IPV4,
+    FilterNamesValues::IPV6,
+    FilterNamesValues::ARP,
+    FilterNamesValues::ICMP,
+    FilterNamesValues::ICMPV6,
+    FilterNamesValues::TCP,
+    FilterNamesValues::UDP,
+];
+
 /// List of all the collected packets and additional data structures to speed up the filtering process
 #[derive(Debug)]
 pub struct PacketsCollection {
     pub packets: Vec<Arc<ParsedPacket>>,
 
+    /// Data aggregated from the packets
+    pub registry: RegistryAnalytics,
+    pub statistics: CaptureStatistics,
+    pub io_graph: IoGraph,
+    pub conversations: ConnectionTracker,
+    pub endpoints: EndpointTracker,
+    pub inventory: HostInventory,
+    pub arp_watch: ArpWatch,
+    pub icmp_watch: IcmpWatch,
+    pub proxy_config_watch: ProxyConfigWatch,
+    pub ntp_watch: NtpWatch,
+    pub reflection_watch: ReflectionWatch,
+    pub latency_watch: LatencyWatch,
+    pub tcp_features: TcpFeatureTracker,
+    pub happy_eyeballs: HappyEyeballsTracker,
+    pub mptcp: MptcpTracker,
+    pub service_discovery: ServiceDiscovery,
+    pub gtp_sessions: GtpSessions,
+    pub http_objects: HttpObjects,
+    pub security_associations: SecurityAssociations,
+
+    /// Persistent store the packets are written to as well, if open
+    pub store: Option<PacketStore>,
+
     /// Indexes as Binary Trees for fast selective searching
     pub source_ip_index: BTreeMap<String, Vec<Arc<ParsedPacket>>>,
     pub dest_ip_index: BTreeMap<String, Vec<Arc<ParsedPacket>>>,
@@ -74,6 +193,7 @@ pub struct PacketsCollection {
     pub dest_port_index: BTreeMap<String, Vec<Arc<ParsedPacket>>>,
     pub source_mac_index: BTreeMap<String, Vec<Arc<ParsedPacket>>>,
     pub dest_mac_index: BTreeMap<String, Vec<Arc<ParsedPacket>>>,
+    pub interface_index: BTreeMap<String, Vec<Arc<ParsedPacket>>>,
 
     pub ethernet_packets: Vec<Arc<ParsedPacket>>,
     pub malformed_packets: Vec<Arc<ParsedPacket>>,
@@ -83,11 +203,33 @@ pub struct PacketsCollection {
     pub icmp_packets: Vec<Arc<ParsedPacket>>,
     pub icmpv6_packets: Vec<Arc<ParsedPacket>>,
     pub http_packets: Vec<Arc<ParsedPacket>>,
+    pub http2_packets: Vec<Arc<ParsedPacket>>,
     pub tls_packets: Vec<Arc<ParsedPacket>>,
+    pub quic_packets: Vec<Arc<ParsedPacket>>,
     pub ipv4_packets: Vec<Arc<ParsedPacket>>,
     pub ipv6_packets: Vec<Arc<ParsedPacket>>,
     pub dns_packets: Vec<Arc<ParsedPacket>>,
     pub arp_packets: Vec<Arc<ParsedPacket>>,
+    pub ptp_packets: Vec<Arc<ParsedPacket>>,
+    pub dhcp_packets: Vec<Arc<ParsedPacket>>,
+    pub goose_packets: Vec<Arc<ParsedPacket>>,
+    pub sv_packets: Vec<Arc<ParsedPacket>>,
+    pub profinet_packets: Vec<Arc<ParsedPacket>>,
+    pub ethercat_packets: Vec<Arc<ParsedPacket>>,
+    pub s7comm_packets: Vec<Arc<ParsedPacket>>,
+    pub iscsi_packets: Vec<Arc<ParsedPacket>>,
+    pub nvme_tcp_packets: Vec<Arc<ParsedPacket>>,
+    pub cql_packets: Vec<Arc<ParsedPacket>>,
+    pub kafka_packets: Vec<Arc<ParsedPacket>>,
+    pub zookeeper_packets: Vec<Arc<ParsedPacket>>,
+    pub custom_packets: Vec<Arc<ParsedPacket>>,
+    pub snmp_packets: Vec<Arc<ParsedPacket>>,
+    pub gtp_packets: Vec<Arc<ParsedPacket>>,
+    pub esp_packets: Vec<Arc<ParsedPacket>>,
+    pub ah_packets: Vec<Arc<ParsedPacket>>,
+    pub ike_packets: Vec<Arc<ParsedPacket>>,
+    pub wireguard_packets: Vec<Arc<ParsedPacket>>,
+    pub ntp_packets: Vec<Arc<ParsedPacket>>,
 }
 
 impl PacketsCollection {
@@ -95,12 +237,35 @@ impl PacketsCollection {
         PacketsCollection {
             packets: vec![],
 
+            registry: RegistryAnalytics::new(),
+            statistics: CaptureStatistics::new(),
+            io_graph: IoGraph::new(),
+            conversations: ConnectionTracker::new(),
+            endpoints: EndpointTracker::new(),
+            inventory: HostInventory::new(),
+            arp_watch: ArpWatch::new(),
+            icmp_watch: IcmpWatch::new(),
+            proxy_config_watch: ProxyConfigWatch::new(),
+            ntp_watch: NtpWatch::new(),
+            reflection_watch: ReflectionWatch::new(),
+            latency_watch: LatencyWatch::new(),
+            tcp_features: TcpFeatureTracker::new(),
+            happy_eyeballs: HappyEyeballsTracker::new(),
+            mptcp: MptcpTracker::new(),
+            service_discovery: ServiceDiscovery::new(),
+            gtp_sessions: GtpSessions::new(),
+            http_objects: HttpObjects::new(),
+            security_associations: SecurityAssociations::new(),
+
+            store: None,
+
             source_ip_index: BTreeMap::new(),
             dest_ip_index: BTreeMap::new(),
             source_port_index: BTreeMap::new(),
             dest_port_index: BTreeMap::new(),
             source_mac_index: BTreeMap::new(),
             dest_mac_index: BTreeMap::new(),
+            interface_index: BTreeMap::new(),
 
             unknown_packets: vec![],
             ethernet_packets: vec![],
@@ -110,17 +275,240 @@ impl PacketsCollection {
             icmp_packets: vec![],
             icmpv6_packets: vec![],
             http_packets: vec![],
+            http2_packets: vec![],
             tls_packets: vec![],
+            quic_packets: vec![],
             ipv4_packets: vec![],
             ipv6_packets: vec![],
             dns_packets: vec![],
             arp_packets: vec![],
+            ptp_packets: vec![],
+            dhcp_packets: vec![],
+            goose_packets: vec![],
+            sv_packets: vec![],
+            profinet_packets: vec![],
+            ethercat_packets: vec![],
+            s7comm_packets: vec![],
+            iscsi_packets: vec![],
+            nvme_tcp_packets: vec![],
+            cql_packets: vec![],
+            kafka_packets: vec![],
+            zookeeper_packets: vec![],
+            custom_packets: vec![],
+            snmp_packets: vec![],
+            gtp_packets: vec![],
+            esp_packets: vec![],
+            ah_packets: vec![],
+            ike_packets: vec![],
+            wireguard_packets: vec![],
+            ntp_packets: vec![],
+        }
+    }
+
+    /// Insert a packet in the collection, updating all the indexes
+    ///
+    /// The interface is the one the packet was received from, none for offline captures.
+    pub fn insert(&mut self, parsed_packet: Arc<ParsedPacket>, interface: Option<&str>) {
+        // Index by Interface
+        if let Some(interface) = interface {
+            self.interface_index
+                .entry(interface.to_owned())
+                .and_modify(|packets| packets.push(Arc::clone(&parsed_packet)))
+                .or_insert(vec![Arc::clone(&parsed_packet)]);
+        }
+
+        // Index by Source IP
+        if let Some(ip_address) = get_source_ip(&parsed_packet) {
+            self.source_ip_index
+                .entry(ip_address)
+                .and_modify(|packets| packets.push(Arc::clone(&parsed_packet)))
+                .or_insert(vec![Arc::clone(&parsed_packet)]);
+        }
+
+        // Index by Dest IP
+        if let Some(ip_address) = get_dest_ip(&parsed_packet) {
+            self.dest_ip_index
+                .entry(ip_address)
+                .and_modify(|packets| packets.push(Arc::clone(&parsed_packet)))
+                .or_insert(vec![Arc::clone(&parsed_packet)]);
+        }
+
+        // Index by Source MAC
+        if let Some(mac_address) = get_source_mac(&parsed_packet) {
+            self.source_mac_index
+                .entry(mac_address)
+                .and_modify(|packets| packets.push(Arc::clone(&parsed_packet)))
+                .or_insert(vec![Arc::clone(&parsed_packet)]);
+        }
+
+        // Index by Dest MAC
+        if let Some(mac_address) = get_dest_mac(&parsed_packet) {
+            self.dest_mac_index
+                .entry(mac_address)
+                .and_modify(|packets| packets.push(Arc::clone(&parsed_packet)))
+                .or_insert(vec![Arc::clone(&parsed_packet)]);
+        }
+
+        // Index by Source Port
+        if let Some(port) = get_source_port(&parsed_packet) {
+            self.source_port_index
+                .entry(port)
+                .and_modify(|packets| packets.push(Arc::clone(&parsed_packet)))
+                .or_insert(vec![Arc::clone(&parsed_packet)]);
+        }
+
+        // Index by Dest Port
+        if let Some(port) = get_dest_port(&parsed_packet) {
+            self.dest_port_index
+                .entry(port)
+                .and_modify(|packets| packets.push(Arc::clone(&parsed_packet)))
+                .or_insert(vec![Arc::clone(&parsed_packet)]);
+        }
+
+        for (contains, packets) in self.protocol_indexes() {
+            if contains(&parsed_packet) {
+                packets.push(Arc::clone(&parsed_packet));
+            }
+        }
+
+        self.registry.update(&parsed_packet);
+
+        // Write packet to the store, closing it on failure
+        if let Some(store) = self.store.as_mut() {
+            if let Err(e) = store.insert(&parsed_packet, interface) {
+                error!("[{}] Packet store failed: {}", store.path(), e);
+                self.store = None;
+            }
         }
+
+        // Insert packet
+        self.packets.push(parsed_packet);
+    }
+
+    /// Replace a collected packet with its frame dissected again, updating all the indexes
+    ///
+    /// The addresses, ports and interface of the frame are unchanged: the packet is replaced in
+    /// their indexes, and moved between the indexes of the protocols it contains.
+    pub fn replace(&mut self, parsed_packet: Arc<ParsedPacket>) {
+        let id = parsed_packet.get_id();
+        let replaced = match self.packets.get(id) {
+            Some(replaced) => Arc::clone(replaced),
+            None => return,
+        };
+
+        let replace_entry = |packets: &mut Vec<Arc<ParsedPacket>>| {
+            if let Ok(position) = packets.binary_search_by_key(&id, |packet| packet.get_id()) {
+                packets[position] = Arc::clone(&parsed_packet);
+            }
+        };
+        let attribute_indexes = [
+            (&mut self.source_ip_index, get_source_ip(&replaced)),
+            (&mut self.dest_ip_index, get_dest_ip(&replaced)),
+            (&mut self.source_mac_index, get_source_mac(&replaced)),
+            (&mut self.dest_mac_index, get_dest_mac(&replaced)),
+            (&mut self.source_port_index, get_source_port(&replaced)),
+            (&mut self.dest_port_index, get_dest_port(&replaced)),
+        ];
+        for (index, key) in attribute_indexes {
+            if let Some(packets) = key.and_then(|key| index.get_mut(&key)) {
+                replace_entry(packets);
+            }
+        }
+        self.interface_index.values_mut().for_each(replace_entry);
+
+        for (contains, packets) in self.protocol_indexes() {
+            let position = packets.binary_search_by_key(&id, |packet| packet.get_id());
+            match (position, contains(&parsed_packet)) {
+                (Ok(position), true) => packets[position] = Arc::clone(&parsed_packet),
+                (Ok(position), false) => {
+                    packets.remove(position);
+                }
+                (Err(position), true) => packets.insert(position, Arc::clone(&parsed_packet)),
+                (Err(_), false) => (),
+            }
+        }
+
+        // Rewrite the packet in the store, closing it on failure
+        if let Some(store) = self.store.as_mut() {
+            if let Err(e) = store.replace(&replaced, &parsed_packet) {
+                error!("[{}] Packet store failed: {}", store.path(), e);
+                self.store = None;
+            }
+        }
+
+        self.packets[id] = parsed_packet;
+    }
+
+    /// Get the indexes of the packets by protocol, with the check of the packets they contain
+    fn protocol_indexes(&mut self) -> [ProtocolIndex; 35] {
+        [
+            (contains_ethernet, &mut self.ethernet_packets),
+            (contains_malformed, &mut self.malformed_packets),
+            (contains_unknokn, &mut self.unknown_packets),
+            (contains_tcp, &mut self.tcp_packets),
+            (contains_udp, &mut self.udp_packets),
+            (contains_icmp, &mut self.icmp_packets),
+            (contains_icmp6, &mut self.icmpv6_packets),
+            (contains_http, &mut self.http_packets),
+            (contains_http2, &mut self.http2_packets),
+            (contains_tls, &mut self.tls_packets),
+            (contains_quic, &mut self.quic_packets),
+            (contains_ipv4, &mut self.ipv4_packets),
+            (contains_ipv6, &mut self.ipv6_packets),
+            (contains_arp, &mut self.arp_packets),
+            (contains_dns, &mut self.dns_packets),
+            (contains_ptp, &mut self.ptp_packets),
+            (contains_dhcp, &mut self.dhcp_packets),
+            (contains_goose, &mut self.goose_packets),
+            (contains_sv, &mut self.sv_packets),
+            (contains_profinet, &mut self.profinet_packets),
+            (contains_ethercat, &mut self.ethercat_packets),
+            (contains_s7comm, &mut self.s7comm_packets),
+            (contains_iscsi, &mut self.iscsi_packets),
+            (contains_nvme_tcp, &mut self.nvme_tcp_packets),
+            (contains_cql, &mut self.cql_packets),
+            (contains_kafka, &mut self.kafka_packets),
+            (contains_zookeeper, &mut self.zookeeper_packets),
+            (contains_custom, &mut self.custom_packets),
+            (contains_snmp, &mut self.snmp_packets),
+            (contains_gtp, &mut self.gtp_packets),
+            (contains_esp, &mut self.esp_packets),
+            (contains_ah, &mut self.ah_packets),
+            (contains_ike, &mut self.ike_packets),
+            (contains_wireguard, &mut self.wireguard_packets),
+            (contains_ntp, &mut self.ntp_packets),
+        ]
     }
 
     /// Empty the data structures
     pub fn clear(&mut self) {
+        // The packets collected from now on are a new capture of the store
+        if let Some(store) = self.store.as_mut() {
+            if let Err(e) = store.end_capture(&self.conversations, &self.statistics) {
+                error!("[{}] Packet store failed: {}", store.path(), e);
+            }
+        }
+
         self.packets.clear();
+        self.registry.clear();
+        self.statistics.clear();
+        self.io_graph.clear();
+        self.conversations.clear();
+        self.endpoints.clear();
+        self.inventory.clear();
+        self.arp_watch.clear();
+        self.icmp_watch.clear();
+        self.proxy_config_watch.clear();
+        self.ntp_watch.clear();
+        self.reflection_watch.clear();
+        self.latency_watch.clear();
+        self.tcp_features.clear();
+        self.happy_eyeballs.clear();
+        self.mptcp.clear();
+        self.service_discovery.clear();
+        self.gtp_sessions.clear();
+        self.http_objects.clear();
+        self.security_associations.clear();
 
         self.source_ip_index.clear();
         self.dest_ip_index.clear();
@@ -128,6 +516,7 @@ impl PacketsCollection {
         self.dest_port_index.clear();
         self.source_mac_index.clear();
         self.dest_mac_index.clear();
+        self.interface_index.clear();
 
         self.ethernet_packets.clear();
         self.malformed_packets.clear();
@@ -137,14 +526,39 @@ impl PacketsCollection {
         self.icmp_packets.clear();
         self.icmpv6_packets.clear();
         self.http_packets.clear();
+        self.http2_packets.clear();
         self.tls_packets.clear();
+        self.quic_packets.clear();
         self.ipv4_packets.clear();
         self.ipv6_packets.clear();
         self.dns_packets.clear();
         self.arp_packets.clear();
+        self.ptp_packets.clear();
+        self.dhcp_packets.clear();
+        self.goose_packets.clear();
+        self.sv_packets.clear();
+        self.profinet_packets.clear();
+        self.ethercat_packets.clear();
+        self.s7comm_packets.clear();
+        self.iscsi_packets.clear();
+        self.nvme_tcp_packets.clear();
+        self.cql_packets.clear();
+        self.kafka_packets.clear();
+        self.zookeeper_packets.clear();
+        self.custom_packets.clear();
+        self.snmp_packets.clear();
+        self.gtp_packets.clear();
+        self.esp_packets.clear();
+        self.ah_packets.clear();
+        self.ike_packets.clear();
+        self.wireguard_packets.clear();
+        self.ntp_packets.clear();
     }
 }
 
+/// Index of the packets containing a protocol, with the check of the protocol
+type ProtocolIndex<'a> = (fn(&ParsedPacket) -> bool, &'a mut Vec<Arc<ParsedPacket>>);
+
 fn get_slice(packets: &Vec<Arc<ParsedPacket>>, start: usize, end: usize) -> &[Arc<ParsedPacket>] {
     match packets.get(start..end) {
         Some(values) => values,
@@ -152,53 +566,241 @@ fn get_slice(packets: &Vec<Arc<ParsedPacket>>, start: usize, end: usize) -> &[Ar
     }
 }
 
-/// Returns a slice of the collected packets opnionally applying the selected filters
+/// Filters selecting the packets requested, all of them holding for the packets selected
+#[derive(Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PacketFilter {
+    /// Protocols of the packets, any of them being contained (e.g. `tcp`)
+    pub types: Vec<String>,
+    /// Attributes of the packets, with their values (e.g. `("src_ip", "10.0.0.1")`)
+    pub values: Vec<(String, String)>,
+    /// Display filter matching the packets
+    pub expression: Option<String>,
+}
+
+impl PacketFilter {
+    fn is_empty(&self) -> bool {
+        self.types.is_empty() && self.values.is_empty() && self.expression.is_none()
+    }
+}
+
+/// Attribute the packets are sorted by
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum PacketOrder {
+    Id,
+    Timestamp,
+    /// Source IP address, then source MAC address
+    Source,
+    /// Destination IP address, then destination MAC address
+    Destination,
+    /// Topmost protocol dissected
+    Protocol,
+    /// Bytes of the frame on the wire
+    Length,
+}
+
+/// Sorting of the packets requested
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct PacketSort {
+    pub by: PacketOrder,
+    #[serde(default)]
+    pub descending: bool,
+}
+
+/// Window of the packets matching a filter, with the number of all of them
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PacketWindow {
+    pub offset: usize,
+    pub total: usize,
+    pub packets: Vec<ParsedPacket>,
+}
+
+/// Returns a window of the collected packets, optionally filtered and sorted, along with the number
+/// of all the packets matching the filter
+///
+/// Packets are in the order they were collected unless sorted otherwise: the ones with the same
+/// sorting key keep it, reversed when sorting in descending order.
 #[tauri::command]
-pub fn get_packets<'a>(
-    start: usize,
-    end: usize,
-    filters_type: Vec<&'a str>,
-    filters_value: Vec<(&'a str, &'a str)>,
+pub fn get_packets(
+    offset: usize,
+    limit: usize,
+    filter: PacketFilter,
+    sort: Option<PacketSort>,
     state: tauri::State<SniffingState>,
-) -> Result<Vec<ParsedPacket>, SniffingError> {
+) -> Result<PacketWindow, SniffingError> {
+    let end = offset.saturating_add(limit);
+    let display_filter = filter
+        .expression
+        .as_deref()
+        .map(DisplayFilter::compile)
+        .transpose()
+        .map_err(|e| {
+            SniffingError::InvalidDisplayFilter(format!("Invalid display filter: {}", e))
+        })?;
+
+    // Offline frames are dissected only when requested: filters and sorting need all of them.
+    // The application layers of huge captures are parsed only for the flows of the packets whose
+    // filtering or sorting depends on them, then for the flows of the window.
+    let mut offline_total = None;
+    let mut lazy = false;
+    if let Some(offline) = state.offline.lock().unwrap().as_mut() {
+        if filter.is_empty() && sort.is_none() {
+            offline.dissect_until(end, &state.info, &state.packets, &state.exchanged_packets);
+            offline.parse_applications(
+                offset..end.min(offline.len()),
+                &state.packets,
+                &state.exchanged_packets,
+            );
+            offline_total = Some(offline.len());
+        } else if offline.lazy_applications() {
+            let len = offline.len();
+            offline.dissect_until(len, &state.info, &state.packets, &state.exchanged_packets);
+            if needs_applications(&filter, display_filter.as_ref(), sort) {
+                if filter.values.is_empty() {
+                    offline.parse_applications(0..len, &state.packets, &state.exchanged_packets);
+                } else {
+                    // Attributes are the ones of the transport layer and below
+                    let values = filter
+                        .values
+                        .iter()
+                        .map(|(name, value)| (name.as_str(), value.as_str()))
+                        .collect();
+                    let matching: Vec<usize> = apply_all_strong_filters(
+                        usize::MAX,
+                        &values,
+                        &mut state.packets.lock().unwrap(),
+                    )?
+                    .iter()
+                    .map(|packet| packet.get_id())
+                    .collect();
+                    offline.parse_applications(matching, &state.packets, &state.exchanged_packets);
+                }
+            }
+            lazy = true;
+        } else {
+            offline.dissect_all(&state.info, &state.packets, &state.exchanged_packets);
+        }
+    }
+
     let mut packets_collection = state.packets.lock().unwrap();
-    let result = get_packets_internal(
-        start,
-        end,
-        &filters_type,
-        &filters_value,
+    let mut window = get_packets_window(
+        offset,
+        limit,
+        &filter,
+        display_filter.as_ref(),
+        sort,
         &mut *packets_collection,
+    )?;
+    drop(packets_collection);
+
+    if lazy {
+        if let Some(offline) = state.offline.lock().unwrap().as_mut() {
+            offline.parse_window(
+                &mut window.packets,
+                &state.packets,
+                &state.exchanged_packets,
+            );
+        }
+    }
+    if let Some(total) = offline_total {
+        window.total = window.total.max(total);
+    }
+    state
+        .geoip
+        .lock()
+        .unwrap()
+        .locate_packets(&mut window.packets);
+
+    info!(
+        "Received getPackets request ({}+{}); Len: {}, Total: {}, Filter: {:?}, Sort: {:?}",
+        offset,
+        limit,
+        window.packets.len(),
+        window.total,
+        filter,
+        sort
+    );
+    debug!(
+        "Requested packets ids: {:?}",
+        window
+            .packets
+            .iter()
+            .map(|x| x.get_id())
+            .collect::<Vec<usize>>()
     );
 
-    match &result {
-        Ok(packets) => {
-            info!(
-                "Received getPackets request ({}-{}); Len: {}, Type Filters: {:?} Strong Filters: {:?}",
-                start, end, packets.len(), filters_type, filters_value
-            );
+    Ok(window)
+}
 
-            debug!(
-                "Requested packets ids: {:?}",
-                packets.iter().map(|x| x.get_id()).collect::<Vec<usize>>()
-            )
-        }
-        _ => (),
+/// Check whether filtering and sorting the packets depend on their application layers
+fn needs_applications(
+    filter: &PacketFilter,
+    display_filter: Option<&DisplayFilter>,
+    sort: Option<PacketSort>,
+) -> bool {
+    filter
+        .types
+        .iter()
+        .any(|name| !TRANSPORT_TYPES.contains(&name.as_str()))
+        || display_filter.is_some_and(DisplayFilter::needs_applications)
+        || sort.is_some_and(|sort| sort.by == PacketOrder::Protocol)
+}
+
+fn get_packets_window(
+    offset: usize,
+    limit: usize,
+    filter: &PacketFilter,
+    display_filter: Option<&DisplayFilter>,
+    sort: Option<PacketSort>,
+    packets_collection: &mut PacketsCollection,
+) -> Result<PacketWindow, SniffingError> {
+    let window = |packets: &[Arc<ParsedPacket>]| PacketWindow {
+        offset,
+        total: packets.len(),
+        packets: packets
+            .iter()
+            .skip(offset)
+            .take(limit)
+            .map(|x| ParsedPacket::clone(x))
+            .collect(),
+    };
+
+    // Without filters and sorting, the window is taken from the collected packets as they are
+    if filter.is_empty() && sort.is_none() {
+        return Ok(window(&packets_collection.packets));
     }
 
-    result
+    let filters_type = filter.types.iter().map(String::as_str).collect();
+    let filters_value = filter
+        .values
+        .iter()
+        .map(|(name, value)| (name.as_str(), value.as_str()))
+        .collect();
+    let mut packets = get_packets_internal(&filters_type, &filters_value, packets_collection)?;
+
+    if let Some(display_filter) = display_filter {
+        packets.retain(|packet| display_filter.matches(packet));
+    }
+    if let Some(sort) = sort {
+        sort_packets(&mut packets, sort);
+    }
+
+    Ok(window(&packets))
 }
 
+/// Get all the collected packets applying the selected filters, in the order they were collected
 fn get_packets_internal<'a>(
-    start: usize,
-    end: usize,
     filters_type: &Vec<&'a str>,
     filters_value: &Vec<(&'a str, &'a str)>,
     packets_collection: &mut PacketsCollection,
-) -> Result<Vec<ParsedPacket>, SniffingError> {
+) -> Result<Vec<Arc<ParsedPacket>>, SniffingError> {
     if !filters_type.is_empty() || !filters_value.is_empty() {
         // Apply all Strong Filters
         let mut filtered_packets =
-            apply_all_strong_filters(end, &filters_value, packets_collection)?;
+            apply_all_strong_filters(usize::MAX, &filters_value, packets_collection)?;
 
         // If Strong filters are enabled
         if !filters_value.is_empty() {
@@ -220,7 +822,7 @@ fn get_packets_internal<'a>(
                 });
             }
 
-            return Ok(bound_and_map(start, end, &filtered_packets));
+            return Ok(filtered_packets);
         } else {
             // If Type filters are disabled
             if filters_type.is_empty() {
@@ -230,39 +832,101 @@ fn get_packets_internal<'a>(
             // If just 1 Type filter is enabled
             if filters_type.len() == 1 {
                 let single_filter = get_bounded_type_filter_index_iter(
-                    start,
-                    end,
+                    0,
+                    usize::MAX,
                     filters_type[0],
                     &packets_collection,
                 )?;
 
-                return Ok(single_filter.map(|x| ParsedPacket::clone(&*x)).collect());
+                return Ok(single_filter.cloned().collect());
             } else {
                 // If more than 1 Type filters are enabled
                 let mut filters_array = vec![];
 
                 for f in filters_type {
                     let mut iter =
-                        get_bounded_type_filter_index_iter(0, end, f, &packets_collection)?;
+                        get_bounded_type_filter_index_iter(0, usize::MAX, f, &packets_collection)?;
 
                     let value = iter.next();
                     filters_array.push((iter, value));
                 }
 
-                let merged = merge_filter_type_arrays(&mut filters_array);
-                return Ok(bound_and_map(start, end, &merged));
+                return Ok(merge_filter_type_arrays(&mut filters_array));
             }
         }
     } else {
-        return Ok(bound_and_map(start, end, &packets_collection.packets));
+        return Ok(packets_collection.packets.clone());
     }
 }
 
-fn bound_and_map(start: usize, end: usize, packets: &Vec<Arc<ParsedPacket>>) -> Vec<ParsedPacket> {
-    get_slice(packets, start, end)
-        .iter()
-        .map(|x| ParsedPacket::clone(&*x))
-        .collect()
+/// Sort packets by an attribute, keeping the order of the ones with the same value
+fn sort_packets(packets: &mut [Arc<ParsedPacket>], sort: PacketSort) {
+    match sort.by {
+        PacketOrder::Id => packets.sort_by_key(|packet| packet.get_id()),
+        PacketOrder::Timestamp => packets.sort_by_key(|packet| packet.get_timestamp()),
+        PacketOrder::Source => packets.sort_by_cached_key(|packet| {
+            address_key(get_source_ip(packet), get_source_mac(packet))
+        }),
+        PacketOrder::Destination => packets
+            .sort_by_cached_key(|packet| address_key(get_dest_ip(packet), get_dest_mac(packet))),
+        PacketOrder::Protocol => {
+            packets.sort_by_cached_key(|packet| get_sender_receiver(packet).1.pop())
+        }
+        PacketOrder::Length => packets.sort_by_key(|packet| packet.get_meta().wire_length),
+    }
+
+    if sort.descending {
+        packets.reverse();
+    }
+}
+
+/// Get the key sorting packets by address: IP addresses by value, then MAC addresses
+fn address_key(ip: Option<String>, mac: Option<String>) -> (Option<IpAddr>, Option<String>) {
+    (ip.and_then(|ip| ip.parse().ok()), mac)
+}
+
+/// Notifications of the packets collected, sent in batches at most once per interval
+#[derive(Debug, Default)]
+pub struct PacketsNotifier {
+    pending: usize,
+    last: Option<Instant>,
+}
+
+/// Packets collected since the previous notification, with the number of all of them
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PacketsReceived {
+    pub count: usize,
+    pub total: usize,
+}
+
+impl PacketsNotifier {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Count a packet collected, getting the number of packets to notify when the interval elapsed
+    pub fn receive(&mut self, now: Instant) -> Option<usize> {
+        self.pending += 1;
+        self.poll(now)
+    }
+
+    /// Get the number of packets to notify, if any, when the interval elapsed
+    pub fn poll(&mut self, now: Instant) -> Option<usize> {
+        match self.last {
+            Some(last) if now.saturating_duration_since(last) < NOTIFICATION_INTERVAL => None,
+            _ => self.flush(now),
+        }
+    }
+
+    /// Get the number of packets to notify, if any, without waiting for the interval
+    pub fn flush(&mut self, now: Instant) -> Option<usize> {
+        if self.pending == 0 {
+            return None;
+        }
+
+        self.last = Some(now);
+        Some(std::mem::take(&mut self.pending))
+    }
 }
 
 fn get_bounded_type_filter_index_iter<'a>(
@@ -299,8 +963,58 @@ fn get_bounded_type_filter_index_iter<'a>(
         FilterNamesValues::HTTP => {
             Ok(get_slice(&packets_collection.http_packets, start, end).iter())
         }
+        FilterNamesValues::HTTP2 => {
+            Ok(get_slice(&packets_collection.http2_packets, start, end).iter())
+        }
         FilterNamesValues::TLS => Ok(get_slice(&packets_collection.tls_packets, start, end).iter()),
+        FilterNamesValues::QUIC => {
+            Ok(get_slice(&packets_collection.quic_packets, start, end).iter())
+        }
         FilterNamesValues::DNS => Ok(get_slice(&packets_collection.dns_packets, start, end).iter()),
+        FilterNamesValues::PTP => Ok(get_slice(&packets_collection.ptp_packets, start, end).iter()),
+        FilterNamesValues::DHCP => {
+            Ok(get_slice(&packets_collection.dhcp_packets, start, end).iter())
+        }
+        FilterNamesValues::GOOSE => {
+            Ok(get_slice(&packets_collection.goose_packets, start, end).iter())
+        }
+        FilterNamesValues::SV => Ok(get_slice(&packets_collection.sv_packets, start, end).iter()),
+        FilterNamesValues::PROFINET => {
+            Ok(get_slice(&packets_collection.profinet_packets, start, end).iter())
+        }
+        FilterNamesValues::ETHERCAT => {
+            Ok(get_slice(&packets_collection.ethercat_packets, start, end).iter())
+        }
+        FilterNamesValues::S7COMM => {
+            Ok(get_slice(&packets_collection.s7comm_packets, start, end).iter())
+        }
+        FilterNamesValues::ISCSI => {
+            Ok(get_slice(&packets_collection.iscsi_packets, start, end).iter())
+        }
+        FilterNamesValues::NVME_TCP => {
+            Ok(get_slice(&packets_collection.nvme_tcp_packets, start, end).iter())
+        }
+        FilterNamesValues::CQL => Ok(get_slice(&packets_collection.cql_packets, start, end).iter()),
+        FilterNamesValues::KAFKA => {
+            Ok(get_slice(&packets_collection.kafka_packets, start, end).iter())
+        }
+        FilterNamesValues::ZOOKEEPER => {
+            Ok(get_slice(&packets_collection.zookeeper_packets, start, end).iter())
+        }
+        FilterNamesValues::CUSTOM => {
+            Ok(get_slice(&packets_collection.custom_packets, start, end).iter())
+        }
+        FilterNamesValues::SNMP => {
+            Ok(get_slice(&packets_collection.snmp_packets, start, end).iter())
+        }
+        FilterNamesValues::GTP => Ok(get_slice(&packets_collection.gtp_packets, start, end).iter()),
+        FilterNamesValues::ESP => Ok(get_slice(&packets_collection.esp_packets, start, end).iter()),
+        FilterNamesValues::AH => Ok(get_slice(&packets_collection.ah_packets, start, end).iter()),
+        FilterNamesValues::IKE => Ok(get_slice(&packets_collection.ike_packets, start, end).iter()),
+        FilterNamesValues::WIREGUARD => {
+            Ok(get_slice(&packets_collection.wireguard_packets, start, end).iter())
+        }
+        FilterNamesValues::NTP => Ok(get_slice(&packets_collection.ntp_packets, start, end).iter()),
         _ => {
             warn!("Unknown filter type: {}", index_name);
             Err(SniffingError::UnknownFilterType(format!(
@@ -390,8 +1104,30 @@ pub fn apply_layer_type_filter(
         FilterNamesValues::ICMP => Ok(contains_icmp(packet)),
         FilterNamesValues::ICMPV6 => Ok(contains_icmp6(packet)),
         FilterNamesValues::HTTP => Ok(contains_http(packet)),
+        FilterNamesValues::HTTP2 => Ok(contains_http2(packet)),
         FilterNamesValues::TLS => Ok(contains_tls(packet)),
+        FilterNamesValues::QUIC => Ok(contains_quic(packet)),
         FilterNamesValues::DNS => Ok(contains_dns(packet)),
+        FilterNamesValues::PTP => Ok(contains_ptp(packet)),
+        FilterNamesValues::DHCP => Ok(contains_dhcp(packet)),
+        FilterNamesValues::GOOSE => Ok(contains_goose(packet)),
+        FilterNamesValues::SV => Ok(contains_sv(packet)),
+        FilterNamesValues::PROFINET => Ok(contains_profinet(packet)),
+        FilterNamesValues::ETHERCAT => Ok(contains_ethercat(packet)),
+        FilterNamesValues::S7COMM => Ok(contains_s7comm(packet)),
+        FilterNamesValues::ISCSI => Ok(contains_iscsi(packet)),
+        FilterNamesValues::NVME_TCP => Ok(contains_nvme_tcp(packet)),
+        FilterNamesValues::CQL => Ok(contains_cql(packet)),
+        FilterNamesValues::KAFKA => Ok(contains_kafka(packet)),
+        FilterNamesValues::ZOOKEEPER => Ok(contains_zookeeper(packet)),
+        FilterNamesValues::CUSTOM => Ok(contains_custom(packet)),
+        FilterNamesValues::SNMP => Ok(contains_snmp(packet)),
+        FilterNamesValues::GTP => Ok(contains_gtp(packet)),
+        FilterNamesValues::ESP => Ok(contains_esp(packet)),
+        FilterNamesValues::AH => Ok(contains_ah(packet)),
+        FilterNamesValues::IKE => Ok(contains_ike(packet)),
+        FilterNamesValues::WIREGUARD => Ok(contains_wireguard(packet)),
+        FilterNamesValues::NTP => Ok(contains_ntp(packet)),
 
         _ => {
             warn!("Unknown filter type: {}", name);
@@ -473,6 +1209,16 @@ pub fn apply_specific_filter<'a>(
             );
             Ok(())
         }
+        FilterNamesValues::INTERFACE => {
+            filter_by_interface(
+                &packets_collection.interface_index,
+                end,
+                value,
+                is_index_used,
+                filtered_packets,
+            );
+            Ok(())
+        }
         _ => {
             warn!("Unknown filter type: {}", name);
             Err(SniffingError::UnknownFilterType(format!(
@@ -705,12 +1451,49 @@ pub fn filter_by_dst_port<'a>(
     }
 }
 
+/// Filter collected packets by the interface they were received from
+pub fn filter_by_interface<'a>(
+    index: &'a BTreeMap<String, Vec<Arc<ParsedPacket>>>,
+    end: usize,
+    interface: &'a str,
+    is_index_used: bool,
+    filtered_packets: &mut Vec<Arc<ParsedPacket>>,
+) {
+    if filtered_packets.is_empty() && !is_index_used {
+        return;
+    }
+
+    let interface_packets = index.get(interface).map(Vec::as_slice).unwrap_or(&[]);
+
+    if !is_index_used {
+        // Packets of an interface are sorted by id, like all the collected ones
+        let mut counter = 0;
+        *filtered_packets = filtered_packets
+            .iter()
+            .filter(|p| {
+                interface_packets
+                    .binary_search_by_key(&p.get_id(), |packet| packet.get_id())
+                    .is_ok()
+            })
+            .map(Arc::clone)
+            .take_while(|_| {
+                counter += 1;
+                counter <= end
+            })
+            .collect();
+    } else {
+        filtered_packets.extend_from_slice(interface_packets);
+    }
+}
+
 #[cfg(test)]
 pub mod tests {
     use std::net::Ipv6Addr;
+    use std::time::{Duration, Instant};
     use std::{net::Ipv4Addr, sync::Arc};
 
     use pnet::util::MacAddr;
+    use sniffer_parser::serializable_packet::bytes::FrameBytes;
     use sniffer_parser::serializable_packet::network::SerializableIpv6Packet;
     use sniffer_parser::serializable_packet::transport::SerializableUdpPacket;
     use sniffer_parser::serializable_packet::{
@@ -720,12 +1503,15 @@ pub mod tests {
             get_dest_ip, get_dest_mac, get_dest_port, get_source_ip, get_source_mac,
             get_source_port,
         },
-        ParsedPacket, SerializableEthernetPacket, SerializablePacket,
+        PacketMeta, ParsedPacket, SerializableEthernetPacket, SerializablePacket,
     };
 
     use crate::SniffingError;
 
-    use super::{get_packets_internal, FilterNamesValues, PacketsCollection};
+    use super::{
+        get_packets_internal, get_packets_window, FilterNamesValues, PacketFilter, PacketOrder,
+        PacketSort, PacketsCollection, PacketsNotifier, NOTIFICATION_INTERVAL,
+    };
 
     const SOURCE_IP: &str = "10.10.10.10";
     const DEST_IP: &str = "11.11.11.11";
@@ -740,13 +1526,7 @@ pub mod tests {
             ("RandomUnknownFilterType", "random"),
         ];
 
-        match get_packets_internal(
-            0,
-            100,
-            &filters_type,
-            &filters_value,
-            &mut PacketsCollection::new(),
-        ) {
+        match get_packets_internal(&filters_type, &filters_value, &mut PacketsCollection::new()) {
             Err(SniffingError::UnknownFilterType(str)) => {
                 assert_eq!(str, "Unknown filter type: RandomUnknownFilterType")
             }
@@ -768,8 +1548,6 @@ pub mod tests {
         )];
 
         match get_packets_internal(
-            0,
-            100,
             &filters_type,
             &filters_value,
             &mut build_test_packets_collection(parsed_packets),
@@ -793,8 +1571,6 @@ pub mod tests {
         )];
 
         match get_packets_internal(
-            0,
-            100,
             &filters_type,
             &filters_value,
             &mut build_test_packets_collection(parsed_packets),
@@ -824,8 +1600,6 @@ pub mod tests {
         )];
 
         match get_packets_internal(
-            0,
-            100,
             &filters_type,
             &filters_value,
             &mut build_test_packets_collection(parsed_packets),
@@ -858,8 +1632,6 @@ pub mod tests {
         )];
 
         match get_packets_internal(
-            0,
-            100,
             &filters_type,
             &filters_value,
             &mut build_test_packets_collection(parsed_packets),
@@ -888,8 +1660,6 @@ pub mod tests {
         )];
 
         match get_packets_internal(
-            0,
-            100,
             &filters_type,
             &filters_value,
             &mut build_test_packets_collection(parsed_packets),
@@ -918,8 +1688,6 @@ pub mod tests {
         )];
 
         match get_packets_internal(
-            0,
-            100,
             &filters_type,
             &filters_value,
             &mut build_test_packets_collection(parsed_packets),
@@ -960,8 +1728,6 @@ pub mod tests {
         ];
 
         match get_packets_internal(
-            0,
-            100,
             &filters_type,
             &filters_value,
             &mut build_test_packets_collection(parsed_packets),
@@ -1004,8 +1770,6 @@ pub mod tests {
         //println!("{:#?}", build_test_packets_collection(parsed_packets.clone()));
 
         match get_packets_internal(
-            0,
-            100,
             &filters_type,
             &filters_value,
             &mut build_test_packets_collection(parsed_packets),
@@ -1018,6 +1782,178 @@ pub mod tests {
         }
     }
 
+    #[test]
+    fn interface_selective_filter_with_results() {
+        let filters_type = Vec::new();
+        let mut packet_collection = PacketsCollection::new();
+        for (source_ip, interface) in [
+            (Ipv4Addr::new(10, 10, 10, 10), "eth0"),
+            (Ipv4Addr::new(12, 12, 12, 12), "eth0"),
+            (Ipv4Addr::new(10, 10, 10, 10), "wlan0"),
+        ] {
+            let parsed_packet = build_test_parsed_packet(
+                MacAddr::new(10, 10, 10, 10, 10, 10),
+                MacAddr::new(11, 11, 11, 11, 11, 11),
+                source_ip,
+                Ipv4Addr::new(11, 11, 11, 11),
+                SOURCE_PORT,
+                DEST_PORT,
+            );
+            packet_collection.insert(Arc::new(parsed_packet), Some(interface));
+        }
+
+        let filters_value = vec![
+            (FilterNamesValues::INTERFACE, "eth0"),
+            (FilterNamesValues::SRC_IP, SOURCE_IP),
+        ];
+        match get_packets_internal(&filters_type, &filters_value, &mut packet_collection) {
+            Ok(single) => {
+                assert_eq!(single.len(), 1);
+                assert_eq!(get_source_ip(single.get(0).unwrap()).unwrap(), SOURCE_IP);
+            }
+            _ => unreachable!(),
+        }
+
+        let filters_value = vec![(FilterNamesValues::INTERFACE, "lo")];
+        match get_packets_internal(&filters_type, &filters_value, &mut packet_collection) {
+            Ok(empty) => assert!(empty.is_empty()),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn window_of_sorted_packets() {
+        let mut packet_collection = PacketsCollection::new();
+        for source_ip in [
+            Ipv4Addr::new(10, 10, 10, 10),
+            Ipv4Addr::new(9, 9, 9, 9),
+            Ipv4Addr::new(12, 12, 12, 12),
+        ] {
+            let parsed_packet = build_test_parsed_packet(
+                MacAddr::new(10, 10, 10, 10, 10, 10),
+                MacAddr::new(11, 11, 11, 11, 11, 11),
+                source_ip,
+                Ipv4Addr::new(11, 11, 11, 11),
+                SOURCE_PORT,
+                DEST_PORT,
+            );
+            packet_collection.insert(Arc::new(parsed_packet), None);
+        }
+
+        let mut window = |offset: usize, filter: &PacketFilter, sort: Option<PacketSort>| {
+            let window =
+                get_packets_window(offset, 1, filter, None, sort, &mut packet_collection).unwrap();
+            assert_eq!(window.offset, offset);
+            assert_eq!(window.packets.len(), 1);
+            (window.total, get_source_ip(&window.packets[0]).unwrap())
+        };
+
+        let unfiltered = PacketFilter::default();
+        assert_eq!(window(1, &unfiltered, None), (3, "9.9.9.9".to_owned()));
+
+        // Addresses are sorted by value, not as text
+        let mut sort = PacketSort {
+            by: PacketOrder::Source,
+            descending: false,
+        };
+        assert_eq!(
+            window(0, &unfiltered, Some(sort)),
+            (3, "9.9.9.9".to_owned())
+        );
+        assert_eq!(
+            window(1, &unfiltered, Some(sort)),
+            (3, SOURCE_IP.to_owned())
+        );
+        sort.descending = true;
+        assert_eq!(
+            window(0, &unfiltered, Some(sort)),
+            (3, "12.12.12.12".to_owned())
+        );
+
+        let filter = PacketFilter {
+            types: vec![FilterNamesValues::TCP.to_owned()],
+            values: vec![(FilterNamesValues::DST_IP.to_owned(), DEST_IP.to_owned())],
+            expression: None,
+        };
+        assert_eq!(window(2, &filter, Some(sort)), (3, "9.9.9.9".to_owned()));
+        sort.by = PacketOrder::Id;
+        assert_eq!(window(1, &filter, Some(sort)), (3, "9.9.9.9".to_owned()));
+
+        // Windows past the end of the packets are empty
+        let past_end =
+            get_packets_window(3, 10, &filter, None, None, &mut packet_collection).unwrap();
+        assert_eq!(past_end.total, 3);
+        assert!(past_end.packets.is_empty());
+    }
+
+    #[test]
+    fn replaced_packet_reindexed() {
+        let mut packet_collection = PacketsCollection::new();
+        let packet = build_test_parsed_packet(
+            MacAddr::new(10, 10, 10, 10, 10, 10),
+            MacAddr::new(11, 11, 11, 11, 11, 11),
+            Ipv4Addr::new(10, 10, 10, 10),
+            Ipv4Addr::new(11, 11, 11, 11),
+            SOURCE_PORT,
+            DEST_PORT,
+        );
+        for id in 0..3 {
+            packet_collection.insert(Arc::new(with_id(&packet, id)), Some("eth0"));
+        }
+
+        // The same frame parsed again with its application layer
+        let mut parsed = with_id(&packet, 1);
+        parsed.set_application_layer_packet(Some(SerializablePacket::MalformedPacket(
+            "Malformed Packet".to_owned(),
+        )));
+        packet_collection.replace(Arc::new(parsed));
+
+        let parsed = |packets: &[Arc<ParsedPacket>]| {
+            packets
+                .iter()
+                .map(|packet| packet.get_application_layer_packet().is_some())
+                .collect::<Vec<bool>>()
+        };
+        assert_eq!(parsed(&packet_collection.packets), [false, true, false]);
+        assert_eq!(
+            parsed(&packet_collection.source_ip_index[SOURCE_IP]),
+            [false, true, false]
+        );
+        assert_eq!(
+            parsed(&packet_collection.interface_index["eth0"]),
+            [false, true, false]
+        );
+        assert_eq!(parsed(&packet_collection.tcp_packets), [false, true, false]);
+        assert_eq!(parsed(&packet_collection.malformed_packets), [true]);
+        assert_eq!(packet_collection.malformed_packets[0].get_id(), 1);
+
+        packet_collection.replace(Arc::new(with_id(&packet, 1)));
+        assert!(packet_collection.malformed_packets.is_empty());
+        assert_eq!(
+            parsed(&packet_collection.tcp_packets),
+            [false, false, false]
+        );
+    }
+
+    #[test]
+    fn packets_notified_in_batches() {
+        let start = Instant::now();
+        let mut notifier = PacketsNotifier::new();
+
+        assert_eq!(notifier.poll(start), None);
+        assert_eq!(notifier.receive(start), Some(1));
+        assert_eq!(notifier.receive(start), None);
+        assert_eq!(notifier.receive(start + NOTIFICATION_INTERVAL / 2), None);
+        assert_eq!(notifier.poll(start + NOTIFICATION_INTERVAL), Some(2));
+        assert_eq!(notifier.poll(start + NOTIFICATION_INTERVAL * 3), None);
+
+        // Packets left are notified when the capture ends
+        assert_eq!(notifier.receive(start + NOTIFICATION_INTERVAL * 3), Some(1));
+        assert_eq!(notifier.receive(start + NOTIFICATION_INTERVAL * 3), None);
+        assert_eq!(notifier.flush(start + NOTIFICATION_INTERVAL * 3), Some(1));
+        assert_eq!(notifier.flush(start + NOTIFICATION_INTERVAL * 3), None);
+    }
+
     // Utils
 
     fn build_test_packets_collection(parsed_packets: Vec<ParsedPacket>) -> PacketsCollection {
@@ -1095,8 +2031,10 @@ pub mod tests {
             SerializableEthernetPacket {
                 destination: dest_mac,
                 source: source_mac,
+                destination_vendor: None,
+                source_vendor: None,
                 ethertype: "Ipv4".to_owned(),
-                payload: Vec::new(),
+                payload: FrameBytes::default(),
             },
         )));
 
@@ -1115,6 +2053,9 @@ pub mod tests {
                 checksum: 1,
                 source: source_ip,
                 destination: dest_ip,
+                multicast_group: None,
+                source_location: None,
+                destination_location: None,
                 length: 1,
             },
         )));
@@ -1132,13 +2073,27 @@ pub mod tests {
                 checksum: 1,
                 urgent_ptr: 1,
                 options: Vec::new(),
+                mptcp: Vec::new(),
                 length: 1,
+                analysis: Vec::new(),
+                ack_rtt: None,
+                handshake_rtt: None,
             },
         )));
 
         parsed_packet
     }
 
+    /// Copy the layers of a packet into a new one with the given id
+    fn with_id(packet: &ParsedPacket, id: usize) -> ParsedPacket {
+        let mut with_id = ParsedPacket::with_meta(PacketMeta::new(id, Duration::ZERO));
+        with_id.set_link_layer_packet(packet.get_link_layer_packet().cloned());
+        with_id.set_network_layer_packet(packet.get_network_layer_packet().cloned());
+        with_id.set_transport_layer_packet(packet.get_transport_layer_packet().cloned());
+        with_id.set_application_layer_packet(packet.get_application_layer_packet().cloned());
+        with_id
+    }
+
     fn build_second_test_parsed_packet(
         source_mac: MacAddr,
         dest_mac: MacAddr,
@@ -1153,8 +2108,10 @@ pub mod tests {
             SerializableEthernetPacket {
                 destination: dest_mac,
                 source: source_mac,
+                destination_vendor: None,
+                source_vendor: None,
                 ethertype: "Ipv4".to_owned(),
-                payload: Vec::new(),
+                payload: FrameBytes::default(),
             },
         )));
 
@@ -1168,7 +2125,12 @@ pub mod tests {
                 hop_limit: 0,
                 source: source_ip,
                 destination: dest_ip,
+                multicast_group: None,
+                source_location: None,
+                destination_location: None,
                 length: 1,
+                extension_headers: vec![],
+                labelled_flow: None,
             },
         )));
 
@@ -0,0 +1,327 @@
+//! Export of packets as test fixtures of the parser
+//!
+//! The frames of the selected packets are written to a Rust source file as byte arrays, followed
+//! by a test replaying them in capture order through the parsing function of the link type of the
+//! capture. The test asserts the layers each packet is currently dissected into, so that it can be
+//! pasted among the tests of the `sniffer_parser` crate and adjusted to the expected dissection
+//! when turning a parser bug into a regression test.
+//!
+//! Raw frames are kept only for imported capture files. The frames of 802.11 captures are exported
+//! as captured: traffic decrypted with WPA2 credentials is replayed without them.
+
+use std::collections::BTreeSet;
+use std::fmt::Write;
+use std::fs;
+
+use serde_json::Value;
+use sniffer_parser::serializable_packet::{ParsedPacket, SerializablePacket};
+use tracing::info;
+
+use crate::capture_file::{CaptureRecord, LinkTypes};
+use crate::export::packet_comment;
+use crate::{SniffingError, SniffingState};
+
+/// Bytes written on each line of a fixture
+const BYTES_PER_LINE: usize = 15;
+
+/// Frame of a packet to export, with its capture metadata and dissection
+pub struct Fixture<'a> {
+    pub id: usize,
+    pub record: &'a CaptureRecord,
+    pub frame: &'a [u8],
+    pub packet: &'a ParsedPacket,
+}
+
+/// Generate the source of the fixtures of the given frames, together with the test replaying them
+pub fn generate_fixtures(link_type: u32, fixtures: &[Fixture]) -> String {
+    let parse_function = match link_type {
+        LinkTypes::IEEE802_11 => "parse_ieee80211_frame",
+        LinkTypes::IEEE802_11_RADIOTAP => "parse_radiotap_frame",
+        LinkTypes::PPP | LinkTypes::PPP_HDLC => "parse_ppp_frame",
+        LinkTypes::SLIP => "parse_slip_frame",
+        LinkTypes::NULL | LinkTypes::LOOP => "parse_loopback_frame",
+        LinkTypes::RAW | LinkTypes::IPV4 | LinkTypes::IPV6 => "parse_raw_ip_frame",
+        LinkTypes::LINUX_SLL => "parse_linux_sll_frame",
+        LinkTypes::LINUX_SLL2 => "parse_linux_sll2_frame",
+        _ => "parse_ethernet_frame",
+    };
+    let parse_call = |id: usize| match link_type {
+        LinkTypes::IEEE802_11 | LinkTypes::IEEE802_11_RADIOTAP => {
+            format!("{}(FRAME_{}, None, {})", parse_function, id, id)
+        }
+        _ if parse_function == "parse_ethernet_frame" => format!(
+            "{}(&EthernetPacket::new(FRAME_{}).unwrap(), {})",
+            parse_function, id, id
+        ),
+        _ => format!("{}(FRAME_{}, {})", parse_function, id, id),
+    };
+
+    let mut source = String::new();
+    let _ = writeln!(
+        source,
+        "//! Frames exported by wirefish, replayed in capture order"
+    );
+    let _ = writeln!(source);
+    let _ = writeln!(source, "use std::time::Duration;");
+    let _ = writeln!(source);
+    if parse_function == "parse_ethernet_frame" {
+        let _ = writeln!(source, "use pnet::packet::ethernet::EthernetPacket;");
+        let _ = writeln!(source);
+    }
+    let _ = writeln!(
+        source,
+        "use crate::serializable_packet::SerializablePacket;"
+    );
+    let _ = writeln!(
+        source,
+        "use crate::{{at_capture_time, cleanup_sniffing_state, {}}};",
+        parse_function
+    );
+
+    for Fixture {
+        id, frame, packet, ..
+    } in fixtures
+    {
+        let _ = writeln!(source);
+        let _ = match packet_comment(packet) {
+            Some(comment) => writeln!(source, "/// Frame {}: {}", id, comment),
+            None => writeln!(source, "/// Frame {}", id),
+        };
+        let _ = writeln!(source, "const FRAME_{}: &[u8] = &[", id);
+        for line in frame.chunks(BYTES_PER_LINE) {
+            let bytes: Vec<String> = line.iter().map(|byte| format!("0x{:02x},", byte)).collect();
+            let _ = writeln!(source, "    {}", bytes.join(" "));
+        }
+        let _ = writeln!(source, "];");
+    }
+
+    let _ = writeln!(source);
+    let _ = writeln!(source, "#[test]");
+    let _ = writeln!(source, "fn replay_frames() {{");
+    let _ = writeln!(source, "    cleanup_sniffing_state();");
+    for Fixture {
+        id, record, packet, ..
+    } in fixtures
+    {
+        let _ = writeln!(source);
+        let _ = writeln!(
+            source,
+            "    let parsed_packet = at_capture_time(Duration::new({}, {}), || {{",
+            record.seconds, record.nanoseconds
+        );
+        let _ = writeln!(source, "        {}", parse_call(*id));
+        let _ = writeln!(source, "    }});");
+
+        // The layers of the current dissection, to be adjusted to the expected one
+        let layers = [
+            ("link", packet.get_link_layer_packet()),
+            ("network", packet.get_network_layer_packet()),
+            ("transport", packet.get_transport_layer_packet()),
+            ("application", packet.get_application_layer_packet()),
+        ];
+        for (layer, layer_packet) in layers {
+            match layer_packet.and_then(layer_type) {
+                Some(layer_type) => {
+                    let _ = writeln!(source, "    assert!(matches!(");
+                    let _ = writeln!(
+                        source,
+                        "        parsed_packet.get_{}_layer_packet(),",
+                        layer
+                    );
+                    let _ = writeln!(
+                        source,
+                        "        Some(SerializablePacket::{}(_))",
+                        layer_type
+                    );
+                    let _ = writeln!(source, "    ));");
+                }
+                None => {
+                    let _ = writeln!(
+                        source,
+                        "    assert!(parsed_packet.get_{}_layer_packet().is_none());",
+                        layer
+                    );
+                }
+            }
+        }
+    }
+    let _ = writeln!(source, "}}");
+
+    source
+}
+
+/// Get the name of the variant representing a layer
+fn layer_type(packet: &SerializablePacket) -> Option<String> {
+    match serde_json::to_value(packet).ok()? {
+        Value::Object(layer) => layer.get("type")?.as_str().map(str::to_owned),
+        _ => None,
+    }
+}
+
+/// Exports the frames of the selected packets of the imported capture as test fixtures, together
+/// with a test replaying them, returning the number of fixtures exported
+#[tauri::command]
+pub fn export_fixtures(
+    path: String,
+    ids: Vec<usize>,
+    state: tauri::State<SniffingState>,
+) -> Result<usize, SniffingError> {
+    let mut offline = state.offline.lock().unwrap();
+
+    // Raw frames are kept only for imported capture files
+    let offline = offline.as_mut().ok_or_else(|| {
+        SniffingError::ExportFailed(
+            "Fixtures export is available only for imported files".to_owned(),
+        )
+    })?;
+
+    // Frames are replayed in capture order, once each
+    let ids: BTreeSet<usize> = ids.into_iter().collect();
+    let last = match ids.iter().next_back() {
+        Some(&last) if last < offline.len() => last,
+        Some(&last) => {
+            return Err(SniffingError::ExportFailed(format!(
+                "Packet {} is not in the imported capture",
+                last
+            )))
+        }
+        None => {
+            return Err(SniffingError::ExportFailed(
+                "No packets selected".to_owned(),
+            ))
+        }
+    };
+
+    // The expected layers come from the dissection of the frames
+    offline.dissect_until(
+        last + 1,
+        &state.info,
+        &state.packets,
+        &state.exchanged_packets,
+    );
+    offline.parse_applications(
+        ids.iter().copied(),
+        &state.packets,
+        &state.exchanged_packets,
+    );
+
+    let packets = state.packets.lock().unwrap();
+    let fixtures: Vec<Fixture> = ids
+        .iter()
+        .filter_map(|&id| {
+            let (record, frame) = offline.frame(id)?;
+            let packet = packets.packets.get(id)?;
+            Some(Fixture {
+                id,
+                record,
+                frame,
+                packet,
+            })
+        })
+        .collect();
+
+    fs::write(
+        &path,
+        generate_fixtures(offline.header().link_type, &fixtures),
+    )
+    .map_err(|e| SniffingError::ExportFailed(format!("Export failed: {}", e)))?;
+
+    info!("[{}] Exported {} fixtures", path, fixtures.len());
+
+    Ok(fixtures.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use pnet::packet::ethernet::EthernetPacket;
+    use sniffer_parser::parse_ethernet_frame;
+
+    use super::{generate_fixtures, Fixture};
+    use crate::capture_file::{CaptureRecord, LinkTypes};
+
+    const TCP_FRAME: &[u8] = &[
+        0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x08, 0x00, 0x45,
+        0x00, 0x00, 0x2b, 0x00, 0x00, 0x00, 0x00, 0x40, 0x06, 0x00, 0x00, 0x0a, 0x0a, 0x0a, 0x0a,
+        0x0b, 0x0b, 0x0b, 0x0b, 0x11, 0x5c, 0x01, 0xbb, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x50, 0x18, 0xff, 0xff, 0x00, 0x00, 0x00, 0x00, 0x47, 0x45, 0x54,
+    ];
+
+    #[test]
+    fn ethernet_fixtures() {
+        let packet = parse_ethernet_frame(&EthernetPacket::new(TCP_FRAME).unwrap(), 7);
+        let record = build_test_record();
+        let source = generate_fixtures(
+            LinkTypes::ETHERNET,
+            &[Fixture {
+                id: 7,
+                record: &record,
+                frame: TCP_FRAME,
+                packet: &packet,
+            }],
+        );
+
+        assert!(source.contains("/// Frame 7: IPv4, TCP"));
+        assert!(source.contains(
+            "const FRAME_7: &[u8] = &[\n    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00, 0x00, 0x00, \
+             0x00, 0x00, 0x01, 0x08, 0x00, 0x45,\n"
+        ));
+        assert!(source.contains(
+            "    0x00, 0x50, 0x18, 0xff, 0xff, 0x00, 0x00, 0x00, 0x00, 0x47, 0x45, 0x54,\n];"
+        ));
+        assert!(source.contains(
+            "at_capture_time(Duration::new(1700000000, 250), || {\n        \
+             parse_ethernet_frame(&EthernetPacket::new(FRAME_7).unwrap(), 7)\n    });"
+        ));
+        assert!(source.contains("Some(SerializablePacket::TcpPacket(_))"));
+    }
+
+    #[test]
+    fn ieee80211_fixtures() {
+        let packet = parse_ethernet_frame(&EthernetPacket::new(TCP_FRAME).unwrap(), 3);
+        let record = build_test_record();
+        let source = generate_fixtures(
+            LinkTypes::IEEE802_11_RADIOTAP,
+            &[Fixture {
+                id: 3,
+                record: &record,
+                frame: TCP_FRAME,
+                packet: &packet,
+            }],
+        );
+
+        assert!(source.contains("parse_radiotap_frame(FRAME_3, None, 3)"));
+        assert!(!source.contains("use pnet"));
+    }
+
+    #[test]
+    fn ppp_fixtures() {
+        let packet = parse_ethernet_frame(&EthernetPacket::new(TCP_FRAME).unwrap(), 5);
+        let record = build_test_record();
+        let source = generate_fixtures(
+            LinkTypes::PPP,
+            &[Fixture {
+                id: 5,
+                record: &record,
+                frame: TCP_FRAME,
+                packet: &packet,
+            }],
+        );
+
+        assert!(source
+            .contains("use crate::{at_capture_time, cleanup_sniffing_state, parse_ppp_frame};"));
+        assert!(source.contains("parse_ppp_frame(FRAME_5, 5)"));
+        assert!(!source.contains("use pnet"));
+    }
+
+    ///////////////////// Utils
+
+    fn build_test_record() -> CaptureRecord {
+        CaptureRecord {
+            offset: 40,
+            seconds: 1_700_000_000,
+            nanoseconds: 250,
+            captured_length: 57,
+            original_length: 57,
+        }
+    }
+}
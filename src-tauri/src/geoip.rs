@@ -0,0 +1,269 @@
+//! Location of the IP addresses, from MaxMind GeoIP2 or GeoLite2 databases
+//!
+//! The databases (`.mmdb` files) are optional and set by the user: the City and Country ones tell
+//! the country and the city of an address, the ASN ones its autonomous system, so that several can
+//! be set together (e.g. GeoLite2-City and GeoLite2-ASN).
+//!
+//! The packets and the conversations are located when returned to the frontend, so that setting
+//! the databases locates the ones already collected too. Private addresses, as any other address
+//! missing from the databases, have no location.
+
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+
+use maxminddb::{geoip2, Reader};
+use serde::Serialize;
+use sniffer_parser::serializable_packet::network::GeoLocation;
+use sniffer_parser::serializable_packet::{ParsedPacket, SerializablePacket};
+use tracing::info;
+
+use crate::conversations::Conversation;
+use crate::{SniffingError, SniffingState};
+
+/// Language of the names of the countries and the cities
+const NAMES_LANGUAGE: &str = "en";
+
+/// Content of a GeoIP database
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum GeoIpDatabaseKind {
+    City,
+    Country,
+    Asn,
+}
+
+impl GeoIpDatabaseKind {
+    /// Get the content of a database, given the type in its metadata (e.g. "GeoLite2-City")
+    fn from_database_type(database_type: &str) -> Option<Self> {
+        if database_type.ends_with("-City") {
+            Some(GeoIpDatabaseKind::City)
+        } else if database_type.ends_with("-Country") {
+            Some(GeoIpDatabaseKind::Country)
+        } else if database_type.ends_with("-ASN") {
+            Some(GeoIpDatabaseKind::Asn)
+        } else {
+            None
+        }
+    }
+}
+
+/// GeoIP database, as returned to the frontend
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct GeoIpDatabase {
+    pub path: String,
+    pub kind: GeoIpDatabaseKind,
+    /// Type in the metadata of the database, e.g. "GeoLite2-City"
+    pub database_type: String,
+    /// Build time of the database, as a Unix timestamp in seconds
+    pub build_epoch: u64,
+}
+
+/// Locator of the IP addresses, in the databases set by the user
+#[derive(Debug, Default)]
+pub struct GeoIp {
+    databases: Vec<(GeoIpDatabase, Reader<Vec<u8>>)>,
+}
+
+impl GeoIp {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Open the databases at the given paths
+    pub fn open(paths: &[String]) -> Result<Self, String> {
+        let databases = paths
+            .iter()
+            .map(|path| {
+                let reader = Reader::open_readfile(path).map_err(|e| format!("{}: {}", path, e))?;
+                let database_type = reader.metadata.database_type.clone();
+                let kind =
+                    GeoIpDatabaseKind::from_database_type(&database_type).ok_or_else(|| {
+                        format!("{}: unsupported database type {}", path, database_type)
+                    })?;
+
+                Ok((
+                    GeoIpDatabase {
+                        path: path.clone(),
+                        kind,
+                        database_type,
+                        build_epoch: reader.metadata.build_epoch,
+                    },
+                    reader,
+                ))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+
+        Ok(GeoIp { databases })
+    }
+
+    pub fn databases(&self) -> Vec<GeoIpDatabase> {
+        self.databases
+            .iter()
+            .map(|(database, _)| database.clone())
+            .collect()
+    }
+
+    /// Get the location of an address, if any database has it
+    pub fn locate(&self, address: IpAddr) -> Option<GeoLocation> {
+        let mut location = GeoLocation::default();
+
+        for (database, reader) in &self.databases {
+            match database.kind {
+                GeoIpDatabaseKind::City => {
+                    if let Ok(city) = reader.lookup::<geoip2::City>(address) {
+                        if let Some(country) = city.country {
+                            location.country_code = location
+                                .country_code
+                                .or_else(|| country.iso_code.map(str::to_owned));
+                            location.country = location.country.or_else(|| name(country.names));
+                        }
+                        location.city = location
+                            .city
+                            .or_else(|| city.city.and_then(|city| name(city.names)));
+                    }
+                }
+                GeoIpDatabaseKind::Country => {
+                    if let Ok(Some(country)) = reader
+                        .lookup::<geoip2::Country>(address)
+                        .map(|country| country.country)
+                    {
+                        location.country_code = location
+                            .country_code
+                            .or_else(|| country.iso_code.map(str::to_owned));
+                        location.country = location.country.or_else(|| name(country.names));
+                    }
+                }
+                GeoIpDatabaseKind::Asn => {
+                    if let Ok(asn) = reader.lookup::<geoip2::Asn>(address) {
+                        location.asn = location.asn.or(asn.autonomous_system_number);
+                        location.organization = location
+                            .organization
+                            .or_else(|| asn.autonomous_system_organization.map(str::to_owned));
+                    }
+                }
+            }
+        }
+
+        (location != GeoLocation::default()).then_some(location)
+    }
+
+    /// Fill in the locations of the addresses of the packets
+    pub fn locate_packets(&self, packets: &mut [ParsedPacket]) {
+        if self.databases.is_empty() {
+            return;
+        }
+
+        for packet in packets {
+            let network_packet = match packet.get_network_layer_packet() {
+                Some(SerializablePacket::Ipv4Packet(ipv4)) => {
+                    let mut ipv4 = ipv4.clone();
+                    ipv4.source_location = self.locate(IpAddr::V4(ipv4.source));
+                    ipv4.destination_location = self.locate(IpAddr::V4(ipv4.destination));
+                    SerializablePacket::Ipv4Packet(ipv4)
+                }
+                Some(SerializablePacket::Ipv6Packet(ipv6)) => {
+                    let mut ipv6 = ipv6.clone();
+                    ipv6.source_location = self.locate(IpAddr::V6(ipv6.source));
+                    ipv6.destination_location = self.locate(IpAddr::V6(ipv6.destination));
+                    SerializablePacket::Ipv6Packet(ipv6)
+                }
+                _ => continue,
+            };
+            packet.set_network_layer_packet(Some(network_packet));
+        }
+    }
+
+    /// Fill in the locations of the endpoints of the conversations
+    pub fn locate_conversations(&self, conversations: &mut [Conversation]) {
+        if self.databases.is_empty() {
+            return;
+        }
+
+        for conversation in conversations {
+            conversation.initiator_location = self.locate(conversation.initiator);
+            conversation.responder_location = self.locate(conversation.responder);
+        }
+    }
+}
+
+/// Get the name in English, among the names in each language
+fn name(names: Option<BTreeMap<&str, &str>>) -> Option<String> {
+    names?.get(NAMES_LANGUAGE).map(|name| (*name).to_owned())
+}
+
+/// Sets the GeoIP databases (.mmdb files) locating the IP addresses, replacing the previous ones,
+/// an empty list disabling the locations
+#[tauri::command]
+pub fn set_geoip_databases(
+    paths: Vec<String>,
+    state: tauri::State<SniffingState>,
+) -> Result<Vec<GeoIpDatabase>, SniffingError> {
+    let geoip = GeoIp::open(&paths).map_err(|e| {
+        SniffingError::InvalidGeoIpDatabase(format!("Invalid GeoIP database: {}", e))
+    })?;
+    let databases = geoip.databases();
+    *state.geoip.lock().unwrap() = geoip;
+
+    info!("GeoIP databases set: {:?}", databases);
+
+    Ok(databases)
+}
+
+/// Returns the GeoIP databases locating the IP addresses
+#[tauri::command]
+pub fn get_geoip_databases(state: tauri::State<SniffingState>) -> Vec<GeoIpDatabase> {
+    state.geoip.lock().unwrap().databases()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::fs;
+
+    use super::{name, GeoIp, GeoIpDatabaseKind};
+
+    #[test]
+    fn database_kinds() {
+        assert_eq!(
+            GeoIpDatabaseKind::from_database_type("GeoLite2-City"),
+            Some(GeoIpDatabaseKind::City)
+        );
+        assert_eq!(
+            GeoIpDatabaseKind::from_database_type("GeoIP2-Country"),
+            Some(GeoIpDatabaseKind::Country)
+        );
+        assert_eq!(
+            GeoIpDatabaseKind::from_database_type("GeoLite2-ASN"),
+            Some(GeoIpDatabaseKind::Asn)
+        );
+        assert_eq!(
+            GeoIpDatabaseKind::from_database_type("GeoIP2-Anonymous-IP"),
+            None
+        );
+    }
+
+    #[test]
+    fn invalid_databases() {
+        let path = std::env::temp_dir().join("wirefish-invalid.mmdb");
+        fs::write(&path, b"not a MaxMind database").unwrap();
+
+        assert!(GeoIp::open(&[path.to_string_lossy().into_owned()]).is_err());
+        assert!(GeoIp::open(&["/nonexistent/GeoLite2-City.mmdb".to_owned()]).is_err());
+
+        // Without databases, no address is located
+        let geoip = GeoIp::open(&[]).unwrap();
+        assert!(geoip.databases().is_empty());
+        assert!(geoip.locate("8.8.8.8".parse().unwrap()).is_none());
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn english_names() {
+        let names = BTreeMap::from([("de", "Deutschland"), ("en", "Germany")]);
+        assert_eq!(name(Some(names)), Some("Germany".to_owned()));
+        assert_eq!(name(Some(BTreeMap::from([("de", "Köln")]))), None);
+        assert_eq!(name(None), None);
+    }
+}
@@ -0,0 +1,487 @@
+//! Correlation of the GTPv2-C sessions of the subscribers with their user-plane tunnels
+//!
+//! In a mobile core network, the session of a subscriber (a PDN connection) is created by a
+//! Create Session Request, answered with the tunnel endpoints allocated by the gateway, modified by
+//! Modify Bearer Requests (e.g. carrying the tunnel endpoint of the eNodeB after an attach or a
+//! handover) and ended by a Delete Session Request. The messages of a session are correlated by the
+//! control-plane TEIDs of the two peers: the F-TEIDs of the request and of the response creating
+//! the session announce them, and the header of each following message carries the one of its
+//! receiver. For each session are kept:
+//! - the subscriber: IMSI, MSISDN, APN and allocated address
+//! - its state, and the cause of the last response
+//! - its bearers, with the user-plane F-TEIDs announced for each one
+//! - the traffic of the G-PDUs sent to those F-TEIDs, uplink and downlink
+//!
+//! The messages of the sessions whose creation was not seen are ignored.
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+
+use chrono::{DateTime, Local};
+use serde::Serialize;
+use sniffer_parser::serializable_packet::application::{GtpFTeid, SerializableGtpPacket};
+use sniffer_parser::serializable_packet::{ParsedPacket, SerializablePacket};
+
+use crate::inventory::type_number;
+use crate::statistics::{Counters, Timestamp};
+use crate::SniffingState;
+
+/// GTPv2-C Message Types
+#[allow(non_snake_case)]
+mod MessageTypes {
+    pub const CREATE_SESSION_REQUEST: u8 = 32;
+    pub const CREATE_SESSION_RESPONSE: u8 = 33;
+    pub const DELETE_SESSION_RESPONSE: u8 = 37;
+}
+
+/// GTP-U message type of the packets of the subscribers
+const G_PDU: u8 = 255;
+
+/// State of a GTPv2-C session
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum GtpSessionState {
+    /// Create Session Request sent
+    Requested,
+    /// Creation accepted
+    Active,
+    /// Creation rejected
+    Rejected,
+    /// Deletion accepted
+    Deleted,
+}
+
+/// User-plane tunnel endpoint of a bearer
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct GtpTunnel {
+    pub interface: String,
+    pub address: IpAddr,
+    pub teid: u32,
+}
+
+/// Bearer of a session, with the traffic of its user-plane tunnels
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct GtpSessionBearer {
+    /// EPS bearer ID
+    pub bearer_id: u8,
+    /// Last tunnel endpoint announced on each interface
+    pub tunnels: Vec<GtpTunnel>,
+    /// Traffic sent by the subscriber
+    pub uplink: Counters,
+    /// Traffic sent to the subscriber
+    pub downlink: Counters,
+}
+
+/// GTPv2-C session of a subscriber, as returned to the frontend
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct GtpSession {
+    pub imsi: Option<String>,
+    pub msisdn: Option<String>,
+    pub apn: Option<String>,
+    /// Address allocated to the subscriber
+    pub pdn_address: Option<IpAddr>,
+    pub state: GtpSessionState,
+    /// Cause of the last response
+    pub cause: Option<String>,
+    /// Control-plane TEID of the peer requesting the session (e.g. the MME)
+    pub requester_teid: Option<u32>,
+    /// Control-plane TEID of the peer serving the session (e.g. the SGW)
+    pub responder_teid: Option<u32>,
+    pub bearers: Vec<GtpSessionBearer>,
+    pub first_seen: Timestamp,
+    pub last_seen: Timestamp,
+    /// Time between the first and the last packet, in milliseconds
+    pub duration: i64,
+}
+
+/// Address and TEID of a tunnel endpoint
+type TunnelEndpoint = (IpAddr, u32);
+
+/// Tracker of the GTPv2-C sessions of the collected packets
+#[derive(Debug, Default)]
+pub struct GtpSessions {
+    /// Sessions, in the order they were first seen
+    sessions: Vec<GtpSession>,
+    /// Index of the session of each control-plane tunnel endpoint
+    control: HashMap<TunnelEndpoint, usize>,
+    /// Indices of the session and of the bearer of each user-plane tunnel endpoint, and whether
+    /// its traffic is uplink
+    user: HashMap<TunnelEndpoint, (usize, usize, bool)>,
+}
+
+impl GtpSessions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Correlate a GTP packet of the given length with its session, if any, given the time it was
+    /// received
+    pub fn update(&mut self, packet: &ParsedPacket, bytes: usize, time: DateTime<Local>) {
+        let (source, destination) = match packet.get_network_layer_packet() {
+            Some(SerializablePacket::Ipv4Packet(ipv4)) => {
+                (IpAddr::V4(ipv4.source), IpAddr::V4(ipv4.destination))
+            }
+            Some(SerializablePacket::Ipv6Packet(ipv6)) => {
+                (IpAddr::V6(ipv6.source), IpAddr::V6(ipv6.destination))
+            }
+            _ => return,
+        };
+        let gtp = match packet.get_application_layer_packet() {
+            Some(SerializablePacket::GtpPacket(gtp)) => gtp,
+            _ => return,
+        };
+        let message_type = match type_number(&gtp.message_type) {
+            Some(message_type) => message_type,
+            None => return,
+        };
+        let time = time.timestamp_millis();
+
+        if gtp.version == 1 {
+            let tunnel = gtp
+                .teid
+                .and_then(|teid| self.user.get(&(destination, teid)));
+            if let (G_PDU, Some(&(index, bearer, uplink))) = (message_type, tunnel) {
+                let session = &mut self.sessions[index];
+                let bearer = &mut session.bearers[bearer];
+                if uplink {
+                    bearer.uplink.add(bytes);
+                } else {
+                    bearer.downlink.add(bytes);
+                }
+                touch(session, time);
+            }
+            return;
+        }
+
+        let index = if message_type == MessageTypes::CREATE_SESSION_REQUEST {
+            let sender = match sender_endpoint(gtp, source) {
+                Some(sender) => sender,
+                None => return,
+            };
+            // Retransmitted requests belong to the session they created
+            match self.control.get(&sender) {
+                Some(&index) if self.sessions[index].state == GtpSessionState::Requested => index,
+                _ => self.open(sender, time),
+            }
+        } else {
+            match gtp
+                .teid
+                .and_then(|teid| self.control.get(&(destination, teid)))
+            {
+                Some(&index) => index,
+                None => return,
+            }
+        };
+
+        if message_type == MessageTypes::CREATE_SESSION_RESPONSE {
+            if let Some(sender) = sender_endpoint(gtp, source) {
+                self.control.insert(sender, index);
+                self.sessions[index].responder_teid = Some(sender.1);
+            }
+        }
+
+        let session = &mut self.sessions[index];
+        touch(session, time);
+        if gtp.imsi.is_some() {
+            session.imsi = gtp.imsi.clone();
+        }
+        if gtp.msisdn.is_some() {
+            session.msisdn = gtp.msisdn.clone();
+        }
+        if gtp.apn.is_some() {
+            session.apn = gtp.apn.clone();
+        }
+        // Requests carry an unspecified address, unless a static one is requested
+        if let Some(address) = gtp.pdn_address.filter(|address| !address.is_unspecified()) {
+            session.pdn_address = Some(address);
+        }
+
+        for bearer in &gtp.bearers {
+            let bearer_id = match bearer.bearer_id {
+                Some(bearer_id) => bearer_id,
+                None => continue,
+            };
+            let bearers = &mut self.sessions[index].bearers;
+            let bearer_index = match bearers.iter().position(|b| b.bearer_id == bearer_id) {
+                Some(bearer_index) => bearer_index,
+                None => {
+                    bearers.push(GtpSessionBearer {
+                        bearer_id,
+                        tunnels: vec![],
+                        uplink: Counters::default(),
+                        downlink: Counters::default(),
+                    });
+                    bearers.len() - 1
+                }
+            };
+
+            for f_teid in &bearer.f_teids {
+                let (address, uplink) = match (address(f_teid), is_uplink(f_teid)) {
+                    (Some(address), Some(uplink)) => (address, uplink),
+                    _ => continue,
+                };
+                let tunnels = &mut bearers[bearer_index].tunnels;
+                tunnels.retain(|tunnel| tunnel.interface != f_teid.interface);
+                tunnels.push(GtpTunnel {
+                    interface: f_teid.interface.clone(),
+                    address,
+                    teid: f_teid.teid,
+                });
+                self.user
+                    .insert((address, f_teid.teid), (index, bearer_index, uplink));
+            }
+        }
+
+        if let Some(cause) = &gtp.cause {
+            let session = &mut self.sessions[index];
+            session.cause = Some(cause.clone());
+            let accepted = type_number(cause).is_some_and(|cause| (16..64).contains(&cause));
+            let state = match message_type {
+                MessageTypes::CREATE_SESSION_RESPONSE if accepted => GtpSessionState::Active,
+                MessageTypes::CREATE_SESSION_RESPONSE => GtpSessionState::Rejected,
+                MessageTypes::DELETE_SESSION_RESPONSE if accepted => GtpSessionState::Deleted,
+                _ => session.state,
+            };
+            session.state = state;
+
+            // The TEIDs of an ended session may be allocated to a new one
+            if matches!(state, GtpSessionState::Rejected | GtpSessionState::Deleted) {
+                self.control.retain(|_, session| *session != index);
+                self.user.retain(|_, (session, _, _)| *session != index);
+            }
+        }
+    }
+
+    /// Open a session requested by the given control-plane tunnel endpoint, returning its index
+    fn open(&mut self, requester: TunnelEndpoint, time: i64) -> usize {
+        let index = self.sessions.len();
+        self.control.insert(requester, index);
+        self.sessions.push(GtpSession {
+            imsi: None,
+            msisdn: None,
+            apn: None,
+            pdn_address: None,
+            state: GtpSessionState::Requested,
+            cause: None,
+            requester_teid: Some(requester.1),
+            responder_teid: None,
+            bearers: vec![],
+            first_seen: time,
+            last_seen: time,
+            duration: 0,
+        });
+
+        index
+    }
+
+    /// Get the sessions, in the order they were first seen
+    pub fn sessions(&self) -> Vec<GtpSession> {
+        self.sessions.clone()
+    }
+
+    pub fn clear(&mut self) {
+        self.sessions.clear();
+        self.control.clear();
+        self.user.clear();
+    }
+}
+
+fn touch(session: &mut GtpSession, time: i64) {
+    session.last_seen = session.last_seen.max(time);
+    session.duration = session.last_seen - session.first_seen;
+}
+
+/// Get the control-plane tunnel endpoint of the sender of a message: its F-TEID with the address
+/// the message is sent from, or else its first one
+fn sender_endpoint(gtp: &SerializableGtpPacket, source: IpAddr) -> Option<TunnelEndpoint> {
+    let f_teid = gtp
+        .f_teids
+        .iter()
+        .find(|f_teid| address(f_teid) == Some(source))
+        .or(gtp.f_teids.first())?;
+
+    Some((address(f_teid).unwrap_or(source), f_teid.teid))
+}
+
+fn address(f_teid: &GtpFTeid) -> Option<IpAddr> {
+    f_teid.ipv4.map(IpAddr::V4).or(f_teid.ipv6.map(IpAddr::V6))
+}
+
+/// Check if the traffic sent to a user-plane F-TEID is uplink, none for the control-plane ones
+fn is_uplink(f_teid: &GtpFTeid) -> Option<bool> {
+    match type_number(&f_teid.interface)? {
+        // eNodeB, RNC and SGW towards the PGW
+        0 | 2 | 4 => Some(false),
+        // SGW towards the radio network, and PGW
+        1 | 3 | 5 => Some(true),
+        _ => None,
+    }
+}
+
+/// Returns the GTPv2-C sessions of the collected packets, with the traffic of their bearers
+#[tauri::command]
+pub fn get_gtp_sessions(state: tauri::State<SniffingState>) -> Vec<GtpSession> {
+    state.packets.lock().unwrap().gtp_sessions.sessions()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr};
+
+    use sniffer_parser::serializable_packet::ParsedPacket;
+
+    use super::{GtpSessionState, GtpSessions, GtpTunnel};
+    use crate::test_utils::{at, udp_packet};
+
+    const MME: [u8; 4] = [10, 0, 0, 1];
+    const SGW: [u8; 4] = [10, 0, 0, 2];
+    const SGW_USER_PLANE: [u8; 4] = [10, 0, 0, 3];
+    const ENODEB: [u8; 4] = [10, 0, 1, 1];
+    const SUBSCRIBER: [u8; 4] = [100, 64, 0, 7];
+
+    #[test]
+    fn session_lifecycle_and_bearer_traffic() {
+        let mut sessions = GtpSessions::new();
+
+        let mut request = ie(1, &[0x21, 0x43, 0x65, 0x87, 0x09, 0x21, 0x43, 0xf5]);
+        request.extend(ie(71, b"\x08internet"));
+        request.extend(ie(87, &f_teid(10, 0x100, MME)));
+        request.extend(ie(79, &[0x01, 0, 0, 0, 0]));
+        request.extend(ie(93, &ie(73, &[5])));
+        sessions.update(&gtp_c(MME, SGW, 32, None, &request), 200, at(0));
+
+        let mut response = ie(2, &[16, 0]);
+        response.extend(ie(87, &f_teid(11, 0x200, SGW)));
+        let mut paa = vec![0x01];
+        paa.extend(SUBSCRIBER);
+        response.extend(ie(79, &paa));
+        let mut bearer = ie(73, &[5]);
+        bearer.extend(ie(87, &f_teid(1, 0x300, SGW_USER_PLANE)));
+        response.extend(ie(93, &bearer));
+        sessions.update(&gtp_c(SGW, MME, 33, Some(0x100), &response), 200, at(10));
+
+        // Tunnel endpoint of the eNodeB, once the radio bearer is set up
+        let mut bearer = ie(73, &[5]);
+        bearer.extend(ie(87, &f_teid(0, 0x400, ENODEB)));
+        let modify = ie(93, &bearer);
+        sessions.update(&gtp_c(MME, SGW, 34, Some(0x200), &modify), 100, at(20));
+
+        sessions.update(&g_pdu(ENODEB, SGW_USER_PLANE, 0x300), 100, at(30));
+        sessions.update(&g_pdu(SGW_USER_PLANE, ENODEB, 0x400), 1000, at(40));
+        sessions.update(&g_pdu(SGW_USER_PLANE, ENODEB, 0x400), 1000, at(50));
+        // Tunnel of another session
+        sessions.update(&g_pdu(ENODEB, SGW_USER_PLANE, 0x301), 100, at(60));
+
+        let session = &sessions.sessions()[0];
+        assert_eq!(session.state, GtpSessionState::Active);
+        assert_eq!(session.imsi.as_deref(), Some("123456789012345"));
+        assert_eq!(session.apn.as_deref(), Some("internet"));
+        assert_eq!(
+            session.pdn_address,
+            Some(IpAddr::V4(Ipv4Addr::from(SUBSCRIBER)))
+        );
+        assert_eq!(session.requester_teid, Some(0x100));
+        assert_eq!(session.responder_teid, Some(0x200));
+        let bearer = &session.bearers[0];
+        assert_eq!(bearer.bearer_id, 5);
+        assert_eq!(
+            bearer.tunnels[1],
+            GtpTunnel {
+                interface: "S1-U eNodeB GTP-U (0)".to_owned(),
+                address: IpAddr::V4(Ipv4Addr::from(ENODEB)),
+                teid: 0x400,
+            }
+        );
+        assert_eq!(bearer.uplink.packets, 1);
+        assert_eq!(bearer.downlink.bytes, 2000);
+        assert_eq!(session.duration, 50);
+
+        let delete = ie(73, &[5]);
+        sessions.update(&gtp_c(MME, SGW, 36, Some(0x200), &delete), 100, at(70));
+        let deleted = ie(2, &[16, 0]);
+        sessions.update(&gtp_c(SGW, MME, 37, Some(0x100), &deleted), 100, at(80));
+        sessions.update(&g_pdu(ENODEB, SGW_USER_PLANE, 0x300), 100, at(90));
+
+        let session = &sessions.sessions()[0];
+        assert_eq!(session.state, GtpSessionState::Deleted);
+        assert_eq!(session.cause.as_deref(), Some("Request accepted (16)"));
+        assert_eq!(session.bearers[0].uplink.packets, 1);
+        assert_eq!(session.last_seen - session.first_seen, 80);
+
+        sessions.clear();
+        assert!(sessions.sessions().is_empty());
+    }
+
+    #[test]
+    fn rejected_and_unknown_sessions() {
+        let mut sessions = GtpSessions::new();
+
+        let request = ie(87, &f_teid(10, 0x100, MME));
+        sessions.update(&gtp_c(MME, SGW, 32, None, &request), 100, at(0));
+        // Retransmission of the request
+        sessions.update(&gtp_c(MME, SGW, 32, None, &request), 100, at(5));
+        let rejected = ie(2, &[73, 0]);
+        sessions.update(&gtp_c(SGW, MME, 33, Some(0x100), &rejected), 100, at(10));
+        // Modification of a session whose creation was not seen
+        sessions.update(&gtp_c(MME, SGW, 34, Some(0x999), &[]), 100, at(20));
+
+        let all = sessions.sessions();
+        assert_eq!(all.len(), 1);
+        assert_eq!(all[0].state, GtpSessionState::Rejected);
+        assert_eq!(all[0].cause.as_deref(), Some("No resources available (73)"));
+
+        // The TEID of the rejected session is allocated to a new one
+        sessions.update(&gtp_c(MME, SGW, 32, None, &request), 100, at(30));
+        assert_eq!(sessions.sessions().len(), 2);
+    }
+
+    ///////////////////// Utils
+
+    fn ie(ie_type: u8, value: &[u8]) -> Vec<u8> {
+        let mut ie = vec![ie_type];
+        ie.extend((value.len() as u16).to_be_bytes());
+        ie.push(0);
+        ie.extend(value);
+        ie
+    }
+
+    fn f_teid(interface_type: u8, teid: u32, ipv4: [u8; 4]) -> Vec<u8> {
+        let mut f_teid = vec![0x80 | interface_type];
+        f_teid.extend(teid.to_be_bytes());
+        f_teid.extend(ipv4);
+        f_teid
+    }
+
+    fn gtp_c(
+        source: [u8; 4],
+        destination: [u8; 4],
+        message_type: u8,
+        teid: Option<u32>,
+        ies: &[u8],
+    ) -> ParsedPacket {
+        let mut message = vec![if teid.is_some() { 0x48 } else { 0x40 }, message_type, 0, 0];
+        if let Some(teid) = teid {
+            message.extend(teid.to_be_bytes());
+        }
+        message.extend([0x00, 0x00, 0x01, 0x00]);
+        message.extend(ies);
+        let length = (message.len() - 4) as u16;
+        message[2..4].copy_from_slice(&length.to_be_bytes());
+
+        udp_packet(0, (source, 2123), (destination, 2123), &message)
+    }
+
+    fn g_pdu(source: [u8; 4], destination: [u8; 4], teid: u32) -> ParsedPacket {
+        let mut message = vec![0x30, 255, 0, 20];
+        message.extend(teid.to_be_bytes());
+        message.extend([0x45, 0x00, 0x00, 0x14, 0x00, 0x00, 0x00, 0x00, 0x40, 0x11]);
+        message.extend([0x00, 0x00]);
+        message.extend(SUBSCRIBER);
+        message.extend([8, 8, 8, 8]);
+
+        udp_packet(0, (source, 2152), (destination, 2152), &message)
+    }
+}
@@ -0,0 +1,610 @@
+//! Dual-stack connection races (Happy Eyeballs) of the clients, for each destination
+//!
+//! A dual-stack client connecting to a service resolved to both IPv6 and IPv4 addresses attempts
+//! an IPv6 connection first, then an IPv4 one if the first is not established after a short delay
+//! (250 ms recommended by RFC 8305), and keeps the connection established first. A race is
+//! detected when a client, identified by its MAC address, sends SYNs to the same port of an IPv6
+//! and an IPv4 address within the race window, while the first one is still unanswered. The two
+//! addresses must be resolved from a same name by the captured DNS answers, or both be unknown to
+//! them: the destination of the race is that name, or else the pair of addresses.
+//!
+//! The family winning a race is the one whose SYN-ACK arrives first, by a margin up to the SYN-ACK
+//! of the other family, if it arrives at all. IPv4 winning most races towards a destination hints
+//! at a slow or broken IPv6 path.
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::net::IpAddr;
+
+use chrono::{DateTime, Local};
+use pnet::packet::tcp::TcpFlags;
+use pnet::util::MacAddr;
+use serde::Serialize;
+use sniffer_parser::serializable_packet::{ParsedPacket, SerializablePacket};
+use sniffer_parser::{FlowDirection, FlowKey};
+
+use crate::statistics::Timestamp;
+use crate::tls_destination::DnsNames;
+use crate::SniffingState;
+
+/// Time between the SYNs of the two families of a race at most, in microseconds
+const RACE_WINDOW: i64 = 2_000_000;
+
+/// Handshakes waiting for an answer at most, the ones waiting for longer than the pending timeout
+/// being dropped once reached
+const PENDING_SIZE: usize = 10_000;
+
+/// Time after which a handshake is no longer answered, in microseconds
+const PENDING_TIMEOUT: i64 = 10_000_000;
+
+/// Last races kept for each destination
+const RACES_PER_DESTINATION: usize = 100;
+
+/// Address family of a connection attempt
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum AddressFamily {
+    Ipv6,
+    Ipv4,
+}
+
+impl AddressFamily {
+    fn of(address: &IpAddr) -> Self {
+        match address {
+            IpAddr::V6(_) => AddressFamily::Ipv6,
+            IpAddr::V4(_) => AddressFamily::Ipv4,
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+
+    fn other(self) -> Self {
+        match self {
+            AddressFamily::Ipv6 => AddressFamily::Ipv4,
+            AddressFamily::Ipv4 => AddressFamily::Ipv6,
+        }
+    }
+}
+
+/// Race between an IPv6 and an IPv4 connection, as returned to the frontend
+///
+/// Delays are in milliseconds.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ConnectionRace {
+    pub ipv6_server: IpAddr,
+    pub ipv4_server: IpAddr,
+    /// Time of the first SYN
+    pub started: Timestamp,
+    /// Family attempted first
+    pub first: AddressFamily,
+    /// Delay of the SYN of the family attempted second
+    pub attempt_delay: f64,
+    /// Family whose SYN-ACK arrived first, none if neither arrived
+    pub winner: Option<AddressFamily>,
+    /// Time between the SYN-ACKs of the two families, none unless both arrived
+    pub margin: Option<f64>,
+    /// Round-trip times of the handshakes, none if unanswered
+    pub ipv6_handshake: Option<f64>,
+    pub ipv4_handshake: Option<f64>,
+}
+
+/// Connection races towards a destination, as returned to the frontend
+///
+/// Margins are in milliseconds.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DestinationRaces {
+    /// Name resolved to the addresses raced, or else the addresses
+    pub destination: String,
+    pub port: u16,
+    pub races: usize,
+    pub ipv6_wins: usize,
+    pub ipv4_wins: usize,
+    /// Average margin of the races won by each family, none unless the other family was answered
+    /// in some of them
+    pub ipv6_average_margin: Option<f64>,
+    pub ipv4_average_margin: Option<f64>,
+    /// Last races, the oldest first
+    pub last_races: Vec<ConnectionRace>,
+}
+
+/// Flow of a handshake, and the direction of its SYN
+type Handshake = (FlowKey, FlowDirection);
+
+/// Name, or addresses, and port of a destination
+type Destination = (String, u16);
+
+/// Handshake waiting for its SYN-ACK
+#[derive(Debug)]
+struct Attempt {
+    /// Time of the SYN, in microseconds
+    syn: i64,
+    race: Option<Destination>,
+}
+
+/// Race between the handshakes of the two families, indexed by family, with their times in
+/// microseconds
+#[derive(Debug)]
+struct Race {
+    handshakes: [Handshake; 2],
+    syns: [i64; 2],
+    syn_acks: [Option<i64>; 2],
+}
+
+impl Race {
+    fn to_serializable(&self) -> ConnectionRace {
+        let first = if self.syns[1] < self.syns[0] {
+            AddressFamily::Ipv4
+        } else {
+            AddressFamily::Ipv6
+        };
+        let winner = match self.syn_acks {
+            [Some(ipv6), Some(ipv4)] if ipv4 < ipv6 => Some(AddressFamily::Ipv4),
+            [Some(_), _] => Some(AddressFamily::Ipv6),
+            [None, Some(_)] => Some(AddressFamily::Ipv4),
+            [None, None] => None,
+        };
+        let margin = match self.syn_acks {
+            [Some(ipv6), Some(ipv4)] => Some(millis(ipv6 - ipv4).abs()),
+            _ => None,
+        };
+        let handshake = |family: usize| {
+            self.syn_acks[family].map(|syn_ack| millis(syn_ack - self.syns[family]))
+        };
+
+        ConnectionRace {
+            ipv6_server: server_endpoint(&self.handshakes[0]).0,
+            ipv4_server: server_endpoint(&self.handshakes[1]).0,
+            started: self.syns[0].min(self.syns[1]) / 1000,
+            first,
+            attempt_delay: millis(self.syns[0] - self.syns[1]).abs(),
+            winner,
+            margin,
+            ipv6_handshake: handshake(0),
+            ipv4_handshake: handshake(1),
+        }
+    }
+}
+
+/// Races towards a destination, with the wins and the sum of the margins of each family
+#[derive(Debug, Default)]
+struct TrackedDestination {
+    races: usize,
+    wins: [usize; 2],
+    margins: [(f64, usize); 2],
+    last_races: VecDeque<Race>,
+}
+
+/// Tracker of the connection races between IPv6 and IPv4 of the collected packets
+#[derive(Debug, Default)]
+pub struct HappyEyeballsTracker {
+    dns_names: DnsNames,
+    attempts: HashMap<Handshake, Attempt>,
+    /// Handshakes not racing yet, by client and server port, the oldest first
+    unraced: HashMap<(Option<MacAddr>, u16), VecDeque<Handshake>>,
+    destinations: BTreeMap<Destination, TrackedDestination>,
+}
+
+impl HappyEyeballsTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Inspect a packet, if it is a segment of a TCP handshake or a DNS response, given the time it
+    /// was received
+    pub fn update(&mut self, packet: &ParsedPacket, time: DateTime<Local>) {
+        self.dns_names.update(packet);
+
+        let (source, destination) = match packet.get_network_layer_packet() {
+            Some(SerializablePacket::Ipv4Packet(ipv4)) => {
+                (IpAddr::V4(ipv4.source), IpAddr::V4(ipv4.destination))
+            }
+            Some(SerializablePacket::Ipv6Packet(ipv6)) => {
+                (IpAddr::V6(ipv6.source), IpAddr::V6(ipv6.destination))
+            }
+            _ => return,
+        };
+        let tcp = match packet.get_transport_layer_packet() {
+            Some(SerializablePacket::TcpPacket(tcp)) if tcp.flags & TcpFlags::SYN != 0 => tcp,
+            _ => return,
+        };
+        let micros = time.timestamp_micros();
+
+        if tcp.flags & TcpFlags::ACK == 0 {
+            let client = match packet.get_link_layer_packet() {
+                Some(SerializablePacket::EthernetPacket(ethernet)) => Some(ethernet.source),
+                _ => None,
+            };
+            self.syn(
+                FlowKey::of((source, tcp.source), (destination, tcp.destination)),
+                client,
+                micros,
+            );
+        } else {
+            // The SYN-ACK is sent by the server
+            self.syn_ack(
+                FlowKey::of((destination, tcp.destination), (source, tcp.source)),
+                micros,
+            );
+        }
+    }
+
+    fn syn(&mut self, handshake: Handshake, client: Option<MacAddr>, micros: i64) {
+        // SYNs sent again keep the time of the first one
+        if self.attempts.contains_key(&handshake) {
+            return;
+        }
+        self.prune(micros);
+
+        let (server, port) = server_endpoint(&handshake);
+        let family = AddressFamily::of(&server);
+        let attempts = &self.attempts;
+        let dns_names = &self.dns_names;
+        let unraced = self.unraced.entry((client, port)).or_default();
+
+        // Handshakes answered, racing or out of the window can no longer race
+        unraced.retain(|unraced| {
+            attempts.get(unraced).is_some_and(|attempt| {
+                attempt.race.is_none() && micros - attempt.syn <= RACE_WINDOW
+            })
+        });
+        let opponent = unraced.iter().enumerate().find_map(|(i, unraced)| {
+            let other = server_endpoint(unraced).0;
+            if AddressFamily::of(&other) == family {
+                return None;
+            }
+            let (ipv6, ipv4) = match family {
+                AddressFamily::Ipv6 => (server, other),
+                AddressFamily::Ipv4 => (other, server),
+            };
+            shared_destination(dns_names, &ipv6, &ipv4).map(|name| (i, name))
+        });
+
+        let (i, name) = match opponent {
+            Some(opponent) => opponent,
+            None => {
+                unraced.push_back(handshake);
+                self.attempts.insert(
+                    handshake,
+                    Attempt {
+                        syn: micros,
+                        race: None,
+                    },
+                );
+                return;
+            }
+        };
+        let other = unraced.remove(i).unwrap();
+        let destination = (name, port);
+
+        let other_attempt = self.attempts.get_mut(&other).unwrap();
+        other_attempt.race = Some(destination.clone());
+        let mut race = Race {
+            handshakes: [handshake, other],
+            syns: [micros, other_attempt.syn],
+            syn_acks: [None, None],
+        };
+        if family == AddressFamily::Ipv4 {
+            race.handshakes.swap(0, 1);
+            race.syns.swap(0, 1);
+        }
+        self.attempts.insert(
+            handshake,
+            Attempt {
+                syn: micros,
+                race: Some(destination.clone()),
+            },
+        );
+
+        let tracked = self.destinations.entry(destination).or_default();
+        tracked.races += 1;
+        if tracked.last_races.len() >= RACES_PER_DESTINATION {
+            tracked.last_races.pop_front();
+        }
+        tracked.last_races.push_back(race);
+    }
+
+    fn syn_ack(&mut self, handshake: Handshake, micros: i64) {
+        // SYN-ACKs sent again find no handshake waiting
+        let destination = match self.attempts.remove(&handshake) {
+            Some(Attempt {
+                race: Some(destination),
+                ..
+            }) => destination,
+            _ => return,
+        };
+        let tracked = match self.destinations.get_mut(&destination) {
+            Some(tracked) => tracked,
+            None => return,
+        };
+
+        let family = AddressFamily::of(&server_endpoint(&handshake).0);
+        let (i, other) = (family.index(), family.other().index());
+        let race = match tracked
+            .last_races
+            .iter_mut()
+            .find(|race| race.handshakes[i] == handshake)
+        {
+            Some(race) => race,
+            None => return,
+        };
+        race.syn_acks[i] = Some(micros);
+
+        match race.syn_acks[other] {
+            None => tracked.wins[i] += 1,
+            Some(winner) => {
+                let margins = &mut tracked.margins[other];
+                margins.0 += millis(micros - winner);
+                margins.1 += 1;
+            }
+        }
+    }
+
+    /// Drop the handshakes no longer answered, once too many are waiting
+    fn prune(&mut self, micros: i64) {
+        if self.attempts.len() < PENDING_SIZE {
+            return;
+        }
+
+        self.attempts
+            .retain(|_, attempt| micros - attempt.syn < PENDING_TIMEOUT);
+        let attempts = &self.attempts;
+        self.unraced.retain(|_, unraced| {
+            unraced.retain(|handshake| attempts.contains_key(handshake));
+            !unraced.is_empty()
+        });
+    }
+
+    /// Get the connection races of each destination, sorted by destination and port
+    pub fn races(&self) -> Vec<DestinationRaces> {
+        self.destinations
+            .iter()
+            .map(|((destination, port), tracked)| {
+                let average = |(sum, count): (f64, usize)| (count > 0).then(|| sum / count as f64);
+                DestinationRaces {
+                    destination: destination.clone(),
+                    port: *port,
+                    races: tracked.races,
+                    ipv6_wins: tracked.wins[AddressFamily::Ipv6.index()],
+                    ipv4_wins: tracked.wins[AddressFamily::Ipv4.index()],
+                    ipv6_average_margin: average(tracked.margins[AddressFamily::Ipv6.index()]),
+                    ipv4_average_margin: average(tracked.margins[AddressFamily::Ipv4.index()]),
+                    last_races: tracked
+                        .last_races
+                        .iter()
+                        .map(Race::to_serializable)
+                        .collect(),
+                }
+            })
+            .collect()
+    }
+
+    pub fn clear(&mut self) {
+        self.dns_names.clear();
+        self.attempts.clear();
+        self.unraced.clear();
+        self.destinations.clear();
+    }
+}
+
+/// Get the destination of a race between two addresses: a name resolved to both, the most
+/// recently resolved to the IPv6 one, or the addresses if neither was resolved
+fn shared_destination(dns_names: &DnsNames, ipv6: &IpAddr, ipv4: &IpAddr) -> Option<String> {
+    let (ipv6_names, ipv4_names) = (dns_names.names(ipv6), dns_names.names(ipv4));
+    if ipv6_names.is_empty() && ipv4_names.is_empty() {
+        return Some(format!("{} / {}", ipv6, ipv4));
+    }
+
+    ipv6_names
+        .iter()
+        .find(|name| ipv4_names.contains(name))
+        .cloned()
+}
+
+/// Get the server endpoint of a handshake, the destination of its SYN
+fn server_endpoint((key, direction): &Handshake) -> (IpAddr, u16) {
+    let (lower, upper) = key.endpoints();
+    match direction {
+        FlowDirection::Forward => upper,
+        FlowDirection::Reverse => lower,
+    }
+}
+
+fn millis(micros: i64) -> f64 {
+    micros as f64 / 1000.0
+}
+
+/// Returns the races between IPv6 and IPv4 connections of the dual-stack clients, for each
+/// destination
+#[tauri::command]
+pub fn get_happy_eyeballs_races(state: tauri::State<SniffingState>) -> Vec<DestinationRaces> {
+    state.packets.lock().unwrap().happy_eyeballs.races()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+    use pnet::packet::tcp::TcpFlags;
+    use sniffer_parser::serializable_packet::ParsedPacket;
+
+    use super::{AddressFamily, ConnectionRace, HappyEyeballsTracker};
+    use crate::test_utils::{at, tcp_packet, udp_packet};
+
+    const CLIENT_V4: [u8; 4] = [10, 0, 0, 10];
+    const CLIENT_V6: [u8; 16] = [
+        0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x10,
+    ];
+    const SERVER_V4: [u8; 4] = [192, 0, 2, 80];
+    const SERVER_V6: [u8; 16] = [
+        0x20, 0x01, 0x0d, 0xb8, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x80,
+    ];
+    const OTHER_SERVER_V4: [u8; 4] = [198, 51, 100, 80];
+    const DNS_SERVER: [u8; 4] = [10, 0, 0, 53];
+
+    #[test]
+    fn races_between_families() {
+        let mut tracker = HappyEyeballsTracker::new();
+        let syn = TcpFlags::SYN;
+        let syn_ack = TcpFlags::SYN | TcpFlags::ACK;
+        tracker.update(&dns_response(SERVER_V4, SERVER_V6), at(0));
+
+        // IPv6 attempted first, IPv4 after 250 ms, IPv4 answered first and IPv6 30 ms later
+        tracker.update(
+            &tcp_packet(0, (CLIENT_V6, 40000), (SERVER_V6, 443), syn),
+            at(1000),
+        );
+        tracker.update(
+            &tcp_packet(0, (CLIENT_V4, 40001), (SERVER_V4, 443), syn),
+            at(1250),
+        );
+        tracker.update(
+            &tcp_packet(0, (SERVER_V4, 443), (CLIENT_V4, 40001), syn_ack),
+            at(1270),
+        );
+        tracker.update(
+            &tcp_packet(0, (SERVER_V6, 443), (CLIENT_V6, 40000), syn_ack),
+            at(1300),
+        );
+
+        // IPv6 answered first, IPv4 never
+        tracker.update(
+            &tcp_packet(0, (CLIENT_V6, 40002), (SERVER_V6, 443), syn),
+            at(5000),
+        );
+        tracker.update(
+            &tcp_packet(0, (CLIENT_V4, 40003), (SERVER_V4, 443), syn),
+            at(5250),
+        );
+        tracker.update(
+            &tcp_packet(0, (SERVER_V6, 443), (CLIENT_V6, 40002), syn_ack),
+            at(5260),
+        );
+
+        // IPv6 answered before the IPv4 attempt: no race
+        tracker.update(
+            &tcp_packet(0, (CLIENT_V6, 40004), (SERVER_V6, 443), syn),
+            at(9000),
+        );
+        tracker.update(
+            &tcp_packet(0, (SERVER_V6, 443), (CLIENT_V6, 40004), syn_ack),
+            at(9010),
+        );
+        tracker.update(
+            &tcp_packet(0, (CLIENT_V4, 40005), (SERVER_V4, 443), syn),
+            at(9100),
+        );
+
+        // Attempts too far apart, or towards an address resolved from another name
+        tracker.update(
+            &tcp_packet(0, (CLIENT_V6, 40006), (SERVER_V6, 443), syn),
+            at(20000),
+        );
+        tracker.update(
+            &tcp_packet(0, (CLIENT_V4, 40007), (OTHER_SERVER_V4, 443), syn),
+            at(20100),
+        );
+        tracker.update(
+            &tcp_packet(0, (CLIENT_V4, 40008), (SERVER_V4, 443), syn),
+            at(23000),
+        );
+
+        let races = tracker.races();
+        assert_eq!(races.len(), 1);
+        let destination = &races[0];
+        assert_eq!(
+            (destination.destination.as_str(), destination.port),
+            ("www.example.com", 443)
+        );
+        assert_eq!(destination.races, 2);
+        assert_eq!((destination.ipv6_wins, destination.ipv4_wins), (1, 1));
+        assert_eq!(destination.ipv6_average_margin, None);
+        assert_eq!(destination.ipv4_average_margin, Some(30.0));
+        assert_eq!(
+            destination.last_races,
+            vec![
+                ConnectionRace {
+                    ipv6_server: v6(SERVER_V6),
+                    ipv4_server: v4(SERVER_V4),
+                    started: 1_700_000_001_000,
+                    first: AddressFamily::Ipv6,
+                    attempt_delay: 250.0,
+                    winner: Some(AddressFamily::Ipv4),
+                    margin: Some(30.0),
+                    ipv6_handshake: Some(300.0),
+                    ipv4_handshake: Some(20.0),
+                },
+                ConnectionRace {
+                    ipv6_server: v6(SERVER_V6),
+                    ipv4_server: v4(SERVER_V4),
+                    started: 1_700_000_005_000,
+                    first: AddressFamily::Ipv6,
+                    attempt_delay: 250.0,
+                    winner: Some(AddressFamily::Ipv6),
+                    margin: None,
+                    ipv6_handshake: Some(260.0),
+                    ipv4_handshake: None,
+                },
+            ]
+        );
+
+        tracker.clear();
+        assert!(tracker.races().is_empty());
+    }
+
+    #[test]
+    fn races_between_unresolved_addresses() {
+        let mut tracker = HappyEyeballsTracker::new();
+
+        tracker.update(
+            &tcp_packet(0, (CLIENT_V4, 40000), (SERVER_V4, 80), TcpFlags::SYN),
+            at(0),
+        );
+        // SYNs sent again keep the time of the first one
+        tracker.update(
+            &tcp_packet(0, (CLIENT_V4, 40000), (SERVER_V4, 80), TcpFlags::SYN),
+            at(100),
+        );
+        tracker.update(
+            &tcp_packet(0, (CLIENT_V6, 40001), (SERVER_V6, 80), TcpFlags::SYN),
+            at(150),
+        );
+
+        let races = tracker.races();
+        assert_eq!(races.len(), 1);
+        assert_eq!(races[0].destination, "2001:db8:1::80 / 192.0.2.80");
+        assert_eq!(races[0].last_races[0].first, AddressFamily::Ipv4);
+        assert_eq!(races[0].last_races[0].attempt_delay, 150.0);
+        assert_eq!(races[0].last_races[0].winner, None);
+    }
+
+    ///////////////////// Utils
+
+    /// UDP datagram carrying a DNS response resolving www.example.com to an A and an AAAA record
+    fn dns_response(ipv4: [u8; 4], ipv6: [u8; 16]) -> ParsedPacket {
+        let mut message = vec![0x00, 0x01, 0x81, 0x80];
+        message.extend([0x00, 0x01, 0x00, 0x02, 0x00, 0x00, 0x00, 0x00]);
+        message.extend(b"\x03www\x07example\x03com\x00");
+        message.extend([0x00, 0x01, 0x00, 0x01]);
+        message.extend([
+            0xc0, 0x0c, 0x00, 0x01, 0x00, 0x01, 0x00, 0x00, 0x00, 0x3c, 0x00, 0x04,
+        ]);
+        message.extend(ipv4);
+        message.extend([
+            0xc0, 0x0c, 0x00, 0x1c, 0x00, 0x01, 0x00, 0x00, 0x00, 0x3c, 0x00, 0x10,
+        ]);
+        message.extend(ipv6);
+
+        udp_packet(0, (DNS_SERVER, 53), (CLIENT_V4, 50000), &message)
+    }
+
+    fn v4(address: [u8; 4]) -> IpAddr {
+        IpAddr::V4(Ipv4Addr::from(address))
+    }
+
+    fn v6(address: [u8; 16]) -> IpAddr {
+        IpAddr::V6(Ipv6Addr::from(address))
+    }
+}
@@ -0,0 +1,420 @@
+//! Objects transferred over HTTP ("export objects")
+//!
+//! The body of each complete HTTP response, as decoded by the parser (after removing the chunked,
+//! gzip or deflate content encodings), is kept as an object along with the host and the URI of the
+//! request it answers and its content type. The objects can be listed, filtered by these, and
+//! saved to disk as files.
+//!
+//! Up to `MAX_OBJECTS_BYTES` of bodies are kept; the objects beyond them are listed without their
+//! bodies, which can't be saved.
+
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::path::Path;
+
+use serde::Serialize;
+use sniffer_parser::serializable_packet::application::HttpContentType;
+use sniffer_parser::serializable_packet::util::get_source_ip;
+use sniffer_parser::serializable_packet::{PacketRelation, ParsedPacket, SerializablePacket};
+use tracing::info;
+
+use crate::{SniffingError, SniffingState};
+
+/// Bytes of the bodies kept for all the objects
+const MAX_OBJECTS_BYTES: usize = 256 << 20;
+
+/// Requests waiting for their response
+const MAX_PENDING_REQUESTS: usize = 1024;
+
+/// Content type of the responses without one
+const DEFAULT_CONTENT_TYPE: &str = "application/octet-stream";
+
+/// Object transferred over HTTP, as returned to the frontend
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct HttpObject {
+    pub id: usize,
+    /// Response carrying the object
+    pub packet_id: usize,
+    pub request_id: Option<usize>,
+    /// Host of the request, or address of the server without a Host header
+    pub host: String,
+    pub uri: String,
+    /// Media type, without its parameters
+    pub content_type: String,
+    /// Name of the file the object is saved as
+    pub filename: String,
+    pub size: usize,
+    /// Whether the body is kept and can be saved
+    pub available: bool,
+}
+
+/// Host and URI of a request
+#[derive(Debug, Clone)]
+struct PendingRequest {
+    host: Option<String>,
+    uri: String,
+}
+
+/// Objects of the HTTP responses of the collected packets
+#[derive(Debug, Default)]
+pub struct HttpObjects {
+    objects: Vec<HttpObject>,
+    bodies: HashMap<usize, Vec<u8>>,
+    kept: usize,
+    requests: HashMap<usize, PendingRequest>,
+    /// Order of the pending requests, to forget the oldest ones
+    request_order: VecDeque<usize>,
+}
+
+impl HttpObjects {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Update the objects with a packet
+    pub fn update(&mut self, packet: &ParsedPacket) {
+        match packet.get_application_layer_packet() {
+            Some(SerializablePacket::HttpRequestPacket(request)) => {
+                self.requests.insert(
+                    packet.get_id(),
+                    PendingRequest {
+                        host: header(&request.headers, "Host").map(|host| host.to_owned()),
+                        uri: request.path.clone(),
+                    },
+                );
+                self.request_order.push_back(packet.get_id());
+                if self.request_order.len() > MAX_PENDING_REQUESTS {
+                    if let Some(oldest) = self.request_order.pop_front() {
+                        self.requests.remove(&oldest);
+                    }
+                }
+            }
+            Some(SerializablePacket::HttpResponsePacket(response)) => {
+                let body = match body_bytes(&response.payload) {
+                    Some(body) if !body.is_empty() => body,
+                    _ => return,
+                };
+
+                let request_id = packet
+                    .get_references()
+                    .iter()
+                    .find(|reference| reference.relation == PacketRelation::Request)
+                    .map(|reference| reference.id);
+                let request = request_id.and_then(|id| self.requests.remove(&id));
+                let host = request
+                    .as_ref()
+                    .and_then(|request| request.host.clone())
+                    .or_else(|| get_source_ip(packet))
+                    .unwrap_or_default();
+                let uri = request.map(|request| request.uri).unwrap_or_default();
+                let content_type = header(&response.headers, "Content-Type")
+                    .and_then(|value| value.split(';').next())
+                    .map(|media_type| media_type.trim().to_ascii_lowercase())
+                    .filter(|media_type| !media_type.is_empty())
+                    .unwrap_or_else(|| DEFAULT_CONTENT_TYPE.to_owned());
+
+                let id = self.objects.len();
+                let available = self.kept + body.len() <= MAX_OBJECTS_BYTES;
+                self.objects.push(HttpObject {
+                    id,
+                    packet_id: packet.get_id(),
+                    request_id,
+                    host,
+                    filename: filename(id, &uri, &content_type),
+                    uri,
+                    content_type,
+                    size: body.len(),
+                    available,
+                });
+                if available {
+                    self.kept += body.len();
+                    self.bodies.insert(id, body);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Get the objects, filtered by host, content type (e.g. "image/png", or "image/" for all the
+    /// images) and a part of the URI
+    pub fn objects(
+        &self,
+        host: Option<&str>,
+        content_type: Option<&str>,
+        uri: Option<&str>,
+    ) -> Vec<HttpObject> {
+        self.objects
+            .iter()
+            .filter(|object| host.is_none_or(|host| object.host.eq_ignore_ascii_case(host)))
+            .filter(|object| {
+                content_type.is_none_or(|content_type| {
+                    object
+                        .content_type
+                        .starts_with(&content_type.to_ascii_lowercase())
+                })
+            })
+            .filter(|object| uri.is_none_or(|uri| object.uri.contains(uri)))
+            .cloned()
+            .collect()
+    }
+
+    /// Get the body of an object with the name of its file, if kept
+    pub fn body(&self, id: usize) -> Option<(&str, &[u8])> {
+        let object = self.objects.get(id)?;
+        let body = self.bodies.get(&id)?;
+        Some((&object.filename, body))
+    }
+}
+
+/// Get the value of a header, given its name
+fn header<'a>(headers: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|(header, _)| header.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.as_str())
+}
+
+/// Get the bytes of a decoded body
+fn body_bytes(payload: &HttpContentType) -> Option<Vec<u8>> {
+    match payload {
+        HttpContentType::TextCorrectlyDecoded(text)
+        | HttpContentType::TextMalformedDecoded(text)
+        | HttpContentType::TextDefaultDecoded(text) => Some(text.as_bytes().to_vec()),
+        HttpContentType::Image(bytes)
+        | HttpContentType::Unknown(bytes)
+        | HttpContentType::Encoded(_, bytes)
+        | HttpContentType::Multipart(bytes) => Some(bytes.clone()),
+        HttpContentType::None => None,
+    }
+}
+
+/// Name the file of an object after the last segment of its URI, prefixed by its id to keep the
+/// names unique, with the extension of its content type when missing
+fn filename(id: usize, uri: &str, content_type: &str) -> String {
+    let path = uri.split(['?', '#']).next().unwrap_or_default();
+    let segment: String = path
+        .rsplit('/')
+        .next()
+        .unwrap_or_default()
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '.' | '-' | '_' => c,
+            _ => '_',
+        })
+        .collect();
+    let segment = segment.trim_start_matches('.');
+    let name = if segment.is_empty() {
+        "object"
+    } else {
+        segment
+    };
+
+    match extension(content_type) {
+        Some(extension) if !name.contains('.') => format!("{}-{}.{}", id, name, extension),
+        _ => format!("{}-{}", id, name),
+    }
+}
+
+/// Get the usual extension of the files of a content type
+fn extension(content_type: &str) -> Option<&'static str> {
+    let extension = match content_type {
+        "text/html" => "html",
+        "text/plain" => "txt",
+        "text/css" => "css",
+        "text/javascript" | "application/javascript" => "js",
+        "application/json" => "json",
+        "application/xml" | "text/xml" => "xml",
+        "application/pdf" => "pdf",
+        "application/zip" => "zip",
+        "image/png" => "png",
+        "image/jpeg" => "jpg",
+        "image/gif" => "gif",
+        "image/webp" => "webp",
+        "image/svg+xml" => "svg",
+        "image/x-icon" | "image/vnd.microsoft.icon" => "ico",
+        _ => return None,
+    };
+
+    Some(extension)
+}
+
+/// Get the objects transferred over HTTP, filtered by host, content type and part of the URI
+#[tauri::command]
+pub fn get_http_objects(
+    host: Option<String>,
+    content_type: Option<String>,
+    uri: Option<String>,
+    state: tauri::State<SniffingState>,
+) -> Vec<HttpObject> {
+    state.packets.lock().unwrap().http_objects.objects(
+        host.as_deref(),
+        content_type.as_deref(),
+        uri.as_deref(),
+    )
+}
+
+/// Saves the bodies of the given objects as files of a directory, returning the number of files
+/// written
+#[tauri::command]
+pub fn save_http_objects(
+    ids: Vec<usize>,
+    directory: String,
+    state: tauri::State<SniffingState>,
+) -> Result<usize, SniffingError> {
+    let packets = state.packets.lock().unwrap();
+
+    let mut saved = 0;
+    for id in ids {
+        let (filename, body) = packets.http_objects.body(id).ok_or_else(|| {
+            SniffingError::ExportFailed(format!("Export failed: object {} not available", id))
+        })?;
+        fs::write(Path::new(&directory).join(filename), body)
+            .map_err(|e| SniffingError::ExportFailed(format!("Export failed: {}", e)))?;
+        saved += 1;
+    }
+
+    info!("[{}] Exported {} HTTP objects", directory, saved);
+
+    Ok(saved)
+}
+
+#[cfg(test)]
+mod tests {
+    use sniffer_parser::serializable_packet::application::{
+        HttpContentType, SerializableHttpRequestPacket, SerializableHttpResponsePacket,
+    };
+    use sniffer_parser::serializable_packet::{PacketRelation, ParsedPacket, SerializablePacket};
+
+    use super::{filename, HttpObjects, MAX_OBJECTS_BYTES};
+
+    #[test]
+    fn objects_of_responses() {
+        let mut objects = HttpObjects::new();
+        objects.update(&request(1, "/img/logo.png?v=2", Some("example.com")));
+        objects.update(&response(
+            2,
+            Some(1),
+            "image/png",
+            HttpContentType::Image(vec![0x89, b'P', b'N', b'G']),
+        ));
+        objects.update(&request(3, "/", Some("Example.com")));
+        objects.update(&response(
+            4,
+            Some(3),
+            "text/html; charset=utf-8",
+            HttpContentType::TextCorrectlyDecoded("<html></html>".to_owned()),
+        ));
+        objects.update(&request(5, "/empty", Some("example.com")));
+        objects.update(&response(6, Some(5), "text/plain", HttpContentType::None));
+
+        let all = objects.objects(None, None, None);
+        assert_eq!(all.len(), 2);
+        assert_eq!(all[0].packet_id, 2);
+        assert_eq!(all[0].request_id, Some(1));
+        assert_eq!(all[0].host, "example.com");
+        assert_eq!(all[0].uri, "/img/logo.png?v=2");
+        assert_eq!(all[0].content_type, "image/png");
+        assert_eq!(all[0].filename, "0-logo.png");
+        assert_eq!(all[0].size, 4);
+        assert_eq!(all[1].content_type, "text/html");
+        assert_eq!(all[1].filename, "1-object.html");
+
+        assert_eq!(objects.objects(Some("EXAMPLE.COM"), None, None).len(), 2);
+        assert_eq!(objects.objects(None, Some("image/"), None).len(), 1);
+        assert_eq!(objects.objects(None, None, Some("logo")).len(), 1);
+        assert_eq!(objects.objects(Some("other.com"), None, None).len(), 0);
+
+        let (name, body) = objects.body(1).unwrap();
+        assert_eq!(name, "1-object.html");
+        assert_eq!(body, b"<html></html>");
+        assert!(objects.body(2).is_none());
+    }
+
+    #[test]
+    fn bodies_beyond_the_limit_are_not_kept() {
+        let mut objects = HttpObjects::new();
+        objects.update(&response(
+            1,
+            None,
+            "application/zip",
+            HttpContentType::Unknown(vec![0; MAX_OBJECTS_BYTES - 10]),
+        ));
+        objects.update(&response(
+            2,
+            None,
+            "application/zip",
+            HttpContentType::Unknown(vec![0; 20]),
+        ));
+
+        let all = objects.objects(None, None, None);
+        assert_eq!(all[0].host, "");
+        assert!(all[0].available);
+        assert!(!all[1].available);
+        assert_eq!(all[1].size, 20);
+        assert!(objects.body(1).is_none());
+    }
+
+    #[test]
+    fn filenames() {
+        assert_eq!(
+            filename(3, "/a/b/report.pdf", "application/pdf"),
+            "3-report.pdf"
+        );
+        assert_eq!(
+            filename(3, "/api/items", "application/json"),
+            "3-items.json"
+        );
+        assert_eq!(filename(3, "/../..", "text/plain"), "3-object.txt");
+        assert_eq!(filename(3, "/x y#frag", "font/woff2"), "3-x_y");
+    }
+
+    ///////////////////// Utils
+
+    fn request(id: usize, path: &str, host: Option<&str>) -> ParsedPacket {
+        let mut packet = ParsedPacket::new(id);
+        packet.set_application_layer_packet(Some(SerializablePacket::HttpRequestPacket(
+            SerializableHttpRequestPacket {
+                method: "GET".to_owned(),
+                path: path.to_owned(),
+                version: 1,
+                headers: host
+                    .map(|host| vec![("Host".to_owned(), host.to_owned())])
+                    .unwrap_or_default(),
+                trailers: 0,
+                payload: HttpContentType::None,
+                registry: None,
+                quirks: vec![],
+            },
+        )));
+        packet
+    }
+
+    fn response(
+        id: usize,
+        request_id: Option<usize>,
+        content_type: &str,
+        payload: HttpContentType,
+    ) -> ParsedPacket {
+        let mut packet = ParsedPacket::new(id);
+        if let Some(request_id) = request_id {
+            packet.add_reference(request_id, PacketRelation::Request);
+        }
+        packet.set_application_layer_packet(Some(SerializablePacket::HttpResponsePacket(
+            SerializableHttpResponsePacket {
+                version: 1,
+                code: 200,
+                reason: "OK".to_owned(),
+                headers: vec![("Content-Type".to_owned(), content_type.to_owned())],
+                trailers: 0,
+                payload,
+                quirks: vec![],
+            },
+        )));
+        packet
+    }
+}
@@ -0,0 +1,330 @@
+//! Rates of the ICMP errors sent by each source, and detection of the error storms
+//!
+//! The ICMP and ICMPv6 error messages are counted by source and kind (destination unreachable,
+//! redirect, time exceeded, others), together with the highest number of errors sent by the
+//! source within a second. A security alert is raised when a source sends a storm of:
+//! - destination unreachable errors, as a routing loop or a scan of closed ports would cause
+//! - redirects, as a misconfigured router or a host hijacking the traffic would send
+//!
+//! Alerts are emitted to the frontend as `security_alert` events while sniffing, together with
+//! the ARP spoofing ones.
+
+use std::cmp::Reverse;
+use std::collections::{HashMap, VecDeque};
+use std::net::IpAddr;
+
+use chrono::{DateTime, Local};
+use serde::Serialize;
+use sniffer_parser::serializable_packet::{ParsedPacket, SerializablePacket};
+use tracing::warn;
+
+use crate::arp_watch::{SecurityAlert, ALERTS_SIZE};
+use crate::inventory::type_number;
+use crate::statistics::Timestamp;
+use crate::SniffingState;
+
+/// Window of the errors counted for the rates and the storms, in milliseconds
+const STORM_WINDOW: i64 = 1000;
+
+/// Destination unreachable errors of a source, within the storm window, raising an alert
+const UNREACHABLE_STORM_COUNT: usize = 100;
+
+/// Redirects of a source, within the storm window, raising an alert
+const REDIRECT_STORM_COUNT: usize = 20;
+
+/// Kind of an ICMP error message
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum IcmpErrorKind {
+    Unreachable,
+    Redirect,
+    TimeExceeded,
+    /// Source quench, parameter problem, packet too big
+    Other,
+}
+
+/// ICMP errors sent by a source, as returned to the frontend
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct IcmpErrorSource {
+    pub source: IpAddr,
+    pub unreachable: usize,
+    pub redirect: usize,
+    pub time_exceeded: usize,
+    pub other: usize,
+    /// Highest number of errors sent within a second
+    pub peak_rate: usize,
+    pub first_seen: Timestamp,
+    pub last_seen: Timestamp,
+}
+
+impl IcmpErrorSource {
+    fn total(&self) -> usize {
+        self.unreachable + self.redirect + self.time_exceeded + self.other
+    }
+}
+
+/// Errors of a source, with the times of the recent ones
+#[derive(Debug)]
+struct SourceErrors {
+    errors: IcmpErrorSource,
+    /// Times of the errors within the storm window
+    recent: VecDeque<i64>,
+    recent_unreachable: VecDeque<i64>,
+    recent_redirects: VecDeque<i64>,
+}
+
+/// Tracker of the ICMP errors of the collected packets
+#[derive(Debug, Default)]
+pub struct IcmpWatch {
+    sources: HashMap<IpAddr, SourceErrors>,
+    /// Last alerts raised, the oldest first
+    alerts: VecDeque<SecurityAlert>,
+}
+
+impl IcmpWatch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Count a packet, if it is an ICMP error, given the time it was received, returning the
+    /// alerts raised
+    pub fn update(&mut self, packet: &ParsedPacket, time: DateTime<Local>) -> Vec<SecurityAlert> {
+        let source = match packet.get_network_layer_packet() {
+            Some(SerializablePacket::Ipv4Packet(ipv4)) => IpAddr::V4(ipv4.source),
+            Some(SerializablePacket::Ipv6Packet(ipv6)) => IpAddr::V6(ipv6.source),
+            _ => return vec![],
+        };
+        let kind = match packet.get_transport_layer_packet() {
+            Some(SerializablePacket::IcmpPacket(icmp)) => {
+                type_number(&icmp.icmp_type).and_then(icmp_error_kind)
+            }
+            Some(SerializablePacket::Icmpv6Packet(icmpv6)) => {
+                type_number(&icmpv6.icmpv6_type).and_then(icmpv6_error_kind)
+            }
+            _ => None,
+        };
+        let kind = match kind {
+            Some(kind) => kind,
+            None => return vec![],
+        };
+
+        let time = time.timestamp_millis();
+        let source_errors = self.sources.entry(source).or_insert_with(|| SourceErrors {
+            errors: IcmpErrorSource {
+                source,
+                unreachable: 0,
+                redirect: 0,
+                time_exceeded: 0,
+                other: 0,
+                peak_rate: 0,
+                first_seen: time,
+                last_seen: time,
+            },
+            recent: VecDeque::new(),
+            recent_unreachable: VecDeque::new(),
+            recent_redirects: VecDeque::new(),
+        });
+
+        let errors = &mut source_errors.errors;
+        errors.last_seen = errors.last_seen.max(time);
+        let rate = within_window(&mut source_errors.recent, time);
+        errors.peak_rate = errors.peak_rate.max(rate);
+
+        // Raised once per storm, when the count is first reached
+        let storm = match kind {
+            IcmpErrorKind::Unreachable => {
+                errors.unreachable += 1;
+                let count = within_window(&mut source_errors.recent_unreachable, time);
+                (count == UNREACHABLE_STORM_COUNT).then_some(count)
+            }
+            IcmpErrorKind::Redirect => {
+                errors.redirect += 1;
+                let count = within_window(&mut source_errors.recent_redirects, time);
+                (count == REDIRECT_STORM_COUNT).then_some(count)
+            }
+            IcmpErrorKind::TimeExceeded => {
+                errors.time_exceeded += 1;
+                None
+            }
+            IcmpErrorKind::Other => {
+                errors.other += 1;
+                None
+            }
+        };
+
+        let alerts: Vec<SecurityAlert> = storm
+            .map(|count| SecurityAlert::IcmpErrorStorm {
+                time,
+                source,
+                kind,
+                count,
+            })
+            .into_iter()
+            .collect();
+        for alert in &alerts {
+            warn!("Security alert: {:?}", alert);
+            if self.alerts.len() >= ALERTS_SIZE {
+                self.alerts.pop_front();
+            }
+            self.alerts.push_back(alert.clone());
+        }
+
+        alerts
+    }
+
+    /// Get the sources of ICMP errors, the ones sending the most first
+    pub fn sources(&self) -> Vec<IcmpErrorSource> {
+        let mut sources: Vec<IcmpErrorSource> = self
+            .sources
+            .values()
+            .map(|source| source.errors.clone())
+            .collect();
+        sources.sort_by_key(|source| (Reverse(source.total()), source.source));
+
+        sources
+    }
+
+    /// Get the last alerts raised, the oldest first
+    pub fn alerts(&self) -> Vec<SecurityAlert> {
+        self.alerts.iter().cloned().collect()
+    }
+
+    pub fn clear(&mut self) {
+        self.sources.clear();
+        self.alerts.clear();
+    }
+}
+
+/// Add the time of an error to the times within the window ending with it, returning their count
+fn within_window(times: &mut VecDeque<i64>, time: i64) -> usize {
+    while times
+        .front()
+        .is_some_and(|first| time - first >= STORM_WINDOW)
+    {
+        times.pop_front();
+    }
+    times.push_back(time);
+
+    times.len()
+}
+
+fn icmp_error_kind(icmp_type: u8) -> Option<IcmpErrorKind> {
+    match icmp_type {
+        3 => Some(IcmpErrorKind::Unreachable),
+        5 => Some(IcmpErrorKind::Redirect),
+        11 => Some(IcmpErrorKind::TimeExceeded),
+        4 | 12 => Some(IcmpErrorKind::Other),
+        _ => None,
+    }
+}
+
+fn icmpv6_error_kind(icmpv6_type: u8) -> Option<IcmpErrorKind> {
+    match icmpv6_type {
+        1 => Some(IcmpErrorKind::Unreachable),
+        137 => Some(IcmpErrorKind::Redirect),
+        3 => Some(IcmpErrorKind::TimeExceeded),
+        2 | 4 => Some(IcmpErrorKind::Other),
+        _ => None,
+    }
+}
+
+/// Returns the sources of ICMP errors of the collected packets, the ones sending the most first
+#[tauri::command]
+pub fn get_icmp_error_sources(state: tauri::State<SniffingState>) -> Vec<IcmpErrorSource> {
+    state.packets.lock().unwrap().icmp_watch.sources()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr};
+
+    use pnet::packet::ethernet::EthernetPacket;
+    use sniffer_parser::parse_ethernet_frame;
+    use sniffer_parser::serializable_packet::ParsedPacket;
+
+    use super::{IcmpErrorKind, IcmpWatch, REDIRECT_STORM_COUNT, UNREACHABLE_STORM_COUNT};
+    use crate::arp_watch::SecurityAlert;
+    use crate::test_utils::at;
+
+    const ROUTER: [u8; 4] = [10, 0, 0, 1];
+    const HOST: [u8; 4] = [10, 0, 0, 10];
+
+    #[test]
+    fn error_rates() {
+        let mut watch = IcmpWatch::new();
+
+        for i in 0..3 {
+            watch.update(&icmp_packet(ROUTER, 3), at(i * 100));
+        }
+        watch.update(&icmp_packet(ROUTER, 11), at(2000));
+        // Echo requests are not errors
+        watch.update(&icmp_packet(HOST, 8), at(2000));
+        watch.update(&icmp_packet(HOST, 12), at(3000));
+
+        let sources = watch.sources();
+        assert_eq!(sources.len(), 2);
+        let router = &sources[0];
+        assert_eq!(router.source, IpAddr::V4(Ipv4Addr::from(ROUTER)));
+        assert_eq!(router.unreachable, 3);
+        assert_eq!(router.time_exceeded, 1);
+        assert_eq!(router.peak_rate, 3);
+        assert_eq!(router.last_seen - router.first_seen, 2000);
+        assert_eq!(sources[1].other, 1);
+
+        watch.clear();
+        assert!(watch.sources().is_empty());
+    }
+
+    #[test]
+    fn unreachable_and_redirect_storms() {
+        let mut watch = IcmpWatch::new();
+
+        let mut alerts = vec![];
+        for i in 0..2 * UNREACHABLE_STORM_COUNT as i64 {
+            alerts.extend(watch.update(&icmp_packet(ROUTER, 3), at(i)));
+        }
+        // Redirects spread over time are not a storm
+        for i in 0..REDIRECT_STORM_COUNT as i64 {
+            alerts.extend(watch.update(&icmp_packet(HOST, 5), at(i * 1000)));
+        }
+        for i in 0..REDIRECT_STORM_COUNT as i64 {
+            alerts.extend(watch.update(&icmp_packet(HOST, 5), at(100_000 + i)));
+        }
+
+        assert_eq!(
+            alerts,
+            vec![
+                SecurityAlert::IcmpErrorStorm {
+                    time: at(UNREACHABLE_STORM_COUNT as i64 - 1).timestamp_millis(),
+                    source: IpAddr::V4(Ipv4Addr::from(ROUTER)),
+                    kind: IcmpErrorKind::Unreachable,
+                    count: UNREACHABLE_STORM_COUNT,
+                },
+                SecurityAlert::IcmpErrorStorm {
+                    time: at(100_000 + REDIRECT_STORM_COUNT as i64 - 1).timestamp_millis(),
+                    source: IpAddr::V4(Ipv4Addr::from(HOST)),
+                    kind: IcmpErrorKind::Redirect,
+                    count: REDIRECT_STORM_COUNT,
+                },
+            ]
+        );
+        assert_eq!(watch.alerts(), alerts);
+    }
+
+    ///////////////////// Utils
+
+    fn icmp_packet(source: [u8; 4], icmp_type: u8) -> ParsedPacket {
+        let mut frame = vec![0xff; 6];
+        frame.extend([0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x08, 0x00]);
+        frame.extend([
+            0x45, 0x00, 0x00, 0x24, 0x00, 0x00, 0x00, 0x00, 0x40, 0x01, 0x00, 0x00,
+        ]);
+        frame.extend(source);
+        frame.extend([10, 0, 0, 99]);
+        frame.extend([icmp_type, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
+        frame.extend([0x00; 8]);
+
+        parse_ethernet_frame(&EthernetPacket::new(&frame).unwrap(), 0)
+    }
+}
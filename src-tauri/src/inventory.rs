@@ -0,0 +1,427 @@
+//! Passive inventory of the hosts of the local network
+//!
+//! Hosts are learned from the bindings between IP and MAC addresses announced on the link, without
+//! sending any packet:
+//! - ARP requests and replies, by their sender
+//! - NDP messages (router and neighbor solicitations and advertisements), by their source
+//!
+//! For each host are kept its first and last time seen, the host names found in the DNS answers
+//! or set by the user, and the services it offers: the TCP ports it accepts connections on
+//! (SYN-ACK sent), and the UDP ports below 1024 it answers from. The vendor comes from the OUI of
+//! the MAC address: imported with a Wireshark profile, among the well-known ones, or in the OUI
+//! table embedded in the parser.
+//!
+//! The inventory can be exported as CSV or JSON.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::net::IpAddr;
+
+use chrono::{DateTime, Local, TimeZone};
+use pnet::packet::tcp::TcpFlags;
+use pnet::util::MacAddr;
+use serde::{Deserialize, Serialize};
+use sniffer_parser::oui::mac_vendor;
+use sniffer_parser::serializable_packet::application::CustomResourceData;
+use sniffer_parser::serializable_packet::{ParsedPacket, SerializablePacket};
+use tracing::info;
+
+use crate::name_resolution::NameResolver;
+use crate::statistics::Timestamp;
+use crate::{SniffingError, SniffingState};
+
+/// ICMPv6 types of the NDP messages carrying the link-layer address of their source
+const NDP_TYPES: [u8; 4] = [133, 134, 135, 136];
+
+/// Highest port of the UDP services
+const UDP_SERVICE_PORT_MAX: u16 = 1023;
+
+/// Vendors of well-known OUIs, mostly of virtual machines and single-board computers
+const VENDORS: [(&str, &str); 11] = [
+    ("00:05:69", "VMware"),
+    ("00:0c:29", "VMware"),
+    ("00:15:5d", "Microsoft Hyper-V"),
+    ("00:1c:14", "VMware"),
+    ("00:1c:42", "Parallels"),
+    ("00:50:56", "VMware"),
+    ("08:00:27", "VirtualBox"),
+    ("52:54:00", "QEMU/KVM"),
+    ("b8:27:eb", "Raspberry Pi"),
+    ("dc:a6:32", "Raspberry Pi"),
+    ("e4:5f:01", "Raspberry Pi"),
+];
+
+/// Output format of an inventory export
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum InventoryFormat {
+    Csv,
+    Json,
+}
+
+/// Host of the inventory, as returned to the frontend
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Asset {
+    pub ip: IpAddr,
+    pub mac: String,
+    pub vendor: Option<String>,
+    pub hostnames: Vec<String>,
+    pub first_seen: Timestamp,
+    pub last_seen: Timestamp,
+    /// Services offered, like `TCP/443`
+    pub services: Vec<String>,
+}
+
+/// Host learned from an IP-MAC binding
+#[derive(Debug, Clone)]
+struct Host {
+    mac: MacAddr,
+    first_seen: i64,
+    last_seen: i64,
+}
+
+/// Hosts of the local network, learned from the collected packets
+#[derive(Debug, Default)]
+pub struct HostInventory {
+    hosts: BTreeMap<IpAddr, Host>,
+    /// Names found in the DNS answers, of any address
+    hostnames: HashMap<IpAddr, BTreeSet<String>>,
+    /// Services offered by any address, by transport protocol and port
+    services: HashMap<IpAddr, BTreeSet<(&'static str, u16)>>,
+}
+
+impl HostInventory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Learn from a packet, given the time it was received
+    pub fn update(&mut self, packet: &ParsedPacket, time: DateTime<Local>) {
+        let time = time.timestamp_millis();
+
+        let source = match packet.get_network_layer_packet() {
+            Some(SerializablePacket::ArpPacket(arp)) => {
+                // ARP probes have no sender address yet
+                if !arp.sender_proto_addr.is_unspecified() {
+                    self.learn(IpAddr::V4(arp.sender_proto_addr), arp.sender_hw_addr, time);
+                }
+                return;
+            }
+            Some(SerializablePacket::Ipv4Packet(ipv4)) => IpAddr::V4(ipv4.source),
+            Some(SerializablePacket::Ipv6Packet(ipv6)) => IpAddr::V6(ipv6.source),
+            _ => return,
+        };
+
+        match packet.get_transport_layer_packet() {
+            Some(SerializablePacket::Icmpv6Packet(icmpv6)) if !source.is_unspecified() => {
+                if let (Some(icmpv6_type), Some(SerializablePacket::EthernetPacket(link_packet))) = (
+                    type_number(&icmpv6.icmpv6_type),
+                    packet.get_link_layer_packet(),
+                ) {
+                    if NDP_TYPES.contains(&icmpv6_type) {
+                        self.learn(source, link_packet.source, time);
+                    }
+                }
+            }
+            Some(SerializablePacket::TcpPacket(tcp))
+                if tcp.flags & (TcpFlags::SYN | TcpFlags::ACK) == TcpFlags::SYN | TcpFlags::ACK =>
+            {
+                self.services
+                    .entry(source)
+                    .or_default()
+                    .insert(("TCP", tcp.source));
+            }
+            Some(SerializablePacket::UdpPacket(udp))
+                if udp.source <= UDP_SERVICE_PORT_MAX && udp.destination > UDP_SERVICE_PORT_MAX =>
+            {
+                self.services
+                    .entry(source)
+                    .or_default()
+                    .insert(("UDP", udp.source));
+            }
+            _ => (),
+        }
+
+        if let Some(SerializablePacket::DnsPacket(dns_packet)) =
+            packet.get_application_layer_packet()
+        {
+            for record in dns_packet.answers.iter().chain(&dns_packet.additional) {
+                let address = match &record.data {
+                    CustomResourceData::A(a) => IpAddr::V4(a.address),
+                    CustomResourceData::AAAA(aaaa) => IpAddr::V6(aaaa.address),
+                    _ => continue,
+                };
+                self.hostnames
+                    .entry(address)
+                    .or_default()
+                    .insert(record.name.clone());
+            }
+        }
+
+        if let Some(host) = self.hosts.get_mut(&source) {
+            host.last_seen = host.last_seen.max(time);
+        }
+    }
+
+    /// Bind an IP address to a MAC address, replacing any previous binding
+    fn learn(&mut self, ip: IpAddr, mac: MacAddr, time: i64) {
+        let host = self.hosts.entry(ip).or_insert(Host {
+            mac,
+            first_seen: time,
+            last_seen: time,
+        });
+        host.mac = mac;
+        host.first_seen = host.first_seen.min(time);
+        host.last_seen = host.last_seen.max(time);
+    }
+
+    /// Get the hosts of the inventory, sorted by IP address, with the names set by the user
+    pub fn assets(&self, resolver: &NameResolver) -> Vec<Asset> {
+        self.hosts
+            .iter()
+            .map(|(ip, host)| {
+                let mac = host.mac.to_string();
+
+                let mut hostnames = self.hostnames.get(ip).cloned().unwrap_or_default();
+                hostnames.extend(resolver.host(ip).cloned());
+                hostnames.extend(resolver.ether(&mac).cloned());
+
+                Asset {
+                    ip: *ip,
+                    vendor: vendor(&mac, resolver),
+                    mac,
+                    hostnames: hostnames.into_iter().collect(),
+                    first_seen: host.first_seen,
+                    last_seen: host.last_seen,
+                    services: self
+                        .services
+                        .get(ip)
+                        .map(|services| {
+                            services
+                                .iter()
+                                .map(|(protocol, port)| format!("{}/{}", protocol, port))
+                                .collect()
+                        })
+                        .unwrap_or_default(),
+                }
+            })
+            .collect()
+    }
+
+    pub fn clear(&mut self) {
+        self.hosts.clear();
+        self.hostnames.clear();
+        self.services.clear();
+    }
+}
+
+/// Get the number of an ICMP type, formatted like `Name (n)`
+pub(crate) fn type_number(icmp_type: &str) -> Option<u8> {
+    icmp_type
+        .rsplit_once('(')?
+        .1
+        .strip_suffix(')')?
+        .parse()
+        .ok()
+}
+
+/// Get the vendor of a MAC address, preferring the ones imported by the user
+fn vendor(mac: &str, resolver: &NameResolver) -> Option<String> {
+    let oui = mac.get(..8)?;
+    if let Some(vendor) = resolver.vendor(mac) {
+        return Some(vendor.clone());
+    }
+    if let Some((_, vendor)) = VENDORS.iter().find(|(prefix, _)| *prefix == oui) {
+        return Some(vendor.to_string());
+    }
+    if let Some(vendor) = mac.parse().ok().and_then(mac_vendor) {
+        return Some(vendor.to_owned());
+    }
+
+    // Second least significant bit of the first octet
+    u8::from_str_radix(mac.get(..2)?, 16)
+        .ok()
+        .filter(|octet| octet & 0x02 != 0)
+        .map(|_| "Locally administered".to_owned())
+}
+
+/// Format the inventory as CSV, with a header line
+pub fn inventory_to_csv(assets: &[Asset]) -> String {
+    let mut csv = String::from("IP,MAC,Vendor,Hostnames,First Seen,Last Seen,Services\n");
+
+    for asset in assets {
+        let fields = [
+            asset.ip.to_string(),
+            asset.mac.clone(),
+            asset.vendor.clone().unwrap_or_default(),
+            asset.hostnames.join(" "),
+            format_time(asset.first_seen),
+            format_time(asset.last_seen),
+            asset.services.join(" "),
+        ];
+        let fields: Vec<String> = fields.iter().map(|field| csv_field(field)).collect();
+        csv.push_str(&fields.join(","));
+        csv.push('\n');
+    }
+
+    csv
+}
+
+/// Format a Unix timestamp in milliseconds as a local date and time
+fn format_time(timestamp: i64) -> String {
+    Local
+        .timestamp_millis_opt(timestamp)
+        .single()
+        .map(|time| time.format("%Y-%m-%d %H:%M:%S").to_string())
+        .unwrap_or_default()
+}
+
+/// Quote a CSV field containing separators or quotes
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
+
+/// Returns the hosts of the local network learned from the collected packets
+#[tauri::command]
+pub fn get_inventory(state: tauri::State<SniffingState>) -> Vec<Asset> {
+    let resolver = state.resolver.lock().unwrap();
+    state.packets.lock().unwrap().inventory.assets(&resolver)
+}
+
+/// Exports the hosts of the local network learned from the collected packets, returning the
+/// number of hosts exported
+#[tauri::command]
+pub fn export_inventory(
+    path: String,
+    format: InventoryFormat,
+    state: tauri::State<SniffingState>,
+) -> Result<usize, SniffingError> {
+    let assets = get_inventory(state);
+
+    let data = match format {
+        InventoryFormat::Csv => inventory_to_csv(&assets),
+        InventoryFormat::Json => serde_json::to_string_pretty(&assets)
+            .map_err(|e| SniffingError::ExportFailed(format!("Export failed: {}", e)))?,
+    };
+
+    fs::write(&path, data)
+        .map_err(|e| SniffingError::ExportFailed(format!("Export failed: {}", e)))?;
+
+    info!("[{}] Exported {} hosts", path, assets.len());
+
+    Ok(assets.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr};
+
+    use chrono::{Local, TimeZone};
+    use pnet::packet::ethernet::EthernetPacket;
+    use sniffer_parser::parse_ethernet_frame;
+
+    use super::{inventory_to_csv, type_number, vendor, Asset, HostInventory};
+    use crate::name_resolution::NameResolver;
+
+    // ARP reply of 10.10.10.10 (08:00:27:aa:bb:cc) to 10.10.10.1
+    const ARP_FRAME: &[u8] = &[
+        0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x08, 0x00, 0x27, 0xaa, 0xbb, 0xcc, 0x08, 0x06, 0x00,
+        0x01, 0x08, 0x00, 0x06, 0x04, 0x00, 0x02, 0x08, 0x00, 0x27, 0xaa, 0xbb, 0xcc, 0x0a, 0x0a,
+        0x0a, 0x0a, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x0a, 0x0a, 0x0a, 0x01,
+    ];
+
+    // SYN-ACK sent by 10.10.10.10:443 to 11.11.11.11:4444
+    const SYN_ACK_FRAME: &[u8] = &[
+        0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x08, 0x00, 0x27, 0xaa, 0xbb, 0xcc, 0x08, 0x00, 0x45,
+        0x00, 0x00, 0x28, 0x00, 0x00, 0x00, 0x00, 0x40, 0x06, 0x00, 0x00, 0x0a, 0x0a, 0x0a, 0x0a,
+        0x0b, 0x0b, 0x0b, 0x0b, 0x01, 0xbb, 0x11, 0x5c, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x50, 0x12, 0xff, 0xff, 0x00, 0x00, 0x00, 0x00,
+    ];
+
+    #[test]
+    fn hosts_learned_from_packets() {
+        let mut inventory = HostInventory::new();
+        let arp = parse_ethernet_frame(&EthernetPacket::new(ARP_FRAME).unwrap(), 0);
+        let syn_ack = parse_ethernet_frame(&EthernetPacket::new(SYN_ACK_FRAME).unwrap(), 1);
+
+        // Services are kept for hosts learned later too
+        inventory.update(&syn_ack, Local.timestamp_opt(1_700_000_000, 0).unwrap());
+        inventory.update(&arp, Local.timestamp_opt(1_700_000_010, 0).unwrap());
+        inventory.update(&syn_ack, Local.timestamp_opt(1_700_000_020, 0).unwrap());
+
+        assert_eq!(
+            inventory.assets(&NameResolver::new()),
+            vec![Asset {
+                ip: IpAddr::V4(Ipv4Addr::new(10, 10, 10, 10)),
+                mac: "08:00:27:aa:bb:cc".to_owned(),
+                vendor: Some("VirtualBox".to_owned()),
+                hostnames: vec![],
+                first_seen: 1_700_000_010_000,
+                last_seen: 1_700_000_020_000,
+                services: vec!["TCP/443".to_owned()],
+            }]
+        );
+
+        inventory.clear();
+        assert!(inventory.assets(&NameResolver::new()).is_empty());
+    }
+
+    #[test]
+    fn mac_vendors() {
+        let resolver = NameResolver::new();
+
+        assert_eq!(
+            vendor("00:50:56:01:02:03", &resolver).as_deref(),
+            Some("VMware")
+        );
+        assert_eq!(
+            vendor("02:42:ac:11:00:02", &resolver).as_deref(),
+            Some("Locally administered")
+        );
+        // Embedded OUI table
+        assert_eq!(
+            vendor("00:1b:63:84:45:e6", &resolver).as_deref(),
+            Some("Apple, Inc.")
+        );
+        assert_eq!(vendor("00:00:01:84:45:e6", &resolver), None);
+    }
+
+    #[test]
+    fn icmp_type_numbers() {
+        assert_eq!(type_number("Neighbor Advertisement (136)"), Some(136));
+        assert_eq!(type_number("Unknown (200)"), Some(200));
+        assert_eq!(type_number("Unknown"), None);
+    }
+
+    #[test]
+    fn inventory_csv() {
+        let csv = inventory_to_csv(&[Asset {
+            ip: IpAddr::V4(Ipv4Addr::new(10, 10, 10, 10)),
+            mac: "00:00:0c:01:02:03".to_owned(),
+            vendor: Some("Cisco Systems, Inc".to_owned()),
+            hostnames: vec!["router".to_owned(), "gateway.lan".to_owned()],
+            first_seen: 1_700_000_000_000,
+            last_seen: 1_700_000_000_000,
+            services: vec!["TCP/22".to_owned(), "UDP/53".to_owned()],
+        }]);
+        let time = Local
+            .timestamp_opt(1_700_000_000, 0)
+            .unwrap()
+            .format("%Y-%m-%d %H:%M:%S");
+
+        assert_eq!(
+            csv,
+            format!(
+                "IP,MAC,Vendor,Hostnames,First Seen,Last Seen,Services\n\
+                 10.10.10.10,00:00:0c:01:02:03,\"Cisco Systems, Inc\",router gateway.lan,{},{},\
+                 TCP/22 UDP/53\n",
+                time, time
+            )
+        );
+    }
+}
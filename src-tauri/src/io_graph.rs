@@ -0,0 +1,299 @@
+//! Time series of the traffic, for the IO graphs
+//!
+//! The packets and bytes captured are bucketed by interval of capture time (one second by
+//! default), for the whole traffic and, optionally:
+//! - for each protocol, the innermost one of each packet
+//! - for each of a list of display filters
+//!
+//! The series are accumulated as the packets are stored, whatever the layers they are dissected
+//! up to, and start over whenever the settings change.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use sniffer_parser::serializable_packet::ParsedPacket;
+use tracing::info;
+
+use crate::display_filter::DisplayFilter;
+use crate::statistics::Counters;
+use crate::{SniffingError, SniffingState};
+
+/// Default length of the intervals, in milliseconds
+const DEFAULT_INTERVAL: u64 = 1000;
+
+/// Buckets returned for each series, at most
+const MAX_BUCKETS: usize = 100_000;
+
+/// Series accumulated for the IO graphs
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct IoGraphSettings {
+    /// Length of the intervals, in milliseconds
+    pub interval: u64,
+    pub split_by_protocol: bool,
+    /// Display filters of the packets of the additional series
+    pub filters: Vec<String>,
+}
+
+impl Default for IoGraphSettings {
+    fn default() -> Self {
+        IoGraphSettings {
+            interval: DEFAULT_INTERVAL,
+            split_by_protocol: false,
+            filters: vec![],
+        }
+    }
+}
+
+/// Series of the traffic of a set of packets, as returned to the frontend
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct IoSeries {
+    /// "All", the protocol or the display filter of the packets
+    pub name: String,
+    /// Packets and bytes of each interval
+    pub packets: Vec<usize>,
+    pub bytes: Vec<usize>,
+}
+
+/// Time series of the traffic, as returned to the frontend
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct IoSeriesReport {
+    /// Start of the first interval, as a Unix timestamp in milliseconds
+    pub start: i64,
+    pub interval: u64,
+    /// Series of the whole traffic, then of each protocol, then of each display filter
+    pub series: Vec<IoSeries>,
+}
+
+/// Collector of the time series of the collected packets
+#[derive(Debug, Default)]
+pub struct IoGraph {
+    settings: IoGraphSettings,
+    filters: Vec<DisplayFilter>,
+    total: BTreeMap<i64, Counters>,
+    protocols: BTreeMap<String, BTreeMap<i64, Counters>>,
+    filtered: Vec<BTreeMap<i64, Counters>>,
+}
+
+impl IoGraph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn settings(&self) -> IoGraphSettings {
+        self.settings.clone()
+    }
+
+    /// Replace the settings, compiling the display filters, and start the series over
+    fn configure(&mut self, settings: IoGraphSettings) -> Result<(), String> {
+        if settings.interval == 0 {
+            return Err("The interval must be at least 1 ms".to_owned());
+        }
+        let filters = settings
+            .filters
+            .iter()
+            .map(|filter| DisplayFilter::compile(filter))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        self.settings = settings;
+        self.filters = filters;
+        self.clear();
+
+        Ok(())
+    }
+
+    /// Account a packet of the given length, given its protocol stack
+    pub fn update(&mut self, packet: &ParsedPacket, protocols: &[String], bytes: usize) {
+        let time = (packet.get_timestamp() / 1000) as i64;
+        let bucket = time - time.rem_euclid(self.settings.interval as i64);
+
+        self.total.entry(bucket).or_default().add(bytes);
+
+        if self.settings.split_by_protocol {
+            let protocol = protocols
+                .last()
+                .map_or("Other", |protocol| protocol.as_str());
+            self.protocols
+                .entry(protocol.to_owned())
+                .or_default()
+                .entry(bucket)
+                .or_default()
+                .add(bytes);
+        }
+
+        for (filter, series) in self.filters.iter().zip(self.filtered.iter_mut()) {
+            if filter.matches(packet) {
+                series.entry(bucket).or_default().add(bytes);
+            }
+        }
+    }
+
+    /// Get the series, over the intervals from the first to the last packet
+    pub fn series(&self) -> IoSeriesReport {
+        let interval = self.settings.interval;
+        let (start, end) = match (self.total.keys().next(), self.total.keys().next_back()) {
+            (Some(&start), Some(&end)) => (start, end),
+            _ => {
+                return IoSeriesReport {
+                    start: 0,
+                    interval,
+                    series: vec![],
+                }
+            }
+        };
+        // The most recent intervals are kept
+        let buckets = (((end - start) as u64 / interval) as usize + 1).min(MAX_BUCKETS);
+        let start = end - (buckets as i64 - 1) * interval as i64;
+
+        let series = |name: &str, buckets_counters: &BTreeMap<i64, Counters>| {
+            let mut series = IoSeries {
+                name: name.to_owned(),
+                packets: vec![0; buckets],
+                bytes: vec![0; buckets],
+            };
+            for (bucket, counters) in buckets_counters.range(start..) {
+                let index = ((bucket - start) as u64 / interval) as usize;
+                series.packets[index] = counters.packets;
+                series.bytes[index] = counters.bytes;
+            }
+            series
+        };
+
+        let mut report = vec![series("All", &self.total)];
+        report.extend(
+            self.protocols
+                .iter()
+                .map(|(protocol, buckets_counters)| series(protocol, buckets_counters)),
+        );
+        report.extend(
+            self.filters
+                .iter()
+                .zip(&self.filtered)
+                .map(|(filter, buckets_counters)| series(filter.text(), buckets_counters)),
+        );
+
+        IoSeriesReport {
+            start,
+            interval,
+            series: report,
+        }
+    }
+
+    /// Forget the series, keeping the settings
+    pub fn clear(&mut self) {
+        self.total.clear();
+        self.protocols.clear();
+        self.filtered = vec![BTreeMap::new(); self.filters.len()];
+    }
+}
+
+/// Sets the interval and the series of the IO graphs, starting them over
+#[tauri::command]
+pub fn set_io_graph(
+    settings: IoGraphSettings,
+    state: tauri::State<SniffingState>,
+) -> Result<(), SniffingError> {
+    state
+        .packets
+        .lock()
+        .unwrap()
+        .io_graph
+        .configure(settings.clone())
+        .map_err(|e| SniffingError::InvalidIoGraph(format!("Invalid IO graph: {}", e)))?;
+
+    info!("IO graph set: {:?}", settings);
+
+    Ok(())
+}
+
+/// Returns the interval and the series of the IO graphs
+#[tauri::command]
+pub fn get_io_graph(state: tauri::State<SniffingState>) -> IoGraphSettings {
+    state.packets.lock().unwrap().io_graph.settings()
+}
+
+/// Returns the time series of the traffic, for the IO graphs
+#[tauri::command]
+pub fn get_io_series(state: tauri::State<SniffingState>) -> IoSeriesReport {
+    state.packets.lock().unwrap().io_graph.series()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use pnet::packet::ethernet::EthernetPacket;
+    use sniffer_parser::serializable_packet::ParsedPacket;
+    use sniffer_parser::{at_capture_time, parse_ethernet_frame};
+
+    use super::{IoGraph, IoGraphSettings};
+
+    // UDP datagram sent by 10.10.10.10:4444 to 11.11.11.11:9
+    const UDP_FRAME: &[u8] = &[
+        0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x08, 0x00, 0x45,
+        0x00, 0x00, 0x1c, 0x00, 0x00, 0x00, 0x00, 0x40, 0x11, 0x00, 0x00, 0x0a, 0x0a, 0x0a, 0x0a,
+        0x0b, 0x0b, 0x0b, 0x0b, 0x11, 0x5c, 0x00, 0x09, 0x00, 0x08, 0x00, 0x00,
+    ];
+
+    #[test]
+    fn series_of_each_interval() {
+        let mut io_graph = IoGraph::new();
+        io_graph
+            .configure(IoGraphSettings {
+                interval: 500,
+                split_by_protocol: true,
+                filters: vec!["tcp".to_owned()],
+            })
+            .unwrap();
+
+        for (milliseconds, protocols) in [(100, "UDP"), (300, "UDP"), (1700, "DNS")] {
+            let packet = udp_packet(milliseconds);
+            io_graph.update(&packet, &["IPv4".to_owned(), protocols.to_owned()], 42);
+        }
+
+        let report = io_graph.series();
+        assert_eq!(report.start, 1_700_000_000_000);
+        assert_eq!(report.interval, 500);
+        assert_eq!(report.series.len(), 4);
+        assert_eq!(report.series[0].name, "All");
+        assert_eq!(report.series[0].packets, vec![2, 0, 0, 1]);
+        assert_eq!(report.series[0].bytes, vec![84, 0, 0, 42]);
+        assert_eq!(report.series[1].name, "DNS");
+        assert_eq!(report.series[1].packets, vec![0, 0, 0, 1]);
+        assert_eq!(report.series[2].name, "UDP");
+        assert_eq!(report.series[2].packets, vec![2, 0, 0, 0]);
+        assert_eq!(report.series[3].name, "tcp");
+        assert_eq!(report.series[3].packets, vec![0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn invalid_settings() {
+        let mut io_graph = IoGraph::new();
+        assert!(io_graph
+            .configure(IoGraphSettings {
+                interval: 0,
+                ..IoGraphSettings::default()
+            })
+            .is_err());
+        assert!(io_graph
+            .configure(IoGraphSettings {
+                filters: vec!["ip.src ==".to_owned()],
+                ..IoGraphSettings::default()
+            })
+            .is_err());
+        assert_eq!(io_graph.settings(), IoGraphSettings::default());
+        assert!(io_graph.series().series.is_empty());
+    }
+
+    ///////////////////// Utils
+
+    /// Packet captured the given milliseconds after 1700000000 s since the Unix epoch
+    fn udp_packet(milliseconds: u64) -> ParsedPacket {
+        at_capture_time(
+            Duration::from_millis(1_700_000_000_000 + milliseconds),
+            || parse_ethernet_frame(&EthernetPacket::new(UDP_FRAME).unwrap(), 0),
+        )
+    }
+}
@@ -0,0 +1,455 @@
+//! Passive latency baselines of the destinations, and alerts on their regressions
+//!
+//! The latency towards each destination is measured from the packets exchanged with it, without
+//! sending any probe:
+//! - TCP handshake: time between a SYN and the SYN-ACK answering it, for the server (SYNs sent
+//!   again are not measured, as their SYN-ACK may answer any of them)
+//! - DNS: time between a query and its response, paired by transaction ID, for the DNS server
+//!
+//! The baseline of each destination and kind of measurement is the exponentially weighted moving
+//! average of its samples. Once it has enough samples, the median of the last ones is compared to
+//! it, raising an alert when the median exceeds the baseline by the regression ratio and by a
+//! minimum latency, not to alert on the jitter of the closest destinations. The samples of a
+//! regression are left out of the baseline, so that a degraded latency does not become the new
+//! normal, and a new alert is raised only once the latency recovered.
+
+use std::collections::{HashMap, VecDeque};
+use std::net::IpAddr;
+
+use chrono::{DateTime, Local};
+use pnet::packet::tcp::TcpFlags;
+use serde::Serialize;
+use sniffer_parser::serializable_packet::{ParsedPacket, SerializablePacket};
+use tracing::warn;
+
+use crate::arp_watch::{SecurityAlert, ALERTS_SIZE};
+use crate::statistics::Timestamp;
+use crate::SniffingState;
+
+/// Weight of a new sample in the baseline
+const BASELINE_WEIGHT: f64 = 1.0 / 16.0;
+
+/// Samples of a baseline before its regressions are detected
+const BASELINE_MIN_SAMPLES: usize = 20;
+
+/// Last samples whose median is compared to the baseline
+const RECENT_SAMPLES: usize = 5;
+
+/// Ratio between the recent latency and the baseline raising an alert
+const REGRESSION_RATIO: f64 = 2.0;
+
+/// Increase of the recent latency over the baseline raising an alert, in milliseconds
+const REGRESSION_MIN_INCREASE: f64 = 10.0;
+
+/// Handshakes and queries waiting for an answer at most, the ones waiting for longer than the
+/// pending timeout being dropped once reached
+const PENDING_SIZE: usize = 10_000;
+
+/// Time after which a handshake or a query is no longer answered, in microseconds
+const PENDING_TIMEOUT: i64 = 10_000_000;
+
+/// Measurement of the latency of a destination
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[serde(rename_all = "camelCase")]
+pub enum LatencyKind {
+    TcpHandshake,
+    Dns,
+}
+
+/// Latency baseline of a destination, as returned to the frontend
+///
+/// Latencies are in milliseconds.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct LatencyBaseline {
+    pub destination: IpAddr,
+    pub kind: LatencyKind,
+    pub samples: usize,
+    pub baseline: f64,
+    /// Median of the last samples
+    pub recent: f64,
+    pub min: f64,
+    pub max: f64,
+    /// Whether the recent latency regressed from the baseline
+    pub degraded: bool,
+    pub regressions: usize,
+    pub last_seen: Timestamp,
+}
+
+/// Baseline of a destination with its last samples
+#[derive(Debug)]
+struct TrackedBaseline {
+    baseline: LatencyBaseline,
+    /// Samples folded in the baseline
+    baseline_samples: usize,
+    recent: VecDeque<f64>,
+}
+
+impl TrackedBaseline {
+    fn new(destination: IpAddr, kind: LatencyKind, latency: f64, time: i64) -> Self {
+        TrackedBaseline {
+            baseline: LatencyBaseline {
+                destination,
+                kind,
+                samples: 0,
+                baseline: latency,
+                recent: latency,
+                min: latency,
+                max: latency,
+                degraded: false,
+                regressions: 0,
+                last_seen: time,
+            },
+            baseline_samples: 0,
+            recent: VecDeque::with_capacity(RECENT_SAMPLES),
+        }
+    }
+
+    /// Add a sample, returning whether the latency just regressed
+    fn add(&mut self, latency: f64, time: i64) -> bool {
+        let baseline = &mut self.baseline;
+        baseline.samples += 1;
+        baseline.min = baseline.min.min(latency);
+        baseline.max = baseline.max.max(latency);
+        baseline.last_seen = baseline.last_seen.max(time);
+
+        if self.recent.len() >= RECENT_SAMPLES {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(latency);
+        baseline.recent = median(&self.recent);
+
+        let regressed = self.baseline_samples >= BASELINE_MIN_SAMPLES
+            && self.recent.len() >= RECENT_SAMPLES
+            && baseline.recent >= REGRESSION_RATIO * baseline.baseline
+            && baseline.recent - baseline.baseline >= REGRESSION_MIN_INCREASE;
+        if regressed {
+            let started = !baseline.degraded;
+            if started {
+                baseline.degraded = true;
+                baseline.regressions += 1;
+            }
+            return started;
+        }
+
+        baseline.degraded = false;
+        baseline.baseline += BASELINE_WEIGHT * (latency - baseline.baseline);
+        self.baseline_samples += 1;
+
+        false
+    }
+}
+
+/// Client and server addresses and ports of a handshake or a query
+type PendingKey = (IpAddr, u16, IpAddr, u16);
+
+/// Tracker of the latency of the destinations of the collected packets
+#[derive(Debug, Default)]
+pub struct LatencyWatch {
+    /// Time of the SYN of each handshake, in microseconds, none if sent again
+    handshakes: HashMap<PendingKey, Option<i64>>,
+    /// Time of each DNS query, by its transaction ID, in microseconds
+    queries: HashMap<(PendingKey, u16), i64>,
+    baselines: HashMap<(IpAddr, LatencyKind), TrackedBaseline>,
+    /// Last alerts raised, the oldest first
+    alerts: VecDeque<SecurityAlert>,
+}
+
+impl LatencyWatch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Measure the latency answering a packet, if it is a SYN-ACK or a DNS response, given the
+    /// time it was received, returning the alerts raised
+    pub fn update(&mut self, packet: &ParsedPacket, time: DateTime<Local>) -> Vec<SecurityAlert> {
+        let (source, destination) = match packet.get_network_layer_packet() {
+            Some(SerializablePacket::Ipv4Packet(ipv4)) => {
+                (IpAddr::V4(ipv4.source), IpAddr::V4(ipv4.destination))
+            }
+            Some(SerializablePacket::Ipv6Packet(ipv6)) => {
+                (IpAddr::V6(ipv6.source), IpAddr::V6(ipv6.destination))
+            }
+            _ => return vec![],
+        };
+        let micros = time.timestamp_micros();
+
+        let sample = match packet.get_transport_layer_packet() {
+            Some(SerializablePacket::TcpPacket(tcp)) if tcp.flags & TcpFlags::SYN != 0 => {
+                if tcp.flags & TcpFlags::ACK == 0 {
+                    let key = (source, tcp.source, destination, tcp.destination);
+                    self.prune(micros);
+                    self.handshakes
+                        .entry(key)
+                        .and_modify(|syn| *syn = None)
+                        .or_insert(Some(micros));
+                    None
+                } else {
+                    let key = (destination, tcp.destination, source, tcp.source);
+                    self.handshakes
+                        .remove(&key)
+                        .flatten()
+                        .map(|syn| (LatencyKind::TcpHandshake, micros - syn))
+                }
+            }
+            Some(SerializablePacket::UdpPacket(udp)) => match packet.get_application_layer_packet()
+            {
+                Some(SerializablePacket::DnsPacket(dns)) if dns.header.query => {
+                    let key = (source, udp.source, destination, udp.destination);
+                    self.prune(micros);
+                    self.queries.insert((key, dns.header.id), micros);
+                    None
+                }
+                Some(SerializablePacket::DnsPacket(dns)) => {
+                    let key = (destination, udp.destination, source, udp.source);
+                    self.queries
+                        .remove(&(key, dns.header.id))
+                        .map(|query| (LatencyKind::Dns, micros - query))
+                }
+                _ => None,
+            },
+            _ => None,
+        };
+        let (kind, latency) = match sample {
+            Some((kind, latency)) if latency >= 0 => (kind, latency as f64 / 1000.0),
+            _ => return vec![],
+        };
+
+        let time = time.timestamp_millis();
+        let tracked = self
+            .baselines
+            .entry((source, kind))
+            .or_insert_with(|| TrackedBaseline::new(source, kind, latency, time));
+        if !tracked.add(latency, time) {
+            return vec![];
+        }
+
+        let alert = SecurityAlert::LatencyRegression {
+            time,
+            destination: source,
+            kind,
+            baseline: tracked.baseline.baseline,
+            latency: tracked.baseline.recent,
+        };
+        warn!("Security alert: {:?}", alert);
+        if self.alerts.len() >= ALERTS_SIZE {
+            self.alerts.pop_front();
+        }
+        self.alerts.push_back(alert.clone());
+
+        vec![alert]
+    }
+
+    /// Drop the handshakes and the queries no longer answered, once too many are waiting
+    fn prune(&mut self, micros: i64) {
+        if self.handshakes.len() >= PENDING_SIZE {
+            self.handshakes
+                .retain(|_, syn| syn.is_some_and(|syn| micros - syn < PENDING_TIMEOUT));
+        }
+        if self.queries.len() >= PENDING_SIZE {
+            self.queries
+                .retain(|_, query| micros - *query < PENDING_TIMEOUT);
+        }
+    }
+
+    /// Get the latency baselines, sorted by destination and kind
+    pub fn baselines(&self) -> Vec<LatencyBaseline> {
+        let mut baselines: Vec<LatencyBaseline> = self
+            .baselines
+            .values()
+            .map(|tracked| tracked.baseline.clone())
+            .collect();
+        baselines.sort_by_key(|baseline| (baseline.destination, baseline.kind));
+
+        baselines
+    }
+
+    /// Get the last alerts raised, the oldest first
+    pub fn alerts(&self) -> Vec<SecurityAlert> {
+        self.alerts.iter().cloned().collect()
+    }
+
+    pub fn clear(&mut self) {
+        self.handshakes.clear();
+        self.queries.clear();
+        self.baselines.clear();
+        self.alerts.clear();
+    }
+}
+
+/// Get the median of some latencies
+fn median(latencies: &VecDeque<f64>) -> f64 {
+    let mut sorted: Vec<f64> = latencies.iter().copied().collect();
+    sorted.sort_by(f64::total_cmp);
+
+    match sorted.len() {
+        0 => 0.0,
+        len if len % 2 == 1 => sorted[len / 2],
+        len => (sorted[len / 2 - 1] + sorted[len / 2]) / 2.0,
+    }
+}
+
+/// Returns the latency baselines of the TCP handshakes and the DNS queries of each destination
+#[tauri::command]
+pub fn get_latency_baselines(state: tauri::State<SniffingState>) -> Vec<LatencyBaseline> {
+    state.packets.lock().unwrap().latency_watch.baselines()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr};
+
+    use pnet::packet::tcp::TcpFlags;
+    use sniffer_parser::serializable_packet::ParsedPacket;
+
+    use super::{LatencyKind, LatencyWatch, BASELINE_MIN_SAMPLES, RECENT_SAMPLES};
+    use crate::arp_watch::SecurityAlert;
+    use crate::test_utils::{at, tcp_packet, udp_packet};
+
+    const CLIENT: [u8; 4] = [10, 0, 0, 10];
+    const SERVER: [u8; 4] = [192, 0, 2, 80];
+    const DNS_SERVER: [u8; 4] = [10, 0, 0, 53];
+
+    #[test]
+    fn handshake_regression() {
+        let mut watch = LatencyWatch::new();
+        let mut alerts = vec![];
+        let mut start = 0;
+        let mut handshake = |watch: &mut LatencyWatch, port: u16, latency: i64| {
+            let syn = tcp_packet(0, (CLIENT, port), (SERVER, 443), TcpFlags::SYN);
+            let syn_ack = tcp_packet(
+                0,
+                (SERVER, 443),
+                (CLIENT, port),
+                TcpFlags::SYN | TcpFlags::ACK,
+            );
+            let mut alerts = watch.update(&syn, at(start));
+            alerts.extend(watch.update(&syn_ack, at(start + latency)));
+            start += 1000;
+            alerts
+        };
+
+        for port in 0..BASELINE_MIN_SAMPLES as u16 {
+            alerts.extend(handshake(&mut watch, 40000 + port, 20));
+        }
+        // A single slow handshake is not a regression
+        alerts.extend(handshake(&mut watch, 41000, 200));
+        assert!(alerts.is_empty());
+
+        for port in 0..2 * RECENT_SAMPLES as u16 {
+            alerts.extend(handshake(&mut watch, 42000 + port, 100));
+        }
+        assert_eq!(alerts.len(), 1);
+        match &alerts[0] {
+            SecurityAlert::LatencyRegression {
+                destination,
+                kind,
+                baseline,
+                latency,
+                ..
+            } => {
+                assert_eq!(*destination, IpAddr::V4(Ipv4Addr::from(SERVER)));
+                assert_eq!(*kind, LatencyKind::TcpHandshake);
+                assert!(*baseline > 20.0 && *baseline < 40.0);
+                assert_eq!(*latency, 100.0);
+            }
+            alert => panic!("Unexpected alert {:?}", alert),
+        }
+
+        let baselines = watch.baselines();
+        assert_eq!(baselines.len(), 1);
+        assert!(baselines[0].degraded);
+        assert_eq!(baselines[0].regressions, 1);
+        assert_eq!(
+            baselines[0].samples,
+            BASELINE_MIN_SAMPLES + 1 + 2 * RECENT_SAMPLES
+        );
+        assert_eq!((baselines[0].min, baselines[0].max), (20.0, 200.0));
+
+        // Once recovered, a new regression raises a new alert
+        for port in 0..RECENT_SAMPLES as u16 {
+            alerts.extend(handshake(&mut watch, 43000 + port, 20));
+        }
+        assert!(!watch.baselines()[0].degraded);
+        for port in 0..RECENT_SAMPLES as u16 {
+            alerts.extend(handshake(&mut watch, 44000 + port, 100));
+        }
+        assert_eq!(alerts.len(), 2);
+        assert_eq!(watch.alerts(), alerts);
+
+        // SYNs sent again are not measured
+        let syn = tcp_packet(0, (CLIENT, 45000), (SERVER, 443), TcpFlags::SYN);
+        let syn_ack = tcp_packet(
+            0,
+            (SERVER, 443),
+            (CLIENT, 45000),
+            TcpFlags::SYN | TcpFlags::ACK,
+        );
+        watch.update(&syn, at(start));
+        watch.update(&syn, at(start + 1000));
+        watch.update(&syn_ack, at(start + 1010));
+        assert_eq!(
+            watch.baselines()[0].samples,
+            BASELINE_MIN_SAMPLES + 1 + 4 * RECENT_SAMPLES
+        );
+
+        watch.clear();
+        assert!(watch.baselines().is_empty());
+    }
+
+    #[test]
+    fn dns_latency() {
+        let mut watch = LatencyWatch::new();
+
+        watch.update(
+            &dns_packet((CLIENT, 50000), (DNS_SERVER, 53), 1, false),
+            at(0),
+        );
+        watch.update(
+            &dns_packet((CLIENT, 50000), (DNS_SERVER, 53), 2, false),
+            at(5),
+        );
+        watch.update(
+            &dns_packet((DNS_SERVER, 53), (CLIENT, 50000), 2, true),
+            at(15),
+        );
+        watch.update(
+            &dns_packet((DNS_SERVER, 53), (CLIENT, 50000), 1, true),
+            at(30),
+        );
+        // Responses without a query are not measured
+        watch.update(
+            &dns_packet((DNS_SERVER, 53), (CLIENT, 50000), 3, true),
+            at(40),
+        );
+
+        let baselines = watch.baselines();
+        assert_eq!(baselines.len(), 1);
+        assert_eq!(
+            baselines[0].destination,
+            IpAddr::V4(Ipv4Addr::from(DNS_SERVER))
+        );
+        assert_eq!(baselines[0].kind, LatencyKind::Dns);
+        assert_eq!(baselines[0].samples, 2);
+        assert_eq!((baselines[0].min, baselines[0].max), (10.0, 30.0));
+        assert_eq!(baselines[0].recent, 20.0);
+    }
+
+    ///////////////////// Utils
+
+    /// UDP datagram carrying a DNS query or response for an A record
+    fn dns_packet(
+        source: ([u8; 4], u16),
+        destination: ([u8; 4], u16),
+        id: u16,
+        response: bool,
+    ) -> ParsedPacket {
+        let mut message = id.to_be_bytes().to_vec();
+        message.extend(if response { [0x81, 0x80] } else { [0x01, 0x00] });
+        message.extend([0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
+        message.extend(b"\x07example\x03com\x00");
+        message.extend([0x00, 0x01, 0x00, 0x01]);
+
+        udp_packet(0, source, destination, &message)
+    }
+}
@@ -0,0 +1,382 @@
+//! Logging of the application
+//!
+//! Events are recorded with `tracing`, the records of the dependencies still using the `log`
+//! facade being forwarded to it. They are filtered by the level of the module emitting them, which
+//! can be changed at runtime: a level applies to the targets starting with the path of the module
+//! (e.g. "sniffer_parser::application" covers all the dissectors), the most specific one taking
+//! precedence, and the default one to all the other modules. The levels are turned into the
+//! directives of an `EnvFilter`, replaced through the handle of its reload layer.
+//!
+//! Besides being written to the log file and to the standard output, the last records are kept in
+//! a ring buffer by a layer of their own, so that they can be displayed in the app or exported to a
+//! file.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt::{self, Write as _};
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock, RwLock};
+
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use tracing::field::{Field, Visit};
+use tracing::{info, warn, Event, Subscriber};
+use tracing_log::NormalizeEvent;
+use tracing_subscriber::filter::{EnvFilter, LevelFilter};
+use tracing_subscriber::fmt::format::Writer;
+use tracing_subscriber::fmt::time::FormatTime;
+use tracing_subscriber::layer::{Context, SubscriberExt};
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{reload, Layer, Registry};
+
+use crate::SniffingError;
+
+/// Records kept in the ring buffer
+const LOG_BUFFER_SIZE: usize = 5000;
+
+/// Levels of the modules, shared by all the threads logging
+static LOG_LEVELS: OnceLock<RwLock<LogLevels>> = OnceLock::new();
+
+/// Handle replacing the filter of the subscriber, set once it is installed
+static FILTER_HANDLE: OnceLock<reload::Handle<EnvFilter, Registry>> = OnceLock::new();
+
+/// Last records logged, the oldest first
+static LOG_BUFFER: Mutex<VecDeque<LogRecord>> = Mutex::new(VecDeque::new());
+
+/// Verbosity of a module
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum LogLevel {
+    Off,
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl From<LogLevel> for LevelFilter {
+    fn from(level: LogLevel) -> Self {
+        match level {
+            LogLevel::Off => LevelFilter::OFF,
+            LogLevel::Error => LevelFilter::ERROR,
+            LogLevel::Warn => LevelFilter::WARN,
+            LogLevel::Info => LevelFilter::INFO,
+            LogLevel::Debug => LevelFilter::DEBUG,
+            LogLevel::Trace => LevelFilter::TRACE,
+        }
+    }
+}
+
+/// Levels of the modules logging records
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct LogLevels {
+    /// Level of the modules without a level of their own
+    pub default: LogLevel,
+    /// Levels of the modules, by their path (e.g. "sniffer_parser::application::http")
+    pub modules: BTreeMap<String, LogLevel>,
+}
+
+impl Default for LogLevels {
+    fn default() -> Self {
+        LogLevels {
+            default: LogLevel::Warn,
+            modules: BTreeMap::from([
+                ("wirefish".to_owned(), LogLevel::Info),
+                ("sniffer_parser".to_owned(), LogLevel::Debug),
+            ]),
+        }
+    }
+}
+
+impl LogLevels {
+    /// Build the filter enabling the events of each module up to its level
+    fn filter(&self) -> Result<EnvFilter, String> {
+        let directives: Vec<String> = std::iter::once(LevelFilter::from(self.default).to_string())
+            .chain(
+                self.modules
+                    .iter()
+                    .map(|(path, level)| format!("{}={}", path, LevelFilter::from(*level))),
+            )
+            .collect();
+
+        EnvFilter::try_new(directives.join(","))
+            .map_err(|e| format!("Invalid log levels {:?}: {}", directives, e))
+    }
+}
+
+/// Record kept in the ring buffer
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct LogRecord {
+    pub time: String,
+    pub level: String,
+    pub target: String,
+    pub message: String,
+}
+
+/// Layer keeping the events recorded in a ring buffer of the given size
+struct RingBufferLayer {
+    buffer: &'static Mutex<VecDeque<LogRecord>>,
+    size: usize,
+}
+
+impl<S: Subscriber> Layer<S> for RingBufferLayer {
+    fn on_event(&self, event: &Event<'_>, _context: Context<'_, S>) {
+        let mut visitor = MessageVisitor::default();
+        event.record(&mut visitor);
+
+        // Records forwarded from the log facade keep their own target in their fields
+        let normalized = event.normalized_metadata();
+        let metadata = normalized.as_ref().unwrap_or_else(|| event.metadata());
+        let record = LogRecord {
+            time: Local::now().format("%Y-%m-%d %H:%M:%S%.3f").to_string(),
+            level: metadata.level().to_string(),
+            target: metadata.target().to_owned(),
+            message: visitor.message + &visitor.fields,
+        };
+
+        push_record(&mut self.buffer.lock().unwrap(), record, self.size);
+    }
+}
+
+/// Visitor writing the message of an event, and its other fields after it
+#[derive(Default)]
+struct MessageVisitor {
+    message: String,
+    fields: String,
+}
+
+impl Visit for MessageVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        match field.name() {
+            "message" => {
+                let _ = write!(self.message, "{:?}", value);
+            }
+            // Target and location of the records forwarded from the log facade
+            name if name.starts_with("log.") => {}
+            name => {
+                let _ = write!(self.fields, " {}={:?}", name, value);
+            }
+        }
+    }
+}
+
+/// Local time of the events written to the standard output and to the log file
+struct LocalTime;
+
+impl FormatTime for LocalTime {
+    fn format_time(&self, writer: &mut Writer<'_>) -> fmt::Result {
+        write!(writer, "{}", Local::now().format("[%Y-%m-%d][%H:%M:%S]"))
+    }
+}
+
+/// Append a record to a ring buffer of the given size, dropping the oldest one if full
+fn push_record(buffer: &mut VecDeque<LogRecord>, record: LogRecord, size: usize) {
+    while buffer.len() >= size {
+        buffer.pop_front();
+    }
+    buffer.push_back(record);
+}
+
+fn log_levels() -> &'static RwLock<LogLevels> {
+    LOG_LEVELS.get_or_init(|| RwLock::new(LogLevels::default()))
+}
+
+/// Open the log file for appending, creating its directory if needed
+fn open_log_file(path: &Path) -> Result<File, String> {
+    if let Some(directory) = path.parent() {
+        fs::create_dir_all(directory)
+            .map_err(|e| format!("[{}] Log directory not created: {}", directory.display(), e))?;
+    }
+
+    File::options()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| format!("[{}] Log file not opened: {}", path.display(), e))
+}
+
+/// Install the subscriber of the events, writing them to the standard output, to the given log
+/// file if it can be opened, and to the ring buffer
+pub fn init(log_file: Option<PathBuf>) {
+    let filter = log_levels().read().unwrap().filter().unwrap();
+    let (filter, handle) = reload::Layer::new(filter);
+
+    let (file, file_error) = match log_file.map(|path| open_log_file(&path)).transpose() {
+        Ok(file) => (file, None),
+        Err(e) => (None, Some(e)),
+    };
+
+    let initialized = Registry::default()
+        .with(filter)
+        .with(tracing_subscriber::fmt::layer().with_timer(LocalTime))
+        .with(file.map(|file| {
+            tracing_subscriber::fmt::layer()
+                .with_timer(LocalTime)
+                .with_ansi(false)
+                .with_writer(Mutex::new(file))
+        }))
+        .with(RingBufferLayer {
+            buffer: &LOG_BUFFER,
+            size: LOG_BUFFER_SIZE,
+        })
+        .try_init();
+
+    if initialized.is_ok() {
+        let _ = FILTER_HANDLE.set(handle);
+    }
+    if let Some(error) = file_error {
+        warn!("{}", error);
+    }
+}
+
+/// Replaces the levels of the modules logging records
+#[tauri::command]
+pub fn set_log_levels(levels: LogLevels) -> Result<(), SniffingError> {
+    let filter = levels.filter().map_err(SniffingError::InvalidLogLevels)?;
+
+    info!("Log levels set: {:?}", levels);
+    if let Some(handle) = FILTER_HANDLE.get() {
+        handle
+            .reload(filter)
+            .map_err(|e| SniffingError::InvalidLogLevels(format!("Log levels not set: {}", e)))?;
+    }
+    *log_levels().write().unwrap() = levels;
+
+    Ok(())
+}
+
+/// Returns the levels of the modules logging records
+#[tauri::command]
+pub fn get_log_levels() -> LogLevels {
+    log_levels().read().unwrap().clone()
+}
+
+/// Returns the last records logged, the oldest first
+#[tauri::command]
+pub fn get_log_records() -> Vec<LogRecord> {
+    LOG_BUFFER.lock().unwrap().iter().cloned().collect()
+}
+
+/// Exports the last records logged to a text file, returning the number of records exported
+#[tauri::command]
+pub fn export_log_records(path: String) -> Result<usize, SniffingError> {
+    let records = get_log_records();
+    let text: String = records
+        .iter()
+        .map(|record| {
+            format!(
+                "[{}][{}][{}] {}\n",
+                record.time, record.level, record.target, record.message
+            )
+        })
+        .collect();
+
+    fs::write(&path, text)
+        .map_err(|e| SniffingError::ExportFailed(format!("Export failed: {}", e)))?;
+
+    info!("[{}] Exported {} log records", path, records.len());
+
+    Ok(records.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap, VecDeque};
+    use std::sync::Mutex;
+
+    use tracing::{debug, info, trace, warn};
+    use tracing_subscriber::layer::SubscriberExt;
+    use tracing_subscriber::Registry;
+
+    use super::{push_record, LogLevel, LogLevels, LogRecord, RingBufferLayer};
+
+    #[test]
+    fn module_levels() {
+        static BUFFER: Mutex<VecDeque<LogRecord>> = Mutex::new(VecDeque::new());
+        let levels = LogLevels {
+            default: LogLevel::Warn,
+            modules: BTreeMap::from([
+                ("sniffer_parser".to_owned(), LogLevel::Info),
+                ("sniffer_parser::application".to_owned(), LogLevel::Trace),
+                ("sniffer_parser::application::tls".to_owned(), LogLevel::Off),
+            ]),
+        };
+        let subscriber = Registry::default()
+            .with(levels.filter().unwrap())
+            .with(RingBufferLayer {
+                buffer: &BUFFER,
+                size: 10,
+            });
+
+        tracing::subscriber::with_default(subscriber, || {
+            info!(target: "wirefish::offline", "default level");
+            warn!(target: "wirefish::offline", "default level");
+            info!(target: "sniffer_parser", "crate level");
+            debug!(target: "sniffer_parser::transport", "crate level");
+            trace!(target: "sniffer_parser::application::http", id = 3, "module level");
+            warn!(target: "sniffer_parser::application::tls", "module off");
+        });
+
+        let buffer = BUFFER.lock().unwrap();
+        let records: Vec<(&str, &str, &str)> = buffer
+            .iter()
+            .map(|record| {
+                (
+                    record.level.as_str(),
+                    record.target.as_str(),
+                    record.message.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            records,
+            vec![
+                ("WARN", "wirefish::offline", "default level"),
+                ("INFO", "sniffer_parser", "crate level"),
+                (
+                    "TRACE",
+                    "sniffer_parser::application::http",
+                    "module level id=3"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_module_levels() {
+        let levels = LogLevels {
+            default: LogLevel::Warn,
+            modules: BTreeMap::from([("sniffer_parser=info".to_owned(), LogLevel::Info)]),
+        };
+
+        assert!(levels.filter().is_err());
+    }
+
+    #[test]
+    fn ring_buffer() {
+        let mut buffer = VecDeque::new();
+        for i in 0..5 {
+            push_record(&mut buffer, build_test_record(i), 3);
+        }
+
+        let messages: Vec<&str> = buffer
+            .iter()
+            .map(|record| record.message.as_str())
+            .collect();
+        assert_eq!(messages, vec!["2", "3", "4"]);
+    }
+
+    ///////////////////// Utils
+
+    fn build_test_record(i: usize) -> LogRecord {
+        LogRecord {
+            time: "2022-09-10 13:38:03.000".to_owned(),
+            level: "INFO".to_owned(),
+            target: "wirefish".to_owned(),
+            message: i.to_string(),
+        }
+    }
+}
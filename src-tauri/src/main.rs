@@ -3,11 +3,81 @@
 //! Functionalities
 //! - List all available network interfaces
 //! - Select a network interface
-//! - Start the sniffing process
+//! - Start the sniffing process, on wireless interfaces in monitor mode too
 //! - Stop the sniffing process
+//! - Stop the sniffing process on its own after a number of packets, megabytes or seconds, or on
+//!   a packet satisfying a display filter
 //! - Pause the sniffing process
 //! - Resume the sniffing process
+//! - Get a window of the collected packets, filtered and sorted, the packets collected being
+//!   notified in batches
 //! - Generate a .csv report of the collected data
+//! - Test the capture on a network interface before sniffing
+//! - Generate synthetic flows (HTTP request, DNS query, TLS handshake and ICMP ping) on an
+//!   interface, to verify the capture, the filters and the dissection end to end
+//! - Get the frames received, undissected and dropped by the kernel or the interface while
+//!   sniffing, to tell whether the sniffer keeps up with the traffic
+//! - Import an offline .pcap file
+//! - Parse a whole .pcap file at once
+//! - Get the packets of a flow of the imported file
+//! - Encrypt a capture file with a passphrase
+//! - Export the collected packets (JSON, .pcap or .pcapng) with a redaction profile
+//! - Export the frames of selected packets as test fixtures of the parser
+//! - Edit the addresses, ports and payload of imported packets before exporting them, fixing up
+//!   their lengths and checksums
+//! - Set the WPA2 credentials to decrypt imported 802.11 captures
+//! - Save the sniffed frames to a .pcapng file, or to a ring buffer of files rotated by size,
+//!   duration or number of frames
+//! - Write the collected packets, conversations and statistics to a SQLite store, and query them
+//!   page by page, filtered or with a full-text search over the decoded fields
+//! - Get the Docker registry downloads and the HTTP proxy tunnels
+//! - Filter the collected packets with a display filter expression
+//! - Get the traffic statistics, in aggregate and for each captured interface
+//! - Get the time series of the packets and bytes captured, split by protocol or display filter,
+//!   for the IO graphs
+//! - Narrow the live capture to selected conversations or endpoints with a capture filter
+//! - Sample the received frames on busy links
+//! - Truncate the received frames to their headers and the first bytes of payload
+//! - List the conversations between endpoints, sorted and filtered, with the service reached by
+//!   each TLS flow
+//! - List the endpoints of each layer (MAC, IP, TCP and UDP) with the traffic they sent and
+//!   received, sorted to find the top talkers
+//! - Follow the reassembled byte streams of a TCP conversation
+//! - List the objects transferred over HTTP and save them to disk
+//! - Get or export the inventory of the hosts of the local network (CSV or JSON)
+//! - Detect ARP spoofing, emitting security alerts
+//! - Get the rates of the ICMP errors of each source, alerting on unreachable and redirect storms
+//! - Detect the proxy auto-configuration (WPAD/PAC) files served by unexpected sources
+//! - Detect the NTP servers answering monlist (mode 7) requests or amplifying their traffic
+//! - Detect the hosts flooded with unsolicited DNS, SSDP or memcached responses by reflection
+//!   attacks
+//! - Keep baselines of the TCP handshake and DNS latency of each destination, alerting on their
+//!   regressions
+//! - Get the support of TCP Fast Open and ECN negotiated by each server
+//! - Detect the races between IPv6 and IPv4 connections of dual-stack clients (Happy Eyeballs),
+//!   with the family winning them and by how much, for each destination
+//! - Decode Multipath TCP options and group the subflows of each MPTCP connection
+//! - List the services advertised on the local network with mDNS and DNS-SD
+//! - Account the IPsec traffic to its security associations, decoding NULL-encrypted ESP
+//! - Correlate the GTPv2-C sessions of the subscribers with the traffic of their GTP-U tunnels
+//! - Decode the traffic of a port as a specific application protocol
+//! - List the application-layer dissectors
+//! - Set the timeouts of the flows of each protocol
+//! - Set how strictly the HTTP messages are parsed
+//! - Dissect the packets only up to the transport layer, keeping just the statistics and the
+//!   conversations, to monitor a link for days
+//! - Set the limits of the bytes buffered by the parsers and count the flows dropped
+//! - Get the health metrics of the dissectors
+//! - Get the panics caught while dissecting packets
+//! - Get the descriptions of the values of protocol fields in a locale
+//! - Set the log level of each module, and get or export the last log records
+//! - Import the name resolution tables and decode-as entries of a Wireshark profile
+//! - Locate the IP addresses of the packets and the conversations (country, city and ASN) with
+//!   MaxMind GeoIP databases
+//! - Resolve the vendors of the Ethernet and ARP addresses from an embedded OUI table
+//! - Resolve the host names of the addresses of the conversations and the endpoints with
+//!   background, rate-limited reverse DNS lookups
+//! - Bookmark packets and name packet ranges, jump between them and save them to a session file
 //!
 //! Errors
 //! These are the errors that can occur during the sniffing process, grouped by the action that can cause them:
@@ -25,8 +95,76 @@
 //!     - Another interface selected previously
 //! - Stop Sniffing
 //!     - Sniffing process wasn't started
+//! - Get packets
+//!     - Unknown filter type, or invalid display filter
+//! - Generate traffic
+//!     - No interface provided nor selected, or interface not using Ethernet
+//!     - Failed channel creation, or frames not sent
 //! - Generate report
 //!     - Generation failed (Permission denied)
+//! - Import file
+//!     - Another import is running
+//!     - File not readable or not a valid .pcap
+//!     - Missing or wrong passphrase of an encrypted file
+//! - Parse file
+//!     - Another import is running
+//!     - File not readable, not a valid .pcap or truncated
+//! - Cancel import
+//!     - Import wasn't started
+//! - Get flow packets
+//!     - No file imported
+//! - Encrypt file
+//!     - File not readable or already encrypted
+//! - Export packets
+//!     - PCAP or PCAPNG export of live captured packets
+//!     - Write failed (Permission denied)
+//! - Export inventory
+//!     - Write failed (Permission denied)
+//! - Export log records
+//!     - Write failed (Permission denied)
+//! - Edit packet
+//!     - No file imported
+//!     - Packet not in the imported file, truncated or fragmented, or edit not fitting it
+//! - Export fixtures
+//!     - Export of live captured packets
+//!     - No packets selected, or packets not in the imported file
+//!     - Write failed (Permission denied)
+//! - Save HTTP objects
+//!     - Object not available, or write failed (Permission denied)
+//! - Set WPA2 credentials
+//!     - SSID or passphrase of invalid length
+//! - Start capture to file
+//!     - Without prior selection of the interface
+//!     - File not writable (Permission denied)
+//! - Stop capture to file
+//!     - Capture to file wasn't started
+//!     - Write failed
+//! - Open packet store
+//!     - File not writable or not a SQLite database
+//! - Query packet store
+//!     - No packet store open, or invalid search expression
+//! - Set display filter
+//!     - Invalid filter expression
+//! - Configure capture
+//!     - Stop condition of 0 packets, megabytes or seconds, or invalid display filter
+//! - Get statistics
+//!     - No packets received from the interface
+//! - Follow stream
+//!     - Conversation not found, or not over TCP
+//! - Set capture filter
+//!     - Conversation of an unsupported protocol, or between different IP versions
+//! - Set sampling mode
+//!     - Sampling ratio lower than 1, or probability outside (0, 1]
+//! - Set IO graph
+//!     - Interval of 0 ms, or invalid display filter
+//! - Import Wireshark profile
+//!     - Not a directory, or files not readable
+//! - Set GeoIP databases
+//!     - File not readable, or not a City, Country or ASN MaxMind database
+//! - Set packet range
+//!     - Empty name, or first packet after the last one
+//! - Save or load session
+//!     - File not writable or not readable, or not a valid session file
 
 #![cfg_attr(
     all(not(debug_assertions), target_os = "windows"),
@@ -38,43 +176,144 @@ extern crate pnet;
 extern crate sniffer_parser;
 extern crate sudo;
 
+mod arp_watch;
+mod auto_stop;
+mod bookmarks;
+mod capture_file;
+mod capture_filter;
+mod capture_index;
+mod capture_stats;
+mod capture_to_file;
+mod conversations;
+mod display_filter;
+mod encryption;
+mod endpoints;
+mod export;
 mod filtering;
+mod fixtures;
+mod geoip;
+mod gtp_sessions;
+mod happy_eyeballs;
+mod http_objects;
+mod icmp_watch;
+mod inventory;
+mod io_graph;
+mod latency_watch;
+mod logging;
+mod mptcp;
+mod name_resolution;
+mod ntp_watch;
+mod offline;
+mod packet_edits;
+mod packet_store;
+mod pcapng;
+mod proxy_config_watch;
+mod reflection_watch;
+mod registry;
 mod report;
+mod reverse_dns;
+mod sampling;
+mod security_associations;
+mod service_discovery;
+mod statistics;
+mod streams;
+mod tcp_features;
+#[cfg(test)]
+mod test_utils;
+mod tls_destination;
+mod traffic_generator;
+mod truncation;
 
 use dotenv;
-use log::{error, info};
 use serde::Serialize;
-use sniffer_parser::serializable_packet::util::{
-    contains_arp, contains_dns, contains_ethernet, contains_http, contains_icmp, contains_icmp6,
-    contains_ipv4, contains_ipv6, contains_malformed, contains_tcp, contains_tls, contains_udp,
-    contains_unknokn, get_dest_ip, get_dest_mac, get_dest_port, get_source_ip, get_source_mac,
-    get_source_port,
-};
+use sniffer_parser::serializable_packet::{Direction, ParsedPacket};
+use sniffer_parser::descriptions::{FieldDescriptions, Locale};
+use sniffer_parser::health::{DissectorHealth, DissectorPanic};
+use sniffer_parser::http::HttpParsingMode;
 use sniffer_parser::HeaderLength;
-use tauri_plugin_log::fern::colors::{ColoredLevelConfig, Color};
-use tauri_plugin_log::{LogTarget, LoggerBuilder};
+use tracing::{error, info};
 
 use pnet::datalink::Channel::Ethernet;
 use pnet::datalink::{self, ChannelType, Config, NetworkInterface};
-use pnet::packet::ethernet::EthernetPacket;
 
-use chrono::Local;
-use filtering::{get_packets, PacketsCollection};
+use arp_watch::{get_security_alerts, SecurityAlert};
+use auto_stop::{configure_capture, get_capture_configuration, AutoStop};
+use bookmarks::{
+    get_bookmarks, jump_to_bookmark, jump_to_packet_range, load_session, remove_bookmark,
+    remove_packet_range, save_session, set_bookmark, set_packet_range, Bookmarks,
+};
+use capture_filter::{
+    derive_capture_filter, get_capture_filter, set_capture_filter, CaptureFilter,
+};
+use capture_stats::{get_capture_stats, CaptureStats};
+use capture_to_file::{
+    list_ring_buffer_files, start_capture_to_file, stop_capture_to_file, CaptureToFile,
+};
+use chrono::{DateTime, Local};
+use conversations::get_conversations;
+use display_filter::{get_filtered_packets, set_display_filter, DisplayFilter};
+use encryption::encrypt_capture_file;
+use endpoints::get_endpoints;
+use export::export_packets;
+use filtering::{
+    get_packets, PacketsCollection, PacketsNotifier, PacketsReceived, NOTIFICATION_INTERVAL,
+};
+use fixtures::export_fixtures;
+use geoip::{get_geoip_databases, set_geoip_databases, GeoIp};
+use gtp_sessions::get_gtp_sessions;
+use happy_eyeballs::get_happy_eyeballs_races;
+use http_objects::{get_http_objects, save_http_objects};
+use icmp_watch::get_icmp_error_sources;
+use inventory::{export_inventory, get_inventory};
+use io_graph::{get_io_graph, get_io_series, set_io_graph};
+use latency_watch::get_latency_baselines;
+use logging::{export_log_records, get_log_levels, get_log_records, set_log_levels};
+use mptcp::get_mptcp_connections;
+use name_resolution::{get_name_resolutions, import_wireshark_profile, NameResolver};
+use ntp_watch::get_ntp_exchanges;
+use offline::{
+    cancel_import, get_flow_packets, import_pcap_file, parse_pcap_file, set_wpa2_credentials,
+    OfflineCapture,
+};
+use packet_edits::{get_packet_edits, remove_packet_edit, set_packet_edit, PacketEdits};
+use packet_store::{
+    close_packet_store, get_stored_captures, get_stored_statistics, open_packet_store,
+    query_stored_conversations, query_stored_packets,
+};
+use proxy_config_watch::{
+    get_proxy_config_fetches, get_trusted_proxy_config_sources, set_trusted_proxy_config_sources,
+};
+use reflection_watch::get_reflection_targets;
+use registry::get_registry_analytics;
 use report::{
     data::{PacketExchange, SourceDestination},
     write_report,
 };
-use std::collections::HashMap;
+use reverse_dns::{get_reverse_dns_status, set_reverse_dns, ReverseDns};
+use sampling::{get_sampling_mode, set_sampling_mode, Sampler};
+use security_associations::get_security_associations;
+use service_discovery::get_discovered_services;
+use statistics::{get_interfaces_statistics, get_statistics};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io;
+use std::time::{Duration, Instant};
+use streams::follow_stream;
 use tauri::{Window, Wry};
+use tcp_features::get_tcp_features;
+use traffic_generator::generate_traffic;
+use truncation::{get_snap_lengths, set_snap_lengths, truncate_packet, SnapLengths, SnappedFrame};
 
 use std::sync::mpsc::{channel, Receiver, Sender};
 use std::sync::{Arc, Mutex};
 
 use sniffer_parser::{
-    cleanup_sniffing_state, parse_ethernet_frame, serializable_packet::SerializablePacket,
+    cleanup_sniffing_state, parse_frame, serializable_packet::SerializablePacket,
+    ApplicationProtocol, BufferLimits, DissectionDepth, FlowEvictions, FlowTimeouts, FrameMeta,
+    LinkType,
 };
 
-use crate::report::get_sender_receiver;
+use crate::report::{get_sender_receiver, get_tls_fingerprints};
 
 const CONFIG: Config = Config {
     write_buffer_size: 16384,
@@ -87,6 +326,9 @@ const CONFIG: Config = Config {
     promiscuous: true,
 };
 
+const CAPTURE_TEST_DURATION: Duration = Duration::from_millis(300);
+const CAPTURE_TEST_READ_TIMEOUT: Duration = Duration::from_millis(100);
+
 /// Errors that can occur during the sniffing process
 #[derive(Serialize, Debug)]
 #[serde(tag = "type", content = "description")]
@@ -100,6 +342,38 @@ pub enum SniffingError {
     ReportGenerationFailed(String),
     ReadingChannelFailed(String),
     UnknownFilterType(String),
+    ImportAlreadyRunning(String),
+    ImportFailed(String),
+    CancelImportWithoutPriorStart(String),
+    NoOfflineCapture(String),
+    EncryptionFailed(String),
+    ExportFailed(String),
+    InvalidWpa2Credentials(String),
+    CaptureToFileFailed(String),
+    InvalidDisplayFilter(String),
+    ProfileImportFailed(String),
+    InvalidPacketRange(String),
+    SessionFileFailed(String),
+    InvalidSamplingMode(String),
+    InvalidCaptureFilter(String),
+    StreamNotFound(String),
+    InvalidIoGraph(String),
+    InvalidGeoIpDatabase(String),
+    InvalidStopConditions(String),
+    InvalidPacketEdit(String),
+    TrafficGenerationFailed(String),
+    PacketStoreFailed(String),
+    InvalidLogLevels(String),
+}
+
+/// Result of a capture test performed on a network interface
+#[derive(Serialize, Debug)]
+#[serde(tag = "type", content = "description")]
+pub enum CaptureTestResult {
+    Ok(String),
+    PermissionDenied(String),
+    DeviceBusy(String),
+    NoLink(String),
 }
 
 /// Sniffing channel and data collected by the sniffing process
@@ -111,6 +385,21 @@ pub struct SniffingState {
     exchanged_packets: Arc<Mutex<HashMap<SourceDestination, PacketExchange>>>,
     info: Arc<Mutex<SniffingInfo>>,
     packets: Arc<Mutex<PacketsCollection>>,
+    import: Arc<Mutex<Option<Sender<()>>>>,
+    offline: Arc<Mutex<Option<OfflineCapture>>>,
+    wpa2_credentials: Arc<Mutex<Option<(String, String)>>>,
+    capture_to_file: Arc<Mutex<Option<CaptureToFile>>>,
+    display_filter: Arc<Mutex<Option<DisplayFilter>>>,
+    resolver: Arc<Mutex<NameResolver>>,
+    geoip: Arc<Mutex<GeoIp>>,
+    reverse_dns: Arc<Mutex<ReverseDns>>,
+    bookmarks: Arc<Mutex<Bookmarks>>,
+    capture_filter: Arc<Mutex<CaptureFilter>>,
+    sampler: Arc<Mutex<Sampler>>,
+    snap_lengths: Arc<Mutex<SnapLengths>>,
+    auto_stop: Arc<Mutex<AutoStop>>,
+    packet_edits: Arc<Mutex<PacketEdits>>,
+    capture_stats: Arc<Mutex<CaptureStats>>,
 }
 
 impl SniffingState {
@@ -120,6 +409,21 @@ impl SniffingState {
             exchanged_packets: Arc::new(Mutex::new(HashMap::new())),
             info: Arc::new(Mutex::new(SniffingInfo::new())),
             packets: Arc::new(Mutex::new(PacketsCollection::new())),
+            import: Arc::new(Mutex::new(None)),
+            offline: Arc::new(Mutex::new(None)),
+            wpa2_credentials: Arc::new(Mutex::new(None)),
+            capture_to_file: Arc::new(Mutex::new(None)),
+            display_filter: Arc::new(Mutex::new(None)),
+            resolver: Arc::new(Mutex::new(NameResolver::new())),
+            geoip: Arc::new(Mutex::new(GeoIp::new())),
+            reverse_dns: Arc::new(Mutex::new(ReverseDns::new())),
+            bookmarks: Arc::new(Mutex::new(Bookmarks::new())),
+            capture_filter: Arc::new(Mutex::new(CaptureFilter::new())),
+            sampler: Arc::new(Mutex::new(Sampler::new())),
+            snap_lengths: Arc::new(Mutex::new(SnapLengths::default())),
+            auto_stop: Arc::new(Mutex::new(AutoStop::new())),
+            packet_edits: Arc::new(Mutex::new(PacketEdits::new())),
+            capture_stats: Arc::new(Mutex::new(CaptureStats::new())),
         }
     }
 }
@@ -159,12 +463,8 @@ fn get_interfaces_list() -> Vec<String> {
     interfaces
 }
 
-/// Selection of a network interface among all the available ones
-#[tauri::command]
-fn select_interface(
-    state: tauri::State<SniffingState>,
-    interface_name: String,
-) -> Result<(), SniffingError> {
+/// Find the network interface with the provided name
+fn find_interface(interface_name: &str) -> Result<NetworkInterface, SniffingError> {
     let interface_names_match = |iface: &NetworkInterface| {
         if cfg!(target_os = "windows") {
             iface.description == interface_name
@@ -173,15 +473,34 @@ fn select_interface(
         }
     };
 
-    // Find the network interface with the provided name
-    let interfaces = datalink::interfaces();
-    let interface = interfaces
+    datalink::interfaces()
         .into_iter()
         .filter(interface_names_match)
         .next()
         .ok_or(SniffingError::InterfaceNotFound(
             "The provided interface is inexistent".to_owned(),
-        ))?;
+        ))
+}
+
+/// Get the link type of the frames delivered by an interface: 802.11 ones, with or without a
+/// Radiotap header, for the wireless interfaces in monitor mode, Ethernet ones otherwise
+pub(crate) fn interface_link_type(interface_name: &str) -> LinkType {
+    // ARPHRD_ type of the interface, on Linux
+    let hardware_type = fs::read_to_string(format!("/sys/class/net/{}/type", interface_name));
+    match hardware_type.as_deref().map(str::trim) {
+        Ok("801") => LinkType::Ieee80211,
+        Ok("803") => LinkType::Ieee80211Radiotap,
+        _ => LinkType::Ethernet,
+    }
+}
+
+/// Selection of a network interface among all the available ones
+#[tauri::command]
+fn select_interface(
+    state: tauri::State<SniffingState>,
+    interface_name: String,
+) -> Result<(), SniffingError> {
+    let interface = find_interface(&interface_name)?;
 
     info!("Interface selected: {}", interface_name);
 
@@ -197,6 +516,251 @@ fn select_interface(
     Ok(())
 }
 
+/// Opens the network interface and captures for a fraction of a second, in order to check
+/// whether the sniffing process can be started on it
+#[tauri::command]
+fn test_capture(interface_name: String) -> Result<CaptureTestResult, SniffingError> {
+    let interface = find_interface(&interface_name)?;
+
+    if !interface.is_up() {
+        info!("[{}] Capture test: interface is down", interface_name);
+        return Ok(CaptureTestResult::NoLink(
+            "The interface is down or has no link".to_owned(),
+        ));
+    }
+
+    let config = Config {
+        read_timeout: Some(CAPTURE_TEST_READ_TIMEOUT),
+        ..CONFIG
+    };
+
+    let mut interface_channel = match datalink::channel(&interface, config) {
+        Ok(Ethernet(_, rx)) => rx,
+        Ok(_) => {
+            return Err(SniffingError::UnhandledChannelType(
+                "Unhandled channel type".to_owned(),
+            ))
+        }
+        Err(e) if e.kind() == io::ErrorKind::PermissionDenied => {
+            info!("[{}] Capture test: permission denied", interface_name);
+            return Ok(CaptureTestResult::PermissionDenied(format!(
+                "Not enough privileges to capture: {}",
+                e
+            )));
+        }
+        Err(e) if is_device_busy(&e) => {
+            info!("[{}] Capture test: device busy", interface_name);
+            return Ok(CaptureTestResult::DeviceBusy(format!(
+                "The interface is busy: {}",
+                e
+            )));
+        }
+        Err(e) => {
+            error!("Unexpected channel creation failure: {}", e);
+            return Err(SniffingError::FailedChannelCreation(
+                "Unexpected channel creation failure".to_owned(),
+            ));
+        }
+    };
+
+    let start = Instant::now();
+    let mut received = 0;
+
+    while start.elapsed() < CAPTURE_TEST_DURATION {
+        match interface_channel.next() {
+            Ok(_) => received += 1,
+            Err(e) if e.kind() == io::ErrorKind::TimedOut => (),
+            Err(e) if e.kind() == io::ErrorKind::PermissionDenied => {
+                return Ok(CaptureTestResult::PermissionDenied(format!(
+                    "Not enough privileges to capture: {}",
+                    e
+                )));
+            }
+            Err(e) => {
+                return Err(SniffingError::ReadingChannelFailed(format!(
+                    "Reading from channel failed: {}",
+                    e
+                )));
+            }
+        }
+    }
+
+    info!(
+        "[{}] Capture test: {} packets received",
+        interface_name, received
+    );
+
+    Ok(CaptureTestResult::Ok(format!(
+        "{} packets received in {} ms",
+        received,
+        CAPTURE_TEST_DURATION.as_millis()
+    )))
+}
+
+/// Check if the channel creation failed because the device is already in use
+fn is_device_busy(e: &io::Error) -> bool {
+    // EBUSY
+    cfg!(unix) && e.raw_os_error() == Some(16)
+}
+
+/// Save a parsed packet in the packets collection and update the exchanged packets data
+///
+/// The interface is the one the packet was received from, none for offline captures.
+/// The snapped frame, if any, tells the bytes of the packet kept once counted.
+/// Returns the security alerts raised by the packet.
+fn store_packet(
+    mut new_packet: ParsedPacket,
+    interface: Option<&str>,
+    snapped: Option<SnappedFrame>,
+    now: DateTime<Local>,
+    packets: &Mutex<PacketsCollection>,
+    exchanged_packets: &Mutex<HashMap<SourceDestination, PacketExchange>>,
+) -> Vec<SecurityAlert> {
+    let sender_receiver = get_sender_receiver(&new_packet);
+    let tls_fingerprints = get_tls_fingerprints(&new_packet);
+    let protocols: Vec<String> = sender_receiver.1;
+    let transmitted_bytes = get_transmitted_bytes(&new_packet);
+
+    let mut packets = packets.lock().unwrap();
+    packets.statistics.update(
+        interface,
+        &protocols,
+        &sender_receiver.0.ip_source,
+        transmitted_bytes,
+        now,
+    );
+    packets
+        .conversations
+        .update(&new_packet, transmitted_bytes, now);
+    packets
+        .endpoints
+        .update(&new_packet, transmitted_bytes, now);
+    packets
+        .io_graph
+        .update(&new_packet, &protocols, transmitted_bytes);
+
+    // Packets dissected only up to the transport layer are dropped once accounted for
+    let mut alerts = vec![];
+    if sniffer_parser::get_dissection_depth() == DissectionDepth::Application {
+        let stream_payload = new_packet.take_stream_payload();
+        packets
+            .conversations
+            .append_stream(&new_packet, stream_payload);
+        packets.inventory.update(&new_packet, now);
+        alerts.extend(packets.arp_watch.update(&new_packet, now));
+        alerts.extend(packets.icmp_watch.update(&new_packet, now));
+        alerts.extend(packets.proxy_config_watch.update(&new_packet, now));
+        alerts.extend(packets.ntp_watch.update(&new_packet, now));
+        alerts.extend(packets.reflection_watch.update(&new_packet, now));
+        alerts.extend(packets.latency_watch.update(&new_packet, now));
+        packets.tcp_features.update(&new_packet);
+        packets.happy_eyeballs.update(&new_packet, now);
+        packets.mptcp.update(&new_packet, transmitted_bytes, now);
+        packets.service_discovery.update(&new_packet, now);
+        packets
+            .gtp_sessions
+            .update(&new_packet, transmitted_bytes, now);
+        packets.http_objects.update(&new_packet);
+        packets
+            .security_associations
+            .update(&new_packet, transmitted_bytes, now);
+        if let Some(snapped) = snapped {
+            truncate_packet(&mut new_packet, &snapped);
+        }
+        packets.insert(Arc::new(new_packet), interface);
+    }
+    drop(packets);
+
+    let mut exchanged_packets = exchanged_packets.lock().unwrap();
+    exchanged_packets
+        .entry(sender_receiver.0)
+        .and_modify(|exchange| exchange.add_packet(protocols.clone(), transmitted_bytes, now))
+        .or_insert(PacketExchange::new(protocols, transmitted_bytes, now))
+        .add_tls_fingerprints(tls_fingerprints);
+
+    alerts
+}
+
+/// Replace a collected packet dissected up to the transport layer with the same packet parsed
+/// with its application layers, updating the indexes and the data derived from them
+///
+/// Only the data depending on the application layers alone is updated: the conversations keep the
+/// details inferred from the transport layer, and the latency baselines get the DNS samples alone,
+/// the TCP handshakes being measured already. Since the flows are parsed when requested, their DNS
+/// samples reach the baselines in that order rather than in capture order.
+/// Returns the security alerts raised by the packet.
+fn store_applications(
+    mut new_packet: ParsedPacket,
+    now: DateTime<Local>,
+    packets: &Mutex<PacketsCollection>,
+    exchanged_packets: &Mutex<HashMap<SourceDestination, PacketExchange>>,
+) -> Vec<SecurityAlert> {
+    let mut packets = packets.lock().unwrap();
+    let counted = match packets.packets.get(new_packet.get_id()) {
+        Some(packet) => get_sender_receiver(packet).1.len(),
+        None => return vec![],
+    };
+    let (sender_receiver, protocols) = get_sender_receiver(&new_packet);
+    let tls_fingerprints = get_tls_fingerprints(&new_packet);
+    let transmitted_bytes = get_transmitted_bytes(&new_packet);
+
+    packets
+        .statistics
+        .update_upper_protocols(None, &protocols, counted, transmitted_bytes);
+
+    let stream_payload = new_packet.take_stream_payload();
+    packets
+        .conversations
+        .append_stream(&new_packet, stream_payload);
+    packets.inventory.update(&new_packet, now);
+    let mut alerts = packets.proxy_config_watch.update(&new_packet, now);
+    alerts.extend(packets.ntp_watch.update(&new_packet, now));
+    if let Some(SerializablePacket::DnsPacket(_)) = new_packet.get_application_layer_packet() {
+        alerts.extend(packets.latency_watch.update(&new_packet, now));
+    }
+    packets.service_discovery.update(&new_packet, now);
+    packets
+        .gtp_sessions
+        .update(&new_packet, transmitted_bytes, now);
+    packets.http_objects.update(&new_packet);
+    packets.registry.update(&new_packet);
+    // Only the ESP packets carried by UDP were not accounted to their security association yet
+    if let Some(SerializablePacket::EspPacket(_)) = new_packet.get_application_layer_packet() {
+        packets
+            .security_associations
+            .update(&new_packet, transmitted_bytes, now);
+    }
+
+    packets.replace(Arc::new(new_packet));
+    drop(packets);
+
+    exchanged_packets
+        .lock()
+        .unwrap()
+        .entry(sender_receiver)
+        .and_modify(|exchange| exchange.add_packet(protocols.clone(), 0, now))
+        .or_insert(PacketExchange::new(protocols, 0, now))
+        .add_tls_fingerprints(tls_fingerprints);
+
+    alerts
+}
+
+/// Get the number of bytes transmitted with a packet, from its link layer
+fn get_transmitted_bytes(packet: &ParsedPacket) -> usize {
+    match packet.get_link_layer_packet() {
+        Some(SerializablePacket::EthernetPacket(link_packet)) => {
+            link_packet.payload.len() + HeaderLength::ETHERNET
+        }
+        Some(SerializablePacket::PppPacket(link_packet)) => link_packet.length,
+        Some(SerializablePacket::SlipPacket(link_packet)) => link_packet.length,
+        Some(SerializablePacket::LinuxSllPacket(link_packet)) => link_packet.length,
+        Some(SerializablePacket::LoopbackPacket(link_packet)) => link_packet.length,
+        Some(SerializablePacket::RawPacket(link_packet)) => link_packet.length,
+        Some(SerializablePacket::Ieee80211Packet(link_packet)) => link_packet.length,
+        _ => 0,
+    }
+}
+
 /// Instantiates a new thread that will execute the sniffing process
 #[tauri::command]
 fn start_sniffing(
@@ -222,13 +786,21 @@ fn start_sniffing(
 
     if !is_resume {
         packet_collection.clear();
+        state.bookmarks.lock().unwrap().clear();
+        state.packet_edits.lock().unwrap().clear();
+        state.offline.lock().unwrap().take();
     }
     info!("[{}] Sniffing started", interface_name);
 
     let _sniffer = sniffers.get_mut(interface_name);
     // if sniffer.is_none() || sniffer.unwrap().0.send(()).is_err() {
     // Create a new channel, dealing with layer 2 packets
-    let (_, mut interface_channel) = match datalink::channel(interface, CONFIG) {
+    // Reads time out to notify the packets collected even when no more are received
+    let config = Config {
+        read_timeout: Some(NOTIFICATION_INTERVAL),
+        ..CONFIG
+    };
+    let (_, mut interface_channel) = match datalink::channel(interface, config) {
         Ok(Ethernet(tx, rx)) => Ok((tx, rx)),
         Ok(_) => Err(SniffingError::UnhandledChannelType(
             "Unhandled channel type".to_owned(),
@@ -249,164 +821,148 @@ fn start_sniffing(
     let exchanged_packets = Arc::clone(&state.exchanged_packets);
     let packets = Arc::clone(&state.packets);
     let info = Arc::clone(&state.info);
+    let file_capture = Arc::clone(&state.capture_to_file);
+    let capture_filter = Arc::clone(&state.capture_filter);
+    let sampler = Arc::clone(&state.sampler);
+    let snap_lengths = Arc::clone(&state.snap_lengths);
+    let auto_stop = Arc::clone(&state.auto_stop);
+    auto_stop.lock().unwrap().start(is_resume, Instant::now());
+    let capture_stats = Arc::clone(&state.capture_stats);
+    capture_stats
+        .lock()
+        .unwrap()
+        .start(interface_name, is_resume);
+    let interface_name = interface_name.clone();
+    let interface_mac = interface.mac;
+    let link_type = interface_link_type(&interface_name);
+    if link_type != LinkType::Ethernet {
+        info!(
+            "[{}] Sniffing 802.11 frames in monitor mode",
+            interface_name
+        );
+    }
 
     std::thread::spawn(move || {
         // let mut counter_id = 0;
+        let mut notifier = PacketsNotifier::new();
+        let notify = |count| {
+            let total = packets.lock().unwrap().packets.len();
+            let _result = window.emit("packets_received", PacketsReceived { count, total });
+        };
+
         loop {
             match interface_channel.next() {
                 Ok(packet) if receive_stop.try_recv().is_err() => {
-                    let ethernet_packet = EthernetPacket::new(packet).unwrap();
-
-                    let mut info = info.lock().unwrap();
-                    let new_packet = parse_ethernet_frame(&ethernet_packet, info.counter);
-                    info.counter += 1;
+                    capture_stats.lock().unwrap().receive();
 
-                    /* Save packet in HashMap */
-                    let now = Local::now();
-                    let sender_receiver = get_sender_receiver(&new_packet);
-                    let mut transmitted_bytes = 0;
-                    let protocols: Vec<String> = sender_receiver.1;
-                    if let SerializablePacket::EthernetPacket(link_packet) =
-                        new_packet.get_link_layer_packet().unwrap()
-                    {
-                        transmitted_bytes = link_packet.payload.len() + HeaderLength::ETHERNET;
+                    // Frames left out by the capture filter are ignored altogether
+                    if !capture_filter.lock().unwrap().matches(link_type, packet) {
+                        continue;
                     }
 
-                    let mut packets_collection = packets.lock().unwrap();
-                    let parsed_packet = Arc::new(new_packet);
-
-                    // Index by Source IP
-                    if let Some(ip_address) = get_source_ip(&parsed_packet) {
-                        packets_collection
-                            .source_ip_index
-                            .entry(ip_address)
-                            .and_modify(|packets| packets.push(Arc::clone(&parsed_packet)))
-                            .or_insert(vec![Arc::clone(&parsed_packet)]);
+                    // Frames left out by the sampling are only counted
+                    if !sampler.lock().unwrap().sample() {
+                        packets
+                            .lock()
+                            .unwrap()
+                            .statistics
+                            .skip(Some(&interface_name));
+                        continue;
                     }
 
-                    // Index by Dest IP
-                    if let Some(ip_address) = get_dest_ip(&parsed_packet) {
-                        packets_collection
-                            .dest_ip_index
-                            .entry(ip_address)
-                            .and_modify(|packets| packets.push(Arc::clone(&parsed_packet)))
-                            .or_insert(vec![Arc::clone(&parsed_packet)]);
-                    }
-
-                    // Index by Source MAC
-                    if let Some(mac_address) = get_source_mac(&parsed_packet) {
-                        packets_collection
-                            .source_mac_index
-                            .entry(mac_address)
-                            .and_modify(|packets| packets.push(Arc::clone(&parsed_packet)))
-                            .or_insert(vec![Arc::clone(&parsed_packet)]);
-                    }
-
-                    // Index by Dest MAC
-                    if let Some(mac_address) = get_dest_mac(&parsed_packet) {
-                        packets_collection
-                            .dest_mac_index
-                            .entry(mac_address)
-                            .and_modify(|packets| packets.push(Arc::clone(&parsed_packet)))
-                            .or_insert(vec![Arc::clone(&parsed_packet)]);
-                    }
+                    // Channels deliver the frames as they are: on Ethernet interfaces, the ones
+                    // sent by the interface carry its MAC address as source
+                    let direction = interface_mac
+                        .zip(packet.get(6..12))
+                        .filter(|_| link_type == LinkType::Ethernet)
+                        .map(|(mac, source)| {
+                            if source == [mac.0, mac.1, mac.2, mac.3, mac.4, mac.5] {
+                                Direction::Outbound
+                            } else {
+                                Direction::Inbound
+                            }
+                        });
 
-                    // Index by Source Port
-                    if let Some(port) = get_source_port(&parsed_packet) {
-                        packets_collection
-                            .source_port_index
-                            .entry(port)
-                            .and_modify(|packets| packets.push(Arc::clone(&parsed_packet)))
-                            .or_insert(vec![Arc::clone(&parsed_packet)]);
-                    }
-
-                    // Index by Dest Port
-                    if let Some(port) = get_dest_port(&parsed_packet) {
-                        packets_collection
-                            .dest_port_index
-                            .entry(port)
-                            .and_modify(|packets| packets.push(Arc::clone(&parsed_packet)))
-                            .or_insert(vec![Arc::clone(&parsed_packet)]);
-                    }
-
-                    if contains_ethernet(&parsed_packet) {
-                        packets_collection
-                            .ethernet_packets
-                            .push(parsed_packet.clone());
-                    }
-
-                    if contains_malformed(&parsed_packet) {
-                        packets_collection
-                            .malformed_packets
-                            .push(parsed_packet.clone());
-                    }
-
-                    if contains_unknokn(&parsed_packet) {
-                        packets_collection
-                            .unknown_packets
-                            .push(parsed_packet.clone());
-                    }
-
-                    if contains_tcp(&parsed_packet) {
-                        packets_collection.tcp_packets.push(parsed_packet.clone());
-                    }
-
-                    if contains_udp(&parsed_packet) {
-                        packets_collection.udp_packets.push(parsed_packet.clone());
-                    }
-
-                    if contains_icmp(&parsed_packet) {
-                        packets_collection.icmp_packets.push(parsed_packet.clone());
-                    }
-
-                    if contains_icmp6(&parsed_packet) {
-                        packets_collection
-                            .icmpv6_packets
-                            .push(parsed_packet.clone());
-                    }
-
-                    if contains_http(&parsed_packet) {
-                        packets_collection.http_packets.push(parsed_packet.clone());
+                    let mut info = info.lock().unwrap();
+                    let meta = FrameMeta {
+                        interface: Some(interface_name.clone()),
+                        direction,
+                        ..FrameMeta::new(info.counter)
+                    };
+                    let new_packet = parse_frame(link_type, packet, meta);
+                    info.counter += 1;
+                    let stats = {
+                        let mut capture_stats = capture_stats.lock().unwrap();
+                        capture_stats.dissect(&new_packet);
+                        capture_stats.poll(Instant::now())
+                    };
+
+                    let snap_length =
+                        snap_lengths
+                            .lock()
+                            .unwrap()
+                            .snap_length(link_type, packet, &new_packet);
+
+                    let mut file_capture = file_capture.lock().unwrap();
+                    if let Some(capture) = file_capture.as_mut() {
+                        if let Err(e) = capture.write(packet, snap_length, &new_packet) {
+                            error!("Capture to file failed: {}", e);
+                            capture_to_file::close(file_capture.take().unwrap());
+                        }
                     }
-
-                    if contains_tls(&parsed_packet) {
-                        packets_collection.tls_packets.push(parsed_packet.clone());
+                    drop(file_capture);
+
+                    let stop_reason =
+                        auto_stop
+                            .lock()
+                            .unwrap()
+                            .check(packet.len(), &new_packet, Instant::now());
+
+                    let alerts = store_packet(
+                        new_packet,
+                        Some(&interface_name),
+                        Some(SnappedFrame {
+                            link_type,
+                            frame: packet,
+                            snap_length,
+                        }),
+                        Local::now(),
+                        &packets,
+                        &exchanged_packets,
+                    );
+
+                    if let Some(count) = notifier.receive(Instant::now()) {
+                        notify(count);
                     }
-
-                    if contains_ipv4(&parsed_packet) {
-                        packets_collection.ipv4_packets.push(parsed_packet.clone());
+                    for alert in alerts {
+                        let _result = window.emit("security_alert", alert);
                     }
-
-                    if contains_ipv6(&parsed_packet) {
-                        packets_collection.ipv6_packets.push(parsed_packet.clone());
+                    if let Some(warning) = sniffer_parser::take_capacity_warning() {
+                        let _result = window.emit("flow_capacity_exceeded", warning);
                     }
-
-                    if contains_arp(&parsed_packet) {
-                        packets_collection.arp_packets.push(parsed_packet.clone());
+                    if let Some(stats) = stats {
+                        let _result = window.emit("capture_stats", stats);
                     }
 
-                    if contains_dns(&parsed_packet) {
-                        packets_collection.dns_packets.push(parsed_packet.clone());
+                    if let Some(reason) = stop_reason {
+                        info!("[{}] Sniffing stopped: {:?}", interface_name, reason);
+                        if let Some(capture) = file_capture.lock().unwrap().take() {
+                            capture_to_file::close(capture);
+                        }
+                        let _result = window.emit("capture_stopped", reason);
+                        break;
                     }
-
-                    // Insert packet
-                    packets_collection.packets.push(parsed_packet);
-
-                    let mut exchanged_packets = exchanged_packets.lock().unwrap();
-                    exchanged_packets
-                        .entry(sender_receiver.0)
-                        .and_modify(|exchange| {
-                            exchange.add_packet(protocols.clone(), transmitted_bytes, now)
-                        })
-                        .or_insert(PacketExchange::new(protocols, transmitted_bytes, now));
-
-                    let _result = window.emit("packet_received", ());
                 }
                 Ok(_) => {
                     // Clean the channel
                     while !receive_stop.try_recv().is_err() {}
                     break;
                 }
+                Err(e) if e.kind() == io::ErrorKind::TimedOut => {
+                    if let Some(count) = notifier.poll(Instant::now()) {
+                        notify(count);
+                    }
+                }
                 Err(e) => {
                     match send_error.send(SniffingError::ReadingChannelFailed(format!(
                         "Reading from channel failed: {}",
@@ -421,6 +977,10 @@ fn start_sniffing(
                 }
             }
         }
+
+        if let Some(count) = notifier.flush(Instant::now()) {
+            notify(count);
+        }
     });
     // }
 
@@ -437,8 +997,12 @@ fn stop_sniffing(state: tauri::State<SniffingState>, stop: bool) -> Result<(), S
         let mut exchanged_packets = state.exchanged_packets.lock().unwrap();
         std::mem::take(&mut *exchanged_packets);
         sniffing_state.counter = 0;
+
+        if let Some(capture) = state.capture_to_file.lock().unwrap().take() {
+            capture_to_file::close(capture);
+        }
     }
-    
+
     let interface_name = sniffing_state.interface_name.as_ref().ok_or(
         SniffingError::StopSniffingWithoutPriorStart(
             "Stop sniffing without prior starting of the process".to_owned(),
@@ -483,36 +1047,141 @@ fn generate_report(
     })
 }
 
+/// Replaces the table of the application protocols to decode on specific ports, instead of the
+/// ones detected from the well-known ports or from the payloads
+#[tauri::command]
+fn set_port_overrides(overrides: BTreeMap<u16, ApplicationProtocol>) {
+    info!("Port overrides set: {:?}", overrides);
+    sniffer_parser::set_port_overrides(overrides);
+}
+
+/// Returns the table of the application protocols to decode on specific ports
+#[tauri::command]
+fn get_port_overrides() -> BTreeMap<u16, ApplicationProtocol> {
+    sniffer_parser::get_port_overrides()
+}
+
+/// Replaces the idle times after which the flows of each protocol expire, dropping their state
+#[tauri::command]
+fn set_flow_timeouts(timeouts: FlowTimeouts) {
+    info!("Flow timeouts set: {:?}", timeouts);
+    sniffer_parser::set_flow_timeouts(timeouts);
+}
+
+/// Returns the idle times after which the flows of each protocol expire
+#[tauri::command]
+fn get_flow_timeouts() -> FlowTimeouts {
+    sniffer_parser::get_flow_timeouts()
+}
+
+/// Replaces how strictly the HTTP messages are parsed
+#[tauri::command]
+fn set_http_parsing_mode(mode: HttpParsingMode) {
+    info!("HTTP parsing mode set: {:?}", mode);
+    sniffer_parser::http::set_http_parsing_mode(mode);
+}
+
+/// Returns how strictly the HTTP messages are parsed
+#[tauri::command]
+fn get_http_parsing_mode() -> HttpParsingMode {
+    sniffer_parser::http::get_http_parsing_mode()
+}
+
+/// Replaces the layers the packets are dissected up to
+///
+/// Packets dissected only up to the transport layer count in the statistics, the conversations
+/// and the report, but are not collected.
+#[tauri::command]
+fn set_dissection_depth(depth: DissectionDepth) {
+    info!("Dissection depth set: {:?}", depth);
+    sniffer_parser::set_dissection_depth(depth);
+}
+
+/// Returns the layers the packets are dissected up to
+#[tauri::command]
+fn get_dissection_depth() -> DissectionDepth {
+    sniffer_parser::get_dissection_depth()
+}
+
+/// Replaces the limits of the bytes buffered by the parsers, beyond which the state of the flows is
+/// dropped
+#[tauri::command]
+fn set_buffer_limits(limits: BufferLimits) {
+    info!("Buffer limits set: {:?}", limits);
+    sniffer_parser::set_buffer_limits(limits);
+}
+
+/// Returns the limits of the bytes buffered by the parsers
+#[tauri::command]
+fn get_buffer_limits() -> BufferLimits {
+    sniffer_parser::get_buffer_limits()
+}
+
+/// Returns the number of flows whose state was dropped, for each reason
+#[tauri::command]
+fn get_flow_evictions() -> FlowEvictions {
+    sniffer_parser::get_flow_evictions()
+}
+
+/// Replaces the maximum flows tracked by each sniffing thread, beyond which the packets of new
+/// flows are dissected up to the transport layer only
+#[tauri::command]
+fn set_max_flows(max_flows: usize) {
+    info!("Maximum flows set: {}", max_flows);
+    sniffer_parser::set_max_flows(max_flows);
+}
+
+/// Returns the maximum flows tracked by each sniffing thread
+#[tauri::command]
+fn get_max_flows() -> usize {
+    sniffer_parser::get_max_flows()
+}
+
+/// Returns the names of the application-layer dissectors, in order of precedence
+#[tauri::command]
+fn get_dissectors() -> Vec<String> {
+    sniffer_parser::registry::get_dissectors()
+}
+
+/// Returns the parse times, errors, panics and buffers of each dissector, the slowest first
+#[tauri::command]
+fn get_parser_health() -> Vec<DissectorHealth> {
+    sniffer_parser::health::get_parser_health()
+}
+
+/// Returns the last panics caught while dissecting packets, with the flows of the packets
+#[tauri::command]
+fn get_dissector_panics() -> Vec<DissectorPanic> {
+    sniffer_parser::health::get_dissector_panics()
+}
+
+/// Returns the descriptions of the values of the protocol fields (e.g. ICMP types, TLS alerts) in
+/// a locale
+#[tauri::command]
+fn get_descriptions(locale: Locale) -> Vec<FieldDescriptions> {
+    sniffer_parser::descriptions::get_descriptions(locale)
+}
+
+/// Resets the parse times, errors and panics of the dissectors
+#[tauri::command]
+fn reset_parser_health() {
+    info!("Parser health reset");
+    sniffer_parser::health::reset_parser_health();
+}
+
 fn main() {
     dotenv::dotenv().ok();
     if !cfg!(target_os = "windows") {
         // sudo::escalate_if_needed();
     }
 
+    let context = tauri::generate_context!();
+    logging::init(
+        tauri::api::path::log_dir(context.config())
+            .map(|directory| directory.join(format!("{}.log", context.package_info().name))),
+    );
+
     tauri::Builder::default()
-        .plugin(
-            LoggerBuilder::default()
-                .format(move |out, message, record| {
-                    out.finish(format_args!(
-                        "{}[{}] {}",
-                        Local::now().format(format!("[%Y-%m-%d][%H:%M:%S]").as_str()),
-                        ColoredLevelConfig::default()
-                            .debug(Color::Blue)
-                            .info(Color::Green)
-                            .color(record.level()),
-                        message
-                    ))
-                })
-                .level(log::LevelFilter::Warn)
-                .level_for("wirefish", log::LevelFilter::Info)
-                .level_for("sniffer_parser", log::LevelFilter::Debug)
-                .targets([
-                    // LogTarget::Folder("./logs".into()),
-                    LogTarget::LogDir,
-                    LogTarget::Stdout,
-                ])
-                .build(),
-        )
         .manage(SniffingState::new())
         .invoke_handler(tauri::generate_handler![
             start_sniffing,
@@ -520,8 +1189,106 @@ fn main() {
             get_interfaces_list,
             generate_report,
             select_interface,
+            test_capture,
             get_packets,
+            import_pcap_file,
+            parse_pcap_file,
+            cancel_import,
+            get_flow_packets,
+            encrypt_capture_file,
+            export_packets,
+            export_fixtures,
+            set_packet_edit,
+            remove_packet_edit,
+            get_packet_edits,
+            set_wpa2_credentials,
+            start_capture_to_file,
+            stop_capture_to_file,
+            list_ring_buffer_files,
+            open_packet_store,
+            close_packet_store,
+            get_stored_captures,
+            query_stored_packets,
+            query_stored_conversations,
+            get_stored_statistics,
+            get_registry_analytics,
+            set_display_filter,
+            get_filtered_packets,
+            get_statistics,
+            get_interfaces_statistics,
+            get_capture_stats,
+            set_io_graph,
+            get_io_graph,
+            get_io_series,
+            derive_capture_filter,
+            set_capture_filter,
+            get_capture_filter,
+            set_sampling_mode,
+            get_sampling_mode,
+            set_snap_lengths,
+            get_snap_lengths,
+            get_conversations,
+            get_endpoints,
+            follow_stream,
+            get_inventory,
+            export_inventory,
+            get_security_alerts,
+            get_icmp_error_sources,
+            get_proxy_config_fetches,
+            set_trusted_proxy_config_sources,
+            get_trusted_proxy_config_sources,
+            get_ntp_exchanges,
+            get_reflection_targets,
+            get_latency_baselines,
+            get_tcp_features,
+            get_happy_eyeballs_races,
+            get_mptcp_connections,
+            get_discovered_services,
+            get_gtp_sessions,
+            get_http_objects,
+            save_http_objects,
+            get_security_associations,
+            set_port_overrides,
+            get_port_overrides,
+            get_dissectors,
+            set_flow_timeouts,
+            get_flow_timeouts,
+            set_http_parsing_mode,
+            get_http_parsing_mode,
+            set_dissection_depth,
+            get_dissection_depth,
+            set_buffer_limits,
+            get_buffer_limits,
+            get_flow_evictions,
+            set_max_flows,
+            get_max_flows,
+            configure_capture,
+            get_capture_configuration,
+            get_parser_health,
+            get_dissector_panics,
+            reset_parser_health,
+            get_descriptions,
+            set_log_levels,
+            get_log_levels,
+            get_log_records,
+            export_log_records,
+            import_wireshark_profile,
+            get_name_resolutions,
+            set_geoip_databases,
+            get_geoip_databases,
+            set_reverse_dns,
+            get_reverse_dns_status,
+            set_bookmark,
+            remove_bookmark,
+            set_packet_range,
+            remove_packet_range,
+            get_bookmarks,
+            jump_to_bookmark,
+            jump_to_packet_range,
+            save_session,
+            load_session,
+            generate_traffic,
         ])
-        .run(tauri::generate_context!())
+        .run(context)
         .expect("Error while running tauri application");
 }
@@ -0,0 +1,389 @@
+//! Correlation of the subflows of the Multipath TCP connections
+//!
+//! An MPTCP connection is opened by a first subflow, whose handshake carries the MP_CAPABLE
+//! option with the keys of the hosts, and is joined by other subflows, whose SYN carries the
+//! MP_JOIN option with the token of the connection on the host receiving it. The tokens derived
+//! from the keys let the subflows be grouped into a single logical connection, for which are kept:
+//! - the subflows, with the traffic sent in each direction
+//! - the traffic sent in each direction over all the subflows
+//! - first and last time a packet was seen, and the duration in between
+//!
+//! The client of a subflow is the endpoint sending its SYN, the client of a connection is the one
+//! of its first subflow. Subflows joining connections whose handshake was not seen are ignored.
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+
+use chrono::{DateTime, Local};
+use pnet::packet::tcp::TcpFlags;
+use serde::Serialize;
+use sniffer_parser::serializable_packet::transport::MptcpOption;
+use sniffer_parser::serializable_packet::{ParsedPacket, SerializablePacket};
+use sniffer_parser::{FlowDirection, FlowKey};
+
+use crate::statistics::{Counters, Timestamp};
+use crate::SniffingState;
+
+/// Subflow of an MPTCP connection, as returned to the frontend
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct MptcpSubflow {
+    pub client: IpAddr,
+    pub client_port: u16,
+    pub server: IpAddr,
+    pub server_port: u16,
+    /// Address ID announced by the MP_JOIN option, none for the first subflow
+    pub address_id: Option<u8>,
+    /// Whether the subflow is only to be used when the others fail
+    pub backup: bool,
+    /// Traffic sent by the client
+    pub sent: Counters,
+    /// Traffic sent by the server
+    pub received: Counters,
+}
+
+/// MPTCP connection, as returned to the frontend
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct MptcpConnection {
+    pub version: u8,
+    /// Token of the connection on the client, once its key is seen
+    pub client_token: Option<u32>,
+    /// Token of the connection on the server, once its key is seen
+    pub server_token: Option<u32>,
+    /// Subflows of the connection, the first one first
+    pub subflows: Vec<MptcpSubflow>,
+    /// Traffic sent by the client, over all the subflows
+    pub sent: Counters,
+    /// Traffic sent by the server, over all the subflows
+    pub received: Counters,
+    pub first_seen: Timestamp,
+    pub last_seen: Timestamp,
+    /// Time between the first and the last packet, in milliseconds
+    pub duration: i64,
+}
+
+/// Tracker of the MPTCP connections of the collected packets
+#[derive(Debug, Default)]
+pub struct MptcpTracker {
+    /// Connections, in the order they were first seen
+    connections: Vec<MptcpConnection>,
+    /// Indices of the connection and of the subflow within it, for each subflow, with the
+    /// direction of the packets sent by the client
+    subflows: HashMap<FlowKey, (usize, usize, FlowDirection)>,
+    /// Index of the connection of each token, on either host
+    tokens: HashMap<u32, usize>,
+}
+
+impl MptcpTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Account a packet of the given length to its MPTCP connection, if any, given the time it
+    /// was received
+    pub fn update(&mut self, packet: &ParsedPacket, bytes: usize, time: DateTime<Local>) {
+        let (source, destination) = match packet.get_network_layer_packet() {
+            Some(SerializablePacket::Ipv4Packet(ipv4)) => {
+                (IpAddr::V4(ipv4.source), IpAddr::V4(ipv4.destination))
+            }
+            Some(SerializablePacket::Ipv6Packet(ipv6)) => {
+                (IpAddr::V6(ipv6.source), IpAddr::V6(ipv6.destination))
+            }
+            _ => return,
+        };
+        let tcp = match packet.get_transport_layer_packet() {
+            Some(SerializablePacket::TcpPacket(tcp)) => tcp,
+            _ => return,
+        };
+
+        let time = time.timestamp_millis();
+        let source = (source, tcp.source);
+        let destination = (destination, tcp.destination);
+        let is_syn = tcp.flags & TcpFlags::SYN != 0;
+        let is_ack = tcp.flags & TcpFlags::ACK != 0;
+
+        for option in &tcp.mptcp {
+            match *option {
+                MptcpOption::MpCapable {
+                    version,
+                    sender_token,
+                    receiver_token,
+                    ..
+                } => {
+                    // The SYN-ACK is sent by the server, the other segments by the client
+                    let (client, server) = if is_syn && is_ack {
+                        (destination, source)
+                    } else {
+                        (source, destination)
+                    };
+                    let (client_token, server_token) = match (is_syn, is_ack) {
+                        (true, false) => (sender_token, None),
+                        (true, true) => (None, sender_token),
+                        _ => (sender_token, receiver_token),
+                    };
+
+                    let index = match self.subflows.get(&FlowKey::new(client, server)) {
+                        Some(&(index, _, _)) => index,
+                        None => self.open(version, client, server, time),
+                    };
+                    let connection = &mut self.connections[index];
+                    connection.client_token = connection.client_token.or(client_token);
+                    connection.server_token = connection.server_token.or(server_token);
+                    for token in [client_token, server_token].into_iter().flatten() {
+                        self.tokens.insert(token, index);
+                    }
+                }
+                MptcpOption::MpJoin {
+                    backup,
+                    address_id,
+                    receiver_token: Some(token),
+                    ..
+                } if is_syn && !is_ack => {
+                    let (key, direction) = FlowKey::of(source, destination);
+                    if self.subflows.contains_key(&key) {
+                        continue;
+                    }
+                    if let Some(&index) = self.tokens.get(&token) {
+                        let subflows = &mut self.connections[index].subflows;
+                        self.subflows
+                            .insert(key, (index, subflows.len(), direction));
+                        subflows.push(subflow(source, destination, address_id, backup));
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        let (key, direction) = FlowKey::of(source, destination);
+        let (index, subflow, client_direction) = match self.subflows.get(&key) {
+            Some(&subflow) => subflow,
+            None => return,
+        };
+        let from_client = direction == client_direction;
+
+        let connection = &mut self.connections[index];
+        if from_client {
+            connection.sent.add(bytes);
+            connection.subflows[subflow].sent.add(bytes);
+        } else {
+            connection.received.add(bytes);
+            connection.subflows[subflow].received.add(bytes);
+        }
+        connection.last_seen = connection.last_seen.max(time);
+        connection.duration = connection.last_seen - connection.first_seen;
+    }
+
+    /// Open a connection with its first subflow, returning its index
+    fn open(
+        &mut self,
+        version: u8,
+        client: (IpAddr, u16),
+        server: (IpAddr, u16),
+        time: i64,
+    ) -> usize {
+        let index = self.connections.len();
+        let (key, direction) = FlowKey::of(client, server);
+        self.subflows.insert(key, (index, 0, direction));
+        self.connections.push(MptcpConnection {
+            version,
+            client_token: None,
+            server_token: None,
+            subflows: vec![subflow(client, server, None, false)],
+            sent: Counters::default(),
+            received: Counters::default(),
+            first_seen: time,
+            last_seen: time,
+            duration: 0,
+        });
+
+        index
+    }
+
+    /// Get the connections, in the order they were first seen
+    pub fn connections(&self) -> Vec<MptcpConnection> {
+        self.connections.clone()
+    }
+
+    pub fn clear(&mut self) {
+        self.connections.clear();
+        self.subflows.clear();
+        self.tokens.clear();
+    }
+}
+
+fn subflow(
+    (client, client_port): (IpAddr, u16),
+    (server, server_port): (IpAddr, u16),
+    address_id: Option<u8>,
+    backup: bool,
+) -> MptcpSubflow {
+    MptcpSubflow {
+        client,
+        client_port,
+        server,
+        server_port,
+        address_id,
+        backup,
+        sent: Counters::default(),
+        received: Counters::default(),
+    }
+}
+
+/// Returns the MPTCP connections of the collected packets, with their subflows
+#[tauri::command]
+pub fn get_mptcp_connections(state: tauri::State<SniffingState>) -> Vec<MptcpConnection> {
+    state.packets.lock().unwrap().mptcp.connections()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr};
+
+    use pnet::packet::ethernet::EthernetPacket;
+    use pnet::packet::tcp::TcpFlags;
+    use sniffer_parser::parse_ethernet_frame;
+    use sniffer_parser::serializable_packet::ParsedPacket;
+
+    use super::MptcpTracker;
+    use crate::test_utils::{at, ip_frame};
+
+    const CLIENT: [u8; 4] = [10, 0, 0, 1];
+    const CLIENT_WIFI: [u8; 4] = [192, 168, 1, 1];
+    const SERVER: [u8; 4] = [10, 0, 0, 2];
+    const CLIENT_KEY: [u8; 8] = [0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88];
+    const SERVER_KEY: [u8; 8] = [0x99, 0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0xff, 0x00];
+
+    #[test]
+    fn subflows_of_a_connection() {
+        let mut tracker = MptcpTracker::new();
+        let first = (CLIENT, 40000);
+        let second = (CLIENT_WIFI, 40001);
+        let server = (SERVER, 443);
+
+        // Handshake of the first subflow, version 1
+        tracker.update(
+            &tcp_packet(first, server, TcpFlags::SYN, &[30, 4, 0x01, 0x81]),
+            100,
+            at(0),
+        );
+        tracker.update(
+            &tcp_packet(
+                server,
+                first,
+                TcpFlags::SYN | TcpFlags::ACK,
+                &mp_capable(&[SERVER_KEY]),
+            ),
+            100,
+            at(10),
+        );
+        tracker.update(
+            &tcp_packet(
+                first,
+                server,
+                TcpFlags::ACK,
+                &mp_capable(&[CLIENT_KEY, SERVER_KEY]),
+            ),
+            100,
+            at(20),
+        );
+        let connections = tracker.connections();
+        let server_token = connections[0].server_token.unwrap();
+        assert!(connections[0].client_token.is_some());
+
+        // Second subflow, joining with the token of the server
+        let mut join = vec![30, 12, 0x11, 0x02];
+        join.extend(server_token.to_be_bytes());
+        join.extend([0, 0, 0, 7]);
+        tracker.update(
+            &tcp_packet(second, server, TcpFlags::SYN, &join),
+            100,
+            at(30),
+        );
+        tracker.update(
+            &tcp_packet(server, second, TcpFlags::ACK, &[]),
+            1000,
+            at(50),
+        );
+
+        // Unrelated connection
+        tracker.update(
+            &tcp_packet((CLIENT, 40002), server, TcpFlags::SYN, &[]),
+            100,
+            at(60),
+        );
+
+        let connections = tracker.connections();
+        assert_eq!(connections.len(), 1);
+        let connection = &connections[0];
+        assert_eq!(connection.version, 1);
+        assert_eq!(connection.subflows.len(), 2);
+        assert_eq!(connection.sent.packets, 3);
+        assert_eq!(connection.received.packets, 2);
+        assert_eq!(connection.received.bytes, 1100);
+        assert_eq!(connection.duration, 50);
+
+        let subflow = &connection.subflows[1];
+        assert_eq!(subflow.client, IpAddr::V4(Ipv4Addr::from(CLIENT_WIFI)));
+        assert_eq!(subflow.client_port, 40001);
+        assert_eq!(subflow.address_id, Some(2));
+        assert!(subflow.backup);
+        assert_eq!(subflow.received.bytes, 1000);
+
+        tracker.clear();
+        assert!(tracker.connections().is_empty());
+    }
+
+    #[test]
+    fn join_of_unknown_connection() {
+        let mut tracker = MptcpTracker::new();
+
+        tracker.update(
+            &tcp_packet(
+                (CLIENT, 40000),
+                (SERVER, 443),
+                TcpFlags::SYN,
+                &[30, 12, 0x10, 0x01, 0xde, 0xad, 0xbe, 0xef, 0, 0, 0, 7],
+            ),
+            100,
+            at(0),
+        );
+
+        assert!(tracker.connections().is_empty());
+    }
+
+    ///////////////////// Utils
+
+    /// MP_CAPABLE option of version 1 with the given keys, padded to a multiple of 4 bytes
+    fn mp_capable(keys: &[[u8; 8]]) -> Vec<u8> {
+        let mut option = vec![30, (4 + 8 * keys.len()) as u8, 0x01, 0x81];
+        for key in keys {
+            option.extend(key);
+        }
+        while option.len() % 4 != 0 {
+            option.push(1);
+        }
+
+        option
+    }
+
+    fn tcp_packet(
+        source: ([u8; 4], u16),
+        destination: ([u8; 4], u16),
+        flags: u16,
+        options: &[u8],
+    ) -> ParsedPacket {
+        let tcp_length = 20 + options.len();
+
+        let mut segment = vec![];
+        segment.extend(source.1.to_be_bytes());
+        segment.extend(destination.1.to_be_bytes());
+        segment.extend([0x00; 8]);
+        segment.extend(((((tcp_length / 4) as u16) << 12) | flags).to_be_bytes());
+        segment.extend([0xff, 0xff, 0x00, 0x00, 0x00, 0x00]);
+        segment.extend(options);
+
+        let frame = ip_frame(source.0, destination.0, 0x06, &segment);
+        parse_ethernet_frame(&EthernetPacket::new(&frame).unwrap(), 0)
+    }
+}
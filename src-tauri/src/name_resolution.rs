@@ -0,0 +1,403 @@
+//! Name resolution tables and import of Wireshark profiles
+//!
+//! Users migrating from Wireshark can import the following files of a configuration profile:
+//! - `hosts`: host names of IP addresses, one `<address> <name> [aliases...]` entry per line
+//! - `ethers`: names of MAC addresses, one `<address> <name>` entry per line
+//! - `manuf`: vendors of MAC address prefixes (OUIs), one `<prefix>\t<short name>[\t<name>]` entry
+//!   per line
+//! - `decode_as_entries`: protocols to decode on TCP/UDP ports, applied as port overrides
+//!
+//! Blank lines and `#` comments are ignored, as well as the entries wirefish cannot apply (e.g.
+//! decode-as entries of fields other than ports, or of protocols not dissected by wirefish).
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::net::IpAddr;
+use std::path::Path;
+
+use serde::Serialize;
+use sniffer_parser::{get_port_overrides, set_port_overrides, ApplicationProtocol};
+use tracing::info;
+
+use crate::{SniffingError, SniffingState};
+
+/// Names of the Wireshark profile files
+#[allow(non_snake_case)]
+mod ProfileFiles {
+    pub const HOSTS: &str = "hosts";
+    pub const ETHERS: &str = "ethers";
+    pub const MANUF: &str = "manuf";
+    pub const DECODE_AS: &str = "decode_as_entries";
+}
+
+/// Host names of IP addresses and names of MAC addresses, set by the user
+#[derive(Debug, Default)]
+pub struct NameResolver {
+    hosts: BTreeMap<IpAddr, String>,
+    /// Names by lowercase, colon-separated MAC address
+    ethers: BTreeMap<String, String>,
+    /// Vendors by lowercase, colon-separated OUI
+    vendors: BTreeMap<String, String>,
+}
+
+impl NameResolver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get all the host names, sorted by address
+    pub fn hosts(&self) -> impl Iterator<Item = (&IpAddr, &String)> {
+        self.hosts.iter()
+    }
+
+    /// Get the host name of an IP address
+    pub fn host(&self, address: &IpAddr) -> Option<&String> {
+        self.hosts.get(address)
+    }
+
+    /// Get the name of a lowercase, colon-separated MAC address
+    pub fn ether(&self, address: &str) -> Option<&String> {
+        self.ethers.get(address)
+    }
+
+    /// Get the vendor of a lowercase, colon-separated MAC address, from its OUI
+    pub fn vendor(&self, address: &str) -> Option<&String> {
+        self.vendors.get(address.get(..8)?)
+    }
+}
+
+/// Outcome of the import of a Wireshark profile
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ProfileImport {
+    pub hosts: usize,
+    pub ethers: usize,
+    pub vendors: usize,
+    pub port_overrides: usize,
+    /// Decode-as entries which cannot be applied
+    pub skipped: Vec<String>,
+}
+
+/// Name resolution tables, as returned to the frontend
+#[derive(Serialize, Debug, Clone)]
+pub struct NameResolutions {
+    pub hosts: Vec<(String, String)>,
+    pub ethers: Vec<(String, String)>,
+}
+
+/// Parse a `hosts` file, keeping the first name of each address
+pub fn parse_hosts(text: &str) -> Vec<(IpAddr, String)> {
+    entries(text)
+        .filter_map(|fields| {
+            let address = fields.first()?.parse().ok()?;
+            Some((address, fields.get(1)?.to_string()))
+        })
+        .collect()
+}
+
+/// Parse an `ethers` file, whose addresses can be separated by `:`, `-` or `.`
+pub fn parse_ethers(text: &str) -> Vec<(String, String)> {
+    entries(text)
+        .filter_map(|fields| {
+            let address = normalize_mac(fields.first()?)?;
+            Some((address, fields.get(1)?.to_string()))
+        })
+        .collect()
+}
+
+/// Parse a `manuf` file, keeping the full name of each vendor if present
+///
+/// Entries of prefixes longer than an OUI (e.g. `00:1B:C5:00:00:00/36`) are skipped.
+pub fn parse_manuf(text: &str) -> Vec<(String, String)> {
+    text.lines()
+        .map(|line| line.split('#').next().unwrap_or_default())
+        .filter_map(|line| {
+            let fields: Vec<&str> = line
+                .split('\t')
+                .map(str::trim)
+                .filter(|field| !field.is_empty())
+                .collect();
+            let prefix = normalize_oui(fields.first()?)?;
+            let vendor = fields.get(2).or_else(|| fields.get(1))?;
+            Some((prefix, vendor.to_string()))
+        })
+        .collect()
+}
+
+/// Parse a `decode_as_entries` file, returning the port overrides and the entries skipped
+///
+/// Entries look like `decode_as_entry: tcp.port,8888,(none),HTTP`, where the last value is the
+/// protocol to decode.
+pub fn parse_decode_as(text: &str) -> (Vec<(u16, ApplicationProtocol)>, Vec<String>) {
+    let mut overrides = vec![];
+    let mut skipped = vec![];
+
+    for line in text.lines().map(str::trim) {
+        let entry = match line.strip_prefix("decode_as_entry:") {
+            Some(entry) => entry.trim(),
+            None => continue,
+        };
+
+        let values: Vec<&str> = entry.split(',').map(str::trim).collect();
+        let port_override = match values[..] {
+            [field, value, .., protocol] if field == "tcp.port" || field == "udp.port" => {
+                value.parse().ok().zip(application_protocol(protocol))
+            }
+            _ => None,
+        };
+
+        match port_override {
+            Some(port_override) => overrides.push(port_override),
+            None => skipped.push(entry.to_owned()),
+        }
+    }
+
+    (overrides, skipped)
+}
+
+/// Get the application protocol corresponding to a Wireshark dissector name
+fn application_protocol(name: &str) -> Option<ApplicationProtocol> {
+    match name.to_lowercase().as_str() {
+        "http" => Some(ApplicationProtocol::Http),
+        "http2" => Some(ApplicationProtocol::Http2),
+        "tls" | "ssl" => Some(ApplicationProtocol::Tls),
+        "quic" => Some(ApplicationProtocol::Quic),
+        "dns" | "mdns" => Some(ApplicationProtocol::Dns),
+        "ptp" => Some(ApplicationProtocol::Ptp),
+        "dhcp" | "bootp" => Some(ApplicationProtocol::Dhcp),
+        "snmp" => Some(ApplicationProtocol::Snmp),
+        "gtp" => Some(ApplicationProtocol::Gtp),
+        "ike" | "isakmp" => Some(ApplicationProtocol::Ike),
+        "wireguard" | "wg" => Some(ApplicationProtocol::WireGuard),
+        "ntp" => Some(ApplicationProtocol::Ntp),
+        "s7comm" => Some(ApplicationProtocol::S7comm),
+        "iscsi" => Some(ApplicationProtocol::Iscsi),
+        "nvme-tcp" | "nvme/tcp" => Some(ApplicationProtocol::NvmeTcp),
+        "cql" => Some(ApplicationProtocol::Cql),
+        "kafka" => Some(ApplicationProtocol::Kafka),
+        "zookeeper" => Some(ApplicationProtocol::Zookeeper),
+        _ => None,
+    }
+}
+
+/// Iterate over the whitespace-separated fields of the lines of a file, without comments
+fn entries(text: &str) -> impl Iterator<Item = Vec<&str>> {
+    text.lines()
+        .map(|line| line.split('#').next().unwrap_or_default())
+        .map(|line| line.split_whitespace().collect::<Vec<_>>())
+        .filter(|fields| !fields.is_empty())
+}
+
+/// Format a MAC address like `aa:bb:cc:dd:ee:ff`
+fn normalize_mac(address: &str) -> Option<String> {
+    let digits: String = address
+        .chars()
+        .filter(|c| !matches!(c, ':' | '-' | '.'))
+        .collect();
+    if digits.len() != 12 || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    let octets: Vec<&str> = (0..12).step_by(2).map(|i| &digits[i..i + 2]).collect();
+    Some(octets.join(":").to_lowercase())
+}
+
+/// Format an OUI like `aa:bb:cc`, given as three octets or as a 24 bits prefix
+fn normalize_oui(prefix: &str) -> Option<String> {
+    let (address, bits) = match prefix.split_once('/') {
+        Some((address, bits)) => (address, Some(bits)),
+        None => (prefix, None),
+    };
+    if bits.is_some() && bits != Some("24") {
+        return None;
+    }
+
+    let octets: Vec<&str> = address.split([':', '-', '.']).collect();
+    let oui = match octets[..] {
+        [a, b, c] | [a, b, c, "00", "00", "00"] => [a, b, c],
+        _ => return None,
+    };
+    if !oui
+        .iter()
+        .all(|octet| octet.len() == 2 && octet.chars().all(|c| c.is_ascii_hexdigit()))
+    {
+        return None;
+    }
+
+    Some(oui.join(":").to_lowercase())
+}
+
+/// Read a profile file, if present
+fn read_profile_file(profile: &Path, name: &str) -> io::Result<Option<String>> {
+    match fs::read_to_string(profile.join(name)) {
+        Ok(text) => Ok(Some(text)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Imports the name resolution tables and the decode-as entries of a Wireshark profile directory
+///
+/// Imported names and port overrides are added to the current ones, replacing the entries of the
+/// same addresses and ports.
+#[tauri::command]
+pub fn import_wireshark_profile(
+    path: String,
+    state: tauri::State<SniffingState>,
+) -> Result<ProfileImport, SniffingError> {
+    let profile = Path::new(&path);
+    if !profile.is_dir() {
+        return Err(SniffingError::ProfileImportFailed(format!(
+            "{} is not a directory",
+            path
+        )));
+    }
+
+    let read = |name| {
+        read_profile_file(profile, name)
+            .map_err(|e| SniffingError::ProfileImportFailed(format!("Cannot read {}: {}", name, e)))
+    };
+    let hosts = read(ProfileFiles::HOSTS)?.map_or(vec![], |text| parse_hosts(&text));
+    let ethers = read(ProfileFiles::ETHERS)?.map_or(vec![], |text| parse_ethers(&text));
+    let vendors = read(ProfileFiles::MANUF)?.map_or(vec![], |text| parse_manuf(&text));
+    let (port_overrides, skipped) =
+        read(ProfileFiles::DECODE_AS)?.map_or((vec![], vec![]), |text| parse_decode_as(&text));
+
+    let result = ProfileImport {
+        hosts: hosts.len(),
+        ethers: ethers.len(),
+        vendors: vendors.len(),
+        port_overrides: port_overrides.len(),
+        skipped,
+    };
+
+    let mut resolver = state.resolver.lock().unwrap();
+    resolver.hosts.extend(hosts);
+    resolver.ethers.extend(ethers);
+    resolver.vendors.extend(vendors);
+
+    let mut overrides = get_port_overrides();
+    overrides.extend(port_overrides);
+    set_port_overrides(overrides);
+
+    info!("[{}] Wireshark profile imported: {:?}", path, result);
+
+    Ok(result)
+}
+
+/// Returns the name resolution tables set by the user
+#[tauri::command]
+pub fn get_name_resolutions(state: tauri::State<SniffingState>) -> NameResolutions {
+    let resolver = state.resolver.lock().unwrap();
+
+    NameResolutions {
+        hosts: resolver
+            .hosts
+            .iter()
+            .map(|(address, name)| (address.to_string(), name.clone()))
+            .collect(),
+        ethers: resolver
+            .ethers
+            .iter()
+            .map(|(address, name)| (address.clone(), name.clone()))
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+    use sniffer_parser::ApplicationProtocol;
+
+    use super::{parse_decode_as, parse_ethers, parse_hosts, parse_manuf};
+
+    #[test]
+    fn hosts_file() {
+        let hosts = parse_hosts(
+            "# Local hosts\n\
+             192.168.1.1   router gateway\n\
+             \n\
+             ::1 localhost # loopback\n\
+             not-an-address name\n\
+             10.0.0.1\n",
+        );
+
+        assert_eq!(
+            hosts,
+            vec![
+                (
+                    IpAddr::V4(Ipv4Addr::new(192, 168, 1, 1)),
+                    "router".to_owned()
+                ),
+                (IpAddr::V6(Ipv6Addr::LOCALHOST), "localhost".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn ethers_file() {
+        let ethers = parse_ethers(
+            "00:1B:63:84:45:E6 laptop\n\
+             00-1b-63-84-45-e7\tprinter # office\n\
+             001b.6384.45e8 switch\n\
+             00:1b:63:84:45 short\n",
+        );
+
+        assert_eq!(
+            ethers,
+            vec![
+                ("00:1b:63:84:45:e6".to_owned(), "laptop".to_owned()),
+                ("00:1b:63:84:45:e7".to_owned(), "printer".to_owned()),
+                ("00:1b:63:84:45:e8".to_owned(), "switch".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn manuf_file() {
+        let vendors = parse_manuf(
+            "# Wireshark OUI database\n\
+             00:00:0C\tCisco\tCisco Systems, Inc\n\
+             08-00-27\tPCSSystemtec\n\
+             00:1B:C5:00:00:00/36\tConverging\tConverging Systems Inc.\n\
+             00:50:56:00:00:00/24\tVMware\tVMware, Inc.\n\
+             00:00:0\tShort\n",
+        );
+
+        assert_eq!(
+            vendors,
+            vec![
+                ("00:00:0c".to_owned(), "Cisco Systems, Inc".to_owned()),
+                ("08:00:27".to_owned(), "PCSSystemtec".to_owned()),
+                ("00:50:56".to_owned(), "VMware, Inc.".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn decode_as_entries_file() {
+        let (overrides, skipped) = parse_decode_as(
+            "# \"Decode As\" entries file for Wireshark\n\
+             decode_as_entry: tcp.port,8888,(none),HTTP\n\
+             decode_as_entry: udp.port,5300,DNS,DNS\n\
+             decode_as_entry: tcp.port,9443,(none),TLS\n\
+             decode_as_entry: tcp.port,1883,(none),MQTT\n\
+             decode_as_entry: ip.proto,253,(none),UDP\n",
+        );
+
+        assert_eq!(
+            overrides,
+            vec![
+                (8888, ApplicationProtocol::Http),
+                (5300, ApplicationProtocol::Dns),
+                (9443, ApplicationProtocol::Tls),
+            ]
+        );
+        assert_eq!(
+            skipped,
+            vec!["tcp.port,1883,(none),MQTT", "ip.proto,253,(none),UDP"]
+        );
+    }
+}
@@ -0,0 +1,293 @@
+//! Detection of the NTP servers abused for amplification
+//!
+//! NTP servers answer small requests with large responses: the monlist private request (mode 7)
+//! returns up to 100 items about the last clients of ntpd, and some control requests (mode 6) a
+//! few hundreds of bytes. Sent with the spoofed address of a victim, the requests make the servers
+//! flood it with the responses.
+//!
+//! The requests and the responses exchanged by each server and client are counted, raising a
+//! security alert:
+//! - on the first response to each private request of a client, as only the servers exposing the
+//!   deprecated mode 7 answer them
+//! - when the responses of a server to a client outweigh its requests by the amplification ratio,
+//!   beyond a minimum size, as for the victims receiving responses they never requested
+
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::net::IpAddr;
+
+use chrono::{DateTime, Local};
+use serde::Serialize;
+use sniffer_parser::serializable_packet::{ParsedPacket, SerializablePacket};
+use tracing::warn;
+
+use crate::arp_watch::{SecurityAlert, ALERTS_SIZE};
+use crate::statistics::{Counters, Timestamp};
+use crate::SniffingState;
+
+/// Bytes of the responses of a server to a client, raising an amplification alert
+const AMPLIFICATION_MIN_BYTES: usize = 10_000;
+
+/// Ratio between the bytes of the responses and the ones of the requests raising an alert
+const AMPLIFICATION_RATIO: usize = 10;
+
+/// NTP traffic between a server and a client, as returned to the frontend
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct NtpExchange {
+    pub server: IpAddr,
+    pub client: IpAddr,
+    /// Client and control or private requests sent by the client
+    pub requests: Counters,
+    /// Server and control or private responses sent by the server
+    pub responses: Counters,
+    /// Responses to private (mode 7) requests
+    pub private_responses: usize,
+    pub first_seen: Timestamp,
+    pub last_seen: Timestamp,
+}
+
+/// Tracker of the NTP exchanges of the collected packets
+#[derive(Debug, Default)]
+pub struct NtpWatch {
+    exchanges: HashMap<(IpAddr, IpAddr), NtpExchange>,
+    /// Server, client and private request of the alerts raised, to raise them once
+    private_alerted: HashSet<(IpAddr, IpAddr, String)>,
+    /// Server and client of the amplification alerts raised
+    amplification_alerted: HashSet<(IpAddr, IpAddr)>,
+    /// Last alerts raised, the oldest first
+    alerts: VecDeque<SecurityAlert>,
+}
+
+impl NtpWatch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Count a packet, if it is an NTP request or response, given the time it was received,
+    /// returning the alerts raised
+    pub fn update(&mut self, packet: &ParsedPacket, time: DateTime<Local>) -> Vec<SecurityAlert> {
+        let ntp = match packet.get_application_layer_packet() {
+            Some(SerializablePacket::NtpPacket(ntp)) => ntp,
+            _ => return vec![],
+        };
+        let (source, destination) = match packet.get_network_layer_packet() {
+            Some(SerializablePacket::Ipv4Packet(ipv4)) => {
+                (IpAddr::V4(ipv4.source), IpAddr::V4(ipv4.destination))
+            }
+            Some(SerializablePacket::Ipv6Packet(ipv6)) => {
+                (IpAddr::V6(ipv6.source), IpAddr::V6(ipv6.destination))
+            }
+            _ => return vec![],
+        };
+        // Symmetric and broadcast messages are neither requests nor responses
+        let is_response = match ntp.mode.as_str() {
+            "Client" => false,
+            "Server" => true,
+            "Control" | "Private" => ntp.response,
+            _ => return vec![],
+        };
+        let (server, client) = if is_response {
+            (source, destination)
+        } else {
+            (destination, source)
+        };
+
+        let time = time.timestamp_millis();
+        let exchange = self
+            .exchanges
+            .entry((server, client))
+            .or_insert_with(|| NtpExchange {
+                server,
+                client,
+                requests: Counters::default(),
+                responses: Counters::default(),
+                private_responses: 0,
+                first_seen: time,
+                last_seen: time,
+            });
+        exchange.first_seen = exchange.first_seen.min(time);
+        exchange.last_seen = exchange.last_seen.max(time);
+
+        if !is_response {
+            exchange.requests.add(ntp.length);
+            return vec![];
+        }
+        exchange.responses.add(ntp.length);
+
+        let mut alerts = vec![];
+
+        if ntp.mode == "Private" {
+            exchange.private_responses += 1;
+
+            let request = ntp.request.clone().unwrap_or_default();
+            if self
+                .private_alerted
+                .insert((server, client, request.clone()))
+            {
+                alerts.push(SecurityAlert::NtpPrivateResponse {
+                    time,
+                    server,
+                    client,
+                    request,
+                    items: ntp.item_count.unwrap_or_default() as usize,
+                });
+            }
+        }
+
+        let (request_bytes, response_bytes) = (exchange.requests.bytes, exchange.responses.bytes);
+        if response_bytes >= AMPLIFICATION_MIN_BYTES
+            && response_bytes >= AMPLIFICATION_RATIO * request_bytes
+            && self.amplification_alerted.insert((server, client))
+        {
+            alerts.push(SecurityAlert::NtpAmplification {
+                time,
+                server,
+                client,
+                request_bytes,
+                response_bytes,
+            });
+        }
+
+        for alert in &alerts {
+            warn!("Security alert: {:?}", alert);
+            if self.alerts.len() >= ALERTS_SIZE {
+                self.alerts.pop_front();
+            }
+            self.alerts.push_back(alert.clone());
+        }
+
+        alerts
+    }
+
+    /// Get the NTP exchanges, the ones with the most bytes of responses first
+    pub fn exchanges(&self) -> Vec<NtpExchange> {
+        let mut exchanges: Vec<NtpExchange> = self.exchanges.values().cloned().collect();
+        exchanges.sort_by_key(|exchange| {
+            (
+                Reverse(exchange.responses.bytes),
+                exchange.server,
+                exchange.client,
+            )
+        });
+
+        exchanges
+    }
+
+    /// Get the last alerts raised, the oldest first
+    pub fn alerts(&self) -> Vec<SecurityAlert> {
+        self.alerts.iter().cloned().collect()
+    }
+
+    pub fn clear(&mut self) {
+        self.exchanges.clear();
+        self.private_alerted.clear();
+        self.amplification_alerted.clear();
+        self.alerts.clear();
+    }
+}
+
+/// Returns the NTP traffic between each server and client of the collected packets, the servers
+/// responding the most first
+#[tauri::command]
+pub fn get_ntp_exchanges(state: tauri::State<SniffingState>) -> Vec<NtpExchange> {
+    state.packets.lock().unwrap().ntp_watch.exchanges()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr};
+
+    use super::{NtpWatch, AMPLIFICATION_MIN_BYTES};
+    use crate::arp_watch::SecurityAlert;
+    use crate::test_utils::{at, udp_packet};
+
+    const SERVER: [u8; 4] = [10, 0, 0, 123];
+    const CLIENT: [u8; 4] = [10, 0, 0, 10];
+    const VICTIM: [u8; 4] = [192, 0, 2, 1];
+
+    #[test]
+    fn time_exchanges() {
+        let mut watch = NtpWatch::new();
+
+        for i in 0..300 {
+            let mut request = vec![0x00; 48];
+            request[0] = 0x23;
+            let mut response = vec![0x00; 48];
+            response[..2].copy_from_slice(&[0x24, 0x02]);
+
+            assert!(watch
+                .update(
+                    &udp_packet(0, (CLIENT, 40000), (SERVER, 123), &request),
+                    at(i)
+                )
+                .is_empty());
+            assert!(watch
+                .update(
+                    &udp_packet(0, (SERVER, 123), (CLIENT, 40000), &response),
+                    at(i)
+                )
+                .is_empty());
+        }
+
+        let exchanges = watch.exchanges();
+        assert_eq!(exchanges.len(), 1);
+        assert_eq!(exchanges[0].server, IpAddr::V4(Ipv4Addr::from(SERVER)));
+        assert_eq!(exchanges[0].client, IpAddr::V4(Ipv4Addr::from(CLIENT)));
+        assert_eq!(exchanges[0].requests.packets, 300);
+        assert_eq!(exchanges[0].responses.bytes, 300 * 48);
+        assert_eq!(exchanges[0].private_responses, 0);
+
+        watch.clear();
+        assert!(watch.exchanges().is_empty());
+    }
+
+    #[test]
+    fn monlist_amplification() {
+        let mut watch = NtpWatch::new();
+        let (server, victim) = (
+            IpAddr::V4(Ipv4Addr::from(SERVER)),
+            IpAddr::V4(Ipv4Addr::from(VICTIM)),
+        );
+
+        // MON_GETLIST_1 request spoofing the address of the victim
+        let request = [0x17, 0x00, 0x03, 0x2a, 0x00, 0x00, 0x00, 0x00];
+        assert!(watch
+            .update(&udp_packet(0, (VICTIM, 80), (SERVER, 123), &request), at(0))
+            .is_empty());
+
+        // Responses of 6 items of 72 bytes
+        let mut response = vec![0xd7, 0x00, 0x03, 0x2a, 0x00, 0x06, 0x00, 0x48];
+        response.extend([0x00; 6 * 72]);
+        let responses = AMPLIFICATION_MIN_BYTES / response.len() + 1;
+        let mut alerts = vec![];
+        for i in 0..responses as i64 {
+            alerts.extend(watch.update(
+                &udp_packet(0, (SERVER, 123), (VICTIM, 80), &response),
+                at(i),
+            ));
+        }
+
+        assert_eq!(
+            alerts,
+            vec![
+                SecurityAlert::NtpPrivateResponse {
+                    time: at(0).timestamp_millis(),
+                    server,
+                    client: victim,
+                    request: "MON_GETLIST_1".to_owned(),
+                    items: 6,
+                },
+                SecurityAlert::NtpAmplification {
+                    time: at(responses as i64 - 1).timestamp_millis(),
+                    server,
+                    client: victim,
+                    request_bytes: 8,
+                    response_bytes: responses * response.len(),
+                },
+            ]
+        );
+        assert_eq!(watch.alerts(), alerts);
+        assert_eq!(watch.exchanges()[0].private_responses, responses);
+    }
+}
@@ -0,0 +1,748 @@
+//! Import of offline capture files (.pcap and .pcapng)
+//!
+//! The capture file is memory-mapped and a dedicated thread walks its records, building
+//! the list of frames contained in it. While the import is running the `import_progress`
+//! event is periodically emitted, and the import can be cancelled at any time.
+//!
+//! Frames are dissected lazily: a frame goes through the same parsing and indexing steps
+//! of the live sniffing process only the first time it is requested (together with all the
+//! frames preceding it, so that the reassembly of application-layer data is preserved).
+//!
+//! Frames of huge captures are dissected only up to the transport layer at first: the application
+//! layers of a frame are parsed when it is requested, together with the frames of its flow, by a
+//! parser context of its own, so that the state of the parsers is the one of the flow alone. The
+//! frames parsed this way replace the ones in the collected packets, and update the protocol
+//! statistics, streams, HTTP objects, DNS latency baselines and the other data derived from their
+//! application layers, along with the indexes of the collection; conversations keep the details of
+//! the transport layer. Filters and sorting work on the frames dissected up to the transport
+//! layer, parsing only the flows of the packets they match when they depend on the application
+//! layers, and then the flows of the window requested. Exports dissect the whole capture again.
+//! Monitor-mode 802.11 captures are supported too: when WPA2 credentials are set, the traffic
+//! of the stations whose handshake was captured is decrypted.
+//!
+//! The position, timestamp and 5-tuple of the frames are saved in a sidecar index file: importing
+//! the same capture again skips the walk of its records, and the frames of a flow are located
+//! without dissecting the whole file. No index is saved for encrypted captures, since it would
+//! disclose their flows.
+//!
+//! Small captures can also be parsed at once, without the background import: all their frames are
+//! dissected in order and collected like live-captured packets.
+
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::net::IpAddr;
+use std::sync::mpsc::channel;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Local, TimeZone};
+use serde::Serialize;
+use sniffer_parser::serializable_packet::ParsedPacket;
+use sniffer_parser::{
+    cleanup_sniffing_state, parse_frame, DissectionDepth, FrameMeta, LinkType, ParserContext,
+    Wpa2Decryptor,
+};
+use tauri::{Window, Wry};
+use tracing::{debug, error, info, warn};
+
+use crate::capture_file::{CaptureFile, CaptureRecord, GlobalHeader, LinkTypes};
+use crate::capture_index::{
+    extract_five_tuple, index_path, read_index, write_index, FiveTuple, IndexEntry,
+};
+use crate::filtering::{PacketsCollection, PacketsReceived};
+use crate::report::data::{PacketExchange, SourceDestination};
+use crate::{store_applications, store_packet, SniffingError, SniffingInfo, SniffingState};
+
+/// Minimum interval between two progress notifications
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Size of the captures whose application layers are parsed only when their frames are requested
+const LAZY_APPLICATIONS_SIZE: usize = 256 * 1024 * 1024;
+
+/// Status of an offline file import
+#[derive(Serialize, Debug, Clone)]
+#[serde(tag = "type", content = "description")]
+pub enum ImportStatus {
+    Running,
+    Completed,
+    Cancelled,
+    Failed(String),
+}
+
+/// Progress of an offline file import, notified through the `import_progress` event
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportProgress {
+    pub bytes_processed: u64,
+    pub total_bytes: u64,
+    pub packets_parsed: usize,
+    pub eta_ms: Option<u64>,
+    pub status: ImportStatus,
+}
+
+/// An imported capture file, whose frames are dissected on first access
+pub struct OfflineCapture {
+    file: CaptureFile,
+    entries: Vec<IndexEntry>,
+    flows: HashMap<FiveTuple, Vec<usize>>,
+    decryptor: Option<Wpa2Decryptor>,
+    /// State of the parsers of the frames dissected in capture order
+    context: ParserContext,
+    dissected: usize,
+    /// Whether the frames are dissected up to the transport layer, until requested
+    lazy_applications: bool,
+    /// Number of frames of the flows parsed with their application layers, in both directions
+    parsed_flows: HashMap<FiveTuple, usize>,
+}
+
+impl OfflineCapture {
+    pub fn new(
+        file: CaptureFile,
+        entries: Vec<IndexEntry>,
+        decryptor: Option<Wpa2Decryptor>,
+    ) -> Self {
+        let mut flows: HashMap<FiveTuple, Vec<usize>> = HashMap::new();
+        for (index, entry) in entries.iter().enumerate() {
+            if let Some(flow) = entry.flow {
+                flows.entry(flow).or_default().push(index);
+            }
+        }
+
+        // Decrypting a frame needs the handshakes of the whole capture, not just of its flow
+        let lazy_applications = file.len() >= LAZY_APPLICATIONS_SIZE && decryptor.is_none();
+        let mut context = ParserContext::new();
+        if lazy_applications {
+            context.set_dissection_depth(Some(DissectionDepth::Transport));
+        }
+
+        OfflineCapture {
+            file,
+            entries,
+            flows,
+            decryptor,
+            context,
+            dissected: 0,
+            lazy_applications,
+            parsed_flows: HashMap::new(),
+        }
+    }
+
+    /// Get the number of frames contained in the capture
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Check if the capture contains no frames
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Check if the frames are dissected up to the transport layer, until requested
+    pub fn lazy_applications(&self) -> bool {
+        self.lazy_applications
+    }
+
+    /// Get the global header of the capture file
+    pub fn header(&self) -> &GlobalHeader {
+        self.file.header()
+    }
+
+    /// Iterate over the records of the capture, together with their frame bytes
+    pub fn frames(&self) -> impl Iterator<Item = (&CaptureRecord, &[u8])> {
+        self.entries
+            .iter()
+            .map(|entry| (&entry.record, self.file.frame(&entry.record)))
+    }
+
+    /// Get the record of a frame of the capture, together with its bytes
+    pub fn frame(&self, index: usize) -> Option<(&CaptureRecord, &[u8])> {
+        self.entries
+            .get(index)
+            .map(|entry| (&entry.record, self.file.frame(&entry.record)))
+    }
+
+    /// Get the indexes of the frames exchanged in both directions of a flow, in capture order
+    pub fn flow_frames(&self, flow: &FiveTuple) -> Vec<usize> {
+        let mut frames = self.flows.get(flow).cloned().unwrap_or_default();
+        if let Some(reversed) = self.flows.get(&flow.reversed()) {
+            frames.extend(reversed);
+            frames.sort_unstable();
+        }
+
+        frames
+    }
+
+    /// Dissect all the frames up to the provided index (excluded), if not already done
+    ///
+    /// The frames of huge captures are dissected up to the transport layer.
+    pub fn dissect_until(
+        &mut self,
+        end: usize,
+        info: &Mutex<SniffingInfo>,
+        packets: &Mutex<PacketsCollection>,
+        exchanged_packets: &Mutex<HashMap<SourceDestination, PacketExchange>>,
+    ) {
+        let end = end.min(self.entries.len());
+        if end <= self.dissected {
+            return;
+        }
+
+        debug!("Dissecting offline frames {}-{}", self.dissected, end);
+
+        let mut info = info.lock().unwrap();
+        for IndexEntry { record, .. } in &self.entries[self.dissected..end] {
+            let new_packet = dissect_frame(
+                Some(&mut self.context),
+                self.file.header().link_type,
+                record,
+                self.file.frame(record),
+                self.decryptor.as_mut(),
+                info.counter,
+            );
+            info.counter += 1;
+
+            store_packet(
+                new_packet,
+                None,
+                None,
+                get_timestamp(record.seconds as i64, record.nanoseconds),
+                packets,
+                exchanged_packets,
+            );
+        }
+
+        self.dissected = end;
+    }
+
+    /// Dissect all the frames with their application layers, dissecting again the whole capture
+    /// if its frames were dissected up to the transport layer
+    pub fn dissect_all(
+        &mut self,
+        info: &Mutex<SniffingInfo>,
+        packets: &Mutex<PacketsCollection>,
+        exchanged_packets: &Mutex<HashMap<SourceDestination, PacketExchange>>,
+    ) {
+        if self.lazy_applications {
+            debug!("Dissecting all offline frames with their application layers");
+
+            packets.lock().unwrap().clear();
+            std::mem::take(&mut *exchanged_packets.lock().unwrap());
+            info.lock().unwrap().counter = 0;
+            self.context = ParserContext::new();
+            self.dissected = 0;
+            self.lazy_applications = false;
+            self.parsed_flows.clear();
+        }
+
+        let len = self.len();
+        self.dissect_until(len, info, packets, exchanged_packets);
+    }
+
+    /// Parse the application layers of the flows of the given dissected frames, if not already
+    /// done, replacing their packets in the collection
+    pub fn parse_applications(
+        &mut self,
+        indexes: impl IntoIterator<Item = usize>,
+        packets: &Mutex<PacketsCollection>,
+        exchanged_packets: &Mutex<HashMap<SourceDestination, PacketExchange>>,
+    ) {
+        if !self.lazy_applications {
+            return;
+        }
+
+        // Both the directions of a flow are parsed together
+        let mut seen = HashSet::new();
+        let mut flows = vec![];
+        for index in indexes {
+            match self.entries.get(index).and_then(|entry| entry.flow) {
+                Some(flow) if index < self.dissected && seen.insert(flow) => {
+                    seen.insert(flow.reversed());
+                    flows.push(flow);
+                }
+                _ => (),
+            }
+        }
+
+        for flow in flows {
+            let flow_frames = self.flow_frames(&flow);
+            let parsed = self.parsed_flows.get(&flow).copied().unwrap_or(0);
+            // Flows with frames not dissected yet are parsed again once they are
+            let dissected = flow_frames
+                .iter()
+                .take_while(|&&index| index < self.dissected)
+                .count();
+            if dissected == parsed {
+                continue;
+            }
+            self.parsed_flows.insert(flow, dissected);
+            self.parsed_flows.insert(flow.reversed(), dissected);
+
+            debug!(
+                "Parsing the application layers of {} offline frames",
+                dissected
+            );
+            let link_type = self.header().link_type;
+            let mut context = ParserContext::new();
+            for (position, &index) in flow_frames[..dissected].iter().enumerate() {
+                let record = self.entries[index].record;
+                let new_packet = dissect_frame(
+                    Some(&mut context),
+                    link_type,
+                    &record,
+                    self.file.frame(&record),
+                    None,
+                    index,
+                );
+                // The frames parsed before are already accounted for
+                if position >= parsed {
+                    store_applications(
+                        new_packet,
+                        get_timestamp(record.seconds as i64, record.nanoseconds),
+                        packets,
+                        exchanged_packets,
+                    );
+                }
+            }
+        }
+    }
+
+    /// Parse the application layers of the flows of a window of dissected packets, if not already
+    /// done, replacing the packets of the window with the ones parsed
+    pub fn parse_window(
+        &mut self,
+        window: &mut [ParsedPacket],
+        packets: &Mutex<PacketsCollection>,
+        exchanged_packets: &Mutex<HashMap<SourceDestination, PacketExchange>>,
+    ) {
+        if !self.lazy_applications {
+            return;
+        }
+        self.parse_applications(
+            window.iter().map(ParsedPacket::get_id),
+            packets,
+            exchanged_packets,
+        );
+
+        let packets = packets.lock().unwrap();
+        for packet in window.iter_mut() {
+            if let Some(parsed) = packets.packets.get(packet.get_id()) {
+                *packet = ParsedPacket::clone(parsed);
+            }
+        }
+    }
+}
+
+/// Imports a .pcap file, replacing the collected packets with the ones contained in it
+///
+/// The passphrase is needed only for files encrypted by wirefish
+#[tauri::command]
+pub fn import_pcap_file(
+    path: String,
+    passphrase: Option<String>,
+    state: tauri::State<SniffingState>,
+    window: Window<Wry>,
+) -> Result<(), SniffingError> {
+    let mut running_import = state.import.lock().unwrap();
+    if running_import.is_some() {
+        return Err(SniffingError::ImportAlreadyRunning(
+            "An import is already running".to_owned(),
+        ));
+    }
+
+    let file = CaptureFile::open(&path, passphrase.as_deref())
+        .map_err(|e| SniffingError::ImportFailed(format!("Cannot open {}: {}", path, e)))?;
+
+    reset_collected_packets(&state);
+
+    let (send_stop, receive_stop) = channel();
+    *running_import = Some(send_stop);
+
+    let offline = Arc::clone(&state.offline);
+    let import = Arc::clone(&state.import);
+    let wpa2_credentials = state.wpa2_credentials.lock().unwrap().clone();
+
+    info!("[{}] Import started", path);
+
+    std::thread::spawn(move || {
+        let start = Instant::now();
+        let mut last_notification = start;
+        let mut entries = vec![];
+        let mut progress = ImportProgress {
+            bytes_processed: 0,
+            total_bytes: file.len() as u64,
+            packets_parsed: 0,
+            eta_ms: None,
+            status: ImportStatus::Running,
+        };
+
+        let metadata = std::fs::metadata(&path)
+            .ok()
+            .filter(|_| !file.is_decrypted());
+        let index = index_path(&path);
+        let indexed = metadata
+            .as_ref()
+            .and_then(|metadata| match read_index(&index, metadata) {
+                // Frames must lie inside the mapped file, whatever the index says
+                Ok(entries) => entries
+                    .filter(|entries| entries.iter().all(|entry| entry.record.end() <= file.len())),
+                Err(e) => {
+                    debug!("[{}] Index not available: {}", path, e);
+                    None
+                }
+            });
+
+        let from_index = indexed.is_some();
+        if let Some(indexed) = indexed {
+            info!("[{}] Records loaded from index", path);
+            progress.packets_parsed = indexed.len();
+            progress.bytes_processed = progress.total_bytes;
+            progress.status = ImportStatus::Completed;
+            entries = indexed;
+        }
+
+        let mut iter = file.records();
+        while let ImportStatus::Running = progress.status {
+            if receive_stop.try_recv().is_ok() {
+                progress.status = ImportStatus::Cancelled;
+                break;
+            }
+
+            match iter.next() {
+                Some(Ok(record)) => {
+                    entries.push(IndexEntry {
+                        record,
+                        flow: extract_five_tuple(file.frame(&record)),
+                    });
+                    progress.packets_parsed += 1;
+                    progress.bytes_processed = iter.offset() as u64;
+
+                    if last_notification.elapsed() >= PROGRESS_INTERVAL {
+                        progress.eta_ms = estimate_remaining(
+                            start.elapsed(),
+                            progress.bytes_processed,
+                            progress.total_bytes,
+                        )
+                        .map(|eta| eta.as_millis() as u64);
+
+                        let _result = window.emit("import_progress", progress.clone());
+                        last_notification = Instant::now();
+                    }
+                }
+                Some(Err(e)) => {
+                    error!("Import failed: {}", e);
+                    progress.status = ImportStatus::Failed(format!("Reading file failed: {}", e));
+                    break;
+                }
+                None => {
+                    progress.status = ImportStatus::Completed;
+                    break;
+                }
+            }
+        }
+
+        if let ImportStatus::Completed = progress.status {
+            if let (Some(metadata), false) = (&metadata, from_index) {
+                if let Err(e) = write_index(&index, metadata, &entries) {
+                    warn!("[{}] Index not saved: {}", path, e);
+                }
+            }
+
+            let decryptor = new_decryptor(file.header(), wpa2_credentials);
+            *offline.lock().unwrap() = Some(OfflineCapture::new(file, entries, decryptor));
+        }
+        import.lock().unwrap().take();
+
+        progress.eta_ms = Some(0);
+        info!(
+            "Import terminated: {:?}; Packets: {}",
+            progress.status, progress.packets_parsed
+        );
+        let total = progress.packets_parsed;
+        let _result = window.emit("import_progress", progress);
+        let _result = window.emit(
+            "packets_received",
+            PacketsReceived {
+                count: total,
+                total,
+            },
+        );
+    });
+
+    Ok(())
+}
+
+/// Parses a whole .pcap file at once, replacing the collected packets with the ones contained in it
+///
+/// Unlike the import, the call returns only when all the frames have been dissected, so it is
+/// meant for small captures. The number of parsed packets is returned.
+#[tauri::command]
+pub fn parse_pcap_file(
+    path: String,
+    passphrase: Option<String>,
+    state: tauri::State<SniffingState>,
+) -> Result<usize, SniffingError> {
+    let running_import = state.import.lock().unwrap();
+    if running_import.is_some() {
+        return Err(SniffingError::ImportAlreadyRunning(
+            "An import is already running".to_owned(),
+        ));
+    }
+
+    let file = CaptureFile::open(&path, passphrase.as_deref())
+        .map_err(|e| SniffingError::ImportFailed(format!("Cannot open {}: {}", path, e)))?;
+
+    reset_collected_packets(&state);
+
+    // The import lock is held until the end, so that no import can start in the meantime
+    let decryptor = new_decryptor(
+        file.header(),
+        state.wpa2_credentials.lock().unwrap().clone(),
+    );
+    let parsed = dissect_file(&file, decryptor, |record, new_packet| {
+        store_packet(
+            new_packet,
+            None,
+            None,
+            get_timestamp(record.seconds as i64, record.nanoseconds),
+            &state.packets,
+            &state.exchanged_packets,
+        );
+    });
+
+    let count = state.packets.lock().unwrap().packets.len();
+    state.info.lock().unwrap().counter = count;
+
+    match parsed {
+        Ok(()) => {
+            info!("[{}] Parsed; Packets: {}", path, count);
+            Ok(count)
+        }
+        Err(e) => {
+            error!("[{}] Parsing failed after {} packets: {}", path, count, e);
+            Err(SniffingError::ImportFailed(format!(
+                "Reading file failed: {}",
+                e
+            )))
+        }
+    }
+}
+
+/// Cancels the running import of an offline file
+#[tauri::command]
+pub fn cancel_import(state: tauri::State<SniffingState>) -> Result<(), SniffingError> {
+    let import = state.import.lock().unwrap();
+
+    match import.as_ref() {
+        Some(send_stop) => {
+            // The import thread may have just terminated on its own
+            let _result = send_stop.send(());
+            info!("Import cancelled");
+            Ok(())
+        }
+        None => Err(SniffingError::CancelImportWithoutPriorStart(
+            "Cancel import without prior starting of the process".to_owned(),
+        )),
+    }
+}
+
+/// Sets the WPA2 network credentials used to decrypt the 802.11 captures imported afterwards
+#[tauri::command]
+pub fn set_wpa2_credentials(
+    ssid: String,
+    passphrase: String,
+    state: tauri::State<SniffingState>,
+) -> Result<(), SniffingError> {
+    if ssid.is_empty() || ssid.len() > 32 || !(8..=63).contains(&passphrase.len()) {
+        return Err(SniffingError::InvalidWpa2Credentials(
+            "The SSID must be 1-32 bytes long, the passphrase 8-63 characters long".to_owned(),
+        ));
+    }
+
+    info!("[{}] WPA2 credentials set", ssid);
+    *state.wpa2_credentials.lock().unwrap() = Some((ssid, passphrase));
+
+    Ok(())
+}
+
+/// Get all the packets exchanged in a flow of the imported capture file, in both directions
+#[tauri::command]
+pub fn get_flow_packets(
+    source_ip: IpAddr,
+    source_port: u16,
+    destination_ip: IpAddr,
+    destination_port: u16,
+    protocol: u8,
+    state: tauri::State<SniffingState>,
+) -> Result<Vec<ParsedPacket>, SniffingError> {
+    let flow = FiveTuple {
+        source: source_ip,
+        destination: destination_ip,
+        source_port,
+        destination_port,
+        protocol,
+    };
+
+    let mut offline = state.offline.lock().unwrap();
+    let offline = offline.as_mut().ok_or_else(|| {
+        SniffingError::NoOfflineCapture("No capture file has been imported".to_owned())
+    })?;
+
+    let frames = offline.flow_frames(&flow);
+    if let Some(last) = frames.last() {
+        offline.dissect_until(
+            last + 1,
+            &state.info,
+            &state.packets,
+            &state.exchanged_packets,
+        );
+    }
+    offline.parse_applications(
+        frames.iter().copied(),
+        &state.packets,
+        &state.exchanged_packets,
+    );
+
+    // Offline packets are identified by their position in the capture file
+    let packets = state.packets.lock().unwrap();
+    let mut result: Vec<ParsedPacket> = frames
+        .iter()
+        .filter_map(|&index| packets.packets.get(index))
+        .map(|packet| ParsedPacket::clone(&**packet))
+        .collect();
+    state.geoip.lock().unwrap().locate_packets(&mut result);
+
+    info!(
+        "Received getFlowPackets request ({:?}); Len: {}",
+        flow,
+        result.len()
+    );
+
+    Ok(result)
+}
+
+/// Remove the packets collected so far, together with the state of the parsers
+fn reset_collected_packets(state: &SniffingState) {
+    state.offline.lock().unwrap().take();
+    state.packets.lock().unwrap().clear();
+    state.bookmarks.lock().unwrap().clear();
+    state.packet_edits.lock().unwrap().clear();
+    std::mem::take(&mut *state.exchanged_packets.lock().unwrap());
+    state.info.lock().unwrap().counter = 0;
+    cleanup_sniffing_state();
+}
+
+/// Create the decryptor of an 802.11 capture, if the WPA2 credentials are set
+fn new_decryptor(
+    header: &GlobalHeader,
+    wpa2_credentials: Option<(String, String)>,
+) -> Option<Wpa2Decryptor> {
+    match (header.link_type, wpa2_credentials) {
+        (LinkTypes::IEEE802_11 | LinkTypes::IEEE802_11_RADIOTAP, Some((ssid, passphrase))) => {
+            Some(Wpa2Decryptor::new(&ssid, &passphrase))
+        }
+        _ => None,
+    }
+}
+
+/// Dissect the records of a capture file one after the other, stopping at the first unreadable one
+fn dissect_file(
+    file: &CaptureFile,
+    mut decryptor: Option<Wpa2Decryptor>,
+    mut on_packet: impl FnMut(&CaptureRecord, ParsedPacket),
+) -> io::Result<()> {
+    for (id, record) in file.records().enumerate() {
+        let record = record?;
+        on_packet(
+            &record,
+            dissect_record(file, &record, decryptor.as_mut(), id),
+        );
+    }
+
+    Ok(())
+}
+
+/// Parse a frame of the capture file, according to its link type
+fn dissect_record(
+    file: &CaptureFile,
+    record: &CaptureRecord,
+    decryptor: Option<&mut Wpa2Decryptor>,
+    id: usize,
+) -> ParsedPacket {
+    dissect_frame(
+        None,
+        file.header().link_type,
+        record,
+        file.frame(record),
+        decryptor,
+        id,
+    )
+}
+
+/// Parse a frame of a record, according to the link type of its capture file
+///
+/// Without a parser context, the frame is parsed with the state of the current thread.
+fn dissect_frame(
+    context: Option<&mut ParserContext>,
+    linktype: u32,
+    record: &CaptureRecord,
+    frame: &[u8],
+    decryptor: Option<&mut Wpa2Decryptor>,
+    id: usize,
+) -> ParsedPacket {
+    // Frames of unsupported link types are dissected as Ethernet ones
+    let link_type = LinkType::from_linktype(linktype).unwrap_or(LinkType::Ethernet);
+    let meta = FrameMeta {
+        capture_time: Some(Duration::new(record.seconds as u64, record.nanoseconds)),
+        wire_length: Some(record.original_length as usize),
+        decryptor,
+        ..FrameMeta::new(id)
+    };
+
+    match context {
+        Some(context) => context.parse_frame(link_type, frame, meta),
+        None => parse_frame(link_type, frame, meta),
+    }
+}
+
+/// Estimate the remaining time of the import, based on the average throughput so far
+fn estimate_remaining(elapsed: Duration, processed: u64, total: u64) -> Option<Duration> {
+    if processed == 0 || elapsed.is_zero() {
+        return None;
+    }
+
+    let remaining = total.saturating_sub(processed);
+    Some(elapsed.mul_f64(remaining as f64 / processed as f64))
+}
+
+/// Convert a PCAP record timestamp to local time
+fn get_timestamp(seconds: i64, nanoseconds: u32) -> DateTime<Local> {
+    Local
+        .timestamp_opt(seconds, nanoseconds)
+        .single()
+        .unwrap_or_else(Local::now)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::estimate_remaining;
+
+    #[test]
+    fn eta_with_nothing_processed() {
+        assert!(estimate_remaining(Duration::from_secs(1), 0, 100).is_none());
+        assert!(estimate_remaining(Duration::ZERO, 10, 100).is_none());
+    }
+
+    #[test]
+    fn eta_halfway() {
+        let eta = estimate_remaining(Duration::from_secs(2), 50, 100).unwrap();
+        assert_eq!(eta, Duration::from_secs(2));
+    }
+
+    #[test]
+    fn eta_completed() {
+        let eta = estimate_remaining(Duration::from_secs(2), 120, 100).unwrap();
+        assert_eq!(eta, Duration::ZERO);
+    }
+}
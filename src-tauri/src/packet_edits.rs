@@ -0,0 +1,571 @@
+//! Editing of the frames of the imported packets, to build sanitized or targeted test captures
+//!
+//! The fields of the selected packets can be modified before they are exported:
+//! - Source and destination IP addresses, of the same version as the packet
+//! - Source and destination ports of TCP and UDP
+//! - Payload of TCP and UDP, overwritten from an offset or replaced altogether
+//!
+//! The lengths (IPv4 total length, IPv6 payload length, UDP length) and the checksums (IPv4
+//! header, TCP, UDP and ICMPv6) of the edited frames are fixed up, so that they are dissected like
+//! captured ones; UDP checksums left unset over IPv4 stay unset. Only the IP packets of Ethernet,
+//! Linux cooked, loopback and raw IP captures can be edited, and neither fragmented nor truncated
+//! ones.
+//!
+//! Each edit is applied to the frame as soon as it is set, failing if it does not fit the packet.
+//! The PCAP and PCAPNG exports write the edited frames in place of the captured ones, while the
+//! collected packets keep their original dissection. Edits are dropped together with the
+//! collected packets.
+
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+
+use pnet::packet::icmpv6::{self, Icmpv6Packet};
+use pnet::packet::ip::{IpNextHeaderProtocol, IpNextHeaderProtocols};
+use pnet::packet::ipv4::{self, Ipv4Flags, Ipv4Packet, MutableIpv4Packet};
+use pnet::packet::ipv6::{Ipv6Packet, MutableIpv6Packet};
+use pnet::packet::tcp::{self, TcpPacket};
+use pnet::packet::udp::{self, UdpPacket};
+use serde::{Deserialize, Serialize};
+use sniffer_parser::{ieee80211_network_offset, radiotap_network_offset, HeaderLength};
+use tracing::info;
+
+use crate::capture_file::{CaptureRecord, LinkTypes};
+use crate::{SniffingError, SniffingState};
+
+const IPV6_HEADER_LENGTH: usize = 40;
+const UDP_HEADER_LENGTH: usize = 8;
+const LOOPBACK_HEADER_LENGTH: usize = 4;
+const LINUX_SLL_HEADER_LENGTH: usize = 16;
+const LINUX_SLL2_HEADER_LENGTH: usize = 20;
+/// Direction and compressed header preceding the captured SLIP packets
+const SLIP_HEADER_LENGTH: usize = 16;
+
+/// EtherTypes of the IP packets, as found in Ethernet and Linux cooked headers
+const ETHERTYPE_IPV4: u16 = 0x0800;
+const ETHERTYPE_IPV6: u16 = 0x86dd;
+
+/// Address and control fields of the PPP frames in HDLC-like framing
+const PPP_ADDRESS_CONTROL: [u8; 2] = [0xff, 0x03];
+/// PPP protocols of the IP packets
+const PPP_IPV4: u16 = 0x0021;
+const PPP_IPV6: u16 = 0x0057;
+
+/// Link types whose frames can be edited: the others have a frame check sequence or a copy of the
+/// IP header that would not be fixed up
+const EDITABLE_LINK_TYPES: [u32; 8] = [
+    LinkTypes::ETHERNET,
+    LinkTypes::LINUX_SLL,
+    LinkTypes::LINUX_SLL2,
+    LinkTypes::NULL,
+    LinkTypes::LOOP,
+    LinkTypes::RAW,
+    LinkTypes::IPV4,
+    LinkTypes::IPV6,
+];
+
+/// Offsets of the checksums in their headers
+const TCP_CHECKSUM: usize = 16;
+const UDP_CHECKSUM: usize = 6;
+const ICMPV6_CHECKSUM: usize = 2;
+
+/// Fields of a packet to modify, the ones not set being left as captured
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct PacketEdit {
+    pub source_ip: Option<IpAddr>,
+    pub dest_ip: Option<IpAddr>,
+    pub source_port: Option<u16>,
+    pub dest_port: Option<u16>,
+    pub payload: Option<PayloadEdit>,
+}
+
+/// Modification of the payload of a TCP or UDP packet
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "type", content = "value", rename_all = "camelCase")]
+pub enum PayloadEdit {
+    /// Bytes written from an offset of the payload, extending it if needed
+    Overwrite { offset: usize, bytes: Vec<u8> },
+    /// Bytes replacing the whole payload
+    Replace(Vec<u8>),
+}
+
+/// Edits of the imported packets, with the frames they produced
+#[derive(Debug, Default)]
+pub struct PacketEdits {
+    edits: BTreeMap<usize, (PacketEdit, Vec<u8>)>,
+}
+
+impl PacketEdits {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Remove all the edits
+    pub fn clear(&mut self) {
+        self.edits.clear();
+    }
+
+    /// Get the edits of the packets, by packet id
+    pub fn edits(&self) -> BTreeMap<usize, PacketEdit> {
+        self.edits
+            .iter()
+            .map(|(&id, (edit, _))| (id, edit.clone()))
+            .collect()
+    }
+
+    /// Get the record and the frame of a packet to export: the edited ones, if the packet is edited
+    pub fn apply<'a>(
+        &'a self,
+        id: usize,
+        record: &CaptureRecord,
+        frame: &'a [u8],
+    ) -> (CaptureRecord, &'a [u8]) {
+        match self.edits.get(&id) {
+            Some((_, edited)) => (
+                CaptureRecord {
+                    captured_length: edited.len() as u32,
+                    original_length: edited.len() as u32,
+                    ..*record
+                },
+                edited,
+            ),
+            None => (*record, frame),
+        }
+    }
+}
+
+/// Apply an edit to a whole frame of the given link type, fixing up its lengths and checksums
+pub fn edit_frame(link_type: u32, frame: &[u8], edit: &PacketEdit) -> Result<Vec<u8>, String> {
+    let editable = EDITABLE_LINK_TYPES.contains(&link_type);
+    let offset = network_offset(link_type, frame).filter(|_| editable).ok_or_else(|| {
+        "Only the IP packets of Ethernet, Linux cooked, loopback and raw IP captures can be edited"
+            .to_owned()
+    })?;
+    let (link_header, packet) = frame.split_at(offset);
+
+    let version = packet.first().map(|byte| byte >> 4);
+    let (header_length, end, protocol) = match version {
+        Some(4) => {
+            let ipv4 = Ipv4Packet::new(packet).ok_or("Malformed IPv4 packet")?;
+            if ipv4.get_flags() & Ipv4Flags::MoreFragments != 0 || ipv4.get_fragment_offset() != 0 {
+                return Err("Fragmented packets can't be edited".to_owned());
+            }
+
+            (
+                ipv4.get_header_length() as usize * 4,
+                ipv4.get_total_length() as usize,
+                ipv4.get_next_level_protocol(),
+            )
+        }
+        Some(6) => {
+            let ipv6 = Ipv6Packet::new(packet).ok_or("Malformed IPv6 packet")?;
+
+            (
+                IPV6_HEADER_LENGTH,
+                IPV6_HEADER_LENGTH + ipv6.get_payload_length() as usize,
+                ipv6.get_next_header(),
+            )
+        }
+        _ => return Err("Only IPv4 and IPv6 packets can be edited".to_owned()),
+    };
+    if header_length < 20 || end < header_length || end > packet.len() {
+        return Err("Malformed IP packet".to_owned());
+    }
+
+    let mut ip_header = packet[..header_length].to_vec();
+    let mut transport = packet[header_length..end].to_vec();
+    edit_transport(&mut transport, protocol, edit)?;
+
+    if version == Some(4) {
+        let mut ipv4 = MutableIpv4Packet::new(&mut ip_header).unwrap();
+        let total_length = u16::try_from(header_length + transport.len())
+            .map_err(|_| "The edited packet exceeds the IPv4 maximum length".to_owned())?;
+        ipv4.set_total_length(total_length);
+        match edit.source_ip {
+            Some(IpAddr::V4(address)) => ipv4.set_source(address),
+            Some(IpAddr::V6(_)) => return Err("The packet is over IPv4".to_owned()),
+            None => (),
+        }
+        match edit.dest_ip {
+            Some(IpAddr::V4(address)) => ipv4.set_destination(address),
+            Some(IpAddr::V6(_)) => return Err("The packet is over IPv4".to_owned()),
+            None => (),
+        }
+        let checksum = ipv4::checksum(&ipv4.to_immutable());
+        ipv4.set_checksum(checksum);
+
+        let (source, destination) = (ipv4.get_source(), ipv4.get_destination());
+        match protocol {
+            IpNextHeaderProtocols::Tcp => {
+                let checksum =
+                    tcp::ipv4_checksum(&TcpPacket::new(&transport).unwrap(), &source, &destination);
+                set_checksum(&mut transport, TCP_CHECKSUM, checksum);
+            }
+            IpNextHeaderProtocols::Udp if transport[UDP_CHECKSUM..UDP_CHECKSUM + 2] != [0; 2] => {
+                let checksum =
+                    udp::ipv4_checksum(&UdpPacket::new(&transport).unwrap(), &source, &destination);
+                set_checksum(&mut transport, UDP_CHECKSUM, udp_checksum(checksum));
+            }
+            _ => (),
+        }
+    } else {
+        let mut ipv6 = MutableIpv6Packet::new(&mut ip_header).unwrap();
+        let payload_length = u16::try_from(transport.len())
+            .map_err(|_| "The edited packet exceeds the IPv6 maximum length".to_owned())?;
+        ipv6.set_payload_length(payload_length);
+        match edit.source_ip {
+            Some(IpAddr::V6(address)) => ipv6.set_source(address),
+            Some(IpAddr::V4(_)) => return Err("The packet is over IPv6".to_owned()),
+            None => (),
+        }
+        match edit.dest_ip {
+            Some(IpAddr::V6(address)) => ipv6.set_destination(address),
+            Some(IpAddr::V4(_)) => return Err("The packet is over IPv6".to_owned()),
+            None => (),
+        }
+
+        let (source, destination) = (ipv6.get_source(), ipv6.get_destination());
+        match protocol {
+            IpNextHeaderProtocols::Tcp => {
+                let checksum =
+                    tcp::ipv6_checksum(&TcpPacket::new(&transport).unwrap(), &source, &destination);
+                set_checksum(&mut transport, TCP_CHECKSUM, checksum);
+            }
+            IpNextHeaderProtocols::Udp => {
+                let checksum =
+                    udp::ipv6_checksum(&UdpPacket::new(&transport).unwrap(), &source, &destination);
+                set_checksum(&mut transport, UDP_CHECKSUM, udp_checksum(checksum));
+            }
+            IpNextHeaderProtocols::Icmpv6 => {
+                if let Some(icmpv6_packet) = Icmpv6Packet::new(&transport) {
+                    let checksum = icmpv6::checksum(&icmpv6_packet, &source, &destination);
+                    set_checksum(&mut transport, ICMPV6_CHECKSUM, checksum);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    // Bytes following the IP packet (e.g. the Ethernet padding) are kept
+    Ok([link_header, &ip_header, &transport, &packet[end..]].concat())
+}
+
+/// Apply the edit of the ports and the payload to a TCP or UDP packet, fixing up the UDP length
+fn edit_transport(
+    transport: &mut Vec<u8>,
+    protocol: IpNextHeaderProtocol,
+    edit: &PacketEdit,
+) -> Result<(), String> {
+    let header_length = match protocol {
+        IpNextHeaderProtocols::Tcp => TcpPacket::new(transport)
+            .map(|tcp| tcp.get_data_offset() as usize * 4)
+            .filter(|&length| length >= 20 && length <= transport.len()),
+        IpNextHeaderProtocols::Udp => {
+            Some(UDP_HEADER_LENGTH).filter(|&length| length <= transport.len())
+        }
+        _ if edit.source_port.is_none() && edit.dest_port.is_none() && edit.payload.is_none() => {
+            return Ok(());
+        }
+        _ => return Err("Ports and payload can be edited only in TCP and UDP packets".to_owned()),
+    }
+    .ok_or_else(|| "Malformed transport header".to_owned())?;
+
+    if let Some(port) = edit.source_port {
+        transport[0..2].copy_from_slice(&port.to_be_bytes());
+    }
+    if let Some(port) = edit.dest_port {
+        transport[2..4].copy_from_slice(&port.to_be_bytes());
+    }
+    match &edit.payload {
+        Some(PayloadEdit::Overwrite { offset, bytes }) => {
+            let start = header_length + offset;
+            if start > transport.len() {
+                return Err(format!(
+                    "Offset {} beyond the {} bytes of payload",
+                    offset,
+                    transport.len() - header_length
+                ));
+            }
+            let end = start + bytes.len();
+            if end > transport.len() {
+                transport.resize(end, 0);
+            }
+            transport[start..end].copy_from_slice(bytes);
+        }
+        Some(PayloadEdit::Replace(bytes)) => {
+            transport.truncate(header_length);
+            transport.extend_from_slice(bytes);
+        }
+        None => (),
+    }
+
+    if protocol == IpNextHeaderProtocols::Udp {
+        let length = u16::try_from(transport.len())
+            .map_err(|_| "The edited datagram exceeds the UDP maximum length".to_owned())?;
+        transport[4..6].copy_from_slice(&length.to_be_bytes());
+    }
+
+    Ok(())
+}
+
+/// Get the offset of the IP packet carried by a frame of the given link type
+pub(crate) fn network_offset(link_type: u32, frame: &[u8]) -> Option<usize> {
+    let ethertype = |offset: usize| {
+        frame
+            .get(offset..offset + 2)
+            .map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]))
+            .filter(|ethertype| [ETHERTYPE_IPV4, ETHERTYPE_IPV6].contains(ethertype))
+    };
+
+    match link_type {
+        LinkTypes::ETHERNET => ethertype(12).map(|_| HeaderLength::ETHERNET),
+        LinkTypes::LINUX_SLL => ethertype(14).map(|_| LINUX_SLL_HEADER_LENGTH),
+        LinkTypes::LINUX_SLL2 => ethertype(0).map(|_| LINUX_SLL2_HEADER_LENGTH),
+        LinkTypes::NULL | LinkTypes::LOOP => Some(LOOPBACK_HEADER_LENGTH),
+        LinkTypes::RAW | LinkTypes::IPV4 | LinkTypes::IPV6 => Some(0),
+        LinkTypes::SLIP => frame
+            .get(SLIP_HEADER_LENGTH)
+            .filter(|byte| [4, 6].contains(&(*byte >> 4)))
+            .map(|_| SLIP_HEADER_LENGTH),
+        LinkTypes::PPP | LinkTypes::PPP_HDLC => ppp_network_offset(frame),
+        LinkTypes::IEEE802_11 => {
+            ieee80211_network_offset(frame).and_then(|offset| ethertype(offset - 2).map(|_| offset))
+        }
+        LinkTypes::IEEE802_11_RADIOTAP => {
+            radiotap_network_offset(frame).and_then(|offset| ethertype(offset - 2).map(|_| offset))
+        }
+        _ => None,
+    }
+    .filter(|&offset| offset < frame.len())
+}
+
+/// Get the offset of the IP packet carried by a PPP frame, whose address, control and protocol
+/// fields may be compressed
+fn ppp_network_offset(frame: &[u8]) -> Option<usize> {
+    let start = if frame.starts_with(&PPP_ADDRESS_CONTROL) {
+        PPP_ADDRESS_CONTROL.len()
+    } else {
+        0
+    };
+    // Protocol numbers are odd in their last byte: a compressed field is that byte alone
+    let (protocol, length) = match frame.get(start..)? {
+        [protocol, ..] if protocol & 0x01 == 1 => (*protocol as u16, 1),
+        [high, low, ..] if low & 0x01 == 1 => (u16::from_be_bytes([*high, *low]), 2),
+        _ => return None,
+    };
+
+    [PPP_IPV4, PPP_IPV6]
+        .contains(&protocol)
+        .then_some(start + length)
+}
+
+/// Write a checksum at the given offset of a header
+fn set_checksum(header: &mut [u8], offset: usize, checksum: u16) {
+    header[offset..offset + 2].copy_from_slice(&checksum.to_be_bytes());
+}
+
+/// Get the UDP checksum to transmit: a computed one of zero is sent as all ones, zero meaning no
+/// checksum
+fn udp_checksum(checksum: u16) -> u16 {
+    if checksum == 0 {
+        0xffff
+    } else {
+        checksum
+    }
+}
+
+/// Sets the edit of a packet of the imported capture, replacing its previous one
+#[tauri::command]
+pub fn set_packet_edit(
+    id: usize,
+    edit: PacketEdit,
+    state: tauri::State<SniffingState>,
+) -> Result<(), SniffingError> {
+    let offline = state.offline.lock().unwrap();
+    // Raw frames are kept only for imported capture files
+    let offline = offline.as_ref().ok_or_else(|| {
+        SniffingError::NoOfflineCapture("Packets can be edited only in imported files".to_owned())
+    })?;
+
+    let (record, frame) = offline.frame(id).ok_or_else(|| {
+        SniffingError::InvalidPacketEdit(format!("Packet {} not in the imported file", id))
+    })?;
+    if record.original_length as usize != frame.len() {
+        return Err(SniffingError::InvalidPacketEdit(format!(
+            "Packet {} is truncated",
+            id
+        )));
+    }
+    let edited = edit_frame(offline.header().link_type, frame, &edit).map_err(|e| {
+        SniffingError::InvalidPacketEdit(format!("Packet {} can't be edited: {}", id, e))
+    })?;
+
+    info!("Packet {} edited: {:?}", id, edit);
+    state
+        .packet_edits
+        .lock()
+        .unwrap()
+        .edits
+        .insert(id, (edit, edited));
+
+    Ok(())
+}
+
+/// Removes the edit of a packet, returning whether it was edited
+#[tauri::command]
+pub fn remove_packet_edit(id: usize, state: tauri::State<SniffingState>) -> bool {
+    state
+        .packet_edits
+        .lock()
+        .unwrap()
+        .edits
+        .remove(&id)
+        .is_some()
+}
+
+/// Returns the edits of the imported packets, by packet id
+#[tauri::command]
+pub fn get_packet_edits(state: tauri::State<SniffingState>) -> BTreeMap<usize, PacketEdit> {
+    state.packet_edits.lock().unwrap().edits()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+    use pnet::packet::ethernet::EthernetPacket;
+    use pnet::packet::ipv4::{self, Ipv4Packet};
+    use pnet::packet::tcp::{self, TcpPacket};
+    use pnet::packet::udp::{self, UdpPacket};
+    use pnet::packet::Packet;
+
+    use crate::capture_file::{CaptureRecord, LinkTypes};
+
+    use super::{edit_frame, PacketEdit, PacketEdits, PayloadEdit};
+
+    // Ethernet (14) + IPv4 (20) + TCP (20) + 3 bytes of payload
+    const TCP_FRAME: &[u8] = &[
+        0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x08, 0x00, 0x45,
+        0x00, 0x00, 0x2b, 0x00, 0x00, 0x00, 0x00, 0x40, 0x06, 0x00, 0x00, 0x0a, 0x0a, 0x0a, 0x0a,
+        0x0b, 0x0b, 0x0b, 0x0b, 0x11, 0x5c, 0x01, 0xbb, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x50, 0x18, 0xff, 0xff, 0x00, 0x00, 0x00, 0x00, 0x47, 0x45, 0x54,
+    ];
+
+    // IPv6 (40) + UDP (8) + 2 bytes of payload
+    const UDP_PACKET: &[u8] = &[
+        0x60, 0x00, 0x00, 0x00, 0x00, 0x0a, 0x11, 0x40, 0x20, 0x01, 0x0d, 0xb8, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x20, 0x01, 0x0d, 0xb8, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x30, 0x39, 0x00, 0x35, 0x00,
+        0x0a, 0x00, 0x00, 0x61, 0x62,
+    ];
+
+    #[test]
+    fn addresses_and_ports_of_tcp_frame() {
+        let edit = PacketEdit {
+            source_ip: Some(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1))),
+            dest_port: Some(8443),
+            ..PacketEdit::default()
+        };
+        let frame = edit_frame(LinkTypes::ETHERNET, TCP_FRAME, &edit).unwrap();
+        assert_eq!(frame.len(), TCP_FRAME.len());
+
+        let ethernet = EthernetPacket::new(&frame).unwrap();
+        let ipv4 = Ipv4Packet::new(ethernet.payload()).unwrap();
+        assert_eq!(ipv4.get_source(), Ipv4Addr::new(192, 0, 2, 1));
+        assert_eq!(ipv4.get_destination(), Ipv4Addr::new(11, 11, 11, 11));
+        assert_eq!(ipv4.get_checksum(), ipv4::checksum(&ipv4));
+
+        let tcp = TcpPacket::new(ipv4.payload()).unwrap();
+        assert_eq!((tcp.get_source(), tcp.get_destination()), (4444, 8443));
+        assert_eq!(
+            tcp.get_checksum(),
+            tcp::ipv4_checksum(&tcp, &ipv4.get_source(), &ipv4.get_destination())
+        );
+        assert_eq!(tcp.payload(), b"GET");
+    }
+
+    #[test]
+    fn payload_of_udp_packet() {
+        let edit = PacketEdit {
+            payload: Some(PayloadEdit::Replace(b"hello".to_vec())),
+            ..PacketEdit::default()
+        };
+        let packet = edit_frame(LinkTypes::RAW, UDP_PACKET, &edit).unwrap();
+        assert_eq!(packet.len(), UDP_PACKET.len() + 3);
+        // Payload length of IPv6, length of UDP
+        assert_eq!(packet[4..6], [0x00, 0x0d]);
+        assert_eq!(packet[44..46], [0x00, 0x0d]);
+
+        let udp = UdpPacket::new(&packet[40..]).unwrap();
+        assert_eq!(udp.payload(), b"hello");
+        let (source, destination) = (
+            Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1),
+            Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 2),
+        );
+        assert_eq!(
+            udp.get_checksum(),
+            udp::ipv6_checksum(&udp, &source, &destination)
+        );
+
+        let edit = PacketEdit {
+            payload: Some(PayloadEdit::Overwrite {
+                offset: 1,
+                bytes: b"xyz".to_vec(),
+            }),
+            ..PacketEdit::default()
+        };
+        let packet = edit_frame(LinkTypes::RAW, UDP_PACKET, &edit).unwrap();
+        assert_eq!(&packet[48..], b"axyz");
+    }
+
+    #[test]
+    fn invalid_edits() {
+        let edits = [
+            PacketEdit {
+                dest_ip: Some(IpAddr::V6(Ipv6Addr::LOCALHOST)),
+                ..PacketEdit::default()
+            },
+            PacketEdit {
+                payload: Some(PayloadEdit::Overwrite {
+                    offset: 4,
+                    bytes: vec![0x00],
+                }),
+                ..PacketEdit::default()
+            },
+        ];
+        for edit in edits {
+            assert!(edit_frame(LinkTypes::ETHERNET, TCP_FRAME, &edit).is_err());
+        }
+        assert!(edit_frame(LinkTypes::PPP, TCP_FRAME, &PacketEdit::default()).is_err());
+    }
+
+    #[test]
+    fn edited_frames_exported() {
+        let record = CaptureRecord {
+            offset: 24,
+            seconds: 1,
+            nanoseconds: 0,
+            captured_length: TCP_FRAME.len() as u32,
+            original_length: TCP_FRAME.len() as u32,
+        };
+        let edit = PacketEdit {
+            payload: Some(PayloadEdit::Replace(vec![])),
+            ..PacketEdit::default()
+        };
+        let mut edits = PacketEdits::new();
+        let edited = edit_frame(LinkTypes::ETHERNET, TCP_FRAME, &edit).unwrap();
+        edits.edits.insert(1, (edit.clone(), edited));
+
+        assert_eq!(edits.apply(0, &record, TCP_FRAME), (record, TCP_FRAME));
+        let (edited_record, frame) = edits.apply(1, &record, TCP_FRAME);
+        assert_eq!(frame.len(), 54);
+        assert_eq!(
+            (edited_record.captured_length, edited_record.original_length),
+            (54, 54)
+        );
+        assert_eq!(edits.edits().get(&1), Some(&edit));
+
+        edits.clear();
+        assert!(edits.edits().is_empty());
+    }
+}
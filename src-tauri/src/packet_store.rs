@@ -0,0 +1,870 @@
+//! Persistent store of the collected packets, in a SQLite database
+//!
+//! Keeping all the packets of a capture in the memory of the frontend limits its size. While a
+//! store is open, the collected packets are written to it as well, together with the
+//! conversations and the traffic statistics of their capture, so that they can be queried page by
+//! page:
+//! - packets, by capture, address, port, protocol, interface and time, and with a full-text search
+//!   over the text values of their decoded fields (FTS5 query syntax, e.g. `"example.com"` or
+//!   `GET AND index`)
+//! - conversations of a capture, by protocol and address, and the statistics of a capture, saved
+//!   when the capture ends, when the store is closed, or when they are queried
+//!
+//! Each capture started (or file imported) while the store is open is a new capture of the store,
+//! which keeps the ones of the earlier sessions too. Packets are written in batches, each in a
+//! transaction. The database uses write-ahead logging: queries read it through connections of
+//! their own, without holding the collected packets.
+
+use rusqlite::types::Value as SqlValue;
+use rusqlite::{params, params_from_iter, Connection, OpenFlags, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sniffer_parser::serializable_packet::ParsedPacket;
+use tracing::{error, info};
+
+use crate::conversations::{ConnectionTracker, ConversationOrder};
+use crate::report::get_sender_receiver;
+use crate::statistics::CaptureStatistics;
+use crate::{SniffingError, SniffingState};
+
+/// Packets written in each transaction
+const BATCH_SIZE: usize = 1000;
+
+const SCHEMA: &str = "
+    PRAGMA journal_mode = WAL;
+    CREATE TABLE IF NOT EXISTS captures (
+        id INTEGER PRIMARY KEY,
+        started INTEGER NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS packets (
+        id INTEGER PRIMARY KEY,
+        capture INTEGER NOT NULL REFERENCES captures (id),
+        packet_id INTEGER NOT NULL,
+        timestamp INTEGER NOT NULL,
+        interface TEXT,
+        source TEXT,
+        destination TEXT,
+        source_port INTEGER,
+        destination_port INTEGER,
+        protocols TEXT NOT NULL,
+        packet TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS packets_capture ON packets (capture, packet_id);
+    CREATE INDEX IF NOT EXISTS packets_source ON packets (source);
+    CREATE INDEX IF NOT EXISTS packets_destination ON packets (destination);
+    CREATE INDEX IF NOT EXISTS packets_timestamp ON packets (timestamp);
+    CREATE VIRTUAL TABLE IF NOT EXISTS packet_fields USING fts5 (fields, content = '');
+    CREATE TABLE IF NOT EXISTS conversations (
+        capture INTEGER NOT NULL REFERENCES captures (id),
+        id INTEGER NOT NULL,
+        protocol TEXT NOT NULL,
+        initiator TEXT NOT NULL,
+        responder TEXT NOT NULL,
+        first_seen INTEGER NOT NULL,
+        conversation TEXT NOT NULL,
+        PRIMARY KEY (capture, id)
+    );
+    CREATE TABLE IF NOT EXISTS statistics (
+        capture INTEGER PRIMARY KEY REFERENCES captures (id),
+        statistics TEXT NOT NULL
+    );
+";
+
+/// Capture saved in the store, as returned to the frontend
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct StoredCapture {
+    pub id: i64,
+    /// Time of the first packet, in microseconds since the Unix epoch
+    pub started: i64,
+    pub packets: usize,
+}
+
+/// Packet saved in the store, as returned to the frontend
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct StoredPacket {
+    pub capture: i64,
+    /// Identifier of the packet in its capture
+    pub id: usize,
+    /// Time the packet was captured, in microseconds since the Unix epoch
+    pub timestamp: i64,
+    pub interface: Option<String>,
+    pub source: Option<String>,
+    pub destination: Option<String>,
+    pub source_port: Option<u16>,
+    pub destination_port: Option<u16>,
+    pub protocols: Vec<String>,
+    /// Packet as serialized when collected
+    pub packet: Value,
+}
+
+/// Page of the packets satisfying a query, with the number of all of them
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct StoredPackets {
+    pub total: usize,
+    pub packets: Vec<StoredPacket>,
+}
+
+/// Conditions on the stored packets, all of them holding for the packets selected
+///
+/// Times are in microseconds since the Unix epoch.
+#[derive(Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PacketQuery {
+    pub capture: Option<i64>,
+    /// Source or destination address
+    pub address: Option<String>,
+    /// Source or destination port
+    pub port: Option<u16>,
+    pub protocol: Option<String>,
+    pub interface: Option<String>,
+    pub from: Option<i64>,
+    pub to: Option<i64>,
+    /// Full-text search over the text values of the decoded fields
+    pub search: Option<String>,
+}
+
+impl PacketQuery {
+    /// Get the SQL condition selecting the packets, with its parameters
+    fn condition(&self) -> (String, Vec<SqlValue>) {
+        let mut conditions = vec!["1 = 1"];
+        let mut parameters = vec![];
+
+        if let Some(capture) = self.capture {
+            conditions.push("capture = ?");
+            parameters.push(SqlValue::Integer(capture));
+        }
+        if let Some(address) = &self.address {
+            conditions.push("(source = ? OR destination = ?)");
+            parameters.push(SqlValue::Text(address.clone()));
+            parameters.push(SqlValue::Text(address.clone()));
+        }
+        if let Some(port) = self.port {
+            conditions.push("(source_port = ? OR destination_port = ?)");
+            parameters.push(SqlValue::Integer(port.into()));
+            parameters.push(SqlValue::Integer(port.into()));
+        }
+        if let Some(protocol) = &self.protocol {
+            // Case insensitive, as LIKE is for ASCII letters
+            conditions.push("',' || protocols || ',' LIKE ?");
+            parameters.push(SqlValue::Text(format!("%,{},%", protocol)));
+        }
+        if let Some(interface) = &self.interface {
+            conditions.push("interface = ?");
+            parameters.push(SqlValue::Text(interface.clone()));
+        }
+        if let Some(from) = self.from {
+            conditions.push("timestamp >= ?");
+            parameters.push(SqlValue::Integer(from));
+        }
+        if let Some(to) = self.to {
+            conditions.push("timestamp <= ?");
+            parameters.push(SqlValue::Integer(to));
+        }
+        if let Some(search) = &self.search {
+            conditions.push("id IN (SELECT rowid FROM packet_fields WHERE packet_fields MATCH ?)");
+            parameters.push(SqlValue::Text(search.clone()));
+        }
+
+        (conditions.join(" AND "), parameters)
+    }
+}
+
+/// SQLite database receiving the collected packets
+#[derive(Debug)]
+pub struct PacketStore {
+    path: String,
+    connection: Connection,
+    /// Capture the packets are written to, created with its first packet
+    capture: Option<i64>,
+    /// Packets written in the open transaction
+    pending: usize,
+}
+
+impl PacketStore {
+    /// Open the database, creating it if needed
+    pub fn open(path: &str) -> rusqlite::Result<Self> {
+        let connection = Connection::open(path)?;
+        connection.execute_batch(SCHEMA)?;
+
+        Ok(PacketStore {
+            path: path.to_owned(),
+            connection,
+            capture: None,
+            pending: 0,
+        })
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Write a collected packet, received from an interface (none for offline captures)
+    pub fn insert(
+        &mut self,
+        packet: &ParsedPacket,
+        interface: Option<&str>,
+    ) -> rusqlite::Result<()> {
+        let timestamp = packet.get_timestamp() as i64;
+        let capture = match self.capture {
+            Some(capture) => capture,
+            None => {
+                self.connection.execute(
+                    "INSERT INTO captures (started) VALUES (?1)",
+                    params![timestamp],
+                )?;
+                *self.capture.insert(self.connection.last_insert_rowid())
+            }
+        };
+
+        let serialized = to_json(packet)?;
+        let mut fields = vec![];
+        text_values(&serialized, &mut fields);
+        let (endpoints, protocols) = get_sender_receiver(packet);
+        let address = |address: String| (address != "-").then_some(address);
+        let port = |port: &str| port.parse::<u16>().ok();
+
+        if self.pending == 0 {
+            self.connection.execute_batch("BEGIN")?;
+        }
+        self.connection
+            .prepare_cached(
+                "INSERT INTO packets (capture, packet_id, timestamp, interface, source, \
+                 destination, source_port, destination_port, protocols, packet) \
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            )?
+            .execute(params![
+                capture,
+                packet.get_id() as i64,
+                timestamp,
+                interface,
+                address(endpoints.ip_source),
+                address(endpoints.ip_destination),
+                port(&endpoints.port_source),
+                port(&endpoints.port_destination),
+                protocols.join(","),
+                serialized.to_string(),
+            ])?;
+        self.connection
+            .prepare_cached("INSERT INTO packet_fields (rowid, fields) VALUES (?1, ?2)")?
+            .execute(params![
+                self.connection.last_insert_rowid(),
+                fields.join("\n")
+            ])?;
+
+        self.pending += 1;
+        if self.pending >= BATCH_SIZE {
+            self.commit()?;
+        }
+
+        Ok(())
+    }
+
+    /// Rewrite a packet of the current capture dissected again, given the one written before
+    pub fn replace(
+        &mut self,
+        replaced: &ParsedPacket,
+        packet: &ParsedPacket,
+    ) -> rusqlite::Result<()> {
+        let capture = match self.capture {
+            Some(capture) => capture,
+            None => return Ok(()),
+        };
+        let row: Option<i64> = self
+            .connection
+            .prepare_cached("SELECT id FROM packets WHERE capture = ?1 AND packet_id = ?2")?
+            .query_row(params![capture, packet.get_id() as i64], |row| row.get(0))
+            .optional()?;
+        let row = match row {
+            Some(row) => row,
+            None => return Ok(()),
+        };
+
+        let (replaced_serialized, serialized) = (to_json(replaced)?, to_json(packet)?);
+        let (mut replaced_fields, mut fields) = (vec![], vec![]);
+        text_values(&replaced_serialized, &mut replaced_fields);
+        text_values(&serialized, &mut fields);
+        let protocols = get_sender_receiver(packet).1;
+
+        if self.pending == 0 {
+            self.connection.execute_batch("BEGIN")?;
+        }
+        self.connection
+            .prepare_cached("UPDATE packets SET protocols = ?1, packet = ?2 WHERE id = ?3")?
+            .execute(params![protocols.join(","), serialized.to_string(), row])?;
+        // The rows of a contentless full-text table are deleted given the values they were
+        // written with
+        self.connection
+            .prepare_cached(
+                "INSERT INTO packet_fields (packet_fields, rowid, fields) \
+                 VALUES ('delete', ?1, ?2)",
+            )?
+            .execute(params![row, replaced_fields.join("\n")])?;
+        self.connection
+            .prepare_cached("INSERT INTO packet_fields (rowid, fields) VALUES (?1, ?2)")?
+            .execute(params![row, fields.join("\n")])?;
+
+        self.pending += 1;
+        if self.pending >= BATCH_SIZE {
+            self.commit()?;
+        }
+
+        Ok(())
+    }
+
+    /// Commit the packets written since the last batch
+    pub fn commit(&mut self) -> rusqlite::Result<()> {
+        if self.pending > 0 {
+            self.connection.execute_batch("COMMIT")?;
+            self.pending = 0;
+        }
+
+        Ok(())
+    }
+
+    /// Save the conversations and the statistics of the current capture, committing its packets
+    pub fn save(
+        &mut self,
+        conversations: &ConnectionTracker,
+        statistics: &CaptureStatistics,
+    ) -> rusqlite::Result<()> {
+        self.commit()?;
+        let capture = match self.capture {
+            Some(capture) => capture,
+            None => return Ok(()),
+        };
+
+        let transaction = self.connection.transaction()?;
+        {
+            let mut statement = transaction.prepare_cached(
+                "INSERT OR REPLACE INTO conversations (capture, id, protocol, initiator, \
+                 responder, first_seen, conversation) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            )?;
+            for conversation in
+                conversations.conversations(ConversationOrder::FirstSeen, false, None, None, None)
+            {
+                statement.execute(params![
+                    capture,
+                    conversation.id as i64,
+                    conversation.protocol,
+                    conversation.initiator.to_string(),
+                    conversation.responder.to_string(),
+                    conversation.first_seen,
+                    to_json(&conversation)?.to_string(),
+                ])?;
+            }
+        }
+        if let Some(report) = statistics.report(None) {
+            transaction.execute(
+                "INSERT OR REPLACE INTO statistics (capture, statistics) VALUES (?1, ?2)",
+                params![capture, to_json(&report)?.to_string()],
+            )?;
+        }
+
+        transaction.commit()
+    }
+
+    /// Save the current capture, the next packets being written to a new one
+    pub fn end_capture(
+        &mut self,
+        conversations: &ConnectionTracker,
+        statistics: &CaptureStatistics,
+    ) -> rusqlite::Result<()> {
+        let result = self.save(conversations, statistics);
+        self.capture = None;
+
+        result
+    }
+}
+
+fn to_json<T: Serialize>(value: &T) -> rusqlite::Result<Value> {
+    serde_json::to_value(value).map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))
+}
+
+/// Collect the text values of a serialized packet, the ones searched
+fn text_values<'a>(value: &'a Value, texts: &mut Vec<&'a str>) {
+    match value {
+        Value::String(text) => texts.push(text),
+        Value::Array(values) => values.iter().for_each(|value| text_values(value, texts)),
+        Value::Object(fields) => fields.values().for_each(|value| text_values(value, texts)),
+        _ => (),
+    }
+}
+
+/// Get the captures saved in a store, the oldest first
+fn captures(connection: &Connection) -> rusqlite::Result<Vec<StoredCapture>> {
+    let mut statement = connection.prepare(
+        "SELECT captures.id, captures.started, COUNT(packets.id) FROM captures \
+         LEFT JOIN packets ON packets.capture = captures.id \
+         GROUP BY captures.id ORDER BY captures.id",
+    )?;
+    let captures = statement.query_map([], |row| {
+        Ok(StoredCapture {
+            id: row.get(0)?,
+            started: row.get(1)?,
+            packets: row.get::<_, i64>(2)? as usize,
+        })
+    })?;
+
+    captures.collect()
+}
+
+/// Get the packets satisfying a query, between two positions in the order they were collected
+fn query_packets(
+    connection: &Connection,
+    start: usize,
+    end: usize,
+    query: &PacketQuery,
+) -> rusqlite::Result<StoredPackets> {
+    let (condition, mut parameters) = query.condition();
+
+    let total: i64 = connection.query_row(
+        &format!("SELECT COUNT(*) FROM packets WHERE {}", condition),
+        params_from_iter(&parameters),
+        |row| row.get(0),
+    )?;
+
+    parameters.push(SqlValue::Integer(end.saturating_sub(start) as i64));
+    parameters.push(SqlValue::Integer(start as i64));
+    let mut statement = connection.prepare(&format!(
+        "SELECT capture, packet_id, timestamp, interface, source, destination, source_port, \
+         destination_port, protocols, packet FROM packets WHERE {} ORDER BY id LIMIT ? OFFSET ?",
+        condition
+    ))?;
+    let packets = statement
+        .query_map(params_from_iter(&parameters), stored_packet)?
+        .collect::<rusqlite::Result<_>>()?;
+
+    Ok(StoredPackets {
+        total: total as usize,
+        packets,
+    })
+}
+
+fn stored_packet(row: &Row) -> rusqlite::Result<StoredPacket> {
+    let protocols: String = row.get(8)?;
+    let packet: String = row.get(9)?;
+
+    Ok(StoredPacket {
+        capture: row.get(0)?,
+        id: row.get::<_, i64>(1)? as usize,
+        timestamp: row.get(2)?,
+        interface: row.get(3)?,
+        source: row.get(4)?,
+        destination: row.get(5)?,
+        source_port: row.get(6)?,
+        destination_port: row.get(7)?,
+        protocols: protocols
+            .split(',')
+            .filter(|protocol| !protocol.is_empty())
+            .map(str::to_owned)
+            .collect(),
+        packet: serde_json::from_str(&packet).map_err(|e| {
+            rusqlite::Error::FromSqlConversionFailure(9, rusqlite::types::Type::Text, Box::new(e))
+        })?,
+    })
+}
+
+/// Get the conversations of a capture of a protocol, involving an address, between two positions
+/// in the order they started
+fn query_conversations(
+    connection: &Connection,
+    capture: i64,
+    start: usize,
+    end: usize,
+    protocol: Option<&str>,
+    address: Option<&str>,
+) -> rusqlite::Result<Vec<Value>> {
+    let mut statement = connection.prepare(
+        "SELECT conversation FROM conversations WHERE capture = ?1 \
+         AND (?2 IS NULL OR protocol = ?2 COLLATE NOCASE) \
+         AND (?3 IS NULL OR initiator = ?3 OR responder = ?3) \
+         ORDER BY first_seen, id LIMIT ?4 OFFSET ?5",
+    )?;
+    let conversations = statement.query_map(
+        params![
+            capture,
+            protocol,
+            address,
+            end.saturating_sub(start) as i64,
+            start as i64
+        ],
+        |row| row.get::<_, String>(0),
+    )?;
+
+    conversations
+        .map(|conversation| {
+            serde_json::from_str(&conversation?).map_err(|e| {
+                rusqlite::Error::FromSqlConversionFailure(
+                    0,
+                    rusqlite::types::Type::Text,
+                    Box::new(e),
+                )
+            })
+        })
+        .collect()
+}
+
+/// Get the statistics of a capture, if saved
+fn query_statistics(connection: &Connection, capture: i64) -> rusqlite::Result<Option<Value>> {
+    let statistics: Option<String> = connection
+        .query_row(
+            "SELECT statistics FROM statistics WHERE capture = ?1",
+            params![capture],
+            |row| row.get(0),
+        )
+        .optional()?;
+
+    statistics
+        .map(|statistics| {
+            serde_json::from_str(&statistics).map_err(|e| {
+                rusqlite::Error::FromSqlConversionFailure(
+                    0,
+                    rusqlite::types::Type::Text,
+                    Box::new(e),
+                )
+            })
+        })
+        .transpose()
+}
+
+/// Open a connection reading the store, once the packets written so far are committed, and the
+/// conversations and the statistics of the current capture are saved if needed
+fn read_store(state: &SniffingState, save_capture: bool) -> Result<Connection, SniffingError> {
+    let mut packets = state.packets.lock().unwrap();
+    let packets = &mut *packets;
+    let store = packets
+        .store
+        .as_mut()
+        .ok_or_else(|| SniffingError::PacketStoreFailed("No packet store is open".to_owned()))?;
+
+    let written = if save_capture {
+        store.save(&packets.conversations, &packets.statistics)
+    } else {
+        store.commit()
+    };
+    written
+        .and_then(|_| Connection::open_with_flags(&store.path, OpenFlags::SQLITE_OPEN_READ_ONLY))
+        .map_err(|e| SniffingError::PacketStoreFailed(format!("Cannot read {}: {}", store.path, e)))
+}
+
+fn query_failed(e: rusqlite::Error) -> SniffingError {
+    SniffingError::PacketStoreFailed(format!("Query failed: {}", e))
+}
+
+/// Close a packet store which is no longer needed, saving its current capture and logging any
+/// failure
+pub fn close(
+    mut store: PacketStore,
+    conversations: &ConnectionTracker,
+    statistics: &CaptureStatistics,
+) {
+    match store.end_capture(conversations, statistics) {
+        Ok(()) => info!("[{}] Packet store closed", store.path),
+        Err(e) => error!("[{}] Packet store failed: {}", store.path, e),
+    }
+}
+
+/// Opens a SQLite database as the store of the packets collected from now on, creating it if
+/// needed, and replacing any store already open
+#[tauri::command]
+pub fn open_packet_store(
+    path: String,
+    state: tauri::State<SniffingState>,
+) -> Result<(), SniffingError> {
+    let store = PacketStore::open(&path)
+        .map_err(|e| SniffingError::PacketStoreFailed(format!("Cannot open {}: {}", path, e)))?;
+
+    let mut packets = state.packets.lock().unwrap();
+    let packets = &mut *packets;
+    if let Some(previous) = packets.store.replace(store) {
+        close(previous, &packets.conversations, &packets.statistics);
+    }
+    info!("[{}] Packet store opened", path);
+
+    Ok(())
+}
+
+/// Closes the packet store, saving the conversations and the statistics of the current capture
+#[tauri::command]
+pub fn close_packet_store(state: tauri::State<SniffingState>) -> Result<(), SniffingError> {
+    let mut packets = state.packets.lock().unwrap();
+    let packets = &mut *packets;
+    let store = packets
+        .store
+        .take()
+        .ok_or_else(|| SniffingError::PacketStoreFailed("No packet store is open".to_owned()))?;
+
+    close(store, &packets.conversations, &packets.statistics);
+
+    Ok(())
+}
+
+/// Returns the captures saved in the packet store, the oldest first
+#[tauri::command]
+pub fn get_stored_captures(
+    state: tauri::State<SniffingState>,
+) -> Result<Vec<StoredCapture>, SniffingError> {
+    captures(&read_store(&state, false)?).map_err(query_failed)
+}
+
+/// Returns a slice of the stored packets satisfying a query, with the number of all of them
+///
+/// Bounds refer to the list of the packets satisfying the query, in the order they were collected.
+#[tauri::command]
+pub fn query_stored_packets(
+    start: usize,
+    end: usize,
+    query: PacketQuery,
+    state: tauri::State<SniffingState>,
+) -> Result<StoredPackets, SniffingError> {
+    let packets = query_packets(&read_store(&state, false)?, start, end, &query)
+        .map_err(|e| SniffingError::PacketStoreFailed(format!("Invalid packet query: {}", e)))?;
+    info!(
+        "Received queryStoredPackets request ({}-{}); Len: {}, Total: {}, Query: {:?}",
+        start,
+        end,
+        packets.packets.len(),
+        packets.total,
+        query
+    );
+
+    Ok(packets)
+}
+
+/// Returns a slice of the stored conversations of a capture, optionally keeping only the ones of
+/// a protocol or involving an address, in the order they started
+#[tauri::command]
+pub fn query_stored_conversations(
+    capture: i64,
+    start: usize,
+    end: usize,
+    protocol: Option<String>,
+    address: Option<String>,
+    state: tauri::State<SniffingState>,
+) -> Result<Vec<Value>, SniffingError> {
+    query_conversations(
+        &read_store(&state, true)?,
+        capture,
+        start,
+        end,
+        protocol.as_deref(),
+        address.as_deref(),
+    )
+    .map_err(query_failed)
+}
+
+/// Returns the stored statistics of a capture, none if they were not saved
+#[tauri::command]
+pub fn get_stored_statistics(
+    capture: i64,
+    state: tauri::State<SniffingState>,
+) -> Result<Option<Value>, SniffingError> {
+    query_statistics(&read_store(&state, true)?, capture).map_err(query_failed)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use chrono::{Local, TimeZone};
+    use pnet::packet::tcp::TcpFlags;
+    use rusqlite::{Connection, OpenFlags};
+    use sniffer_parser::serializable_packet::ParsedPacket;
+
+    use super::{
+        captures, query_conversations, query_packets, query_statistics, PacketQuery, PacketStore,
+    };
+    use crate::conversations::ConnectionTracker;
+    use crate::report::get_sender_receiver;
+    use crate::statistics::CaptureStatistics;
+    use crate::test_utils::{tcp_packet, udp_packet};
+
+    const CLIENT: [u8; 4] = [10, 0, 0, 10];
+    const SERVER: [u8; 4] = [192, 0, 2, 80];
+    const DNS_SERVER: [u8; 4] = [10, 0, 0, 53];
+
+    #[test]
+    fn packets_queried_from_store() {
+        let path = std::env::temp_dir().join(format!("wirefish-store-{}.db", std::process::id()));
+        let path = path.to_str().unwrap();
+        let _removed = fs::remove_file(path);
+
+        let mut store = PacketStore::open(path).unwrap();
+        let mut conversations = ConnectionTracker::new();
+        let mut statistics = CaptureStatistics::new();
+        collect(
+            &mut store,
+            &mut conversations,
+            &mut statistics,
+            tcp_packet(0, (CLIENT, 40000), (SERVER, 443), TcpFlags::ACK),
+        );
+        collect(
+            &mut store,
+            &mut conversations,
+            &mut statistics,
+            dns_packet(1, (CLIENT, 50000), (DNS_SERVER, 53)),
+        );
+        collect(
+            &mut store,
+            &mut conversations,
+            &mut statistics,
+            tcp_packet(2, (SERVER, 443), (CLIENT, 40000), TcpFlags::ACK),
+        );
+        store.end_capture(&conversations, &statistics).unwrap();
+        // The packets of a new capture are kept apart
+        collect(
+            &mut store,
+            &mut conversations,
+            &mut statistics,
+            tcp_packet(0, (CLIENT, 40001), (SERVER, 80), TcpFlags::ACK),
+        );
+        store.commit().unwrap();
+
+        let connection =
+            Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY).unwrap();
+        let stored = captures(&connection).unwrap();
+        assert_eq!(stored.len(), 2);
+        assert_eq!(
+            stored
+                .iter()
+                .map(|capture| capture.packets)
+                .collect::<Vec<_>>(),
+            vec![3, 1]
+        );
+        let capture = stored[0].id;
+
+        let query = |start, end, query: PacketQuery| {
+            query_packets(&connection, start, end, &query).unwrap()
+        };
+        let all = query(0, 10, PacketQuery::default());
+        assert_eq!(all.total, 4);
+        assert_eq!(all.packets[1].protocols, vec!["IPv4", "UDP", "DNS"]);
+        assert_eq!(all.packets[1].source_port, Some(50000));
+        assert_eq!(all.packets[1].interface.as_deref(), Some("eth0"));
+
+        // Pages of the packets of a capture
+        let page = query(
+            1,
+            2,
+            PacketQuery {
+                capture: Some(capture),
+                ..Default::default()
+            },
+        );
+        assert_eq!(page.total, 3);
+        assert_eq!(page.packets.len(), 1);
+        assert_eq!((page.packets[0].capture, page.packets[0].id), (capture, 1));
+
+        let by_port = query(
+            0,
+            10,
+            PacketQuery {
+                address: Some("192.0.2.80".to_owned()),
+                port: Some(443),
+                ..Default::default()
+            },
+        );
+        assert_eq!(by_port.total, 2);
+        let by_protocol = query(
+            0,
+            10,
+            PacketQuery {
+                protocol: Some("dns".to_owned()),
+                ..Default::default()
+            },
+        );
+        assert_eq!(by_protocol.total, 1);
+
+        // Search over the decoded fields
+        let search = |text: &str| PacketQuery {
+            search: Some(text.to_owned()),
+            ..Default::default()
+        };
+        assert_eq!(query(0, 10, search("\"example.com\"")).packets[0].id, 1);
+        assert_eq!(query(0, 10, search("missing")).total, 0);
+        assert!(query_packets(&connection, 0, 10, &search("\"unterminated")).is_err());
+
+        let stored_conversations =
+            query_conversations(&connection, capture, 0, 10, Some("tcp"), None).unwrap();
+        assert_eq!(stored_conversations.len(), 1);
+        assert_eq!(stored_conversations[0]["responderPort"], 443);
+        assert!(
+            query_conversations(&connection, capture, 0, 10, None, Some("192.0.2.81"))
+                .unwrap()
+                .is_empty()
+        );
+        let stored_statistics = query_statistics(&connection, capture).unwrap().unwrap();
+        assert_eq!(stored_statistics["packets"], 3);
+        assert!(query_statistics(&connection, stored[1].id)
+            .unwrap()
+            .is_none());
+
+        drop(connection);
+        drop(store);
+        let _removed = fs::remove_file(path);
+    }
+
+    #[test]
+    fn packet_replaced_in_store() {
+        let path =
+            std::env::temp_dir().join(format!("wirefish-store-replaced-{}.db", std::process::id()));
+        let path = path.to_str().unwrap();
+        let _removed = fs::remove_file(path);
+
+        let mut store = PacketStore::open(path).unwrap();
+        let replaced = tcp_packet(0, (CLIENT, 40000), (SERVER, 443), TcpFlags::ACK);
+        store.insert(&replaced, None).unwrap();
+        store
+            .replace(&replaced, &dns_packet(0, (CLIENT, 50000), (DNS_SERVER, 53)))
+            .unwrap();
+        store.commit().unwrap();
+
+        let connection =
+            Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY).unwrap();
+        let search = |text: &str| {
+            let query = PacketQuery {
+                search: Some(text.to_owned()),
+                ..Default::default()
+            };
+            query_packets(&connection, 0, 10, &query).unwrap()
+        };
+        let replacing = search("\"example.com\"");
+        assert_eq!(replacing.total, 1);
+        assert_eq!(replacing.packets[0].protocols, vec!["IPv4", "UDP", "DNS"]);
+        // The fields of the packet replaced are no longer searched
+        assert_eq!(search("TcpPacket").total, 0);
+
+        drop(connection);
+        drop(store);
+        let _removed = fs::remove_file(path);
+    }
+
+    ///////////////////// Utils
+
+    /// Collect a packet as the sniffing process does, received from eth0
+    fn collect(
+        store: &mut PacketStore,
+        conversations: &mut ConnectionTracker,
+        statistics: &mut CaptureStatistics,
+        packet: ParsedPacket,
+    ) {
+        let time = Local.timestamp_opt(1_700_000_000, 0).unwrap();
+        let (endpoints, protocols) = get_sender_receiver(&packet);
+        conversations.update(&packet, 100, time);
+        statistics.update(Some("eth0"), &protocols, &endpoints.ip_source, 100, time);
+        store.insert(&packet, Some("eth0")).unwrap();
+    }
+
+    /// UDP datagram carrying a DNS query for an A record of example.com
+    fn dns_packet(id: usize, source: ([u8; 4], u16), destination: ([u8; 4], u16)) -> ParsedPacket {
+        let mut message = vec![0x00, 0x01, 0x01, 0x00];
+        message.extend([0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
+        message.extend(b"\x07example\x03com\x00");
+        message.extend([0x00, 0x01, 0x00, 0x01]);
+
+        udp_packet(id, source, destination, &message)
+    }
+}
@@ -0,0 +1,490 @@
+//! Writing and reading of .pcapng capture files
+//!
+//! A file is made of a Section Header Block, followed by an Interface Description Block for each
+//! capturing interface and by an Enhanced Packet Block for each frame. A Name Resolution Block can
+//! map the addresses found in the frames to their host names. Blocks are written in little endian
+//! order; timestamps have nanosecond resolution (`if_tsresol` option set to 9).
+//!
+//! Files are read converting them to the .pcap format, with nanosecond timestamps: only the
+//! Enhanced Packet Blocks are kept, and all the interfaces must have the same link type.
+
+use std::io::{self, Write};
+use std::net::IpAddr;
+use std::time::Duration;
+
+use crate::capture_file::{
+    write_global_header, write_record, CaptureRecord, GlobalHeader, LinkTypes,
+};
+
+/// PCAPNG block types
+#[allow(non_snake_case)]
+mod BlockTypes {
+    pub const SECTION_HEADER: u32 = 0x0a0d0d0a;
+    pub const INTERFACE_DESCRIPTION: u32 = 0x00000001;
+    pub const NAME_RESOLUTION: u32 = 0x00000004;
+    pub const ENHANCED_PACKET: u32 = 0x00000006;
+}
+
+/// PCAPNG option codes
+#[allow(non_snake_case)]
+mod OptionCodes {
+    pub const END_OF_OPTIONS: u16 = 0;
+    pub const COMMENT: u16 = 1;
+    pub const SHB_USER_APPLICATION: u16 = 4;
+    pub const IF_NAME: u16 = 2;
+    pub const IF_TSRESOL: u16 = 9;
+}
+
+/// Name Resolution Block record types
+#[allow(non_snake_case)]
+mod RecordTypes {
+    pub const END_OF_RECORDS: u16 = 0;
+    pub const IPV4: u16 = 1;
+    pub const IPV6: u16 = 2;
+}
+
+const BYTE_ORDER_MAGIC: u32 = 0x1a2b3c4d;
+/// Timestamps are expressed in nanoseconds (10^-9 seconds)
+const NANOSECONDS_RESOLUTION: u8 = 9;
+/// Timestamps of the interfaces without the `if_tsresol` option are expressed in microseconds
+const DEFAULT_UNITS_PER_SECOND: u64 = 1_000_000;
+const DEFAULT_SNAP_LENGTH: u32 = 65535;
+
+/// Write a Section Header Block, with unspecified section length
+pub fn write_section_header<W: Write>(writer: &mut W, application: &str) -> io::Result<()> {
+    let mut body = vec![];
+    body.extend(BYTE_ORDER_MAGIC.to_le_bytes());
+    // Version 1.0
+    body.extend([0x01, 0x00, 0x00, 0x00]);
+    body.extend((-1i64).to_le_bytes());
+    push_option(
+        &mut body,
+        OptionCodes::SHB_USER_APPLICATION,
+        application.as_bytes(),
+    );
+    push_option(&mut body, OptionCodes::END_OF_OPTIONS, &[]);
+
+    write_block(writer, BlockTypes::SECTION_HEADER, &body)
+}
+
+/// Write an Interface Description Block, whose frames have nanosecond timestamps
+///
+/// Interfaces are identified by the order of their description blocks, starting from zero.
+pub fn write_interface_description<W: Write>(
+    writer: &mut W,
+    link_type: u16,
+    snap_length: u32,
+    name: Option<&str>,
+) -> io::Result<()> {
+    let mut body = vec![];
+    body.extend(link_type.to_le_bytes());
+    body.extend([0x00, 0x00]);
+    body.extend(snap_length.to_le_bytes());
+    if let Some(name) = name {
+        push_option(&mut body, OptionCodes::IF_NAME, name.as_bytes());
+    }
+    push_option(
+        &mut body,
+        OptionCodes::IF_TSRESOL,
+        &[NANOSECONDS_RESOLUTION],
+    );
+    push_option(&mut body, OptionCodes::END_OF_OPTIONS, &[]);
+
+    write_block(writer, BlockTypes::INTERFACE_DESCRIPTION, &body)
+}
+
+/// Write an Enhanced Packet Block, with an optional comment
+///
+/// The timestamp is the time elapsed since the Unix epoch.
+pub fn write_enhanced_packet<W: Write>(
+    writer: &mut W,
+    interface_id: u32,
+    timestamp: Duration,
+    frame: &[u8],
+    original_length: u32,
+    comment: Option<&str>,
+) -> io::Result<()> {
+    let timestamp = timestamp.as_nanos() as u64;
+
+    let mut body = vec![];
+    body.extend(interface_id.to_le_bytes());
+    body.extend(((timestamp >> 32) as u32).to_le_bytes());
+    body.extend((timestamp as u32).to_le_bytes());
+    body.extend((frame.len() as u32).to_le_bytes());
+    body.extend(original_length.to_le_bytes());
+    body.extend(frame);
+    pad(&mut body);
+    if let Some(comment) = comment {
+        push_option(&mut body, OptionCodes::COMMENT, comment.as_bytes());
+        push_option(&mut body, OptionCodes::END_OF_OPTIONS, &[]);
+    }
+
+    write_block(writer, BlockTypes::ENHANCED_PACKET, &body)
+}
+
+/// Write a Name Resolution Block, associating each address with a host name
+pub fn write_name_resolution<W: Write>(
+    writer: &mut W,
+    names: &[(IpAddr, String)],
+) -> io::Result<()> {
+    let mut body = vec![];
+    for (address, name) in names {
+        let (record_type, mut value) = match address {
+            IpAddr::V4(address) => (RecordTypes::IPV4, address.octets().to_vec()),
+            IpAddr::V6(address) => (RecordTypes::IPV6, address.octets().to_vec()),
+        };
+        value.extend(name.as_bytes());
+        value.push(0);
+
+        // Records share the layout of the options
+        push_option(&mut body, record_type, &value);
+    }
+    push_option(&mut body, RecordTypes::END_OF_RECORDS, &[]);
+
+    write_block(writer, BlockTypes::NAME_RESOLUTION, &body)
+}
+
+/// Check if the data starts with a Section Header Block, as .pcapng files do
+pub fn is_pcapng(data: &[u8]) -> bool {
+    // The block type reads the same in both byte orders
+    data.get(..4) == Some(&BlockTypes::SECTION_HEADER.to_le_bytes()[..])
+}
+
+/// Convert the content of a .pcapng file to the one of a .pcap file, with nanosecond timestamps
+pub fn convert_to_pcap(data: &[u8]) -> io::Result<Vec<u8>> {
+    let mut header: Option<GlobalHeader> = None;
+    let mut records = vec![];
+    // Link type, snap length and timestamp units per second of the interfaces of the section
+    let mut interfaces: Vec<(u32, u32, u64)> = vec![];
+    let mut big_endian = false;
+
+    let mut offset = 0;
+    while offset < data.len() {
+        let block = data.get(offset..offset + 12).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::UnexpectedEof, "Truncated pcapng block")
+        })?;
+        if is_pcapng(block) {
+            big_endian = if block[8..12] == BYTE_ORDER_MAGIC.to_be_bytes() {
+                true
+            } else if block[8..12] == BYTE_ORDER_MAGIC.to_le_bytes() {
+                false
+            } else {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "Unknown pcapng byte-order magic",
+                ));
+            };
+            interfaces.clear();
+        }
+
+        let block_type = read_u32(block, 0, big_endian);
+        let total_length = read_u32(block, 4, big_endian) as usize;
+        if total_length < 12 || total_length & 0b11 != 0 || data.len() - offset < total_length {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Invalid pcapng block length",
+            ));
+        }
+        let body = &data[offset + 8..offset + total_length - 4];
+
+        match block_type {
+            BlockTypes::INTERFACE_DESCRIPTION if body.len() >= 8 => {
+                let link_type = read_u16(body, 0, big_endian) as u32;
+                let snap_length = read_u32(body, 4, big_endian);
+                let units_per_second = read_options(&body[8..], big_endian)
+                    .find(|(code, _)| *code == OptionCodes::IF_TSRESOL)
+                    .and_then(|(_, value)| value.first().copied())
+                    .and_then(units_per_second)
+                    .unwrap_or(DEFAULT_UNITS_PER_SECOND);
+                interfaces.push((link_type, snap_length, units_per_second));
+            }
+            BlockTypes::ENHANCED_PACKET if body.len() >= 20 => {
+                let interface_id = read_u32(body, 0, big_endian) as usize;
+                let &(link_type, snap_length, units_per_second) =
+                    interfaces.get(interface_id).ok_or_else(|| {
+                        io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            "Packet of an undescribed interface",
+                        )
+                    })?;
+                let header = *header.get_or_insert(GlobalHeader {
+                    big_endian: false,
+                    nanoseconds: true,
+                    snap_length,
+                    link_type,
+                });
+                if link_type != header.link_type {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "Interfaces of different link types",
+                    ));
+                }
+
+                let units = ((read_u32(body, 4, big_endian) as u64) << 32)
+                    | read_u32(body, 8, big_endian) as u64;
+                let captured_length = read_u32(body, 12, big_endian);
+                let frame = body.get(20..20 + captured_length as usize).ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::InvalidData, "Truncated packet")
+                })?;
+                let record = CaptureRecord {
+                    offset: 0,
+                    seconds: (units / units_per_second) as u32,
+                    nanoseconds: ((units % units_per_second) as u128 * 1_000_000_000
+                        / units_per_second as u128) as u32,
+                    captured_length,
+                    original_length: read_u32(body, 16, big_endian),
+                };
+                write_record(&mut records, &header, &record, frame)?;
+            }
+            _ => (),
+        }
+
+        offset += total_length;
+    }
+
+    // Captures without packets keep the link type of their first interface
+    let header = header.unwrap_or(GlobalHeader {
+        big_endian: false,
+        nanoseconds: true,
+        snap_length: interfaces
+            .first()
+            .map_or(DEFAULT_SNAP_LENGTH, |interface| interface.1),
+        link_type: interfaces
+            .first()
+            .map_or(LinkTypes::ETHERNET, |interface| interface.0),
+    });
+    let mut pcap = vec![];
+    write_global_header(&mut pcap, &header)?;
+    pcap.extend(records);
+
+    Ok(pcap)
+}
+
+/// Iterate over the options of a block, as codes and values
+fn read_options(options: &[u8], big_endian: bool) -> impl Iterator<Item = (u16, &[u8])> {
+    let mut offset = 0;
+    std::iter::from_fn(move || {
+        let code = read_u16(options.get(offset..offset + 4)?, 0, big_endian);
+        let length = read_u16(options, offset + 2, big_endian) as usize;
+        let value = options.get(offset + 4..offset + 4 + length)?;
+        if code == OptionCodes::END_OF_OPTIONS {
+            return None;
+        }
+
+        offset += 4 + length.next_multiple_of(4);
+        Some((code, value))
+    })
+}
+
+/// Get the timestamp units per second of an `if_tsresol` option: a negative power of 10, or of 2
+/// when the most significant bit is set
+fn units_per_second(resolution: u8) -> Option<u64> {
+    if resolution & 0x80 != 0 {
+        1u64.checked_shl((resolution & 0x7f) as u32)
+    } else {
+        10u64.checked_pow(resolution as u32)
+    }
+}
+
+fn read_u16(data: &[u8], offset: usize, big_endian: bool) -> u16 {
+    let bytes = [data[offset], data[offset + 1]];
+    if big_endian {
+        u16::from_be_bytes(bytes)
+    } else {
+        u16::from_le_bytes(bytes)
+    }
+}
+
+fn read_u32(data: &[u8], offset: usize, big_endian: bool) -> u32 {
+    let bytes = [
+        data[offset],
+        data[offset + 1],
+        data[offset + 2],
+        data[offset + 3],
+    ];
+    if big_endian {
+        u32::from_be_bytes(bytes)
+    } else {
+        u32::from_le_bytes(bytes)
+    }
+}
+
+/// Write a block, enclosing its (padded) body between type and total length fields
+fn write_block<W: Write>(writer: &mut W, block_type: u32, body: &[u8]) -> io::Result<()> {
+    let total_length = (4 + 4 + body.len() + 4) as u32;
+
+    writer.write_all(&block_type.to_le_bytes())?;
+    writer.write_all(&total_length.to_le_bytes())?;
+    writer.write_all(body)?;
+    writer.write_all(&total_length.to_le_bytes())
+}
+
+/// Append an option, padding its value to 32 bits
+fn push_option(body: &mut Vec<u8>, code: u16, value: &[u8]) {
+    body.extend(code.to_le_bytes());
+    body.extend((value.len() as u16).to_le_bytes());
+    body.extend(value);
+    pad(body);
+}
+
+fn pad(body: &mut Vec<u8>) {
+    body.resize(body.len().next_multiple_of(4), 0);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+    use std::time::Duration;
+
+    use super::{
+        convert_to_pcap, is_pcapng, write_enhanced_packet, write_interface_description,
+        write_name_resolution, write_section_header,
+    };
+    use crate::capture_file::{parse_global_header, parse_record_header, PcapHeaderLength};
+
+    #[test]
+    fn section_header_block() {
+        let mut data = vec![];
+        write_section_header(&mut data, "wirefish").unwrap();
+
+        assert_eq!(&data[..4], &[0x0a, 0x0d, 0x0d, 0x0a]);
+        assert_eq!(&data[8..12], &[0x4d, 0x3c, 0x2b, 0x1a]);
+        assert_eq!(data.len(), 44);
+        assert_block_lengths(&data);
+    }
+
+    #[test]
+    fn interface_description_block() {
+        let mut data = vec![];
+        write_interface_description(&mut data, 1, 65535, Some("eth0")).unwrap();
+
+        assert_eq!(u16::from_le_bytes([data[8], data[9]]), 1);
+        assert_eq!(
+            &data[16..24],
+            &[0x02, 0x00, 0x04, 0x00, b'e', b't', b'h', b'0']
+        );
+        assert_eq!(&data[24..29], &[0x09, 0x00, 0x01, 0x00, 0x09]);
+        assert_block_lengths(&data);
+    }
+
+    #[test]
+    fn enhanced_packet_block_with_comment() {
+        let mut data = vec![];
+        let timestamp = Duration::new(0x1_0000_0000, 5);
+        write_enhanced_packet(
+            &mut data,
+            0,
+            timestamp,
+            &[0xde, 0xad, 0xbe],
+            60,
+            Some("TCP"),
+        )
+        .unwrap();
+
+        let nanoseconds = timestamp.as_nanos() as u64;
+        assert_eq!(
+            u32::from_le_bytes(data[12..16].try_into().unwrap()),
+            (nanoseconds >> 32) as u32
+        );
+        assert_eq!(
+            u32::from_le_bytes(data[16..20].try_into().unwrap()),
+            nanoseconds as u32
+        );
+        assert_eq!(u32::from_le_bytes(data[20..24].try_into().unwrap()), 3);
+        assert_eq!(u32::from_le_bytes(data[24..28].try_into().unwrap()), 60);
+        // Frame padded to 32 bits, followed by the comment and the end of options
+        assert_eq!(&data[28..32], &[0xde, 0xad, 0xbe, 0x00]);
+        assert_eq!(
+            &data[32..40],
+            &[0x01, 0x00, 0x03, 0x00, b'T', b'C', b'P', 0x00]
+        );
+        assert_eq!(&data[40..44], &[0x00; 4]);
+        assert_block_lengths(&data);
+    }
+
+    #[test]
+    fn enhanced_packet_block_without_comment() {
+        let mut data = vec![];
+        write_enhanced_packet(&mut data, 0, Duration::ZERO, &[0u8; 4], 4, None).unwrap();
+
+        assert_eq!(data.len(), 4 + 4 + 20 + 4 + 4);
+        assert_block_lengths(&data);
+    }
+
+    #[test]
+    fn conversion_to_pcap() {
+        let mut data = vec![];
+        write_section_header(&mut data, "wirefish").unwrap();
+        write_interface_description(&mut data, 105, 65535, Some("wlan0")).unwrap();
+        write_enhanced_packet(
+            &mut data,
+            0,
+            Duration::new(16, 5),
+            &[0xde, 0xad, 0xbe],
+            60,
+            Some("TCP"),
+        )
+        .unwrap();
+        assert!(is_pcapng(&data));
+
+        let pcap = convert_to_pcap(&data).unwrap();
+        let header = parse_global_header(&pcap).unwrap();
+        assert!(header.nanoseconds);
+        assert_eq!(header.link_type, 105);
+
+        let record = parse_record_header(&pcap, PcapHeaderLength::GLOBAL, &header).unwrap();
+        assert_eq!((record.seconds, record.nanoseconds), (16, 5));
+        assert_eq!(record.original_length, 60);
+        assert_eq!(&pcap[record.offset..record.end()], &[0xde, 0xad, 0xbe]);
+        assert_eq!(record.end(), pcap.len());
+    }
+
+    #[test]
+    fn conversion_of_packet_without_interface() {
+        let mut data = vec![];
+        write_section_header(&mut data, "wirefish").unwrap();
+        write_enhanced_packet(&mut data, 0, Duration::ZERO, &[0u8; 4], 4, None).unwrap();
+
+        assert!(convert_to_pcap(&data).is_err());
+        assert!(!is_pcapng(&[0xd4, 0xc3, 0xb2, 0xa1]));
+    }
+
+    #[test]
+    fn name_resolution_block() {
+        let mut data = vec![];
+        let names = [
+            (IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)), "a.io".to_owned()),
+            (IpAddr::V6(Ipv6Addr::LOCALHOST), "localhost".to_owned()),
+        ];
+        write_name_resolution(&mut data, &names).unwrap();
+
+        assert_eq!(u32::from_le_bytes(data[..4].try_into().unwrap()), 4);
+        // IPv4 record: address and zero-terminated name, padded to 32 bits
+        assert_eq!(&data[8..12], &[0x01, 0x00, 0x09, 0x00]);
+        assert_eq!(&data[12..21], &[10, 0, 0, 1, b'a', b'.', b'i', b'o', 0]);
+        assert_eq!(&data[21..24], &[0x00; 3]);
+        // IPv6 record
+        assert_eq!(&data[24..28], &[0x02, 0x00, 0x1a, 0x00]);
+        assert_eq!(&data[28..44], &Ipv6Addr::LOCALHOST.octets());
+        assert_eq!(&data[44..54], b"localhost\0");
+        // End of records
+        assert_eq!(&data[56..60], &[0x00; 4]);
+        assert_eq!(data.len(), 64);
+        assert_block_lengths(&data);
+    }
+
+    ///////////////////// Utils
+
+    fn assert_block_lengths(block: &[u8]) {
+        let length = block.len();
+        assert_eq!(length % 4, 0);
+        assert_eq!(
+            u32::from_le_bytes(block[4..8].try_into().unwrap()) as usize,
+            length
+        );
+        assert_eq!(
+            u32::from_le_bytes(block[length - 4..].try_into().unwrap()) as usize,
+            length
+        );
+    }
+}
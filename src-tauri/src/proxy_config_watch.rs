@@ -0,0 +1,458 @@
+//! Detection of the proxy auto-configuration files fetched from unexpected sources
+//!
+//! Browsers configured to auto-detect the proxy (WPAD) look up a `wpad` host by DHCP, DNS, LLMNR
+//! or NetBIOS and fetch its `/wpad.dat` file, a PAC script choosing the proxy of each URL. Any host
+//! answering the lookup on the local network can so redirect the web traffic through a proxy of
+//! its own (WPAD spoofing).
+//!
+//! The PAC files served over HTTP, recognized by their media type, or by their path and their
+//! `FindProxyForURL` function, are tracked along with the server and the client of each fetch. A
+//! security alert is raised when a client is served a PAC file by a source which is not trusted:
+//! - if trusted sources (host names or addresses of the servers) are set, any other source
+//! - otherwise, the WPAD servers, and the servers replacing the one a host name was first served
+//!   by, as a replayed or spoofed configuration would be
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::net::IpAddr;
+
+use chrono::{DateTime, Local};
+use serde::Serialize;
+use sniffer_parser::serializable_packet::application::HttpContentType;
+use sniffer_parser::serializable_packet::{PacketRelation, ParsedPacket, SerializablePacket};
+use tracing::{info, warn};
+
+use crate::arp_watch::{SecurityAlert, ALERTS_SIZE};
+use crate::statistics::Timestamp;
+use crate::SniffingState;
+
+/// Media types of the PAC files
+const PAC_MEDIA_TYPES: [&str; 2] = [
+    "application/x-ns-proxy-autoconfig",
+    "application/x-javascript-config",
+];
+
+/// Function every PAC file defines
+const PAC_FUNCTION: &str = "FindProxyForURL";
+
+/// Requests waiting for their response
+const MAX_PENDING_REQUESTS: usize = 1024;
+
+/// PAC file served to a client, as returned to the frontend
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ProxyConfigFetch {
+    pub time: Timestamp,
+    /// Response carrying the PAC file
+    pub packet_id: usize,
+    pub request_id: Option<usize>,
+    pub client: IpAddr,
+    pub server: IpAddr,
+    /// Host of the request, or address of the server without a Host header
+    pub host: String,
+    pub uri: String,
+    /// Whether the file was looked up by WPAD
+    pub wpad: bool,
+    pub trusted: bool,
+}
+
+/// Host and URI of a request
+#[derive(Debug, Clone)]
+struct PendingRequest {
+    host: Option<String>,
+    uri: String,
+}
+
+/// Tracker of the PAC files of the collected packets
+#[derive(Debug, Default)]
+pub struct ProxyConfigWatch {
+    /// Host names and addresses of the servers trusted to serve PAC files, lowercase
+    trusted_sources: Vec<String>,
+    fetches: Vec<ProxyConfigFetch>,
+    /// Server first seen serving the PAC files of each host name
+    servers: HashMap<String, IpAddr>,
+    /// Client, server and host of the alerts raised, to raise them once
+    alerted: HashSet<(IpAddr, IpAddr, String)>,
+    requests: HashMap<usize, PendingRequest>,
+    /// Order of the pending requests, to forget the oldest ones
+    request_order: VecDeque<usize>,
+    /// Last alerts raised, the oldest first
+    alerts: VecDeque<SecurityAlert>,
+}
+
+impl ProxyConfigWatch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn trusted_sources(&self) -> Vec<String> {
+        self.trusted_sources.clone()
+    }
+
+    /// Replace the trusted sources, for the PAC files served from now on
+    pub fn set_trusted_sources(&mut self, sources: Vec<String>) {
+        self.trusted_sources = sources
+            .iter()
+            .map(|source| source.trim().to_ascii_lowercase())
+            .filter(|source| !source.is_empty())
+            .collect();
+    }
+
+    /// Track a packet, if it carries a PAC file, given the time it was received, returning the
+    /// alerts raised
+    pub fn update(&mut self, packet: &ParsedPacket, time: DateTime<Local>) -> Vec<SecurityAlert> {
+        let response = match packet.get_application_layer_packet() {
+            Some(SerializablePacket::HttpRequestPacket(request)) => {
+                self.requests.insert(
+                    packet.get_id(),
+                    PendingRequest {
+                        host: header(&request.headers, "Host").map(str::to_ascii_lowercase),
+                        uri: request.path.clone(),
+                    },
+                );
+                self.request_order.push_back(packet.get_id());
+                if self.request_order.len() > MAX_PENDING_REQUESTS {
+                    if let Some(oldest) = self.request_order.pop_front() {
+                        self.requests.remove(&oldest);
+                    }
+                }
+                return vec![];
+            }
+            Some(SerializablePacket::HttpResponsePacket(response)) => response,
+            _ => return vec![],
+        };
+        // The response goes from the server to the client
+        let (server, client) = match packet.get_network_layer_packet() {
+            Some(SerializablePacket::Ipv4Packet(ipv4)) => {
+                (IpAddr::V4(ipv4.source), IpAddr::V4(ipv4.destination))
+            }
+            Some(SerializablePacket::Ipv6Packet(ipv6)) => {
+                (IpAddr::V6(ipv6.source), IpAddr::V6(ipv6.destination))
+            }
+            _ => return vec![],
+        };
+
+        let request_id = packet
+            .get_references()
+            .iter()
+            .find(|reference| reference.relation == PacketRelation::Request)
+            .map(|reference| reference.id);
+        let request = request_id.and_then(|id| self.requests.remove(&id));
+        let uri = request
+            .as_ref()
+            .map(|request| request.uri.clone())
+            .unwrap_or_default();
+        let path = uri.split(['?', '#']).next().unwrap_or_default();
+
+        let media_type = header(&response.headers, "Content-Type")
+            .and_then(|value| value.split(';').next())
+            .map(|media_type| media_type.trim().to_ascii_lowercase());
+        let is_pac = media_type.is_some_and(|media_type| PAC_MEDIA_TYPES.contains(&&*media_type))
+            || (is_pac_path(path) && defines_pac_function(&response.payload));
+        if !(200..300).contains(&response.code) || !is_pac {
+            return vec![];
+        }
+
+        let host = request
+            .and_then(|request| request.host)
+            .map(|host| strip_port(&host).to_owned())
+            .unwrap_or_else(|| server.to_string());
+        let wpad = host == "wpad" || host.starts_with("wpad.") || path == "/wpad.dat";
+
+        let first_server = *self.servers.entry(host.clone()).or_insert(server);
+        let trusted = if self.trusted_sources.is_empty() {
+            !wpad && first_server == server
+        } else {
+            self.trusted_sources
+                .iter()
+                .any(|source| *source == host || *source == server.to_string())
+        };
+
+        let time = time.timestamp_millis();
+        self.fetches.push(ProxyConfigFetch {
+            time,
+            packet_id: packet.get_id(),
+            request_id,
+            client,
+            server,
+            host: host.clone(),
+            uri: uri.clone(),
+            wpad,
+            trusted,
+        });
+
+        // Raised once per client, server and host
+        if trusted || !self.alerted.insert((client, server, host.clone())) {
+            return vec![];
+        }
+        let alert = SecurityAlert::UnexpectedProxyConfiguration {
+            time,
+            client,
+            server,
+            host,
+            uri,
+            wpad,
+        };
+        warn!("Security alert: {:?}", alert);
+        if self.alerts.len() >= ALERTS_SIZE {
+            self.alerts.pop_front();
+        }
+        self.alerts.push_back(alert.clone());
+
+        vec![alert]
+    }
+
+    /// Get the PAC files served, in the order they were served
+    pub fn fetches(&self) -> Vec<ProxyConfigFetch> {
+        self.fetches.clone()
+    }
+
+    /// Get the last alerts raised, the oldest first
+    pub fn alerts(&self) -> Vec<SecurityAlert> {
+        self.alerts.iter().cloned().collect()
+    }
+
+    /// Forget the PAC files served, keeping the trusted sources
+    pub fn clear(&mut self) {
+        *self = Self {
+            trusted_sources: std::mem::take(&mut self.trusted_sources),
+            ..Self::default()
+        };
+    }
+}
+
+/// Get the value of a header, given its name
+fn header<'a>(headers: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|(header, _)| header.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.as_str())
+}
+
+/// Remove the port from a Host header, keeping the brackets of IPv6 addresses
+fn strip_port(host: &str) -> &str {
+    match host.rsplit_once(':') {
+        Some((name, port))
+            if !port.is_empty()
+                && port.bytes().all(|byte| byte.is_ascii_digit())
+                && (!name.contains(':') || name.ends_with(']')) =>
+        {
+            name
+        }
+        _ => host,
+    }
+}
+
+/// Whether the path of a request is the one of a PAC file
+fn is_pac_path(path: &str) -> bool {
+    let path = path.to_ascii_lowercase();
+    path.ends_with(".pac") || path.ends_with("/wpad.dat") || path.ends_with("/proxy.dat")
+}
+
+/// Whether a body defines the function of the PAC files
+fn defines_pac_function(payload: &HttpContentType) -> bool {
+    match payload {
+        HttpContentType::TextCorrectlyDecoded(text)
+        | HttpContentType::TextMalformedDecoded(text)
+        | HttpContentType::TextDefaultDecoded(text) => text.contains(PAC_FUNCTION),
+        HttpContentType::Unknown(bytes) => bytes
+            .windows(PAC_FUNCTION.len())
+            .any(|window| window == PAC_FUNCTION.as_bytes()),
+        _ => false,
+    }
+}
+
+/// Sets the host names and addresses of the servers trusted to serve PAC files, an empty list
+/// trusting the servers first seen serving the PAC files of a host name, other than WPAD ones
+#[tauri::command]
+pub fn set_trusted_proxy_config_sources(sources: Vec<String>, state: tauri::State<SniffingState>) {
+    info!("Trusted proxy configuration sources set: {:?}", sources);
+
+    state
+        .packets
+        .lock()
+        .unwrap()
+        .proxy_config_watch
+        .set_trusted_sources(sources);
+}
+
+/// Returns the host names and addresses of the servers trusted to serve PAC files
+#[tauri::command]
+pub fn get_trusted_proxy_config_sources(state: tauri::State<SniffingState>) -> Vec<String> {
+    state
+        .packets
+        .lock()
+        .unwrap()
+        .proxy_config_watch
+        .trusted_sources()
+}
+
+/// Returns the PAC files served in the collected packets, in the order they were served
+#[tauri::command]
+pub fn get_proxy_config_fetches(state: tauri::State<SniffingState>) -> Vec<ProxyConfigFetch> {
+    state.packets.lock().unwrap().proxy_config_watch.fetches()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr};
+
+    use pnet::packet::ipv4::Ipv4Packet;
+    use sniffer_parser::serializable_packet::application::{
+        HttpContentType, SerializableHttpRequestPacket, SerializableHttpResponsePacket,
+    };
+    use sniffer_parser::serializable_packet::network::SerializableIpv4Packet;
+    use sniffer_parser::serializable_packet::{PacketRelation, ParsedPacket, SerializablePacket};
+
+    use super::{strip_port, ProxyConfigWatch};
+    use crate::arp_watch::SecurityAlert;
+    use crate::test_utils::at;
+
+    const CLIENT: [u8; 4] = [10, 0, 0, 10];
+    const PROXY: [u8; 4] = [10, 0, 0, 2];
+    const ATTACKER: [u8; 4] = [10, 0, 0, 66];
+    const PAC: &str = "function FindProxyForURL(url, host) { return \"PROXY 10.0.0.2:3128\"; }";
+
+    #[test]
+    fn wpad_files_are_unexpected() {
+        let mut watch = ProxyConfigWatch::new();
+
+        assert!(watch
+            .update(&request(1, "/proxy.pac", "proxy.corp.example:8080"), at(0))
+            .is_empty());
+        assert!(watch
+            .update(
+                &response(2, 1, PROXY, "application/x-ns-proxy-autoconfig"),
+                at(10)
+            )
+            .is_empty());
+        // Served by WPAD, recognized by its path and its function
+        watch.update(&request(3, "/wpad.dat", "wpad"), at(1000));
+        let alerts = watch.update(&response(4, 3, ATTACKER, "text/plain"), at(1010));
+        assert_eq!(
+            alerts,
+            vec![SecurityAlert::UnexpectedProxyConfiguration {
+                time: at(1010).timestamp_millis(),
+                client: IpAddr::V4(Ipv4Addr::from(CLIENT)),
+                server: IpAddr::V4(Ipv4Addr::from(ATTACKER)),
+                host: "wpad".to_owned(),
+                uri: "/wpad.dat".to_owned(),
+                wpad: true,
+            }]
+        );
+        // Raised once per client, server and host
+        watch.update(&request(5, "/wpad.dat", "wpad"), at(2000));
+        assert!(watch
+            .update(&response(6, 5, ATTACKER, "text/plain"), at(2010))
+            .is_empty());
+        // Other files are not PAC files
+        watch.update(&request(7, "/index.html", "wpad"), at(3000));
+        assert!(watch
+            .update(&response(8, 7, ATTACKER, "text/html"), at(3010))
+            .is_empty());
+
+        let fetches = watch.fetches();
+        assert_eq!(fetches.len(), 3);
+        assert_eq!(fetches[0].host, "proxy.corp.example");
+        assert_eq!(fetches[0].request_id, Some(1));
+        assert!(fetches[0].trusted);
+        assert!(!fetches[0].wpad);
+        assert!(fetches[1].wpad);
+        assert!(!fetches[1].trusted);
+        assert_eq!(watch.alerts(), alerts);
+    }
+
+    #[test]
+    fn replaced_and_untrusted_sources() {
+        let mut watch = ProxyConfigWatch::new();
+
+        watch.update(&request(1, "/proxy.pac", "proxy.corp.example"), at(0));
+        watch.update(
+            &response(2, 1, PROXY, "application/x-ns-proxy-autoconfig"),
+            at(10),
+        );
+        // Served by another server than the first one
+        watch.update(&request(3, "/proxy.pac", "proxy.corp.example"), at(1000));
+        let alerts = watch.update(
+            &response(4, 3, ATTACKER, "application/x-ns-proxy-autoconfig"),
+            at(1010),
+        );
+        assert_eq!(alerts.len(), 1);
+
+        watch.set_trusted_sources(vec![" WPAD ".to_owned(), "".to_owned()]);
+        watch.clear();
+        assert_eq!(watch.trusted_sources(), vec!["wpad".to_owned()]);
+        watch.update(&request(1, "/wpad.dat", "wpad"), at(0));
+        assert!(watch
+            .update(&response(2, 1, PROXY, "text/plain"), at(10))
+            .is_empty());
+        watch.update(&request(3, "/proxy.pac", "proxy.corp.example"), at(1000));
+        assert_eq!(
+            watch
+                .update(
+                    &response(4, 3, PROXY, "application/x-ns-proxy-autoconfig"),
+                    at(1010)
+                )
+                .len(),
+            1
+        );
+        assert!(watch.fetches()[0].trusted);
+    }
+
+    #[test]
+    fn host_ports() {
+        assert_eq!(strip_port("wpad:8080"), "wpad");
+        assert_eq!(strip_port("wpad"), "wpad");
+        assert_eq!(strip_port("[fe80::1]:80"), "[fe80::1]");
+        assert_eq!(strip_port("[fe80::1]"), "[fe80::1]");
+    }
+
+    ///////////////////// Utils
+
+    fn ipv4(source: [u8; 4], destination: [u8; 4]) -> SerializablePacket {
+        let mut header = vec![
+            0x45, 0x00, 0x00, 0x14, 0x00, 0x00, 0x00, 0x00, 0x40, 0x06, 0x00, 0x00,
+        ];
+        header.extend(source);
+        header.extend(destination);
+
+        SerializablePacket::Ipv4Packet(SerializableIpv4Packet::from(
+            &Ipv4Packet::new(&header).unwrap(),
+        ))
+    }
+
+    fn request(id: usize, path: &str, host: &str) -> ParsedPacket {
+        let mut packet = ParsedPacket::new(id);
+        packet.set_network_layer_packet(Some(ipv4(CLIENT, PROXY)));
+        packet.set_application_layer_packet(Some(SerializablePacket::HttpRequestPacket(
+            SerializableHttpRequestPacket {
+                method: "GET".to_owned(),
+                path: path.to_owned(),
+                version: 1,
+                headers: vec![("Host".to_owned(), host.to_owned())],
+                trailers: 0,
+                payload: HttpContentType::None,
+                registry: None,
+                quirks: vec![],
+            },
+        )));
+        packet
+    }
+
+    fn response(id: usize, request_id: usize, server: [u8; 4], content_type: &str) -> ParsedPacket {
+        let mut packet = ParsedPacket::new(id);
+        packet.add_reference(request_id, PacketRelation::Request);
+        packet.set_network_layer_packet(Some(ipv4(server, CLIENT)));
+        packet.set_application_layer_packet(Some(SerializablePacket::HttpResponsePacket(
+            SerializableHttpResponsePacket {
+                version: 1,
+                code: 200,
+                reason: "OK".to_owned(),
+                headers: vec![("Content-Type".to_owned(), content_type.to_owned())],
+                trailers: 0,
+                payload: HttpContentType::TextCorrectlyDecoded(PAC.to_owned()),
+                quirks: vec![],
+            },
+        )));
+        packet
+    }
+}
@@ -0,0 +1,312 @@
+//! Indicators of the reflection attacks involving the hosts of the network
+//!
+//! A reflection attack floods a victim with the responses of servers (reflectors) to requests sent
+//! with the spoofed address of the victim, over UDP protocols answering small requests with large
+//! responses: DNS (ANY queries), SSDP (M-SEARCH requests) and memcached (stats and get commands).
+//!
+//! The responses of these protocols received by each host are counted, distinguishing the ones it
+//! never requested: a response is unsolicited when its destination sent no request to the
+//! reflector, nor to a multicast group, as the SSDP searches are. A security alert is raised when a
+//! host receives many large unsolicited responses of a protocol from several reflectors, and the
+//! hosts receiving unsolicited responses are summarized with their likely involvement in an attack.
+
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::net::IpAddr;
+
+use chrono::{DateTime, Local};
+use serde::Serialize;
+use sniffer_parser::serializable_packet::{ParsedPacket, SerializablePacket};
+use tracing::warn;
+
+use crate::arp_watch::{SecurityAlert, ALERTS_SIZE};
+use crate::statistics::{Counters, Timestamp};
+use crate::SniffingState;
+
+/// Length of the UDP header, not counted in the sizes of the responses
+const UDP_HEADER_LENGTH: usize = 8;
+
+/// UDP ports of the protocols abused for reflection, with the size of a large response
+const REFLECTION_PROTOCOLS: [(u16, &str, usize); 3] = [
+    (53, "DNS", 512),
+    (1900, "SSDP", 256),
+    (11211, "memcached", 1024),
+];
+
+/// Large unsolicited responses received by a host, raising an alert
+const ATTACK_MIN_RESPONSES: usize = 50;
+
+/// Reflectors of the large unsolicited responses received by a host, raising an alert
+const ATTACK_MIN_REFLECTORS: usize = 5;
+
+/// Responses of a protocol received by a host, as returned to the frontend
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ReflectionTarget {
+    pub host: IpAddr,
+    pub protocol: String,
+    /// Responses to the requests of the host
+    pub solicited: Counters,
+    /// Responses the host never requested
+    pub unsolicited: Counters,
+    /// Unsolicited responses of at least the size of a large response of the protocol
+    pub large_unsolicited: usize,
+    /// Servers sending the unsolicited responses
+    pub reflectors: usize,
+    /// Whether the host is likely the victim of a reflection attack
+    pub likely_victim: bool,
+    pub first_seen: Timestamp,
+    pub last_seen: Timestamp,
+}
+
+/// Responses received by a host, with the reflectors of the unsolicited ones
+#[derive(Debug)]
+struct TargetResponses {
+    target: ReflectionTarget,
+    reflectors: HashSet<IpAddr>,
+}
+
+/// Tracker of the responses of the reflection protocols of the collected packets
+#[derive(Debug, Default)]
+pub struct ReflectionWatch {
+    /// Client, server and port of the requests sent
+    requests: HashSet<(IpAddr, IpAddr, u16)>,
+    /// Client and port of the requests sent to multicast groups
+    multicast_requests: HashSet<(IpAddr, u16)>,
+    targets: HashMap<(IpAddr, u16), TargetResponses>,
+    /// Last alerts raised, the oldest first
+    alerts: VecDeque<SecurityAlert>,
+}
+
+impl ReflectionWatch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Count a packet, if it is a request or a response of a reflection protocol, given the time
+    /// it was received, returning the alerts raised
+    pub fn update(&mut self, packet: &ParsedPacket, time: DateTime<Local>) -> Vec<SecurityAlert> {
+        let (source, destination) = match packet.get_network_layer_packet() {
+            Some(SerializablePacket::Ipv4Packet(ipv4)) => {
+                (IpAddr::V4(ipv4.source), IpAddr::V4(ipv4.destination))
+            }
+            Some(SerializablePacket::Ipv6Packet(ipv6)) => {
+                (IpAddr::V6(ipv6.source), IpAddr::V6(ipv6.destination))
+            }
+            _ => return vec![],
+        };
+        let udp = match packet.get_transport_layer_packet() {
+            Some(SerializablePacket::UdpPacket(udp)) => udp,
+            _ => return vec![],
+        };
+
+        if let Some(&(port, _, _)) = REFLECTION_PROTOCOLS
+            .iter()
+            .find(|(port, _, _)| *port == udp.destination)
+        {
+            if destination.is_multicast() {
+                self.multicast_requests.insert((source, port));
+            } else {
+                self.requests.insert((source, destination, port));
+            }
+            return vec![];
+        }
+        let (port, protocol, large_response) = match REFLECTION_PROTOCOLS
+            .iter()
+            .find(|(port, _, _)| *port == udp.source)
+        {
+            Some(&reflection_protocol) if !destination.is_multicast() => reflection_protocol,
+            _ => return vec![],
+        };
+
+        let time = time.timestamp_millis();
+        let bytes = (udp.length as usize).saturating_sub(UDP_HEADER_LENGTH);
+        let solicited = self.requests.contains(&(destination, source, port))
+            || self.multicast_requests.contains(&(destination, port));
+
+        let responses =
+            self.targets
+                .entry((destination, port))
+                .or_insert_with(|| TargetResponses {
+                    target: ReflectionTarget {
+                        host: destination,
+                        protocol: protocol.to_owned(),
+                        solicited: Counters::default(),
+                        unsolicited: Counters::default(),
+                        large_unsolicited: 0,
+                        reflectors: 0,
+                        likely_victim: false,
+                        first_seen: time,
+                        last_seen: time,
+                    },
+                    reflectors: HashSet::new(),
+                });
+        let target = &mut responses.target;
+        target.first_seen = target.first_seen.min(time);
+        target.last_seen = target.last_seen.max(time);
+
+        if solicited {
+            target.solicited.add(bytes);
+            return vec![];
+        }
+        target.unsolicited.add(bytes);
+        if bytes < large_response {
+            return vec![];
+        }
+        target.large_unsolicited += 1;
+        responses.reflectors.insert(source);
+        target.reflectors = responses.reflectors.len();
+
+        // Raised once per host and protocol, when both counts are first reached
+        if target.likely_victim
+            || target.large_unsolicited < ATTACK_MIN_RESPONSES
+            || target.reflectors < ATTACK_MIN_REFLECTORS
+        {
+            return vec![];
+        }
+        target.likely_victim = true;
+
+        let alert = SecurityAlert::ReflectionAttack {
+            time,
+            victim: destination,
+            protocol: protocol.to_owned(),
+            reflectors: target.reflectors,
+            responses: target.large_unsolicited,
+            bytes: target.unsolicited.bytes,
+        };
+        warn!("Security alert: {:?}", alert);
+        if self.alerts.len() >= ALERTS_SIZE {
+            self.alerts.pop_front();
+        }
+        self.alerts.push_back(alert.clone());
+
+        vec![alert]
+    }
+
+    /// Get the hosts receiving unsolicited responses, the likely victims first, then the ones
+    /// receiving the most bytes of them
+    pub fn targets(&self) -> Vec<ReflectionTarget> {
+        let mut targets: Vec<ReflectionTarget> = self
+            .targets
+            .values()
+            .filter(|responses| responses.target.unsolicited.packets > 0)
+            .map(|responses| responses.target.clone())
+            .collect();
+        targets.sort_by_key(|target| {
+            (
+                Reverse(target.likely_victim),
+                Reverse(target.unsolicited.bytes),
+                target.host,
+            )
+        });
+
+        targets
+    }
+
+    /// Get the last alerts raised, the oldest first
+    pub fn alerts(&self) -> Vec<SecurityAlert> {
+        self.alerts.iter().cloned().collect()
+    }
+
+    pub fn clear(&mut self) {
+        self.requests.clear();
+        self.multicast_requests.clear();
+        self.targets.clear();
+        self.alerts.clear();
+    }
+}
+
+/// Returns the hosts receiving unsolicited DNS, SSDP or memcached responses, with their likely
+/// involvement in a reflection attack
+#[tauri::command]
+pub fn get_reflection_targets(state: tauri::State<SniffingState>) -> Vec<ReflectionTarget> {
+    state.packets.lock().unwrap().reflection_watch.targets()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr};
+
+    use sniffer_parser::serializable_packet::ParsedPacket;
+
+    use super::{ReflectionWatch, ATTACK_MIN_REFLECTORS, ATTACK_MIN_RESPONSES};
+    use crate::arp_watch::SecurityAlert;
+    use crate::test_utils::{at, udp_packet};
+
+    const HOST: [u8; 4] = [10, 0, 0, 10];
+    const RESOLVER: [u8; 4] = [10, 0, 0, 1];
+
+    #[test]
+    fn solicited_responses() {
+        let mut watch = ReflectionWatch::new();
+
+        // SSDP search, answered by the devices of the network
+        watch.update(
+            &datagram((HOST, 40000), ([239, 255, 255, 250], 1900), 90),
+            at(0),
+        );
+        for device in 20..30 {
+            watch.update(
+                &datagram(([10, 0, 0, device], 1900), (HOST, 40000), 300),
+                at(10),
+            );
+        }
+        // DNS query and its response, then a response from a resolver never queried
+        watch.update(&datagram((HOST, 40001), (RESOLVER, 53), 40), at(20));
+        watch.update(&datagram((RESOLVER, 53), (HOST, 40001), 600), at(30));
+        watch.update(&datagram(([8, 8, 8, 8], 53), (HOST, 40001), 600), at(40));
+
+        let targets = watch.targets();
+        assert_eq!(targets.len(), 1);
+        assert_eq!(targets[0].protocol, "DNS");
+        assert_eq!(targets[0].solicited.bytes, 600 - 8);
+        assert_eq!(targets[0].unsolicited.packets, 1);
+        assert_eq!(targets[0].large_unsolicited, 1);
+        assert_eq!(targets[0].reflectors, 1);
+        assert!(!targets[0].likely_victim);
+
+        watch.clear();
+        assert!(watch.targets().is_empty());
+    }
+
+    #[test]
+    fn reflection_attack() {
+        let mut watch = ReflectionWatch::new();
+
+        let mut alerts = vec![];
+        for i in 0..2 * ATTACK_MIN_RESPONSES {
+            let reflector = [192, 0, 2, (i % ATTACK_MIN_REFLECTORS) as u8];
+            alerts.extend(watch.update(
+                &datagram((reflector, 11211), (HOST, 80), 1400),
+                at(i as i64),
+            ));
+            // Small responses are not counted as large ones
+            watch.update(&datagram((reflector, 11211), (HOST, 80), 100), at(i as i64));
+        }
+
+        assert_eq!(
+            alerts,
+            vec![SecurityAlert::ReflectionAttack {
+                time: at(ATTACK_MIN_RESPONSES as i64 - 1).timestamp_millis(),
+                victim: IpAddr::V4(Ipv4Addr::from(HOST)),
+                protocol: "memcached".to_owned(),
+                reflectors: ATTACK_MIN_REFLECTORS,
+                responses: ATTACK_MIN_RESPONSES,
+                bytes: ATTACK_MIN_RESPONSES * 1392 + (ATTACK_MIN_RESPONSES - 1) * 92,
+            }]
+        );
+        assert_eq!(watch.alerts(), alerts);
+
+        let targets = watch.targets();
+        assert!(targets[0].likely_victim);
+        assert_eq!(targets[0].large_unsolicited, 2 * ATTACK_MIN_RESPONSES);
+        assert_eq!(targets[0].unsolicited.packets, 4 * ATTACK_MIN_RESPONSES);
+    }
+
+    ///////////////////// Utils
+
+    /// UDP datagram of the given length, header included, carrying zeros
+    fn datagram(source: ([u8; 4], u16), destination: ([u8; 4], u16), length: u16) -> ParsedPacket {
+        udp_packet(0, source, destination, &vec![0x00; length as usize - 8])
+    }
+}
@@ -0,0 +1,217 @@
+//! Analytics of the Docker registry and HTTP proxy traffic
+//!
+//! HTTP requests to the registry (distribution v2) API and `CONNECT` requests to proxies are
+//! matched with their responses, flowing in the opposite direction of the same connection, in
+//! order to aggregate:
+//! - the manifest and blob (layer) pulls of each image, with the bytes downloaded
+//! - the tunnels requested through proxies, for each target host
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+use serde::Serialize;
+use sniffer_parser::serializable_packet::application::{
+    DockerRegistryEndpoint, DockerRegistryRequest, SerializableHttpResponsePacket,
+};
+use sniffer_parser::serializable_packet::util::{
+    get_dest_ip, get_dest_port, get_source_ip, get_source_port,
+};
+use sniffer_parser::serializable_packet::{ParsedPacket, SerializablePacket};
+
+use crate::SniffingState;
+
+/// Source and destination (IP, port) of an HTTP connection
+type Connection = ((String, String), (String, String));
+
+/// Request waiting for its response
+#[derive(Debug)]
+enum PendingRequest {
+    Registry(String, DockerRegistryRequest),
+    Connect(String),
+}
+
+/// Downloads of a single image from Docker registries
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ImageDownloads {
+    pub image: String,
+    pub manifest_pulls: usize,
+    pub blob_pulls: usize,
+    pub redirected_blob_pulls: usize,
+    pub bytes: u64,
+    /// Size of each downloaded layer, by digest
+    pub layers: BTreeMap<String, u64>,
+}
+
+/// Tunnels requested through HTTP proxies towards a single target
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ProxyTunnels {
+    pub target: String,
+    pub requests: usize,
+    pub established: usize,
+    pub refused: usize,
+}
+
+/// Registry and proxy analytics, as returned to the frontend
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RegistryReport {
+    pub images: Vec<ImageDownloads>,
+    pub tunnels: Vec<ProxyTunnels>,
+}
+
+/// Registry and proxy data collected from the HTTP packets
+#[derive(Debug, Default)]
+pub struct RegistryAnalytics {
+    pending: HashMap<Connection, VecDeque<PendingRequest>>,
+    images: HashMap<String, ImageDownloads>,
+    tunnels: HashMap<String, ProxyTunnels>,
+}
+
+impl RegistryAnalytics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Update the analytics with a parsed packet, ignoring the ones other than HTTP
+    pub fn update(&mut self, packet: &ParsedPacket) {
+        let connection = match connection(packet) {
+            Some(connection) => connection,
+            None => return,
+        };
+
+        match packet.get_application_layer_packet() {
+            Some(SerializablePacket::HttpRequestPacket(request)) => {
+                let pending = if request.method == "CONNECT" {
+                    self.tunnels
+                        .entry(request.path.clone())
+                        .or_insert_with(|| ProxyTunnels {
+                            target: request.path.clone(),
+                            ..Default::default()
+                        })
+                        .requests += 1;
+
+                    PendingRequest::Connect(request.path.clone())
+                } else if let Some(registry) = &request.registry {
+                    PendingRequest::Registry(request.method.clone(), registry.clone())
+                } else {
+                    return;
+                };
+
+                self.pending
+                    .entry(connection)
+                    .or_default()
+                    .push_back(pending);
+            }
+            Some(SerializablePacket::HttpResponsePacket(response)) => {
+                let (source, destination) = connection;
+                let pending = self
+                    .pending
+                    .get_mut(&(destination, source))
+                    .and_then(|requests| requests.pop_front());
+
+                match pending {
+                    Some(PendingRequest::Registry(method, request)) => {
+                        self.add_registry_response(&method, request, response)
+                    }
+                    Some(PendingRequest::Connect(target)) => {
+                        if let Some(tunnels) = self.tunnels.get_mut(&target) {
+                            match response.code {
+                                200..=299 => tunnels.established += 1,
+                                _ => tunnels.refused += 1,
+                            }
+                        }
+                    }
+                    None => (),
+                }
+            }
+            _ => (),
+        }
+    }
+
+    /// Get the downloads of each image, sorted by volume, and the tunnels of each target
+    pub fn report(&self) -> RegistryReport {
+        let mut images: Vec<ImageDownloads> = self.images.values().cloned().collect();
+        images.sort_by(|a, b| b.bytes.cmp(&a.bytes).then_with(|| a.image.cmp(&b.image)));
+
+        let mut tunnels: Vec<ProxyTunnels> = self.tunnels.values().cloned().collect();
+        tunnels.sort_by(|a, b| {
+            b.requests
+                .cmp(&a.requests)
+                .then_with(|| a.target.cmp(&b.target))
+        });
+
+        RegistryReport { images, tunnels }
+    }
+
+    pub fn clear(&mut self) {
+        self.pending.clear();
+        self.images.clear();
+        self.tunnels.clear();
+    }
+
+    fn add_registry_response(
+        &mut self,
+        method: &str,
+        request: DockerRegistryRequest,
+        response: &SerializableHttpResponsePacket,
+    ) {
+        let image = match request.image {
+            Some(image) => image,
+            None => return,
+        };
+
+        // HEAD requests check the existence of manifests and blobs, without pulling them
+        if method != "GET" {
+            return;
+        }
+
+        let downloads = self
+            .images
+            .entry(image.clone())
+            .or_insert_with(|| ImageDownloads {
+                image,
+                ..Default::default()
+            });
+        let length = content_length(response);
+
+        match (request.endpoint, response.code) {
+            (DockerRegistryEndpoint::Manifest, 200..=299) => {
+                downloads.manifest_pulls += 1;
+                downloads.bytes += length;
+            }
+            (DockerRegistryEndpoint::Blob, 200..=299) => {
+                downloads.blob_pulls += 1;
+                downloads.bytes += length;
+                if let Some(digest) = request.reference {
+                    downloads.layers.insert(digest, length);
+                }
+            }
+            // Blobs are often served by a storage backend the client is redirected to
+            (DockerRegistryEndpoint::Blob, 300..=399) => downloads.redirected_blob_pulls += 1,
+            _ => (),
+        }
+    }
+}
+
+/// Get the registry and proxy analytics of the collected packets
+#[tauri::command]
+pub fn get_registry_analytics(state: tauri::State<SniffingState>) -> RegistryReport {
+    state.packets.lock().unwrap().registry.report()
+}
+
+fn connection(packet: &ParsedPacket) -> Option<Connection> {
+    Some((
+        (get_source_ip(packet)?, get_source_port(packet)?),
+        (get_dest_ip(packet)?, get_dest_port(packet)?),
+    ))
+}
+
+fn content_length(response: &SerializableHttpResponsePacket) -> u64 {
+    response
+        .headers
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case("Content-Length"))
+        .and_then(|(_, value)| value.trim().parse().ok())
+        .unwrap_or(0)
+}
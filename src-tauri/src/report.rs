@@ -56,11 +56,15 @@
 
 use self::data::{PacketExchange, SourceDestination};
 use sniffer_parser::serializable_packet::util::{
-    contains_arp, contains_dns, contains_http, contains_icmp, contains_icmp6, contains_ipv4,
-    contains_ipv6, contains_tcp, contains_tls, contains_udp, get_dest_ip, get_dest_port,
-    get_source_ip, get_source_port,
+    contains_ah, contains_arp, contains_cql, contains_dhcp, contains_dns, contains_esp,
+    contains_ethercat, contains_goose, contains_gtp, contains_http, contains_http2, contains_icmp,
+    contains_icmp6, contains_ike, contains_ipv4, contains_ipv6, contains_iscsi, contains_kafka,
+    contains_ntp, contains_nvme_tcp, contains_profinet, contains_ptp, contains_quic,
+    contains_s7comm, contains_snmp, contains_sv, contains_tcp, contains_tls, contains_udp,
+    contains_wireguard, contains_zookeeper, get_dest_ip, get_dest_port, get_source_ip,
+    get_source_port,
 };
-use sniffer_parser::serializable_packet::ParsedPacket;
+use sniffer_parser::serializable_packet::{ParsedPacket, SerializablePacket};
 use std::collections::HashMap;
 use std::ffi::OsStr;
 use std::fs::{self, OpenOptions};
@@ -120,6 +124,7 @@ pub fn write_report(
             "Last Data Exchange",
             "Bytes Exchanged",
             "Protocols",
+            "TLS Fingerprints",
         ];
         writer.write_all((headers.join(",") + "\n").as_bytes())?;
     }
@@ -138,6 +143,26 @@ pub fn write_report(
     Ok(true)
 }
 
+/// Returns the JA3 and JA3S fingerprints of the TLS hellos contained in a packet, over TLS or QUIC
+pub fn get_tls_fingerprints(packet: &ParsedPacket) -> Vec<String> {
+    let handshake = match packet.get_application_layer_packet() {
+        Some(SerializablePacket::TlsPacket(tls_packet)) => tls_packet.handshake.as_ref(),
+        Some(SerializablePacket::QuicPacket(quic_packet)) => quic_packet.handshake.as_ref(),
+        _ => None,
+    };
+
+    handshake
+        .map(|handshake| {
+            let ja3 = handshake.ja3.iter().map(|ja3| format!("JA3:{}", ja3.hash));
+            let ja3s = handshake
+                .ja3s
+                .iter()
+                .map(|ja3s| format!("JA3S:{}", ja3s.hash));
+            ja3.chain(ja3s).collect()
+        })
+        .unwrap_or_default()
+}
+
 /// Returns (Source IP, Destination IP, Source Port, Destination Port, and Protocols) contained in a packet
 pub fn get_sender_receiver(packet: &ParsedPacket) -> (SourceDestination, Vec<String>) {
     let network_source = get_source_ip(packet).unwrap_or(String::from("-"));
@@ -166,12 +191,60 @@ pub fn get_sender_receiver(packet: &ParsedPacket) -> (SourceDestination, Vec<Str
         protocols.push(String::from("UDP"));
     }
 
+    if contains_esp(packet) {
+        protocols.push(String::from("ESP"));
+    } else if contains_ah(packet) {
+        protocols.push(String::from("AH"));
+    }
+
     if contains_dns(packet) {
         protocols.push(String::from("DNS"));
+    } else if contains_dhcp(packet) {
+        protocols.push(String::from("DHCP"));
+    } else if contains_snmp(packet) {
+        protocols.push(String::from("SNMP"));
+    } else if contains_gtp(packet) {
+        protocols.push(String::from("GTP"));
+    } else if contains_ike(packet) {
+        protocols.push(String::from("IKE"));
+    } else if contains_wireguard(packet) {
+        protocols.push(String::from("WireGuard"));
+    } else if contains_ntp(packet) {
+        protocols.push(String::from("NTP"));
     } else if contains_http(packet) {
         protocols.push(String::from("HTTP"));
+    } else if contains_http2(packet) {
+        protocols.push(String::from("HTTP/2"));
     } else if contains_tls(packet) {
         protocols.push(String::from("TLS"));
+    } else if contains_quic(packet) {
+        protocols.push(String::from("QUIC"));
+    } else if contains_ptp(packet) {
+        protocols.push(String::from("PTP"));
+    } else if contains_goose(packet) {
+        protocols.push(String::from("GOOSE"));
+    } else if contains_sv(packet) {
+        protocols.push(String::from("SV"));
+    } else if contains_profinet(packet) {
+        protocols.push(String::from("PROFINET"));
+    } else if contains_ethercat(packet) {
+        protocols.push(String::from("EtherCAT"));
+    } else if contains_s7comm(packet) {
+        protocols.push(String::from("S7comm"));
+    } else if contains_iscsi(packet) {
+        protocols.push(String::from("iSCSI"));
+    } else if contains_nvme_tcp(packet) {
+        protocols.push(String::from("NVMe/TCP"));
+    } else if contains_cql(packet) {
+        protocols.push(String::from("CQL"));
+    } else if contains_kafka(packet) {
+        protocols.push(String::from("Kafka"));
+    } else if contains_zookeeper(packet) {
+        protocols.push(String::from("Zookeeper"));
+    } else if let Some(SerializablePacket::CustomPacket(custom)) =
+        packet.get_application_layer_packet()
+    {
+        protocols.push(custom.protocol.clone());
     }
 
     (
@@ -200,11 +273,12 @@ pub mod data {
         pub port_destination: String,
     }
 
-    /// Data structure describing the list of protocols, total bytes, and timestamps of the
-    /// first and last packet exchange in a connection
+    /// Data structure describing the list of protocols, total bytes, timestamps of the
+    /// first and last packet exchange, and TLS fingerprints in a connection
     #[derive(Debug)]
     pub struct PacketExchange {
         protocols: HashSet<String>,
+        tls_fingerprints: HashSet<String>,
         pub transmitted_bytes: usize,
         first_exchange: DateTime<Local>,
         last_exchange: DateTime<Local>,
@@ -246,6 +320,7 @@ pub mod data {
         ) -> Self {
             PacketExchange {
                 protocols: HashSet::from_iter(protocols.into_iter()),
+                tls_fingerprints: HashSet::new(),
                 first_exchange: exchange_time,
                 last_exchange: exchange_time,
                 transmitted_bytes,
@@ -268,6 +343,11 @@ pub mod data {
             self.first_exchange = cmp::min(self.first_exchange, exchange_time);
             self.last_exchange = cmp::max(self.last_exchange, exchange_time);
         }
+
+        /// Add the JA3/JA3S fingerprints of the TLS hellos exchanged in the connection
+        pub fn add_tls_fingerprints(&mut self, tls_fingerprints: Vec<String>) {
+            self.tls_fingerprints.extend(tls_fingerprints);
+        }
     }
 
     impl ToString for PacketExchange {
@@ -280,12 +360,21 @@ pub mod data {
             } else {
                 protocols_set.join(";")
             };
+            let mut tls_fingerprints: Vec<&str> =
+                self.tls_fingerprints.iter().map(String::as_str).collect();
+            tls_fingerprints.sort();
+            let tls_fingerprints = if tls_fingerprints.is_empty() {
+                "-".to_owned()
+            } else {
+                tls_fingerprints.join(";")
+            };
 
             [
                 first_exchange,
                 last_exchange,
                 self.transmitted_bytes.to_string(),
                 protocols,
+                tls_fingerprints,
             ]
             .join(",")
         }
@@ -418,6 +507,21 @@ pub mod data {
             assert_eq!(exchange.last_exchange, now);
         }
 
+        #[test]
+        fn add_packet_exchange_tls_fingerprints() {
+            let now = Local::now();
+            let protocol = String::from("TLS");
+            let mut exchange = PacketExchange::new(vec![protocol], 100, now);
+            exchange
+                .add_tls_fingerprints(vec![String::from("JA3:02aa4679df284f240695da144b70c288")]);
+            exchange
+                .add_tls_fingerprints(vec![String::from("JA3:02aa4679df284f240695da144b70c288")]);
+            assert_eq!(exchange.tls_fingerprints.len(), 1);
+            assert!(exchange
+                .to_string()
+                .ends_with(",100,TLS,JA3:02aa4679df284f240695da144b70c288"));
+        }
+
         #[test]
         fn source_destination_ipv4() {
             let ip_source = String::from("1.1.1.1");
@@ -0,0 +1,340 @@
+//! Reverse DNS resolution of the IP addresses of the conversations and the endpoints
+//!
+//! Once enabled by the user, the host names of the addresses are looked up in background, by the
+//! resolver of the system, so that neither the capture nor the commands returning the
+//! conversations and the endpoints wait for the DNS servers. An address is queued the first time
+//! it is returned to the frontend, and named the next times once resolved:
+//! - each address is looked up once, the names found as well as the missing ones being cached
+//! - the lookups are rate limited, not to flood the DNS servers when a capture sees many hosts
+//! - a limited number of addresses wait to be resolved, the others being queued the next time they
+//!   are returned
+//!
+//! Multicast, broadcast and unspecified addresses have no host name, and are never looked up.
+
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr};
+use std::sync::mpsc::{sync_channel, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use serde::Serialize;
+use tracing::{debug, info};
+
+use crate::conversations::Conversation;
+use crate::endpoints::{Endpoint, EndpointKind};
+use crate::SniffingState;
+
+/// Lookups performed per second at most
+const LOOKUPS_PER_SECOND: u64 = 10;
+
+/// Addresses waiting to be resolved at most
+const QUEUE_SIZE: usize = 256;
+
+/// Addresses cached at most, the further ones being never resolved
+const CACHE_SIZE: usize = 65_536;
+
+/// Host name of an address, as cached
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Resolution {
+    /// Waiting to be looked up
+    Pending,
+    /// Looked up, with the name found if any
+    Resolved(Option<String>),
+}
+
+/// State of the reverse DNS resolution, as returned to the frontend
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ReverseDnsStatus {
+    pub enabled: bool,
+    /// Addresses looked up, with or without a name found
+    pub resolved: usize,
+    /// Addresses with a name found
+    pub named: usize,
+    /// Addresses waiting to be looked up
+    pub pending: usize,
+}
+
+/// Background resolver of the host names of the IP addresses
+#[derive(Debug)]
+pub struct ReverseDns {
+    cache: Arc<Mutex<HashMap<IpAddr, Resolution>>>,
+    /// Queue of the lookup thread, while the resolution is enabled
+    queue: Option<SyncSender<IpAddr>>,
+    /// Function looking up the host name of an address
+    lookup: fn(IpAddr) -> Option<String>,
+}
+
+impl Default for ReverseDns {
+    fn default() -> Self {
+        Self::with_lookup(lookup_host_name)
+    }
+}
+
+impl ReverseDns {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn with_lookup(lookup: fn(IpAddr) -> Option<String>) -> Self {
+        ReverseDns {
+            cache: Arc::new(Mutex::new(HashMap::new())),
+            queue: None,
+            lookup,
+        }
+    }
+
+    /// Enable or disable the resolution, starting a lookup thread when enabled
+    ///
+    /// Once disabled, the addresses still queued are not looked up, while the names already
+    /// resolved are kept for when the resolution is enabled again.
+    pub fn set_enabled(&mut self, enabled: bool) {
+        if enabled == self.queue.is_some() {
+            return;
+        }
+
+        if !enabled {
+            self.queue = None;
+            self.cache
+                .lock()
+                .unwrap()
+                .retain(|_, resolution| *resolution != Resolution::Pending);
+            return;
+        }
+
+        let (sender, receiver) = sync_channel::<IpAddr>(QUEUE_SIZE);
+        let (cache, lookup) = (self.cache.clone(), self.lookup);
+        thread::spawn(move || {
+            for address in receiver {
+                // Skip the addresses dropped when the resolution was disabled
+                if cache.lock().unwrap().get(&address) != Some(&Resolution::Pending) {
+                    continue;
+                }
+
+                let name = lookup(address);
+                debug!("Reverse DNS of {}: {:?}", address, name);
+                cache
+                    .lock()
+                    .unwrap()
+                    .insert(address, Resolution::Resolved(name));
+
+                thread::sleep(Duration::from_millis(1000 / LOOKUPS_PER_SECOND));
+            }
+        });
+        self.queue = Some(sender);
+    }
+
+    /// Get the host name of an address if resolved, queuing it to be looked up otherwise
+    pub fn name(&self, address: IpAddr) -> Option<String> {
+        let queue = self.queue.as_ref()?;
+        let mut cache = self.cache.lock().unwrap();
+
+        match cache.get(&address) {
+            Some(Resolution::Resolved(name)) => return name.clone(),
+            Some(Resolution::Pending) => return None,
+            None => {}
+        }
+        if !resolvable(address) || cache.len() >= CACHE_SIZE {
+            return None;
+        }
+
+        // Pending before being sent, not to be skipped by the lookup thread
+        cache.insert(address, Resolution::Pending);
+        if queue.try_send(address).is_err() {
+            cache.remove(&address);
+        }
+
+        None
+    }
+
+    /// Fill in the host names of the endpoints of the conversations
+    pub fn name_conversations(&self, conversations: &mut [Conversation]) {
+        if self.queue.is_none() {
+            return;
+        }
+
+        for conversation in conversations {
+            conversation.initiator_name = self.name(conversation.initiator);
+            conversation.responder_name = self.name(conversation.responder);
+        }
+    }
+
+    /// Fill in the host names of the IP, TCP and UDP endpoints
+    pub fn name_endpoints(&self, endpoints: &mut [Endpoint]) {
+        if self.queue.is_none() {
+            return;
+        }
+
+        for endpoint in endpoints {
+            if endpoint.kind == EndpointKind::Ethernet {
+                continue;
+            }
+            endpoint.name = endpoint
+                .address
+                .parse()
+                .ok()
+                .and_then(|address| self.name(address));
+        }
+    }
+
+    pub fn status(&self) -> ReverseDnsStatus {
+        let cache = self.cache.lock().unwrap();
+        let pending = cache
+            .values()
+            .filter(|resolution| **resolution == Resolution::Pending)
+            .count();
+        let named = cache
+            .values()
+            .filter(|resolution| matches!(resolution, Resolution::Resolved(Some(_))))
+            .count();
+
+        ReverseDnsStatus {
+            enabled: self.queue.is_some(),
+            resolved: cache.len() - pending,
+            named,
+            pending,
+        }
+    }
+}
+
+/// Check if an address may have a host name
+fn resolvable(address: IpAddr) -> bool {
+    !address.is_unspecified()
+        && !address.is_multicast()
+        && address != IpAddr::V4(Ipv4Addr::BROADCAST)
+}
+
+/// Look up the host name of an address with the resolver of the system
+fn lookup_host_name(address: IpAddr) -> Option<String> {
+    // Without a PTR record, the address itself is returned
+    dns_lookup::lookup_addr(&address)
+        .ok()
+        .filter(|name| name.parse::<IpAddr>().is_err())
+}
+
+/// Enables or disables the reverse DNS resolution of the addresses of the conversations and the
+/// endpoints
+#[tauri::command]
+pub fn set_reverse_dns(enabled: bool, state: tauri::State<SniffingState>) -> ReverseDnsStatus {
+    let mut reverse_dns = state.reverse_dns.lock().unwrap();
+    reverse_dns.set_enabled(enabled);
+
+    info!("Reverse DNS resolution enabled: {}", enabled);
+
+    reverse_dns.status()
+}
+
+/// Returns whether the reverse DNS resolution is enabled, and the addresses resolved so far
+#[tauri::command]
+pub fn get_reverse_dns_status(state: tauri::State<SniffingState>) -> ReverseDnsStatus {
+    state.reverse_dns.lock().unwrap().status()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr};
+    use std::thread;
+    use std::time::Duration;
+
+    use super::{ReverseDns, ReverseDnsStatus};
+    use crate::endpoints::{Endpoint, EndpointKind};
+    use crate::statistics::Counters;
+
+    #[test]
+    fn background_resolution() {
+        let mut reverse_dns = ReverseDns::with_lookup(fake_lookup);
+        let (named, unnamed) = (ip(1), ip(2));
+
+        // Nothing is looked up until enabled
+        assert_eq!(reverse_dns.name(named), None);
+        assert_eq!(reverse_dns.status().pending, 0);
+
+        reverse_dns.set_enabled(true);
+        assert_eq!(reverse_dns.name(named), None);
+        assert_eq!(reverse_dns.name(unnamed), None);
+        wait_resolved(&reverse_dns);
+
+        assert_eq!(reverse_dns.name(named), Some("host-1.example".to_owned()));
+        assert_eq!(reverse_dns.name(unnamed), None);
+        assert_eq!(
+            reverse_dns.status(),
+            ReverseDnsStatus {
+                enabled: true,
+                resolved: 2,
+                named: 1,
+                pending: 0,
+            }
+        );
+
+        let mut endpoints = vec![
+            endpoint(EndpointKind::Ethernet, "00:00:00:00:00:01"),
+            endpoint(EndpointKind::Ipv4, "10.0.0.1"),
+        ];
+        reverse_dns.name_endpoints(&mut endpoints);
+        assert_eq!(endpoints[0].name, None);
+        assert_eq!(endpoints[1].name, Some("host-1.example".to_owned()));
+
+        // Names are kept, but not returned, while disabled
+        reverse_dns.set_enabled(false);
+        assert_eq!(reverse_dns.name(named), None);
+        reverse_dns.set_enabled(true);
+        assert_eq!(reverse_dns.name(named), Some("host-1.example".to_owned()));
+    }
+
+    #[test]
+    fn unresolvable_addresses() {
+        let mut reverse_dns = ReverseDns::with_lookup(fake_lookup);
+        reverse_dns.set_enabled(true);
+
+        for address in [
+            IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            IpAddr::V4(Ipv4Addr::BROADCAST),
+            IpAddr::V4(Ipv4Addr::new(224, 0, 0, 251)),
+            "ff02::1".parse().unwrap(),
+        ] {
+            assert_eq!(reverse_dns.name(address), None);
+        }
+        assert_eq!(reverse_dns.status().pending, 0);
+        assert_eq!(reverse_dns.status().resolved, 0);
+    }
+
+    ///////////////////// Utils
+
+    fn ip(host: u8) -> IpAddr {
+        IpAddr::V4(Ipv4Addr::new(10, 0, 0, host))
+    }
+
+    /// Lookup naming the odd hosts of 10.0.0.0/24
+    fn fake_lookup(address: IpAddr) -> Option<String> {
+        match address {
+            IpAddr::V4(ipv4) if ipv4.octets()[3] % 2 == 1 => {
+                Some(format!("host-{}.example", ipv4.octets()[3]))
+            }
+            _ => None,
+        }
+    }
+
+    fn wait_resolved(reverse_dns: &ReverseDns) {
+        for _ in 0..100 {
+            if reverse_dns.status().pending == 0 {
+                return;
+            }
+            thread::sleep(Duration::from_millis(20));
+        }
+        panic!("Addresses not resolved");
+    }
+
+    fn endpoint(kind: EndpointKind, address: &str) -> Endpoint {
+        Endpoint {
+            kind,
+            address: address.to_owned(),
+            port: None,
+            sent: Counters::default(),
+            received: Counters::default(),
+            first_seen: 0,
+            last_seen: 0,
+            name: None,
+        }
+    }
+}
@@ -0,0 +1,166 @@
+//! Sampling of the frames received from the network interface
+//!
+//! On busy links, the frames can be sampled before being dissected, so that sniffing can be left
+//! running just to follow the trends of the traffic:
+//! - Every nth: one frame out of every N, deterministically
+//! - Probabilistic: each frame with the given probability
+//!
+//! Frames left out are not dissected, stored nor saved to the capture file, but are counted by the
+//! statistics, which report the ratio of the frames sampled. Imported captures are never sampled.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::{SniffingError, SniffingState};
+
+/// Frames kept by the sampling
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(tag = "type", content = "value", rename_all = "camelCase")]
+pub enum SamplingMode {
+    Disabled,
+    EveryNth(u32),
+    Probabilistic(f64),
+}
+
+/// Sampler of the received frames
+#[derive(Debug)]
+pub struct Sampler {
+    mode: SamplingMode,
+    /// Frames seen since the last sampled one, for the every nth sampling
+    skipped: u32,
+    /// State of the xorshift generator of the probabilistic sampling
+    random: u64,
+}
+
+impl Sampler {
+    pub fn new() -> Self {
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_nanos() as u64)
+            .unwrap_or_default();
+
+        Sampler {
+            mode: SamplingMode::Disabled,
+            skipped: 0,
+            // The state of the generator must not be zero
+            random: seed | 1,
+        }
+    }
+
+    pub fn mode(&self) -> SamplingMode {
+        self.mode
+    }
+
+    /// Replace the sampling mode, checking its ratio
+    fn set_mode(&mut self, mode: SamplingMode) -> Result<(), String> {
+        match mode {
+            SamplingMode::EveryNth(0) => {
+                return Err("The sampling ratio must be at least 1".to_owned());
+            }
+            SamplingMode::Probabilistic(probability)
+                if !(probability > 0.0 && probability <= 1.0) =>
+            {
+                return Err("The sampling probability must be in (0, 1]".to_owned());
+            }
+            _ => (),
+        }
+
+        self.mode = mode;
+        self.skipped = 0;
+        Ok(())
+    }
+
+    /// Check if the next frame is sampled
+    pub fn sample(&mut self) -> bool {
+        match self.mode {
+            SamplingMode::Disabled => true,
+            SamplingMode::EveryNth(n) => {
+                let sampled = self.skipped == 0;
+                self.skipped = (self.skipped + 1) % n;
+                sampled
+            }
+            SamplingMode::Probabilistic(probability) => self.next_random() < probability,
+        }
+    }
+
+    /// Get a random number in [0, 1)
+    fn next_random(&mut self) -> f64 {
+        self.random ^= self.random << 13;
+        self.random ^= self.random >> 7;
+        self.random ^= self.random << 17;
+
+        (self.random >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+impl Default for Sampler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Sets the sampling applied to the frames received from the network interface
+#[tauri::command]
+pub fn set_sampling_mode(
+    mode: SamplingMode,
+    state: tauri::State<SniffingState>,
+) -> Result<(), SniffingError> {
+    state
+        .sampler
+        .lock()
+        .unwrap()
+        .set_mode(mode)
+        .map_err(SniffingError::InvalidSamplingMode)?;
+
+    info!("Sampling mode set: {:?}", mode);
+
+    Ok(())
+}
+
+/// Returns the sampling applied to the frames received from the network interface
+#[tauri::command]
+pub fn get_sampling_mode(state: tauri::State<SniffingState>) -> SamplingMode {
+    state.sampler.lock().unwrap().mode()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Sampler, SamplingMode};
+
+    #[test]
+    fn every_nth_frame() {
+        let mut sampler = Sampler::new();
+        sampler.set_mode(SamplingMode::EveryNth(3)).unwrap();
+
+        let sampled: Vec<bool> = (0..7).map(|_| sampler.sample()).collect();
+        assert_eq!(sampled, vec![true, false, false, true, false, false, true]);
+    }
+
+    #[test]
+    fn probabilistic_sampling() {
+        let mut sampler = Sampler::new();
+        sampler.set_mode(SamplingMode::Probabilistic(0.25)).unwrap();
+
+        let sampled = (0..100_000).filter(|_| sampler.sample()).count();
+        assert!((23_000..27_000).contains(&sampled));
+
+        sampler.set_mode(SamplingMode::Probabilistic(1.0)).unwrap();
+        assert!((0..1000).all(|_| sampler.sample()));
+    }
+
+    #[test]
+    fn invalid_modes() {
+        let mut sampler = Sampler::new();
+
+        assert!(sampler.set_mode(SamplingMode::EveryNth(0)).is_err());
+        assert!(sampler.set_mode(SamplingMode::Probabilistic(0.0)).is_err());
+        assert!(sampler.set_mode(SamplingMode::Probabilistic(1.5)).is_err());
+        assert!(sampler
+            .set_mode(SamplingMode::Probabilistic(f64::NAN))
+            .is_err());
+        assert_eq!(sampler.mode(), SamplingMode::Disabled);
+        assert!((0..10).all(|_| sampler.sample()));
+    }
+}
@@ -0,0 +1,215 @@
+//! Traffic of the IPsec security associations
+//!
+//! The payload of the ESP packets is encrypted, yet their header tells the SA protecting them: its
+//! SPI, chosen by the receiver and so unique to the destination. The ESP and AH packets are
+//! accounted to the SA of their SPI, source and destination, tracking the gaps in their sequence
+//! numbers, so that the traffic of each direction of the IPsec tunnels can be measured.
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+
+use chrono::{DateTime, Local};
+use serde::Serialize;
+use sniffer_parser::serializable_packet::{ParsedPacket, SerializablePacket};
+
+use crate::statistics::{Counters, Timestamp};
+use crate::SniffingState;
+
+/// Traffic of a security association, as returned to the frontend
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SecurityAssociation {
+    /// ESP or AH
+    pub protocol: String,
+    pub spi: u32,
+    pub source: IpAddr,
+    pub destination: IpAddr,
+    /// Whether the ESP packets are encapsulated in UDP to traverse a NAT
+    pub udp_encapsulated: bool,
+    /// Whether the ESP packets are NULL-encrypted, with the mode of the SA if so
+    pub null_encrypted: bool,
+    pub mode: Option<String>,
+    #[serde(flatten)]
+    pub counters: Counters,
+    /// Highest sequence number seen
+    pub last_sequence: u32,
+    /// Sequence numbers skipped, not received yet
+    pub missing: u64,
+    /// Packets received after one with a higher sequence number
+    pub out_of_order: u64,
+    pub first_seen: Timestamp,
+    pub last_seen: Timestamp,
+    /// Time between the first and the last packet, in milliseconds
+    pub duration: i64,
+}
+
+/// Protocol, SPI, source and destination of a security association
+type SaKey = (&'static str, u32, IpAddr, IpAddr);
+
+/// Tracker of the security associations of the collected packets
+#[derive(Debug, Default)]
+pub struct SecurityAssociations {
+    /// Security associations, in the order they were first seen
+    associations: Vec<SecurityAssociation>,
+    index: HashMap<SaKey, usize>,
+}
+
+impl SecurityAssociations {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Account an ESP or AH packet of the given length to its security association, given the
+    /// time it was received
+    pub fn update(&mut self, packet: &ParsedPacket, bytes: usize, time: DateTime<Local>) {
+        let (source, destination) = match packet.get_network_layer_packet() {
+            Some(SerializablePacket::Ipv4Packet(ipv4)) => {
+                (IpAddr::V4(ipv4.source), IpAddr::V4(ipv4.destination))
+            }
+            Some(SerializablePacket::Ipv6Packet(ipv6)) => {
+                (IpAddr::V6(ipv6.source), IpAddr::V6(ipv6.destination))
+            }
+            _ => return,
+        };
+
+        // ESP packets traversing a NAT are carried by UDP
+        let esp = match (
+            packet.get_transport_layer_packet(),
+            packet.get_application_layer_packet(),
+        ) {
+            (Some(SerializablePacket::EspPacket(esp)), _)
+            | (_, Some(SerializablePacket::EspPacket(esp))) => Some(esp),
+            _ => None,
+        };
+        let (protocol, spi, sequence) = match (esp, packet.get_transport_layer_packet()) {
+            (Some(esp), _) => ("ESP", esp.spi, esp.sequence),
+            (None, Some(SerializablePacket::AhPacket(ah))) => ("AH", ah.spi, ah.sequence),
+            _ => return,
+        };
+
+        let time = time.timestamp_millis();
+        let associations = &mut self.associations;
+        let index = *self
+            .index
+            .entry((protocol, spi, source, destination))
+            .or_insert_with(|| {
+                associations.push(SecurityAssociation {
+                    protocol: protocol.to_owned(),
+                    spi,
+                    source,
+                    destination,
+                    udp_encapsulated: false,
+                    null_encrypted: false,
+                    mode: None,
+                    counters: Counters::default(),
+                    last_sequence: sequence.wrapping_sub(1),
+                    missing: 0,
+                    out_of_order: 0,
+                    first_seen: time,
+                    last_seen: time,
+                    duration: 0,
+                });
+                associations.len() - 1
+            });
+
+        let association = &mut self.associations[index];
+        association.counters.add(bytes);
+        if let Some(esp) = esp {
+            association.udp_encapsulated |= esp.udp_encapsulated;
+            if let Some(cleartext) = &esp.cleartext {
+                association.null_encrypted = true;
+                association.mode = Some(cleartext.mode.clone());
+            }
+        }
+
+        if sequence > association.last_sequence {
+            association.missing += (sequence - association.last_sequence - 1) as u64;
+            association.last_sequence = sequence;
+        } else {
+            association.out_of_order += 1;
+            association.missing = association.missing.saturating_sub(1);
+        }
+
+        association.last_seen = association.last_seen.max(time);
+        association.duration = association.last_seen - association.first_seen;
+    }
+
+    pub fn associations(&self) -> Vec<SecurityAssociation> {
+        self.associations.clone()
+    }
+
+    pub fn clear(&mut self) {
+        self.associations.clear();
+        self.index.clear();
+    }
+}
+
+/// Get the traffic of the IPsec security associations
+#[tauri::command]
+pub fn get_security_associations(state: tauri::State<SniffingState>) -> Vec<SecurityAssociation> {
+    state
+        .packets
+        .lock()
+        .unwrap()
+        .security_associations
+        .associations()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Local, TimeZone};
+    use pnet::packet::ethernet::EthernetPacket;
+    use sniffer_parser::parse_ethernet_frame;
+    use sniffer_parser::serializable_packet::ParsedPacket;
+
+    use super::SecurityAssociations;
+
+    #[test]
+    fn esp_traffic_of_each_sa() {
+        let mut associations = SecurityAssociations::new();
+        for (id, (spi, sequence)) in [
+            (0x1001, 1),
+            (0x1001, 2),
+            (0x1001, 5),
+            (0x1001, 4),
+            (0x2002, 1),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            let packet = esp_packet(id, spi, sequence);
+            let time = Local.timestamp_millis_opt(1_700_000_000_000 + id as i64 * 10);
+            associations.update(&packet, 74, time.unwrap());
+        }
+
+        let associations = associations.associations();
+        assert_eq!(associations.len(), 2);
+        assert_eq!(associations[0].protocol, "ESP");
+        assert_eq!(associations[0].spi, 0x1001);
+        assert_eq!(associations[0].counters.packets, 4);
+        assert_eq!(associations[0].counters.bytes, 296);
+        assert_eq!(associations[0].last_sequence, 5);
+        assert_eq!(associations[0].missing, 1);
+        assert_eq!(associations[0].out_of_order, 1);
+        assert_eq!(associations[0].duration, 30);
+        assert!(!associations[0].null_encrypted);
+        assert_eq!(associations[1].spi, 0x2002);
+        assert_eq!(associations[1].missing, 0);
+    }
+
+    ///////////////////// Utils
+
+    /// ESP packet sent by 10.10.10.10 to 11.11.11.11, with 32 bytes of encrypted payload
+    fn esp_packet(id: usize, spi: u32, sequence: u32) -> ParsedPacket {
+        let mut frame = vec![
+            0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x08, 0x00,
+            0x45, 0x00, 0x00, 0x3c, 0x00, 0x00, 0x00, 0x00, 0x40, 0x32, 0x00, 0x00, 0x0a, 0x0a,
+            0x0a, 0x0a, 0x0b, 0x0b, 0x0b, 0x0b,
+        ];
+        frame.extend(spi.to_be_bytes());
+        frame.extend(sequence.to_be_bytes());
+        frame.extend([0xab; 32]);
+
+        parse_ethernet_frame(&EthernetPacket::new(&frame).unwrap(), id)
+    }
+}
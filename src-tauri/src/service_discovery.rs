@@ -0,0 +1,405 @@
+//! Services advertised on the local network with DNS-SD over multicast DNS
+//!
+//! Devices announce their services in the answers of the mDNS responses (UDP port 5353):
+//! - PTR records, from a service type (e.g. `_ipp._tcp.local`) to an instance of it
+//! - SRV records, from an instance to the host and port offering it
+//! - TXT records, from an instance to its attributes (`key=value` strings)
+//! - A and AAAA records, from a host to its addresses
+//!
+//! The records of each instance are grouped, whatever the responses they came in, into a
+//! discovered service. A record with a TTL of 0 is a goodbye: the instance is no longer offered.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::net::IpAddr;
+
+use chrono::{DateTime, Local};
+use serde::Serialize;
+use sniffer_parser::serializable_packet::application::CustomResourceData;
+use sniffer_parser::serializable_packet::{ParsedPacket, SerializablePacket};
+
+use crate::statistics::Timestamp;
+use crate::SniffingState;
+
+/// UDP port of multicast DNS
+const MDNS_PORT: u16 = 5353;
+
+/// Service types enumerating the other service types, instead of instances
+const SERVICE_TYPES_ENUMERATION: &str = "_services._dns-sd._udp.local";
+
+/// Kinds of devices, by the service they advertise
+const KINDS: [(&str, &str); 14] = [
+    ("_airplay", "AirPlay"),
+    ("_raop", "AirPlay"),
+    ("_googlecast", "Chromecast"),
+    ("_spotify-connect", "Spotify Connect"),
+    ("_ipp", "Printer"),
+    ("_ipps", "Printer"),
+    ("_printer", "Printer"),
+    ("_pdl-datastream", "Printer"),
+    ("_uscan", "Scanner"),
+    ("_hap", "HomeKit"),
+    ("_smb", "File sharing"),
+    ("_afpovertcp", "File sharing"),
+    ("_ssh", "SSH"),
+    ("_http", "Web server"),
+];
+
+/// Service advertised on the local network, as returned to the frontend
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DiscoveredService {
+    /// Full name of the instance, e.g. `Office._ipp._tcp.local`
+    pub instance: String,
+    pub service_type: String,
+    /// Kind of device advertising the service, for the well-known service types
+    pub kind: Option<String>,
+    pub host: Option<String>,
+    pub port: Option<u16>,
+    /// Addresses of the host, as announced
+    pub addresses: Vec<IpAddr>,
+    pub txt: Vec<String>,
+    /// Address of the device last announcing the service
+    pub announcer: IpAddr,
+    pub first_seen: Timestamp,
+    pub last_seen: Timestamp,
+}
+
+/// Tracker of the DNS-SD services advertised in the collected packets
+#[derive(Debug, Default)]
+pub struct ServiceDiscovery {
+    /// Services, by instance
+    services: BTreeMap<String, DiscoveredService>,
+    /// Addresses announced for each host
+    addresses: HashMap<String, BTreeSet<IpAddr>>,
+}
+
+impl ServiceDiscovery {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the advertisements of a packet, if it is an mDNS response, given the time it was
+    /// received
+    pub fn update(&mut self, packet: &ParsedPacket, time: DateTime<Local>) {
+        let announcer = match packet.get_network_layer_packet() {
+            Some(SerializablePacket::Ipv4Packet(ipv4)) => IpAddr::V4(ipv4.source),
+            Some(SerializablePacket::Ipv6Packet(ipv6)) => IpAddr::V6(ipv6.source),
+            _ => return,
+        };
+        let is_mdns = matches!(
+            packet.get_transport_layer_packet(),
+            Some(SerializablePacket::UdpPacket(udp))
+                if udp.source == MDNS_PORT || udp.destination == MDNS_PORT
+        );
+        if !is_mdns {
+            return;
+        }
+        let dns = match packet.get_application_layer_packet() {
+            Some(SerializablePacket::DnsPacket(dns)) if !dns.header.query => dns,
+            _ => return,
+        };
+
+        let time = time.timestamp_millis();
+        let records: Vec<_> = dns.answers.iter().chain(&dns.additional).collect();
+
+        // Instances first, for the records about them in the same response
+        for record in &records {
+            if let CustomResourceData::PTR(ptr) = &record.data {
+                if !record.name.starts_with('_') || record.name == SERVICE_TYPES_ENUMERATION {
+                    continue;
+                }
+                if record.ttl == 0 {
+                    self.services.remove(&ptr.name);
+                } else {
+                    // Subtypes are announced as `<subtype>._sub.<service type>`
+                    let service_type = record
+                        .name
+                        .split_once("._sub.")
+                        .map_or(record.name.as_str(), |(_, service_type)| service_type);
+                    self.service(&ptr.name, service_type, announcer, time);
+                }
+            }
+        }
+
+        for record in &records {
+            match &record.data {
+                CustomResourceData::SRV(srv) if record.ttl > 0 => {
+                    if let Some(service) = self.instance(&record.name, announcer, time) {
+                        service.host = Some(srv.target.clone());
+                        service.port = Some(srv.port);
+                    }
+                }
+                CustomResourceData::TXT(txt) if record.ttl > 0 => {
+                    if let Some(service) = self.instance(&record.name, announcer, time) {
+                        service.txt = txt.strings.clone();
+                    }
+                }
+                CustomResourceData::A(a) => {
+                    self.address(&record.name, IpAddr::V4(a.address), record.ttl);
+                }
+                CustomResourceData::AAAA(aaaa) => {
+                    self.address(&record.name, IpAddr::V6(aaaa.address), record.ttl);
+                }
+                _ => (),
+            }
+        }
+    }
+
+    /// Get the service of an instance, adding it if new
+    fn service(
+        &mut self,
+        instance: &str,
+        service_type: &str,
+        announcer: IpAddr,
+        time: i64,
+    ) -> &mut DiscoveredService {
+        let service =
+            self.services
+                .entry(instance.to_owned())
+                .or_insert_with(|| DiscoveredService {
+                    instance: instance.to_owned(),
+                    service_type: service_type.to_owned(),
+                    kind: kind(service_type).map(str::to_owned),
+                    host: None,
+                    port: None,
+                    addresses: vec![],
+                    txt: vec![],
+                    announcer,
+                    first_seen: time,
+                    last_seen: time,
+                });
+        service.announcer = announcer;
+        service.last_seen = service.last_seen.max(time);
+
+        service
+    }
+
+    /// Get the service of an instance named by a SRV or TXT record, adding it if new
+    ///
+    /// The service type follows the first label of the instance, which may contain dots.
+    fn instance(
+        &mut self,
+        instance: &str,
+        announcer: IpAddr,
+        time: i64,
+    ) -> Option<&mut DiscoveredService> {
+        let service_type = match self.services.get(instance) {
+            Some(service) => service.service_type.clone(),
+            None => instance
+                .split_once("._")
+                .map(|(_, rest)| format!("_{}", rest))?,
+        };
+
+        Some(self.service(instance, &service_type, announcer, time))
+    }
+
+    fn address(&mut self, host: &str, address: IpAddr, ttl: u32) {
+        let addresses = self.addresses.entry(host.to_owned()).or_default();
+        if ttl == 0 {
+            addresses.remove(&address);
+        } else {
+            addresses.insert(address);
+        }
+    }
+
+    /// Get the services advertised, sorted by instance
+    pub fn services(&self) -> Vec<DiscoveredService> {
+        self.services
+            .values()
+            .map(|service| {
+                let mut service = service.clone();
+                if let Some(addresses) = service
+                    .host
+                    .as_ref()
+                    .and_then(|host| self.addresses.get(host))
+                {
+                    service.addresses = addresses.iter().copied().collect();
+                }
+                service
+            })
+            .collect()
+    }
+
+    pub fn clear(&mut self) {
+        self.services.clear();
+        self.addresses.clear();
+    }
+}
+
+/// Get the kind of device advertising a service type, if well-known
+fn kind(service_type: &str) -> Option<&'static str> {
+    let name = service_type.split('.').next()?;
+
+    KINDS
+        .iter()
+        .find(|(service, _)| *service == name)
+        .map(|(_, kind)| *kind)
+}
+
+/// Returns the services advertised on the local network with DNS-SD, sorted by instance
+#[tauri::command]
+pub fn get_discovered_services(state: tauri::State<SniffingState>) -> Vec<DiscoveredService> {
+    state.packets.lock().unwrap().service_discovery.services()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr};
+
+    use pnet::packet::ethernet::EthernetPacket;
+    use sniffer_parser::parse_ethernet_frame;
+    use sniffer_parser::serializable_packet::ParsedPacket;
+
+    use super::ServiceDiscovery;
+    use crate::test_utils::at;
+
+    const PRINTER: [u8; 4] = [192, 168, 1, 20];
+
+    #[test]
+    fn printer_advertisement() {
+        let mut discovery = ServiceDiscovery::new();
+
+        discovery.update(
+            &mdns_response(
+                PRINTER,
+                &[
+                    record(
+                        &["_ipp", "_tcp", "local"],
+                        12,
+                        4500,
+                        &name(&["Office", "_ipp", "_tcp", "local"]),
+                    ),
+                    record(
+                        &["Office", "_ipp", "_tcp", "local"],
+                        33,
+                        120,
+                        &[&[0, 0, 0, 0, 0x02, 0x77][..], &name(&["printer", "local"])].concat(),
+                    ),
+                    record(
+                        &["Office", "_ipp", "_tcp", "local"],
+                        16,
+                        4500,
+                        b"\x06rp=ipp\x07color=T",
+                    ),
+                    record(&["printer", "local"], 1, 120, &PRINTER),
+                ],
+            ),
+            at(0),
+        );
+        // Enumeration of the service types, and a reverse lookup
+        discovery.update(
+            &mdns_response(
+                PRINTER,
+                &[
+                    record(
+                        &["_services", "_dns-sd", "_udp", "local"],
+                        12,
+                        4500,
+                        &name(&["_ipp", "_tcp", "local"]),
+                    ),
+                    record(
+                        &["20", "1", "168", "192", "in-addr", "arpa"],
+                        12,
+                        120,
+                        &name(&["printer", "local"]),
+                    ),
+                ],
+            ),
+            at(1000),
+        );
+
+        let services = discovery.services();
+        assert_eq!(services.len(), 1);
+        let service = &services[0];
+        assert_eq!(service.instance, "Office._ipp._tcp.local");
+        assert_eq!(service.service_type, "_ipp._tcp.local");
+        assert_eq!(service.kind.as_deref(), Some("Printer"));
+        assert_eq!(service.host.as_deref(), Some("printer.local"));
+        assert_eq!(service.port, Some(631));
+        assert_eq!(service.addresses, vec![IpAddr::V4(Ipv4Addr::from(PRINTER))]);
+        assert_eq!(service.txt, vec!["rp=ipp", "color=T"]);
+        assert_eq!(service.last_seen, at(0).timestamp_millis());
+
+        discovery.clear();
+        assert!(discovery.services().is_empty());
+    }
+
+    #[test]
+    fn goodbye_removes_service() {
+        let mut discovery = ServiceDiscovery::new();
+        let instance = name(&["Living Room", "_googlecast", "_tcp", "local"]);
+
+        discovery.update(
+            &mdns_response(
+                PRINTER,
+                &[record(
+                    &["_googlecast", "_tcp", "local"],
+                    12,
+                    120,
+                    &instance,
+                )],
+            ),
+            at(0),
+        );
+        assert_eq!(discovery.services()[0].kind.as_deref(), Some("Chromecast"));
+
+        discovery.update(
+            &mdns_response(
+                PRINTER,
+                &[record(&["_googlecast", "_tcp", "local"], 12, 0, &instance)],
+            ),
+            at(1000),
+        );
+        assert!(discovery.services().is_empty());
+    }
+
+    ///////////////////// Utils
+
+    fn name(labels: &[&str]) -> Vec<u8> {
+        let mut name = vec![];
+        for label in labels {
+            name.push(label.len() as u8);
+            name.extend(label.as_bytes());
+        }
+        name.push(0);
+
+        name
+    }
+
+    /// Resource record of class IN, with the cache-flush bit clear
+    fn record(labels: &[&str], record_type: u16, ttl: u32, data: &[u8]) -> Vec<u8> {
+        let mut record = name(labels);
+        record.extend(record_type.to_be_bytes());
+        record.extend([0x00, 0x01]);
+        record.extend(ttl.to_be_bytes());
+        record.extend((data.len() as u16).to_be_bytes());
+        record.extend(data);
+
+        record
+    }
+
+    fn mdns_response(source: [u8; 4], answers: &[Vec<u8>]) -> ParsedPacket {
+        let mut dns = vec![0x00, 0x00, 0x84, 0x00, 0x00, 0x00];
+        dns.extend((answers.len() as u16).to_be_bytes());
+        dns.extend([0x00, 0x00, 0x00, 0x00]);
+        for answer in answers {
+            dns.extend(answer);
+        }
+        let udp_length = (8 + dns.len()) as u16;
+
+        let mut frame = vec![0x01, 0x00, 0x5e, 0x00, 0x00, 0xfb];
+        frame.extend([0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x08, 0x00]);
+        frame.extend([0x45, 0x00]);
+        frame.extend((20 + udp_length).to_be_bytes());
+        frame.extend([0x00, 0x00, 0x00, 0x00, 0xff, 0x11, 0x00, 0x00]);
+        frame.extend(source);
+        frame.extend([224, 0, 0, 251]);
+        frame.extend(5353u16.to_be_bytes());
+        frame.extend(5353u16.to_be_bytes());
+        frame.extend(udp_length.to_be_bytes());
+        frame.extend([0x00, 0x00]);
+        frame.extend(dns);
+
+        parse_ethernet_frame(&EthernetPacket::new(&frame).unwrap(), 0)
+    }
+}
@@ -0,0 +1,455 @@
+//! Traffic statistics of the collected packets
+//!
+//! Statistics are aggregated over all the collected packets and, when multiple interfaces are
+//! captured, broken down by the interface each packet was received from:
+//! - protocol hierarchy: packets and bytes of each stack of protocols (e.g. `IPv4/TCP/HTTP`)
+//! - top talkers: source addresses which sent the most bytes
+//! - throughput: packets and bytes received in each second
+//!
+//! Packets of offline captures belong to no interface, so they only count in the aggregate.
+//!
+//! When the received frames are sampled, the statistics refer to the sampled ones only, and report
+//! the ratio of the frames sampled out of all the ones received.
+//!
+//! When the packets are dissected only up to the transport layer, for long-running monitoring,
+//! the statistics still count all of them, although the packets themselves are not collected: the
+//! protocol hierarchy then stops at the transport protocols.
+
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::{DateTime, Local};
+use serde::Serialize;
+
+use crate::{SniffingError, SniffingState};
+
+/// Maximum number of talkers in a report
+const TOP_TALKERS: usize = 10;
+
+/// Time of the traffic returned to the frontend, as a Unix timestamp in milliseconds
+pub type Timestamp = i64;
+
+/// Amount of traffic
+#[derive(Serialize, Debug, Clone, Copy, Default, PartialEq)]
+pub struct Counters {
+    pub packets: usize,
+    pub bytes: usize,
+}
+
+impl Counters {
+    pub fn add(&mut self, bytes: usize) {
+        self.packets += 1;
+        self.bytes += bytes;
+    }
+}
+
+/// Traffic of a protocol stack
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ProtocolStatistics {
+    pub protocol: String,
+    #[serde(flatten)]
+    pub counters: Counters,
+}
+
+/// Traffic sent by an address
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct TalkerStatistics {
+    pub address: String,
+    #[serde(flatten)]
+    pub counters: Counters,
+}
+
+/// Traffic received in a second, identified by its Unix timestamp
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ThroughputSample {
+    pub second: i64,
+    #[serde(flatten)]
+    pub counters: Counters,
+}
+
+/// Statistics of an interface (or of all of them), as returned to the frontend
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct StatisticsReport {
+    /// Interface the statistics refer to, none for the aggregate
+    pub interface: Option<String>,
+    #[serde(flatten)]
+    pub counters: Counters,
+    /// Frames received, including the ones left out by the sampling
+    pub received_packets: usize,
+    /// Ratio of the received frames which were sampled
+    pub sampling_ratio: f64,
+    pub protocols: Vec<ProtocolStatistics>,
+    pub top_talkers: Vec<TalkerStatistics>,
+    pub throughput: Vec<ThroughputSample>,
+}
+
+/// Statistics of a set of packets
+#[derive(Debug, Default)]
+struct TrafficStatistics {
+    counters: Counters,
+    received: usize,
+    protocols: BTreeMap<String, Counters>,
+    talkers: HashMap<String, Counters>,
+    throughput: BTreeMap<i64, Counters>,
+}
+
+impl TrafficStatistics {
+    fn update(&mut self, protocols: &[String], source: &str, bytes: usize, time: DateTime<Local>) {
+        self.counters.add(bytes);
+        self.received += 1;
+
+        // Every prefix of the stack is a node of the hierarchy
+        for depth in 1..=protocols.len() {
+            self.protocols
+                .entry(protocols[..depth].join("/"))
+                .or_default()
+                .add(bytes);
+        }
+
+        if source != "-" {
+            self.talkers
+                .entry(source.to_owned())
+                .or_default()
+                .add(bytes);
+        }
+
+        self.throughput
+            .entry(time.timestamp())
+            .or_default()
+            .add(bytes);
+    }
+
+    /// Count a packet already counted with the first protocols of its stack for the deeper ones
+    fn update_upper_protocols(&mut self, protocols: &[String], counted: usize, bytes: usize) {
+        for depth in counted + 1..=protocols.len() {
+            self.protocols
+                .entry(protocols[..depth].join("/"))
+                .or_default()
+                .add(bytes);
+        }
+    }
+
+    fn report(&self, interface: Option<&str>) -> StatisticsReport {
+        let protocols = self
+            .protocols
+            .iter()
+            .map(|(protocol, counters)| ProtocolStatistics {
+                protocol: protocol.clone(),
+                counters: *counters,
+            })
+            .collect();
+
+        let mut top_talkers: Vec<TalkerStatistics> = self
+            .talkers
+            .iter()
+            .map(|(address, counters)| TalkerStatistics {
+                address: address.clone(),
+                counters: *counters,
+            })
+            .collect();
+        top_talkers.sort_by(|a, b| {
+            b.counters
+                .bytes
+                .cmp(&a.counters.bytes)
+                .then_with(|| a.address.cmp(&b.address))
+        });
+        top_talkers.truncate(TOP_TALKERS);
+
+        let throughput = self
+            .throughput
+            .iter()
+            .map(|(second, counters)| ThroughputSample {
+                second: *second,
+                counters: *counters,
+            })
+            .collect();
+
+        let sampling_ratio = match self.received {
+            0 => 1.0,
+            received => self.counters.packets as f64 / received as f64,
+        };
+
+        StatisticsReport {
+            interface: interface.map(str::to_owned),
+            counters: self.counters,
+            received_packets: self.received,
+            sampling_ratio,
+            protocols,
+            top_talkers,
+            throughput,
+        }
+    }
+}
+
+/// Statistics of the collected packets, in aggregate and for each interface
+#[derive(Debug, Default)]
+pub struct CaptureStatistics {
+    aggregate: TrafficStatistics,
+    interfaces: BTreeMap<String, TrafficStatistics>,
+}
+
+impl CaptureStatistics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Update the statistics with a packet received from an interface, given its protocols,
+    /// source address and size
+    pub fn update(
+        &mut self,
+        interface: Option<&str>,
+        protocols: &[String],
+        source: &str,
+        bytes: usize,
+        time: DateTime<Local>,
+    ) {
+        self.aggregate.update(protocols, source, bytes, time);
+
+        if let Some(interface) = interface {
+            self.interfaces
+                .entry(interface.to_owned())
+                .or_default()
+                .update(protocols, source, bytes, time);
+        }
+    }
+
+    /// Update the statistics with the protocols of a packet deeper than the first `counted` ones,
+    /// once its upper layers are parsed
+    pub fn update_upper_protocols(
+        &mut self,
+        interface: Option<&str>,
+        protocols: &[String],
+        counted: usize,
+        bytes: usize,
+    ) {
+        self.aggregate
+            .update_upper_protocols(protocols, counted, bytes);
+
+        if let Some(interface) = interface {
+            self.interfaces
+                .entry(interface.to_owned())
+                .or_default()
+                .update_upper_protocols(protocols, counted, bytes);
+        }
+    }
+
+    /// Count a frame received from an interface and left out by the sampling
+    pub fn skip(&mut self, interface: Option<&str>) {
+        self.aggregate.received += 1;
+
+        if let Some(interface) = interface {
+            self.interfaces
+                .entry(interface.to_owned())
+                .or_default()
+                .received += 1;
+        }
+    }
+
+    /// Get the statistics of an interface, or the aggregate ones when no interface is provided
+    pub fn report(&self, interface: Option<&str>) -> Option<StatisticsReport> {
+        match interface {
+            Some(interface) => self
+                .interfaces
+                .get(interface)
+                .map(|statistics| statistics.report(Some(interface))),
+            None => Some(self.aggregate.report(None)),
+        }
+    }
+
+    /// Get the statistics of each interface packets were received from
+    pub fn interfaces_report(&self) -> Vec<StatisticsReport> {
+        self.interfaces
+            .iter()
+            .map(|(interface, statistics)| statistics.report(Some(interface)))
+            .collect()
+    }
+
+    pub fn clear(&mut self) {
+        self.aggregate = TrafficStatistics::default();
+        self.interfaces.clear();
+    }
+}
+
+/// Get the statistics of the packets received from an interface, or of all the collected packets
+/// when no interface is provided
+#[tauri::command]
+pub fn get_statistics(
+    interface: Option<String>,
+    state: tauri::State<SniffingState>,
+) -> Result<StatisticsReport, SniffingError> {
+    let packets = state.packets.lock().unwrap();
+
+    packets
+        .statistics
+        .report(interface.as_deref())
+        .ok_or_else(|| {
+            SniffingError::InterfaceNotFound(format!(
+                "No packets received from {}",
+                interface.unwrap_or_default()
+            ))
+        })
+}
+
+/// Get the statistics of each interface packets were received from
+#[tauri::command]
+pub fn get_interfaces_statistics(state: tauri::State<SniffingState>) -> Vec<StatisticsReport> {
+    state.packets.lock().unwrap().statistics.interfaces_report()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Local, TimeZone};
+
+    use super::{CaptureStatistics, Counters};
+
+    #[test]
+    fn protocol_hierarchy() {
+        let mut statistics = CaptureStatistics::new();
+        statistics.update(
+            Some("eth0"),
+            &protocols(&["IPv4", "TCP", "HTTP"]),
+            "10.0.0.1",
+            100,
+            at(0),
+        );
+        statistics.update(
+            Some("eth0"),
+            &protocols(&["IPv4", "UDP"]),
+            "10.0.0.1",
+            50,
+            at(0),
+        );
+        statistics.update(None, &protocols(&["ARP"]), "-", 42, at(0));
+
+        let report = statistics.report(None).unwrap();
+        let hierarchy: Vec<(&str, Counters)> = report
+            .protocols
+            .iter()
+            .map(|protocol| (protocol.protocol.as_str(), protocol.counters))
+            .collect();
+
+        assert_eq!(
+            hierarchy,
+            vec![
+                ("ARP", counters(1, 42)),
+                ("IPv4", counters(2, 150)),
+                ("IPv4/TCP", counters(1, 100)),
+                ("IPv4/TCP/HTTP", counters(1, 100)),
+                ("IPv4/UDP", counters(1, 50)),
+            ]
+        );
+        assert_eq!(report.counters, counters(3, 192));
+    }
+
+    #[test]
+    fn upper_protocols() {
+        let mut statistics = CaptureStatistics::new();
+        let stack = protocols(&["IPv4", "TCP", "TLS"]);
+        statistics.update(Some("eth0"), &stack[..2], "10.0.0.1", 100, at(0));
+        statistics.update_upper_protocols(Some("eth0"), &stack, 2, 100);
+
+        let report = statistics.report(Some("eth0")).unwrap();
+        let hierarchy: Vec<(&str, Counters)> = report
+            .protocols
+            .iter()
+            .map(|protocol| (protocol.protocol.as_str(), protocol.counters))
+            .collect();
+
+        assert_eq!(
+            hierarchy,
+            vec![
+                ("IPv4", counters(1, 100)),
+                ("IPv4/TCP", counters(1, 100)),
+                ("IPv4/TCP/TLS", counters(1, 100)),
+            ]
+        );
+        assert_eq!(report.counters, counters(1, 100));
+    }
+
+    #[test]
+    fn statistics_per_interface() {
+        let mut statistics = CaptureStatistics::new();
+        statistics.update(Some("eth0"), &protocols(&["IPv4"]), "10.0.0.1", 100, at(0));
+        statistics.update(Some("wlan0"), &protocols(&["IPv6"]), "fe80::1", 200, at(0));
+        statistics.update(Some("eth0"), &protocols(&["IPv4"]), "10.0.0.2", 300, at(1));
+
+        let eth0 = statistics.report(Some("eth0")).unwrap();
+        assert_eq!(eth0.interface.as_deref(), Some("eth0"));
+        assert_eq!(eth0.counters, counters(2, 400));
+        assert_eq!(eth0.protocols.len(), 1);
+
+        let interfaces: Vec<Option<String>> = statistics
+            .interfaces_report()
+            .into_iter()
+            .map(|report| report.interface)
+            .collect();
+        assert_eq!(
+            interfaces,
+            vec![Some("eth0".to_owned()), Some("wlan0".to_owned())]
+        );
+
+        assert_eq!(statistics.report(None).unwrap().counters, counters(3, 600));
+        assert!(statistics.report(Some("lo")).is_none());
+    }
+
+    #[test]
+    fn top_talkers_and_throughput() {
+        let mut statistics = CaptureStatistics::new();
+        for i in 0..12 {
+            let source = format!("10.0.0.{}", i);
+            statistics.update(
+                Some("eth0"),
+                &protocols(&["IPv4"]),
+                &source,
+                10 * i,
+                at(i as i64 / 5),
+            );
+        }
+
+        let report = statistics.report(Some("eth0")).unwrap();
+        assert_eq!(report.top_talkers.len(), 10);
+        assert_eq!(report.top_talkers[0].address, "10.0.0.11");
+        assert_eq!(report.top_talkers[9].address, "10.0.0.2");
+
+        let throughput: Vec<Counters> = report
+            .throughput
+            .iter()
+            .map(|sample| sample.counters)
+            .collect();
+        assert_eq!(
+            throughput,
+            vec![counters(5, 100), counters(5, 350), counters(2, 210)]
+        );
+        assert_eq!(report.throughput[1].second - report.throughput[0].second, 1);
+    }
+
+    #[test]
+    fn sampling_ratio() {
+        let mut statistics = CaptureStatistics::new();
+        assert_eq!(statistics.report(None).unwrap().sampling_ratio, 1.0);
+
+        statistics.update(Some("eth0"), &protocols(&["IPv4"]), "10.0.0.1", 100, at(0));
+        for _ in 0..3 {
+            statistics.skip(Some("eth0"));
+        }
+
+        let report = statistics.report(Some("eth0")).unwrap();
+        assert_eq!(report.counters, counters(1, 100));
+        assert_eq!(report.received_packets, 4);
+        assert_eq!(report.sampling_ratio, 0.25);
+    }
+
+    ///////////////////// Utils
+
+    fn protocols(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    fn counters(packets: usize, bytes: usize) -> Counters {
+        Counters { packets, bytes }
+    }
+
+    fn at(seconds: i64) -> chrono::DateTime<Local> {
+        Local.timestamp_opt(1_700_000_000 + seconds, 0).unwrap()
+    }
+}
@@ -0,0 +1,264 @@
+//! Reassembled byte streams of the TCP conversations ("follow TCP stream")
+//!
+//! The bytes handed over by the TCP reassembly to the application-layer parsers are kept for each
+//! TCP conversation, as chunks in the order they were captured, each one marked with the direction
+//! it was sent in: retransmitted bytes appear once, and the bytes given up on by the reassembly
+//! are marked as missing before the chunk following them. Up to `MAX_STREAM_BYTES` are kept for
+//! each conversation; later bytes are only counted.
+//!
+//! The streams are rendered both raw (hexadecimal) and printable (ASCII, other bytes replaced
+//! by dots), interleaved as chunks and as the whole stream sent in each direction.
+
+use std::net::IpAddr;
+
+use serde::Serialize;
+use sniffer_parser::serializable_packet::bytes::FrameBytes;
+
+use crate::{SniffingError, SniffingState};
+
+/// Bytes kept for the stream of each TCP conversation
+const MAX_STREAM_BYTES: usize = 4 << 20;
+
+/// Direction of a chunk of a TCP stream
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum StreamDirection {
+    /// Sent by the initiator of the conversation
+    ClientToServer,
+    ServerToClient,
+}
+
+/// Bytes of a TCP stream handed over with a packet
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct StreamChunk {
+    direction: StreamDirection,
+    packet_id: usize,
+    /// Bytes missing before the chunk
+    missing: Option<u32>,
+    /// Shared with the frame of the packet, when not reassembled
+    data: FrameBytes,
+}
+
+/// Bytes of the TCP stream of a conversation, in both directions
+#[derive(Debug, Default)]
+pub struct TcpStreamData {
+    chunks: Vec<StreamChunk>,
+    kept: usize,
+    /// Bytes beyond the ones kept
+    dropped: usize,
+}
+
+impl TcpStreamData {
+    /// Append the bytes handed over with a packet, as far as the stream has room for them
+    pub fn append(
+        &mut self,
+        direction: StreamDirection,
+        packet_id: usize,
+        missing: Option<u32>,
+        mut data: FrameBytes,
+    ) {
+        let room = MAX_STREAM_BYTES - self.kept;
+        if data.len() > room {
+            self.dropped += data.len() - room;
+            data.truncate(room);
+        }
+        if data.is_empty() {
+            return;
+        }
+
+        self.kept += data.len();
+        self.chunks.push(StreamChunk {
+            direction,
+            packet_id,
+            missing,
+            data,
+        });
+    }
+
+    /// Render the stream, given the conversation it belongs to
+    pub fn render(
+        &self,
+        connection_id: usize,
+        client: (IpAddr, Option<u16>),
+        server: (IpAddr, Option<u16>),
+    ) -> FollowedStream {
+        let chunks = self
+            .chunks
+            .iter()
+            .map(|chunk| RenderedChunk {
+                direction: chunk.direction,
+                packet_id: chunk.packet_id,
+                missing: chunk.missing,
+                raw: to_hex(&chunk.data),
+                printable: to_printable(&chunk.data),
+            })
+            .collect();
+
+        let direction = |direction: StreamDirection| {
+            let data: Vec<u8> = self
+                .chunks
+                .iter()
+                .filter(|chunk| chunk.direction == direction)
+                .flat_map(|chunk| chunk.data.iter().copied())
+                .collect();
+            RenderedStream {
+                bytes: data.len(),
+                raw: to_hex(&data),
+                printable: to_printable(&data),
+            }
+        };
+
+        FollowedStream {
+            connection_id,
+            client: client.0,
+            client_port: client.1,
+            server: server.0,
+            server_port: server.1,
+            chunks,
+            client_to_server: direction(StreamDirection::ClientToServer),
+            server_to_client: direction(StreamDirection::ServerToClient),
+            dropped: self.dropped,
+        }
+    }
+}
+
+/// Chunk of a TCP stream, as returned to the frontend
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct RenderedChunk {
+    pub direction: StreamDirection,
+    /// Packet the chunk was handed over with
+    pub packet_id: usize,
+    pub missing: Option<u32>,
+    /// Bytes of the chunk, hexadecimal
+    pub raw: String,
+    pub printable: String,
+}
+
+/// Bytes sent in a direction of a TCP stream, as returned to the frontend
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct RenderedStream {
+    pub bytes: usize,
+    pub raw: String,
+    pub printable: String,
+}
+
+/// TCP stream of a conversation, as returned to the frontend
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct FollowedStream {
+    pub connection_id: usize,
+    pub client: IpAddr,
+    pub client_port: Option<u16>,
+    pub server: IpAddr,
+    pub server_port: Option<u16>,
+    /// Chunks of both directions, in the order they were captured
+    pub chunks: Vec<RenderedChunk>,
+    pub client_to_server: RenderedStream,
+    pub server_to_client: RenderedStream,
+    /// Bytes of the stream beyond the ones kept
+    pub dropped: usize,
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Render bytes as ASCII text, keeping the line breaks and tabs, other bytes replaced by dots
+fn to_printable(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|&byte| match byte {
+            b'\r' | b'\n' | b'\t' | 0x20..=0x7e => byte as char,
+            _ => '.',
+        })
+        .collect()
+}
+
+/// Get the reassembled byte streams of a TCP conversation, given its id
+#[tauri::command]
+pub fn follow_stream(
+    connection_id: usize,
+    state: tauri::State<SniffingState>,
+) -> Result<FollowedStream, SniffingError> {
+    state
+        .packets
+        .lock()
+        .unwrap()
+        .conversations
+        .follow_stream(connection_id)
+        .ok_or_else(|| {
+            SniffingError::StreamNotFound(format!("No TCP conversation with id {}", connection_id))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr};
+
+    use super::{StreamDirection, TcpStreamData, MAX_STREAM_BYTES};
+
+    #[test]
+    fn rendered_stream() {
+        let mut stream = TcpStreamData::default();
+        stream.append(
+            StreamDirection::ClientToServer,
+            1,
+            None,
+            b"GET / HTTP/1.1\r\n\r\n".to_vec().into(),
+        );
+        stream.append(
+            StreamDirection::ServerToClient,
+            2,
+            None,
+            b"HTTP/1.1 ".to_vec().into(),
+        );
+        stream.append(
+            StreamDirection::ServerToClient,
+            4,
+            Some(3),
+            vec![0x00, b'O', b'K'].into(),
+        );
+
+        let client = (IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)), Some(50000));
+        let server = (IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2)), Some(80));
+        let followed = stream.render(7, client, server);
+
+        assert_eq!(followed.connection_id, 7);
+        assert_eq!(followed.server_port, Some(80));
+        assert_eq!(followed.chunks.len(), 3);
+        assert_eq!(
+            followed.chunks[2].direction,
+            StreamDirection::ServerToClient
+        );
+        assert_eq!(followed.chunks[2].missing, Some(3));
+        assert_eq!(followed.chunks[2].raw, "004f4b");
+        assert_eq!(followed.chunks[2].printable, ".OK");
+        assert_eq!(
+            followed.client_to_server.printable,
+            "GET / HTTP/1.1\r\n\r\n"
+        );
+        assert_eq!(followed.server_to_client.bytes, 12);
+        assert_eq!(followed.server_to_client.printable, "HTTP/1.1 .OK");
+        assert_eq!(followed.dropped, 0);
+    }
+
+    #[test]
+    fn bytes_beyond_the_limit_are_counted() {
+        let mut stream = TcpStreamData::default();
+        stream.append(
+            StreamDirection::ClientToServer,
+            1,
+            None,
+            vec![0; MAX_STREAM_BYTES - 10].into(),
+        );
+        stream.append(StreamDirection::ServerToClient, 2, None, vec![0; 30].into());
+        stream.append(StreamDirection::ClientToServer, 3, None, vec![0; 5].into());
+
+        assert_eq!(stream.kept, MAX_STREAM_BYTES);
+        assert_eq!(stream.dropped, 25);
+        assert_eq!(stream.chunks.len(), 2);
+        assert_eq!(stream.chunks[1].data.len(), 10);
+    }
+}
@@ -0,0 +1,402 @@
+//! Support of the TCP extensions negotiated during the handshakes, for each server
+//!
+//! The SYN and SYN-ACK segments of the handshakes are inspected for:
+//! - ECN: a SYN with ECE and CWR set requests it, a SYN-ACK with ECE set and CWR clear accepts it
+//! - TCP Fast Open: a SYN with the TFO option requests a cookie (empty option) or sends data with
+//!   a cookie, a SYN-ACK with a cookie grants it, and the data of the SYN is accepted when the
+//!   SYN-ACK acknowledges it
+//!
+//! The TFO option is recognized both with its IANA kind (34) and with the experimental one (254,
+//! magic 0xF989). A server is the endpoint receiving the SYN, identified by its address and port.
+//! The support rate of each extension is the ratio of the requests a server accepted.
+
+use std::collections::{BTreeMap, HashMap};
+use std::net::IpAddr;
+
+use pnet::packet::tcp::TcpFlags;
+use serde::Serialize;
+use sniffer_parser::serializable_packet::transport::SerializableTcpPacket;
+use sniffer_parser::serializable_packet::{ParsedPacket, SerializablePacket};
+use sniffer_parser::{FlowDirection, FlowKey};
+
+use crate::SniffingState;
+
+/// Kind of the TCP Fast Open option
+const TFO_OPTION: u8 = 34;
+
+/// Kind and magic of the experimental TCP Fast Open option
+const TFO_EXPERIMENTAL_OPTION: u8 = 254;
+const TFO_EXPERIMENTAL_MAGIC: [u8; 2] = [0xf9, 0x89];
+
+/// Support of the TCP extensions by a server, as returned to the frontend
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ServerFeatures {
+    pub server: IpAddr,
+    pub port: u16,
+    /// SYN segments received
+    pub syns: usize,
+    /// SYN-ACK segments sent
+    pub syn_acks: usize,
+    /// SYN segments requesting ECN
+    pub ecn_requested: usize,
+    /// SYN-ACK segments accepting ECN
+    pub ecn_accepted: usize,
+    /// SYN segments carrying the TFO option, with or without a cookie
+    pub tfo_requested: usize,
+    /// SYN-ACK segments granting a TFO cookie
+    pub tfo_cookies: usize,
+    /// SYN segments carrying data with a TFO cookie
+    pub tfo_data_sent: usize,
+    /// SYN-ACK segments acknowledging the data of the SYN
+    pub tfo_data_accepted: usize,
+    /// Ratio of the ECN requests accepted, none without requests
+    pub ecn_support_rate: Option<f64>,
+    /// Ratio of the TFO requests granted a cookie, none without requests
+    pub tfo_support_rate: Option<f64>,
+}
+
+/// TCP Fast Open option of a segment
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FastOpen {
+    /// Request of a cookie, without one
+    CookieRequest,
+    /// Cookie of the given length
+    Cookie(usize),
+}
+
+/// SYN waiting for its SYN-ACK, with the bytes of data it carried with a TFO cookie
+#[derive(Debug, Clone, Copy)]
+struct PendingSyn {
+    sequence: u32,
+    tfo_data: usize,
+}
+
+/// Support of the TCP extensions by the servers of the collected packets
+#[derive(Debug, Default)]
+pub struct TcpFeatureTracker {
+    servers: BTreeMap<(IpAddr, u16), ServerFeatures>,
+    /// SYNs of each handshake, keyed by the direction they are sent in
+    pending: HashMap<(FlowKey, FlowDirection), PendingSyn>,
+}
+
+impl TcpFeatureTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Inspect a packet, if it is a segment of a TCP handshake
+    pub fn update(&mut self, packet: &ParsedPacket) {
+        let (source, destination) = match packet.get_network_layer_packet() {
+            Some(SerializablePacket::Ipv4Packet(ipv4)) => {
+                (IpAddr::V4(ipv4.source), IpAddr::V4(ipv4.destination))
+            }
+            Some(SerializablePacket::Ipv6Packet(ipv6)) => {
+                (IpAddr::V6(ipv6.source), IpAddr::V6(ipv6.destination))
+            }
+            _ => return,
+        };
+        let tcp = match packet.get_transport_layer_packet() {
+            Some(SerializablePacket::TcpPacket(tcp)) if tcp.flags & TcpFlags::SYN != 0 => tcp,
+            _ => return,
+        };
+
+        let client = (source, tcp.source);
+        let server = (destination, tcp.destination);
+        if tcp.flags & TcpFlags::ACK == 0 {
+            self.syn(tcp, client, server);
+        } else {
+            // The SYN-ACK is sent by the server
+            self.syn_ack(tcp, server, client);
+        }
+    }
+
+    fn syn(&mut self, tcp: &SerializableTcpPacket, client: (IpAddr, u16), server: (IpAddr, u16)) {
+        let features = self.server(server);
+        features.syns += 1;
+
+        let ecn = TcpFlags::ECE | TcpFlags::CWR;
+        if tcp.flags & ecn == ecn {
+            features.ecn_requested += 1;
+        }
+
+        let option = fast_open(&tcp.options);
+        if option.is_some() {
+            features.tfo_requested += 1;
+        }
+        let tfo_data = match option {
+            Some(FastOpen::Cookie(_)) if tcp.length > 0 => {
+                features.tfo_data_sent += 1;
+                tcp.length
+            }
+            _ => 0,
+        };
+
+        self.pending.insert(
+            FlowKey::of(client, server),
+            PendingSyn {
+                sequence: tcp.sequence,
+                tfo_data,
+            },
+        );
+    }
+
+    fn syn_ack(
+        &mut self,
+        tcp: &SerializableTcpPacket,
+        server: (IpAddr, u16),
+        client: (IpAddr, u16),
+    ) {
+        // Retransmitted SYN-ACKs find no SYN waiting
+        let pending = self.pending.remove(&FlowKey::of(client, server));
+
+        let features = self.server(server);
+        features.syn_acks += 1;
+
+        if tcp.flags & TcpFlags::ECE != 0 && tcp.flags & TcpFlags::CWR == 0 {
+            features.ecn_accepted += 1;
+        }
+
+        if let Some(FastOpen::Cookie(_)) = fast_open(&tcp.options) {
+            features.tfo_cookies += 1;
+        }
+
+        if let Some(pending) = pending.filter(|pending| pending.tfo_data > 0) {
+            let acknowledged = tcp.acknowledgement.wrapping_sub(pending.sequence) as usize;
+            if acknowledged == pending.tfo_data + 1 {
+                features.tfo_data_accepted += 1;
+            }
+        }
+    }
+
+    fn server(&mut self, server: (IpAddr, u16)) -> &mut ServerFeatures {
+        self.servers
+            .entry(server)
+            .or_insert_with(|| ServerFeatures {
+                server: server.0,
+                port: server.1,
+                syns: 0,
+                syn_acks: 0,
+                ecn_requested: 0,
+                ecn_accepted: 0,
+                tfo_requested: 0,
+                tfo_cookies: 0,
+                tfo_data_sent: 0,
+                tfo_data_accepted: 0,
+                ecn_support_rate: None,
+                tfo_support_rate: None,
+            })
+    }
+
+    /// Get the support of the TCP extensions by each server, sorted by address and port
+    pub fn features(&self) -> Vec<ServerFeatures> {
+        self.servers
+            .values()
+            .map(|features| ServerFeatures {
+                ecn_support_rate: rate(features.ecn_accepted, features.ecn_requested),
+                tfo_support_rate: rate(features.tfo_cookies, features.tfo_requested),
+                ..features.clone()
+            })
+            .collect()
+    }
+
+    pub fn clear(&mut self) {
+        self.servers.clear();
+        self.pending.clear();
+    }
+}
+
+fn rate(accepted: usize, requested: usize) -> Option<f64> {
+    (requested > 0).then(|| accepted.min(requested) as f64 / requested as f64)
+}
+
+/// Get the TCP Fast Open option among the options of a segment, if any
+fn fast_open(options: &[u8]) -> Option<FastOpen> {
+    let mut i = 0;
+    while i < options.len() {
+        let kind = options[i];
+        match kind {
+            // End of options
+            0 => return None,
+            // No operation
+            1 => {
+                i += 1;
+                continue;
+            }
+            _ => (),
+        }
+
+        let length = *options.get(i + 1)? as usize;
+        if length < 2 || i + length > options.len() {
+            return None;
+        }
+        let data = &options[i + 2..i + length];
+
+        let cookie = match kind {
+            TFO_OPTION => Some(data.len()),
+            TFO_EXPERIMENTAL_OPTION if data.starts_with(&TFO_EXPERIMENTAL_MAGIC) => {
+                Some(data.len() - TFO_EXPERIMENTAL_MAGIC.len())
+            }
+            _ => None,
+        };
+        match cookie {
+            Some(0) => return Some(FastOpen::CookieRequest),
+            Some(length) => return Some(FastOpen::Cookie(length)),
+            None => i += length,
+        }
+    }
+
+    None
+}
+
+/// Returns the support of the TCP extensions negotiated during the handshakes by each server
+#[tauri::command]
+pub fn get_tcp_features(state: tauri::State<SniffingState>) -> Vec<ServerFeatures> {
+    state.packets.lock().unwrap().tcp_features.features()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr};
+
+    use pnet::packet::ethernet::EthernetPacket;
+    use pnet::packet::tcp::TcpFlags;
+    use sniffer_parser::parse_ethernet_frame;
+    use sniffer_parser::serializable_packet::ParsedPacket;
+
+    use super::{fast_open, FastOpen, ServerFeatures, TcpFeatureTracker};
+    use crate::test_utils::ip_frame;
+
+    const CLIENT: [u8; 4] = [10, 10, 10, 10];
+    const SERVER: [u8; 4] = [11, 11, 11, 11];
+
+    #[test]
+    fn fast_open_options() {
+        // MSS, NOP, NOP, TFO cookie request
+        assert_eq!(
+            fast_open(&[2, 4, 0x05, 0xb4, 1, 1, 34, 2]),
+            Some(FastOpen::CookieRequest)
+        );
+        assert_eq!(
+            fast_open(&[34, 10, 1, 2, 3, 4, 5, 6, 7, 8]),
+            Some(FastOpen::Cookie(8))
+        );
+        assert_eq!(
+            fast_open(&[254, 12, 0xf9, 0x89, 1, 2, 3, 4, 5, 6, 7, 8]),
+            Some(FastOpen::Cookie(8))
+        );
+        // Other experimental options, end of options, truncated options
+        assert_eq!(fast_open(&[254, 4, 0x12, 0x34]), None);
+        assert_eq!(fast_open(&[0, 34, 2]), None);
+        assert_eq!(fast_open(&[34, 10, 1, 2]), None);
+        assert_eq!(fast_open(&[2]), None);
+    }
+
+    #[test]
+    fn negotiated_features() {
+        let mut tracker = TcpFeatureTracker::new();
+        let ecn = TcpFlags::ECE | TcpFlags::CWR;
+        let syn_ack = TcpFlags::SYN | TcpFlags::ACK;
+
+        // ECN and a TFO cookie requested and granted
+        tracker.update(&tcp_packet(
+            (CLIENT, 40000),
+            (SERVER, 443),
+            TcpFlags::SYN | ecn,
+            (100, 0),
+            &[34, 2, 0, 0],
+            0,
+        ));
+        tracker.update(&tcp_packet(
+            (SERVER, 443),
+            (CLIENT, 40000),
+            syn_ack | TcpFlags::ECE,
+            (500, 101),
+            &[34, 6, 1, 2, 3, 4, 1, 1],
+            0,
+        ));
+
+        // Data sent with the cookie and accepted, ECN refused
+        tracker.update(&tcp_packet(
+            (CLIENT, 40001),
+            (SERVER, 443),
+            TcpFlags::SYN | ecn,
+            (200, 0),
+            &[34, 6, 1, 2, 3, 4, 1, 1],
+            10,
+        ));
+        tracker.update(&tcp_packet(
+            (SERVER, 443),
+            (CLIENT, 40001),
+            syn_ack,
+            (600, 211),
+            &[],
+            0,
+        ));
+
+        // Data sent with the cookie and not accepted
+        tracker.update(&tcp_packet(
+            (CLIENT, 40002),
+            (SERVER, 443),
+            TcpFlags::SYN,
+            (300, 0),
+            &[34, 6, 1, 2, 3, 4, 1, 1],
+            10,
+        ));
+        tracker.update(&tcp_packet(
+            (SERVER, 443),
+            (CLIENT, 40002),
+            syn_ack,
+            (700, 301),
+            &[],
+            0,
+        ));
+
+        assert_eq!(
+            tracker.features(),
+            vec![ServerFeatures {
+                server: IpAddr::V4(Ipv4Addr::from(SERVER)),
+                port: 443,
+                syns: 3,
+                syn_acks: 3,
+                ecn_requested: 2,
+                ecn_accepted: 1,
+                tfo_requested: 3,
+                tfo_cookies: 1,
+                tfo_data_sent: 2,
+                tfo_data_accepted: 1,
+                ecn_support_rate: Some(0.5),
+                tfo_support_rate: Some(1.0 / 3.0),
+            }]
+        );
+
+        tracker.clear();
+        assert!(tracker.features().is_empty());
+    }
+
+    ///////////////////// Utils
+
+    fn tcp_packet(
+        source: ([u8; 4], u16),
+        destination: ([u8; 4], u16),
+        flags: u16,
+        (sequence, acknowledgement): (u32, u32),
+        options: &[u8],
+        payload: usize,
+    ) -> ParsedPacket {
+        let tcp_length = 20 + options.len();
+
+        let mut segment = vec![];
+        segment.extend(source.1.to_be_bytes());
+        segment.extend(destination.1.to_be_bytes());
+        segment.extend(sequence.to_be_bytes());
+        segment.extend(acknowledgement.to_be_bytes());
+        segment.extend(((((tcp_length / 4) as u16) << 12) | flags).to_be_bytes());
+        segment.extend([0xff, 0xff, 0x00, 0x00, 0x00, 0x00]);
+        segment.extend(options);
+        segment.extend(vec![0x00; payload]);
+
+        let frame = ip_frame(source.0, destination.0, 0x06, &segment);
+        parse_ethernet_frame(&EthernetPacket::new(&frame).unwrap(), 0)
+    }
+}
@@ -0,0 +1,86 @@
+//! Helpers shared by the tests of the modules, building the times and the frames they are fed with
+//!
+//! Frames are Ethernet frames sent by 00:00:00:00:00:01 to the broadcast address, carrying an IPv4
+//! or IPv6 packet with a TTL of 64 and no checksums.
+
+use std::net::IpAddr;
+
+use chrono::{DateTime, Local, TimeZone};
+use pnet::packet::ethernet::EthernetPacket;
+use sniffer_parser::parse_ethernet_frame;
+use sniffer_parser::serializable_packet::ParsedPacket;
+
+/// Local time of the given milliseconds after the instant the tests start from
+pub fn at(milliseconds: i64) -> DateTime<Local> {
+    Local
+        .timestamp_millis_opt(1_700_000_000_000 + milliseconds)
+        .unwrap()
+}
+
+/// Ethernet frame carrying the payload of the given protocol between two addresses of the same
+/// family
+pub fn ip_frame(
+    source: impl Into<IpAddr>,
+    destination: impl Into<IpAddr>,
+    protocol: u8,
+    payload: &[u8],
+) -> Vec<u8> {
+    let mut frame = vec![0xff; 6];
+    frame.extend([0x00, 0x00, 0x00, 0x00, 0x00, 0x01]);
+    match (source.into(), destination.into()) {
+        (IpAddr::V4(source), IpAddr::V4(destination)) => {
+            frame.extend([0x08, 0x00, 0x45, 0x00]);
+            frame.extend((20 + payload.len() as u16).to_be_bytes());
+            frame.extend([0x00, 0x00, 0x00, 0x00, 0x40, protocol, 0x00, 0x00]);
+            frame.extend(source.octets());
+            frame.extend(destination.octets());
+        }
+        (IpAddr::V6(source), IpAddr::V6(destination)) => {
+            frame.extend([0x86, 0xdd, 0x60, 0x00, 0x00, 0x00]);
+            frame.extend((payload.len() as u16).to_be_bytes());
+            frame.extend([protocol, 0x40]);
+            frame.extend(source.octets());
+            frame.extend(destination.octets());
+        }
+        _ => panic!("Addresses of different families"),
+    }
+    frame.extend(payload);
+
+    frame
+}
+
+/// TCP segment with the given flags and no options nor payload
+pub fn tcp_packet(
+    id: usize,
+    (source, source_port): (impl Into<IpAddr>, u16),
+    (destination, destination_port): (impl Into<IpAddr>, u16),
+    flags: u16,
+) -> ParsedPacket {
+    let mut segment = vec![];
+    segment.extend(source_port.to_be_bytes());
+    segment.extend(destination_port.to_be_bytes());
+    segment.extend([0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00]);
+    segment.extend(((5 << 12) | flags).to_be_bytes());
+    segment.extend([0xff, 0xff, 0x00, 0x00, 0x00, 0x00]);
+
+    let frame = ip_frame(source, destination, 0x06, &segment);
+    parse_ethernet_frame(&EthernetPacket::new(&frame).unwrap(), id)
+}
+
+/// UDP datagram carrying the given payload
+pub fn udp_packet(
+    id: usize,
+    (source, source_port): (impl Into<IpAddr>, u16),
+    (destination, destination_port): (impl Into<IpAddr>, u16),
+    payload: &[u8],
+) -> ParsedPacket {
+    let mut datagram = vec![];
+    datagram.extend(source_port.to_be_bytes());
+    datagram.extend(destination_port.to_be_bytes());
+    datagram.extend((8 + payload.len() as u16).to_be_bytes());
+    datagram.extend([0x00, 0x00]);
+    datagram.extend(payload);
+
+    let frame = ip_frame(source, destination, 0x11, &datagram);
+    parse_ethernet_frame(&EthernetPacket::new(&frame).unwrap(), id)
+}
@@ -0,0 +1,346 @@
+//! Inference of the service reached by a TLS flow
+//!
+//! The ClientHello of a TLS connection, or of the Initial packets of QUIC, names the server in its
+//! SNI extension, unless the client leaves it out or encrypts it (ESNI, ECH). The service reached
+//! is then told by the other names known for the flow:
+//! - the names resolved to the address of the server, from the captured DNS answers
+//! - the DNS names the leaf certificate of the server is valid for (its SANs), readable until
+//!   TLS 1.3 encrypts the Certificate message
+//!
+//! The most likely service is inferred with a confidence level:
+//! - high: a name confirmed by another source (e.g. the SNI covered by the certificate)
+//! - medium: the SNI alone, or the only name resolved to the server
+//! - low: a name contradicted by the certificate, one of several names sharing the address of the
+//!   server (e.g. a CDN), or only a name of the certificate
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+
+use serde::Serialize;
+use sniffer_parser::serializable_packet::application::{
+    CustomResourceData, SerializableTlsHandshakePacket,
+};
+use sniffer_parser::serializable_packet::{ParsedPacket, SerializablePacket};
+
+/// Names kept for each resolved address, the most recent first
+const NAMES_PER_ADDRESS: usize = 8;
+
+/// Confidence in the service inferred for a TLS flow
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum DestinationConfidence {
+    High,
+    Medium,
+    Low,
+}
+
+/// Source of a name of the service reached by a TLS flow
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum DestinationSource {
+    /// SNI extension of the ClientHello
+    ServerName,
+    /// DNS answers resolving the address of the server
+    Dns,
+    /// Subject alternative names of the certificate of the server
+    Certificate,
+}
+
+/// Service most likely reached by a TLS flow
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct TlsDestination {
+    pub service: String,
+    pub confidence: DestinationConfidence,
+    /// Sources naming the service
+    pub sources: Vec<DestinationSource>,
+}
+
+/// Names resolved to each address by the captured DNS answers
+#[derive(Debug, Default)]
+pub struct DnsNames {
+    names: HashMap<IpAddr, Vec<String>>,
+}
+
+impl DnsNames {
+    /// Learn the names resolved by a packet, if it is a DNS response
+    ///
+    /// Both the name queried and the one owning the address, at the end of any CNAME chain, are
+    /// kept: the first is the one the client asked for.
+    pub fn update(&mut self, packet: &ParsedPacket) {
+        let dns = match packet.get_application_layer_packet() {
+            Some(SerializablePacket::DnsPacket(dns)) if !dns.header.query => dns,
+            _ => return,
+        };
+
+        for record in dns.answers.iter().chain(&dns.additional) {
+            let address = match &record.data {
+                CustomResourceData::A(a) => IpAddr::V4(a.address),
+                CustomResourceData::AAAA(aaaa) => IpAddr::V6(aaaa.address),
+                _ => continue,
+            };
+            self.add(address, &record.name);
+            for question in &dns.questions {
+                self.add(address, &question.query_name);
+            }
+        }
+    }
+
+    /// Add a name resolved to an address, as its most recent one
+    fn add(&mut self, address: IpAddr, name: &str) {
+        let name = name.trim_end_matches('.').to_ascii_lowercase();
+        let names = self.names.entry(address).or_default();
+        names.retain(|known| *known != name);
+        names.insert(0, name);
+        names.truncate(NAMES_PER_ADDRESS);
+    }
+
+    /// Get the names resolved to an address, the most recent first
+    pub fn names(&self, address: &IpAddr) -> &[String] {
+        self.names.get(address).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn clear(&mut self) {
+        self.names.clear();
+    }
+}
+
+/// Names of the server found in the handshake of a TLS flow
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct TlsNames {
+    /// SNI of the ClientHello
+    server_name: Option<String>,
+    /// DNS names of the leaf certificate sent by the server
+    certificate_names: Vec<String>,
+}
+
+impl TlsNames {
+    /// Check if a packet belongs to a TLS flow, over TCP or QUIC
+    pub fn is_tls(packet: &ParsedPacket) -> bool {
+        match packet.get_application_layer_packet() {
+            Some(SerializablePacket::TlsPacket(_)) => true,
+            Some(SerializablePacket::QuicPacket(quic)) => quic.handshake.is_some(),
+            _ => false,
+        }
+    }
+
+    /// Learn the names carried by a packet of the flow, sent by the client or by the server
+    pub fn update(&mut self, packet: &ParsedPacket, from_client: bool) {
+        let (handshake, certificates) = match packet.get_application_layer_packet() {
+            Some(SerializablePacket::TlsPacket(tls)) => {
+                (tls.handshake.as_ref(), tls.certificates.as_slice())
+            }
+            Some(SerializablePacket::QuicPacket(quic)) => (quic.handshake.as_ref(), &[][..]),
+            _ => return,
+        };
+
+        if let Some(SerializableTlsHandshakePacket {
+            message_type,
+            server_name: Some(server_name),
+            ..
+        }) = handshake
+        {
+            if message_type == "ClientHello" {
+                self.server_name = Some(server_name.trim_end_matches('.').to_ascii_lowercase());
+            }
+        }
+
+        // Certificates sent by the client authenticate the client instead
+        if let (false, Some(leaf)) = (from_client, certificates.first()) {
+            self.certificate_names = leaf
+                .subject_alt_names
+                .iter()
+                .filter(|name| is_dns_name(name))
+                .map(|name| name.to_ascii_lowercase())
+                .collect();
+        }
+    }
+
+    /// Infer the service reached, given the names resolved to the address of the server
+    pub fn infer(&self, dns_names: &[String]) -> Option<TlsDestination> {
+        let certified = |name: &str| {
+            self.certificate_names
+                .iter()
+                .any(|pattern| covers(pattern, name))
+        };
+
+        if let Some(server_name) = &self.server_name {
+            let mut sources = vec![DestinationSource::ServerName];
+            if dns_names.contains(server_name) {
+                sources.push(DestinationSource::Dns);
+            }
+            if certified(server_name) {
+                sources.push(DestinationSource::Certificate);
+            }
+            // A certificate not valid for the SNI hints at domain fronting or at an interception
+            let confidence = if sources.len() > 1 {
+                DestinationConfidence::High
+            } else if self.certificate_names.is_empty() {
+                DestinationConfidence::Medium
+            } else {
+                DestinationConfidence::Low
+            };
+
+            return Some(TlsDestination {
+                service: server_name.clone(),
+                confidence,
+                sources,
+            });
+        }
+
+        if let Some(name) = dns_names.iter().find(|name| certified(name)) {
+            return Some(TlsDestination {
+                service: name.clone(),
+                confidence: DestinationConfidence::High,
+                sources: vec![DestinationSource::Dns, DestinationSource::Certificate],
+            });
+        }
+
+        if let Some(name) = dns_names.first() {
+            let confidence = if dns_names.len() == 1 && self.certificate_names.is_empty() {
+                DestinationConfidence::Medium
+            } else {
+                DestinationConfidence::Low
+            };
+
+            return Some(TlsDestination {
+                service: name.clone(),
+                confidence,
+                sources: vec![DestinationSource::Dns],
+            });
+        }
+
+        // Wildcards name a domain rather than a service
+        let name = self
+            .certificate_names
+            .iter()
+            .find(|name| !name.starts_with("*."))
+            .or(self.certificate_names.first())?;
+
+        Some(TlsDestination {
+            service: name.clone(),
+            confidence: DestinationConfidence::Low,
+            sources: vec![DestinationSource::Certificate],
+        })
+    }
+}
+
+/// Check if a subject alternative name is a DNS name, rather than an address, an e-mail or a URI
+fn is_dns_name(name: &str) -> bool {
+    !name.contains(['@', ':', '/']) && name.parse::<IpAddr>().is_err()
+}
+
+/// Check if a DNS name of a certificate, possibly a wildcard, covers a host name
+fn covers(pattern: &str, name: &str) -> bool {
+    match pattern.strip_prefix("*.") {
+        // The wildcard stands for a single label
+        Some(domain) => name
+            .split_once('.')
+            .is_some_and(|(label, rest)| !label.is_empty() && rest.eq_ignore_ascii_case(domain)),
+        None => pattern.eq_ignore_ascii_case(name),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr};
+
+    use super::{
+        covers, DestinationConfidence, DestinationSource, DnsNames, TlsDestination, TlsNames,
+    };
+
+    #[test]
+    fn names_resolved_by_dns() {
+        let server = IpAddr::V4(Ipv4Addr::new(93, 184, 216, 34));
+        let mut dns_names = DnsNames::default();
+
+        dns_names.add(server, "www.example.com.");
+        dns_names.add(server, "cdn.example.net");
+        dns_names.add(server, "WWW.example.com");
+
+        assert_eq!(
+            dns_names.names(&server),
+            &["www.example.com".to_owned(), "cdn.example.net".to_owned()]
+        );
+        assert!(dns_names.names(&IpAddr::V4(Ipv4Addr::LOCALHOST)).is_empty());
+
+        dns_names.clear();
+        assert!(dns_names.names(&server).is_empty());
+    }
+
+    #[test]
+    fn destination_inference() {
+        use DestinationConfidence::{High, Low, Medium};
+        use DestinationSource::{Certificate, Dns, ServerName};
+
+        let certificate = vec!["example.com".to_owned(), "*.example.com".to_owned()];
+        let dns = ["www.example.com".to_owned()];
+
+        let sni = names(Some("www.example.com"), &certificate);
+        assert_eq!(
+            sni.infer(&dns),
+            destination("www.example.com", High, &[ServerName, Dns, Certificate])
+        );
+        let sni_only = names(Some("www.example.com"), &[]);
+        assert_eq!(
+            sni_only.infer(&[]),
+            destination("www.example.com", Medium, &[ServerName])
+        );
+        let fronted = names(Some("blocked.example.org"), &certificate);
+        assert_eq!(
+            fronted.infer(&dns),
+            destination("blocked.example.org", Low, &[ServerName])
+        );
+
+        // Encrypted SNI
+        let shared = ["api.example.org".to_owned(), "www.example.com".to_owned()];
+        let encrypted = names(None, &certificate);
+        assert_eq!(
+            encrypted.infer(&shared),
+            destination("www.example.com", High, &[Dns, Certificate])
+        );
+        let tls13 = names(None, &[]);
+        assert_eq!(
+            tls13.infer(&dns),
+            destination("www.example.com", Medium, &[Dns])
+        );
+        assert_eq!(
+            tls13.infer(&shared),
+            destination("api.example.org", Low, &[Dns])
+        );
+        assert_eq!(
+            encrypted.infer(&[]),
+            destination("example.com", Low, &[Certificate])
+        );
+        assert_eq!(tls13.infer(&[]), None);
+    }
+
+    #[test]
+    fn wildcard_names() {
+        assert!(covers("*.example.com", "www.EXAMPLE.com"));
+        assert!(!covers("*.example.com", "example.com"));
+        assert!(!covers("*.example.com", "a.b.example.com"));
+        assert!(covers("Example.com", "example.com"));
+    }
+
+    ///////////////////// Utils
+
+    fn names(server_name: Option<&str>, certificate_names: &[String]) -> TlsNames {
+        TlsNames {
+            server_name: server_name.map(str::to_owned),
+            certificate_names: certificate_names.to_vec(),
+        }
+    }
+
+    fn destination(
+        service: &str,
+        confidence: DestinationConfidence,
+        sources: &[DestinationSource],
+    ) -> Option<TlsDestination> {
+        Some(TlsDestination {
+            service: service.to_owned(),
+            confidence,
+            sources: sources.to_vec(),
+        })
+    }
+}
@@ -0,0 +1,448 @@
+//! Generator of synthetic traffic, to test the capture, the filters and the dissection end to end
+//!
+//! Each flow is made of the frames exchanged by a client and a server, both directions included:
+//! - HTTP request: TCP handshake, `GET` request and `200 OK` response
+//! - DNS query: `A` query and its answer
+//! - TLS handshake: TCP handshake and Client Hello with a server name
+//! - ICMP ping: echo request and reply
+//!
+//! Frames are sent on an Ethernet interface (the selected one by default, or e.g. the loopback
+//! one), between addresses of the documentation range 192.0.2.0/24, so that no host answers them.
+//! The client has the MAC address of the interface, the server a locally administered one.
+//! Frames sent are captured back by the sniffer like the ones of any other application.
+
+use std::net::Ipv4Addr;
+
+use pnet::datalink::{self, Channel::Ethernet};
+use pnet::packet::icmp::{self, IcmpPacket};
+use pnet::packet::ip::{IpNextHeaderProtocol, IpNextHeaderProtocols};
+use pnet::packet::ipv4::{self, Ipv4Packet};
+use pnet::packet::tcp::{self, TcpPacket};
+use pnet::packet::udp::{self, UdpPacket};
+use serde::Deserialize;
+use sniffer_parser::LinkType;
+use tracing::info;
+
+use crate::{find_interface, interface_link_type, SniffingError, SniffingState, CONFIG};
+
+/// Hosts exchanging the synthetic flows
+const CLIENT_IP: Ipv4Addr = Ipv4Addr::new(192, 0, 2, 1);
+const SERVER_IP: Ipv4Addr = Ipv4Addr::new(192, 0, 2, 2);
+const SERVER_MAC: [u8; 6] = [0x02, 0x00, 0x00, 0x00, 0x00, 0x02];
+/// MAC address of the client when the interface has none (e.g. the loopback one)
+const DEFAULT_CLIENT_MAC: [u8; 6] = [0x02, 0x00, 0x00, 0x00, 0x00, 0x01];
+
+/// Name resolved and requested by the synthetic flows, and its address
+const SERVER_NAME: &str = "example.com";
+const RESOLVED_IP: Ipv4Addr = Ipv4Addr::new(192, 0, 2, 80);
+
+/// Initial sequence numbers of the TCP connections
+const CLIENT_SEQUENCE: u32 = 1000;
+const SERVER_SEQUENCE: u32 = 5000;
+
+/// TCP flags of the segments
+const SYN: u8 = 0x02;
+const ACK: u8 = 0x10;
+const PSH_ACK: u8 = 0x18;
+const SYN_ACK: u8 = 0x12;
+
+/// Synthetic flow to generate
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum SyntheticFlow {
+    HttpRequest,
+    DnsQuery,
+    TlsHandshake,
+    IcmpPing,
+}
+
+/// Endpoint of a synthetic flow
+#[derive(Debug, Clone, Copy)]
+struct Host {
+    mac: [u8; 6],
+    ip: Ipv4Addr,
+}
+
+/// Build the frames of a flow, in the order they are exchanged
+pub fn flow_frames(flow: SyntheticFlow, client_mac: [u8; 6]) -> Vec<Vec<u8>> {
+    let client = Host {
+        mac: client_mac,
+        ip: CLIENT_IP,
+    };
+    let server = Host {
+        mac: SERVER_MAC,
+        ip: SERVER_IP,
+    };
+
+    match flow {
+        SyntheticFlow::HttpRequest => {
+            let request = format!(
+                "GET / HTTP/1.1\r\nHost: {}\r\nUser-Agent: wirefish\r\n\r\n",
+                SERVER_NAME
+            );
+            let response =
+                "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Length: 8\r\n\r\nwirefish";
+
+            tcp_exchange(
+                client,
+                server,
+                (49152, 80),
+                request.as_bytes(),
+                Some(response.as_bytes()),
+            )
+        }
+        SyntheticFlow::TlsHandshake => {
+            tcp_exchange(client, server, (49153, 443), &client_hello(), None)
+        }
+        SyntheticFlow::DnsQuery => {
+            let question = dns_question();
+            let mut query = vec![
+                0x12, 0x34, 0x01, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            ];
+            query.extend(&question);
+            let mut response = vec![
+                0x12, 0x34, 0x81, 0x80, 0x00, 0x01, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00,
+            ];
+            response.extend(&question);
+            // Name pointing to the question, A record of class IN with a TTL of 300 seconds
+            response.extend([
+                0xc0, 0x0c, 0x00, 0x01, 0x00, 0x01, 0x00, 0x00, 0x01, 0x2c, 0x00, 0x04,
+            ]);
+            response.extend(RESOLVED_IP.octets());
+
+            vec![
+                ipv4_frame(
+                    client,
+                    server,
+                    IpNextHeaderProtocols::Udp,
+                    &udp_datagram((49154, 53), &query),
+                ),
+                ipv4_frame(
+                    server,
+                    client,
+                    IpNextHeaderProtocols::Udp,
+                    &udp_datagram((53, 49154), &response),
+                ),
+            ]
+        }
+        SyntheticFlow::IcmpPing => {
+            let echo = |icmp_type: u8| {
+                let mut message = vec![icmp_type, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x01];
+                message.extend(b"wirefish");
+                message
+            };
+
+            vec![
+                ipv4_frame(client, server, IpNextHeaderProtocols::Icmp, &echo(8)),
+                ipv4_frame(server, client, IpNextHeaderProtocols::Icmp, &echo(0)),
+            ]
+        }
+    }
+}
+
+/// Build the frames of a TCP connection carrying a request and, if any, its response
+fn tcp_exchange(
+    client: Host,
+    server: Host,
+    ports: (u16, u16),
+    request: &[u8],
+    response: Option<&[u8]>,
+) -> Vec<Vec<u8>> {
+    let (client_port, server_port) = ports;
+    let to_server = |sequence: u32, acknowledgement: u32, flags: u8, payload: &[u8]| {
+        let segment = tcp_segment(ports, sequence, acknowledgement, flags, payload);
+        ipv4_frame(client, server, IpNextHeaderProtocols::Tcp, &segment)
+    };
+    let to_client = |sequence: u32, acknowledgement: u32, flags: u8, payload: &[u8]| {
+        let segment = tcp_segment(
+            (server_port, client_port),
+            sequence,
+            acknowledgement,
+            flags,
+            payload,
+        );
+        ipv4_frame(server, client, IpNextHeaderProtocols::Tcp, &segment)
+    };
+
+    // SYN and FIN count as one byte of the sequence
+    let (client_sequence, server_sequence) = (CLIENT_SEQUENCE + 1, SERVER_SEQUENCE + 1);
+    let request_end = client_sequence + request.len() as u32;
+    let mut frames = vec![
+        to_server(CLIENT_SEQUENCE, 0, SYN, &[]),
+        to_client(SERVER_SEQUENCE, client_sequence, SYN_ACK, &[]),
+        to_server(client_sequence, server_sequence, ACK, &[]),
+        to_server(client_sequence, server_sequence, PSH_ACK, request),
+    ];
+    match response {
+        Some(response) => {
+            let response_end = server_sequence + response.len() as u32;
+            frames.push(to_client(server_sequence, request_end, PSH_ACK, response));
+            frames.push(to_server(request_end, response_end, ACK, &[]));
+        }
+        None => frames.push(to_client(server_sequence, request_end, ACK, &[])),
+    }
+
+    frames
+}
+
+/// Build a TCP segment without options, its checksum left to be computed
+fn tcp_segment(
+    ports: (u16, u16),
+    sequence: u32,
+    acknowledgement: u32,
+    flags: u8,
+    payload: &[u8],
+) -> Vec<u8> {
+    let mut segment = Vec::with_capacity(20 + payload.len());
+    segment.extend(ports.0.to_be_bytes());
+    segment.extend(ports.1.to_be_bytes());
+    segment.extend(sequence.to_be_bytes());
+    segment.extend(acknowledgement.to_be_bytes());
+    // Data offset of 5 words, flags, window, checksum and urgent pointer
+    segment.extend([0x50, flags, 0xff, 0xff, 0x00, 0x00, 0x00, 0x00]);
+    segment.extend(payload);
+
+    segment
+}
+
+/// Build a UDP datagram, its checksum left to be computed
+fn udp_datagram(ports: (u16, u16), payload: &[u8]) -> Vec<u8> {
+    let mut datagram = Vec::with_capacity(8 + payload.len());
+    datagram.extend(ports.0.to_be_bytes());
+    datagram.extend(ports.1.to_be_bytes());
+    datagram.extend((8 + payload.len() as u16).to_be_bytes());
+    datagram.extend([0x00, 0x00]);
+    datagram.extend(payload);
+
+    datagram
+}
+
+/// Build an Ethernet frame carrying an IPv4 packet, computing the checksums of its headers
+fn ipv4_frame(
+    source: Host,
+    destination: Host,
+    protocol: IpNextHeaderProtocol,
+    transport: &[u8],
+) -> Vec<u8> {
+    let mut transport = transport.to_vec();
+    let checksum = match protocol {
+        IpNextHeaderProtocols::Tcp => Some((
+            16,
+            tcp::ipv4_checksum(
+                &TcpPacket::new(&transport).unwrap(),
+                &source.ip,
+                &destination.ip,
+            ),
+        )),
+        IpNextHeaderProtocols::Udp => Some((
+            6,
+            udp::ipv4_checksum(
+                &UdpPacket::new(&transport).unwrap(),
+                &source.ip,
+                &destination.ip,
+            ),
+        )),
+        IpNextHeaderProtocols::Icmp => {
+            Some((2, icmp::checksum(&IcmpPacket::new(&transport).unwrap())))
+        }
+        _ => None,
+    };
+    if let Some((offset, checksum)) = checksum {
+        transport[offset..offset + 2].copy_from_slice(&checksum.to_be_bytes());
+    }
+
+    let mut header = vec![0x45, 0x00];
+    header.extend((20 + transport.len() as u16).to_be_bytes());
+    // Identification, don't fragment, TTL, protocol and checksum
+    header.extend([0x00, 0x00, 0x40, 0x00, 0x40, protocol.0, 0x00, 0x00]);
+    header.extend(source.ip.octets());
+    header.extend(destination.ip.octets());
+    let checksum = ipv4::checksum(&Ipv4Packet::new(&header).unwrap());
+    header[10..12].copy_from_slice(&checksum.to_be_bytes());
+
+    let mut frame = Vec::with_capacity(14 + header.len() + transport.len());
+    frame.extend(destination.mac);
+    frame.extend(source.mac);
+    frame.extend([0x08, 0x00]);
+    frame.extend(header);
+    frame.extend(transport);
+
+    frame
+}
+
+/// Build the question of the DNS query: the address of the server name
+fn dns_question() -> Vec<u8> {
+    let mut question = vec![];
+    for label in SERVER_NAME.split('.') {
+        question.push(label.len() as u8);
+        question.extend(label.as_bytes());
+    }
+    // Root label, type A and class IN
+    question.extend([0x00, 0x00, 0x01, 0x00, 0x01]);
+
+    question
+}
+
+/// Build a TLS record carrying a Client Hello with the server name
+fn client_hello() -> Vec<u8> {
+    let name = SERVER_NAME.as_bytes();
+    let mut server_name = vec![0x00, 0x00];
+    server_name.extend((name.len() as u16 + 5).to_be_bytes());
+    server_name.extend((name.len() as u16 + 3).to_be_bytes());
+    server_name.push(0x00);
+    server_name.extend((name.len() as u16).to_be_bytes());
+    server_name.extend(name);
+
+    // TLS 1.2, random, no session id, TLS_AES_128_GCM_SHA256 and no compression
+    let mut hello = vec![0x03, 0x03];
+    hello.extend([0x5a; 32]);
+    hello.extend([0x00, 0x00, 0x02, 0x13, 0x01, 0x01, 0x00]);
+    hello.extend((server_name.len() as u16).to_be_bytes());
+    hello.extend(server_name);
+
+    let mut handshake = vec![0x01];
+    handshake.extend(&(hello.len() as u32).to_be_bytes()[1..]);
+    handshake.extend(hello);
+
+    let mut record = vec![0x16, 0x03, 0x01];
+    record.extend((handshake.len() as u16).to_be_bytes());
+    record.extend(handshake);
+
+    record
+}
+
+/// Sends the frames of the given synthetic flows on an interface, the selected one by default,
+/// returning the number of frames sent
+#[tauri::command]
+pub fn generate_traffic(
+    flows: Vec<SyntheticFlow>,
+    interface_name: Option<String>,
+    state: tauri::State<SniffingState>,
+) -> Result<usize, SniffingError> {
+    let interface_name = match interface_name {
+        Some(interface_name) => interface_name,
+        None => state
+            .info
+            .lock()
+            .unwrap()
+            .interface_name
+            .clone()
+            .ok_or_else(|| {
+                SniffingError::TrafficGenerationFailed(
+                    "No interface provided nor selected".to_owned(),
+                )
+            })?,
+    };
+    let interface = find_interface(&interface_name)?;
+    if interface_link_type(&interface_name) != LinkType::Ethernet {
+        return Err(SniffingError::TrafficGenerationFailed(
+            "Traffic can be generated only on Ethernet interfaces".to_owned(),
+        ));
+    }
+
+    let mut sender = match datalink::channel(&interface, CONFIG) {
+        Ok(Ethernet(tx, _)) => tx,
+        Ok(_) => {
+            return Err(SniffingError::UnhandledChannelType(
+                "Unhandled channel type".to_owned(),
+            ))
+        }
+        Err(e) => {
+            return Err(SniffingError::TrafficGenerationFailed(format!(
+                "Channel creation failed: {}",
+                e
+            )))
+        }
+    };
+
+    let client_mac = interface
+        .mac
+        .filter(|mac| !mac.is_zero())
+        .map(|mac| [mac.0, mac.1, mac.2, mac.3, mac.4, mac.5])
+        .unwrap_or(DEFAULT_CLIENT_MAC);
+    let mut sent = 0;
+    for &flow in &flows {
+        for frame in flow_frames(flow, client_mac) {
+            match sender.send_to(&frame, None) {
+                Some(Ok(())) => sent += 1,
+                Some(Err(e)) => {
+                    return Err(SniffingError::TrafficGenerationFailed(format!(
+                        "Sending {:?} failed: {}",
+                        flow, e
+                    )))
+                }
+                None => {
+                    return Err(SniffingError::TrafficGenerationFailed(
+                        "Not enough space in the write buffer".to_owned(),
+                    ))
+                }
+            }
+        }
+    }
+
+    info!(
+        "[{}] Traffic generated: {:?}; Frames: {}",
+        interface_name, flows, sent
+    );
+
+    Ok(sent)
+}
+
+#[cfg(test)]
+mod tests {
+    use pnet::packet::ethernet::EthernetPacket;
+    use pnet::packet::ipv4::{self, Ipv4Packet};
+    use pnet::packet::Packet;
+    use sniffer_parser::serializable_packet::SerializablePacket;
+    use sniffer_parser::{cleanup_sniffing_state, parse_ethernet_frame};
+
+    use super::{flow_frames, SyntheticFlow, DEFAULT_CLIENT_MAC};
+
+    #[test]
+    fn flows_dissected() {
+        let expected: [(SyntheticFlow, fn(&SerializablePacket) -> bool); 4] = [
+            (SyntheticFlow::HttpRequest, |packet| {
+                matches!(packet, SerializablePacket::HttpRequestPacket(_))
+            }),
+            (SyntheticFlow::DnsQuery, |packet| {
+                matches!(packet, SerializablePacket::DnsPacket(_))
+            }),
+            (SyntheticFlow::TlsHandshake, |packet| {
+                matches!(packet, SerializablePacket::TlsPacket(_))
+            }),
+            (SyntheticFlow::IcmpPing, |packet| {
+                matches!(packet, SerializablePacket::EchoRequestPacket(_))
+            }),
+        ];
+
+        for (flow, is_expected) in expected {
+            cleanup_sniffing_state();
+            let dissected = flow_frames(flow, DEFAULT_CLIENT_MAC)
+                .iter()
+                .enumerate()
+                .map(|(id, frame)| parse_ethernet_frame(&EthernetPacket::new(frame).unwrap(), id))
+                .any(|packet| {
+                    [
+                        packet.get_application_layer_packet(),
+                        packet.get_transport_layer_packet(),
+                    ]
+                    .into_iter()
+                    .flatten()
+                    .any(is_expected)
+                });
+            assert!(dissected, "{:?} not dissected", flow);
+        }
+        cleanup_sniffing_state();
+    }
+
+    #[test]
+    fn valid_ipv4_headers() {
+        for frame in flow_frames(SyntheticFlow::HttpRequest, DEFAULT_CLIENT_MAC) {
+            let ethernet = EthernetPacket::new(&frame).unwrap();
+            let ipv4 = Ipv4Packet::new(ethernet.payload()).unwrap();
+
+            assert_eq!(ipv4.get_checksum(), ipv4::checksum(&ipv4));
+            assert_eq!(ipv4.get_total_length() as usize, ethernet.payload().len());
+        }
+    }
+}
@@ -0,0 +1,246 @@
+//! Truncation of the frames received from the network interface
+//!
+//! For long captures, the frames can be snapped after being dissected: the link, network and
+//! transport headers are always kept whole, followed by the first bytes of the payload, so that
+//! the stored packets take a fraction of the memory while their dissection is left untouched.
+//! The bytes of payload kept can be set for specific protocols (e.g. more for DNS, none for TLS),
+//! overriding the default one.
+//!
+//! Frames saved to a capture file are snapped as well, carrying their original length.
+//! Statistics and conversations count the original lengths. Imported captures are never truncated.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use sniffer_parser::serializable_packet::bytes::FrameBytes;
+use sniffer_parser::serializable_packet::{ParsedPacket, SerializablePacket};
+use sniffer_parser::{ieee80211_network_offset, radiotap_network_offset, HeaderLength, LinkType};
+use tracing::info;
+
+use crate::export::headers_length;
+use crate::report::get_sender_receiver;
+use crate::SniffingState;
+
+/// Bytes of payload kept after the headers of the received frames
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SnapLengths {
+    /// Bytes of payload kept by default, none to keep the whole frames
+    pub payload: Option<usize>,
+    /// Bytes of payload kept for the frames of the given protocols (e.g. "DNS", "TLS"), overriding
+    /// the default
+    pub protocols: BTreeMap<String, usize>,
+}
+
+impl SnapLengths {
+    /// Get the length a frame of the given link type is snapped to, given its dissected
+    /// representation
+    ///
+    /// When the frame carries multiple protocols with a length, the innermost one is used. Frames
+    /// whose headers can't be told apart from their payload are kept whole.
+    pub fn snap_length(&self, link_type: LinkType, frame: &[u8], packet: &ParsedPacket) -> usize {
+        if self.payload.is_none() && self.protocols.is_empty() {
+            return frame.len();
+        }
+
+        let protocols = get_sender_receiver(packet).1;
+        let payload = protocols
+            .iter()
+            .rev()
+            .find_map(|protocol| self.protocols.get(protocol).copied())
+            .or(self.payload);
+
+        match (payload, headers_length(link_type.linktype(), frame)) {
+            (Some(payload), Some(headers)) => headers.saturating_add(payload).min(frame.len()),
+            _ => frame.len(),
+        }
+    }
+}
+
+/// Frame received from a network interface, with the length it is snapped to
+pub struct SnappedFrame<'a> {
+    pub link_type: LinkType,
+    pub frame: &'a [u8],
+    pub snap_length: usize,
+}
+
+/// Drop the bytes of a dissected packet beyond the length its frame is snapped to
+///
+/// The payload of the Ethernet representation of the frame follows its link header: the Ethernet
+/// one, or the 802.11 one with its LLC/SNAP header for the frames captured in monitor mode.
+pub fn truncate_packet(packet: &mut ParsedPacket, snapped: &SnappedFrame) {
+    let link_header = match snapped.link_type {
+        LinkType::Ethernet => Some(HeaderLength::ETHERNET),
+        LinkType::Ieee80211 => ieee80211_network_offset(snapped.frame),
+        LinkType::Ieee80211Radiotap => radiotap_network_offset(snapped.frame),
+        _ => None,
+    };
+    let payload_length = match link_header {
+        Some(link_header) => snapped.snap_length.saturating_sub(link_header),
+        None => return,
+    };
+
+    let truncated = match packet.get_link_layer_packet() {
+        Some(SerializablePacket::EthernetPacket(link_packet))
+            if link_packet.payload.len() > payload_length =>
+        {
+            // Copied out of the frame shared by the payloads, so that the rest of it is released
+            let mut link_packet = link_packet.clone();
+            link_packet.payload = FrameBytes::from(&link_packet.payload[..payload_length]);
+            link_packet
+        }
+        _ => return,
+    };
+
+    packet.set_link_layer_packet(Some(SerializablePacket::EthernetPacket(truncated)));
+}
+
+/// Sets the bytes of payload kept after the headers of the frames received from the network
+/// interface
+#[tauri::command]
+pub fn set_snap_lengths(lengths: SnapLengths, state: tauri::State<SniffingState>) {
+    info!("Snap lengths set: {:?}", lengths);
+    *state.snap_lengths.lock().unwrap() = lengths;
+}
+
+/// Returns the bytes of payload kept after the headers of the frames received from the network
+/// interface
+#[tauri::command]
+pub fn get_snap_lengths(state: tauri::State<SniffingState>) -> SnapLengths {
+    state.snap_lengths.lock().unwrap().clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use pnet::packet::ethernet::EthernetPacket;
+    use sniffer_parser::serializable_packet::SerializablePacket;
+    use sniffer_parser::{parse_ethernet_frame, parse_frame, FrameMeta, LinkType};
+
+    use super::{truncate_packet, SnapLengths, SnappedFrame};
+
+    // Ethernet (14) + IPv4 (20) + TCP (20) + 3 bytes of payload
+    const TCP_FRAME: &[u8] = &[
+        0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x08, 0x00, 0x45,
+        0x00, 0x00, 0x2b, 0x00, 0x00, 0x00, 0x00, 0x40, 0x06, 0x00, 0x00, 0x0a, 0x0a, 0x0a, 0x0a,
+        0x0b, 0x0b, 0x0b, 0x0b, 0x11, 0x5c, 0x01, 0xbb, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x50, 0x18, 0xff, 0xff, 0x00, 0x00, 0x00, 0x00, 0x47, 0x45, 0x54,
+    ];
+
+    #[test]
+    fn snap_lengths() {
+        let packet = parse_ethernet_frame(&EthernetPacket::new(TCP_FRAME).unwrap(), 0);
+        let mut lengths = SnapLengths::default();
+        assert_eq!(
+            lengths.snap_length(LinkType::Ethernet, TCP_FRAME, &packet),
+            57
+        );
+
+        lengths.payload = Some(1);
+        assert_eq!(
+            lengths.snap_length(LinkType::Ethernet, TCP_FRAME, &packet),
+            55
+        );
+
+        // The headers are kept whole
+        lengths.protocols.insert("TCP".to_owned(), 0);
+        assert_eq!(
+            lengths.snap_length(LinkType::Ethernet, TCP_FRAME, &packet),
+            54
+        );
+
+        lengths.protocols.insert("TCP".to_owned(), 1500);
+        assert_eq!(
+            lengths.snap_length(LinkType::Ethernet, TCP_FRAME, &packet),
+            57
+        );
+
+        lengths.payload = None;
+        lengths.protocols.clear();
+        lengths.protocols.insert("UDP".to_owned(), 0);
+        assert_eq!(
+            lengths.snap_length(LinkType::Ethernet, TCP_FRAME, &packet),
+            57
+        );
+    }
+
+    #[test]
+    fn truncated_packet() {
+        let mut packet = parse_ethernet_frame(&EthernetPacket::new(TCP_FRAME).unwrap(), 0);
+        truncate_packet(
+            &mut packet,
+            &SnappedFrame {
+                link_type: LinkType::Ethernet,
+                frame: TCP_FRAME,
+                snap_length: 55,
+            },
+        );
+
+        match packet.get_link_layer_packet() {
+            Some(SerializablePacket::EthernetPacket(link_packet)) => {
+                assert_eq!(link_packet.payload.len(), 41)
+            }
+            _ => unreachable!(),
+        }
+        assert!(matches!(
+            packet.get_transport_layer_packet(),
+            Some(SerializablePacket::TcpPacket(_))
+        ));
+    }
+
+    #[test]
+    fn monitor_mode_frames() {
+        // Radiotap (8) + 802.11 data (24) + LLC/SNAP (8), carrying the IPv4 packet of the frame
+        let frame = [
+            &[0x00, 0x00, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00][..],
+            &[0x08, 0x01, 0x00, 0x00],
+            &[0x00, 0x11, 0x22, 0x33, 0x44, 0x55],
+            &[0x66, 0x77, 0x88, 0x99, 0xaa, 0xbb],
+            &[0xff; 6],
+            &[0x10, 0x00],
+            &[0xaa, 0xaa, 0x03, 0x00, 0x00, 0x00, 0x08, 0x00],
+            &TCP_FRAME[14..],
+        ]
+        .concat();
+        let link_type = LinkType::Ieee80211Radiotap;
+        let mut packet = parse_frame(link_type, &frame, FrameMeta::new(0));
+
+        let lengths = SnapLengths {
+            payload: Some(1),
+            ..SnapLengths::default()
+        };
+        let snap_length = lengths.snap_length(link_type, &frame, &packet);
+        assert_eq!(snap_length, 81);
+
+        truncate_packet(
+            &mut packet,
+            &SnappedFrame {
+                link_type,
+                frame: &frame,
+                snap_length,
+            },
+        );
+        match packet.get_link_layer_packet() {
+            Some(SerializablePacket::EthernetPacket(link_packet)) => {
+                assert_eq!(link_packet.payload.len(), 41)
+            }
+            _ => unreachable!(),
+        }
+
+        // Management frames are kept whole
+        let beacon = [
+            &[0x00, 0x00, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00][..],
+            &[0x80, 0x00, 0x00, 0x00],
+            &[0xff; 6],
+            &[0x00, 0x11, 0x22, 0x33, 0x44, 0x55],
+            &[0x00, 0x11, 0x22, 0x33, 0x44, 0x55],
+            &[0x10, 0x00],
+            &[0u8; 12],
+        ]
+        .concat();
+        let packet = parse_frame(link_type, &beacon, FrameMeta::new(1));
+        assert_eq!(
+            lengths.snap_length(link_type, &beacon, &packet),
+            beacon.len()
+        );
+    }
+}
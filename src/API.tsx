@@ -24,13 +24,30 @@ async function generateReport(
   return invoke("generate_report", { reportPath, firstGeneration });
 }
 
+export interface PacketFilter {
+  types?: string[];
+  values?: [string, string][];
+  expression?: string;
+}
+
+export interface PacketSort {
+  by: "id" | "timestamp" | "source" | "destination" | "protocol" | "length";
+  descending?: boolean;
+}
+
+export interface PacketWindow {
+  offset: number;
+  total: number;
+  packets: GeneralPacket[];
+}
+
 async function getPackets(
-  start: number,
-  end: number,
-  filtersType: any[],
-  filtersValue: any[]
-): Promise<GeneralPacket[]> {
-  return invoke("get_packets", { start, end, filtersType, filtersValue });
+  offset: number,
+  limit: number,
+  filter: PacketFilter,
+  sort?: PacketSort
+): Promise<PacketWindow> {
+  return invoke("get_packets", { offset, limit, filter, sort });
 }
 
 const API = {
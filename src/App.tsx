@@ -173,8 +173,8 @@ function App() {
                 });
             }
 
-            const unlisten = await appWindow.listen('packet_received', (packet: any) => {
-                setPacketCount((old) => old + 1)
+            const unlisten = await appWindow.listen('packets_received', (event: any) => {
+                setPacketCount(event.payload.total)
             });
 
             return () => unlisten();
@@ -210,13 +210,12 @@ function App() {
                     filter_value.push(["dst_port", dstPortForm])
 
 
-                let response: any[] = await API.getPackets(
+                let response = await API.getPackets(
                     (pageState - 1) * 100,
-                    (pageState - 1) * 100 + 100,
-                    filter_name,
-                    filter_value);
+                    100,
+                    {types: filter_name, values: filter_value});
 
-                let packets = response.map((p, index) => new GeneralPacket(p.id, p))
+                let packets = response.packets.map((p: any) => new GeneralPacket(p.id, p))
                 setCapturedPackets(packets)
 
                 if (packets.length >= 100)